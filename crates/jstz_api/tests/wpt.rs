@@ -11,7 +11,10 @@ use boa_gc::{Finalize, Trace};
 use derive_more::{From, Into};
 use jstz_core::{host_defined, Api, Runtime};
 use jstz_runtime::wpt::{WptSubtest, WptSubtestStatus, WptTestStatus};
-use jstz_wpt::{Bundle, BundleItem, TestFilter, TestToRun, Wpt, WptReportTest, WptServe};
+use jstz_wpt::{
+    Bundle, BundleItem, TestFilter, TestToRun, Wpt, WptReportTest, WptRunOptions,
+    WptServe,
+};
 
 const TEST_SUBSET_SIZE: u8 = 5;
 
@@ -355,9 +358,9 @@ fn run_wpt_test(
     }
 }
 
-#[tokio::test]
+#[tokio::test(flavor = "multi_thread")]
 async fn test_wpt() -> Result<()> {
-    let filter = TestFilter::try_from(
+    let mut filter = TestFilter::try_from(
         [
             r"^\/encoding\/[^\/]+\.any\.html$", // TextEncode, TextDecoder
             r"^\/encoding\/streams\/[^\/]+\.any\.html$", // TextEncoderStream, TextDecoderStream
@@ -396,6 +399,7 @@ async fn test_wpt() -> Result<()> {
         ]
         .as_ref(),
     )?;
+    filter.apply_env()?;
 
     let wpt = Wpt::new().await?;
 
@@ -403,8 +407,15 @@ async fn test_wpt() -> Result<()> {
 
     let wpt_serve = wpt.serve(false).await?;
 
-    let report =
-        WptServe::run_test_harness(&wpt_serve, &manifest, &filter, run_wpt_test).await?;
+    let report = tokio::task::block_in_place(|| {
+        WptServe::run_test_harness_parallel(
+            &wpt_serve,
+            &manifest,
+            &filter,
+            &WptRunOptions::from_env(),
+            run_wpt_test,
+        )
+    })?;
     serde_json::to_string_pretty(&report)?;
 
     Ok(())
@@ -1,5 +1,6 @@
 use crate::utils::MUTEZ_PER_TEZ;
 use crate::{
+    codegen,
     config::{Account, Config, NetworkName, SmartFunction, User},
     error::{bail_user_error, user_error, Result},
     utils::AddressOrAlias,
@@ -287,7 +288,10 @@ async fn get_code(
 
     debug!("Getting code.. {:?}.", network);
 
-    let address = AddressOrAlias::resolve_or_use_current_user(account, &cfg)?;
+    let address = AddressOrAlias::resolve_with_registry_or_use_current_user(
+        account, &cfg, &network,
+    )
+    .await?;
     let sf_address = address
         .as_smart_function()
         .ok_or(user_error!("Address is not a smart function"))?;
@@ -303,13 +307,46 @@ async fn get_code(
     Ok(())
 }
 
+async fn get_interface(
+    account: Option<AddressOrAlias>,
+    typescript: bool,
+    network: Option<NetworkName>,
+) -> Result<()> {
+    let cfg = Config::load().await?;
+
+    let address = AddressOrAlias::resolve_with_registry_or_use_current_user(
+        account, &cfg, &network,
+    )
+    .await?;
+    let sf_address = address
+        .as_smart_function()
+        .ok_or(user_error!("Address is not a smart function"))?;
+    debug!("resolved `account` -> {:?}", address);
+    let interface = cfg
+        .jstz_client(&network)?
+        .get_interface(sf_address)
+        .await?
+        .ok_or(user_error!("No interface found for account {}", address))?;
+
+    if typescript {
+        info!("{}", codegen::typescript_client(sf_address, &interface));
+    } else {
+        info!("{}", serde_json::to_string_pretty(&interface)?);
+    }
+
+    Ok(())
+}
+
 async fn get_balance(
     account: Option<AddressOrAlias>,
     network: Option<NetworkName>,
 ) -> Result<()> {
     let cfg = Config::load().await?;
 
-    let address = AddressOrAlias::resolve_or_use_current_user(account, &cfg)?;
+    let address = AddressOrAlias::resolve_with_registry_or_use_current_user(
+        account, &cfg, &network,
+    )
+    .await?;
     debug!("resolved `account` -> {:?}", address);
 
     let balance = cfg.jstz_client(&network)?.get_balance(&address).await?;
@@ -362,6 +399,19 @@ pub enum Command {
         #[arg(short, long, default_value = None)]
         network: Option<NetworkName>,
     },
+    /// 📜 Outputs the interface description of a smart function.
+    Interface {
+        /// Smart function address or alias.
+        #[arg(short, long, value_name = "ALIAS|ADDRESS")]
+        account: Option<AddressOrAlias>,
+        /// Outputs a typed TypeScript client of the smart function instead.
+        #[arg(long)]
+        typescript: bool,
+        /// Specifies the network from the config file, defaulting to the configured default network.
+        /// Use `dev` for the local sandbox.
+        #[arg(short, long, default_value = None)]
+        network: Option<NetworkName>,
+    },
     /// 📈 Outputs the balance of an account.
     Balance {
         /// Address or alias of the account (user or smart function).
@@ -391,6 +441,11 @@ pub async fn exec(command: Command) -> Result<()> {
         Command::Delete { alias } => delete_account(alias).await,
         Command::List { long } => list_accounts(long).await,
         Command::Code { account, network } => get_code(account, network).await,
+        Command::Interface {
+            account,
+            typescript,
+            network,
+        } => get_interface(account, typescript, network).await,
         Command::Balance { account, network } => get_balance(account, network).await,
     }
 }
@@ -14,7 +14,7 @@ const FA_TICKETER: &str =
     include_str!("../../../../contracts/examples/fa_ticketer/fa_ticketer.tz");
 const JSTZ_FA_BRIDGE: &str = include_str!("../../../../contracts/jstz_fa_bridge.tz");
 
-enum FaToken {
+pub(super) enum FaToken {
     Fa12 {
         address: ContractKt1Hash,
     },
@@ -1,5 +1,6 @@
 use std::ops::Deref;
 
+use jstz_client::{jstzd::JstzdClient, models::ContractCall, ApiError};
 use jstz_proto::context::account::Addressable;
 use log::{debug, info};
 
@@ -79,25 +80,23 @@ async fn exec_sandbox(
     amount: Tez,
 ) -> Result<()> {
     // go through jstzd server even when the sandbox is not in a container for simplicity
-    let client = reqwest::Client::new();
+    let client = JstzdClient::new(jstzd_server_base_url.to_string());
     // TODO: Use `Tez` for amount
     // https://linear.app/tezos/issue/JSTZ-475/use-tez-or-decimals
     let amount: f64 = amount.to_string().parse().unwrap();
-    let res = client
-        .post(format!("{jstzd_server_base_url}/contract_call"))
-        .json(&serde_json::json!({
-            "from": from,
-            "contract": NATIVE_BRIDGE_ADDRESS,
-            "amount": amount,
-            "entrypoint": "deposit",
-            "arg": format!("\"{to_pkh}\"")
-        }))
-        .send()
-        .await?;
-    if !res.status().is_success() {
-        bail_user_error!("Failed to deposit XTZ. Please check whether the addresses and network are correct.");
+    let call = ContractCall {
+        from: from.to_string(),
+        contract: NATIVE_BRIDGE_ADDRESS.to_string(),
+        amount,
+        entrypoint: "deposit".to_string(),
+        arg: format!("\"{to_pkh}\""),
+    };
+    match client.call_contract(&call).await {
+        Err(e) if e.downcast_ref::<ApiError>().is_some() => {
+            bail_user_error!("Failed to deposit XTZ. Please check whether the addresses and network are correct.");
+        }
+        result => result,
     }
-    Ok(())
 }
 
 #[cfg(test)]
@@ -0,0 +1,224 @@
+use crate::{
+    config::{Config, NetworkName},
+    utils::{AddressOrAlias, OriginatedOrAlias},
+};
+use anyhow::{anyhow, bail, Result};
+use clap::{arg, Args};
+use jstz_proto::context::account::Addressable;
+use log::info;
+use rust_decimal::Decimal;
+use std::path::PathBuf;
+use tezos_crypto_rs::hash::{ContractKt1Hash, SmartRollupHash};
+
+use super::deploy::FaToken;
+
+impl FaToken {
+    /// Returns the entrypoint and parameter that allow `operator` to transfer `amount`
+    /// tokens of `owner`. FA2 operators can transfer any amount.
+    fn approve_call(
+        &self,
+        owner: &str,
+        operator: &ContractKt1Hash,
+        amount: Option<u64>,
+    ) -> Result<(&'static str, String)> {
+        match self {
+            FaToken::Fa12 { .. } => {
+                let Some(amount) = amount else {
+                    bail!(
+                        "An amount is required to approve the transfer of FA1.2 tokens"
+                    );
+                };
+                Ok(("approve", format!("Pair \"{operator}\" {amount}")))
+            }
+            FaToken::Fa2 { token_id, .. } => Ok((
+                "update_operators",
+                format!("{{ Left (Pair \"{owner}\" \"{operator}\" {token_id}) }}"),
+            )),
+        }
+    }
+
+    fn address(&self) -> &ContractKt1Hash {
+        match self {
+            FaToken::Fa12 { address } | FaToken::Fa2 { address, .. } => address,
+        }
+    }
+}
+
+fn deposit_parameter(rollup: &SmartRollupHash, receiver: &str, amount: u64) -> String {
+    format!("Pair \"{rollup}\" \"{receiver}\" {amount}")
+}
+
+#[derive(Debug, Args)]
+pub struct ApproveBridge {
+    /// Tezos L1 address or alias of the token owner (must be stored in octez-client's wallet)
+    #[arg(long)]
+    pub source: AddressOrAlias,
+    /// Tezos L1 address or alias (must be stored in octez-client's wallet) of the FA token contract.
+    /// Can be either an FA1.2 or FA2 contract
+    #[arg(long)]
+    pub tezos_fa_token: OriginatedOrAlias,
+    /// Token id if the token is an FA2 token
+    #[arg(long = "token-id")]
+    pub fa_token_id: Option<u32>,
+    /// Tezos L1 address or alias of the FA bridge contract
+    #[arg(long)]
+    pub bridge: OriginatedOrAlias,
+    /// Amount of tokens the bridge is allowed to transfer. Required for FA1.2 tokens only
+    #[arg(long, default_value = None)]
+    pub amount: Option<u64>,
+    /// Specifies the network from the config file, defaulting to the configured default network.
+    /// Use `dev` for the local sandbox.
+    #[arg(short, long, default_value = None)]
+    pub network: Option<NetworkName>,
+    /// overrides the path to the config file.
+    #[arg(default_value = None, value_hint = clap::ValueHint::FilePath)]
+    pub config_path: Option<PathBuf>,
+}
+
+impl ApproveBridge {
+    pub async fn exec(self) -> Result<()> {
+        let ApproveBridge {
+            source,
+            tezos_fa_token,
+            fa_token_id,
+            bridge,
+            amount,
+            network,
+            config_path,
+        } = self;
+        let cfg = Config::load_path(config_path).await?;
+        let client = cfg.octez_client(&network)?;
+
+        let source = source.resolve_l1(&cfg, &network)?.to_base58();
+        let fa_token =
+            FaToken::from(&tezos_fa_token.resolve(&cfg, &network)?, fa_token_id);
+        let bridge_address = bridge.resolve(&cfg, &network)?;
+
+        let (entrypoint, parameter) =
+            fa_token.approve_call(&source, &bridge_address, amount)?;
+        client.call_contract(
+            &source,
+            &fa_token.address().to_base58_check(),
+            entrypoint,
+            &parameter,
+            &Decimal::ZERO,
+        )?;
+
+        info!("Approved the FA bridge {bridge_address} to transfer tokens of {source}");
+        Ok(())
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct DepositToken {
+    /// Tezos L1 address or alias of the depositor (must be stored in octez-client's wallet).
+    /// The bridge must be approved to transfer its tokens.
+    #[arg(long)]
+    pub source: AddressOrAlias,
+    /// Tezos L1 address or alias of the FA bridge contract
+    #[arg(long)]
+    pub bridge: OriginatedOrAlias,
+    /// jstz address or alias to deposit to.
+    #[arg(long)]
+    pub to: AddressOrAlias,
+    /// The amount of tokens to deposit.
+    #[arg(long)]
+    pub amount: u64,
+    /// Address of the jstz rollup. Required unless depositing to the `dev` network,
+    /// where it defaults to the rollup of the running sandbox
+    #[arg(long, default_value = None)]
+    pub rollup: Option<SmartRollupHash>,
+    /// Specifies the network from the config file, defaulting to the configured default network.
+    /// Use `dev` for the local sandbox.
+    #[arg(short, long, default_value = None)]
+    pub network: Option<NetworkName>,
+    /// overrides the path to the config file.
+    #[arg(default_value = None, value_hint = clap::ValueHint::FilePath)]
+    pub config_path: Option<PathBuf>,
+}
+
+impl DepositToken {
+    pub async fn exec(self) -> Result<()> {
+        let DepositToken {
+            source,
+            bridge,
+            to,
+            amount,
+            rollup,
+            network,
+            config_path,
+        } = self;
+        let cfg = Config::load_path(config_path).await?;
+        let client = cfg.octez_client(&network)?;
+
+        let source = source.resolve_l1(&cfg, &network)?.to_base58();
+        let bridge_address = bridge.resolve(&cfg, &network)?;
+        let receiver = to.resolve(&cfg)?.to_base58();
+        let rollup = match rollup {
+            Some(rollup) => rollup,
+            None if cfg.network_name(&network)? == NetworkName::Dev => cfg
+                .sandbox_rollup_address()
+                .cloned()
+                .ok_or_else(|| anyhow!("The sandbox is not running"))?,
+            None => bail!("The address of the jstz rollup is required, see --rollup"),
+        };
+
+        client.call_contract(
+            &source,
+            &bridge_address.to_base58_check(),
+            "deposit",
+            &deposit_parameter(&rollup, &receiver, amount),
+            &Decimal::ZERO,
+        )?;
+
+        info!("Deposited {amount} FA tokens from {source} to {receiver}");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tezos_crypto_rs::hash::{ContractKt1Hash, SmartRollupHash};
+
+    use super::{deposit_parameter, FaToken};
+
+    const OWNER: &str = "tz1dbGzJfjYFSjX8umiRZ2fmsAQsk8XMH1E9";
+    const ROLLUP: &str = "sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK";
+
+    fn kt1(address: &str) -> ContractKt1Hash {
+        ContractKt1Hash::from_base58_check(address).unwrap()
+    }
+
+    #[test]
+    fn approve_call_fa2() {
+        let token = FaToken::from(&kt1("KT1TxqZ8QtKvLu3V3JH7Gx58n7Co8pgtpQU5"), Some(1));
+        let bridge = kt1("KT1KRj5VMNmhxobTJBPq7u2kacqbxu9Cntx6");
+        assert_eq!(
+            token.approve_call(OWNER, &bridge, None).unwrap(),
+            (
+                "update_operators",
+                format!("{{ Left (Pair \"{OWNER}\" \"{bridge}\" 1) }}")
+            )
+        );
+    }
+
+    #[test]
+    fn approve_call_fa12() {
+        let token = FaToken::from(&kt1("KT1TxqZ8QtKvLu3V3JH7Gx58n7Co8pgtpQU5"), None);
+        let bridge = kt1("KT1KRj5VMNmhxobTJBPq7u2kacqbxu9Cntx6");
+        assert_eq!(
+            token.approve_call(OWNER, &bridge, Some(10)).unwrap(),
+            ("approve", format!("Pair \"{bridge}\" 10"))
+        );
+        assert!(token.approve_call(OWNER, &bridge, None).is_err());
+    }
+
+    #[test]
+    fn deposit_parameter_format() {
+        let rollup = SmartRollupHash::from_base58_check(ROLLUP).unwrap();
+        assert_eq!(
+            deposit_parameter(&rollup, OWNER, 1000),
+            format!("Pair \"{ROLLUP}\" \"{OWNER}\" 1000")
+        );
+    }
+}
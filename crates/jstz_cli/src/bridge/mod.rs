@@ -1,8 +1,10 @@
 use clap::Subcommand;
 use deploy::DeployBridge;
+use fa::{ApproveBridge, DepositToken};
 
 pub mod deploy;
 mod deposit;
+pub mod fa;
 mod withdraw;
 
 use crate::{
@@ -54,6 +56,12 @@ pub enum Command {
     /// 3. Locking the FA token and minting the same amount of tickets to the bridge contract.
     /// 4. The tickets are then sent to the L2 and can be redeemed for the FA token on the L2.
     FaDeploy(DeployBridge),
+    /// Approves an FA bridge to transfer FA tokens of a Tezos L1 address, which is required
+    /// before depositing them. FA2 tokens register the bridge as an operator, while FA1.2 tokens
+    /// approve a given amount.
+    FaApprove(ApproveBridge),
+    /// 💰 Deposits FA tokens from an existing Tezos L1 address to a jstz address through an FA bridge.
+    FaDeposit(DepositToken),
 }
 
 pub async fn exec(command: Command) -> Result<()> {
@@ -73,5 +81,7 @@ pub async fn exec(command: Command) -> Result<()> {
             let _ = deploy.exec().await?;
             Ok(())
         }
+        Command::FaApprove(approve) => Ok(approve.exec().await?),
+        Command::FaDeposit(deposit) => Ok(deposit.exec().await?),
     }
 }
@@ -6,10 +6,10 @@ use crate::{
     utils::{AddressOrAlias, Tez},
 };
 use anyhow::Context;
+use jstz_client::{jstzd::JstzdClient, ApiError};
 use jstz_crypto::{hash::Hash, public_key_hash::PublicKeyHash};
 use jstz_proto::context::account::{Address, Addressable};
 use log::debug;
-use reqwest::StatusCode;
 
 pub async fn exec(
     to: AddressOrAlias,
@@ -46,24 +46,19 @@ async fn sandbox_resolve_l1(
         AddressOrAlias::Address(v) => Ok(v),
         AddressOrAlias::Alias(alias) => {
             // go through jstzd server even when the sandbox is not in a container for simplicity
-            let res = reqwest::get(format!("{jstzd_server_base_url}/l1_alias/{alias}"))
-                .await
-                .context("failed to connect to jstzd server")?;
-            match res.status() {
-                StatusCode::OK => Ok(Address::User(
-                    PublicKeyHash::from_base58(
-                        &res.text()
-                            .await
-                            .context("failed to load text from response")?,
-                    )
-                    .context("failed to parse address from response")?,
+            let client = JstzdClient::new(jstzd_server_base_url.to_string());
+            match client.resolve_l1_alias(&alias).await {
+                Ok(Some(address)) => Ok(Address::User(
+                    PublicKeyHash::from_base58(&address)
+                        .context("failed to parse address from response")?,
                 )),
-                StatusCode::NOT_FOUND => {
+                Ok(None) => {
                     bail_user_error!("Unknown L1 address alias '{}'", alias)
                 }
-                _ => bail_user_error!(
+                Err(e) if e.downcast_ref::<ApiError>().is_some() => bail_user_error!(
                     "Failed to resolve L1 address aliases in the sandbox."
                 ),
+                Err(e) => Err(e.context("failed to connect to jstzd server")),
             }
         }
     }
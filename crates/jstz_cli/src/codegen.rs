@@ -0,0 +1,401 @@
+//! Generation of typed TypeScript clients from smart function interfaces.
+
+use std::{collections::HashSet, fmt::Write};
+
+use jstz_crypto::smart_function_hash::SmartFunctionHash;
+use jstz_proto::context::interface::{FunctionInterface, RouteInterface};
+use serde_json::{Map, Value};
+
+/// Generates a TypeScript module exporting a `createClient` function, which returns
+/// a method per route of `interface` calling the smart function at `address`
+pub fn typescript_client(
+    address: &SmartFunctionHash,
+    interface: &FunctionInterface,
+) -> String {
+    let mut types = String::new();
+    let mut methods = String::new();
+    let mut names = HashSet::new();
+    for route in &interface.routes {
+        let name = unique_name(&mut names, route_name(route));
+        let type_name = pascal_case(&name);
+        let request = route.request.as_ref().map(|schema| {
+            let type_name = format!("{type_name}Request");
+            let _ = writeln!(
+                types,
+                "export type {type_name} = {};\n",
+                typescript_type(&schema.0)
+            );
+            type_name
+        });
+        let response = match &route.response {
+            Some(schema) => {
+                let type_name = format!("{type_name}Response");
+                let _ = writeln!(
+                    types,
+                    "export type {type_name} = {};\n",
+                    typescript_type(&schema.0)
+                );
+                type_name
+            }
+            None => "void".to_string(),
+        };
+
+        let mut params = Vec::new();
+        let path_params = route.path_params().collect::<Vec<_>>();
+        if !path_params.is_empty() {
+            let fields = path_params
+                .iter()
+                .map(|param| format!("{param}: string"))
+                .collect::<Vec<_>>();
+            params.push(format!("params: {{ {} }}", fields.join("; ")));
+        }
+        if let Some(request) = &request {
+            params.push(format!("body: {request}"));
+        }
+        let path = route
+            .path
+            .split('/')
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(param) => format!("${{encodeURIComponent(params.{param})}}"),
+                None => template_literal(segment),
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+
+        if let Some(description) = &route.description {
+            // `*/` would end the comment
+            let lines = comment_lines(description)
+                .map(|line| line.replace("*/", "*\\/"))
+                .collect::<Vec<_>>();
+            match lines.as_slice() {
+                [] => {}
+                [line] => {
+                    let _ = writeln!(methods, "    /** {line} */");
+                }
+                lines => {
+                    let _ = writeln!(methods, "    /**");
+                    for line in lines {
+                        let _ = writeln!(methods, "     * {line}");
+                    }
+                    let _ = writeln!(methods, "     */");
+                }
+            }
+        }
+        let _ = writeln!(
+            methods,
+            "    async {name}({}): Promise<{response}> {{",
+            params.join(", ")
+        );
+        let _ = writeln!(
+            methods,
+            "      const response = await fetch(new Request(`jstz://${{ADDRESS}}{path}`, {{"
+        );
+        let _ = writeln!(methods, "        method: \"{}\",", route.method);
+        if request.is_some() {
+            let _ = writeln!(
+                methods,
+                "        headers: {{ \"Content-Type\": \"application/json\" }},"
+            );
+            let _ = writeln!(methods, "        body: JSON.stringify(body),");
+        }
+        let _ = writeln!(methods, "      }}));");
+        let _ = writeln!(methods, "      if (!response.ok) {{");
+        let _ = writeln!(
+            methods,
+            "        throw new Error(`{} {} failed with status ${{response.status}}`);",
+            route.method,
+            template_literal(&route.path)
+        );
+        let _ = writeln!(methods, "      }}");
+        if route.response.is_some() {
+            let _ = writeln!(methods, "      return response.json();");
+        }
+        let _ = writeln!(methods, "    }},");
+    }
+
+    let mut module =
+        format!("// Generated by `jstz account interface --typescript` for {address}\n");
+    if let Some(description) = &interface.description {
+        for line in comment_lines(description) {
+            let _ = writeln!(module, "// {line}");
+        }
+    }
+    let _ = write!(
+        module,
+        "\nexport const ADDRESS = \"{address}\";\n\n\
+         export type Fetch = (request: Request) => Promise<Response>;\n\n\
+         {types}\
+         export function createClient(fetch: Fetch = globalThis.fetch) {{\n  \
+         return {{\n{methods}  }};\n}}\n"
+    );
+    module
+}
+
+/// Splits `text` on JavaScript line terminators, so that each line can be written
+/// in a comment without ending it
+fn comment_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split(['\n', '\r', '\u{2028}', '\u{2029}'])
+        .filter(|line| !line.is_empty())
+}
+
+/// Escapes `text` to be written in a template literal
+fn template_literal(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('`', "\\`")
+        .replace("${", "\\${")
+}
+
+/// Name of the method of `route`, derived from its method and path if not set,
+/// e.g. `getTokensById` for `GET /tokens/:id`
+fn route_name(route: &RouteInterface) -> String {
+    if let Some(name) = &route.name {
+        return name.clone();
+    }
+    let mut name = route.method.to_lowercase();
+    for segment in route.path.split('/') {
+        match segment.strip_prefix(':') {
+            Some(param) => {
+                name.push_str("By");
+                name.push_str(&pascal_case(param));
+            }
+            None => name.push_str(&pascal_case(segment)),
+        }
+    }
+    name
+}
+
+fn unique_name(names: &mut HashSet<String>, name: String) -> String {
+    let mut unique = name.clone();
+    let mut suffix = 2;
+    while !names.insert(unique.clone()) {
+        unique = format!("{name}{suffix}");
+        suffix += 1;
+    }
+    unique
+}
+
+/// Converts `name` to PascalCase, dropping the characters that are not valid in
+/// identifiers
+fn pascal_case(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Converts a JSON schema to a TypeScript type. Schemas that cannot be expressed,
+/// such as references, are typed as `unknown`.
+fn typescript_type(schema: &Value) -> String {
+    let schema = match schema {
+        Value::Bool(true) => return "unknown".to_string(),
+        Value::Bool(false) => return "never".to_string(),
+        Value::Object(schema) => schema,
+        _ => return "unknown".to_string(),
+    };
+    if let Some(value) = schema.get("const") {
+        return value.to_string();
+    }
+    if let Some(Value::Array(values)) = schema.get("enum") {
+        return union(values.iter().map(Value::to_string));
+    }
+    for key in ["oneOf", "anyOf"] {
+        if let Some(Value::Array(schemas)) = schema.get(key) {
+            return union(schemas.iter().map(typescript_type));
+        }
+    }
+    if let Some(Value::Array(schemas)) = schema.get("allOf") {
+        let types = schemas.iter().map(typescript_type).collect::<Vec<_>>();
+        return format!("({})", types.join(" & "));
+    }
+    match schema.get("type") {
+        Some(Value::String(ty)) => primitive_type(schema, ty),
+        Some(Value::Array(types)) => union(
+            types
+                .iter()
+                .filter_map(Value::as_str)
+                .map(|ty| primitive_type(schema, ty)),
+        ),
+        _ => "unknown".to_string(),
+    }
+}
+
+fn primitive_type(schema: &Map<String, Value>, ty: &str) -> String {
+    match ty {
+        "string" => "string".to_string(),
+        "number" | "integer" => "number".to_string(),
+        "boolean" => "boolean".to_string(),
+        "null" => "null".to_string(),
+        "array" => match schema.get("items") {
+            Some(items) => format!("Array<{}>", typescript_type(items)),
+            None => "unknown[]".to_string(),
+        },
+        "object" => object_type(schema),
+        _ => "unknown".to_string(),
+    }
+}
+
+fn object_type(schema: &Map<String, Value>) -> String {
+    let required = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|keys| keys.iter().filter_map(Value::as_str).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let mut fields = Vec::new();
+    if let Some(Value::Object(properties)) = schema.get("properties") {
+        for (key, property) in properties {
+            let optional = if required.contains(&key.as_str()) {
+                ""
+            } else {
+                "?"
+            };
+            let key = match is_identifier(key) {
+                true => key.clone(),
+                false => Value::String(key.clone()).to_string(),
+            };
+            fields.push(format!("{key}{optional}: {}", typescript_type(property)));
+        }
+    }
+    match schema.get("additionalProperties") {
+        Some(Value::Bool(false)) => {}
+        Some(additional) if additional != &Value::Bool(true) => {
+            fields.push(format!("[key: string]: {}", typescript_type(additional)))
+        }
+        _ if fields.is_empty() => return "Record<string, unknown>".to_string(),
+        _ => {}
+    }
+    match fields.is_empty() {
+        true => "{}".to_string(),
+        false => format!("{{ {} }}", fields.join("; ")),
+    }
+}
+
+fn union(types: impl Iterator<Item = String>) -> String {
+    let types = types.collect::<Vec<_>>();
+    match types.len() {
+        0 => "never".to_string(),
+        1 => types[0].clone(),
+        _ => format!("({})", types.join(" | ")),
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+#[cfg(test)]
+mod tests {
+    use jstz_crypto::{hash::Hash, smart_function_hash::SmartFunctionHash};
+    use jstz_proto::context::interface::FunctionInterface;
+    use serde_json::json;
+
+    #[test]
+    fn typescript_type() {
+        let ty = super::typescript_type(&json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "integer" },
+                "kind": { "enum": ["a", "b"] },
+                "owner-name": { "type": ["string", "null"] },
+                "tags": { "type": "array", "items": { "type": "string" } }
+            },
+            "required": ["id"]
+        }));
+        assert_eq!(
+            ty,
+            r#"{ id: number; kind?: ("a" | "b"); "owner-name"?: (string | null); tags?: Array<string> }"#
+        );
+        assert_eq!(
+            super::typescript_type(&json!({ "type": "object" })),
+            "Record<string, unknown>"
+        );
+        assert_eq!(
+            super::typescript_type(&json!({
+                "type": "object",
+                "additionalProperties": { "type": "number" }
+            })),
+            "{ [key: string]: number }"
+        );
+        assert_eq!(
+            super::typescript_type(&json!({ "$ref": "#/definitions/Token" })),
+            "unknown"
+        );
+    }
+
+    #[test]
+    fn typescript_client() {
+        let address =
+            SmartFunctionHash::from_base58("KT19GXucGUitURBXXeEMMfqqhSQ5byt4P1zX")
+                .unwrap();
+        let interface = serde_json::from_value::<FunctionInterface>(json!({
+            "routes": [
+                {
+                    "method": "GET",
+                    "path": "/tokens/:id",
+                    "description": "Returns a token",
+                    "response": { "type": "string" }
+                },
+                {
+                    "name": "mint",
+                    "method": "POST",
+                    "path": "/tokens",
+                    "request": { "type": "object" }
+                }
+            ]
+        }))
+        .unwrap();
+        let client = super::typescript_client(&address, &interface);
+        assert!(client.contains(
+            r#"export const ADDRESS = "KT19GXucGUitURBXXeEMMfqqhSQ5byt4P1zX";"#
+        ));
+        assert!(client.contains("export type GetTokensByIdResponse = string;"));
+        assert!(client.contains("export type MintRequest = Record<string, unknown>;"));
+        assert!(client.contains("    /** Returns a token */\n"));
+        assert!(client.contains(
+            "async getTokensById(params: { id: string }): Promise<GetTokensByIdResponse> {"
+        ));
+        assert!(client.contains(
+            "new Request(`jstz://${ADDRESS}/tokens/${encodeURIComponent(params.id)}`, {"
+        ));
+        assert!(client.contains("async mint(body: MintRequest): Promise<void> {"));
+        assert!(client.contains("body: JSON.stringify(body),"));
+    }
+
+    #[test]
+    fn typescript_client_escapes_deployer_strings() {
+        let address =
+            SmartFunctionHash::from_base58("KT19GXucGUitURBXXeEMMfqqhSQ5byt4P1zX")
+                .unwrap();
+        let interface = serde_json::from_value::<FunctionInterface>(json!({
+            "description": "NFTs\nalert(1)",
+            "routes": [
+                {
+                    "method": "GET",
+                    "path": "/`${alert(1)}`/:id",
+                    "description": "Returns a token */ alert(1)\u{2028}/**"
+                }
+            ]
+        }))
+        .unwrap();
+        let client = super::typescript_client(&address, &interface);
+        assert!(client.contains("// NFTs\n// alert(1)\n"));
+        assert!(client.contains(
+            "    /**\n     * Returns a token *\\/ alert(1)\n     * /**\n     */\n"
+        ));
+        assert!(client.contains(
+            "new Request(`jstz://${ADDRESS}/\\`\\${alert(1)}\\`/${encodeURIComponent(params.id)}`, {"
+        ));
+        assert!(client.contains(
+            "throw new Error(`GET /\\`\\${alert(1)}\\`/:id failed with status ${response.status}`);"
+        ));
+    }
+}
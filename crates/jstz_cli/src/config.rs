@@ -1,10 +1,12 @@
+use anyhow::Context;
 use derive_more::{From, TryInto};
+use jstz_client::jstzd::JstzdClient;
 use jstz_crypto::{
     public_key::PublicKey, public_key_hash::PublicKeyHash, secret_key::SecretKey,
     smart_function_hash::SmartFunctionHash,
 };
 use jstz_proto::context::account::Address;
-use log::debug;
+use log::{debug, info};
 use octez::OctezClient;
 use serde::{Deserialize, Serialize};
 use serde_with::{DeserializeFromStr, SerializeDisplay};
@@ -14,6 +16,7 @@ use std::{
     path::PathBuf,
     str::FromStr,
 };
+use tezos_crypto_rs::hash::SmartRollupHash;
 
 use crate::{
     error::{bail, user_error, Result},
@@ -180,6 +183,38 @@ impl AddressOrAlias {
         }
     }
 
+    /// Resolves the account like [`Self::resolve`], except for aliases starting with
+    /// `@`, which are names of the name registry of the jstz node of `network`, e.g.
+    /// `@alice`
+    pub async fn resolve_with_registry(
+        &self,
+        cfg: &Config,
+        network: &Option<NetworkName>,
+    ) -> Result<Address> {
+        match self {
+            AddressOrAlias::Alias(alias) => match alias.strip_prefix('@') {
+                Some(name) => {
+                    let address = resolve_registered_name(name, cfg, network).await?;
+                    info!("Resolved name '{}' to {}", name, address);
+                    Ok(address)
+                }
+                None => self.resolve(cfg),
+            },
+            AddressOrAlias::Address(_) => self.resolve(cfg),
+        }
+    }
+
+    pub async fn resolve_with_registry_or_use_current_user(
+        account: Option<AddressOrAlias>,
+        cfg: &Config,
+        network: &Option<NetworkName>,
+    ) -> Result<Address> {
+        match account {
+            Some(account) => account.resolve_with_registry(cfg, network).await,
+            None => Self::resolve_or_use_current_user(None, cfg),
+        }
+    }
+
     pub fn resolve_l1(
         &self,
         cfg: &Config,
@@ -206,6 +241,26 @@ impl AddressOrAlias {
     }
 }
 
+/// Resolves `name` with the name registry of the jstz node of `network`
+async fn resolve_registered_name(
+    name: &str,
+    cfg: &Config,
+    network: &Option<NetworkName>,
+) -> Result<Address> {
+    let record = cfg
+        .jstz_client(network)?
+        .resolve_name(name)
+        .await
+        .with_context(|| format!("Failed to resolve name '{name}'"))?
+        .ok_or_else(|| user_error!("Name '{}' is not registered.", name))?;
+    Address::from_base58(&record.address).with_context(|| {
+        format!(
+            "Name '{}' resolves to an invalid address '{}'",
+            name, record.address
+        )
+    })
+}
+
 pub struct AccountsIter<'a> {
     inner: hash_map::Iter<'a, String, Account>,
 }
@@ -225,6 +280,7 @@ pub struct JstzdConfig {
     #[allow(unused)]
     pub octez_client: OctezClientConfig,
     pub jstz_node: JstzNodeConfig,
+    pub octez_rollup: Option<OctezRollupConfig>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -243,6 +299,11 @@ pub struct JstzNodeConfig {
     pub endpoint: String,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct OctezRollupConfig {
+    pub address: SmartRollupHash,
+}
+
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Config {
     /// Path to octez installation
@@ -368,14 +429,9 @@ impl Config {
     }
 
     async fn fetch_jstzd_config(jstzd_server_base_url: &str) -> Result<JstzdConfig> {
-        let r = reqwest::Client::new();
-        let c = r
-            .get(format!("{jstzd_server_base_url}/config/"))
-            .send()
-            .await?
-            .json::<JstzdConfig>()
-            .await?;
-        Ok(c)
+        JstzdClient::new(jstzd_server_base_url.to_string())
+            .config()
+            .await
     }
 
     pub fn save_to_path(&self, config_path: Option<PathBuf>) -> Result<()> {
@@ -423,6 +479,14 @@ impl Config {
         Ok(JstzClient::new(network.jstz_node_endpoint.clone()))
     }
 
+    /// Address of the jstz rollup of the running sandbox
+    pub fn sandbox_rollup_address(&self) -> Option<&SmartRollupHash> {
+        self.jstzd_config
+            .as_ref()
+            .and_then(|config| config.octez_rollup.as_ref())
+            .map(|rollup| &rollup.address)
+    }
+
     fn network(&self, name: &Option<NetworkName>) -> Result<Network> {
         let network = match name {
             Some(name) => self.lookup_network(name),
@@ -483,7 +547,11 @@ impl Config {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::{collections::HashMap, str::FromStr};
+
+    use jstz_proto::context::account::Addressable;
+
+    use crate::utils::AddressOrAlias;
 
     use super::{
         Config, JstzNodeConfig, JstzdConfig, Network, NetworkConfig, NetworkName,
@@ -501,6 +569,7 @@ mod tests {
             jstz_node: JstzNodeConfig {
                 endpoint: "http://jstz.node.endpoint/".to_owned(),
             },
+            octez_rollup: None,
         }
     }
 
@@ -592,5 +661,74 @@ mod tests {
         assert_eq!(cfg.octez_node.rpc_endpoint, "foo");
         assert_eq!(cfg.octez_client.base_dir, "bar");
         assert_eq!(cfg.jstz_node.endpoint, "baz");
+        assert!(cfg.octez_rollup.is_none());
+    }
+
+    #[tokio::test]
+    async fn sandbox_rollup_address() {
+        let mut config = Config::default();
+        assert!(config.sandbox_rollup_address().is_none());
+
+        let mut server = mockito::Server::new_async().await;
+        server.mock("GET", "/config/").with_body(r#"{"octez_node":{"rpc_endpoint":"foo"},"octez_client":{"base_dir":"bar"},"jstz_node":{"endpoint":"baz"},"octez_rollup":{"address":"sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK","operator":"tz1"}}"#).create();
+        config
+            .jstzd_config
+            .replace(Config::fetch_jstzd_config(&server.url()).await.unwrap());
+        assert_eq!(
+            config.sandbox_rollup_address().unwrap().to_base58_check(),
+            "sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK"
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_with_registry() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/names/alice")
+            .with_body(
+                r#"{"name":"alice","address":"tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV"}"#,
+            )
+            .create();
+        server.mock("GET", "/names/bob").with_status(404).create();
+        server
+            .mock("GET", "/names/carol")
+            .with_status(400)
+            .with_body(r#"{"error":"name registry is not configured"}"#)
+            .create();
+        let network = Network {
+            octez_node_rpc_endpoint: "a".to_owned(),
+            jstz_node_endpoint: server.url(),
+        };
+        let config = Config {
+            networks: NetworkConfig {
+                networks: HashMap::from([("foo".to_owned(), network)]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let network = Some(NetworkName::Custom("foo".to_string()));
+        let resolve = |account: &str| {
+            let account = AddressOrAlias::from_str(account).unwrap();
+            let (config, network) = (&config, &network);
+            async move { account.resolve_with_registry(config, network).await }
+        };
+
+        assert_eq!(
+            resolve("@alice").await.unwrap().to_base58(),
+            "tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV"
+        );
+        // only names starting with `@` are resolved with the registry
+        assert_eq!(
+            resolve("alice").await.unwrap_err().to_string(),
+            "User/smart function 'alice' not found. Please provide a valid address or alias."
+        );
+        assert_eq!(
+            resolve("@bob").await.unwrap_err().to_string(),
+            "Name 'bob' is not registered."
+        );
+        assert_eq!(
+            format!("{:#}", resolve("@carol").await.unwrap_err()),
+            "Failed to resolve name 'carol': jstz node responded with 400 Bad Request: name registry is not configured"
+        );
     }
 }
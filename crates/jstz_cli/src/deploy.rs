@@ -1,10 +1,10 @@
-use jstz_core::reveal_data::MAX_REVEAL_SIZE;
 use jstz_proto::{
+    context::interface::FunctionInterface,
     operation::{Content, DeployFunction, Operation, SignedOperation},
     receipt::{ReceiptContent, ReceiptResult},
 };
 use log::{debug, info};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::{
     account,
@@ -12,15 +12,18 @@ use crate::{
     error::{anyhow, bail, bail_user_error, user_error, Result},
     sandbox::{assert_sandbox_running, JSTZD_SERVER_BASE_URL},
     term::styles,
-    utils::{read_file_or_input_or_piped, Tez},
+    utils::{operation_fee, read_file_or_input_or_piped, Fees, Tez},
 };
 
+#[allow(clippy::too_many_arguments)]
 pub async fn exec(
     code_op: Option<String>,
     balance: Option<Tez>,
     name: Option<String>,
+    interface: Option<PathBuf>,
     network: Option<NetworkName>,
     force: bool,
+    fees: Fees,
     config_path: Option<PathBuf>,
 ) -> Result<()> {
     let mut cfg = Config::load_path(config_path.clone()).await?;
@@ -55,24 +58,33 @@ pub async fn exec(
 
     debug!("Nonce: {:?}", nonce);
 
+    let constants = jstz_client.get_constants().await?;
+
     let code = read_file_or_input_or_piped(code_op)?
         .ok_or(user_error!("No function code supplied. Please provide a filename or pipe the file contents into stdin."))?;
 
-    if code.len() > MAX_REVEAL_SIZE {
+    if code.len() > constants.max_operation_size {
         bail_user_error!(
-            "Smart functions are currently restricted to {MAX_REVEAL_SIZE} bytes"
+            "Smart functions are currently restricted to {} bytes",
+            constants.max_operation_size
         );
     }
 
     debug!("Code: {}", code);
 
+    let interface = interface.map(|path| read_interface(&path)).transpose()?;
+    let (max_fee, priority_fee) = fees.to_mutez();
+
     let op = Operation {
         public_key: user.public_key.clone(),
         nonce,
         content: Content::DeployFunction(DeployFunction {
             function_code: code,
             account_credit: balance.map(|b| b.to_mutez()).unwrap_or(0),
+            interface,
         }),
+        max_fee,
+        priority_fee,
     };
 
     debug!("Operation: {:?}", op);
@@ -85,6 +97,9 @@ pub async fn exec(
 
     debug!("Signed operation: {:?}", signed_op);
 
+    let fee = operation_fee(&constants, &signed_op)?;
+    debug!("Fee: {fee} mutez");
+
     // 3. Send operation to jstz-node
     jstz_client.post_operation(&signed_op).await?;
     let receipt = jstz_client.wait_for_operation_receipt(&hash).await?;
@@ -127,3 +142,15 @@ pub async fn exec(
 
     Ok(())
 }
+
+fn read_interface(path: &Path) -> Result<FunctionInterface> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        user_error!("Failed to read the interface '{}': {e}", path.display())
+    })?;
+    let interface = serde_json::from_str::<FunctionInterface>(&contents)
+        .map_err(|e| user_error!("Invalid interface '{}': {e}", path.display()))?;
+    interface
+        .validate()
+        .map_err(|e| user_error!("Invalid interface '{}': {e}", path.display()))?;
+    Ok(interface)
+}
@@ -14,7 +14,10 @@ async fn get(
 ) -> Result<()> {
     let cfg = Config::load().await?;
 
-    let address = AddressOrAlias::resolve_or_use_current_user(account, &cfg)?;
+    let address = AddressOrAlias::resolve_with_registry_or_use_current_user(
+        account, &cfg, &network,
+    )
+    .await?;
     debug!("resolved `account` -> {:?}", address);
 
     let value = cfg
@@ -38,7 +41,10 @@ async fn list(
 ) -> Result<()> {
     let cfg = Config::load().await?;
 
-    let address = AddressOrAlias::resolve_or_use_current_user(account, &cfg)?;
+    let address = AddressOrAlias::resolve_with_registry_or_use_current_user(
+        account, &cfg, &network,
+    )
+    .await?;
     debug!("resolved `account` -> {:?}", address);
 
     let value = cfg
@@ -4,6 +4,7 @@ use std::path::PathBuf;
 
 mod account;
 pub mod bridge;
+mod codegen;
 mod completions;
 pub mod config;
 mod deploy;
@@ -23,7 +24,7 @@ mod utils;
 use config::{Config, NetworkName};
 use error::Result;
 use run::DEFAULT_GAS_LIMIT;
-use utils::{AddressOrAlias, Tez};
+use utils::{AddressOrAlias, Fees, Tez};
 
 #[derive(Debug, Parser)]
 #[command(name = "jstz", author = "TriliTech <contact@trili.tech>", version)]
@@ -39,6 +40,9 @@ pub enum Command {
         /// Name (or alias) of the function.
         #[arg(long, default_value = None)]
         name: Option<String>,
+        /// Path to the interface description of the function, a JSON file listing its routes.
+        #[arg(long, value_name = "PATH", default_value = None, value_hint = clap::ValueHint::FilePath)]
+        interface: Option<PathBuf>,
         /// Specifies the network from the config file, defaulting to the configured default network.
         /// Use `dev` for the local sandbox.
         #[arg(short, long, default_value = None)]
@@ -46,6 +50,8 @@ pub enum Command {
         /// Overwrites an existing function name. Effective only when `name` is specified.
         #[arg(short, long)]
         force: bool,
+        #[command(flatten)]
+        fees: Fees,
         /// overrides the path to the config file.
         #[arg(long, value_name = "PATH", default_value = None, value_hint = clap::ValueHint::FilePath)]
         config_path: Option<PathBuf>,
@@ -68,6 +74,9 @@ pub enum Command {
         #[arg(name = "include", short, long, default_value_t = false)]
         include_response_headers: bool,
 
+        #[command(flatten)]
+        fees: Fees,
+
         /// Specifies the network from the config file, defaulting to the configured default network.
         ///  Use `dev` for the local sandbox.
         #[arg(short, long, default_value = None)]
@@ -100,6 +109,8 @@ pub enum Command {
         /// Include response headers in the output
         #[arg(name = "include", short, long)]
         include_response_headers: bool,
+        #[command(flatten)]
+        fees: Fees,
     },
     /// 🌉 Move XTZ between L1 and jstz with the jstz bridge {n}
     #[command(subcommand)]
@@ -170,19 +181,41 @@ pub async fn exec(command: Command) -> Result<()> {
             code,
             balance,
             name,
+            interface,
             network,
             force,
+            fees,
             config_path,
-        } => deploy::exec(code, balance, name, network, force, config_path).await,
+        } => {
+            deploy::exec(
+                code,
+                balance,
+                name,
+                interface,
+                network,
+                force,
+                fees,
+                config_path,
+            )
+            .await
+        }
         Command::Transfer {
             amount,
             to,
             gas_limit,
             include_response_headers,
             network,
+            fees,
         } => {
-            run::exec_transfer(amount, to, gas_limit, include_response_headers, network)
-                .await
+            run::exec_transfer(
+                amount,
+                to,
+                gas_limit,
+                include_response_headers,
+                network,
+                fees,
+            )
+            .await
         }
         Command::Run {
             url,
@@ -193,6 +226,7 @@ pub async fn exec(command: Command) -> Result<()> {
             network,
             trace,
             include_response_headers,
+            fees,
         } => {
             let args = run::RunArgs::new(url, http_method, gas_limit);
             run::exec(
@@ -200,7 +234,8 @@ pub async fn exec(command: Command) -> Result<()> {
                     .set_network(network)
                     .set_trace(trace)
                     .set_amount(amount)
-                    .set_include_response_headers(include_response_headers),
+                    .set_include_response_headers(include_response_headers)
+                    .set_fees(fees),
             )
             .await
         }
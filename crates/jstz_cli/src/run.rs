@@ -13,7 +13,7 @@ use serde_json::Value;
 use tokio::sync::mpsc;
 use url::Url;
 
-use crate::utils::Tez;
+use crate::utils::{Fees, Tez};
 use crate::{
     account,
     config::{Config, NetworkName},
@@ -21,7 +21,7 @@ use crate::{
     jstz::JstzClient,
     logs::{exec_trace, DEFAULT_LOG_LEVEL},
     term::styles,
-    utils::{read_file_or_input_or_piped, AddressOrAlias},
+    utils::{operation_fee, read_file_or_input_or_piped, AddressOrAlias},
 };
 
 // This was measured by running the benchmark.js,
@@ -68,6 +68,7 @@ pub struct RunArgs {
     network: Option<NetworkName>,
     trace: bool,
     include_response_headers: bool,
+    fees: Fees,
 }
 
 impl RunArgs {
@@ -81,6 +82,7 @@ impl RunArgs {
             network: None,
             trace: false,
             include_response_headers: false,
+            fees: Fees::default(),
         }
     }
 
@@ -111,6 +113,11 @@ impl RunArgs {
         self.amount = amount;
         self
     }
+
+    pub fn set_fees(mut self, fees: Fees) -> Self {
+        self.fees = fees;
+        self
+    }
 }
 
 /// transfer is a special case of run, where we add a special header to the request
@@ -122,9 +129,10 @@ pub async fn exec_transfer(
     gas_limit: u32,
     include_response_headers: bool,
     network: Option<NetworkName>,
+    fees: Fees,
 ) -> Result<()> {
     let cfg = Config::load().await?;
-    let to = AddressOrAlias::resolve_or_use_current_user(Some(to), &cfg)?;
+    let to = to.resolve_with_registry(&cfg, &network).await?;
     let url = match &to {
         Address::User(_) => format!("jstz://{to}"),
         // for sf address, ignore the function execution and just transfer the amount
@@ -134,7 +142,8 @@ pub async fn exec_transfer(
     exec(
         args.set_network(network)
             .set_include_response_headers(include_response_headers)
-            .set_amount(Some(amount)),
+            .set_amount(Some(amount))
+            .set_fees(fees),
     )
     .await
     .map_err(|err| anyhow!("Failed to transfer {} XTZ to {}: {}", amount, to, err))?;
@@ -211,6 +220,7 @@ pub async fn exec(args: RunArgs) -> Result<()> {
         );
     }
 
+    let (max_fee, priority_fee) = args.fees.to_mutez();
     let op = Operation {
         public_key: user.public_key.clone(),
         nonce,
@@ -224,6 +234,8 @@ pub async fn exec(args: RunArgs) -> Result<()> {
                 .try_into()
                 .map_err(|_| anyhow!("Invalid gas limit."))?,
         }),
+        max_fee,
+        priority_fee,
     };
 
     debug!("Operation: {:?}", op);
@@ -236,6 +248,10 @@ pub async fn exec(args: RunArgs) -> Result<()> {
 
     debug!("Signed operation: {:?}", signed_op);
 
+    let constants = jstz_client.get_constants().await?;
+    let fee = operation_fee(&constants, &signed_op)?;
+    debug!("Fee: {fee} mutez");
+
     // 4. Send message to jstz node
     debug!(
         "Running function at {} ",
@@ -1,5 +1,6 @@
 use anyhow::Context;
 use in_container::in_container;
+use jstz_client::jstzd::JstzdClient;
 use log::info;
 use tokio::process::{Child, Command};
 use tokio::signal::unix::{signal, SignalKind};
@@ -26,26 +27,16 @@ where
 }
 
 pub(crate) async fn is_jstzd_running(jstzd_server_base_url: &str) -> Result<bool> {
-    match reqwest::get(format!("{jstzd_server_base_url}/health")).await {
-        Err(e) => {
-            // ignore connection error because this is what is returned when the server
-            // is not running
-            if !e.is_connect() {
-                return Err(e.into());
-            }
-            Ok(false)
-        }
-        Ok(r) => Ok(r.status().is_success()),
-    }
+    JstzdClient::new(jstzd_server_base_url.to_string())
+        .is_running()
+        .await
 }
 
 async fn shutdown_jstzd(jstzd_server_base_url: &str) -> Result<()> {
-    reqwest::Client::new()
-        .put(format!("{jstzd_server_base_url}/shutdown"))
-        .send()
+    JstzdClient::new(jstzd_server_base_url.to_string())
+        .shutdown()
         .await
-        .context("failed to stop the sandbox")?;
-    Ok(())
+        .context("failed to stop the sandbox")
 }
 
 async fn run_jstzd(jstzd_server_base_url: &str) -> Result<Child> {
@@ -4,7 +4,12 @@ use crate::{
 };
 use anyhow::anyhow;
 use derive_more::Display;
-use jstz_proto::context::account::Address;
+use jstz_core::BinEncodable;
+use jstz_proto::{
+    constants::Constants,
+    context::account::Address,
+    operation::{Content, SignedOperation},
+};
 use rust_decimal::Decimal;
 use std::{
     fmt, fs,
@@ -186,6 +191,48 @@ impl Deref for Tez {
     }
 }
 
+/// Fees declared by an operation. The source pays the priority fee, capped at the
+/// maximum fee.
+#[derive(Debug, Clone, Copy, Default, clap::Args)]
+pub struct Fees {
+    /// Maximum fee in XTZ paid for the operation.
+    #[arg(long, default_value = None)]
+    pub max_fee: Option<Tez>,
+    /// Fee in XTZ offered to the sequencer to execute the operation before operations offering less.
+    #[arg(long, default_value = None)]
+    pub priority_fee: Option<Tez>,
+}
+
+impl Fees {
+    /// Returns the maximum fee and the priority fee in mutez
+    pub fn to_mutez(self) -> (Option<u64>, Option<u64>) {
+        (
+            self.max_fee.map(Tez::to_mutez),
+            self.priority_fee.map(Tez::to_mutez),
+        )
+    }
+}
+
+/// Returns the fee in mutez charged for `op` under the `constants` of the node, see
+/// `/constants`. Fails if `op` is larger than the node accepts.
+pub fn operation_fee(constants: &Constants, op: &SignedOperation) -> Result<u64> {
+    let size = op
+        .encode()
+        .map_err(|e| anyhow!("Failed to encode the operation: {e}"))?
+        .len();
+    if size > constants.max_operation_size {
+        return Err(user_error!(
+            "Operations are currently restricted to {} bytes, got {size} bytes",
+            constants.max_operation_size
+        ));
+    }
+    let gas_limit = match op.content() {
+        Content::RunFunction(run) => run.gas_limit,
+        _ => 0,
+    };
+    Ok(op.fee().saturating_add(constants.base_fee(gas_limit, size)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -267,4 +314,55 @@ mod tests {
         let tez = Tez::from_str("1.5").unwrap();
         assert_eq!(tez.to_mutez(), 1_500_000);
     }
+
+    #[test]
+    fn test_operation_fee() {
+        use jstz_crypto::{public_key::PublicKey, secret_key::SecretKey};
+        use jstz_proto::{
+            context::account::Nonce,
+            operation::{Operation, RunFunction},
+        };
+
+        let secret_key = SecretKey::from_base58(
+            "edsk4YBTjLtZgLNWKUN95unbAZ6cfq2eXhRveVt4J5oFPYHMzadpc8",
+        )
+        .unwrap();
+        let op = Operation {
+            public_key: PublicKey::from_base58(
+                "edpkuBknW28nW72KG6RoHtYW7p12T6GKc7nAbwYX5m8Wd9sDVC9yav",
+            )
+            .unwrap(),
+            nonce: Nonce(0),
+            content: Content::RunFunction(RunFunction {
+                uri: format!("jstz://{TEST_TZ1}/").parse().unwrap(),
+                method: Default::default(),
+                headers: Default::default(),
+                body: Default::default(),
+                gas_limit: 10,
+            }),
+            max_fee: Some(50),
+            priority_fee: Some(80),
+        };
+        let op = SignedOperation::new(secret_key.sign(&op.hash()).unwrap(), op);
+        let size = op.encode().unwrap().len();
+
+        let constants = Constants::default();
+        assert_eq!(operation_fee(&constants, &op).unwrap(), 50);
+
+        let constants = Constants {
+            gas_price: 2,
+            storage_fee_rate: 1,
+            ..Constants::default()
+        };
+        assert_eq!(
+            operation_fee(&constants, &op).unwrap(),
+            50 + 20 + size as u64
+        );
+
+        let constants = Constants {
+            max_operation_size: size - 1,
+            ..Constants::default()
+        };
+        assert!(operation_fee(&constants, &op).is_err());
+    }
 }
@@ -2,7 +2,10 @@ use jstz_crypto::{
     hash::Blake2b,
     smart_function_hash::{Kt1Hash, SmartFunctionHash},
 };
-use jstz_proto::receipt::{DeployFunctionReceipt, Receipt};
+use jstz_proto::{
+    constants::Constants,
+    receipt::{DeployFunctionReceipt, Receipt},
+};
 use std::{fs::File, io::Write};
 use tempfile::{NamedTempFile, TempDir};
 use tezos_crypto_rs::hash::ContractKt1Hash;
@@ -38,6 +41,10 @@ fn deploy() {
         )
         .with_body("0")
         .create();
+    let constants = server
+        .mock("GET", "/constants")
+        .with_body(serde_json::to_string(&Constants::default()).unwrap())
+        .create();
     server.mock("POST", "/operations").with_status(200).create();
     let receipt = Receipt::new(
         Blake2b::default(),
@@ -131,4 +138,21 @@ fn deploy() {
     );
     let output = process.exp_eof().unwrap();
     assert!(output.contains(success_msg));
+
+    // the code size limit is read from the node
+    constants.remove();
+    let constants = Constants {
+        max_operation_size: 10,
+        ..Constants::default()
+    };
+    server
+        .mock("GET", "/constants")
+        .with_body(serde_json::to_string(&constants).unwrap())
+        .create();
+    let mut process = jstz_cmd(
+        ["deploy", source_file.path().to_str().unwrap(), "--force"],
+        Some(process.tmp),
+    );
+    let output = process.exp_eof().unwrap();
+    assert!(output.contains("Smart functions are currently restricted to 10 bytes"));
 }
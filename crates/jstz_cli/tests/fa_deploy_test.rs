@@ -81,8 +81,10 @@ async fn fa_deploy_test() {
         code: Some(jstz_fa_path),
         balance: None,
         name: Some(fa_token_alias.to_string()),
+        interface: None,
         network: None,
         force: false,
+        fees: Default::default(),
         config_path: Some(temp_file_path.clone()),
     };
     jstz_cli::exec(deploy_jstz_fa).await.unwrap();
@@ -0,0 +1,163 @@
+//! Client of the jstzd server, which manages the sandbox
+
+use anyhow::Result;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+
+use crate::{models::ContractCall, ApiError};
+
+pub struct JstzdClient {
+    endpoint: String,
+    client: reqwest::Client,
+}
+
+impl JstzdClient {
+    pub fn new(endpoint: String) -> Self {
+        Self {
+            endpoint,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Returns true if the jstzd server is up and healthy, and false if it is not
+    /// running
+    pub async fn is_running(&self) -> Result<bool> {
+        match self
+            .client
+            .get(format!("{}/health", self.endpoint))
+            .send()
+            .await
+        {
+            // connection errors are what is returned when the server is not running
+            Err(e) if e.is_connect() => Ok(false),
+            Err(e) => Err(e.into()),
+            Ok(response) => Ok(response.status().is_success()),
+        }
+    }
+
+    /// Stops the sandbox
+    pub async fn shutdown(&self) -> Result<()> {
+        let response = self
+            .client
+            .put(format!("{}/shutdown", self.endpoint))
+            .send()
+            .await?;
+        check(response).await?;
+        Ok(())
+    }
+
+    /// Returns the configuration of the sandbox
+    pub async fn config<T: DeserializeOwned>(&self) -> Result<T> {
+        let response = self
+            .client
+            .get(format!("{}/config/", self.endpoint))
+            .send()
+            .await?;
+        Ok(check(response).await?.json().await?)
+    }
+
+    /// Calls a contract from an account of the octez client of the sandbox
+    pub async fn call_contract(&self, call: &ContractCall) -> Result<()> {
+        let response = self
+            .client
+            .post(format!("{}/contract_call", self.endpoint))
+            .json(call)
+            .send()
+            .await?;
+        check(response).await?;
+        Ok(())
+    }
+
+    /// Returns the address of the L1 account `alias` of the octez client of the
+    /// sandbox, or `None` if there is no such account
+    pub async fn resolve_l1_alias(&self, alias: &str) -> Result<Option<String>> {
+        let response = self
+            .client
+            .get(format!("{}/l1_alias/{}", self.endpoint, alias))
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            _ => Ok(Some(check(response).await?.text().await?)),
+        }
+    }
+}
+
+/// Returns `response` if it is successful, or its [`ApiError`] otherwise
+async fn check(response: reqwest::Response) -> Result<reqwest::Response> {
+    match response.status().is_success() {
+        true => Ok(response),
+        false => Err(ApiError::from_response(response).await.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+
+    use super::JstzdClient;
+    use crate::{models::ContractCall, ApiError};
+
+    #[tokio::test]
+    async fn is_running() {
+        let mut server = mockito::Server::new_async().await;
+        let client = JstzdClient::new(server.url());
+        let mock = server.mock("GET", "/health").with_status(503).create();
+        assert!(!client.is_running().await.unwrap());
+        mock.remove();
+        server.mock("GET", "/health").create();
+        assert!(client.is_running().await.unwrap());
+
+        // Nothing listens on the discard port
+        let client = JstzdClient::new("http://127.0.0.1:9".to_string());
+        assert!(!client.is_running().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn call_contract() {
+        let mut server = mockito::Server::new_async().await;
+        let call = ContractCall {
+            from: "bootstrap1".to_string(),
+            contract: "KT1".to_string(),
+            amount: 1.5,
+            entrypoint: "deposit".to_string(),
+            arg: "\"tz1\"".to_string(),
+        };
+        let mock = server
+            .mock("POST", "/contract_call")
+            .match_body(mockito::Matcher::Json(serde_json::to_value(&call).unwrap()))
+            .create();
+        let client = JstzdClient::new(server.url());
+        client.call_contract(&call).await.unwrap();
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn resolve_l1_alias() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/l1_alias/foo")
+            .with_body("tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV")
+            .create();
+        server
+            .mock("GET", "/l1_alias/bar")
+            .with_status(404)
+            .create();
+        server
+            .mock("GET", "/l1_alias/baz")
+            .with_status(500)
+            .create();
+        let client = JstzdClient::new(server.url());
+
+        assert_eq!(
+            client.resolve_l1_alias("foo").await.unwrap().as_deref(),
+            Some("tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV")
+        );
+        assert_eq!(client.resolve_l1_alias("bar").await.unwrap(), None);
+        let e = client.resolve_l1_alias("baz").await.unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<ApiError>().unwrap().status,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+}
@@ -1,21 +1,72 @@
-use std::time::Duration;
+use std::{fmt, time::Duration};
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use jstz_crypto::smart_function_hash::SmartFunctionHash;
 use jstz_proto::{
-    context::account::{Address, Addressable, Nonce},
+    constants::Constants,
+    context::{
+        account::{Account, Address, Addressable, Nonce},
+        code::CodeHash,
+        interface::FunctionInterface,
+    },
     operation::{OperationHash, SignedOperation},
     receipt::Receipt,
-    runtime::KvValue,
+    runtime::{KvValue, ParsedCode},
 };
 use log::debug;
-use reqwest::StatusCode;
+use models::{AccountBalance, AccountOperations, NameRecord, OperationStatus};
+use reqwest::{RequestBuilder, StatusCode};
 use reqwest_eventsource::EventSource;
+use serde::Serialize;
 use tokio::time::sleep;
 
+pub mod jstzd;
+pub mod models;
+
+/// Error response of the node, which can be downcast from the errors of the client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    /// The `error` field of the response body, or the whole body if it has none
+    pub message: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.message.is_empty() {
+            true => write!(f, "jstz node responded with {}", self.status),
+            false => write!(
+                f,
+                "jstz node responded with {}: {}",
+                self.status, self.message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ApiError {
+    async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|v| v["error"].as_str().map(str::to_string))
+            .unwrap_or(body);
+        Self { status, message }
+    }
+}
+
+#[derive(Serialize)]
+struct BalancesRequest<'a> {
+    addresses: &'a [Address],
+}
+
 pub struct JstzClient {
     endpoint: String,
     client: reqwest::Client,
+    auth_token: Option<String>,
 }
 
 impl JstzClient {
@@ -23,6 +74,90 @@ impl JstzClient {
         Self {
             endpoint,
             client: reqwest::Client::new(),
+            auth_token: None,
+        }
+    }
+
+    /// Sends `token` as bearer token of the requests to the write endpoints, which
+    /// the node requires when it is configured with an auth token
+    pub fn with_auth_token(mut self, token: String) -> Self {
+        self.auth_token = Some(token);
+        self
+    }
+
+    /// Returns true if the node is up and healthy
+    pub async fn health(&self) -> Result<bool> {
+        let response = self.get(&format!("{}/health", self.endpoint)).await?;
+        Ok(response.status().is_success())
+    }
+
+    /// Returns the run mode of the node, e.g. `default` or `sequencer`
+    pub async fn get_mode(&self) -> Result<String> {
+        let response = self.get(&format!("{}/mode", self.endpoint)).await?;
+        Ok(Self::check(response).await?.json::<String>().await?)
+    }
+
+    pub async fn get_constants(&self) -> Result<Constants> {
+        let response = self.get(&format!("{}/constants", self.endpoint)).await?;
+        Ok(Self::check(response).await?.json::<Constants>().await?)
+    }
+
+    pub async fn get_account(&self, address: &Address) -> Result<Option<Account>> {
+        let response = self
+            .get(&format!("{}/accounts/{}", self.endpoint, address))
+            .await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            _ => Ok(Some(Self::check(response).await?.json::<Account>().await?)),
+        }
+    }
+
+    /// Returns the balances of `addresses`, in order
+    pub async fn get_balances(
+        &self,
+        addresses: &[Address],
+    ) -> Result<Vec<AccountBalance>> {
+        let response = self
+            .client
+            .post(format!("{}/accounts/balances", self.endpoint))
+            .json(&BalancesRequest { addresses })
+            .send()
+            .await?;
+        Ok(Self::check(response).await?.json().await?)
+    }
+
+    /// Returns a page of the operation history of `address`, starting from `cursor`,
+    /// the `next_cursor` of the previous page. Only available in sequencer mode.
+    pub async fn get_account_operations(
+        &self,
+        address: &Address,
+        limit: Option<usize>,
+        cursor: Option<u64>,
+    ) -> Result<AccountOperations> {
+        let mut request = self
+            .client
+            .get(format!("{}/accounts/{}/operations", self.endpoint, address));
+        if let Some(limit) = limit {
+            request = request.query(&[("limit", limit)]);
+        }
+        if let Some(cursor) = cursor {
+            request = request.query(&[("cursor", cursor)]);
+        }
+        Ok(Self::check(request.send().await?).await?.json().await?)
+    }
+
+    /// Returns the status of an operation in the sequencer, or `None` if the
+    /// sequencer does not know it. Only available in sequencer mode.
+    pub async fn get_operation_status(
+        &self,
+        hash: &OperationHash,
+    ) -> Result<Option<OperationStatus>> {
+        let response = self
+            .get(&format!("{}/operations/{}/status", self.endpoint, hash))
+            .await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            _ => Ok(Some(Self::check(response).await?.json().await?)),
         }
     }
 
@@ -61,7 +196,7 @@ impl JstzClient {
                 Ok(nonce)
             }
             StatusCode::NOT_FOUND => Ok(Nonce::default()),
-            status => bail!("Failed to get nonce. Status: {}", status),
+            _ => Err(Self::error(response, "Failed to get nonce").await),
         }
     }
 
@@ -78,8 +213,58 @@ impl JstzClient {
             StatusCode::NOT_FOUND => {
                 bail!("Account '{}' not found", address)
             }
-            // For any other status, return a generic error
-            _ => bail!("Failed to get the code"),
+            _ => Err(Self::error(response, "Failed to get the code").await),
+        }
+    }
+
+    /// Returns the code stored under `hash`, which smart function accounts refer to,
+    /// or `None` if no code is stored under it
+    pub async fn get_code_by_hash(&self, hash: &CodeHash) -> Result<Option<ParsedCode>> {
+        let response = self
+            .get(&format!("{}/code/{}", self.endpoint, hash))
+            .await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            _ => Ok(Some(Self::check(response).await?.json().await?)),
+        }
+    }
+
+    /// Returns the interface description the smart function was deployed with, or
+    /// `None` if it was deployed without one
+    pub async fn get_interface(
+        &self,
+        address: &SmartFunctionHash,
+    ) -> Result<Option<FunctionInterface>> {
+        let response = self
+            .get(&format!("{}/accounts/{}/interface", self.endpoint, address))
+            .await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            _ => Ok(Some(Self::check(response).await?.json().await?)),
+        }
+    }
+
+    /// Resolves `name` with the name registry of the node, returning `None` if the
+    /// name is not registered
+    pub async fn resolve_name(&self, name: &str) -> Result<Option<NameRecord>> {
+        let response = self
+            .get(&format!("{}/names/{}", self.endpoint, name))
+            .await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            _ => Ok(Some(Self::check(response).await?.json().await?)),
+        }
+    }
+
+    /// Returns the name registered by `address` in the name registry of the node,
+    /// or `None` if it has not registered any
+    pub async fn lookup_name(&self, address: &Address) -> Result<Option<NameRecord>> {
+        let response = self
+            .get(&format!("{}/addresses/{}/name", self.endpoint, address))
+            .await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            _ => Ok(Some(Self::check(response).await?.json().await?)),
         }
     }
 
@@ -96,7 +281,7 @@ impl JstzClient {
             StatusCode::NOT_FOUND => {
                 bail!("Account '{}' not found", address.to_base58())
             }
-            _ => bail!("Failed to get the balance"),
+            _ => Err(Self::error(response, "Failed to get the balance").await),
         }
     }
 
@@ -118,8 +303,7 @@ impl JstzClient {
                 Ok(Some(kv))
             }
             StatusCode::NOT_FOUND => Ok(None),
-            // For any other status, return a generic error
-            _ => bail!("Failed to get value."),
+            _ => Err(Self::error(response, "Failed to get value").await),
         }
     }
 
@@ -144,7 +328,7 @@ impl JstzClient {
                 Ok(Some(kv))
             }
             StatusCode::NOT_FOUND => Ok(None),
-            _ => bail!("Failed to get subkey list."),
+            _ => Err(Self::error(response, "Failed to get subkey list").await),
         }
     }
 
@@ -171,22 +355,112 @@ impl JstzClient {
         }
     }
 
+    /// Injects `operation` in the node. Errors of the node are returned as
+    /// [`ApiError`].
     pub async fn post_operation(&self, operation: &SignedOperation) -> Result<()> {
         let response = self
-            .client
-            .post(format!("{}/operations", self.endpoint))
+            .authorized(self.client.post(format!("{}/operations", self.endpoint)))
             .json(operation)
             .send()
             .await?;
-
-        match response.status() {
-            StatusCode::OK => Ok(()),
-            // For any other status, return a generic error
-            status => bail!("Failed to post operation. Status: {}", status),
-        }
+        Self::check(response)
+            .await
+            .context("Failed to post operation")?;
+        Ok(())
     }
 
     async fn get(&self, url: &str) -> Result<reqwest::Response> {
         Ok(self.client.get(url).send().await?)
     }
+
+    fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.auth_token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Returns `response` if it is successful, or its [`ApiError`] otherwise
+    async fn check(response: reqwest::Response) -> Result<reqwest::Response> {
+        match response.status().is_success() {
+            true => Ok(response),
+            false => Err(ApiError::from_response(response).await.into()),
+        }
+    }
+
+    /// Returns the [`ApiError`] of the unexpected `response`, with `context`
+    async fn error(response: reqwest::Response, context: &'static str) -> anyhow::Error {
+        anyhow::Error::new(ApiError::from_response(response).await).context(context)
+    }
+}
+
+/// Returns true if `e` is a transient error of the client, i.e. the node could not be
+/// reached or responded with a server error or 429, so that the request can be retried
+pub fn is_transient(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<ApiError>() {
+            return e.status == StatusCode::TOO_MANY_REQUESTS
+                || e.status.is_server_error();
+        }
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_timeout() || e.is_connect() || e.is_request())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use jstz_proto::context::account::Address;
+    use reqwest::StatusCode;
+
+    use crate::{is_transient, ApiError, JstzClient};
+
+    fn address() -> Address {
+        Address::from_base58("tz1ficxJFv7MUtsCimF8bmT9SYPDok52ySg6").unwrap()
+    }
+
+    #[tokio::test]
+    async fn unexpected_statuses_are_api_errors() {
+        let mut server = mockito::Server::new_async().await;
+        let address = address();
+        let path = format!("/accounts/{address}/nonce");
+        let mock = server
+            .mock("GET", path.as_str())
+            .with_status(500)
+            .with_body(r#"{"error":"database is locked"}"#)
+            .create();
+        let client = JstzClient::new(server.url());
+
+        let e = client.get_nonce(&address).await.unwrap_err();
+        mock.assert();
+        let api_error = e.downcast_ref::<ApiError>().unwrap();
+        assert_eq!(api_error.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(api_error.message, "database is locked");
+        assert_eq!(e.to_string(), "Failed to get nonce");
+    }
+
+    #[test]
+    fn transient_errors() {
+        let error = |status| {
+            anyhow::Error::new(ApiError {
+                status,
+                message: String::new(),
+            })
+            .context("Failed to get nonce")
+        };
+        assert!(is_transient(&error(StatusCode::INTERNAL_SERVER_ERROR)));
+        assert!(is_transient(&error(StatusCode::SERVICE_UNAVAILABLE)));
+        assert!(is_transient(&error(StatusCode::TOO_MANY_REQUESTS)));
+        assert!(!is_transient(&error(StatusCode::BAD_REQUEST)));
+        assert!(!is_transient(&error(StatusCode::NOT_FOUND)));
+        assert!(!is_transient(&anyhow::anyhow!("invalid operation")));
+    }
+
+    #[tokio::test]
+    async fn unreachable_node_is_transient() {
+        // Nothing listens on the discard port
+        let client = JstzClient::new("http://127.0.0.1:9".to_string());
+        let e = client.get_nonce(&address()).await.unwrap_err();
+        assert!(is_transient(&e));
+    }
 }
@@ -0,0 +1,77 @@
+//! Models of the jstz-node and jstzd APIs that are not defined in `jstz_proto`. The
+//! node serves the same models, with their OpenAPI schemas when the `utoipa`
+//! feature is enabled.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct AccountBalance {
+    pub address: String,
+    /// Balance of the account, `null` if the account does not exist
+    pub balance: Option<u64>,
+}
+
+/// The state of an operation in the sequencer.
+///
+/// Operations injected through the node start `queued`, and become `included` once
+/// read from an L1 inbox, unless they were executed before. Operations end
+/// `committed` or `failed` once executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum OperationState {
+    /// Waiting in the sequencer queue
+    Queued,
+    /// Read from an L1 inbox and waiting in the sequencer queue
+    Included,
+    /// Being executed by the sequencer
+    Executing,
+    /// Executed, with its receipt and storage writes committed
+    Committed,
+    /// The sequencer failed to execute the operation
+    Failed,
+}
+
+/// The last recorded state of an operation in the sequencer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct OperationStatus {
+    pub state: OperationState,
+    /// L1 level of the inbox the operation was read from, if any
+    pub level: Option<u32>,
+    /// Unix timestamp, in seconds, of the last state transition
+    pub timestamp: u64,
+}
+
+/// A page of the operation history of an account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct AccountOperations {
+    /// Hashes of the operations, most recent first
+    pub operations: Vec<String>,
+    /// Cursor of the next page, `None` on the last page
+    pub next_cursor: Option<u64>,
+}
+
+/// A name of the name registry and the address it resolves to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct NameRecord {
+    pub name: String,
+    pub address: String,
+}
+
+/// A contract call made by jstzd with an account of its octez client, see
+/// [`crate::jstzd::JstzdClient::call_contract`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractCall {
+    /// Alias or address of the account making the call
+    pub from: String,
+    pub contract: String,
+    /// Amount transferred with the call, in tez
+    pub amount: f64,
+    pub entrypoint: String,
+    /// Michelson argument of the entrypoint
+    pub arg: String,
+}
@@ -1,12 +1,18 @@
+use std::cmp::Ordering;
+
 use bincode::{
     config::{Configuration, Fixint, Limit, LittleEndian},
-    Decode, Encode,
+    de::{read::Reader, BorrowDecoder, Decoder},
+    enc::{write::Writer, Encoder},
+    error::{DecodeError, EncodeError},
+    BorrowDecode, Decode, Encode,
 };
+use derive_more::{Deref, DerefMut};
 use serde::{de::DeserializeOwned, Serialize};
 
 use crate::{
     error::{Error, Result},
-    reveal_data::MAX_REVEAL_SIZE,
+    reveal_data::{MAX_DECOMPRESSED_REVEAL_SIZE, MAX_REVEAL_SIZE},
 };
 
 // FixintEncoding is used for predictable, fixed-width integer encoding, which makes decoding
@@ -18,6 +24,16 @@ const BINCODE_CONFIGURATION: Configuration<LittleEndian, Fixint, Limit<MAX_REVEA
         .with_fixed_int_encoding()
         .with_limit();
 
+// Revealed data may be compressed, in which case it decodes to more than
+// MAX_REVEAL_SIZE bytes.
+const REVEAL_BINCODE_CONFIGURATION: Configuration<
+    LittleEndian,
+    Fixint,
+    Limit<MAX_DECOMPRESSED_REVEAL_SIZE>,
+> = bincode::config::standard()
+    .with_fixed_int_encoding()
+    .with_limit();
+
 /// Trait for types that can be encoded to and decoded from binary format
 pub trait BinEncodable {
     fn encode(&self) -> Result<Vec<u8>>;
@@ -45,6 +61,97 @@ impl<T: Encode + Decode> BinEncodable for T {
     }
 }
 
+/// Version passed to [`Versioned::migrate`] for values persisted before their
+/// type adopted [`VersionedValue`]
+pub const UNVERSIONED: u8 = 0;
+
+/// Marks the start of a [`VersionedValue`]. No layout persisted before
+/// [`VersionedValue`] starts with these bytes: enum tags and lengths are small
+/// fixed-width integers.
+const VERSION_MARKER: [u8; 8] = *b"\xff\xffjstzv\xff";
+
+/// A type whose binary layout is tagged with a version, so that a kernel upgrade
+/// can change the layout and still decode the values persisted by earlier kernels.
+/// Values are persisted wrapped in [`VersionedValue`].
+///
+/// To change the layout, bump [`Versioned::VERSION`] and decode the layouts of
+/// the earlier versions in [`Versioned::migrate`]. Values persisted before a
+/// type adopts [`VersionedValue`] are migrated from version [`UNVERSIONED`].
+pub trait Versioned: Encode + Decode {
+    /// The version of the current layout, starting at 1
+    const VERSION: u8;
+
+    /// Decodes a value encoded with the layout of an earlier `version`
+    fn migrate<D: Decoder>(
+        version: u8,
+        _decoder: &mut D,
+    ) -> std::result::Result<Self, DecodeError> {
+        Err(DecodeError::OtherString(format!(
+            "no migration from version {version} to version {}",
+            Self::VERSION
+        )))
+    }
+}
+
+/// A value encoded with a marker and the version of its layout as prefix.
+/// Values must be decoded from a slice, see [`BinEncodable::decode`].
+#[derive(Debug, Clone, PartialEq, Eq, Deref, DerefMut)]
+pub struct VersionedValue<T>(pub T);
+
+impl<T: Versioned> Encode for VersionedValue<T> {
+    fn encode<E: Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> std::result::Result<(), EncodeError> {
+        encoder.writer().write(&VERSION_MARKER)?;
+        T::VERSION.encode(encoder)?;
+        self.0.encode(encoder)
+    }
+}
+
+impl<T: Versioned> Decode for VersionedValue<T> {
+    fn decode<D: Decoder>(decoder: &mut D) -> std::result::Result<Self, DecodeError> {
+        let reader = decoder.reader();
+        let is_versioned = reader
+            .peek_read(VERSION_MARKER.len())
+            .is_some_and(|bytes| bytes == VERSION_MARKER);
+        if !is_versioned {
+            return Ok(Self(T::migrate(UNVERSIONED, decoder)?));
+        }
+        reader.consume(VERSION_MARKER.len());
+
+        let version = u8::decode(decoder)?;
+        let value = match version.cmp(&T::VERSION) {
+            Ordering::Equal => T::decode(decoder)?,
+            Ordering::Less => T::migrate(version, decoder)?,
+            Ordering::Greater => {
+                return Err(DecodeError::OtherString(format!(
+                    "version {version} is newer than the supported version {}",
+                    T::VERSION
+                )))
+            }
+        };
+        Ok(Self(value))
+    }
+}
+
+impl<'de, T: Versioned> BorrowDecode<'de> for VersionedValue<T> {
+    fn borrow_decode<D: BorrowDecoder<'de>>(
+        decoder: &mut D,
+    ) -> std::result::Result<Self, DecodeError> {
+        Self::decode(decoder)
+    }
+}
+
+/// Decodes revealed data, see [`crate::reveal_data::RevealData::reveal_and_decode`]
+pub(crate) fn decode_revealed<T: Decode>(bytes: &[u8]) -> Result<T> {
+    let (value, _) = bincode::decode_from_slice(bytes, REVEAL_BINCODE_CONFIGURATION)
+        .map_err(|err| Error::SerializationError {
+            description: format!("{err}"),
+        })?;
+    Ok(value)
+}
+
 pub fn serde_encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
     bincode::serde::encode_to_vec(value, BINCODE_CONFIGURATION).map_err(|err| {
         Error::SerializationError {
@@ -115,6 +222,116 @@ mod tests {
         assert!(result.is_err_and(|e| e.to_string().contains("LimitExceeded")));
     }
 
+    #[derive(Debug, Clone, PartialEq, Encode, Decode)]
+    struct AccountV1 {
+        amount: u64,
+    }
+
+    impl Versioned for AccountV1 {
+        const VERSION: u8 = 1;
+    }
+
+    #[derive(Debug, Clone, PartialEq, Encode, Decode)]
+    struct AccountV2 {
+        amount: u64,
+        nonce: u64,
+    }
+
+    impl Versioned for AccountV2 {
+        const VERSION: u8 = 2;
+
+        fn migrate<D: Decoder>(
+            version: u8,
+            decoder: &mut D,
+        ) -> std::result::Result<Self, DecodeError> {
+            match version {
+                UNVERSIONED | 1 => {
+                    let AccountV1 { amount } = AccountV1::decode(decoder)?;
+                    Ok(AccountV2 { amount, nonce: 0 })
+                }
+                _ => Err(DecodeError::OtherString(format!(
+                    "unknown version {version}"
+                ))),
+            }
+        }
+    }
+
+    #[test]
+    fn versioned_value_is_tagged_with_version() {
+        let value = VersionedValue(AccountV2 {
+            amount: 10,
+            nonce: 1,
+        });
+
+        let encoded = BinEncodable::encode(&value).unwrap();
+        assert_eq!(encoded[..8], VERSION_MARKER);
+        assert_eq!(encoded[8], 2);
+        assert_eq!(&encoded[9..], BinEncodable::encode(&*value).unwrap());
+
+        let decoded: VersionedValue<AccountV2> = BinEncodable::decode(&encoded).unwrap();
+        assert_eq!(decoded, value);
+    }
+
+    #[test]
+    fn versioned_value_migrates_earlier_versions() {
+        let encoded =
+            BinEncodable::encode(&VersionedValue(AccountV1 { amount: 10 })).unwrap();
+
+        let decoded: VersionedValue<AccountV2> = BinEncodable::decode(&encoded).unwrap();
+        assert_eq!(
+            decoded.0,
+            AccountV2 {
+                amount: 10,
+                nonce: 0
+            }
+        );
+    }
+
+    #[test]
+    fn versioned_value_migrates_unversioned_values() {
+        let encoded = BinEncodable::encode(&AccountV1 { amount: 10 }).unwrap();
+
+        let decoded: VersionedValue<AccountV2> = BinEncodable::decode(&encoded).unwrap();
+        assert_eq!(
+            decoded.0,
+            AccountV2 {
+                amount: 10,
+                nonce: 0
+            }
+        );
+    }
+
+    #[test]
+    fn versioned_value_rejects_later_versions() {
+        let encoded = BinEncodable::encode(&VersionedValue(AccountV2 {
+            amount: 10,
+            nonce: 1,
+        }))
+        .unwrap();
+
+        let result = <VersionedValue<AccountV1> as BinEncodable>::decode(&encoded);
+        assert!(result.is_err_and(|e| e.to_string().contains("newer")));
+    }
+
+    #[test]
+    fn versioned_value_without_migration_fails() {
+        #[derive(Debug, Encode, Decode)]
+        struct Data(u64);
+
+        impl Versioned for Data {
+            const VERSION: u8 = 2;
+        }
+
+        let mut encoded = VERSION_MARKER.to_vec();
+        encoded.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let result = <VersionedValue<Data> as BinEncodable>::decode(&encoded);
+        assert!(result.is_err_and(|e| e.to_string().contains("no migration")));
+
+        let result =
+            <VersionedValue<Data> as BinEncodable>::decode(&[0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(result.is_err_and(|e| e.to_string().contains("no migration")));
+    }
+
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
     struct SerdeTestData {
         field1: String,
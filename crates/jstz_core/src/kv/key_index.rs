@@ -0,0 +1,237 @@
+//! Directory of the keys written by transactions.
+//!
+//! Durable storage can count the subkeys of a path but cannot list them, so
+//! the root commit of a transaction records the name of every child of every
+//! path it writes in a directory under [`KEY_INDEX_ROOT`]. A name stays in
+//! the directory of its parent while the child holds a value or has children
+//! of its own. Keys written directly to durable storage, or before the index
+//! existed, are not listed.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use tezos_smart_rollup_host::{
+    path::{OwnedPath, Path},
+    runtime::Runtime,
+};
+
+use super::{transaction::Key, Storage};
+use crate::error::Result;
+
+pub const KEY_INDEX_ROOT: &str = "/jstz_key_index";
+
+/// The names of the children of a path
+type Directory = BTreeSet<String>;
+
+fn segments(key: &Key) -> Vec<String> {
+    String::from_utf8_lossy(key.as_bytes())
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn key_path(segments: &[String]) -> Result<OwnedPath> {
+    Ok(OwnedPath::try_from(format!("/{}", segments.join("/")))?)
+}
+
+fn directory_path(segments: &[String]) -> Result<OwnedPath> {
+    let path = segments
+        .iter()
+        .fold(KEY_INDEX_ROOT.to_string(), |path, segment| {
+            path + "/" + segment
+        });
+    Ok(OwnedPath::try_from(path)?)
+}
+
+fn read_directory(rt: &impl Runtime, segments: &[String]) -> Result<Directory> {
+    Ok(Storage::get(rt, &directory_path(segments)?)?.unwrap_or_default())
+}
+
+fn write_directory(
+    rt: &mut impl Runtime,
+    segments: &[String],
+    directory: &Directory,
+) -> Result<()> {
+    let path = directory_path(segments)?;
+    if directory.is_empty() {
+        // Only the value is deleted, the directories of the children are kept
+        if Storage::contains_key(rt, &path)? {
+            rt.store_delete_value(&path)?;
+        }
+        Ok(())
+    } else {
+        Storage::insert(rt, &path, directory)
+    }
+}
+
+/// Records `keys` in the directories of their ancestors. The names are
+/// grouped by parent so that each directory is read and written once.
+pub(crate) fn link<'a>(
+    rt: &mut impl Runtime,
+    keys: impl IntoIterator<Item = &'a Key>,
+) -> Result<()> {
+    let mut children: BTreeMap<Vec<String>, Directory> = BTreeMap::new();
+    for key in keys {
+        let segments = segments(key);
+        for depth in (0..segments.len()).rev() {
+            let (parent, child) = (&segments[..depth], &segments[depth]);
+            if !children
+                .entry(parent.to_vec())
+                .or_default()
+                .insert(child.clone())
+            {
+                // The ancestors are already recorded
+                break;
+            }
+        }
+    }
+    for (parent, names) in children {
+        let mut directory = read_directory(rt, &parent)?;
+        let len = directory.len();
+        directory.extend(names);
+        if directory.len() != len {
+            write_directory(rt, &parent, &directory)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes `key` and its subtree from the index, once the value of `key` and
+/// its subtree were removed from durable storage, along with the ancestors that no longer hold a value or
+/// have children.
+pub(crate) fn unlink(rt: &mut impl Runtime, key: &Key) -> Result<()> {
+    let segments = segments(key);
+    let subtree = directory_path(&segments)?;
+    if rt.store_has(&subtree)?.is_some() {
+        rt.store_delete(&subtree)?;
+    }
+    for depth in (0..segments.len()).rev() {
+        let path = &segments[..=depth];
+        if Storage::contains_key(rt, &key_path(path)?)?
+            || !read_directory(rt, path)?.is_empty()
+        {
+            break;
+        }
+        let (parent, child) = (&segments[..depth], &segments[depth]);
+        let mut directory = read_directory(rt, parent)?;
+        directory.remove(child);
+        write_directory(rt, parent, &directory)?;
+    }
+    Ok(())
+}
+
+/// Returns the recorded keys under `prefix`, excluding `prefix` itself
+pub(crate) fn descendants(rt: &impl Runtime, prefix: &Key) -> Result<BTreeSet<Key>> {
+    let mut keys = BTreeSet::new();
+    let mut pending = vec![segments(prefix)];
+    while let Some(parent) = pending.pop() {
+        for child in read_directory(rt, &parent)? {
+            let mut path = parent.clone();
+            path.push(child);
+            let key = key_path(&path)?;
+            if Storage::contains_key(rt, &key)? {
+                keys.insert(key);
+            }
+            pending.push(path);
+        }
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use tezos_smart_rollup_host::path::OwnedPath;
+    use tezos_smart_rollup_mock::MockHost;
+
+    use super::{descendants, link, unlink};
+    use crate::kv::Storage;
+
+    fn key(path: &str) -> OwnedPath {
+        OwnedPath::try_from(path.to_string()).unwrap()
+    }
+
+    fn write(host: &mut MockHost, path: &str) {
+        Storage::insert(host, &key(path), &0u8).unwrap();
+        link(host, [&key(path)]).unwrap();
+    }
+
+    fn delete(host: &mut MockHost, path: &str) {
+        Storage::remove(host, &key(path)).unwrap();
+        unlink(host, &key(path)).unwrap();
+    }
+
+    fn keys(paths: &[&str]) -> BTreeSet<OwnedPath> {
+        paths.iter().map(|path| key(path)).collect()
+    }
+
+    #[test]
+    fn descendants_lists_nested_keys() {
+        let mut host = MockHost::default();
+        write(&mut host, "/a/b");
+        write(&mut host, "/a/b/c");
+        write(&mut host, "/a/d/e");
+        write(&mut host, "/x/y");
+
+        assert_eq!(
+            descendants(&host, &key("/a")).unwrap(),
+            keys(&["/a/b", "/a/b/c", "/a/d/e"])
+        );
+        assert_eq!(descendants(&host, &key("/a/b")).unwrap(), keys(&["/a/b/c"]));
+        assert!(descendants(&host, &key("/z")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn link_records_keys_in_one_pass() {
+        let mut host = MockHost::default();
+        let paths = ["/a/b", "/a/c", "/a/d/e", "/x/y"];
+        for path in paths {
+            Storage::insert(&mut host, &key(path), &0u8).unwrap();
+        }
+        link(&mut host, &paths.map(key)).unwrap();
+        write(&mut host, "/a/f");
+
+        assert_eq!(
+            descendants(&host, &key("/a")).unwrap(),
+            keys(&["/a/b", "/a/c", "/a/d/e", "/a/f"])
+        );
+        assert_eq!(descendants(&host, &key("/x")).unwrap(), keys(&["/x/y"]));
+    }
+
+    #[test]
+    fn unlink_keeps_ancestors_with_values_or_children() {
+        let mut host = MockHost::default();
+        write(&mut host, "/a/b");
+        write(&mut host, "/a/b/c");
+        write(&mut host, "/a/d/e");
+
+        delete(&mut host, "/a/b/c");
+        assert_eq!(
+            descendants(&host, &key("/a")).unwrap(),
+            keys(&["/a/b", "/a/d/e"])
+        );
+
+        delete(&mut host, "/a/d/e");
+        delete(&mut host, "/a/b");
+        assert!(descendants(&host, &key("/a")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn unlink_removes_subtree() {
+        let mut host = MockHost::default();
+        write(&mut host, "/a/b");
+        write(&mut host, "/a/b/c");
+        write(&mut host, "/a/b/d/e");
+        write(&mut host, "/a/f");
+
+        delete(&mut host, "/a/b");
+        assert_eq!(descendants(&host, &key("/a")).unwrap(), keys(&["/a/f"]));
+
+        write(&mut host, "/a/b/g");
+        assert_eq!(
+            descendants(&host, &key("/a")).unwrap(),
+            keys(&["/a/b/g", "/a/f"])
+        );
+    }
+}
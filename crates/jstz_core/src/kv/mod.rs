@@ -8,6 +8,7 @@ use tezos_smart_rollup_host::{path::Path, runtime::Runtime};
 
 use crate::error::Result;
 
+pub mod key_index;
 pub mod outbox;
 pub mod storage_update;
 pub mod transaction;
@@ -14,7 +14,10 @@ use tezos_smart_rollup::{
 };
 
 use tezos_data_encoding::{enc::BinWriter, encoding::HasEncoding, nom::NomReader};
-use tezos_smart_rollup_host::{path::RefPath, runtime::Runtime};
+use tezos_smart_rollup_host::{
+    path::{OwnedPath, RefPath},
+    runtime::Runtime,
+};
 
 use super::Storage;
 
@@ -23,34 +26,35 @@ const PERSISTENT_OUTBOX_QUEUE_ROOT: RefPath<'static> =
 
 const JSTZ_OUTBOX_QUEUE_META: RefPath<'static> = RefPath::assert_from(b"/outbox/meta");
 
+const LEVEL_OUTBOX_ROOT: RefPath<'static> = RefPath::assert_from(b"/outbox/level");
+
+const LEVEL_OUTBOX_LEN: RefPath<'static> = RefPath::assert_from(b"/outbox/level/len");
+
 type WithdrawalParameters = MichelsonPair<MichelsonContract, FA2_1Ticket>;
 type Withdrawal = OutboxMessageTransactionBatch<WithdrawalParameters>;
+pub type OutboxTransaction = OutboxMessageTransaction<WithdrawalParameters>;
 
 #[derive(Debug, HasEncoding, PartialEq)]
 pub enum OutboxMessage {
     Withdrawal(Withdrawal),
 }
 
-impl OutboxMessage {
-    pub fn new_withdrawal_message(
-        receiver: &Contract,
-        destination: &Contract,
-        ticket: FA2_1Ticket,
-        entrypoint: &str,
-    ) -> Result<OutboxMessage> {
-        let entrypoint = Entrypoint::try_from(entrypoint.to_string())
-            .map_err(|_| OutboxError::InvalidEntrypoint)?;
-        let parameters = MichelsonPair(MichelsonContract(receiver.clone()), ticket);
-        let message = OutboxMessage::Withdrawal(
-            vec![OutboxMessageTransaction {
-                entrypoint,
-                parameters,
-                destination: destination.clone(),
-            }]
-            .into(),
-        );
-        Ok(message)
-    }
+/// Creates a withdrawal transaction. Transactions are written to the outbox at
+/// the end of the level, batched with the other transactions of the level.
+pub fn new_withdrawal_transaction(
+    receiver: &Contract,
+    destination: &Contract,
+    ticket: FA2_1Ticket,
+    entrypoint: &str,
+) -> Result<OutboxTransaction> {
+    let entrypoint = Entrypoint::try_from(entrypoint.to_string())
+        .map_err(|_| OutboxError::InvalidEntrypoint)?;
+    let parameters = MichelsonPair(MichelsonContract(receiver.clone()), ticket);
+    Ok(OutboxMessageTransaction {
+        entrypoint,
+        parameters,
+        destination: destination.clone(),
+    })
 }
 
 impl AtomicBatch for OutboxMessage {}
@@ -80,15 +84,15 @@ impl From<OutboxMessage> for OutboxMessageFull<OutboxMessage> {
 /// Represents a pending outbox queue stored as part of the
 /// trasaction's snapshot.
 #[derive(Debug, Default)]
-pub(crate) struct SnapshotOutboxQueue(Vec<OutboxMessage>);
+pub(crate) struct SnapshotOutboxQueue(Vec<OutboxTransaction>);
 
 impl SnapshotOutboxQueue {
     pub fn extend(&mut self, queue: SnapshotOutboxQueue) {
         self.0.extend(queue.0)
     }
 
-    pub fn queue_message(&mut self, message: OutboxMessage) {
-        self.0.push(message)
+    pub fn queue_transaction(&mut self, transaction: OutboxTransaction) {
+        self.0.push(transaction)
     }
 }
 
@@ -244,6 +248,88 @@ impl OutboxQueueMeta {
     }
 }
 
+/// Returns the number of transactions queued during the current level
+pub(crate) fn level_len(rt: &impl Runtime) -> Result<u32> {
+    Ok(Storage::get::<u32>(rt, &LEVEL_OUTBOX_LEN)?.unwrap_or(0))
+}
+
+fn level_transaction_path(index: u32) -> Result<OwnedPath> {
+    Ok(OwnedPath::try_from(format!("/outbox/level/{index}"))?)
+}
+
+/// Appends the transactions of a committed snapshot to the transactions
+/// queued during the current level, which are kept in durable storage until
+/// the end of the level.
+pub(crate) fn stage(
+    rt: &mut impl Runtime,
+    snapshot_queue: SnapshotOutboxQueue,
+) -> Result<()> {
+    if snapshot_queue.0.is_empty() {
+        return Ok(());
+    }
+    let mut len = level_len(rt)?;
+    for transaction in snapshot_queue.0 {
+        let mut buffer = Vec::new();
+        transaction
+            .bin_write(&mut buffer)
+            .map_err(|_| OutboxError::OutboxMessageSerializationError)?;
+        rt.store_write_all(&level_transaction_path(len)?, &buffer)?;
+        len += 1;
+    }
+    Storage::insert(rt, &LEVEL_OUTBOX_LEN, &len)
+}
+
+/// Removes the transactions queued during the current level and groups them
+/// into batches that each fit in a single outbox message. Batches are atomic on
+/// L1, so a batch only holds transactions to the same destination: a failing
+/// destination cannot revert the withdrawals to other destinations. Destinations
+/// are batched in order of first withdrawal, and transactions in order.
+fn take_level_batches(rt: &mut impl Runtime) -> Result<Vec<OutboxMessage>> {
+    let len = level_len(rt)?;
+    if len == 0 {
+        return Ok(vec![]);
+    }
+
+    let mut empty_batch = Vec::new();
+    OutboxMessageFull::from(OutboxMessage::Withdrawal(vec![].into()))
+        .bin_write(&mut empty_batch)
+        .map_err(|_| OutboxError::OutboxMessageSerializationError)?;
+
+    let mut destinations: Vec<(Contract, Vec<(usize, OutboxTransaction)>)> = vec![];
+    for index in 0..len {
+        let bytes = rt.store_read_all(&level_transaction_path(index)?)?;
+        let (_, transaction) = OutboxTransaction::nom_read(&bytes)
+            .map_err(|_| OutboxError::OutboxMessageDeserializationError)?;
+        let entry = (bytes.len(), transaction);
+        match destinations
+            .iter_mut()
+            .find(|(destination, _)| *destination == entry.1.destination)
+        {
+            Some((_, transactions)) => transactions.push(entry),
+            None => destinations.push((entry.1.destination.clone(), vec![entry])),
+        }
+    }
+
+    let mut batches = vec![];
+    for (_, transactions) in destinations {
+        let mut batch = vec![];
+        let mut batch_size = empty_batch.len();
+        for (size, transaction) in transactions {
+            if !batch.is_empty() && batch_size + size > MAX_OUTPUT_SIZE {
+                batches
+                    .push(OutboxMessage::Withdrawal(std::mem::take(&mut batch).into()));
+                batch_size = empty_batch.len();
+            }
+            batch_size += size;
+            batch.push(transaction);
+        }
+        batches.push(OutboxMessage::Withdrawal(batch.into()));
+    }
+
+    rt.store_delete(&LEVEL_OUTBOX_ROOT)?;
+    Ok(batches)
+}
+
 /// Writes the outbox message directly to the Runtime outbox
 fn write_outbox_message(
     rt: &mut impl Runtime,
@@ -257,23 +343,24 @@ fn write_outbox_message(
     Ok(())
 }
 
-/// Flushes the outbox queue in the order of the rollup outbox queue
-/// then the outbox queue snapshot. The outbox has a maximum capacity
-/// of 100 messages per level. Messages that cannot be flushed in the
-/// current level are enqueued into the rollup outbox queue for the
-/// next flush.
+/// Flushes the rollup outbox queue, then the transactions queued during the
+/// current level, batched per destination into as few outbox messages as
+/// possible. Should be
+/// called once at the end of each level. The outbox has a maximum capacity
+/// of 100 messages per level. Batches that cannot be flushed in the current
+/// level are enqueued into the rollup outbox queue for the next flush.
 pub(crate) fn flush(
     rt: &mut impl Runtime,
     persistent_queue: &mut PersistentOutboxQueue,
-    snapshot_queue: SnapshotOutboxQueue,
 ) -> Result<()> {
     let mut flushed_count = 0;
+    let mut deferred_count = 0;
 
     // 1. Flush the existing outbox queue
     flushed_count += persistent_queue.flush(rt)?;
 
-    // 2. Flush the outbox queue snapshot if there is space in the outbox
-    let mut outbox_messages = snapshot_queue.0.into_iter();
+    // 2. Flush the batches of the level if there is space in the outbox
+    let mut outbox_messages = take_level_batches(rt)?.into_iter();
     for message in outbox_messages.by_ref() {
         let message = message.into();
         match write_outbox_message(rt, &message) {
@@ -292,6 +379,7 @@ pub(crate) fn flush(
                 persistent_queue
                     .queue_message(rt, message)
                     .expect("Unexpected error while queueing message");
+                deferred_count += 1;
                 break;
             }
             Err(e) => {
@@ -301,12 +389,14 @@ pub(crate) fn flush(
                 persistent_queue
                     .queue_message(rt, message)
                     .expect("Unexpected error while queueing message");
+                deferred_count += 1;
                 break;
             }
         }
     }
 
-    //  3. Enqueue the remaining messages into the outbox queue
+    //  3. Enqueue the remaining batches into the outbox queue
+    deferred_count += outbox_messages.len();
     persistent_queue.batch_queue_message(rt, outbox_messages)?;
     if flushed_count > 0 {
         debug_msg!(
@@ -315,6 +405,13 @@ pub(crate) fn flush(
             flushed_count
         );
     }
+    if deferred_count > 0 {
+        debug_msg!(
+            rt,
+            "Outbox full, deferred to the next level (deferred_count: {})\n",
+            deferred_count
+        );
+    }
 
     Ok(())
 }
@@ -326,6 +423,9 @@ pub enum OutboxError {
     /// Error while serializing an outbox message.
     /// This is unexpected and probably indicates a bug
     OutboxMessageSerializationError,
+    /// Error while deserializing an outbox transaction queued during the
+    /// level. This is unexpected and probably indicates a bug
+    OutboxMessageDeserializationError,
     OutboxQueueMetaNotFound,
     OutboxQueueMetaAlreadyExists,
     InvalidTicketType,
@@ -337,6 +437,7 @@ mod test {
     use jstz_crypto::{hash::Hash, public_key_hash::PublicKeyHash};
     use tezos_data_encoding::nom::NomReader;
     use tezos_smart_rollup::{
+        core_unsafe::MAX_OUTPUT_SIZE,
         michelson::{
             ticket::FA2_1Ticket, MichelsonContract, MichelsonNat, MichelsonOption,
             MichelsonPair,
@@ -347,13 +448,21 @@ mod test {
 
     use tezos_smart_rollup_mock::MockHost;
 
-    use crate::kv::outbox::{flush, write_outbox_message, PersistentOutboxQueue};
+    use crate::kv::outbox::{
+        flush, level_len, stage, write_outbox_message, PersistentOutboxQueue,
+    };
 
-    use super::{OutboxMessage, SnapshotOutboxQueue};
+    use super::{OutboxMessage, OutboxTransaction, SnapshotOutboxQueue};
 
-    fn make_withdrawal(account: &PublicKeyHash) -> OutboxMessage {
-        let creator =
-            Contract::from_b58check("KT1NgXQ6Mwu3XKFDcKdYFS6dkkY3iNKdBKEc").unwrap();
+    fn make_transaction(account: &PublicKeyHash) -> OutboxTransaction {
+        make_transaction_to(account, "KT1NgXQ6Mwu3XKFDcKdYFS6dkkY3iNKdBKEc")
+    }
+
+    fn make_transaction_to(
+        account: &PublicKeyHash,
+        destination: &str,
+    ) -> OutboxTransaction {
+        let creator = Contract::from_b58check(destination).unwrap();
         let parameters = MichelsonPair(
             MichelsonContract(Contract::try_from(account.to_base58()).unwrap()),
             FA2_1Ticket::new(
@@ -363,20 +472,26 @@ mod test {
             )
             .unwrap(),
         );
-        let outbox_tx = OutboxMessageTransaction {
+        OutboxMessageTransaction {
             parameters,
             destination: creator,
             entrypoint: Entrypoint::try_from("burn".to_string()).unwrap(),
-        };
-        OutboxMessage::Withdrawal(vec![outbox_tx].into())
+        }
+    }
+
+    fn make_withdrawal(account: &PublicKeyHash) -> OutboxMessage {
+        OutboxMessage::Withdrawal(vec![make_transaction(account)].into())
+    }
+
+    fn account(i: usize) -> PublicKeyHash {
+        PublicKeyHash::digest(format!("account{i}").as_bytes()).unwrap()
     }
 
     #[test]
     fn flush_empty_outbox_queue_noop() {
         let mut host = MockHost::default();
-        let outbox_queue_snapshot = SnapshotOutboxQueue(vec![]);
         let mut persistent_queue = PersistentOutboxQueue::default();
-        flush(&mut host, &mut persistent_queue, outbox_queue_snapshot).unwrap();
+        flush(&mut host, &mut persistent_queue).unwrap();
 
         let level = host.run_level(|_| {});
         let outbox = host.outbox_at(level);
@@ -385,7 +500,7 @@ mod test {
     }
 
     #[test]
-    fn flush_empty_snapshot_flushes_rollup_queue() {
+    fn flush_empty_level_flushes_rollup_queue() {
         let mut host = MockHost::default();
 
         let accounts = [
@@ -403,8 +518,7 @@ mod test {
             .batch_queue_message(&mut host, withdrawals.into_iter())
             .unwrap();
 
-        let outbox_queue_snapshot = SnapshotOutboxQueue(vec![]);
-        flush(&mut host, &mut persistent_queue, outbox_queue_snapshot).unwrap();
+        flush(&mut host, &mut persistent_queue).unwrap();
 
         let level = host.run_level(|_| {});
         let outbox = host.outbox_at(level);
@@ -420,68 +534,192 @@ mod test {
     }
 
     #[test]
-    fn flush_rollup_queue_first_then_snapshot_queue() {
+    fn stage_defers_transactions_to_flush() {
+        let mut host = MockHost::default();
+        stage(
+            &mut host,
+            SnapshotOutboxQueue(vec![make_transaction(&account(0))]),
+        )
+        .unwrap();
+        stage(
+            &mut host,
+            SnapshotOutboxQueue(vec![make_transaction(&account(1))]),
+        )
+        .unwrap();
+
+        assert_eq!(2, level_len(&host).unwrap());
+        let level = host.run_level(|_| {});
+        assert_eq!(0, host.outbox_at(level).len());
+    }
+
+    #[test]
+    fn flush_rollup_queue_first_then_level_batch() {
         let mut host = MockHost::default();
         let mut persistent_queue = PersistentOutboxQueue::default();
-        let accounts = [
-            PublicKeyHash::digest(b"account1").unwrap(),
-            PublicKeyHash::digest(b"account2").unwrap(),
-            PublicKeyHash::digest(b"account3").unwrap(),
-            PublicKeyHash::digest(b"account4").unwrap(),
-        ];
+        let accounts: Vec<PublicKeyHash> = (0..4).map(account).collect();
 
         for account in accounts.iter().take(2) {
             persistent_queue
                 .queue_message(&mut host, make_withdrawal(account).into())
                 .unwrap();
         }
+        stage(
+            &mut host,
+            SnapshotOutboxQueue(accounts[2..].iter().map(make_transaction).collect()),
+        )
+        .unwrap();
 
-        let outbox_queue_snapshot =
-            SnapshotOutboxQueue(accounts[2..].iter().map(make_withdrawal).collect());
-
-        flush(&mut host, &mut persistent_queue, outbox_queue_snapshot).unwrap();
+        flush(&mut host, &mut persistent_queue).unwrap();
 
         assert_eq!(0, persistent_queue.len(&mut host).unwrap());
+        assert_eq!(0, level_len(&host).unwrap());
 
         let level = host.run_level(|_| {});
         let outbox = host.outbox_at(level);
 
-        assert_eq!(4, outbox.len());
+        assert_eq!(3, outbox.len());
 
-        for (i, message) in outbox.iter().enumerate() {
+        for (i, message) in outbox.iter().take(2).enumerate() {
             let (_, message) =
                 OutboxMessageFull::<OutboxMessage>::nom_read(message).unwrap();
             assert_eq!(message, make_withdrawal(&accounts[i]).into());
         }
+        let (_, message) =
+            OutboxMessageFull::<OutboxMessage>::nom_read(&outbox[2]).unwrap();
+        assert_eq!(
+            message,
+            OutboxMessage::Withdrawal(
+                accounts[2..]
+                    .iter()
+                    .map(make_transaction)
+                    .collect::<Vec<_>>()
+                    .into()
+            )
+            .into()
+        );
     }
 
     #[test]
-    fn flush_enqueues_remaining_messages_to_rollup_queue() {
+    fn flush_splits_level_into_batches_that_fit_in_outbox_messages() {
         let mut host = MockHost::default();
         let mut persistent_queue = PersistentOutboxQueue::default();
-        let mut messages: Vec<OutboxMessage> = vec![];
-        for i in 0..120 {
-            let account =
-                PublicKeyHash::digest(format!("account{i}").as_bytes()).unwrap();
-            messages.push(make_withdrawal(&account))
+        stage(
+            &mut host,
+            SnapshotOutboxQueue(
+                (0..120).map(|i| make_transaction(&account(i))).collect(),
+            ),
+        )
+        .unwrap();
+
+        flush(&mut host, &mut persistent_queue).unwrap();
+
+        let level = host.run_level(|_| {});
+        let outbox = host.outbox_at(level);
+
+        assert!(outbox.len() > 1);
+        assert!(outbox.len() < 120);
+        let mut flushed = 0;
+        for message in outbox.iter() {
+            assert!(message.len() <= MAX_OUTPUT_SIZE);
+            let (_, message) =
+                OutboxMessageFull::<OutboxMessage>::nom_read(message).unwrap();
+            let OutboxMessageFull::AtomicTransactionBatch(OutboxMessage::Withdrawal(
+                batch,
+            )) = &message
+            else {
+                panic!("Expected a withdrawal batch");
+            };
+            let len = batch.len();
+            let expected: Vec<OutboxTransaction> = (flushed..flushed + len)
+                .map(|i| make_transaction(&account(i)))
+                .collect();
+            assert_eq!(message, OutboxMessage::Withdrawal(expected.into()).into());
+            flushed += len;
         }
-        let mut messages = messages.into_iter();
+        assert_eq!(120, flushed);
+    }
 
-        for message in messages.by_ref().take(60) {
-            persistent_queue
-                .queue_message(&mut host, message.into())
-                .unwrap();
+    #[test]
+    fn flush_batches_level_per_destination() {
+        let mut host = MockHost::default();
+        let mut persistent_queue = PersistentOutboxQueue::default();
+        let first = "KT1NgXQ6Mwu3XKFDcKdYFS6dkkY3iNKdBKEc";
+        let second = "KT18mgybN9E97hF9HG9cDfSz6ofT7w9WTzMH";
+        stage(
+            &mut host,
+            SnapshotOutboxQueue(vec![
+                make_transaction_to(&account(0), first),
+                make_transaction_to(&account(1), second),
+                make_transaction_to(&account(2), first),
+            ]),
+        )
+        .unwrap();
+
+        flush(&mut host, &mut persistent_queue).unwrap();
+
+        let level = host.run_level(|_| {});
+        let outbox = host.outbox_at(level);
+
+        assert_eq!(2, outbox.len());
+        let expected = [
+            vec![
+                make_transaction_to(&account(0), first),
+                make_transaction_to(&account(2), first),
+            ],
+            vec![make_transaction_to(&account(1), second)],
+        ];
+        for (message, expected) in outbox.iter().zip(expected) {
+            let (_, message) =
+                OutboxMessageFull::<OutboxMessage>::nom_read(message).unwrap();
+            assert_eq!(message, OutboxMessage::Withdrawal(expected.into()).into());
         }
+    }
 
-        let outbox_queue_snapshot = SnapshotOutboxQueue(messages.take(60).collect());
+    #[test]
+    fn flush_enqueues_remaining_batches_to_rollup_queue() {
+        let mut host = MockHost::default();
+        let mut persistent_queue = PersistentOutboxQueue::default();
+        let messages: Vec<OutboxMessage> =
+            (0..100).map(|i| make_withdrawal(&account(i))).collect();
+        persistent_queue
+            .batch_queue_message(&mut host, messages.into_iter())
+            .unwrap();
+        stage(
+            &mut host,
+            SnapshotOutboxQueue(
+                (100..102).map(|i| make_transaction(&account(i))).collect(),
+            ),
+        )
+        .unwrap();
 
-        flush(&mut host, &mut persistent_queue, outbox_queue_snapshot).unwrap();
+        flush(&mut host, &mut persistent_queue).unwrap();
 
         let level = host.run_level(|_| {});
         let outbox = host.outbox_at(level);
 
-        assert_eq!(20, persistent_queue.len(&mut host).unwrap());
         assert_eq!(100, outbox.len());
+        assert_eq!(1, persistent_queue.len(&mut host).unwrap());
+        assert_eq!(0, level_len(&host).unwrap());
+
+        // The batch overflows into the next level
+        flush(&mut host, &mut persistent_queue).unwrap();
+
+        let level = host.run_level(|_| {});
+        let outbox = host.outbox_at(level);
+
+        assert_eq!(1, outbox.len());
+        let (_, message) =
+            OutboxMessageFull::<OutboxMessage>::nom_read(&outbox[0]).unwrap();
+        assert_eq!(
+            message,
+            OutboxMessage::Withdrawal(
+                (100..102)
+                    .map(|i| make_transaction(&account(i)))
+                    .collect::<Vec<_>>()
+                    .into()
+            )
+            .into()
+        );
     }
 
     #[test]
@@ -530,14 +768,14 @@ mod test {
         let acc1 = PublicKeyHash::digest(b"account1").unwrap();
         let acc2 = PublicKeyHash::digest(b"account2").unwrap();
         let mut outbox_queue_snapshot1 =
-            SnapshotOutboxQueue(vec![make_withdrawal(&acc1)]);
-        let outbox_queue_snapshot2 = SnapshotOutboxQueue(vec![make_withdrawal(&acc2)]);
+            SnapshotOutboxQueue(vec![make_transaction(&acc1)]);
+        let outbox_queue_snapshot2 = SnapshotOutboxQueue(vec![make_transaction(&acc2)]);
 
         outbox_queue_snapshot1.extend(outbox_queue_snapshot2);
 
         assert_eq!(2, outbox_queue_snapshot1.0.len());
         assert_eq!(
-            vec![make_withdrawal(&acc1), make_withdrawal(&acc2)],
+            vec![make_transaction(&acc1), make_transaction(&acc2)],
             outbox_queue_snapshot1.0
         );
     }
@@ -23,6 +23,9 @@ pub enum StorageUpdate {
     },
     /// Remove the value at the given key.
     Remove { key: String },
+    /// Marks the start of an L1 level. The updates that follow were made while
+    /// processing the inbox of this level.
+    StartOfLevel { level: u32 },
 }
 
 /// Storage update event.
@@ -59,6 +62,11 @@ impl BatchStorageUpdate {
         Ok(batch)
     }
 
+    /// Create a BatchStorageUpdate marking the start of L1 level `level`.
+    pub fn start_of_level(level: u32) -> Self {
+        Self(vec![StorageUpdate::StartOfLevel { level }])
+    }
+
     pub fn push_remove<K: Path>(&mut self, key: &K) {
         self.0.push(StorageUpdate::Remove {
             key: key.to_string(),
@@ -177,4 +185,11 @@ mod tests {
             _ => panic!("Expected Insert variant"),
         }
     }
+
+    #[test]
+    fn test_start_of_level() {
+        let batch = BatchStorageUpdate::start_of_level(42);
+        assert_eq!(batch.0, vec![StorageUpdate::StartOfLevel { level: 42 }]);
+        assert!(!batch.is_empty());
+    }
 }
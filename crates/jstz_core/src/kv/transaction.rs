@@ -10,11 +10,16 @@ use parking_lot::{ArcMutexGuard, Mutex, RawMutex};
 
 use derive_more::{Deref, DerefMut};
 use tezos_smart_rollup::prelude::debug_msg;
-use tezos_smart_rollup_host::{path::OwnedPath, runtime::Runtime};
+use tezos_smart_rollup_host::{
+    path::{OwnedPath, Path},
+    runtime::Runtime,
+};
 
 use super::{
+    key_index,
     outbox::{
-        flush, OutboxError, OutboxMessage, PersistentOutboxQueue, SnapshotOutboxQueue,
+        flush, level_len, stage, OutboxError, OutboxTransaction, PersistentOutboxQueue,
+        SnapshotOutboxQueue,
     },
     value::{BoxedValue, Value},
     Storage,
@@ -301,18 +306,48 @@ impl InnerTransaction {
         Storage::contains_key(rt, key)
     }
 
-    fn queue_outbox_message(
+    /// Returns the keys under `prefix`, excluding `prefix` itself, as seen by
+    /// the current snapshot.
+    fn scan(&self, rt: &impl Runtime, prefix: &Key) -> Result<BTreeSet<Key>> {
+        let mut keys = key_index::descendants(rt, prefix)?;
+
+        // Keys edited by the transaction take the state of their latest edit
+        let is_descendant = |key: &Key| {
+            key.as_bytes()
+                .strip_prefix(prefix.as_bytes())
+                .is_some_and(|rest| rest.starts_with(b"/"))
+        };
+        for (key, history) in self
+            .lookup_map
+            .range(prefix.clone()..)
+            .take_while(|(key, _)| key.as_bytes().starts_with(prefix.as_bytes()))
+            .filter(|(key, _)| is_descendant(key))
+        {
+            let Some(&snapshot_idx) = history.last() else {
+                continue;
+            };
+            if self.stack[snapshot_idx].contains_key(key) {
+                keys.insert(key.clone());
+            } else {
+                keys.remove(key);
+            }
+        }
+
+        Ok(keys)
+    }
+
+    fn queue_outbox_transaction(
         &mut self,
         rt: &mut impl Runtime,
-        message: OutboxMessage,
+        transaction: OutboxTransaction,
     ) -> Result<()> {
-        if self.persistent_outbox.len(rt)? + self.snapshot_outbox_len + 1
+        if self.persistent_outbox.len(rt)? + level_len(rt)? + self.snapshot_outbox_len + 1
             > self.persistent_outbox.max(rt)?
         {
             Err(OutboxError::OutboxQueueFull)?;
         }
         let current_outbox_queue = self.current_snapshot()?.outbox_queue_mut();
-        current_outbox_queue.queue_message(message);
+        current_outbox_queue.queue_transaction(transaction);
         self.snapshot_outbox_len += 1;
         Ok(())
     }
@@ -362,21 +397,29 @@ impl InnerTransaction {
             // TODO: Ensure atomicity
             // https://github.com/jstz-dev/jstz/pull/1319#discussion_r2339917375
             for key in &curr_ctxt.remove_edits {
-                Storage::remove(rt, key)?;
+                if Storage::contains_key(rt, key)? {
+                    Storage::remove(rt, key)?;
+                    key_index::unlink(rt, key)?;
+                }
                 storage_updates.push_remove(key);
             }
 
+            let mut new_keys = Vec::new();
             for (key, value) in curr_ctxt.insert_edits {
                 let value = value.0.as_ref();
+                if !Storage::contains_key(rt, &key)? {
+                    new_keys.push(key.clone());
+                }
                 Storage::insert(rt, &key, value)?;
                 storage_updates.push_insert(&key, value)?;
             }
+            key_index::link(rt, &new_keys)?;
 
             if let Err(e) = storage_updates.publish_event(rt) {
                 debug_msg!(rt, "Failed to publish storage update events: {e}");
             }
 
-            flush(rt, &mut self.persistent_outbox, curr_ctxt.outbox_queue)?;
+            stage(rt, curr_ctxt.outbox_queue)?;
             self.snapshot_outbox_len = 0;
 
             // Update lookup map
@@ -479,6 +522,17 @@ impl Transaction {
         inner.contains_key(rt, key)
     }
 
+    /// Returns the keys under `prefix`, excluding `prefix` itself, in
+    /// ascending order. The keys reflect the uncommitted edits of the
+    /// transaction and its parents, so the scan is consistent with the
+    /// values returned by [`Transaction::get`]. Use [`BTreeSet::range`] to
+    /// iterate over a range of the keys.
+    pub fn scan(&self, rt: &impl Runtime, prefix: &Key) -> Result<BTreeSet<Key>> {
+        let rc = self.acquire_guard()?;
+        let inner = rc.borrow();
+        inner.scan(rt, prefix)
+    }
+
     pub fn insert<V: Value>(&self, key: Key, value: V) -> Result<()> {
         let rc = self.acquire_guard()?;
         let mut inner = rc.borrow_mut();
@@ -531,15 +585,27 @@ impl Transaction {
         }
     }
 
-    pub fn queue_outbox_message(
+    /// Queues an outbox transaction. Transactions are written to the outbox
+    /// by [`Transaction::flush_outbox`] once they are committed.
+    pub fn queue_outbox_transaction(
         &self,
         rt: &mut impl Runtime,
-        message: OutboxMessage,
+        transaction: OutboxTransaction,
     ) -> Result<()> {
         let rc = self.acquire_guard()?;
         let mut inner = rc.borrow_mut();
         inner.set_dirty(true);
-        inner.queue_outbox_message(rt, message)
+        inner.queue_outbox_transaction(rt, transaction)
+    }
+
+    /// Writes the outbox transactions committed during the level to the
+    /// outbox, in batches. Should be called once at the end of each level.
+    /// Batches that do not fit in the outbox of the level are written at the
+    /// next flush.
+    pub fn flush_outbox(&self, rt: &mut impl Runtime) -> Result<()> {
+        let rc = self.acquire_guard()?;
+        let mut inner = rc.borrow_mut();
+        flush(rt, &mut inner.persistent_outbox)
     }
 
     pub fn get_dirty(&self) -> bool {
@@ -816,13 +882,13 @@ mod test {
     use tezos_smart_rollup_mock::MockHost;
 
     use crate::kv::{
-        outbox::{OutboxMessage, PersistentOutboxQueue},
+        outbox::{OutboxMessage, OutboxTransaction, PersistentOutboxQueue},
         Storage,
     };
 
     use super::{GuardedMut, Transaction};
 
-    fn make_withdrawal(account: &PublicKeyHash) -> OutboxMessage {
+    fn make_withdrawal(account: &PublicKeyHash) -> OutboxTransaction {
         let creator =
             Contract::from_b58check("KT1NgXQ6Mwu3XKFDcKdYFS6dkkY3iNKdBKEc").unwrap();
         let parameters = MichelsonPair(
@@ -834,12 +900,11 @@ mod test {
             )
             .unwrap(),
         );
-        let outbox_tx = OutboxMessageTransaction {
+        OutboxMessageTransaction {
             parameters,
             destination: creator,
             entrypoint: Entrypoint::try_from("burn".to_string()).unwrap(),
-        };
-        OutboxMessage::Withdrawal(vec![outbox_tx].into())
+        }
     }
 
     #[test]
@@ -939,7 +1004,7 @@ mod test {
             }
             let acc = PublicKeyHash::digest(format!("account{i}").as_bytes()).unwrap();
             let message = make_withdrawal(&acc);
-            tx.queue_outbox_message(&mut host, message).unwrap();
+            tx.queue_outbox_transaction(&mut host, message).unwrap();
         }
 
         assert_eq!(
@@ -950,7 +1015,7 @@ mod test {
         // Adding an additional message to a full outbox queue without
         // flushing should fail
         let error = tx
-            .queue_outbox_message(
+            .queue_outbox_transaction(
                 &mut host,
                 make_withdrawal(
                     &PublicKeyHash::digest("failure account".to_string().as_bytes())
@@ -980,7 +1045,7 @@ mod test {
             }
             let acc = PublicKeyHash::digest(format!("account{i}").as_bytes()).unwrap();
             let message = make_withdrawal(&acc);
-            tx.queue_outbox_message(&mut host, message).unwrap();
+            tx.queue_outbox_transaction(&mut host, message).unwrap();
         }
 
         tx.commit(&mut host).unwrap();
@@ -997,7 +1062,6 @@ mod test {
     }
 
     #[test]
-    #[ignore]
     fn final_commit_resets_snapshot_queue_len() {
         let mut host = MockHost::default();
         let tx = Transaction::default();
@@ -1010,7 +1074,7 @@ mod test {
             }
             let acc = PublicKeyHash::digest(format!("account{i}").as_bytes()).unwrap();
             let message = make_withdrawal(&acc);
-            tx.queue_outbox_message(&mut host, message).unwrap();
+            tx.queue_outbox_transaction(&mut host, message).unwrap();
         }
 
         tx.commit(&mut host).unwrap();
@@ -1020,21 +1084,48 @@ mod test {
             tx.acquire_guard().unwrap().borrow_mut().snapshot_outbox_len
         );
 
+        // Committed transactions still count towards the capacity of the
+        // outbox queue until they are flushed
+        tx.begin();
+        let acc = PublicKeyHash::digest(b"failure account").unwrap();
+        tx.queue_outbox_transaction(&mut host, make_withdrawal(&acc))
+            .expect_err("Outbox should be full");
+        tx.rollback().unwrap();
+
+        tx.flush_outbox(&mut host).unwrap();
+        tx.begin();
+        tx.queue_outbox_transaction(&mut host, make_withdrawal(&acc))
+            .unwrap();
+    }
+
+    #[test]
+    fn final_commit_defers_outbox_transactions_to_flush() {
+        let mut host = MockHost::default();
+        let tx = Transaction::default();
+
+        tx.begin();
+        let acc = PublicKeyHash::digest(b"account").unwrap();
+        tx.queue_outbox_transaction(&mut host, make_withdrawal(&acc))
+            .unwrap();
+        tx.commit(&mut host).unwrap();
+
         let level = host.run_level(|_| {});
-        let outbox = host.outbox_at(level);
+        assert_eq!(0, host.outbox_at(level).len());
 
-        assert_eq!(100, outbox.len());
+        tx.flush_outbox(&mut host).unwrap();
+        let level = host.run_level(|_| {});
+        assert_eq!(1, host.outbox_at(level).len());
     }
 
     #[test]
-    fn final_commit_flush_outbox_messages_in_enqueue_order() {
+    fn flush_outbox_writes_transactions_in_enqueue_order() {
         let mut host = MockHost::default();
         let tx = Transaction::default();
 
         tx.acquire_guard().unwrap().borrow_mut().persistent_outbox =
             PersistentOutboxQueue::try_new(&mut host, 120).unwrap();
 
-        // Enqueue 120 messages, 60 per snapshot
+        // Enqueue 120 transactions, 60 per snapshot
         for i in 0..120 {
             if i % 60 == 0 {
                 tx.begin();
@@ -1042,73 +1133,45 @@ mod test {
 
             let acc = PublicKeyHash::digest(format!("account{i}").as_bytes()).unwrap();
             let message = make_withdrawal(&acc);
-            tx.queue_outbox_message(&mut host, message).unwrap();
+            tx.queue_outbox_transaction(&mut host, message).unwrap();
         }
 
         // Commit both snapshots
         tx.commit(&mut host).unwrap();
         tx.commit(&mut host).unwrap();
+        tx.flush_outbox(&mut host).unwrap();
 
         let level = host.run_level(|_| {});
         let outbox = host.outbox_at(level);
 
-        // Maximum number of outbox messages per level is 100.
-        // The remaining 20 messages are left in the persistent queue.
-        assert_eq!(100, outbox.len());
-        assert_eq!(
-            20,
-            tx.acquire_guard()
-                .unwrap()
-                .borrow_mut()
-                .persistent_outbox
-                .len(&mut host)
-                .unwrap()
-        );
-
-        for (i, outbox_message) in outbox.iter().enumerate() {
-            let (_, message) =
-                OutboxMessageFull::<OutboxMessage>::nom_read(outbox_message.as_slice())
-                    .unwrap();
-
-            assert_eq!(
-                message,
-                make_withdrawal(
-                    &PublicKeyHash::digest(format!("account{i}").as_bytes()).unwrap()
-                )
-                .into()
-            );
-        }
-
-        tx.begin();
-        tx.commit(&mut host).unwrap();
-
-        let level = host.run_level(|_| {});
-        let outbox = host.outbox_at(level);
+        // Transactions are batched, so there are fewer outbox messages
+        // than transactions
+        assert!(outbox.len() < 120);
 
-        assert_eq!(20, outbox.len());
-        assert_eq!(
-            0,
-            tx.acquire_guard()
-                .unwrap()
-                .borrow_mut()
-                .persistent_outbox
-                .len(&mut host)
-                .unwrap()
-        );
-        for (i, outbox_message) in outbox.iter().enumerate().take(20) {
+        let mut flushed = 0;
+        for outbox_message in outbox.iter() {
             let (_, message) =
                 OutboxMessageFull::<OutboxMessage>::nom_read(outbox_message.as_slice())
                     .unwrap();
-
-            assert_eq!(
-                message,
-                make_withdrawal(
-                    &PublicKeyHash::digest(format!("account{}", 100 + i).as_bytes())
-                        .unwrap()
-                )
-                .into()
-            );
+            let OutboxMessageFull::AtomicTransactionBatch(OutboxMessage::Withdrawal(
+                batch,
+            )) = &message
+            else {
+                panic!("Expected a withdrawal batch");
+            };
+            let len = batch.len();
+
+            let expected: Vec<OutboxTransaction> = (flushed..flushed + len)
+                .map(|i| {
+                    make_withdrawal(
+                        &PublicKeyHash::digest(format!("account{i}").as_bytes()).unwrap(),
+                    )
+                })
+                .collect();
+            assert_eq!(message, OutboxMessage::Withdrawal(expected.into()).into());
+            flushed += len;
         }
+        assert_eq!(120, flushed);
     }
 
     #[test]
@@ -1286,6 +1349,68 @@ mod tests {
         assert!(sink.lines().first().unwrap().is_empty());
     }
 
+    fn key(path: &str) -> OwnedPath {
+        OwnedPath::try_from(path.to_string()).unwrap()
+    }
+
+    #[test]
+    fn scan_merges_committed_and_uncommitted_keys() {
+        let mut hrt = MockHost::default();
+        let tx = Transaction::default();
+        tx.begin();
+        tx.insert(key("/kv/b"), 1).unwrap();
+        tx.insert(key("/kv/d/e"), 2).unwrap();
+        tx.insert(key("/kv-other"), 3).unwrap();
+        tx.commit(&mut hrt).unwrap();
+
+        tx.begin();
+        tx.insert(key("/kv/a"), 4).unwrap();
+        tx.begin();
+        tx.remove(key("/kv/b")).unwrap();
+        tx.insert(key("/kv/c"), 5).unwrap();
+
+        assert_eq!(
+            tx.scan(&hrt, &key("/kv"))
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![key("/kv/a"), key("/kv/c"), key("/kv/d/e")]
+        );
+
+        tx.rollback().unwrap();
+        assert_eq!(
+            tx.scan(&hrt, &key("/kv"))
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![key("/kv/a"), key("/kv/b"), key("/kv/d/e")]
+        );
+    }
+
+    #[test]
+    fn scan_reflects_committed_removals() {
+        let mut hrt = MockHost::default();
+        let tx = Transaction::default();
+        tx.begin();
+        tx.insert(key("/kv/a"), 1).unwrap();
+        tx.insert(key("/kv/b"), 2).unwrap();
+        tx.commit(&mut hrt).unwrap();
+
+        tx.begin();
+        tx.remove(key("/kv/a")).unwrap();
+        tx.commit(&mut hrt).unwrap();
+
+        let tx = Transaction::default();
+        tx.begin();
+        assert_eq!(
+            tx.scan(&hrt, &key("/kv"))
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![key("/kv/b")]
+        );
+    }
+
     #[cfg(feature = "simulation")]
     #[test]
     fn storage_commit_skipped_if_simulation() {
@@ -1311,4 +1436,103 @@ mod tests {
         let tx = Transaction::default();
         assert!(tx.get::<u8>(&mut hrt, path).unwrap().is_none());
     }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Begin,
+        Commit,
+        Rollback,
+        Insert(u8, i32),
+        Remove(u8),
+        Get(u8),
+    }
+
+    fn op() -> impl proptest::strategy::Strategy<Value = Op> {
+        use proptest::prelude::*;
+        prop_oneof![
+            Just(Op::Begin),
+            Just(Op::Commit),
+            Just(Op::Rollback),
+            (0..4u8, any::<i32>()).prop_map(|(k, v)| Op::Insert(k, v)),
+            (0..4u8).prop_map(Op::Remove),
+            (0..4u8).prop_map(Op::Get),
+        ]
+    }
+
+    fn model_lookup(
+        storage: &BTreeMap<u8, i32>,
+        layers: &[BTreeMap<u8, Option<i32>>],
+        k: u8,
+    ) -> Option<i32> {
+        layers
+            .iter()
+            .rev()
+            .find_map(|layer| layer.get(&k).copied())
+            .unwrap_or_else(|| storage.get(&k).copied())
+    }
+
+    proptest::proptest! {
+        // Compares nested transactions, read through the cached lock guard,
+        // with a stack of maps.
+        #[test]
+        fn transaction_matches_model(ops in proptest::collection::vec(op(), 1..64)) {
+            let mut hrt = MockHost::default();
+            let tx = Transaction::default();
+            let mut storage = BTreeMap::new();
+            let mut layers: Vec<BTreeMap<u8, Option<i32>>> = vec![];
+            let key = |k: u8| OwnedPath::try_from(format!("/k{k}")).unwrap();
+
+            for op in ops {
+                match op {
+                    Op::Begin => {
+                        tx.begin();
+                        layers.push(BTreeMap::new());
+                    }
+                    Op::Commit if !layers.is_empty() => {
+                        tx.commit(&mut hrt).unwrap();
+                        let layer = layers.pop().unwrap();
+                        match layers.last_mut() {
+                            Some(parent) => parent.extend(layer),
+                            None => {
+                                for (k, v) in layer {
+                                    match v {
+                                        Some(v) => storage.insert(k, v),
+                                        None => storage.remove(&k),
+                                    };
+                                }
+                            }
+                        }
+                    }
+                    Op::Rollback if !layers.is_empty() => {
+                        tx.rollback().unwrap();
+                        layers.pop();
+                    }
+                    Op::Insert(k, v) if !layers.is_empty() => {
+                        tx.insert(key(k), v).unwrap();
+                        layers.last_mut().unwrap().insert(k, Some(v));
+                    }
+                    Op::Remove(k) if !layers.is_empty() => {
+                        tx.remove(key(k)).unwrap();
+                        layers.last_mut().unwrap().insert(k, None);
+                    }
+                    Op::Get(k) if !layers.is_empty() => {
+                        let expected = model_lookup(&storage, &layers, k);
+                        let first = tx.get::<i32>(&hrt, key(k)).unwrap();
+                        // A second reference reuses the guard held by the first
+                        let second = tx.get::<i32>(&hrt, key(k)).unwrap();
+                        proptest::prop_assert_eq!(first.as_deref().copied(), expected);
+                        proptest::prop_assert_eq!(second.as_deref().copied(), expected);
+                    }
+                    _ => (),
+                }
+            }
+
+            for k in 0..4u8 {
+                proptest::prop_assert_eq!(
+                    Storage::get::<i32>(&hrt, &key(k)).unwrap(),
+                    storage.get(&k).copied()
+                );
+            }
+        }
+    }
 }
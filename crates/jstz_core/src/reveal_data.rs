@@ -1,9 +1,16 @@
 use crate::error::Result;
-use crate::{host::HostRuntime, BinEncodable};
+use crate::{bin_encodable::decode_revealed, host::HostRuntime, BinEncodable};
+use bincode::Decode;
 use derive_more::From;
+use ruzstd::{
+    decoding::StreamingDecoder,
+    encoding::{compress_to_vec, CompressionLevel},
+};
 use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
 use std::fmt;
+use std::io::Read;
+use tezos_smart_rollup::storage::path::Path;
 use tezos_smart_rollup::{
     core_unsafe::PREIMAGE_HASH_SIZE,
     dac::{
@@ -19,6 +26,12 @@ const MAX_DAC_LEVELS: usize = 3;
 const MAX_REVEAL_BUFFER_SIZE: usize = MAX_PAGE_SIZE * (MAX_DAC_LEVELS + 1);
 /// maximum size of the reveal data in bytes (10MB)
 pub const MAX_REVEAL_SIZE: usize = 10 * 1024 * 1024;
+/// maximum size of compressed reveal data once decompressed in bytes (40MB)
+pub const MAX_DECOMPRESSED_REVEAL_SIZE: usize = 4 * MAX_REVEAL_SIZE;
+/// Tag prefixed to reveal data compressed with zstd. Encoded values do not
+/// start with it: read as a little-endian enum variant index or length, the
+/// tag is above any valid one.
+const ZSTD_TAG: &[u8] = b"\xffzstd";
 
 /// A 33-byte hash corresponding to a preimage
 type RawPreimageHash = [u8; PREIMAGE_HASH_SIZE];
@@ -62,6 +75,26 @@ impl fmt::Display for PreimageHash {
     }
 }
 
+/// Encoding of the content of reveal data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContentEncoding {
+    /// The encoded value
+    #[default]
+    Identity,
+    /// The encoded value compressed with zstd, prefixed by a tag
+    Zstd,
+}
+
+impl ContentEncoding {
+    fn of(content: &[u8]) -> Self {
+        if content.starts_with(ZSTD_TAG) {
+            ContentEncoding::Zstd
+        } else {
+            ContentEncoding::Identity
+        }
+    }
+}
+
 #[derive(Debug, Error, From)]
 pub enum Error {
     #[error("Errors encountered while revealing data: {description}")]
@@ -70,6 +103,8 @@ pub enum Error {
     PreimageHashConstructionError(PreimageHashError),
     #[error("Reveal data size exceeds the maximum limit.")]
     RevealDataSizeExceedsMaximumLimit,
+    #[error("Failed to decompress reveal data: {description}")]
+    DecompressionError { description: String },
 }
 
 // TODO: optimize the api and performance
@@ -98,11 +133,37 @@ impl RevealData {
         .map_err(|e| e.to_owned().into())
     }
 
-    /// Reveal the data and decode it into the given type.
+    /// Decompresses the revealed content according to its encoding tag.
+    /// Decompression is bounded by [`MAX_DECOMPRESSED_REVEAL_SIZE`].
+    fn decode_content(content: Vec<u8>) -> Result<Vec<u8>> {
+        match ContentEncoding::of(&content) {
+            ContentEncoding::Identity => Ok(content),
+            ContentEncoding::Zstd => {
+                let decompression_error =
+                    |e: &dyn fmt::Display| Error::DecompressionError {
+                        description: e.to_string(),
+                    };
+                let mut compressed = &content[ZSTD_TAG.len()..];
+                let decoder = StreamingDecoder::new(&mut compressed)
+                    .map_err(|e| decompression_error(&e))?;
+                let mut decompressed = Vec::new();
+                decoder
+                    .take(MAX_DECOMPRESSED_REVEAL_SIZE as u64 + 1)
+                    .read_to_end(&mut decompressed)
+                    .map_err(|e| decompression_error(&e))?;
+                if decompressed.len() > MAX_DECOMPRESSED_REVEAL_SIZE {
+                    return Err(Error::RevealDataSizeExceedsMaximumLimit.into());
+                }
+                Ok(decompressed)
+            }
+        }
+    }
+
+    /// Reveal the data, decompress it if needed, and decode it into the given type.
     pub fn reveal_and_decode<H, T>(hrt: &mut H, root_hash: &PreimageHash) -> Result<T>
     where
         H: HostRuntime,
-        T: BinEncodable,
+        T: Decode,
     {
         // TODO: include the size of the data in the operation to avoid the allocation of a large buffer
         // https://linear.app/tezos/issue/JSTZ-359/optimize-reveal-data
@@ -115,19 +176,70 @@ impl RevealData {
                 Ok(())
             },
         )?;
-        T::decode(&content[..])
+        decode_revealed(&Self::decode_content(content)?)
+    }
+
+    /// Reveal the data and write it to `path` in durable storage, page by page,
+    /// without holding all of it in memory.
+    pub fn reveal_to_store<H>(
+        hrt: &mut H,
+        root_hash: &PreimageHash,
+        path: &impl Path,
+    ) -> Result<usize>
+    where
+        H: HostRuntime,
+    {
+        let mut written = 0;
+        Self::reveal(
+            hrt,
+            root_hash,
+            &mut |hrt: &mut H, page: V0SliceContentPage| {
+                hrt.store_write(path, page.as_ref(), written)
+                    .map_err(|_| "Failed to write revealed page to storage")?;
+                written += page.as_ref().len();
+                Ok(())
+            },
+        )?;
+        Ok(written)
     }
 
     /// Encode the data, prepare the preimages and return the root preimage hash.
     pub fn encode_and_prepare_preimages<T, F>(
         value: &T,
+        handle: F,
+    ) -> Result<PreimageHash>
+    where
+        T: BinEncodable,
+        F: FnMut(PreimageHash, Vec<u8>),
+    {
+        Self::encode_and_prepare_preimages_with(value, ContentEncoding::Identity, handle)
+    }
+
+    /// Encode the data with the given content encoding, prepare the preimages
+    /// and return the root preimage hash. Compressed data may encode values of
+    /// up to [`MAX_DECOMPRESSED_REVEAL_SIZE`] bytes.
+    pub fn encode_and_prepare_preimages_with<T, F>(
+        value: &T,
+        content_encoding: ContentEncoding,
         mut handle: F,
     ) -> Result<PreimageHash>
     where
         T: BinEncodable,
         F: FnMut(PreimageHash, Vec<u8>),
     {
-        let encoded = T::encode(value)?;
+        let encoded = match content_encoding {
+            ContentEncoding::Identity => T::encode(value)?,
+            ContentEncoding::Zstd => {
+                let encoded = T::encode(value)?;
+                if encoded.len() > MAX_DECOMPRESSED_REVEAL_SIZE {
+                    return Err(Error::RevealDataSizeExceedsMaximumLimit.into());
+                }
+                let mut compressed = ZSTD_TAG.to_vec();
+                compressed
+                    .extend(compress_to_vec(&encoded[..], CompressionLevel::Fastest));
+                compressed
+            }
+        };
         if encoded.len() > MAX_REVEAL_SIZE {
             return Err(Error::RevealDataSizeExceedsMaximumLimit.into());
         }
@@ -177,9 +289,80 @@ mod tests {
         encode_and_decode_data_with_rdc(large_data);
     }
 
+    #[test]
+    fn test_encode_and_decode_compressed_with_rdc() {
+        let data = TestData(vec![7; 100]);
+        let mut host = MockHost::default();
+        let preimage_hash = RevealData::encode_and_prepare_preimages_with(
+            &data,
+            ContentEncoding::Zstd,
+            |_, page| {
+                host.set_preimage(page);
+            },
+        )
+        .expect("should prepare preimages");
+
+        let decoded =
+            RevealData::reveal_and_decode::<_, TestData>(&mut host, &preimage_hash)
+                .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn compressed_data_may_exceed_max_reveal_size() {
+        let data = TestData(vec![0; 2 * MAX_REVEAL_SIZE]);
+        let mut host = MockHost::default();
+
+        RevealData::encode_and_prepare_preimages(&data, |_, _| {})
+            .expect_err("should fail uncompressed");
+        let preimage_hash = RevealData::encode_and_prepare_preimages_with(
+            &data,
+            ContentEncoding::Zstd,
+            |_, page| {
+                host.set_preimage(page);
+            },
+        )
+        .expect("should prepare preimages");
+
+        let decoded =
+            RevealData::reveal_and_decode::<_, TestData>(&mut host, &preimage_hash)
+                .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn decode_content_fails_on_corrupted_compressed_data() {
+        let mut content = ZSTD_TAG.to_vec();
+        content.extend_from_slice(&[1, 2, 3, 4]);
+        let err = RevealData::decode_content(content).expect_err("should fail");
+        assert!(matches!(
+            err,
+            Error::RevealDataError {
+                source: super::Error::DecompressionError { .. }
+            }
+        ));
+    }
+
+    #[test]
+    fn reveal_to_store_writes_revealed_data() {
+        let mut host = MockHost::default();
+        let data: Vec<u8> = (0..=u8::MAX).cycle().take(5 * MAX_PAGE_SIZE).collect();
+        let root_hash = dac::prepare_preimages(&data, |_, page| {
+            host.set_preimage(page);
+        })
+        .expect("should prepare preimages");
+        let path = tezos_smart_rollup::storage::path::RefPath::assert_from(b"/revealed");
+
+        let written = RevealData::reveal_to_store(&mut host, &root_hash.into(), &path)
+            .expect("should reveal data");
+
+        assert_eq!(written, data.len());
+        assert_eq!(host.store_read_all(&path).unwrap(), data);
+    }
+
     fn encode_and_decode_data_with_rdc<T>(data: T)
     where
-        T: BinEncodable + Clone + PartialEq + Eq + std::fmt::Debug,
+        T: BinEncodable + Decode + Clone + PartialEq + Eq + std::fmt::Debug,
     {
         let mut host = MockHost::default();
         let preimage_hash = RevealData::encode_and_prepare_preimages(&data, |_, page| {
@@ -12,7 +12,7 @@ use serde::{Deserialize, Serialize};
 use serde_with::base64::{Base64, UrlSafe};
 use serde_with::formats::Unpadded;
 use serde_with::serde_as;
-use tezos_crypto_rs::hash::P256Signature;
+use tezos_crypto_rs::hash::{P256Signature, PublicKeyP256};
 use thiserror::Error;
 
 use base64::Engine;
@@ -51,6 +51,12 @@ pub enum PasskeyError {
 
     #[error("Verification failed")]
     VerificationFailed,
+
+    #[error("InvalidAuthenticatorData: {0}")]
+    InvalidAuthenticatorData(&'static str),
+
+    #[error("InvalidCoseKey: {0}")]
+    InvalidCoseKey(&'static str),
 }
 
 use PasskeyError::*;
@@ -161,6 +167,172 @@ pub fn parse_passkey_signature(signature: &str) -> Result<signature::P256> {
     Ok(sig)
 }
 
+/// Returns the challenge to request from the passkey device when signing the
+/// operation with the given hash, i.e. the hex encoding of the hash.
+/// See [verify_passkey].
+pub fn passkey_challenge(operation_hash: &[u8]) -> Vec<u8> {
+    hex::encode(operation_hash).into_bytes()
+}
+
+/// Flag set when the authenticator data includes attested credential data
+const ATTESTED_CREDENTIAL_DATA_FLAG: u8 = 0x40;
+/// rpIdHash (32) | flags (1) | signCount (4)
+const AUTHENTICATOR_DATA_HEADER_SIZE: usize = 37;
+const AAGUID_SIZE: usize = 16;
+
+/// Extracts the P256 public key of the credential attested in the authenticator
+/// data returned when the passkey is registered. The layout is described in the
+/// [Web Authentication API spec](https://w3c.github.io/webauthn/#sctn-authenticator-data).
+pub fn parse_attested_public_key(authenticator_data: &[u8]) -> Result<public_key::P256> {
+    let flags = *authenticator_data
+        .get(AUTHENTICATOR_DATA_HEADER_SIZE - 5)
+        .ok_or(InvalidAuthenticatorData("too short"))?;
+    if flags & ATTESTED_CREDENTIAL_DATA_FLAG == 0 {
+        Err(InvalidAuthenticatorData("no attested credential data"))?
+    }
+    let credential_id_len_offset = AUTHENTICATOR_DATA_HEADER_SIZE + AAGUID_SIZE;
+    let credential_id_len = match authenticator_data
+        .get(credential_id_len_offset..credential_id_len_offset + 2)
+    {
+        Some(&[hi, lo]) => u16::from_be_bytes([hi, lo]) as usize,
+        _ => Err(InvalidAuthenticatorData("too short"))?,
+    };
+    let cose_key = authenticator_data
+        .get(credential_id_len_offset + 2 + credential_id_len..)
+        .ok_or(InvalidAuthenticatorData("too short"))?;
+    parse_cose_public_key(cose_key)
+}
+
+// COSE key parameters and values, see https://www.rfc-editor.org/rfc/rfc9053
+const COSE_KEY_KTY: i64 = 1;
+const COSE_KEY_ALG: i64 = 3;
+const COSE_KEY_EC2_CRV: i64 = -1;
+const COSE_KEY_EC2_X: i64 = -2;
+const COSE_KEY_EC2_Y: i64 = -3;
+const COSE_KTY_EC2: i64 = 2;
+const COSE_ALG_ES256: i64 = -7;
+const COSE_CRV_P256: i64 = 1;
+
+/// Parses a CBOR encoded COSE_Key holding a P256 (ES256) public key. Trailing
+/// bytes, such as extensions following the key in authenticator data, are
+/// ignored.
+pub fn parse_cose_public_key(cose_key: &[u8]) -> Result<public_key::P256> {
+    let mut cbor = Cbor(cose_key);
+    let entries = cbor.map_len()?;
+    let (mut kty, mut alg, mut crv, mut x, mut y) = (None, None, None, None, None);
+    for _ in 0..entries {
+        let label = cbor.int()?;
+        match label {
+            COSE_KEY_KTY => kty = Some(cbor.int()?),
+            COSE_KEY_ALG => alg = Some(cbor.int()?),
+            COSE_KEY_EC2_CRV => crv = Some(cbor.int()?),
+            COSE_KEY_EC2_X => x = Some(cbor.bytes()?),
+            COSE_KEY_EC2_Y => y = Some(cbor.bytes()?),
+            _ => cbor.skip()?,
+        }
+    }
+    if kty != Some(COSE_KTY_EC2) {
+        Err(InvalidCoseKey("key type must be EC2"))?
+    }
+    if alg.is_some_and(|alg| alg != COSE_ALG_ES256) {
+        Err(InvalidCoseKey("algorithm must be ES256"))?
+    }
+    if crv != Some(COSE_CRV_P256) {
+        Err(InvalidCoseKey("curve must be P-256"))?
+    }
+    let (x, y) = match (x, y) {
+        (Some(x), Some(y)) if x.len() == 32 && y.len() == 32 => (x, y),
+        _ => Err(InvalidCoseKey("coordinates must be 32 bytes long"))?,
+    };
+
+    let mut uncompressed = Vec::with_capacity(65);
+    uncompressed.push(0x04);
+    uncompressed.extend_from_slice(x);
+    uncompressed.extend_from_slice(y);
+    let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(&uncompressed)
+        .map_err(PublicKeyConversionError)?;
+    let compressed = key.to_encoded_point(true);
+    Ok(public_key::P256(PublicKeyP256::try_from(
+        compressed.as_bytes(),
+    )?))
+}
+
+/// Minimal CBOR reader covering the items found in COSE keys
+struct Cbor<'a>(&'a [u8]);
+
+impl<'a> Cbor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.0.len() < n {
+            Err(InvalidCoseKey("unexpected end of input"))?
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    /// Reads the header of the next item and returns its major type and argument
+    fn header(&mut self) -> Result<(u8, u64)> {
+        let initial = self.take(1)?[0];
+        let argument = match initial & 0x1f {
+            n @ 0..=23 => n as u64,
+            24 => self.take(1)?[0] as u64,
+            25 => u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as u64,
+            26 => u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as u64,
+            27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+            _ => Err(InvalidCoseKey("indefinite length items are not supported"))?,
+        };
+        Ok((initial >> 5, argument))
+    }
+
+    fn int(&mut self) -> Result<i64> {
+        match self.header()? {
+            (0, n) => {
+                i64::try_from(n).map_err(|_| InvalidCoseKey("integer overflow").into())
+            }
+            (1, n) => i64::try_from(n)
+                .map(|n| -1 - n)
+                .map_err(|_| InvalidCoseKey("integer overflow").into()),
+            _ => Err(InvalidCoseKey("expected an integer"))?,
+        }
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        match self.header()? {
+            (2, len) => self.take(len as usize),
+            _ => Err(InvalidCoseKey("expected a byte string"))?,
+        }
+    }
+
+    fn map_len(&mut self) -> Result<u64> {
+        match self.header()? {
+            (5, len) => Ok(len),
+            _ => Err(InvalidCoseKey("expected a map"))?,
+        }
+    }
+
+    fn skip(&mut self) -> Result<()> {
+        match self.header()? {
+            (0 | 1 | 7, _) => {}
+            (2 | 3, len) => {
+                self.take(len as usize)?;
+            }
+            (4, len) => {
+                for _ in 0..len {
+                    self.skip()?;
+                }
+            }
+            (5, len) => {
+                for _ in 0..len * 2 {
+                    self.skip()?;
+                }
+            }
+            (6, _) => self.skip()?,
+            _ => Err(InvalidCoseKey("malformed item"))?,
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::public_key::PublicKey;
@@ -270,4 +442,62 @@ mod test {
         let expected = "p2sigtghDmmBqGocWksbS78H4GeEjcahkYMabd5on2Sur9vMbJ1oTwAdpmTTVq4tJhLPLbiPvkb3N821bp7UZ7szjcJLF46uZJ";
         assert_eq!(expected, signature.to_base58_check());
     }
+
+    const COSE_KEY: &str = "a50102032620012158201143a9c56748c29adb6a85863484ffe3f42299acca8470575ae177ec988ff28b225820ec29d9429a3196d3925a1dcc56779e231c645dcd168f6f2a3d9039271e1f0882";
+
+    #[test]
+    fn passkey_challenge_test() {
+        let operation_hash = hex::decode(
+            "84540ed046dc05bf6e2dc356c4eaa3f9b44df68c4c16b7f3ca8f6c7ef85591e9",
+        )
+        .unwrap();
+        assert_eq!(
+            BASE64_URL_SAFE_NO_PAD.encode(passkey_challenge(&operation_hash)),
+            "ODQ1NDBlZDA0NmRjMDViZjZlMmRjMzU2YzRlYWEzZjliNDRkZjY4YzRjMTZiN2YzY2E4ZjZjN2VmODU1OTFlOQ"
+        );
+    }
+
+    #[test]
+    fn parse_cose_public_key_test() {
+        let public_key = PublicKey::P256(
+            parse_cose_public_key(&hex::decode(COSE_KEY).unwrap()).unwrap(),
+        );
+        assert_eq!(
+            public_key.to_base58(),
+            "p2pk64hmP1AMx1kcVAUhLTAj21y1CDEkjn4qY7rUXqmDWLoTqidYhhW"
+        );
+        assert_eq!(public_key.hash(), "tz3WGyV7iRfiyDZoiWzKpVWZqVef6v69HXhB");
+
+        // Ed25519 OKP key
+        let mut okp = hex::decode(COSE_KEY).unwrap();
+        okp[2] = 0x01;
+        assert!(matches!(
+            parse_cose_public_key(&okp),
+            Err(crate::Error::PasskeyError {
+                source: super::PasskeyError::InvalidCoseKey(_)
+            })
+        ));
+        assert!(parse_cose_public_key(&hex::decode(&COSE_KEY[..40]).unwrap()).is_err());
+    }
+
+    #[test]
+    fn parse_attested_public_key_test() {
+        let mut authenticator_data = vec![0; 32];
+        // UP | UV | AT
+        authenticator_data.push(0x45);
+        authenticator_data.extend_from_slice(&[0; 4]);
+        authenticator_data.extend_from_slice(&[0; 16]);
+        authenticator_data.extend_from_slice(&[0, 4, 1, 2, 3, 4]);
+        authenticator_data.extend_from_slice(&hex::decode(COSE_KEY).unwrap());
+
+        let public_key = parse_attested_public_key(&authenticator_data).unwrap();
+        assert_eq!(
+            public_key.to_base58_check(),
+            "p2pk64hmP1AMx1kcVAUhLTAj21y1CDEkjn4qY7rUXqmDWLoTqidYhhW"
+        );
+
+        // Assertions carry no attested credential data
+        authenticator_data[32] = 0x05;
+        assert!(parse_attested_public_key(&authenticator_data).is_err());
+    }
 }
@@ -1,19 +1,26 @@
-use std::io::empty;
+use std::{fmt::Display, io::empty};
 
-use jstz_core::{host::HostRuntime, kv::Storage, BinEncodable};
+use jstz_core::{
+    host::HostRuntime,
+    kv::{Storage, Value},
+    BinEncodable,
+};
 
-use crate::message::MockInternalMessage;
+use crate::message::{
+    malformed_deposit::MockMalformedDeposit, native_deposit::MockNativeDeposit,
+    MockInternalMessage,
+};
 use derive_more::{Deref, DerefMut};
 use jstz_crypto::{public_key::PublicKey, smart_function_hash::SmartFunctionHash};
-use tezos_crypto_rs::hash::ContractKt1Hash;
+use tezos_crypto_rs::hash::{ContractKt1Hash, SmartRollupHash};
 use tezos_smart_rollup::{
-    inbox::ExternalMessageFrame,
+    inbox::{ExternalMessageFrame, InboxMessage, InternalInboxMessage},
     michelson::{
-        ticket::FA2_1Ticket, MichelsonContract, MichelsonOption, MichelsonOr,
-        MichelsonPair,
+        ticket::FA2_1Ticket, MichelsonBytes, MichelsonContract, MichelsonOption,
+        MichelsonOr, MichelsonPair, MichelsonUnit,
     },
-    storage::path::RefPath,
-    types::SmartRollupAddress,
+    storage::path::{OwnedPath, Path, RefPath},
+    types::{SmartRollupAddress, Timestamp},
 };
 use tezos_smart_rollup_mock::{MockHost, TransferMetadata};
 
@@ -44,6 +51,10 @@ pub const MOCK_TICKETER: &str = "KT1H28iie4mW9LmmJeYLjH6zkC8wwSmfHf5P";
 
 pub const TICKETER_PATH: RefPath = RefPath::assert_from(b"/ticketer");
 pub const INJECTOR_PATH: RefPath = RefPath::assert_from(b"/injector");
+// Maximum size of an inbox message accepted by the L1
+pub const MAX_INPUT_MESSAGE_SIZE: usize = 4096;
+// Storage prefix of the accounts
+pub const ACCOUNTS_PATH_PREFIX: &str = "/jstz_account";
 pub type RollupType = MichelsonOr<
     MichelsonPair<MichelsonContract, FA2_1Ticket>,
     MichelsonPair<
@@ -54,17 +65,23 @@ pub type RollupType = MichelsonOr<
 
 // Wrapper over Mockhost to simplify setup of mock scenarios
 #[derive(Deref, DerefMut)]
-pub struct JstzMockHost(MockHost);
+pub struct JstzMockHost {
+    #[deref]
+    #[deref_mut]
+    host: MockHost,
+    timestamp: Option<Timestamp>,
+    skip_meta_messages: bool,
+}
 
 impl JstzMockHost {
     pub fn new(skip_meta_messages: bool) -> Self {
-        let mut mock_host = Self::default();
-        if skip_meta_messages {
-            // skip the SOL and Level info messages
-            mock_host.0.read_input().unwrap();
-            mock_host.0.read_input().unwrap();
-        }
-        mock_host
+        Self::builder()
+            .skip_meta_messages(skip_meta_messages)
+            .build()
+    }
+
+    pub fn builder() -> JstzMockHostBuilder {
+        JstzMockHostBuilder::default()
     }
 
     pub fn add_internal_message<'a, T>(&mut self, message: &'a T)
@@ -76,42 +93,247 @@ impl JstzMockHost {
         if let Some(smart_rollup) = message.smart_rollup() {
             metadata.override_destination(smart_rollup);
         }
-        self.0.add_transfer(payload, &metadata)
+        self.host.add_transfer(payload, &metadata)
+    }
+
+    // Adds a transfer that must not be parsed as a deposit
+    pub fn add_malformed_deposit(&mut self, deposit: MockMalformedDeposit) {
+        match deposit.native_deposit() {
+            Some(native_deposit) => self.add_internal_message(&native_deposit),
+            None => {
+                let default = MockNativeDeposit::default();
+                let metadata = TransferMetadata::new(default.sender, default.source);
+                self.host
+                    .add_transfer(MichelsonBytes(b"not a deposit".to_vec()), &metadata)
+            }
+        }
     }
 
     pub fn add_external_message<T: BinEncodable>(&mut self, message: T) {
-        let message = message.encode().unwrap();
+        self.add_raw_external_message(message.encode().unwrap());
+    }
+
+    // Adds an external message targetting the rollup with the given contents
+    pub fn add_raw_external_message(&mut self, contents: Vec<u8>) {
         let external_message = ExternalMessageFrame::Targetted {
-            address: SmartRollupAddress::new(self.0.reveal_metadata().address()),
-            contents: message,
+            address: SmartRollupAddress::new(self.host.reveal_metadata().address()),
+            contents,
         };
-        self.0.add_external(external_message);
+        self.host.add_external(external_message);
+    }
+
+    // Adds an external message exceeding MAX_INPUT_MESSAGE_SIZE, which the L1
+    // would reject, and returns its contents
+    pub fn add_oversized_external_message(&mut self) -> Vec<u8> {
+        let contents = vec![0xff; MAX_INPUT_MESSAGE_SIZE];
+        self.add_raw_external_message(contents.clone());
+        contents
+    }
+
+    // Runs `n` empty levels and returns the new L1 level. If the host was built
+    // with `skip_meta_messages`, the SOL, Level info and EOL messages of every level
+    // advanced are skipped, so the inbox must not have other unread messages.
+    pub fn advance_level(&mut self, n: u32) -> u32 {
+        for _ in 0..n {
+            self.host.run_level(|_| {});
+        }
+        if n > 0 && self.skip_meta_messages {
+            self.skip_level_messages();
+        }
+        self.host.level()
+    }
+
+    // Predecessor timestamp in the Level info message of the current level, if the
+    // host skipped it
+    pub fn timestamp(&self) -> Option<&Timestamp> {
+        self.timestamp.as_ref()
+    }
+
+    fn skip_level_messages(&mut self) {
+        while let Some(input) = self.host.read_input().unwrap() {
+            match InboxMessage::<MichelsonUnit>::parse(input.as_ref()) {
+                Ok((
+                    _,
+                    InboxMessage::Internal(InternalInboxMessage::InfoPerLevel(info)),
+                )) => self.timestamp = Some(info.predecessor_timestamp),
+                Ok((
+                    _,
+                    InboxMessage::Internal(
+                        InternalInboxMessage::StartOfLevel
+                        | InternalInboxMessage::EndOfLevel,
+                    ),
+                )) => {}
+                _ => panic!("Unread inbox message at level {}", input.level),
+            }
+        }
     }
 
     pub fn get_ticketer(&self) -> SmartFunctionHash {
-        ContractKt1Hash::from_base58_check(NATIVE_TICKETER)
-            .unwrap()
-            .into()
+        Storage::get(&self.host, &TICKETER_PATH)
+            .expect("Could not read ticketer")
+            .expect("Ticketer not set")
     }
 
     pub fn rt(&mut self) -> &mut MockHost {
-        &mut self.0
+        &mut self.host
     }
 }
 
 impl Default for JstzMockHost {
     fn default() -> Self {
-        let mut mock_host = MockHost::default();
-        let ticketer: SmartFunctionHash =
-            ContractKt1Hash::from_base58_check(NATIVE_TICKETER)
+        Self::builder().build()
+    }
+}
+
+// Configures the L1 context and the initial durable storage of a JstzMockHost
+pub struct JstzMockHostBuilder {
+    rollup_address: Option<SmartRollupHash>,
+    ticketer: SmartFunctionHash,
+    injector: PublicKey,
+    level: u32,
+    skip_meta_messages: bool,
+    storage: Vec<(OwnedPath, Vec<u8>)>,
+}
+
+impl Default for JstzMockHostBuilder {
+    fn default() -> Self {
+        Self {
+            rollup_address: None,
+            ticketer: ContractKt1Hash::from_base58_check(NATIVE_TICKETER)
                 .unwrap()
-                .into();
-        Storage::insert(&mut mock_host, &TICKETER_PATH, &ticketer)
+                .into(),
+            injector: PublicKey::from_base58(INJECTOR).unwrap(),
+            level: 0,
+            skip_meta_messages: false,
+            storage: vec![],
+        }
+    }
+}
+
+impl JstzMockHostBuilder {
+    pub fn rollup_address(mut self, rollup_address: SmartRollupHash) -> Self {
+        self.rollup_address = Some(rollup_address);
+        self
+    }
+
+    pub fn ticketer(mut self, ticketer: SmartFunctionHash) -> Self {
+        self.ticketer = ticketer;
+        self
+    }
+
+    pub fn injector(mut self, injector: PublicKey) -> Self {
+        self.injector = injector;
+        self
+    }
+
+    // Runs empty levels until the L1 level of the inbox is at least `level`
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = level;
+        self
+    }
+
+    // Skips the SOL, Level info and EOL messages of the levels run by the builder
+    pub fn skip_meta_messages(mut self, skip_meta_messages: bool) -> Self {
+        self.skip_meta_messages = skip_meta_messages;
+        self
+    }
+
+    // Writes `value` at `path` of the durable storage
+    pub fn with_value<V: Value + ?Sized>(mut self, path: &impl Path, value: &V) -> Self {
+        let bytes = value.encode().expect("Could not encode value");
+        self.storage.push((OwnedPath::from(path), bytes));
+        self
+    }
+
+    // Stores `account`, e.g. a user account with a balance, at `address`
+    pub fn with_account<V: Value>(self, address: impl Display, account: &V) -> Self {
+        let path = OwnedPath::try_from(format!("{ACCOUNTS_PATH_PREFIX}/{address}"))
+            .expect("Invalid account address");
+        self.with_value(&path, account)
+    }
+
+    // Stores the account of a pre-deployed smart function at `address`
+    pub fn with_sf<V: Value>(self, address: &SmartFunctionHash, account: &V) -> Self {
+        self.with_account(address, account)
+    }
+
+    pub fn build(self) -> JstzMockHost {
+        let mut mock_host = match self.rollup_address {
+            Some(address) => MockHost::with_address(&SmartRollupAddress::new(address)),
+            None => MockHost::default(),
+        };
+        mock_host.set_debug_handler(empty());
+        while mock_host.level() < self.level {
+            mock_host.run_level(|_| {});
+        }
+        Storage::insert(&mut mock_host, &TICKETER_PATH, &self.ticketer)
             .expect("Could not insert ticketer");
-        let injector: PublicKey = PublicKey::from_base58(INJECTOR).unwrap();
-        Storage::insert(&mut mock_host, &INJECTOR_PATH, &injector)
+        Storage::insert(&mut mock_host, &INJECTOR_PATH, &self.injector)
             .expect("Could not insert injector");
-        mock_host.set_debug_handler(empty());
-        Self(mock_host)
+        for (path, bytes) in self.storage {
+            mock_host
+                .store_write_all(&path, &bytes)
+                .expect("Could not insert value");
+        }
+        let mut host = JstzMockHost {
+            host: mock_host,
+            timestamp: None,
+            skip_meta_messages: self.skip_meta_messages,
+        };
+        if self.skip_meta_messages {
+            host.skip_level_messages();
+        }
+        host
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jstz_core::{host::HostRuntime, kv::Storage};
+    use jstz_crypto::{hash::Hash, smart_function_hash::SmartFunctionHash};
+    use tezos_smart_rollup::storage::path::OwnedPath;
+
+    use super::{JstzMockHost, MOCK_PROXY, MOCK_TICKETER};
+    use crate::sr1_address;
+
+    #[test]
+    fn builder_configures_host() {
+        let ticketer = SmartFunctionHash::from_base58(MOCK_TICKETER).unwrap();
+        let proxy = SmartFunctionHash::from_base58(MOCK_PROXY).unwrap();
+        let mut host = JstzMockHost::builder()
+            .rollup_address(sr1_address())
+            .ticketer(ticketer.clone())
+            .level(5)
+            .with_sf(&proxy, &42u64)
+            .build();
+
+        assert_eq!(host.get_ticketer(), ticketer);
+        assert_eq!(host.reveal_metadata().address(), sr1_address());
+        assert!(host.level() >= 5);
+        let path = OwnedPath::try_from(format!("/jstz_account/{proxy}")).unwrap();
+        assert_eq!(Storage::get::<u64>(host.rt(), &path).unwrap(), Some(42));
+    }
+
+    #[test]
+    fn advance_level_skips_meta_messages() {
+        let mut host = JstzMockHost::new(true);
+        let level = host.level();
+        let timestamp = host.timestamp().cloned();
+        assert!(timestamp.is_some());
+
+        assert_eq!(host.advance_level(3), level + 3);
+        assert!(host.read_input().unwrap().is_none());
+        assert_ne!(host.timestamp().cloned(), timestamp);
+        assert_eq!(host.advance_level(0), level + 3);
+    }
+
+    #[test]
+    fn advance_level_keeps_meta_messages() {
+        let mut host = JstzMockHost::new(false);
+        let level = host.level();
+
+        assert_eq!(host.advance_level(2), level + 2);
+        assert!(host.read_input().unwrap().is_some());
+        assert_eq!(host.timestamp(), None);
     }
 }
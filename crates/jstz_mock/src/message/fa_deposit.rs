@@ -55,6 +55,30 @@ impl Default for MockFaDeposit {
 }
 
 impl MockFaDeposit {
+    /// Deposit of `amount` FA tickets to `receiver`, or to the default receiver,
+    /// routed through `proxy_contract` if any
+    pub fn new(
+        amount: u32,
+        receiver: Option<Contract>,
+        proxy_contract: Option<jstz_crypto::smart_function_hash::SmartFunctionHash>,
+    ) -> Self {
+        let default = MockFaDeposit::default();
+        Self {
+            receiver: receiver.unwrap_or(default.receiver),
+            ticket_amount: amount,
+            proxy_contract,
+            ..default
+        }
+    }
+
+    /// Deposit of a ticket with the given id and content
+    pub fn with_ticket_content(self, ticket_content: (u32, Option<Vec<u8>>)) -> Self {
+        Self {
+            ticket_content,
+            ..self
+        }
+    }
+
     pub fn ticket_hash(&self) -> TicketHash {
         let ticket = parse_ticket(
             self.ticketer.clone(),
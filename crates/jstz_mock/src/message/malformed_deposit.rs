@@ -0,0 +1,48 @@
+use tezos_crypto_rs::hash::ContractKt1Hash;
+
+use crate::host::MOCK_TICKETER;
+
+use super::native_deposit::MockNativeDeposit;
+
+/// Transfers to the rollup that must not be parsed as deposits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockMalformedDeposit {
+    /// Native deposit of a ticket that was not minted by the native ticketer
+    ForeignTicketer,
+    /// Native deposit of a ticket with a different id than the native ticket
+    ForeignTicketId,
+    /// Native deposit of a ticket with a different content than the native ticket
+    ForeignTicketContent,
+    /// Transfer whose payload is not a deposit
+    InvalidPayload,
+}
+
+impl MockMalformedDeposit {
+    pub const ALL: [Self; 4] = [
+        Self::ForeignTicketer,
+        Self::ForeignTicketId,
+        Self::ForeignTicketContent,
+        Self::InvalidPayload,
+    ];
+
+    /// Returns the native deposit of the malformed ticket, or None if the
+    /// payload is not a deposit
+    pub fn native_deposit(&self) -> Option<MockNativeDeposit> {
+        let default = MockNativeDeposit::default();
+        match self {
+            Self::ForeignTicketer => Some(MockNativeDeposit {
+                ticketer: ContractKt1Hash::from_base58_check(MOCK_TICKETER).unwrap(),
+                ..default
+            }),
+            Self::ForeignTicketId => Some(MockNativeDeposit {
+                ticket_content: (1, None),
+                ..default
+            }),
+            Self::ForeignTicketContent => Some(MockNativeDeposit {
+                ticket_content: (0, Some(b"1234".to_vec())),
+                ..default
+            }),
+            Self::InvalidPayload => None,
+        }
+    }
+}
@@ -2,6 +2,7 @@ use tezos_crypto_rs::hash::ContractKt1Hash;
 use tezos_smart_rollup::types::{PublicKeyHash, SmartRollupAddress};
 
 pub mod fa_deposit;
+pub mod malformed_deposit;
 pub mod native_deposit;
 
 pub trait MockInternalMessage {
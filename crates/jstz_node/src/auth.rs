@@ -0,0 +1,235 @@
+//! Bearer token authentication of the write endpoints.
+//!
+//! When a token is configured, `POST` requests to the `/operations` and `/admin`
+//! endpoints, including those of the rollups served under `/r/{rollup_address}`,
+//! must carry an `Authorization: Bearer <token>` header, otherwise they are
+//! rejected with `401 Unauthorized`. When no token is configured, the `/operations`
+//! endpoints are public and the `/admin` endpoints are rejected with
+//! `403 Forbidden`. Read endpoints stay public.
+
+use std::task::{Context, Poll};
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_util::future::{self, Either, Ready};
+use tower::{Layer, Service};
+
+use crate::tenants::route_path;
+
+/// Path prefix of the admin endpoints
+const ADMIN_PATH: &str = "/admin";
+
+/// Path prefixes of the write endpoints
+const WRITE_PATHS: [&str; 2] = ["/operations", ADMIN_PATH];
+
+fn has_prefix(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+fn is_write_request(req: &Request) -> bool {
+    let path = route_path(req.uri().path());
+    req.method() == Method::POST
+        && WRITE_PATHS.iter().any(|prefix| has_prefix(path, prefix))
+}
+
+fn is_admin_request(req: &Request) -> bool {
+    has_prefix(route_path(req.uri().path()), ADMIN_PATH)
+}
+
+/// Compares the tokens in constant time with respect to their content
+fn token_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Returns true if `req` carries the bearer token `token`
+fn is_authorized(token: &str, req: &Request) -> bool {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|value| token_eq(value.as_bytes(), token.as_bytes()))
+}
+
+/// Layer that requires the bearer token `token` on the write endpoints, or that
+/// disables the admin endpoints if there is no token
+#[derive(Clone)]
+pub struct AuthLayer {
+    token: Option<String>,
+}
+
+impl AuthLayer {
+    pub fn new(token: Option<String>) -> Self {
+        Self { token }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = Auth<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Auth {
+            inner,
+            token: self.token.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Auth<S> {
+    inner: S,
+    token: Option<String>,
+}
+
+impl<S> Service<Request> for Auth<S>
+where
+    S: Service<Request, Response = Response>,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Either<S::Future, Ready<Result<Response, S::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        if is_write_request(&req) {
+            match &self.token {
+                Some(token) if !is_authorized(token, &req) => {
+                    return Either::Right(future::ready(Ok(unauthorized())));
+                }
+                None if is_admin_request(&req) => {
+                    return Either::Right(future::ready(Ok(
+                        StatusCode::FORBIDDEN.into_response()
+                    )));
+                }
+                _ => {}
+            }
+        }
+        Either::Left(self.inner.call(req))
+    }
+}
+
+fn unauthorized() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"))],
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        extract::Request,
+        http::{header, Method, StatusCode},
+        routing::{get, post},
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use super::AuthLayer;
+
+    #[tokio::test]
+    async fn layer_requires_token_on_write_endpoints() {
+        let router = Router::new()
+            .route("/operations", post(|| async {}))
+            .route("/operations/simulate", post(|| async {}))
+            .route("/admin/sequencer/pause", post(|| async {}))
+            .route("/operations/:hash/receipt", get(|| async {}))
+            .route("/operationsx", post(|| async {}))
+            .route(
+                "/r/sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK/operations",
+                post(|| async {}),
+            )
+            .route(
+                "/r/sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK/admin/sequencer/pause",
+                post(|| async {}),
+            )
+            .layer(AuthLayer::new(Some("secret".to_string())));
+        let request = |method: Method, path: &str, token: Option<&str>| {
+            let mut request = Request::builder().method(method).uri(path);
+            if let Some(token) = token {
+                request = request.header(header::AUTHORIZATION, token);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+        let status = |request: Request| {
+            let router = router.clone();
+            async move { router.oneshot(request).await.unwrap().status() }
+        };
+
+        for path in [
+            "/operations",
+            "/operations/simulate",
+            "/admin/sequencer/pause",
+            "/r/sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK/operations",
+            "/r/sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK/admin/sequencer/pause",
+        ] {
+            assert_eq!(
+                status(request(Method::POST, path, None)).await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(
+                status(request(Method::POST, path, Some("Bearer wrong"))).await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(
+                status(request(Method::POST, path, Some("secret"))).await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(
+                status(request(Method::POST, path, Some("Bearer secret"))).await,
+                StatusCode::OK
+            );
+        }
+
+        // read endpoints and other paths are public
+        assert_eq!(
+            status(request(Method::GET, "/operations/abc/receipt", None)).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(request(Method::POST, "/operationsx", None)).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn layer_disables_admin_endpoints_without_token() {
+        let router = Router::new()
+            .route("/operations", post(|| async {}))
+            .route("/admin/sequencer/pause", post(|| async {}))
+            .route(
+                "/r/sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK/admin/sequencer/resume",
+                post(|| async {}),
+            )
+            .layer(AuthLayer::new(None));
+        let status = |path: &str| {
+            let router = router.clone();
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri(path)
+                .header(header::AUTHORIZATION, "Bearer secret")
+                .body(Body::empty())
+                .unwrap();
+            async move { router.oneshot(request).await.unwrap().status() }
+        };
+
+        assert_eq!(status("/operations").await, StatusCode::OK);
+        assert_eq!(
+            status("/admin/sequencer/pause").await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status("/r/sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK/admin/sequencer/resume")
+                .await,
+            StatusCode::FORBIDDEN
+        );
+    }
+}
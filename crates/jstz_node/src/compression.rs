@@ -0,0 +1,163 @@
+//! Compression of the responses and decompression of the requests.
+//!
+//! When enabled, responses larger than `min_size` are compressed with the first
+//! encoding of the `Accept-Encoding` header of the request that the node allows, and
+//! request bodies are decompressed according to their `Content-Encoding` header.
+//! Requests encoded with an encoding that is not allowed are rejected with
+//! `415 Unsupported Media Type`. Server-sent event streams are never compressed, as
+//! the encoder would hold events back until enough of them are buffered.
+
+use axum::{body::Body, Router};
+use serde::{Deserialize, Serialize};
+use tower_http::{
+    compression::{
+        predicate::{NotForContentType, Predicate, SizeAbove},
+        CompressionLayer,
+    },
+    decompression::{DecompressionBody, RequestDecompressionLayer},
+    map_request_body::MapRequestBodyLayer,
+};
+
+/// Size, in bytes, under which responses are not compressed by default
+pub const DEFAULT_MIN_SIZE: u16 = 1024;
+
+/// Content encoding supported by the node
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentEncoding {
+    Gzip,
+    Br,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct CompressionConfig {
+    /// Size, in bytes, under which responses are not compressed
+    #[serde(default = "default_min_size")]
+    pub min_size: u16,
+    /// Encodings of the compressed responses and of the decompressed requests
+    pub encodings: Vec<ContentEncoding>,
+}
+
+fn default_min_size() -> u16 {
+    DEFAULT_MIN_SIZE
+}
+
+impl CompressionConfig {
+    fn allows(&self, encoding: ContentEncoding) -> bool {
+        self.encodings.contains(&encoding)
+    }
+}
+
+/// Compresses the responses and decompresses the requests of `router` according to
+/// `config`. Applied after the layers that read request bodies, so that they read
+/// the decompressed bodies.
+pub fn apply(router: Router, config: &CompressionConfig) -> Router {
+    let gzip = config.allows(ContentEncoding::Gzip);
+    let br = config.allows(ContentEncoding::Br);
+    let predicate = SizeAbove::new(config.min_size)
+        .and(NotForContentType::GRPC)
+        .and(NotForContentType::IMAGES)
+        .and(NotForContentType::SSE);
+    router
+        .layer(MapRequestBodyLayer::new(
+            Body::new::<DecompressionBody<Body>>,
+        ))
+        .layer(RequestDecompressionLayer::new().gzip(gzip).br(br))
+        .layer(
+            CompressionLayer::new()
+                .gzip(gzip)
+                .br(br)
+                .compress_when(predicate),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+        routing::{get, post},
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use super::{CompressionConfig, ContentEncoding};
+
+    fn router(encodings: Vec<ContentEncoding>) -> Router {
+        let router = Router::new()
+            .route("/small", get(|| async { "foo" }))
+            .route("/large", get(|| async { "foo".repeat(1000) }))
+            .route("/echo", post(|body: String| async { body }));
+        super::apply(
+            router,
+            &CompressionConfig {
+                min_size: 100,
+                encodings,
+            },
+        )
+    }
+
+    async fn content_encoding(
+        router: &Router,
+        path: &str,
+        accept: &str,
+    ) -> Option<String> {
+        let res = router
+            .clone()
+            .oneshot(
+                Request::get(path)
+                    .header(header::ACCEPT_ENCODING, accept)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        res.headers()
+            .get(header::CONTENT_ENCODING)
+            .map(|v| v.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn compresses_large_responses() {
+        let both = router(vec![ContentEncoding::Gzip, ContentEncoding::Br]);
+        assert_eq!(
+            content_encoding(&both, "/large", "gzip").await.as_deref(),
+            Some("gzip")
+        );
+        assert_eq!(
+            content_encoding(&both, "/large", "br").await.as_deref(),
+            Some("br")
+        );
+        assert_eq!(content_encoding(&both, "/large", "identity").await, None);
+        assert_eq!(content_encoding(&both, "/small", "gzip").await, None);
+
+        // encodings that are not allowed are not used
+        let br_only = router(vec![ContentEncoding::Br]);
+        assert_eq!(content_encoding(&br_only, "/large", "gzip").await, None);
+    }
+
+    #[tokio::test]
+    async fn rejects_requests_with_unsupported_encoding() {
+        let router = router(vec![ContentEncoding::Gzip]);
+        let res = router
+            .clone()
+            .oneshot(Request::post("/echo").body(Body::from("foo")).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(res.into_body(), 100).await.unwrap();
+        assert_eq!(&body[..], b"foo");
+
+        let res = router
+            .oneshot(
+                Request::post("/echo")
+                    .header(header::CONTENT_ENCODING, "br")
+                    .body(Body::from("foo"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
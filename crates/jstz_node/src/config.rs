@@ -1,15 +1,22 @@
 use std::{
+    collections::HashSet,
     fmt::Display,
     path::{Path, PathBuf},
 };
 
 use anyhow::Context;
+use jstz_crypto::smart_function_hash::SmartFunctionHash;
 use jstz_utils::KeyPair;
 use octez::r#async::endpoint::Endpoint;
 use serde::{Deserialize, Serialize};
 use tempfile::NamedTempFile;
 use tezos_crypto_rs::hash::{ContractKt1Hash, SmartRollupHash};
 
+use crate::{
+    compression::CompressionConfig, rate_limit::RateLimitConfig, reload::ConfigFile,
+    telemetry::TelemetryConfig,
+};
+
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
 #[serde(tag = "type")]
@@ -30,6 +37,10 @@ pub enum RunMode {
         rollup_address: SmartRollupHash,
         ticketer_address: ContractKt1Hash,
     },
+    /// Serves the read endpoints from a runtime database that follows the journal
+    /// of the sequencer at `primary_endpoint`, and forwards injected operations to
+    /// it, see [`crate::replica`]
+    Replica { primary_endpoint: String },
     #[serde(alias = "default")]
     Default,
 }
@@ -45,6 +56,7 @@ impl Display for RunMode {
         match self {
             RunMode::Default => write!(f, "default"),
             RunMode::Sequencer { .. } => write!(f, "sequencer"),
+            RunMode::Replica { .. } => write!(f, "replica"),
         }
     }
 }
@@ -55,6 +67,125 @@ pub enum RunModeType {
     #[default]
     Default,
     Sequencer,
+    Replica,
+}
+
+/// Order in which the sequencer executes the queued operations
+#[derive(
+    Default, Debug, clap::ValueEnum, Clone, Copy, Serialize, Deserialize, PartialEq,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueOrdering {
+    /// In the order they were queued
+    Fifo,
+    /// Operations with the highest effective priority first, which is the fee they
+    /// pay, see
+    /// [`WrappedOperation::tip`](crate::sequencer::queue::WrappedOperation::tip).
+    /// Inbox messages are executed in the order they were queued.
+    #[default]
+    Fee,
+}
+
+/// Limits on the operations of each account injected in the sequencer, so that a
+/// single account cannot take up the capacity of the queue
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AccountQuota {
+    /// Maximum number of queued operations of an account, unlimited if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_pending: Option<usize>,
+    /// Maximum number of operations of an account queued during an L1 level,
+    /// unlimited if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_per_level: Option<usize>,
+}
+
+/// Resource limits of the sequencer worker, see [`crate::sequencer::watchdog`]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+pub struct WatchdogConfig {
+    /// Heap memory of the worker, in megabytes, above which the worker is recycled,
+    /// unlimited if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_memory_mb: Option<u64>,
+    /// CPU usage of the worker thread, in percent of a core, above which the worker
+    /// is recycled, unlimited if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_cpu_percent: Option<f64>,
+}
+
+/// Retention of the operation records of the sequencer, see
+/// [`crate::sequencer::retention`]
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct RetentionConfig {
+    /// Number of L1 levels the receipt attestation, state and logs of an operation
+    /// are kept for after the level of the operation
+    pub levels: u32,
+    /// Hashes of the operations whose records and logs are never pruned
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub pinned: HashSet<String>,
+}
+
+/// Rollup served by a node besides its own, under `/r/{rollup_address}`, see
+/// [`crate::tenants`]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct RollupContext {
+    pub rollup_address: SmartRollupHash,
+    /// Endpoint of the rollup node of the rollup
+    pub rollup_endpoint: Endpoint,
+    /// The path to the preimages directory of the rollup
+    pub rollup_preimages_dir: PathBuf,
+    /// Path to the sqlite db file that keeps the runtime state of the rollup. When
+    /// not set, a temporary file is used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runtime_db_path: Option<PathBuf>,
+    /// The path to the kernel log file of the rollup. When not set, no logs are
+    /// served for the rollup.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kernel_log_file: Option<PathBuf>,
+}
+
+/// Rollup node of the rollup of the node that requests fail over to, with the files
+/// the node shares with it
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct FallbackRollupNode {
+    pub endpoint: Endpoint,
+    /// Preimages directory of the rollup node. The preimages of large operations are
+    /// written there too, so that the rollup node can reveal them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preimages_dir: Option<PathBuf>,
+    /// Kernel log file of the rollup node, whose logs are streamed while the rollup
+    /// node is active. Storage updates are always synced from the kernel log file of
+    /// the rollup endpoint, as they must be read without gaps.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kernel_log_file: Option<PathBuf>,
+    /// Log file of the rollup node, whose events are reported in the detailed health
+    /// check while the rollup node is active.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<PathBuf>,
+}
+
+/// Maximum level of the logs of the node
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for log::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => Self::Off,
+            LogLevel::Error => Self::Error,
+            LogLevel::Warn => Self::Warn,
+            LogLevel::Info => Self::Info,
+            LogLevel::Debug => Self::Debug,
+            LogLevel::Trace => Self::Trace,
+        }
+    }
 }
 
 #[derive(Default, Debug)]
@@ -66,6 +197,7 @@ pub struct RunModeBuilder {
     rollup_address: Option<SmartRollupHash>,
     inbox_checkpoint_path: Option<PathBuf>,
     ticketer_address: Option<ContractKt1Hash>,
+    primary_endpoint: Option<String>,
 }
 
 impl RunModeBuilder {
@@ -131,6 +263,14 @@ impl RunModeBuilder {
         anyhow::bail!("ticketer address can only be set when run mode is 'sequencer'");
     }
 
+    pub fn with_primary_endpoint(mut self, endpoint: String) -> anyhow::Result<Self> {
+        if let RunModeType::Replica = self.mode {
+            self.primary_endpoint.replace(endpoint);
+            return Ok(self);
+        }
+        anyhow::bail!("primary endpoint can only be set when run mode is 'replica'");
+    }
+
     pub fn build(self) -> anyhow::Result<RunMode> {
         Ok(match self.mode {
             RunModeType::Default => RunMode::Default,
@@ -166,16 +306,29 @@ impl RunModeBuilder {
                     rollup_address: self.rollup_address.ok_or(anyhow::anyhow!("smart rollup address is not configured for sequencer"))?
                 }
             }
+            RunModeType::Replica => RunMode::Replica {
+                primary_endpoint: self
+                    .primary_endpoint
+                    .context("primary endpoint is not configured for replica")?,
+            },
         })
     }
 }
 
+/// Environment variable from which the bearer token is read when it is not
+/// configured
+pub const AUTH_TOKEN_ENV: &str = "JSTZ_NODE_AUTH_TOKEN";
+
 #[derive(Clone, Serialize)]
 pub struct JstzNodeConfig {
     /// The endpoint of the jstz node.
     pub endpoint: Endpoint,
     /// Rollup endpoint.
     pub rollup_endpoint: Endpoint,
+    /// Other rollup nodes of the same rollup, in order of preference, which reads
+    /// fail over to when the rollup endpoint is unavailable.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rollup_fallback_nodes: Vec<FallbackRollupNode>,
     /// The path to the rollup preimages directory.
     pub rollup_preimages_dir: PathBuf,
     /// The path to the rollup kernel log file.
@@ -191,6 +344,74 @@ pub struct JstzNodeConfig {
     /// Path to the sqlite db file that keeps the runtime state.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub runtime_db_path: Option<PathBuf>,
+    /// Path to the rollup node log file. When set, rollup node events are reported
+    /// in the detailed health check.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rollup_log_file: Option<PathBuf>,
+    /// Quotas of operation injection. When not set, injection is not rate limited.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimitConfig>,
+    #[serde(skip)]
+    /// Bearer token required by the operation and admin endpoints, falling back to
+    /// [`AUTH_TOKEN_ENV`]. When not set, the operation endpoints are public and the
+    /// admin endpoints are disabled.
+    pub auth_token: Option<String>,
+    /// Export of traces to an OpenTelemetry collector. When not set, traces are not exported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telemetry: Option<TelemetryConfig>,
+    /// Order in which the sequencer executes the queued operations.
+    #[serde(default)]
+    pub queue_ordering: QueueOrdering,
+    /// Interval, in milliseconds, at which the sequencer publishes the operations it
+    /// executed to the rollup inbox. When not set, operations are not published.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blueprint_interval_ms: Option<u64>,
+    /// Number of L1 levels after which the sequencer executes the operations
+    /// submitted directly to the rollup inbox, before the inbox messages of that
+    /// level. When not set, operations are executed in the order they were read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force_inclusion_delay: Option<u32>,
+    /// Snapshot to bootstrap the runtime database and the inbox checkpoint of a
+    /// sequencer from, see [`crate::sequencer::snapshot`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub import_snapshot: Option<PathBuf>,
+    /// Limits on the operations of each account injected in the sequencer. When not
+    /// set, accounts are only limited by the capacity of the queue.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_quota: Option<AccountQuota>,
+    /// Resource limits above which the sequencer worker is recycled. When not set,
+    /// the resource usage of the worker is not monitored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worker_watchdog: Option<WatchdogConfig>,
+    /// Retention of the receipt attestations, states and logs of the executed
+    /// operations. When not set, they are kept forever. Receipts are pruned by the
+    /// kernel only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt_retention: Option<RetentionConfig>,
+    /// Compression of the responses and decompression of the requests. When not set,
+    /// bodies are sent and read as is.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<CompressionConfig>,
+    /// Smart function of the name registry served under `/names` and
+    /// `/addresses/{address}/name`. When not set, these endpoints are disabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_registry: Option<SmartFunctionHash>,
+    /// Origins allowed to make cross-origin requests. When not set, requests are
+    /// allowed from any origin.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cors_origins: Option<Vec<String>>,
+    /// Maximum level of the logs of the node. It cannot exceed the level the logger
+    /// was initialised with. When not set, the level of the logger is used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<LogLevel>,
+    /// Other rollups served by the node, each under `/r/{rollup_address}`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rollups: Vec<RollupContext>,
+    #[serde(skip)]
+    /// File this configuration was read from. When set, the file is watched and
+    /// changes to the tunable settings are applied without restarting the node, see
+    /// [`crate::reload`].
+    pub config_file: Option<ConfigFile>,
 }
 
 impl JstzNodeConfig {
@@ -211,12 +432,30 @@ impl JstzNodeConfig {
         Self {
             endpoint: endpoint.clone(),
             rollup_endpoint: rollup_endpoint.clone(),
+            rollup_fallback_nodes: vec![],
             rollup_preimages_dir: rollup_preimages_dir.to_path_buf(),
             kernel_log_file: kernel_log_file.to_path_buf(),
             injector,
             mode,
             storage_sync,
             runtime_db_path: None,
+            rollup_log_file: None,
+            rate_limit: None,
+            auth_token: None,
+            telemetry: None,
+            queue_ordering: QueueOrdering::default(),
+            blueprint_interval_ms: None,
+            force_inclusion_delay: None,
+            import_snapshot: None,
+            account_quota: None,
+            worker_watchdog: None,
+            receipt_retention: None,
+            compression: None,
+            name_registry: None,
+            cors_origins: None,
+            log_level: None,
+            rollups: vec![],
+            config_file: None,
         }
     }
 }
@@ -225,9 +464,10 @@ impl JstzNodeConfig {
 mod tests {
     use std::str::FromStr;
 
-    use jstz_crypto::{public_key::PublicKey, secret_key::SecretKey};
+    use jstz_crypto::{hash::Hash, public_key::PublicKey, secret_key::SecretKey};
 
     use super::*;
+    use crate::{compression::ContentEncoding, rate_limit::Quota};
 
     #[test]
     fn test_serialize_config() {
@@ -321,6 +561,170 @@ mod tests {
             .replace(PathBuf::from_str("/runtime_db").unwrap());
         let json = serde_json::to_value(&config).unwrap();
         assert_eq!(json["runtime_db_path"], "/runtime_db");
+
+        assert_eq!(json["rollup_log_file"], serde_json::Value::Null);
+        config
+            .rollup_log_file
+            .replace(PathBuf::from_str("/rollup.log").unwrap());
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["rollup_log_file"], "/rollup.log");
+
+        assert_eq!(json["rate_limit"], serde_json::Value::Null);
+        config.rate_limit.replace(RateLimitConfig {
+            per_address: Some(Quota {
+                burst: 10,
+                per_second: 0.5,
+            }),
+            per_ip: None,
+            client_ip_header: None,
+        });
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(
+            json["rate_limit"],
+            serde_json::json!({"per_address": {"burst": 10, "per_second": 0.5}})
+        );
+
+        // the token is a secret and is never serialized
+        config.auth_token.replace("secret".to_string());
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json.get("auth_token"), None);
+
+        assert_eq!(json["telemetry"], serde_json::Value::Null);
+        config.telemetry.replace(TelemetryConfig::new(
+            "http://localhost:4318/v1/traces".to_string(),
+        ));
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(
+            json["telemetry"],
+            serde_json::json!({
+                "otlp_endpoint": "http://localhost:4318/v1/traces",
+                "service_name": "jstz-node"
+            })
+        );
+
+        assert_eq!(json["queue_ordering"], "fee");
+        config.queue_ordering = QueueOrdering::Fifo;
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["queue_ordering"], "fifo");
+
+        assert_eq!(json.get("blueprint_interval_ms"), None);
+        config.blueprint_interval_ms.replace(2000);
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["blueprint_interval_ms"], 2000);
+
+        assert_eq!(json.get("force_inclusion_delay"), None);
+        config.force_inclusion_delay.replace(30);
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["force_inclusion_delay"], 30);
+
+        assert_eq!(json.get("account_quota"), None);
+        config.account_quota.replace(AccountQuota {
+            max_pending: Some(5),
+            max_per_level: None,
+        });
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["account_quota"], serde_json::json!({"max_pending": 5}));
+
+        assert_eq!(json.get("worker_watchdog"), None);
+        config.worker_watchdog.replace(WatchdogConfig {
+            max_memory_mb: Some(2048),
+            max_cpu_percent: None,
+        });
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(
+            json["worker_watchdog"],
+            serde_json::json!({"max_memory_mb": 2048})
+        );
+
+        assert_eq!(json.get("receipt_retention"), None);
+        config.receipt_retention.replace(RetentionConfig {
+            levels: 1000,
+            pinned: HashSet::new(),
+        });
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(
+            json["receipt_retention"],
+            serde_json::json!({"levels": 1000})
+        );
+
+        assert_eq!(json.get("compression"), None);
+        config.compression.replace(CompressionConfig {
+            min_size: 512,
+            encodings: vec![ContentEncoding::Gzip, ContentEncoding::Br],
+        });
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(
+            json["compression"],
+            serde_json::json!({"min_size": 512, "encodings": ["gzip", "br"]})
+        );
+
+        assert_eq!(json.get("name_registry"), None);
+        config.name_registry.replace(
+            SmartFunctionHash::from_base58("KT19GXucGUitURBXXeEMMfqqhSQ5byt4P1zX")
+                .unwrap(),
+        );
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(
+            json["name_registry"],
+            "KT19GXucGUitURBXXeEMMfqqhSQ5byt4P1zX"
+        );
+
+        assert_eq!(json.get("rollup_fallback_nodes"), None);
+        config.rollup_fallback_nodes.push(FallbackRollupNode {
+            endpoint: Endpoint::localhost(8934),
+            preimages_dir: Some(PathBuf::from("/tmp/fallback/preimages")),
+            kernel_log_file: None,
+            log_file: None,
+        });
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(
+            json["rollup_fallback_nodes"],
+            serde_json::json!([{
+                "endpoint": "http://localhost:8934",
+                "preimages_dir": "/tmp/fallback/preimages"
+            }])
+        );
+        assert_eq!(
+            serde_json::from_value::<Vec<FallbackRollupNode>>(
+                json["rollup_fallback_nodes"].clone()
+            )
+            .unwrap(),
+            config.rollup_fallback_nodes
+        );
+
+        assert_eq!(json.get("cors_origins"), None);
+        assert_eq!(json.get("log_level"), None);
+        config.cors_origins = Some(vec!["https://app.jstz.dev".to_string()]);
+        config.log_level = Some(LogLevel::Debug);
+        config.config_file = Some(PathBuf::from("/etc/jstz/node.json").into());
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(
+            json["cors_origins"],
+            serde_json::json!(["https://app.jstz.dev"])
+        );
+        assert_eq!(json["log_level"], "debug");
+        assert_eq!(json.get("config_file"), None);
+
+        assert_eq!(json.get("rollups"), None);
+        config.rollups.push(RollupContext {
+            rollup_address: SmartRollupHash::from_base58_check(
+                "sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK",
+            )
+            .unwrap(),
+            rollup_endpoint: Endpoint::localhost(8935),
+            rollup_preimages_dir: PathBuf::from("/tmp/preimages"),
+            runtime_db_path: None,
+            kernel_log_file: None,
+        });
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(
+            json["rollups"],
+            serde_json::json!([{
+                "rollup_address": "sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK",
+                "rollup_endpoint": "http://localhost:8935",
+                "rollup_preimages_dir": "/tmp/preimages"
+            }])
+        );
     }
 
     #[test]
@@ -349,6 +753,13 @@ mod tests {
             .to_string(),
             "sequencer"
         );
+        assert_eq!(
+            RunMode::Replica {
+                primary_endpoint: "http://127.0.0.1:8933".to_string(),
+            }
+            .to_string(),
+            "replica"
+        );
     }
 
     #[test]
@@ -428,6 +839,30 @@ mod tests {
                 .to_string(),
             "smart rollup address is not configured for sequencer"
         );
+        assert_eq!(
+            RunModeBuilder::new(RunModeType::Sequencer)
+                .with_primary_endpoint("http://127.0.0.1:8933".to_string())
+                .unwrap_err()
+                .to_string(),
+            "primary endpoint can only be set when run mode is 'replica'"
+        );
+        assert_eq!(
+            RunModeBuilder::new(RunModeType::Replica)
+                .build()
+                .unwrap_err()
+                .to_string(),
+            "primary endpoint is not configured for replica"
+        );
+        assert_eq!(
+            RunModeBuilder::new(RunModeType::Replica)
+                .with_primary_endpoint("http://127.0.0.1:8933".to_string())
+                .unwrap()
+                .build()
+                .unwrap(),
+            RunMode::Replica {
+                primary_endpoint: "http://127.0.0.1:8933".to_string()
+            }
+        );
 
         // check default values
         let mode = RunModeBuilder::new(RunModeType::Sequencer)
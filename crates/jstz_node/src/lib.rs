@@ -1,45 +1,101 @@
 use anyhow::{Context, Result};
 use api_doc::{modify, ApiDoc};
-use axum::{extract::DefaultBodyLimit, http, routing::get};
+use axum::{
+    extract::DefaultBodyLimit,
+    routing::{get, post},
+};
 use config::JstzNodeConfig;
-use jstz_core::reveal_data::MAX_REVEAL_SIZE;
+use jstz_core::reveal_data::MAX_DECOMPRESSED_REVEAL_SIZE;
+use jstz_crypto::smart_function_hash::SmartFunctionHash;
 use jstz_utils::KeyPair;
-use octez::OctezRollupClient;
+use octez::{
+    r#async::rollup::log::RollupLogMonitor, FailoverRollupClient, RollupRpc,
+    HEALTH_CHECK_INTERVAL,
+};
+use reload::{ConfigFile, ConfigWatcher, Tunables, RELOAD_INTERVAL};
+use replica::{Replicator, REPLICA_POLL_INTERVAL};
 #[cfg(not(test))]
 use sequencer::inbox;
-use sequencer::{inbox::Monitor, queue::OperationQueue, worker};
+use sequencer::{
+    blueprint::{self, Publisher},
+    inbox::Monitor,
+    queue::OperationQueue,
+    retention::{self, Pruner, PRUNE_INTERVAL},
+    watchdog::WorkerStats,
+    worker::{self, Pause},
+};
 use services::{
     accounts::AccountsService,
+    code::CodeService,
+    deposits::DepositsService,
     logs::{broadcaster::Broadcaster, db::Db, LogsService},
+    names::NamesService,
     operations::OperationsService,
+    sequencer::SequencerService,
     utils,
 };
 use std::{
+    net::SocketAddr,
     path::PathBuf,
     sync::{atomic::AtomicU64, Arc, RwLock},
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 use tempfile::NamedTempFile;
-use tokio::{net::TcpListener, task::JoinSet};
-use tower_http::cors::{Any, CorsLayer};
+use tezos_smart_rollup::types::SmartRollupAddress;
+use tokio::{
+    net::TcpListener,
+    signal::unix::{signal, SignalKind},
+    task::JoinSet,
+};
+use tower_http::{
+    cors::{AllowOrigin, Any, CorsLayer},
+    trace::TraceLayer,
+};
 
 mod api_doc;
 mod services;
 pub mod storage_sync;
+mod typescript;
 use services::Service;
 use utoipa::OpenApi;
 use utoipa_axum::router::OpenApiRouter;
 use utoipa_scalar::{Scalar, Servable};
+pub mod auth;
+pub mod compression;
 pub mod config;
+pub mod periodic;
+pub mod rate_limit;
+pub mod reload;
+pub mod replica;
 pub mod sequencer;
+pub mod telemetry;
+pub mod tenants;
 pub use config::RunMode;
+pub use typescript::typescript_definitions_raw;
 
-use crate::config::RuntimeEnv;
+/// Counts the heap memory of the sequencer worker, see [`sequencer::watchdog`]
+#[global_allocator]
+static ALLOCATOR: sequencer::watchdog::WorkerAllocator =
+    sequencer::watchdog::WorkerAllocator;
+
+use crate::config::{
+    AccountQuota, FallbackRollupNode, LogLevel, QueueOrdering, RetentionConfig,
+    RollupContext, RuntimeEnv, WatchdogConfig,
+};
+use crate::{
+    auth::AuthLayer,
+    compression::CompressionConfig,
+    rate_limit::{RateLimitConfig, RateLimitLayer},
+    telemetry::TelemetryConfig,
+};
 
 #[derive(Clone)]
 pub struct AppState {
-    pub rollup_client: OctezRollupClient,
+    pub rollup_client: Arc<dyn RollupRpc>,
     pub rollup_preimages_dir: PathBuf,
+    /// Preimages directories of the fallback rollup nodes, where the preimages of
+    /// large operations are also written
+    pub rollup_fallback_preimages_dirs: Vec<PathBuf>,
     pub broadcaster: Arc<Broadcaster>,
     pub db: Db,
     pub injector: KeyPair,
@@ -47,8 +103,15 @@ pub struct AppState {
     pub queue: Arc<RwLock<OperationQueue>>,
     pub runtime_db: sequencer::db::Db,
     worker_heartbeat: Arc<AtomicU64>,
+    worker_paused: Arc<Pause>,
+    worker_restarts: Arc<AtomicU64>,
+    worker_stats: Arc<WorkerStats>,
     storage_sync: bool,
     storage_sync_db: sequencer::db::Db,
+    /// Monitor of the log file of each rollup node, in the order of their endpoints
+    rollup_log_monitors: Vec<Option<Arc<RollupLogMonitor>>>,
+    name_registry: Option<SmartFunctionHash>,
+    rollup_failover: Option<Arc<FailoverRollupClient>>,
 }
 
 impl AppState {
@@ -70,51 +133,148 @@ pub struct RunOptions {
     pub addr: String,
     pub port: u16,
     pub rollup_endpoint: String,
+    /// Other rollup nodes of the rollup, in order of preference. Reads fail over to
+    /// them when the rollup endpoint is unavailable.
+    pub rollup_fallbacks: Vec<FallbackRollupNode>,
     pub rollup_preimages_dir: PathBuf,
     pub kernel_log_path: PathBuf,
     pub injector: KeyPair,
     pub mode: RunMode,
     pub storage_sync: bool,
     pub runtime_db_path: Option<PathBuf>,
+    pub rollup_log_path: Option<PathBuf>,
+    pub rate_limit: Option<RateLimitConfig>,
+    pub auth_token: Option<String>,
+    pub telemetry: Option<TelemetryConfig>,
+    pub queue_ordering: QueueOrdering,
+    pub blueprint_interval: Option<Duration>,
+    pub force_inclusion_delay: Option<u32>,
+    pub import_snapshot: Option<PathBuf>,
+    pub account_quota: Option<AccountQuota>,
+    pub worker_watchdog: Option<WatchdogConfig>,
+    /// Retention of the receipts of the sequencer, see [`sequencer::retention`]
+    pub receipt_retention: Option<RetentionConfig>,
+    pub compression: Option<CompressionConfig>,
+    pub name_registry: Option<SmartFunctionHash>,
+    pub cors_origins: Option<Vec<String>>,
+    pub log_level: Option<LogLevel>,
+    /// Other rollups to serve, each under `/r/{rollup_address}`, see [`tenants`]
+    pub rollups: Vec<RollupContext>,
+    /// Configuration file to watch for changes of the tunable settings, see
+    /// [`reload`]
+    pub config_file: Option<ConfigFile>,
 }
 
 pub async fn run_with_config(config: JstzNodeConfig) -> Result<()> {
     let endpoint_addr = config.endpoint.host();
     let endpoint_port = config.endpoint.port();
-    let rollup_endpoint = config.rollup_endpoint.to_string();
     run(RunOptions {
         addr: endpoint_addr.to_string(),
         port: endpoint_port,
-        rollup_endpoint,
+        rollup_endpoint: config.rollup_endpoint.to_string(),
+        rollup_fallbacks: config.rollup_fallback_nodes,
         rollup_preimages_dir: config.rollup_preimages_dir.to_path_buf(),
         kernel_log_path: config.kernel_log_file.to_path_buf(),
         injector: config.injector,
         mode: config.mode,
         storage_sync: config.storage_sync,
         runtime_db_path: config.runtime_db_path,
+        rollup_log_path: config.rollup_log_file,
+        rate_limit: config.rate_limit,
+        auth_token: config.auth_token,
+        telemetry: config.telemetry,
+        queue_ordering: config.queue_ordering,
+        blueprint_interval: config.blueprint_interval_ms.map(Duration::from_millis),
+        force_inclusion_delay: config.force_inclusion_delay,
+        import_snapshot: config.import_snapshot,
+        account_quota: config.account_quota,
+        worker_watchdog: config.worker_watchdog,
+        receipt_retention: config.receipt_retention,
+        compression: config.compression,
+        name_registry: config.name_registry,
+        cors_origins: config.cors_origins,
+        log_level: config.log_level,
+        rollups: config.rollups,
+        config_file: config.config_file,
     })
     .await
 }
 
-pub async fn run(
+pub async fn run(options: RunOptions) -> Result<()> {
+    let telemetry = match &options.telemetry {
+        Some(config) => {
+            Some(telemetry::init(config).context("failed to init telemetry")?)
+        }
+        None => None,
+    };
+    let result = tokio::select! {
+        result = serve(options) => result,
+        // Stops on a signal so that the buffered spans are exported before exiting
+        result = shutdown_signal(), if telemetry.is_some() => result,
+    };
+    if let Some(telemetry) = telemetry {
+        telemetry.shut_down().await;
+    }
+    result
+}
+
+async fn shutdown_signal() -> Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = terminate.recv() => {}
+    };
+    Ok(())
+}
+
+async fn serve(
     RunOptions {
         addr,
         port,
         rollup_endpoint,
+        rollup_fallbacks,
         rollup_preimages_dir,
         kernel_log_path,
         injector,
         mode,
         storage_sync,
         runtime_db_path,
+        rollup_log_path,
+        rate_limit,
+        auth_token,
+        telemetry: _,
+        queue_ordering,
+        blueprint_interval,
+        force_inclusion_delay,
+        import_snapshot,
+        account_quota,
+        worker_watchdog,
+        receipt_retention,
+        compression,
+        name_registry,
+        cors_origins,
+        log_level,
+        rollups,
+        config_file,
     }: RunOptions,
 ) -> Result<()> {
-    let rollup_client = OctezRollupClient::new(rollup_endpoint.to_string());
-    let queue = Arc::new(RwLock::new(OperationQueue::new(match mode {
-        RunMode::Sequencer { capacity, .. } => capacity,
-        _ => 0,
-    })));
-
+    let default_log_level = log::max_level();
+    if let Some(level) = log_level {
+        log::set_max_level(level.into());
+    }
+    let rollup_endpoints: Vec<String> = std::iter::once(rollup_endpoint.clone())
+        .chain(
+            rollup_fallbacks
+                .iter()
+                .map(|node| node.endpoint.to_string()),
+        )
+        .collect();
+    let rollup_failover = Arc::new(
+        FailoverRollupClient::new(&rollup_endpoints)
+            .context("failed to create rollup client")?,
+    );
+    let _health_checks = rollup_failover.spawn_health_checks(HEALTH_CHECK_INTERVAL);
+    let rollup_client: Arc<dyn RollupRpc> = rollup_failover.clone();
     // When runtime_db_path is not provided, the db is created with a temp file rather than
     // with the in-memory setup to keep the behaviour consistent and avoid consuming
     // too much memory unexpectedly. If somehow path-to-str conversion fails, the in-memory
@@ -128,8 +288,45 @@ pub async fn run(
             (f.path().to_path_buf(), Some(f))
         }
     };
+    if let Some(snapshot) = import_snapshot {
+        let RunMode::Sequencer {
+            ref inbox_checkpoint_path,
+            ..
+        } = mode
+        else {
+            anyhow::bail!("snapshots can only be imported in sequencer mode");
+        };
+        let header =
+            sequencer::snapshot::import(&snapshot, &db_path, Some(inbox_checkpoint_path))
+                .context("failed to import snapshot")?;
+        log::info!(
+            "Imported snapshot of {} bytes with checksum {}",
+            header.size,
+            header.checksum
+        );
+    }
     let runtime_db = sequencer::db::Db::init(db_path.as_path().to_str())?;
 
+    // Operations left in the queue by a previous run are restored before the worker starts.
+    let queue = Arc::new(RwLock::new(match mode {
+        RunMode::Sequencer {
+            capacity,
+            ref ticketer_address,
+            ref rollup_address,
+            ..
+        } => OperationQueue::persistent(
+            capacity,
+            runtime_db.clone(),
+            ticketer_address,
+            rollup_address,
+        )
+        .context("failed to restore operation queue")?
+        .with_ordering(queue_ordering)
+        .with_force_inclusion_delay(force_inclusion_delay)
+        .with_account_quota(account_quota.unwrap_or_default()),
+        _ => OperationQueue::new(0),
+    }));
+
     let worker = match mode {
         #[cfg(not(test))]
         RunMode::Sequencer {
@@ -146,6 +343,7 @@ pub async fn run(
                 rollup_preimages_dir.clone(),
                 Some(debug_log_path),
                 runtime_env,
+                worker_watchdog,
             )
             .context("failed to launch worker")?,
         ),
@@ -166,6 +364,7 @@ pub async fn run(
                     rollup_preimages_dir.clone(),
                     Some(debug_log_path),
                     runtime_env,
+                    worker_watchdog,
                     move || {
                         std::fs::File::create(p).unwrap();
                     },
@@ -173,7 +372,7 @@ pub async fn run(
                 .context("failed to launch worker")?,
             )
         }
-        RunMode::Default => None,
+        RunMode::Default | RunMode::Replica { .. } => None,
     };
 
     let _monitor: Option<Monitor> = match mode {
@@ -185,7 +384,7 @@ pub async fn run(
             ..
         } => Some(
             inbox::spawn_monitor(
-                rollup_endpoint,
+                inbox::RollupEndpoint::Active(rollup_failover.clone()),
                 rollup_address.clone(),
                 ticketer_address.clone(),
                 queue.clone(),
@@ -195,19 +394,59 @@ pub async fn run(
         ),
         #[cfg(test)]
         RunMode::Sequencer { .. } => None,
-        RunMode::Default => None,
+        RunMode::Default | RunMode::Replica { .. } => None,
+    };
+
+    let _publisher: Option<Publisher> = match (&mode, blueprint_interval) {
+        (RunMode::Sequencer { rollup_address, .. }, Some(interval)) => {
+            Some(blueprint::spawn_publisher(
+                runtime_db.clone(),
+                rollup_client.clone(),
+                SmartRollupAddress::new(rollup_address.clone()),
+                injector.clone(),
+                interval,
+            ))
+        }
+        _ => None,
+    };
+
+    let _replicator: Option<Replicator> = match &mode {
+        RunMode::Replica { primary_endpoint } => Some(replica::spawn(
+            runtime_db.clone(),
+            primary_endpoint.clone(),
+            REPLICA_POLL_INTERVAL,
+        )),
+        _ => None,
     };
 
     // LogsService expects the log file to exist at instantiation, so this needs to be called after
     // debug log file is created.
     let log_file_path = match mode {
-        RunMode::Default => kernel_log_path.clone(),
+        RunMode::Default | RunMode::Replica { .. } => kernel_log_path.clone(),
         RunMode::Sequencer {
             ref debug_log_path, ..
         } => debug_log_path.clone(),
     };
 
-    let (broadcaster, db, log_service_handle) = LogsService::init(&log_file_path).await?;
+    let (broadcaster, db, log_service_handle) = match mode {
+        RunMode::Default | RunMode::Replica { .. } => {
+            let paths = std::iter::once(Some(log_file_path))
+                .chain(rollup_fallbacks.iter().map(|n| n.kernel_log_file.clone()))
+                .collect();
+            LogsService::follow(paths, rollup_failover.subscribe()).await?
+        }
+        RunMode::Sequencer { .. } => LogsService::init(&log_file_path).await?,
+    };
+
+    let _pruner: Option<Pruner> = match (&mode, receipt_retention) {
+        (RunMode::Sequencer { .. }, Some(config)) => Some(retention::spawn(
+            runtime_db.clone(),
+            db.clone(),
+            config,
+            PRUNE_INTERVAL,
+        )),
+        _ => None,
+    };
 
     let (storage_sync_db, _storage_sync_db_file) = temp_db()?;
     let mut storage_sync_handles = JoinSet::new();
@@ -234,9 +473,43 @@ pub async fn run(
         )?);
     };
 
+    let rollup_log_monitors = std::iter::once(rollup_log_path)
+        .chain(rollup_fallbacks.iter().map(|node| node.log_file.clone()))
+        .map(|path| path.map(|p| Arc::new(RollupLogMonitor::spawn(&p))))
+        .collect();
+    let rollup_fallback_preimages_dirs = rollup_fallbacks
+        .into_iter()
+        .filter_map(|node| node.preimages_dir)
+        .collect();
+
+    let cors_origins = Arc::new(parking_lot::RwLock::new(cors_origins));
+    // The quotas can be set by reloading the configuration, so the layer is added
+    // even without quotas when the configuration file is watched
+    let rate_limit = match (rate_limit, config_file.is_some()) {
+        (None, false) => None,
+        (config, _) => Some(RateLimitLayer::new(config.unwrap_or_default())),
+    };
+    let _config_watcher: Option<ConfigWatcher> = match (config_file, &rate_limit) {
+        (Some(file), Some(rate_limit)) => Some(
+            reload::spawn(
+                file,
+                Tunables {
+                    rate_limit: rate_limit.clone(),
+                    cors_origins: cors_origins.clone(),
+                    queue: queue.clone(),
+                    default_log_level,
+                },
+                RELOAD_INTERVAL,
+            )
+            .context("failed to watch the configuration file")?,
+        ),
+        _ => None,
+    };
+
     let state = AppState {
         rollup_client,
         rollup_preimages_dir,
+        rollup_fallback_preimages_dirs,
         broadcaster,
         db,
         injector,
@@ -244,18 +517,36 @@ pub async fn run(
         queue,
         runtime_db,
         worker_heartbeat: worker.as_ref().map(|w| w.heartbeat()).unwrap_or_default(),
+        worker_paused: worker.as_ref().map(|w| w.paused()).unwrap_or_default(),
+        worker_restarts: worker.as_ref().map(|w| w.restarts()).unwrap_or_default(),
+        worker_stats: worker.as_ref().map(|w| w.stats()).unwrap_or_default(),
         storage_sync,
         storage_sync_db,
+        rollup_log_monitors,
+        name_registry,
+        rollup_failover: Some(rollup_failover),
     };
 
-    let cors = CorsLayer::new()
-        .allow_methods(Any)
-        .allow_origin(Any)
-        .allow_headers(Any);
-
-    let (router, mut openapi) = router().with_state(state).layer(cors).split_for_parts();
+    let tenants = tenants::init(rollups, &state)
+        .await
+        .context("failed to init rollups")?;
+    let (router, mut openapi) = router().with_state(state).split_for_parts();
+    let mut router =
+        tenants::nest(router, &tenants, tenant_routes).layer(cors(cors_origins));
+    if let Some(layer) = rate_limit {
+        router = router.layer(layer);
+    }
+    // Applied last so that unauthorized requests do not take from the quotas
+    router = router.layer(AuthLayer::new(auth_token));
+    // Applied after the layers reading request bodies, which read them decompressed
+    if let Some(config) = &compression {
+        router = compression::apply(router, config);
+    }
+    router = router.layer(TraceLayer::new_for_http());
     modify(&mut openapi);
-    let router = router.merge(Scalar::with_url("/scalar", openapi));
+    let router = router
+        .merge(Scalar::with_url("/scalar", openapi))
+        .into_make_service_with_connect_info::<SocketAddr>();
 
     let listener = TcpListener::bind(format!("{addr}:{port}")).await?;
 
@@ -280,6 +571,9 @@ pub async fn run(
     };
 
     log_service_handle.shutdown().await?;
+    for tenant in tenants {
+        tenant.shut_down().await?;
+    }
     Ok(())
 }
 
@@ -291,15 +585,44 @@ fn temp_db() -> Result<(sequencer::db::Db, NamedTempFile)> {
     Ok((sequencer::db::Db::init(Some(db_path))?, db_file))
 }
 
+/// Allows cross-origin requests from the origins in `origins`, or from any origin
+/// if not set. The origins are read on each request, so that they can be reloaded.
+fn cors(origins: Arc<parking_lot::RwLock<Option<Vec<String>>>>) -> CorsLayer {
+    CorsLayer::new()
+        .allow_methods(Any)
+        .allow_origin(AllowOrigin::predicate(move |origin, _| {
+            match origins.read().as_ref() {
+                Some(origins) => {
+                    origins.iter().any(|o| o.as_bytes() == origin.as_bytes())
+                }
+                None => true,
+            }
+        }))
+        .allow_headers(Any)
+}
+
 fn router() -> OpenApiRouter<AppState> {
     OpenApiRouter::with_openapi(ApiDoc::openapi())
         .merge(OperationsService::router_with_openapi())
         .merge(AccountsService::router_with_openapi())
+        .merge(CodeService::router_with_openapi())
+        .merge(DepositsService::router_with_openapi())
         .merge(LogsService::router_with_openapi())
+        .merge(SequencerService::router_with_openapi())
+        .merge(NamesService::router_with_openapi())
         .route("/mode", get(utils::get_mode))
-        .route("/health", get(http::StatusCode::OK))
+        .route("/constants", get(utils::constants))
+        .route("/health", get(utils::health))
         .route("/worker/health", get(utils::worker_health))
-        .layer(DefaultBodyLimit::max(MAX_REVEAL_SIZE))
+        .route("/health/details", get(utils::health_details))
+        .route("/admin/sequencer/pause", post(utils::pause_worker))
+        .route("/admin/sequencer/resume", post(utils::resume_worker))
+        .layer(DefaultBodyLimit::max(MAX_DECOMPRESSED_REVEAL_SIZE))
+}
+
+/// Routes served for the other rollups of the node, see [`tenants`]
+fn tenant_routes() -> axum::Router<AppState> {
+    router().split_for_parts().0
 }
 
 pub fn openapi_json_raw() -> anyhow::Result<String> {
@@ -322,7 +645,10 @@ mod test {
         secret_key::SecretKey,
     };
     use jstz_proto::context::account::{Account, Nonce, UserAccount};
-    use jstz_utils::test_util::append_async;
+    use jstz_utils::{
+        retry::{retry, RetryPolicy},
+        test_util::append_async,
+    };
     use octez::unused_port;
     use pretty_assertions::assert_eq;
     use tempfile::{NamedTempFile, TempDir};
@@ -386,18 +712,36 @@ mod test {
                 addr: "0.0.0.0".to_string(),
                 port,
                 rollup_endpoint: "0.0.0.0:5678".to_string(),
+                rollup_fallbacks: vec![],
                 rollup_preimages_dir: TempDir::new().unwrap().into_path(),
                 kernel_log_path: kernel_log_file.path().to_path_buf(),
                 injector: default_injector(),
                 mode: mode.clone(),
                 storage_sync: false,
                 runtime_db_path: None,
+                rollup_log_path: None,
+                rate_limit: None,
+                auth_token: None,
+                telemetry: None,
+                queue_ordering: QueueOrdering::default(),
+                blueprint_interval: None,
+                force_inclusion_delay: None,
+                import_snapshot: None,
+                account_quota: None,
+                worker_watchdog: None,
+                receipt_retention: None,
+                compression: None,
+                name_registry: None,
+                cors_origins: None,
+                log_level: None,
+                rollups: vec![],
+                config_file: None,
             }));
 
-            let res = jstz_utils::poll(10, 500, || async {
+            let policy =
+                RetryPolicy::fixed(Duration::from_millis(500)).with_max_attempts(10);
+            let res = retry(policy, || {
                 reqwest::get(format!("http://0.0.0.0:{port}/mode"))
-                    .await
-                    .ok()
             })
             .await
             .expect("should get response")
@@ -450,12 +794,30 @@ mod test {
                 addr: "0.0.0.0".to_string(),
                 port,
                 rollup_endpoint,
+                rollup_fallbacks: vec![],
                 rollup_preimages_dir,
                 kernel_log_path: kernel_log_file.path().to_path_buf(),
                 injector: default_injector(),
                 mode,
                 storage_sync: false,
                 runtime_db_path: None,
+                rollup_log_path: None,
+                rate_limit: None,
+                auth_token: None,
+                telemetry: None,
+                queue_ordering: QueueOrdering::default(),
+                blueprint_interval: None,
+                force_inclusion_delay: None,
+                import_snapshot: None,
+                account_quota: None,
+                worker_watchdog: None,
+                receipt_retention: None,
+                compression: None,
+                name_registry: None,
+                cors_origins: None,
+                log_level: None,
+                rollups: vec![],
+                config_file: None,
             }));
 
             sleep(Duration::from_secs(1)).await;
@@ -550,13 +912,31 @@ mod test {
         tokio::spawn(run(RunOptions {
             addr: "0.0.0.0".to_string(),
             port,
-            rollup_endpoint: "".to_string(),
+            rollup_endpoint: String::new(),
+            rollup_fallbacks: vec![],
             rollup_preimages_dir: TempDir::new().unwrap().into_path(),
             kernel_log_path: kernel_log_file.path().to_path_buf(),
             injector: default_injector(),
             mode,
             storage_sync: true,
             runtime_db_path: None,
+            rollup_log_path: None,
+            rate_limit: None,
+            auth_token: None,
+            telemetry: None,
+            queue_ordering: QueueOrdering::default(),
+            blueprint_interval: None,
+            force_inclusion_delay: None,
+            import_snapshot: None,
+            account_quota: None,
+            worker_watchdog: None,
+            receipt_retention: None,
+            compression: None,
+            name_registry: None,
+            cors_origins: None,
+            log_level: None,
+            rollups: vec![],
+            config_file: None,
         }))
     }
 
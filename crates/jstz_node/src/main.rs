@@ -1,14 +1,22 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use anyhow::Context;
 use clap::ArgAction;
 use clap::Parser;
 use env_logger::Env;
+use jstz_crypto::{hash::Hash, smart_function_hash::SmartFunctionHash};
 use jstz_node::{
-    config::{RunModeBuilder, RunModeType},
+    compression::{CompressionConfig, ContentEncoding, DEFAULT_MIN_SIZE},
+    config::{
+        AccountQuota, FallbackRollupNode, LogLevel, QueueOrdering, RetentionConfig,
+        RunModeBuilder, RunModeType, WatchdogConfig, AUTH_TOKEN_ENV,
+    },
+    rate_limit::{Quota, RateLimitConfig},
+    telemetry::TelemetryConfig,
     RunOptions,
 };
-use jstz_utils::key_pair::parse_key_file;
+use jstz_utils::key_pair::{KeyPair, KeySource};
+use octez::r#async::endpoint::Endpoint;
 use tezos_crypto_rs::hash::ContractKt1Hash;
 use tezos_crypto_rs::hash::SmartRollupHash;
 
@@ -21,6 +29,7 @@ const DEFAULT_JSTZ_NODE_ADDR: &str = "127.0.0.1";
 const DEFAULT_JSTZ_NODE_PORT: u16 = 8933;
 const DEFAULT_RUN_MODE: &str = "default";
 const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+const DEFAULT_QUEUE_ORDERING: &str = "fee";
 
 #[derive(Debug, Parser)]
 enum Command {
@@ -30,6 +39,33 @@ enum Command {
         #[arg(short, long)]
         out: Option<PathBuf>,
     },
+    Types {
+        /// Output path of the TypeScript definitions
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Snapshots of the runtime database of a sequencer node
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommand,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum SnapshotCommand {
+    /// Export a compressed snapshot of the runtime database, along with the inbox
+    /// checkpoint
+    Export {
+        #[arg(long)]
+        runtime_db_path: PathBuf,
+
+        #[arg(long)]
+        inbox_checkpoint_path: Option<PathBuf>,
+
+        /// Output path of the snapshot
+        #[arg(short, long)]
+        out: PathBuf,
+    },
 }
 
 #[derive(Debug, Parser)]
@@ -46,8 +82,24 @@ struct Args {
     #[arg(long, default_value_t = DEFAULT_ROLLUP_RPC_PORT)]
     rollup_node_rpc_port: u16,
 
-    #[arg(short, long)]
-    rollup_endpoint: Option<String>,
+    /// Endpoints of the rollup nodes, in order of preference. Reads fail over to the
+    /// next endpoints when a rollup node is unavailable. Defaults to the rollup node
+    /// RPC address and port.
+    #[arg(short, long, value_delimiter = ',')]
+    rollup_endpoint: Vec<Endpoint>,
+
+    /// Preimages directories of the fallback rollup nodes, in the order of their
+    /// endpoints
+    #[arg(long, value_delimiter = ',')]
+    fallback_preimages_dir: Vec<PathBuf>,
+
+    /// Kernel log files of the fallback rollup nodes, in the order of their endpoints
+    #[arg(long, value_delimiter = ',')]
+    fallback_kernel_log_path: Vec<PathBuf>,
+
+    /// Log files of the fallback rollup nodes, in the order of their endpoints
+    #[arg(long, value_delimiter = ',')]
+    fallback_rollup_log_path: Vec<PathBuf>,
 
     #[arg(long, default_value = DEFAULT_KERNEL_LOG_PATH)]
     kernel_log_path: PathBuf,
@@ -64,9 +116,10 @@ struct Args {
     #[arg(long)]
     debug_log_path: Option<PathBuf>,
 
-    /// Path to file containing injector key pair (format: {"public_key": ..., "secret_key": ...})
+    /// Injector key pair, either a path to a key file or `env:<VAR>` (format: {"public_key": ..., "secret_key": ...}).
+    /// Encrypted key files are decrypted with the password in `JSTZ_KEYSTORE_PASSWORD`.
     #[arg(long)]
-    injector_key_file: PathBuf,
+    injector_key_file: KeySource,
 
     #[arg(long, required_if_eq("mode", "sequencer"))]
     rollup_address: Option<String>,
@@ -77,6 +130,10 @@ struct Args {
     #[arg(long)]
     riscv_kernel_path: Option<PathBuf>,
 
+    /// Endpoint of the sequencer node a replica follows and forwards operations to
+    #[arg(long, required_if_eq("mode", "replica"))]
+    primary_endpoint: Option<String>,
+
     #[arg(long, action = ArgAction::SetTrue)]
     storage_sync: bool,
 
@@ -85,6 +142,111 @@ struct Args {
 
     #[arg(long)]
     inbox_checkpoint_path: Option<PathBuf>,
+
+    /// Path to the rollup node log file, used to report rollup node health
+    #[arg(long)]
+    rollup_log_path: Option<PathBuf>,
+
+    /// Operation injection quota of each source address, as `<burst>:<per_second>`
+    #[arg(long)]
+    address_rate_limit: Option<Quota>,
+
+    /// Operation injection quota of each client IP, as `<burst>:<per_second>`
+    #[arg(long)]
+    ip_rate_limit: Option<Quota>,
+
+    /// Header in which a trusted reverse proxy reports the client IP, e.g.
+    /// X-Forwarded-For. Only set it if the node is reachable only through the proxy
+    #[arg(long)]
+    client_ip_header: Option<String>,
+
+    /// Bearer token required to inject operations and call the admin endpoints,
+    /// read from JSTZ_NODE_AUTH_TOKEN if not set. When neither is set, operations can
+    /// be injected by anyone and the admin endpoints are disabled
+    #[arg(long)]
+    auth_token: Option<String>,
+
+    /// OTLP/HTTP endpoint of the OpenTelemetry collector to export traces to
+    #[arg(long)]
+    otlp_endpoint: Option<String>,
+
+    /// Order in which the sequencer executes the queued operations
+    #[arg(long, default_value = DEFAULT_QUEUE_ORDERING)]
+    queue_ordering: QueueOrdering,
+
+    /// Interval, in milliseconds, at which the sequencer publishes the operations it
+    /// executed to the rollup inbox
+    #[arg(long)]
+    blueprint_interval_ms: Option<u64>,
+
+    /// Number of L1 levels after which the sequencer executes the operations
+    /// submitted directly to the rollup inbox, before the inbox messages of that level
+    #[arg(long)]
+    force_inclusion_delay: Option<u32>,
+
+    /// Snapshot exported with `snapshot export` to bootstrap the runtime database and
+    /// the inbox checkpoint of a sequencer from. The runtime database must not exist
+    #[arg(long)]
+    import_snapshot: Option<PathBuf>,
+
+    /// Maximum number of queued operations of each account in sequencer mode
+    #[arg(long)]
+    max_pending_per_account: Option<usize>,
+
+    /// Maximum number of operations each account can queue during an L1 level in
+    /// sequencer mode
+    #[arg(long)]
+    max_ops_per_level_per_account: Option<usize>,
+
+    /// Heap memory of the sequencer worker, in megabytes, above which the worker is
+    /// recycled
+    #[arg(long)]
+    worker_max_memory_mb: Option<u64>,
+
+    /// CPU usage of the sequencer worker thread, in percent of a core, above which
+    /// the worker is recycled
+    #[arg(long)]
+    worker_max_cpu_percent: Option<f64>,
+
+    /// Number of L1 levels the sequencer keeps the receipt attestation, state and
+    /// logs of an operation for. They are kept forever if not set. Receipts are
+    /// pruned by the kernel only
+    #[arg(long)]
+    receipt_retention_levels: Option<u32>,
+
+    /// Hashes of the operations whose records and logs are never pruned, separated
+    /// by commas
+    #[arg(long, value_delimiter = ',')]
+    pinned_receipts: Vec<String>,
+
+    /// Encodings used to compress the responses and decompress the requests,
+    /// separated by commas. Bodies are not compressed if not set
+    #[arg(long, value_delimiter = ',')]
+    compression: Vec<ContentEncoding>,
+
+    /// Size, in bytes, under which responses are not compressed
+    #[arg(long, default_value_t = DEFAULT_MIN_SIZE)]
+    compression_min_size: u16,
+
+    /// Address of the name registry smart function, whose names are served under
+    /// `/names/{name}` and `/addresses/{address}/name`
+    #[arg(long)]
+    name_registry: Option<String>,
+
+    /// Origins allowed to make cross-origin requests, separated by commas. Requests
+    /// are allowed from any origin if not set
+    #[arg(long, value_delimiter = ',')]
+    cors_origins: Vec<String>,
+
+    /// Maximum level of the logs of the node, up to the level of `RUST_LOG`
+    #[arg(long)]
+    log_level: Option<LogLevel>,
+
+    /// JSON file of the settings that can be changed without restarting the node:
+    /// `rate_limit`, `cors_origins`, `capacity` and `log_level`. The file overrides
+    /// the flags and is watched for changes
+    #[arg(long)]
+    config: Option<PathBuf>,
 }
 
 #[tokio::main]
@@ -92,13 +254,25 @@ async fn main() -> anyhow::Result<()> {
     env_logger::init_from_env(Env::default().default_filter_or("jstz_node=info"));
     match Command::parse() {
         Command::Run(args) => {
-            let rollup_endpoint = args.rollup_endpoint.unwrap_or(format!(
-                "http://{}:{}",
-                args.rollup_node_rpc_addr, args.rollup_node_rpc_port
-            ));
+            let mut rollup_endpoints = args.rollup_endpoint.into_iter();
+            let rollup_endpoint = match rollup_endpoints.next() {
+                Some(endpoint) => endpoint.to_string(),
+                None => format!(
+                    "http://{}:{}",
+                    args.rollup_node_rpc_addr, args.rollup_node_rpc_port
+                ),
+            };
+            let rollup_fallbacks = fallback_rollup_nodes(
+                rollup_endpoints.collect(),
+                args.fallback_preimages_dir,
+                args.fallback_kernel_log_path,
+                args.fallback_rollup_log_path,
+            )?;
 
-            let mut run_mode_builder =
-                RunModeBuilder::new(args.mode).with_capacity(args.capacity)?;
+            let mut run_mode_builder = RunModeBuilder::new(args.mode.clone());
+            if let RunModeType::Sequencer = args.mode {
+                run_mode_builder = run_mode_builder.with_capacity(args.capacity)?;
+            }
             if let Some(path) = args.debug_log_path {
                 run_mode_builder = run_mode_builder.with_debug_log_path(path)?;
             }
@@ -116,17 +290,71 @@ async fn main() -> anyhow::Result<()> {
                 run_mode_builder = run_mode_builder
                     .with_ticketer_address(ContractKt1Hash::from_base58_check(&addr)?)?;
             }
+            if let Some(endpoint) = args.primary_endpoint {
+                run_mode_builder = run_mode_builder.with_primary_endpoint(endpoint)?;
+            }
             jstz_node::run(RunOptions {
                 addr: args.addr,
                 port: args.port,
                 rollup_endpoint,
+                rollup_fallbacks,
                 rollup_preimages_dir: args.preimages_dir,
                 kernel_log_path: args.kernel_log_path,
-                injector: parse_key_file(args.injector_key_file)
+                injector: KeyPair::load(&args.injector_key_file)
                     .context("failed to parse injector key file")?,
                 mode: run_mode_builder.build()?,
                 storage_sync: args.storage_sync,
                 runtime_db_path: args.runtime_db_path,
+                rollup_log_path: args.rollup_log_path,
+                rate_limit: (args.address_rate_limit.is_some()
+                    || args.ip_rate_limit.is_some())
+                .then_some(RateLimitConfig {
+                    per_address: args.address_rate_limit,
+                    per_ip: args.ip_rate_limit,
+                    client_ip_header: args.client_ip_header,
+                }),
+                auth_token: args
+                    .auth_token
+                    .or_else(|| std::env::var(AUTH_TOKEN_ENV).ok()),
+                telemetry: args.otlp_endpoint.map(TelemetryConfig::new),
+                queue_ordering: args.queue_ordering,
+                blueprint_interval: args.blueprint_interval_ms.map(Duration::from_millis),
+                force_inclusion_delay: args.force_inclusion_delay,
+                import_snapshot: args.import_snapshot,
+                account_quota: (args.max_pending_per_account.is_some()
+                    || args.max_ops_per_level_per_account.is_some())
+                .then_some(AccountQuota {
+                    max_pending: args.max_pending_per_account,
+                    max_per_level: args.max_ops_per_level_per_account,
+                }),
+                worker_watchdog: (args.worker_max_memory_mb.is_some()
+                    || args.worker_max_cpu_percent.is_some())
+                .then_some(WatchdogConfig {
+                    max_memory_mb: args.worker_max_memory_mb,
+                    max_cpu_percent: args.worker_max_cpu_percent,
+                }),
+                receipt_retention: args.receipt_retention_levels.map(|levels| {
+                    RetentionConfig {
+                        levels,
+                        pinned: args.pinned_receipts.into_iter().collect(),
+                    }
+                }),
+                compression: (!args.compression.is_empty()).then_some(
+                    CompressionConfig {
+                        min_size: args.compression_min_size,
+                        encodings: args.compression,
+                    },
+                ),
+                name_registry: args
+                    .name_registry
+                    .map(|addr| SmartFunctionHash::from_base58(&addr))
+                    .transpose()
+                    .context("failed to parse name registry address")?,
+                cors_origins: (!args.cors_origins.is_empty())
+                    .then_some(args.cors_origins),
+                log_level: args.log_level,
+                rollups: vec![],
+                config_file: args.config.map(Into::into),
             })
             .await
         }
@@ -138,5 +366,65 @@ async fn main() -> anyhow::Result<()> {
             }
             Ok(())
         }
+        Command::Types { out } => {
+            let types = jstz_node::typescript_definitions_raw()?;
+            match out {
+                Some(out) => std::fs::write(out, types)?,
+                None => print!("{types}"),
+            }
+            Ok(())
+        }
+        Command::Snapshot {
+            command:
+                SnapshotCommand::Export {
+                    runtime_db_path,
+                    inbox_checkpoint_path,
+                    out,
+                },
+        } => {
+            let header = jstz_node::sequencer::snapshot::export(
+                &runtime_db_path,
+                inbox_checkpoint_path.as_deref(),
+                &out,
+            )?;
+            println!(
+                "Exported snapshot of {} bytes with checksum {}",
+                header.size, header.checksum
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Matches the paths of the fallback rollup nodes to their endpoints, by position
+fn fallback_rollup_nodes(
+    endpoints: Vec<Endpoint>,
+    preimages_dirs: Vec<PathBuf>,
+    kernel_log_files: Vec<PathBuf>,
+    log_files: Vec<PathBuf>,
+) -> anyhow::Result<Vec<FallbackRollupNode>> {
+    for (flag, len) in [
+        ("--fallback-preimages-dir", preimages_dirs.len()),
+        ("--fallback-kernel-log-path", kernel_log_files.len()),
+        ("--fallback-rollup-log-path", log_files.len()),
+    ] {
+        if len > endpoints.len() {
+            anyhow::bail!(
+                "{flag} has {len} values but there are {} fallback rollup endpoints",
+                endpoints.len()
+            );
+        }
     }
+    let mut preimages_dirs = preimages_dirs.into_iter();
+    let mut kernel_log_files = kernel_log_files.into_iter();
+    let mut log_files = log_files.into_iter();
+    Ok(endpoints
+        .into_iter()
+        .map(|endpoint| FallbackRollupNode {
+            endpoint,
+            preimages_dir: preimages_dirs.next(),
+            kernel_log_file: kernel_log_files.next(),
+            log_file: log_files.next(),
+        })
+        .collect())
 }
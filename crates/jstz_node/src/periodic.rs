@@ -0,0 +1,88 @@
+//! Background tasks of the node run at a fixed interval.
+
+use std::{future::Future, time::Duration};
+
+use async_dropper_simple::AsyncDrop;
+use async_trait::async_trait;
+use tokio::{select, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+/// A task running in the background until it is shut down or dropped
+#[derive(Default)]
+pub struct PeriodicTask {
+    inner: Option<JoinHandle<()>>,
+    kill_sig: CancellationToken,
+}
+
+impl PeriodicTask {
+    /// Spawns a task calling `tick` every `interval`, starting immediately. A tick
+    /// is never interrupted by the shut down.
+    pub fn spawn<F, Fut>(interval: Duration, mut tick: F) -> Self
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let kill_sig = CancellationToken::new();
+        let kill_sig_clone = kill_sig.clone();
+        let inner = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                select! {
+                    _ = kill_sig_clone.cancelled() => break,
+                    _ = ticker.tick() => tick().await,
+                }
+            }
+        });
+        Self {
+            inner: Some(inner),
+            kill_sig,
+        }
+    }
+
+    pub async fn shut_down(&mut self) {
+        self.kill_sig.cancel();
+        if let Some(h) = self.inner.take() {
+            let _ = h.await;
+        }
+    }
+}
+
+#[async_trait]
+impl AsyncDrop for PeriodicTask {
+    async fn async_drop(&mut self) {
+        self.shut_down().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::PeriodicTask;
+
+    #[tokio::test]
+    async fn stops_on_shut_down() {
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let ticks_clone = ticks.clone();
+        let mut task = PeriodicTask::spawn(Duration::from_millis(1), move || {
+            ticks_clone.fetch_add(1, Ordering::SeqCst);
+            async {}
+        });
+        while ticks.load(Ordering::SeqCst) < 2 {
+            tokio::task::yield_now().await;
+        }
+        task.shut_down().await;
+        // the task is joined, so it does not tick anymore
+        let n = ticks.load(Ordering::SeqCst);
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(ticks.load(Ordering::SeqCst), n);
+    }
+}
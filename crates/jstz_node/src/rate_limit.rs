@@ -0,0 +1,569 @@
+//! Rate limiting of operation injection.
+//!
+//! Injected operations are rate limited per source address and per client IP with
+//! token buckets: each key may inject up to `burst` operations at once, and regains
+//! `per_second` operations every second. Requests over quota are rejected with
+//! `429 Too Many Requests` and a `Retry-After` header. The injections of the rollups
+//! served under `/r/{rollup_address}` take from the same quotas.
+//!
+//! The client IP is the address of the connection, unless the node runs behind a
+//! reverse proxy reporting the client IP in [`RateLimitConfig::client_ip_header`].
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request},
+    http::{header, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_util::future::BoxFuture;
+use jstz_core::reveal_data::MAX_DECOMPRESSED_REVEAL_SIZE;
+use jstz_crypto::public_key_hash::PublicKeyHash;
+use jstz_proto::operation::SignedOperation;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use tower::{Layer, Service};
+
+use crate::tenants::route_path;
+
+/// Path of the operation injection endpoint
+const INJECT_PATH: &str = "/operations";
+
+/// Number of buckets above which the full buckets are dropped
+const MAX_BUCKETS: usize = 10_000;
+
+/// Token bucket quota of a key
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(try_from = "UncheckedQuota")]
+pub struct Quota {
+    /// Number of operations that can be injected at once
+    pub burst: u32,
+    /// Number of operations regained every second
+    pub per_second: f64,
+}
+
+impl Quota {
+    /// Returns a quota, provided that keys can inject operations with it
+    pub fn new(burst: u32, per_second: f64) -> anyhow::Result<Self> {
+        if burst == 0 {
+            anyhow::bail!("burst should be positive");
+        }
+        // Also rejects NaN
+        if !(per_second > 0.0 && per_second.is_finite()) {
+            anyhow::bail!("rate should be a positive number");
+        }
+        Ok(Self { burst, per_second })
+    }
+}
+
+#[derive(Deserialize)]
+struct UncheckedQuota {
+    burst: u32,
+    per_second: f64,
+}
+
+impl TryFrom<UncheckedQuota> for Quota {
+    type Error = anyhow::Error;
+
+    fn try_from(
+        UncheckedQuota { burst, per_second }: UncheckedQuota,
+    ) -> anyhow::Result<Self> {
+        Self::new(burst, per_second)
+    }
+}
+
+impl FromStr for Quota {
+    type Err = anyhow::Error;
+
+    /// Parses a quota formatted as `<burst>:<per_second>`
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (burst, per_second) = s
+            .split_once(':')
+            .context("quota should be formatted as <burst>:<per_second>")?;
+        Self::new(
+            burst.parse().context("invalid burst")?,
+            per_second.parse().context("invalid rate")?,
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct RateLimitConfig {
+    /// Quota of each source address, unlimited if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_address: Option<Quota>,
+    /// Quota of each client IP, unlimited if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_ip: Option<Quota>,
+    /// Header in which a trusted reverse proxy reports the client IP, e.g.
+    /// `X-Forwarded-For`. The last address of the header is the client IP. Only set
+    /// it if clients cannot reach the node without going through the proxy, since
+    /// they could otherwise choose their IP.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ip_header: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Address(PublicKeyHash),
+    Ip(IpAddr),
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn new(quota: &Quota, now: Instant) -> Self {
+        Self {
+            tokens: quota.burst as f64,
+            updated_at: now,
+        }
+    }
+
+    fn refill(&mut self, quota: &Quota, now: Instant) {
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * quota.per_second).min(quota.burst as f64);
+        self.updated_at = now;
+    }
+
+    /// Returns the time until a token is available, if none is
+    fn wait_time(&self, quota: &Quota) -> Option<Duration> {
+        if self.tokens >= 1.0 {
+            return None;
+        }
+        let secs = (1.0 - self.tokens) / quota.per_second;
+        Some(Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX))
+    }
+}
+
+pub struct RateLimiter {
+    config: RwLock<RateLimitConfig>,
+    buckets: Mutex<HashMap<Key, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Replaces the quotas. Keys start over with a full bucket.
+    fn set_config(&self, config: RateLimitConfig) {
+        let mut buckets = self.buckets.lock();
+        *self.config.write() = config;
+        buckets.clear();
+    }
+
+    fn quota<'a>(config: &'a RateLimitConfig, key: &Key) -> Option<&'a Quota> {
+        match key {
+            Key::Address(_) => config.per_address.as_ref(),
+            Key::Ip(_) => config.per_ip.as_ref(),
+        }
+    }
+
+    /// Takes a token from the bucket of every key if all of them have one. Otherwise,
+    /// returns the time until they all do.
+    fn acquire(&self, keys: &[Key], now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock();
+        let config = self.config.read();
+        if buckets.len() > MAX_BUCKETS {
+            buckets.retain(|key, bucket| match Self::quota(&config, key) {
+                Some(quota) => {
+                    bucket.refill(quota, now);
+                    bucket.tokens < quota.burst as f64
+                }
+                None => false,
+            });
+        }
+
+        let mut wait_time = None;
+        for key in keys {
+            let Some(quota) = Self::quota(&config, key) else {
+                continue;
+            };
+            let bucket = buckets
+                .entry(key.clone())
+                .or_insert_with(|| Bucket::new(quota, now));
+            bucket.refill(quota, now);
+            wait_time = wait_time.max(bucket.wait_time(quota));
+        }
+        if let Some(wait_time) = wait_time {
+            return Err(wait_time);
+        }
+
+        for key in keys {
+            if let Some(bucket) = buckets.get_mut(key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Layer that rate limits the requests to the operation injection endpoint, see
+/// [`RateLimitConfig`]. Without a client IP header, the client IP is read from
+/// [`ConnectInfo`], so the router must be served with
+/// `into_make_service_with_connect_info::<SocketAddr>`.
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitLayer {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            limiter: Arc::new(RateLimiter::new(config)),
+        }
+    }
+
+    pub fn config(&self) -> RateLimitConfig {
+        self.limiter.config.read().clone()
+    }
+
+    /// Replaces the quotas of the services created by the layer
+    pub fn set_config(&self, config: RateLimitConfig) {
+        self.limiter.set_config(config);
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S> Service<Request> for RateLimit<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // The service that was polled ready must handle the request
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let limiter = self.limiter.clone();
+        Box::pin(async move {
+            if req.method() != Method::POST || route_path(req.uri().path()) != INJECT_PATH
+            {
+                return inner.call(req).await;
+            }
+
+            let mut keys = vec![];
+            let client_ip_header = limiter.config.read().client_ip_header.clone();
+            if let Some(ip) = client_ip(&req, client_ip_header.as_deref()) {
+                keys.push(Key::Ip(ip));
+            }
+            let (parts, body) = req.into_parts();
+            let Ok(bytes) =
+                axum::body::to_bytes(body, MAX_DECOMPRESSED_REVEAL_SIZE).await
+            else {
+                return Ok(StatusCode::PAYLOAD_TOO_LARGE.into_response());
+            };
+            // Malformed operations are rejected by the endpoint
+            if let Ok(operation) = serde_json::from_slice::<SignedOperation>(&bytes) {
+                keys.push(Key::Address(PublicKeyHash::from(&operation.public_key)));
+            }
+
+            match limiter.acquire(&keys, Instant::now()) {
+                Ok(()) => {
+                    inner
+                        .call(Request::from_parts(parts, Body::from(bytes)))
+                        .await
+                }
+                Err(wait_time) => Ok(too_many_requests(wait_time)),
+            }
+        })
+    }
+}
+
+/// Returns the IP reported in `header` by the proxy the request went through if any,
+/// or the address of the connection
+fn client_ip(req: &Request, header: Option<&str>) -> Option<IpAddr> {
+    // Proxies append the address they received the request from
+    let forwarded = header
+        .and_then(|header| req.headers().get_all(header).iter().last())
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|ip| ip.trim().parse().ok());
+    forwarded.or_else(|| {
+        req.extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+    })
+}
+
+fn too_many_requests(wait_time: Duration) -> Response {
+    let mut retry_after = wait_time.as_secs();
+    if wait_time.subsec_nanos() > 0 {
+        retry_after = retry_after.saturating_add(1);
+    }
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, HeaderValue::from(retry_after))],
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::SocketAddr,
+        time::{Duration, Instant},
+    };
+
+    use axum::{
+        body::Body,
+        extract::{ConnectInfo, Request},
+        http::{header, StatusCode},
+        routing::post,
+        Router,
+    };
+    use jstz_crypto::public_key_hash::PublicKeyHash;
+    use tower::ServiceExt;
+
+    use super::{client_ip, Key, Quota, RateLimitConfig, RateLimitLayer, RateLimiter};
+
+    fn address() -> Key {
+        Key::Address(
+            PublicKeyHash::from_base58("tz1KqTpEZ7Yob7QbPE4Hy4Wo8fHG8LhKxZSx").unwrap(),
+        )
+    }
+
+    fn ip() -> Key {
+        Key::Ip([127, 0, 0, 1].into())
+    }
+
+    #[test]
+    fn parse_quota() {
+        assert_eq!(
+            "10:0.5".parse::<Quota>().unwrap(),
+            Quota {
+                burst: 10,
+                per_second: 0.5
+            }
+        );
+        assert!("10".parse::<Quota>().is_err());
+        assert!("-1:1".parse::<Quota>().is_err());
+        assert!("0:1".parse::<Quota>().is_err());
+        assert!("1:0".parse::<Quota>().is_err());
+        assert!("1:-0.5".parse::<Quota>().is_err());
+        assert!("1:NaN".parse::<Quota>().is_err());
+        assert!("1:inf".parse::<Quota>().is_err());
+    }
+
+    #[test]
+    fn deserialize_quota() {
+        assert_eq!(
+            serde_json::from_str::<Quota>(r#"{"burst": 10, "per_second": 0.5}"#).unwrap(),
+            Quota {
+                burst: 10,
+                per_second: 0.5
+            }
+        );
+        assert!(
+            serde_json::from_str::<Quota>(r#"{"burst": 0, "per_second": 1}"#).is_err()
+        );
+        assert!(
+            serde_json::from_str::<Quota>(r#"{"burst": 1, "per_second": 0}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn client_ip_is_read_from_trusted_header() {
+        let mut request = Request::post("/operations").body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 8080))));
+        assert_eq!(client_ip(&request, None), Some([10, 0, 0, 1].into()));
+        assert_eq!(
+            client_ip(&request, Some("x-forwarded-for")),
+            Some([10, 0, 0, 1].into())
+        );
+
+        request
+            .headers_mut()
+            .insert("x-forwarded-for", "1.2.3.4, 5.6.7.8".parse().unwrap());
+        assert_eq!(
+            client_ip(&request, Some("x-forwarded-for")),
+            Some([5, 6, 7, 8].into())
+        );
+        // the header is ignored unless trusted
+        assert_eq!(client_ip(&request, None), Some([10, 0, 0, 1].into()));
+    }
+
+    #[test]
+    fn acquire_refills_tokens() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            per_address: Some(Quota {
+                burst: 2,
+                per_second: 0.5,
+            }),
+            per_ip: None,
+            client_ip_header: None,
+        });
+        let now = Instant::now();
+        assert_eq!(limiter.acquire(&[address()], now), Ok(()));
+        assert_eq!(limiter.acquire(&[address()], now), Ok(()));
+        assert_eq!(
+            limiter.acquire(&[address()], now),
+            Err(Duration::from_secs(2))
+        );
+        // IPs are not limited
+        assert_eq!(limiter.acquire(&[ip()], now), Ok(()));
+
+        let later = now + Duration::from_secs(1);
+        assert_eq!(
+            limiter.acquire(&[address()], later),
+            Err(Duration::from_secs(1))
+        );
+        let later = now + Duration::from_secs(2);
+        assert_eq!(limiter.acquire(&[address()], later), Ok(()));
+    }
+
+    #[test]
+    fn acquire_takes_tokens_of_all_keys() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            per_address: Some(Quota {
+                burst: 2,
+                per_second: 1.0,
+            }),
+            per_ip: Some(Quota {
+                burst: 1,
+                per_second: 1.0,
+            }),
+            client_ip_header: None,
+        });
+        let now = Instant::now();
+        assert_eq!(limiter.acquire(&[ip(), address()], now), Ok(()));
+        // the request is rejected by the IP quota, the address keeps its token
+        assert_eq!(
+            limiter.acquire(&[ip(), address()], now),
+            Err(Duration::from_secs(1))
+        );
+        assert_eq!(limiter.acquire(&[address()], now), Ok(()));
+        assert_eq!(
+            limiter.acquire(&[address()], now),
+            Err(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn set_config_resets_buckets() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            per_address: Some(Quota {
+                burst: 1,
+                per_second: 1.0,
+            }),
+            per_ip: None,
+            client_ip_header: None,
+        });
+        let now = Instant::now();
+        assert_eq!(limiter.acquire(&[address()], now), Ok(()));
+        assert!(limiter.acquire(&[address()], now).is_err());
+
+        limiter.set_config(RateLimitConfig {
+            per_address: Some(Quota {
+                burst: 2,
+                per_second: 1.0,
+            }),
+            per_ip: None,
+            client_ip_header: None,
+        });
+        assert_eq!(limiter.acquire(&[address()], now), Ok(()));
+        assert_eq!(limiter.acquire(&[address()], now), Ok(()));
+        assert!(limiter.acquire(&[address()], now).is_err());
+
+        limiter.set_config(RateLimitConfig::default());
+        assert_eq!(limiter.acquire(&[address()], now), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn layer_rejects_injections_over_quota() {
+        let router = Router::new()
+            .route("/operations", post(|| async {}))
+            .route("/operations/simulate", post(|| async {}))
+            .route(
+                "/r/sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK/operations",
+                post(|| async {}),
+            )
+            .layer(RateLimitLayer::new(RateLimitConfig {
+                per_address: None,
+                per_ip: Some(Quota {
+                    burst: 1,
+                    per_second: 0.1,
+                }),
+                client_ip_header: None,
+            }));
+        let request = |path: &str| {
+            let mut request = Request::post(path).body(Body::empty()).unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 8080))));
+            request
+        };
+
+        let res = router
+            .clone()
+            .oneshot(request("/operations"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = router
+            .clone()
+            .oneshot(request("/operations"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()[header::RETRY_AFTER], "10");
+
+        // the injections of the tenant rollups take from the same quotas
+        let res = router
+            .clone()
+            .oneshot(request(
+                "/r/sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK/operations",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // other endpoints are not rate limited
+        let res = router
+            .oneshot(request("/operations/simulate"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
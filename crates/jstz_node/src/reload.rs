@@ -0,0 +1,342 @@
+//! Reloading of the configuration of a running node.
+//!
+//! When the node knows the file its configuration was read from, see
+//! [`JstzNodeConfig::config_file`](crate::config::JstzNodeConfig::config_file), the
+//! [`TUNABLE_SETTINGS`] of the file are applied at startup, overriding the other
+//! sources of the configuration, and the file is read again every
+//! [`RELOAD_INTERVAL`]. Changes to the tunable settings are applied to the running
+//! node. The other settings are only read at startup, so changes to them are
+//! ignored with a warning until the node is restarted. A file that cannot be read
+//! or parsed is ignored and the current settings are kept.
+
+use std::{collections::BTreeSet, fs, path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use log::{info, warn, LevelFilter};
+use parking_lot::RwLock;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    config::LogLevel,
+    periodic::PeriodicTask,
+    rate_limit::{RateLimitConfig, RateLimitLayer},
+    sequencer::queue::OperationQueue,
+};
+
+/// Interval at which the configuration file is read
+pub const RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Settings of the configuration file that are applied without restarting the node
+pub const TUNABLE_SETTINGS: [&str; 4] =
+    ["rate_limit", "cors_origins", "capacity", "log_level"];
+
+/// A JSON configuration file of the node
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    /// Key of the object of the file holding the settings of the node, the whole
+    /// file if not set
+    pub section: Option<String>,
+}
+
+impl From<PathBuf> for ConfigFile {
+    fn from(path: PathBuf) -> Self {
+        Self {
+            path,
+            section: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TunableSettings {
+    #[serde(default)]
+    rate_limit: Option<RateLimitConfig>,
+    #[serde(default)]
+    cors_origins: Option<Vec<String>>,
+    #[serde(default)]
+    capacity: Option<usize>,
+    #[serde(default)]
+    log_level: Option<LogLevel>,
+}
+
+/// State of the running node the tunable settings apply to
+#[derive(Clone)]
+pub struct Tunables {
+    pub rate_limit: RateLimitLayer,
+    /// Origins allowed by the CORS layer, any origin if not set
+    pub cors_origins: Arc<RwLock<Option<Vec<String>>>>,
+    pub queue: Arc<std::sync::RwLock<OperationQueue>>,
+    /// Level of the logs when `log_level` is not set
+    pub default_log_level: LevelFilter,
+}
+
+pub type ConfigWatcher = PeriodicTask;
+
+/// Applies the tunable settings of `file` to `tunables`, and spawns a task reading
+/// the file every `interval` and applying the changes of its tunable settings.
+/// Fails if the file cannot be read or its tunable settings are invalid.
+pub fn spawn(
+    file: ConfigFile,
+    tunables: Tunables,
+    interval: Duration,
+) -> Result<ConfigWatcher> {
+    let mut last_read = read(&file)?;
+    let mut applied = last_read.clone();
+    applied.retain(|key, _| !TUNABLE_SETTINGS.contains(&key.as_str()));
+    apply(&mut applied, last_read.clone(), &tunables)?;
+    Ok(PeriodicTask::spawn(interval, move || {
+        match read(&file) {
+            Err(e) => warn!("failed to reload the configuration: {e:?}"),
+            Ok(current) if current == last_read => {}
+            Ok(current) => {
+                last_read = current.clone();
+                if let Err(e) = apply(&mut applied, current, &tunables) {
+                    warn!("invalid configuration, keeping the current settings: {e:?}");
+                }
+            }
+        }
+        async {}
+    }))
+}
+
+fn read(file: &ConfigFile) -> Result<Map<String, Value>> {
+    let path = &file.path;
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let mut config: Map<String, Value> = serde_json::from_str(&content)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    match &file.section {
+        None => Ok(config),
+        Some(section) => match config.remove(section) {
+            None => Ok(Map::new()),
+            Some(Value::Object(settings)) => Ok(settings),
+            Some(_) => {
+                anyhow::bail!("'{section}' of {} is not an object", path.display())
+            }
+        },
+    }
+}
+
+/// Applies the tunable settings of `current` that differ from `applied` to
+/// `tunables`, and records them in `applied`. Changes to the other settings are
+/// logged and ignored.
+fn apply(
+    applied: &mut Map<String, Value>,
+    current: Map<String, Value>,
+    tunables: &Tunables,
+) -> Result<()> {
+    let changed: BTreeSet<String> = applied
+        .keys()
+        .chain(current.keys())
+        .filter(|key| applied.get(*key) != current.get(*key))
+        .cloned()
+        .collect();
+    let (tunable, fixed): (Vec<String>, Vec<String>) = changed
+        .into_iter()
+        .partition(|key| TUNABLE_SETTINGS.contains(&key.as_str()));
+    if !fixed.is_empty() {
+        warn!(
+            "changes to {} require a restart of the node and are ignored",
+            fixed.join(", ")
+        );
+    }
+    if tunable.is_empty() {
+        return Ok(());
+    }
+
+    let settings = TunableSettings::deserialize(Value::Object(current.clone()))?;
+    for key in &tunable {
+        match key.as_str() {
+            "rate_limit" => tunables
+                .rate_limit
+                .set_config(settings.rate_limit.clone().unwrap_or_default()),
+            "cors_origins" => {
+                *tunables.cors_origins.write() = settings.cors_origins.clone()
+            }
+            "capacity" => {
+                if let (Some(capacity), Ok(mut queue)) =
+                    (settings.capacity, tunables.queue.write())
+                {
+                    queue.set_capacity(capacity);
+                }
+            }
+            "log_level" => log::set_max_level(
+                settings
+                    .log_level
+                    .map_or(tunables.default_log_level, Into::into),
+            ),
+            _ => unreachable!(),
+        }
+        match current.get(key) {
+            Some(value) => applied.insert(key.clone(), value.clone()),
+            None => applied.remove(key),
+        };
+    }
+    info!("reloaded {}", tunable.join(", "));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use log::LevelFilter;
+    use parking_lot::RwLock;
+    use serde_json::{json, Map, Value};
+    use tempfile::NamedTempFile;
+
+    use super::{apply, spawn, ConfigFile, Tunables};
+    use crate::{
+        rate_limit::{Quota, RateLimitConfig, RateLimitLayer},
+        sequencer::queue::OperationQueue,
+    };
+
+    fn tunables() -> Tunables {
+        Tunables {
+            rate_limit: RateLimitLayer::new(RateLimitConfig::default()),
+            cors_origins: Arc::new(RwLock::new(None)),
+            queue: Arc::new(std::sync::RwLock::new(OperationQueue::new(10))),
+            default_log_level: LevelFilter::Info,
+        }
+    }
+
+    fn object(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => panic!("expected an object"),
+        }
+    }
+
+    #[test]
+    fn applies_tunable_settings() {
+        let tunables = tunables();
+        let mut applied =
+            object(json!({"endpoint": "http://0.0.0.0:8933", "capacity": 10}));
+        let current = object(json!({
+            "endpoint": "http://0.0.0.0:8000",
+            "capacity": 20,
+            "cors_origins": ["https://app.jstz.dev"],
+            "rate_limit": {"per_ip": {"burst": 5, "per_second": 1.0}}
+        }));
+        apply(&mut applied, current, &tunables).unwrap();
+
+        assert_eq!(tunables.queue.read().unwrap().capacity(), 20);
+        assert_eq!(
+            *tunables.cors_origins.read(),
+            Some(vec!["https://app.jstz.dev".to_string()])
+        );
+        assert_eq!(
+            tunables.rate_limit.config(),
+            RateLimitConfig {
+                per_address: None,
+                per_ip: Some(Quota {
+                    burst: 5,
+                    per_second: 1.0
+                }),
+                client_ip_header: None,
+            }
+        );
+        // settings requiring a restart are not recorded as applied
+        assert_eq!(applied["endpoint"], "http://0.0.0.0:8933");
+        assert_eq!(applied["capacity"], 20);
+
+        // removed settings are reset
+        let current = object(json!({"endpoint": "http://0.0.0.0:8933", "capacity": 20}));
+        apply(&mut applied, current.clone(), &tunables).unwrap();
+        assert_eq!(*tunables.cors_origins.read(), None);
+        assert_eq!(tunables.rate_limit.config(), RateLimitConfig::default());
+        assert_eq!(applied, current);
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let tunables = tunables();
+        let mut applied = object(json!({"capacity": 10}));
+        let current = object(json!({"capacity": -1}));
+        assert!(apply(&mut applied, current, &tunables).is_err());
+        assert_eq!(tunables.queue.read().unwrap().capacity(), 10);
+        assert_eq!(applied["capacity"], 10);
+
+        let current = object(json!({
+            "capacity": 10,
+            "rate_limit": {"per_ip": {"burst": 5, "per_second": 0}}
+        }));
+        assert!(apply(&mut applied, current, &tunables).is_err());
+        assert_eq!(tunables.rate_limit.config(), RateLimitConfig::default());
+    }
+
+    #[tokio::test]
+    async fn watches_config_file() {
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), r#"{"capacity": 7}"#).unwrap();
+        let tunables = tunables();
+        let mut watcher = spawn(
+            file.path().to_path_buf().into(),
+            tunables.clone(),
+            Duration::from_millis(10),
+        )
+        .unwrap();
+        // the settings of the file override the settings the node started with
+        assert_eq!(tunables.queue.read().unwrap().capacity(), 7);
+
+        std::fs::write(file.path(), r#"{"capacity": 5}"#).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while tunables.queue.read().unwrap().capacity() != 5 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("the capacity should be reloaded");
+        watcher.shut_down().await;
+
+        assert!(spawn(
+            file.path().with_extension("missing").into(),
+            tunables.clone(),
+            Duration::from_millis(10)
+        )
+        .is_err());
+
+        std::fs::write(file.path(), r#"{"capacity": -1}"#).unwrap();
+        assert!(spawn(
+            file.path().to_path_buf().into(),
+            tunables,
+            Duration::from_millis(10)
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn reads_config_section() {
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            r#"{"server_port": 8000, "jstz_node": {"capacity": 3}}"#,
+        )
+        .unwrap();
+        let tunables = tunables();
+        let mut watcher = spawn(
+            ConfigFile {
+                path: file.path().to_path_buf(),
+                section: Some("jstz_node".to_string()),
+            },
+            tunables.clone(),
+            Duration::from_secs(60),
+        )
+        .unwrap();
+        assert_eq!(tunables.queue.read().unwrap().capacity(), 3);
+        watcher.shut_down().await;
+
+        std::fs::write(file.path(), r#"{"jstz_node": 1}"#).unwrap();
+        assert!(spawn(
+            ConfigFile {
+                path: file.path().to_path_buf(),
+                section: Some("jstz_node".to_string()),
+            },
+            tunables,
+            Duration::from_secs(60),
+        )
+        .is_err());
+    }
+}
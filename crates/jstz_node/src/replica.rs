@@ -0,0 +1,163 @@
+//! Read replicas of a sequencer node.
+//!
+//! A replica serves the read endpoints from its own runtime database, which follows
+//! the storage of the primary sequencer by tailing its journal (see
+//! `GET /sequencer/journal`), and forwards the operations injected in it to the
+//! primary. Only the storage is replicated: operation statuses, account operation
+//! histories and the ordering log are only served by the primary.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use async_dropper_simple::AsyncDrop;
+use async_trait::async_trait;
+use log::{debug, warn};
+use tokio::{select, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    sequencer::db::Db,
+    services::sequencer::{JournalUpdate, MAX_JOURNAL_LIMIT},
+};
+
+/// Interval at which replicas poll the journal of their primary
+pub const REPLICA_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Default)]
+pub struct Replicator {
+    inner: Option<JoinHandle<()>>,
+    kill_sig: CancellationToken,
+}
+
+impl Replicator {
+    pub async fn shut_down(&mut self) {
+        self.kill_sig.cancel();
+        if let Some(h) = self.inner.take() {
+            let _ = h.await;
+        }
+    }
+}
+
+#[async_trait]
+impl AsyncDrop for Replicator {
+    async fn async_drop(&mut self) {
+        self.shut_down().await;
+    }
+}
+
+/// Spawns a task applying the journal entries of the node at `primary_endpoint` to
+/// `db` every `interval`.
+pub fn spawn(db: Db, primary_endpoint: String, interval: Duration) -> Replicator {
+    let kill_sig = CancellationToken::new();
+    let kill_sig_clone = kill_sig.clone();
+    let inner = tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            select! {
+                _ = kill_sig_clone.cancelled() => break,
+                _ = ticker.tick() => {
+                    match sync(&db, &client, &primary_endpoint).await {
+                        Ok(0) => {}
+                        Ok(n) => debug!("applied {n} journal entries of the primary"),
+                        Err(e) => warn!("failed to sync with the primary: {e:?}"),
+                    }
+                }
+            }
+        }
+    });
+    Replicator {
+        inner: Some(inner),
+        kill_sig,
+    }
+}
+
+/// Applies the journal entries of the node at `primary_endpoint` that follow the
+/// last entry of `db`. Returns the number of applied entries.
+pub async fn sync(
+    db: &Db,
+    client: &reqwest::Client,
+    primary_endpoint: &str,
+) -> Result<usize> {
+    let mut applied = 0;
+    loop {
+        let from = db.last_journal_seq()?.map_or(0, |seq| seq + 1);
+        let entries = client
+            .get(format!("{primary_endpoint}/sequencer/journal"))
+            .query(&[("from", from), ("limit", MAX_JOURNAL_LIMIT as u64)])
+            .send()
+            .await
+            .context("failed to fetch the journal of the primary")?
+            .error_for_status()?
+            .json::<Vec<JournalUpdate>>()
+            .await
+            .context("failed to decode the journal of the primary")?;
+        let count = entries.len();
+        for entry in entries {
+            db.apply_journal_entry(&entry.into())
+                .context("failed to apply journal entry")?;
+        }
+        applied += count;
+        if count < MAX_JOURNAL_LIMIT {
+            return Ok(applied);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+
+    use crate::{
+        sequencer::db::{self, Db},
+        services::sequencer::JournalUpdate,
+    };
+
+    #[tokio::test]
+    async fn sync_applies_missing_journal_entries() {
+        let primary_file = NamedTempFile::new().unwrap();
+        let primary = Db::init(primary_file.path().to_str()).unwrap();
+        let conn = primary.connection().unwrap();
+        for (hash, value) in [("op1", "01"), ("op2", "02")] {
+            db::exec_record_write(&conn, None, "/foo", value).unwrap();
+            primary.commit_journal(None, Some(hash), None).unwrap();
+        }
+        let entries = primary
+            .journal_entries(0, 10)
+            .unwrap()
+            .into_iter()
+            .map(JournalUpdate::from)
+            .collect::<Vec<_>>();
+
+        let mut server = mockito::Server::new_async().await;
+        let mock_all = server
+            .mock("GET", "/sequencer/journal")
+            .match_query(mockito::Matcher::UrlEncoded("from".into(), "0".into()))
+            .with_body(serde_json::to_string(&entries).unwrap())
+            .create();
+
+        let replica_file = NamedTempFile::new().unwrap();
+        let replica = Db::init(replica_file.path().to_str()).unwrap();
+        let client = reqwest::Client::new();
+        assert_eq!(
+            super::sync(&replica, &client, &server.url()).await.unwrap(),
+            2
+        );
+        assert_eq!(replica.read_key("/foo").unwrap(), Some("02".to_string()));
+        assert_eq!(replica.last_journal_seq().unwrap(), Some(2));
+        mock_all.assert();
+
+        // entries already applied are not requested again
+        let mock_next = server
+            .mock("GET", "/sequencer/journal")
+            .match_query(mockito::Matcher::UrlEncoded("from".into(), "3".into()))
+            .with_body("[]")
+            .expect(1)
+            .create();
+        assert_eq!(
+            super::sync(&replica, &client, &server.url()).await.unwrap(),
+            0
+        );
+        mock_next.assert();
+    }
+}
@@ -0,0 +1,318 @@
+//! Publication of blueprints to the rollup inbox.
+//!
+//! The sequencer executes the operations injected in the node before they reach
+//! the L1. Once executed, they are recorded as unpublished (see
+//! [`Db::commit_journal`]), and the publisher periodically injects them, in
+//! execution order, as blueprints: operations signed by the injector that batch
+//! the executed operations, injected through the batcher of the rollup node, so
+//! that the kernel executes them too, see [`Blueprint`]. Operations too large for
+//! an external message were already replaced by `RevealLargePayload` operations
+//! signed by the injector when injected in the node.
+//!
+//! Blueprints are published at least once: operations published again after a
+//! crash fail with a passed nonce in the kernel, without overwriting their
+//! receipt. Published operations read back from the inbox are not executed again
+//! by the sequencer, see
+//! [`OperationQueue::insert`](super::queue::OperationQueue::insert).
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use async_dropper_simple::AsyncDrop;
+use async_trait::async_trait;
+use jstz_core::BinEncodable;
+use jstz_proto::operation::{
+    Blueprint, Operation, SignedOperation, MAX_DIRECT_OPERATION_SIZE,
+};
+use jstz_utils::KeyPair;
+use log::{debug, warn};
+use octez::RollupRpc;
+use tezos_data_encoding::enc::BinWriter;
+use tezos_smart_rollup::{inbox::ExternalMessageFrame, types::SmartRollupAddress};
+use tokio::{select, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+use super::db::Db;
+
+/// Maximum number of operations published in a blueprint
+pub const MAX_BLUEPRINT_OPERATIONS: usize = 64;
+
+#[derive(Default)]
+pub struct Publisher {
+    inner: Option<JoinHandle<()>>,
+    kill_sig: CancellationToken,
+}
+
+impl Publisher {
+    pub async fn shut_down(&mut self) {
+        self.kill_sig.cancel();
+        if let Some(h) = self.inner.take() {
+            let _ = h.await;
+        }
+    }
+}
+
+#[async_trait]
+impl AsyncDrop for Publisher {
+    async fn async_drop(&mut self) {
+        self.shut_down().await;
+    }
+}
+
+/// Spawns a task publishing blueprints of the unpublished operations of `db`,
+/// signed by `injector`, to the inbox of rollup `rollup_address` every `interval`.
+pub fn spawn_publisher(
+    db: Db,
+    rollup_client: Arc<dyn RollupRpc>,
+    rollup_address: SmartRollupAddress,
+    injector: KeyPair,
+    interval: Duration,
+) -> Publisher {
+    let kill_sig = CancellationToken::new();
+    let kill_sig_clone = kill_sig.clone();
+    let inner = tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            select! {
+                _ = kill_sig_clone.cancelled() => break,
+                _ = ticker.tick() => {
+                    let published = publish_blueprint(
+                        &db,
+                        rollup_client.as_ref(),
+                        &rollup_address,
+                        &injector,
+                    )
+                    .await;
+                    match published {
+                        Ok(0) => {}
+                        Ok(n) => debug!("published blueprint of {n} operations"),
+                        Err(e) => warn!("failed to publish blueprint: {e:?}"),
+                    }
+                }
+            }
+        }
+    });
+    Publisher {
+        inner: Some(inner),
+        kill_sig,
+    }
+}
+
+/// Injects up to [`MAX_BLUEPRINT_OPERATIONS`] unpublished operations of `db` into
+/// the inbox of rollup `rollup_address`, as blueprints signed by `injector` that
+/// each fit in an external message. Returns the number of published operations.
+pub async fn publish_blueprint(
+    db: &Db,
+    rollup_client: &dyn RollupRpc,
+    rollup_address: &SmartRollupAddress,
+    injector: &KeyPair,
+) -> Result<usize> {
+    let unpublished = db
+        .unpublished_operations(MAX_BLUEPRINT_OPERATIONS)
+        .context("failed to read unpublished operations")?;
+    let Some(last) = unpublished.last().map(|op| op.seq) else {
+        return Ok(0);
+    };
+    let operations = unpublished
+        .iter()
+        .map(|op| {
+            let bytes = hex::decode(&op.operation)
+                .with_context(|| format!("invalid unpublished operation {}", op.seq))?;
+            SignedOperation::decode(&bytes)
+                .map_err(|e| anyhow!("invalid unpublished operation {}: {e}", op.seq))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut messages = Vec::new();
+    let mut rest = operations.as_slice();
+    while !rest.is_empty() {
+        let (contents, published) = next_blueprint(rest, injector)?;
+        rest = &rest[published..];
+        let frame = ExternalMessageFrame::Targetted {
+            address: rollup_address.clone(),
+            contents,
+        };
+        let mut message = Vec::new();
+        frame
+            .bin_write(&mut message)
+            .map_err(|_| anyhow!("Failed to write binary frame"))?;
+        messages.push(message);
+    }
+    rollup_client
+        .batcher_injection(messages)
+        .await
+        .context("failed to inject blueprint")?;
+    db.mark_published(last)
+        .context("failed to mark operations as published")?;
+    Ok(operations.len())
+}
+
+/// Encodes the blueprint of the longest prefix of `operations` that fits in an
+/// external message, signed by `injector`. Returns it with the number of
+/// operations it holds. An operation too large to be wrapped in a blueprint is
+/// encoded on its own.
+fn next_blueprint(
+    operations: &[SignedOperation],
+    KeyPair(public_key, secret_key): &KeyPair,
+) -> Result<(Vec<u8>, usize)> {
+    let mut next = None;
+    for count in 1..=operations.len() {
+        let op = Operation {
+            public_key: public_key.clone(),
+            nonce: 0.into(),
+            content: Blueprint {
+                operations: operations[..count].to_vec(),
+            }
+            .into(),
+            max_fee: None,
+            priority_fee: None,
+        };
+        let signature = secret_key
+            .sign(op.hash())
+            .map_err(|e| anyhow!("failed to sign blueprint: {e}"))?;
+        let encoded = SignedOperation::new(signature, op)
+            .encode()
+            .map_err(|e| anyhow!("failed to encode blueprint: {e}"))?;
+        if encoded.len() > MAX_DIRECT_OPERATION_SIZE {
+            break;
+        }
+        next = Some((encoded, count));
+    }
+    match next {
+        Some(next) => Ok(next),
+        None => {
+            let encoded = operations[0]
+                .encode()
+                .map_err(|e| anyhow!("failed to encode operation: {e}"))?;
+            Ok((encoded, 1))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jstz_core::BinEncodable;
+    use jstz_mock::sr1_address;
+    use jstz_proto::operation::{
+        internal::InboxId, Blueprint, Content, SignedOperation, MAX_DIRECT_OPERATION_SIZE,
+    };
+    use octez::mock::MockRollupRpc;
+    use tempfile::NamedTempFile;
+    use tezos_smart_rollup::{inbox::ExternalMessageFrame, types::SmartRollupAddress};
+
+    use crate::{
+        sequencer::{
+            db::Db,
+            queue::WrappedOperation,
+            tests::{dummy_signed_op, signer, tipped_op},
+        },
+        test::default_injector,
+    };
+
+    /// Decodes the blueprints of the injected messages
+    fn blueprints(rpc: &MockRollupRpc) -> Vec<SignedOperation> {
+        rpc.injected_messages()
+            .iter()
+            .map(|message| {
+                let ExternalMessageFrame::Targetted { contents, .. } =
+                    ExternalMessageFrame::parse(message).unwrap();
+                SignedOperation::decode(contents).unwrap()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn publish_blueprint_injects_executed_operations_once() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let address = SmartRollupAddress::new(sr1_address());
+        let rpc = MockRollupRpc::new(address.clone());
+        let injector = default_injector();
+        let op = dummy_signed_op();
+        let hash = op.hash().to_string();
+        let encoded = op.encode().unwrap();
+
+        // operations read from the inbox and operations that were not executed yet
+        // are not published
+        let inbox_id = InboxId {
+            l1_level: 1,
+            l1_message_id: 3,
+        };
+        let from_inbox = db.enqueue("0001", Some(&inbox_id)).unwrap();
+        db.commit_journal(None, Some(&hash), Some(from_inbox))
+            .unwrap();
+        let queued = db.enqueue(&hex::encode(&encoded), None).unwrap();
+        assert_eq!(
+            super::publish_blueprint(&db, &rpc, &address, &injector)
+                .await
+                .unwrap(),
+            0
+        );
+
+        db.commit_journal(None, Some(&hash), Some(queued)).unwrap();
+        assert_eq!(
+            super::publish_blueprint(&db, &rpc, &address, &injector)
+                .await
+                .unwrap(),
+            1
+        );
+        let blueprints = blueprints(&rpc);
+        assert_eq!(blueprints.len(), 1);
+        blueprints[0].verify_ref().unwrap();
+        assert_eq!(blueprints[0].public_key, injector.0);
+        assert_eq!(
+            blueprints[0].content(),
+            &Content::Blueprint(Blueprint {
+                operations: vec![op]
+            })
+        );
+
+        assert_eq!(
+            super::publish_blueprint(&db, &rpc, &address, &injector)
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(rpc.injected_messages().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn publish_blueprint_splits_operations_into_messages() {
+        let db = Db::init(Some("")).unwrap();
+        let address = SmartRollupAddress::new(sr1_address());
+        let rpc = MockRollupRpc::new(address.clone());
+        let keys = signer(1);
+        let operations = (0..40)
+            .map(|nonce| match tipped_op(&keys, nonce, 0) {
+                WrappedOperation::FromNode(op) => op,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        for op in &operations {
+            let seq = db
+                .enqueue(&hex::encode(op.encode().unwrap()), None)
+                .unwrap();
+            db.commit_journal(None, Some(&op.hash().to_string()), Some(seq))
+                .unwrap();
+        }
+
+        assert_eq!(
+            super::publish_blueprint(&db, &rpc, &address, &default_injector())
+                .await
+                .unwrap(),
+            40
+        );
+        let blueprints = blueprints(&rpc);
+        assert!(blueprints.len() > 1);
+        let mut published = vec![];
+        for blueprint in blueprints {
+            assert!(blueprint.encode().unwrap().len() <= MAX_DIRECT_OPERATION_SIZE);
+            match blueprint.content() {
+                Content::Blueprint(Blueprint { operations }) => {
+                    published.extend(operations.iter().cloned())
+                }
+                _ => panic!("should be a blueprint"),
+            }
+        }
+        assert_eq!(published, operations);
+    }
+}
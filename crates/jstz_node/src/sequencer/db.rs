@@ -1,19 +1,189 @@
-use std::{fs, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
 
 use anyhow::Context;
 use anyhow::Result;
+use jstz_crypto::hash::Blake2b;
+use jstz_proto::operation::internal::InboxId;
+use jstz_utils::KeyPair;
+use log::info;
 use r2d2::{Pool, PooledConnection};
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::params;
 use rusqlite::Connection;
 use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+pub use jstz_client::models::{AccountOperations, OperationState, OperationStatus};
 
 pub type SqliteConnectionPool = Pool<SqliteConnectionManager>;
 
+/// Identifies the writes of an operation in `jstz_undo`, so that operations executed
+/// concurrently are journaled separately, see [`Db::new_journal`]
+pub type JournalId = u64;
+
+/// Number of L1 levels the storage can be read as of, see [`Db::read_key_at`]. About
+/// a week of 8 second levels.
+pub const HISTORY_RETENTION_LEVELS: u32 = 75_600;
+
+/// An entry of the journal: the storage diff of an executed operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub seq: u64,
+    pub operation_hash: Option<String>,
+    /// The values of the written keys after the operation, `None` for the
+    /// deleted keys
+    pub diff: Vec<(String, Option<String>)>,
+    /// The values the operation wrote to the keys, when they differ from `diff`
+    /// because operations executed concurrently overwrote them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation_diff: Option<Vec<(String, Option<String>)>>,
+}
+
+/// An entry of the ordering log: the position at which the sequencer executed an
+/// operation. `digest` chains the entry to the previous one and is signed by the
+/// sequencer, so that the log cannot be rewritten without being detected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct OrderingEntry {
+    /// Position of the operation in the execution order, starting at 1
+    pub position: u64,
+    pub operation_hash: String,
+    /// L1 level of the inbox the operation was read from, or, for operations
+    /// injected in the node, the level of the previous entry
+    pub level: u32,
+    /// Hex-encoded Blake2b digest of the previous digest, the big-endian position
+    /// and level, and the operation hash
+    pub digest: String,
+    /// Base58-encoded signature of `digest` by the sequencer
+    pub signature: String,
+}
+
+/// The signature of the receipt of an operation by the sequencer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ReceiptAttestation {
+    /// Hex-encoded Blake2b digest of the binary-encoded receipt
+    pub digest: String,
+    /// Base58-encoded signature by the sequencer of `digest` prefixed with the tag
+    /// `jstz:receipt_attestation:`
+    pub signature: String,
+}
+
+/// An operation of the sequencer queue persisted in `jstz_queue`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PersistedOperation {
+    pub seq: u64,
+    /// The hex-encoded inbox message if `inbox_id` is set, the hex-encoded
+    /// signed operation otherwise
+    pub operation: String,
+    pub inbox_id: Option<InboxId>,
+}
+
+fn state_as_str(state: OperationState) -> &'static str {
+    match state {
+        OperationState::Queued => "queued",
+        OperationState::Included => "included",
+        OperationState::Executing => "executing",
+        OperationState::Committed => "committed",
+        OperationState::Failed => "failed",
+    }
+}
+
+fn state_from_str(state: &str) -> Option<OperationState> {
+    match state {
+        "queued" => Some(OperationState::Queued),
+        "included" => Some(OperationState::Included),
+        "executing" => Some(OperationState::Executing),
+        "committed" => Some(OperationState::Committed),
+        "failed" => Some(OperationState::Failed),
+        _ => None,
+    }
+}
+
+/// States only transition to states of a higher rank
+fn state_rank(state: OperationState) -> u8 {
+    match state {
+        OperationState::Queued => 0,
+        OperationState::Included => 1,
+        OperationState::Executing => 2,
+        OperationState::Committed | OperationState::Failed => 3,
+    }
+}
+
+/// The storage diff of an executed operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct OperationDiff {
+    /// The keys written by the operation, in lexicographic order
+    pub updates: Vec<KeyUpdate>,
+}
+
+/// A key written by an operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct KeyUpdate {
+    pub key: String,
+    /// Hex-encoded value of the key after the operation, `None` if the operation
+    /// deleted the key
+    pub value: Option<String>,
+}
+
 /// Database wrapper that manipulates the sequencer database.
+///
+/// Writes are journaled: the value of every key is recorded in `jstz_undo`
+/// before it is written (see [`exec_record_undo`]), tagged with the journal of
+/// the operation that wrote it, until the operation completes and
+/// [`Db::commit_journal`] appends its storage diff to `jstz_journal`. Writes of
+/// an operation that did not complete, e.g. because the node crashed, are rolled
+/// back by [`Db::recover`] when the database is opened. Operations executed
+/// concurrently write to separate journals, see [`Db::new_journal`].
+///
+/// The operations of the sequencer queue are persisted in `jstz_queue` until
+/// the journal entry of their execution is committed, so that the operations
+/// that were not executed can be queued again on restart.
+///
+/// The values the keys had before each journal entry are recorded in
+/// `jstz_rollback`, along with the queued operation that made it, so that the
+/// operations read from the inbox of L1 levels reverted by a reorg can be rolled
+/// back (see [`Db::roll_back`]).
+///
+/// The executed operations that were injected in the node are recorded in
+/// `jstz_unpublished` until they are published to the rollup inbox, see
+/// [`crate::sequencer::blueprint`].
+///
+/// The executed operations of the queue are appended to the signed ordering log
+/// `jstz_ordering` when committed with [`Db::commit_ordered_journal`]. The entries
+/// from the first operation read from a reverted L1 level are removed by
+/// [`Db::roll_back`], and the operations executed again are appended again.
+///
+/// The receipts of the executed operations are signed and recorded in
+/// `jstz_receipt_attestation` (see [`Db::attest_receipt`]), so that clients
+/// accepting receipts before they are confirmed on L1 can prove that the sequencer
+/// equivocated. Only the latest receipt of an operation is kept.
+///
+/// The attestation and the state of the operations older than the retention window
+/// of the node are removed, see [`Db::prune_receipts`]. Receipts themselves are
+/// pruned by the kernel, see [`jstz_proto::executor::receipt_retention`].
+///
+/// Writes made at a known L1 level (see [`exec_write_at`]) are versioned: the
+/// value of the key at the end of the level is recorded in `jstz_history`, so
+/// that the storage can be read as of any level since `jstz_levels` started
+/// (see [`Db::read_key_at`]). The history of the levels more than
+/// [`HISTORY_RETENTION_LEVELS`] old is pruned when a level starts. The sequencer starts the level of each message read
+/// from the inbox when committing it, and the writes it commits are versioned at the
+/// last started level, see [`Db::commit_journal`]. The last started level is open:
+/// its state changes until the next level starts, so it cannot be read as of yet.
+/// A rolled back level is not reopened, see [`Db::roll_back`].
 #[derive(Clone)]
 pub struct Db {
     pool: SqliteConnectionPool,
+    last_journal: Arc<AtomicU64>,
 }
 
 impl Db {
@@ -43,7 +213,15 @@ impl Db {
         let pool = SqliteConnectionPool::new(manager)?;
         Self::setup(pool.clone())?;
 
-        Ok(Db { pool })
+        let db = Db {
+            pool,
+            last_journal: Arc::default(),
+        };
+        let rolled_back = db.recover().context("failed to recover database")?;
+        if rolled_back > 0 {
+            info!("Rolled back {rolled_back} writes of incomplete operations");
+        }
+        Ok(db)
     }
 
     pub fn connection(&self) -> Result<PooledConnection<SqliteConnectionManager>> {
@@ -57,6 +235,39 @@ impl Db {
     fn setup(pool: Pool<SqliteConnectionManager>) -> Result<()> {
         let conn = pool.get().context("failed to get connection from pool")?;
         conn.execute("CREATE TABLE IF NOT EXISTS jstz_kv (jstz_key TEXT NOT NULL PRIMARY KEY, jstz_value, UNIQUE(jstz_key))", []).context("failed to create table")?;
+        conn.execute("CREATE TABLE IF NOT EXISTS jstz_undo (seq INTEGER PRIMARY KEY AUTOINCREMENT, jstz_key TEXT NOT NULL, jstz_value, journal INTEGER, written)", []).context("failed to create undo table")?;
+        // Undo tables created before journals were tagged have no journal column,
+        // nor written values before operation diffs were recorded
+        add_missing_column(&conn, "jstz_undo", "journal", "INTEGER")
+            .context("failed to tag undo table")?;
+        add_missing_column(&conn, "jstz_undo", "written", "")
+            .context("failed to add written values to undo table")?;
+        conn.execute("CREATE TABLE IF NOT EXISTS jstz_journal (seq INTEGER PRIMARY KEY AUTOINCREMENT, operation_hash TEXT, diff TEXT NOT NULL, operation_diff TEXT)", []).context("failed to create journal table")?;
+        add_missing_column(&conn, "jstz_journal", "operation_diff", "TEXT")
+            .context("failed to add operation diffs to journal table")?;
+        conn.execute("CREATE INDEX IF NOT EXISTS jstz_journal_operation_hash ON jstz_journal (operation_hash)", []).context("failed to create journal index")?;
+        conn.execute("CREATE TABLE IF NOT EXISTS jstz_rollback (seq INTEGER PRIMARY KEY, undo TEXT NOT NULL, operation TEXT, l1_level INTEGER, l1_message_id INTEGER)", []).context("failed to create rollback table")?;
+        conn.execute("CREATE INDEX IF NOT EXISTS jstz_rollback_l1_level ON jstz_rollback (l1_level)", []).context("failed to create rollback index")?;
+        conn.execute("CREATE TABLE IF NOT EXISTS jstz_unpublished (seq INTEGER PRIMARY KEY AUTOINCREMENT, operation_hash TEXT NOT NULL, operation TEXT NOT NULL)", []).context("failed to create unpublished operations table")?;
+        conn.execute("CREATE TABLE IF NOT EXISTS jstz_ordering (position INTEGER NOT NULL PRIMARY KEY, operation_hash TEXT NOT NULL, level INTEGER NOT NULL, digest TEXT NOT NULL, signature TEXT NOT NULL)", []).context("failed to create ordering table")?;
+        conn.execute("CREATE INDEX IF NOT EXISTS jstz_ordering_level ON jstz_ordering (level, position)", []).context("failed to create ordering index")?;
+        conn.execute("CREATE TABLE IF NOT EXISTS jstz_receipt_attestation (operation_hash TEXT NOT NULL PRIMARY KEY, digest TEXT NOT NULL, signature TEXT NOT NULL)", []).context("failed to create receipt attestation table")?;
+        conn.execute("CREATE TABLE IF NOT EXISTS jstz_queue (seq INTEGER PRIMARY KEY AUTOINCREMENT, operation TEXT NOT NULL, l1_level INTEGER, l1_message_id INTEGER)", []).context("failed to create queue table")?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS jstz_levels (level INTEGER NOT NULL PRIMARY KEY)",
+            [],
+        )
+        .context("failed to create levels table")?;
+        conn.execute("CREATE TABLE IF NOT EXISTS jstz_operation_status (operation_hash TEXT NOT NULL PRIMARY KEY, state TEXT NOT NULL, rank INTEGER NOT NULL, level INTEGER, timestamp INTEGER NOT NULL)", []).context("failed to create operation status table")?;
+        conn.execute("CREATE TABLE IF NOT EXISTS jstz_account_operations (seq INTEGER PRIMARY KEY AUTOINCREMENT, address TEXT NOT NULL, operation_hash TEXT NOT NULL, UNIQUE(address, operation_hash))", []).context("failed to create account operations table")?;
+        conn.execute("CREATE INDEX IF NOT EXISTS jstz_account_operations_address ON jstz_account_operations (address, seq)", []).context("failed to create account operations index")?;
+        conn.execute("CREATE TABLE IF NOT EXISTS jstz_riscv_inputs (seq INTEGER PRIMARY KEY AUTOINCREMENT, l1_level INTEGER NOT NULL, l1_message_id INTEGER NOT NULL, message BLOB NOT NULL, operation TEXT)", []).context("failed to create riscv inputs table")?;
+        conn.execute("CREATE TABLE IF NOT EXISTS jstz_history (level INTEGER NOT NULL, jstz_key TEXT NOT NULL, jstz_value, PRIMARY KEY (jstz_key, level))", []).context("failed to create history table")?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS jstz_history_level ON jstz_history (level)",
+            [],
+        )
+        .context("failed to create history index")?;
         // Allows reads while writes are taking place. This works when there is only one writer
         // and is fine in our use case.
         conn.pragma_update(None, "journal_mode", "WAL")
@@ -161,6 +372,52 @@ impl Db {
         Ok(if keys.is_empty() { None } else { Some(keys) })
     }
 
+    /// Like [`Db::get_subkeys`], keeping the subkeys that start with `subkey_prefix`
+    /// if set, in lexicographic order and up to `limit` subkeys.
+    pub fn find_subkeys(
+        &self,
+        prefix: &str,
+        subkey_prefix: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Option<Vec<String>>> {
+        let client = self.connection()?;
+        // See `get_subkeys`. A negative limit means no limit.
+        let mut stmt = client.prepare(
+            r#"
+            SELECT subkey FROM (
+                SELECT SUBSTR(jstz_key, LENGTH(?2)) AS subkey
+                FROM jstz_kv
+                WHERE jstz_key = ?1
+                    OR jstz_key GLOB ?2
+                    AND NOT jstz_key GLOB ?3
+                UNION
+                SELECT DISTINCT SUBSTR(SUBSTR(jstz_key, LENGTH(?2)), 0, INSTR(SUBSTR(jstz_key, LENGTH(?2)), '/'))
+                FROM jstz_kv
+                WHERE jstz_key GLOB ?3
+            )
+            WHERE SUBSTR(subkey, 1, LENGTH(?4)) = ?4
+            ORDER BY subkey
+            LIMIT ?5"#,
+        )?;
+        let limit = limit.map_or(-1, |limit| i64::try_from(limit).unwrap_or(i64::MAX));
+        let keys = stmt
+            .query_map(
+                params![
+                    prefix,
+                    format!("{prefix}/*"),
+                    format!("{prefix}/*/*"),
+                    subkey_prefix.unwrap_or_default(),
+                    limit,
+                ],
+                |row| row.get(0),
+            )?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        if keys.is_empty() && self.count_subkeys(prefix)?.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(keys))
+    }
+
     pub fn read_key(&self, key: &str) -> Result<Option<String>> {
         let conn = self.connection()?;
         exec_read(&conn, key)
@@ -170,6 +427,912 @@ impl Db {
         let conn = self.connection()?;
         exec_write(&conn, key, value)
     }
+
+    /// Returns the first and the last L1 levels whose writes were recorded, or
+    /// `None` if no level was started. The last level is still open.
+    pub fn recorded_levels(&self) -> Result<Option<(u32, u32)>> {
+        let conn = self.connection()?;
+        let (first, last) = conn.query_row(
+            "SELECT MIN(level), MAX(level) FROM jstz_levels",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        Ok(first.zip(last))
+    }
+
+    /// Reads the value of a key as of the end of L1 level `level`, which must be
+    /// within [`Db::recorded_levels`] or precede the first recorded level, and must
+    /// not be the last recorded level, which is still open.
+    pub fn read_key_at(&self, key: &str, level: u32) -> Result<Option<String>> {
+        let conn = self.connection()?;
+        let recorded = conn
+            .query_row(
+                "SELECT jstz_value FROM jstz_history WHERE jstz_key = ?1 AND level <= ?2 ORDER BY level DESC LIMIT 1",
+                params![key, level],
+                |row| row.get::<_, Option<String>>(0),
+            )
+            .optional()?;
+        match recorded {
+            Some(value) => Ok(value),
+            // The key was not written since the first recorded level
+            None => exec_read(&conn, key),
+        }
+    }
+
+    /// Returns a new journal, which tags the writes of an operation executed
+    /// concurrently with other operations, see [`exec_record_undo`]. Untagged
+    /// writes belong to the `None` journal.
+    pub fn new_journal(&self) -> JournalId {
+        self.last_journal.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Appends the storage diff of the writes recorded in `journal` since its last
+    /// entry to the journal, making them permanent, and removes the operation
+    /// `dequeued` from the persisted queue. The operation is recorded as unpublished
+    /// if it was injected in the node. If `dequeued` was read from the inbox, its
+    /// level is started, and the writes are recorded in the history at the last
+    /// started level, if any. Returns the sequence number of the entry, or `None` if
+    /// nothing was written.
+    pub fn commit_journal(
+        &self,
+        journal: Option<JournalId>,
+        operation_hash: Option<&str>,
+        dequeued: Option<u64>,
+    ) -> Result<Option<u64>> {
+        self.commit(journal, operation_hash, dequeued, None, None)
+    }
+
+    /// Commits the journal like [`Db::commit_journal`] and appends the executed
+    /// operation `operation_hash`, dequeued from `dequeued`, to the ordering log,
+    /// signed by `signer`.
+    pub fn commit_ordered_journal(
+        &self,
+        journal: Option<JournalId>,
+        operation_hash: &str,
+        dequeued: u64,
+        signer: &KeyPair,
+    ) -> Result<Option<u64>> {
+        self.commit(
+            journal,
+            Some(operation_hash),
+            Some(dequeued),
+            Some(signer),
+            None,
+        )
+    }
+
+    /// Removes the operation `dequeued` from the persisted queue like
+    /// [`Db::commit_journal`] and records `message`, the inbox message it was
+    /// provided to the RISCV PVM as, so that the state of the PVM can be restored by
+    /// providing the recorded messages to a new PVM, see [`Db::riscv_inputs`].
+    pub fn commit_riscv_input(
+        &self,
+        inbox_id: InboxId,
+        message: &[u8],
+        dequeued: Option<u64>,
+    ) -> Result<()> {
+        self.commit(None, None, dequeued, None, Some((inbox_id, message)))?;
+        Ok(())
+    }
+
+    /// Returns the inbox messages provided to the RISCV PVM, in order, see
+    /// [`Db::commit_riscv_input`]
+    pub fn riscv_inputs(&self) -> Result<Vec<(InboxId, Vec<u8>)>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT l1_level, l1_message_id, message FROM jstz_riscv_inputs ORDER BY seq",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                InboxId {
+                    l1_level: row.get(0)?,
+                    l1_message_id: row.get(1)?,
+                },
+                row.get(2)?,
+            ))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    fn commit(
+        &self,
+        journal: Option<JournalId>,
+        operation_hash: Option<&str>,
+        dequeued: Option<u64>,
+        signer: Option<&KeyPair>,
+        riscv_input: Option<(InboxId, &[u8])>,
+    ) -> Result<Option<u64>> {
+        let mut conn = self.connection()?;
+        let tx = conn.transaction()?;
+        let l1_level: Option<u32> = tx
+            .query_row(
+                "SELECT l1_level FROM jstz_queue WHERE seq = ?1",
+                params![dequeued],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        if let Some(l1_level) = l1_level {
+            exec_start_level(&tx, l1_level)?;
+        }
+        let diff = {
+            let mut stmt = tx.prepare(
+                r#"
+                SELECT DISTINCT jstz_undo.jstz_key, jstz_kv.jstz_value
+                FROM jstz_undo
+                LEFT JOIN jstz_kv ON jstz_undo.jstz_key = jstz_kv.jstz_key
+                WHERE jstz_undo.journal IS ?1
+                ORDER BY jstz_undo.jstz_key"#,
+            )?;
+            let rows =
+                stmt.query_map(params![journal], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<Vec<(String, Option<String>)>>>()?
+        };
+
+        // The values written in the journal, which other journals may have
+        // overwritten since
+        let written = {
+            let mut stmt = tx.prepare(
+                r#"
+                SELECT jstz_key, written FROM jstz_undo
+                WHERE seq IN (
+                    SELECT MAX(seq) FROM jstz_undo WHERE journal IS ?1 GROUP BY jstz_key
+                )
+                ORDER BY jstz_key"#,
+            )?;
+            let rows =
+                stmt.query_map(params![journal], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<Vec<(String, Option<String>)>>>()?
+        };
+
+        let seq = if diff.is_empty() {
+            None
+        } else {
+            let operation_diff = (written != diff)
+                .then(|| serde_json::to_string(&written))
+                .transpose()?;
+            tx.execute(
+                "INSERT INTO jstz_journal (operation_hash, diff, operation_diff) VALUES (?1, ?2, ?3)",
+                params![operation_hash, serde_json::to_string(&diff)?, operation_diff],
+            )?;
+            let seq = tx.last_insert_rowid() as u64;
+            // The first recorded value of a key is its value before the entry
+            let undo = {
+                let mut stmt = tx.prepare(
+                    r#"
+                    SELECT jstz_key, jstz_value FROM jstz_undo
+                    WHERE seq IN (
+                        SELECT MIN(seq) FROM jstz_undo WHERE journal IS ?1 GROUP BY jstz_key
+                    )
+                    ORDER BY jstz_key"#,
+                )?;
+                let rows = stmt
+                    .query_map(params![journal], |row| Ok((row.get(0)?, row.get(1)?)))?;
+                rows.collect::<rusqlite::Result<Vec<(String, Option<String>)>>>()?
+            };
+            tx.execute(
+                r#"
+                INSERT INTO jstz_rollback (seq, undo, operation, l1_level, l1_message_id)
+                SELECT ?1, ?2, operation, l1_level, l1_message_id
+                FROM (SELECT 1) LEFT JOIN jstz_queue ON jstz_queue.seq = ?3"#,
+                params![seq, serde_json::to_string(&undo)?, dequeued],
+            )?;
+            let level: Option<u32> =
+                tx.query_row("SELECT MAX(level) FROM jstz_levels", [], |row| row.get(0))?;
+            if let Some(level) = level {
+                for (key, value) in &undo {
+                    exec_record_history_baseline_value(&tx, key, value.as_deref())?;
+                    exec_record_history(&tx, level, key)?;
+                }
+            }
+            tx.execute(
+                "DELETE FROM jstz_undo WHERE journal IS ?1",
+                params![journal],
+            )?;
+            Some(seq)
+        };
+        // Operations injected in the node are recorded so that they can be queued
+        // again when rolled back
+        if let Some((inbox_id, message)) = riscv_input {
+            tx.execute(
+                r#"
+                INSERT INTO jstz_riscv_inputs (l1_level, l1_message_id, message, operation)
+                SELECT ?1, ?2, ?3, operation
+                FROM (SELECT 1) LEFT JOIN jstz_queue ON jstz_queue.seq = ?4 AND jstz_queue.l1_level IS NULL"#,
+                params![inbox_id.l1_level, inbox_id.l1_message_id, message, dequeued],
+            )?;
+        }
+        if let Some(dequeued) = dequeued {
+            if let Some((operation_hash, signer)) = operation_hash.zip(signer) {
+                exec_append_ordering(&tx, operation_hash, dequeued, signer)?;
+            }
+            if let Some(operation_hash) = operation_hash {
+                tx.execute(
+                    r#"
+                    INSERT INTO jstz_unpublished (operation_hash, operation)
+                    SELECT ?1, operation FROM jstz_queue
+                    WHERE seq = ?2 AND l1_level IS NULL"#,
+                    params![operation_hash, dequeued],
+                )?;
+            }
+            tx.execute("DELETE FROM jstz_queue WHERE seq = ?1", params![dequeued])?;
+        }
+        tx.commit()?;
+        Ok(seq)
+    }
+
+    /// Rolls back the writes of all journals recorded since their last entry,
+    /// restoring the storage as of the end of the last journaled operation. Returns
+    /// the number of rolled back writes.
+    pub fn recover(&self) -> Result<usize> {
+        let mut conn = self.connection()?;
+        let tx = conn.transaction()?;
+        let undo = {
+            let mut stmt = tx.prepare(
+                "SELECT jstz_key, jstz_value FROM jstz_undo ORDER BY seq DESC",
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<Vec<(String, Option<String>)>>>()?
+        };
+        for (key, value) in &undo {
+            match value {
+                Some(value) => exec_write(&tx, key, value)?,
+                None => {
+                    exec_delete(&tx, key)?;
+                }
+            }
+        }
+        tx.execute("DELETE FROM jstz_undo", [])?;
+        tx.commit()?;
+        Ok(undo.len())
+    }
+
+    /// Returns true if writes were recorded since the last journal entry, i.e. while
+    /// an operation is being executed.
+    pub fn has_pending_writes(&self) -> Result<bool> {
+        let conn = self.connection()?;
+        Ok(
+            conn.query_row("SELECT EXISTS(SELECT 1 FROM jstz_undo)", [], |row| {
+                row.get(0)
+            })?,
+        )
+    }
+
+    /// Rolls back the operations read from the inbox of an L1 level after `level`,
+    /// and all the operations executed after the first of them, by restoring the
+    /// values the keys had before. The restored values are appended to the journal
+    /// as a new entry, and the states of the rolled back operations are forgotten.
+    /// The rolled back operations that were not published yet are only published
+    /// once executed again.
+    ///
+    /// The messages provided to the RISCV PVM from the first one read from such an
+    /// inbox are forgotten as well, so that a new PVM can be restored without them,
+    /// see [`Db::riscv_inputs`]. So are the entries of the ordering log from the
+    /// first one of such a level. The history of the levels after `level` is
+    /// forgotten, and the level after `level` is started again so that the writes of
+    /// the operations executed again are not recorded at `level`, which was over.
+    ///
+    /// Returns the rolled back operations that were not read from such an inbox, in
+    /// order, so that they can be queued again. Must not be called while writes are
+    /// pending, see [`Db::has_pending_writes`].
+    pub fn roll_back(&self, level: u32) -> Result<Vec<PersistedOperation>> {
+        let mut conn = self.connection()?;
+        let tx = conn.transaction()?;
+        let mut requeued = exec_roll_back_riscv_inputs(&tx, level)?;
+        // Entries take the level of the previous entry unless read from the inbox, so
+        // the entries of later levels follow the first one
+        tx.execute(
+            "DELETE FROM jstz_ordering WHERE position >= (SELECT MIN(position) FROM jstz_ordering WHERE level > ?1)",
+            params![level],
+        )?;
+        let from: Option<u64> = tx.query_row(
+            "SELECT MIN(seq) FROM jstz_rollback WHERE l1_level > ?1",
+            params![level],
+            |row| row.get(0),
+        )?;
+        let Some(from) = from else {
+            tx.commit()?;
+            return Ok(requeued);
+        };
+        let records = {
+            let mut stmt = tx.prepare(
+                r#"
+                SELECT jstz_rollback.seq, undo, operation, l1_level, l1_message_id, operation_hash
+                FROM jstz_rollback
+                LEFT JOIN jstz_journal ON jstz_journal.seq = jstz_rollback.seq
+                WHERE jstz_rollback.seq >= ?1
+                ORDER BY jstz_rollback.seq DESC"#,
+            )?;
+            let rows = stmt.query_map(params![from], |row| {
+                Ok((
+                    row.get::<_, u64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<u32>>(3)?,
+                    row.get::<_, Option<u32>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                ))
+            })?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+
+        let mut restored = BTreeMap::new();
+        let mut rolled_back = vec![];
+        for (seq, undo, operation, l1_level, l1_message_id, operation_hash) in records {
+            let undo: Vec<(String, Option<String>)> =
+                serde_json::from_str(&undo).context("failed to deserialize undo")?;
+            for (key, value) in undo {
+                match &value {
+                    Some(value) => exec_write(&tx, &key, value)?,
+                    None => {
+                        exec_delete(&tx, &key)?;
+                    }
+                }
+                restored.insert(key, value);
+            }
+            if let Some(operation_hash) = operation_hash {
+                tx.execute(
+                    "DELETE FROM jstz_operation_status WHERE operation_hash = ?1",
+                    params![operation_hash],
+                )?;
+                tx.execute(
+                    "DELETE FROM jstz_account_operations WHERE operation_hash = ?1",
+                    params![operation_hash],
+                )?;
+                tx.execute(
+                    "DELETE FROM jstz_unpublished WHERE operation_hash = ?1",
+                    params![operation_hash],
+                )?;
+            }
+            let inbox_id =
+                l1_level
+                    .zip(l1_message_id)
+                    .map(|(l1_level, l1_message_id)| InboxId {
+                        l1_level,
+                        l1_message_id,
+                    });
+            // Operations read from the inbox of earlier levels are only executed after
+            // the first rolled back operation when executed concurrently
+            if let Some(operation) = operation {
+                if inbox_id.is_none_or(|id| id.l1_level <= level) {
+                    rolled_back.push(PersistedOperation {
+                        seq,
+                        operation,
+                        inbox_id,
+                    });
+                }
+            }
+        }
+        rolled_back.reverse();
+        requeued.extend(rolled_back);
+
+        tx.execute("DELETE FROM jstz_rollback WHERE seq >= ?1", params![from])?;
+        tx.execute("DELETE FROM jstz_history WHERE level > ?1", params![level])?;
+        let reverted =
+            tx.execute("DELETE FROM jstz_levels WHERE level > ?1", params![level])?;
+        if reverted > 0 {
+            tx.execute(
+                "INSERT INTO jstz_levels (level) VALUES (?1)",
+                params![level + 1],
+            )?;
+        }
+        if !restored.is_empty() {
+            let diff = restored.into_iter().collect::<Vec<_>>();
+            tx.execute(
+                "INSERT INTO jstz_journal (operation_hash, diff) VALUES (NULL, ?1)",
+                params![serde_json::to_string(&diff)?],
+            )?;
+        }
+        tx.commit()?;
+        Ok(requeued)
+    }
+
+    /// Forgets how to roll back the operations executed before the first operation
+    /// read from the inbox of L1 level `level` or later, see [`Db::roll_back`].
+    pub fn prune_rollback(&self, level: u32) -> Result<()> {
+        let conn = self.connection()?;
+        conn.execute(
+            "DELETE FROM jstz_rollback WHERE seq < (SELECT MIN(seq) FROM jstz_rollback WHERE l1_level >= ?1)",
+            params![level],
+        )?;
+        Ok(())
+    }
+
+    /// Persists an operation pushed to the sequencer queue, see
+    /// [`PersistedOperation`]. Returns its sequence number.
+    pub fn enqueue(&self, operation: &str, inbox_id: Option<&InboxId>) -> Result<u64> {
+        let conn = self.connection()?;
+        conn.execute(
+            "INSERT INTO jstz_queue (operation, l1_level, l1_message_id) VALUES (?1, ?2, ?3)",
+            params![
+                operation,
+                inbox_id.map(|id| id.l1_level),
+                inbox_id.map(|id| id.l1_message_id)
+            ],
+        )?;
+        Ok(conn.last_insert_rowid() as u64)
+    }
+
+    /// Removes an operation from the persisted queue without executing it.
+    pub fn dequeue(&self, seq: u64) -> Result<()> {
+        let conn = self.connection()?;
+        conn.execute("DELETE FROM jstz_queue WHERE seq = ?1", params![seq])?;
+        Ok(())
+    }
+
+    /// Returns up to `limit` executed operations injected in the node that were not
+    /// published yet, in execution order. The operations are hex-encoded signed
+    /// operations.
+    pub fn unpublished_operations(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<PersistedOperation>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT seq, operation FROM jstz_unpublished ORDER BY seq LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit], |row| {
+            Ok(PersistedOperation {
+                seq: row.get(0)?,
+                operation: row.get(1)?,
+                inbox_id: None,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Forgets the unpublished operations up to sequence number `seq`, once
+    /// published, see [`Db::unpublished_operations`].
+    pub fn mark_published(&self, seq: u64) -> Result<()> {
+        let conn = self.connection()?;
+        conn.execute("DELETE FROM jstz_unpublished WHERE seq <= ?1", params![seq])?;
+        Ok(())
+    }
+
+    /// Returns the persisted operations of the sequencer queue, in order.
+    pub fn queued_operations(&self) -> Result<Vec<PersistedOperation>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT seq, operation, l1_level, l1_message_id FROM jstz_queue ORDER BY seq",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let l1_level: Option<u32> = row.get(2)?;
+            let l1_message_id: Option<u32> = row.get(3)?;
+            Ok(PersistedOperation {
+                seq: row.get(0)?,
+                operation: row.get(1)?,
+                inbox_id: l1_level
+                    .zip(l1_message_id)
+                    .map(|(l1_level, l1_message_id)| InboxId {
+                        l1_level,
+                        l1_message_id,
+                    }),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Records that operation `operation_hash` reached `state`, read from the inbox
+    /// of L1 level `level` if any. The transition is ignored if the operation already
+    /// reached a state of the same or a later stage, so that replayed operations do
+    /// not move back, but the level is still recorded.
+    pub fn set_operation_state(
+        &self,
+        operation_hash: &str,
+        state: OperationState,
+        level: Option<u32>,
+    ) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let conn = self.connection()?;
+        conn.execute(
+            r#"
+            INSERT INTO jstz_operation_status (operation_hash, state, rank, level, timestamp)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT (operation_hash) DO UPDATE SET
+                state = CASE WHEN excluded.rank > rank THEN excluded.state ELSE state END,
+                timestamp = CASE WHEN excluded.rank > rank THEN excluded.timestamp ELSE timestamp END,
+                rank = MAX(excluded.rank, rank),
+                level = COALESCE(excluded.level, level)"#,
+            params![operation_hash, state_as_str(state), state_rank(state), level, timestamp],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the last recorded state of operation `operation_hash`, or `None` if
+    /// the operation is unknown.
+    pub fn operation_status(
+        &self,
+        operation_hash: &str,
+    ) -> Result<Option<OperationStatus>> {
+        let conn = self.connection()?;
+        let row = conn
+            .query_row(
+                "SELECT state, level, timestamp FROM jstz_operation_status WHERE operation_hash = ?1",
+                params![operation_hash],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, Option<u32>>(1)?,
+                        row.get::<_, u64>(2)?,
+                    ))
+                },
+            )
+            .optional()?;
+        row.map(|(state, level, timestamp)| {
+            Ok(OperationStatus {
+                state: state_from_str(&state)
+                    .with_context(|| format!("unknown operation state '{state}'"))?,
+                level,
+                timestamp,
+            })
+        })
+        .transpose()
+    }
+
+    /// Removes the receipt attestation and the state of the operations recorded at an
+    /// L1 level at least `retention` levels before the last recorded level, except
+    /// for the operations of `pinned`. The receipts are left in storage, which only
+    /// the kernel prunes. Returns the hashes of the pruned operations.
+    pub fn prune_receipts(
+        &self,
+        retention: u32,
+        pinned: &HashSet<String>,
+    ) -> Result<Vec<String>> {
+        let mut conn = self.connection()?;
+        let tx = conn.transaction()?;
+        let expired = {
+            let mut stmt = tx.prepare(
+                r#"
+                SELECT operation_hash FROM jstz_operation_status
+                WHERE level + ?1 <= (SELECT MAX(level) FROM jstz_operation_status)"#,
+            )?;
+            let rows = stmt.query_map(params![retention], |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<Vec<String>>>()?
+        };
+        let expired: Vec<String> = expired
+            .into_iter()
+            .filter(|hash| !pinned.contains(hash))
+            .collect();
+        for hash in &expired {
+            tx.execute(
+                "DELETE FROM jstz_receipt_attestation WHERE operation_hash = ?1",
+                params![hash],
+            )?;
+            tx.execute(
+                "DELETE FROM jstz_operation_status WHERE operation_hash = ?1",
+                params![hash],
+            )?;
+        }
+        tx.commit()?;
+        Ok(expired)
+    }
+
+    /// Forgets the state of operation `operation_hash`, e.g. because it was dropped
+    /// from the queue before being executed.
+    pub fn forget_operation_state(&self, operation_hash: &str) -> Result<()> {
+        let conn = self.connection()?;
+        conn.execute(
+            "DELETE FROM jstz_operation_status WHERE operation_hash = ?1",
+            params![operation_hash],
+        )?;
+        Ok(())
+    }
+
+    /// Records operation `operation_hash` in the history of the accounts `addresses`.
+    /// Operations already in the history of an account keep their position.
+    pub fn index_account_operation(
+        &self,
+        operation_hash: &str,
+        addresses: &[String],
+    ) -> Result<()> {
+        let mut conn = self.connection()?;
+        let tx = conn.transaction()?;
+        for address in addresses {
+            tx.execute(
+                "INSERT OR IGNORE INTO jstz_account_operations (address, operation_hash) VALUES (?1, ?2)",
+                params![address, operation_hash],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Returns the addresses of the accounts written so far in `journal`, i.e. whose
+    /// account, key-value store or ticket balances were written, see [`key_account`].
+    pub fn journal_accounts(&self, journal: Option<JournalId>) -> Result<Vec<String>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT jstz_key FROM jstz_undo WHERE journal IS ?1 ORDER BY jstz_key",
+        )?;
+        let rows = stmt.query_map(params![journal], |row| row.get::<_, String>(0))?;
+        let mut accounts = Vec::new();
+        for key in rows {
+            if let Some(address) = key_account(&key?) {
+                if !accounts.iter().any(|a| a == address) {
+                    accounts.push(address.to_string());
+                }
+            }
+        }
+        Ok(accounts)
+    }
+
+    /// Returns up to `limit` operations of the history of account `address`, most
+    /// recent first, starting after the page that returned `cursor` if any.
+    pub fn account_operations(
+        &self,
+        address: &str,
+        limit: usize,
+        cursor: Option<u64>,
+    ) -> Result<AccountOperations> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT seq, operation_hash FROM jstz_account_operations WHERE address = ?1 AND seq < ?2 ORDER BY seq DESC LIMIT ?3",
+        )?;
+        // Fetch one more row to tell whether there is a next page
+        let rows = stmt.query_map(
+            params![address, cursor.unwrap_or(i64::MAX as u64), limit + 1],
+            |row| Ok((row.get::<_, u64>(0)?, row.get::<_, String>(1)?)),
+        )?;
+        let mut rows = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+        let next_cursor = if rows.len() > limit {
+            rows.truncate(limit);
+            rows.last().map(|(seq, _)| *seq)
+        } else {
+            None
+        };
+        Ok(AccountOperations {
+            operations: rows.into_iter().map(|(_, hash)| hash).collect(),
+            next_cursor,
+        })
+    }
+
+    /// Returns the values operation `operation_hash` wrote, or `None` if the
+    /// operation has no journal entry, e.g. because it was not executed yet. Writes of
+    /// operations executed concurrently are not included, see
+    /// [`JournalEntry::operation_diff`].
+    pub fn operation_diff(&self, operation_hash: &str) -> Result<Option<OperationDiff>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT COALESCE(operation_diff, diff) FROM jstz_journal WHERE operation_hash = ?1 ORDER BY seq",
+        )?;
+        let rows =
+            stmt.query_map(params![operation_hash], |row| row.get::<_, String>(0))?;
+        let mut updates = BTreeMap::new();
+        let mut found = false;
+        for row in rows {
+            let diff: Vec<(String, Option<String>)> = serde_json::from_str(&row?)
+                .context("failed to deserialize journal diff")?;
+            updates.extend(diff);
+            found = true;
+        }
+        Ok(found.then(|| OperationDiff {
+            updates: updates
+                .into_iter()
+                .map(|(key, value)| KeyUpdate { key, value })
+                .collect(),
+        }))
+    }
+
+    /// Returns up to `limit` journal entries, starting from sequence number `from`.
+    pub fn journal_entries(&self, from: u64, limit: usize) -> Result<Vec<JournalEntry>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT seq, operation_hash, diff, operation_diff FROM jstz_journal WHERE seq >= ?1 ORDER BY seq LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![from, limit], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })?;
+        let mut entries = vec![];
+        for row in rows {
+            let (seq, operation_hash, diff, operation_diff) = row?;
+            entries.push(JournalEntry {
+                seq,
+                operation_hash,
+                diff: serde_json::from_str(&diff)
+                    .context("failed to deserialize journal diff")?,
+                operation_diff: operation_diff
+                    .map(|diff| serde_json::from_str(&diff))
+                    .transpose()
+                    .context("failed to deserialize operation diff")?,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Returns the sequence number of the last journal entry, or `None` if the
+    /// journal is empty.
+    pub fn last_journal_seq(&self) -> Result<Option<u64>> {
+        let conn = self.connection()?;
+        Ok(conn.query_row("SELECT MAX(seq) FROM jstz_journal", [], |row| row.get(0))?)
+    }
+
+    /// Applies the journal entry `entry` of another database, e.g. of the primary
+    /// of a replica: the keys are set to the values of its diff, and the entry is
+    /// appended to the journal with the same sequence number.
+    pub fn apply_journal_entry(&self, entry: &JournalEntry) -> Result<()> {
+        let mut conn = self.connection()?;
+        let tx = conn.transaction()?;
+        for (key, value) in &entry.diff {
+            match value {
+                Some(value) => exec_write(&tx, key, value)?,
+                None => {
+                    exec_delete(&tx, key)?;
+                }
+            }
+        }
+        tx.execute(
+            "INSERT INTO jstz_journal (seq, operation_hash, diff, operation_diff) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                entry.seq,
+                entry.operation_hash,
+                serde_json::to_string(&entry.diff)?,
+                entry
+                    .operation_diff
+                    .as_ref()
+                    .map(serde_json::to_string)
+                    .transpose()?
+            ],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Returns the entries of the ordering log of L1 level `level`, in execution
+    /// order.
+    pub fn ordering_entries(&self, level: u32) -> Result<Vec<OrderingEntry>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT position, operation_hash, level, digest, signature FROM jstz_ordering WHERE level = ?1 ORDER BY position",
+        )?;
+        let rows = stmt.query_map(params![level], |row| {
+            Ok(OrderingEntry {
+                position: row.get(0)?,
+                operation_hash: row.get(1)?,
+                level: row.get(2)?,
+                digest: row.get(3)?,
+                signature: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Signs the binary-encoded receipt `receipt` of operation `operation_hash` with
+    /// `signer`, replacing the attestation of a previous receipt of the operation.
+    /// The signed message is the digest of the receipt prefixed with
+    /// [`RECEIPT_ATTESTATION_TAG`], see [`receipt_attestation_message`].
+    pub fn attest_receipt(
+        &self,
+        operation_hash: &str,
+        receipt: &[u8],
+        signer: &KeyPair,
+    ) -> Result<()> {
+        let digest = receipt_digest(receipt);
+        let signature = signer
+            .1
+            .sign(receipt_attestation_message(&digest))
+            .context("failed to sign receipt")?;
+        self.connection()?.execute(
+            "INSERT OR REPLACE INTO jstz_receipt_attestation (operation_hash, digest, signature) VALUES (?1, ?2, ?3)",
+            params![operation_hash, digest.to_string(), signature.to_base58()],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the attestation of the receipt of operation `operation_hash`, see
+    /// [`Db::attest_receipt`]
+    pub fn receipt_attestation(
+        &self,
+        operation_hash: &str,
+    ) -> Result<Option<ReceiptAttestation>> {
+        Ok(self
+            .connection()?
+            .query_row(
+                "SELECT digest, signature FROM jstz_receipt_attestation WHERE operation_hash = ?1",
+                params![operation_hash],
+                |row| {
+                    Ok(ReceiptAttestation {
+                        digest: row.get(0)?,
+                        signature: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+}
+
+/// Tag prefixed to the receipt digests signed by the sequencer. The injector key
+/// also signs operations and ordering digests, so the tag keeps a receipt
+/// attestation from being valid as any other signature.
+pub const RECEIPT_ATTESTATION_TAG: &[u8] = b"jstz:receipt_attestation:";
+
+/// Digest of a binary-encoded receipt, attested by the sequencer
+pub fn receipt_digest(receipt: &[u8]) -> Blake2b {
+    Blake2b::from(receipt)
+}
+
+/// Message signed by the sequencer to attest the receipt of digest `digest`
+pub fn receipt_attestation_message(digest: &Blake2b) -> Vec<u8> {
+    [RECEIPT_ATTESTATION_TAG, digest.as_ref()].concat()
+}
+
+/// Digest of an entry of the ordering log, chained to the digest `previous` of the
+/// previous entry if any
+pub fn ordering_digest(
+    previous: Option<&Blake2b>,
+    position: u64,
+    level: u32,
+    operation_hash: &str,
+) -> Blake2b {
+    let mut bytes = previous.map(|d| d.as_ref().to_vec()).unwrap_or_default();
+    bytes.extend_from_slice(&position.to_be_bytes());
+    bytes.extend_from_slice(&level.to_be_bytes());
+    bytes.extend_from_slice(operation_hash.as_bytes());
+    Blake2b::from(&bytes)
+}
+
+/// Appends operation `operation_hash` of the queued operation `dequeued` to the
+/// ordering log, see [`OrderingEntry`]. Must be called before the operation is
+/// removed from `jstz_queue`.
+fn exec_append_ordering(
+    conn: &Connection,
+    operation_hash: &str,
+    dequeued: u64,
+    signer: &KeyPair,
+) -> Result<()> {
+    let previous = conn
+        .query_row(
+            "SELECT position, level, digest FROM jstz_ordering ORDER BY position DESC LIMIT 1",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, u64>(0)?,
+                    row.get::<_, u32>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            },
+        )
+        .optional()?;
+    let l1_level: Option<u32> = conn
+        .query_row(
+            "SELECT l1_level FROM jstz_queue WHERE seq = ?1",
+            params![dequeued],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    let (position, level, previous_digest) = match previous {
+        Some((position, level, digest)) => (
+            position + 1,
+            l1_level.unwrap_or(level),
+            Some(Blake2b::try_parse(digest).context("invalid ordering digest")?),
+        ),
+        None => (1, l1_level.unwrap_or_default(), None),
+    };
+    let digest =
+        ordering_digest(previous_digest.as_ref(), position, level, operation_hash);
+    let signature = signer
+        .1
+        .sign(digest.as_ref())
+        .context("failed to sign ordering entry")?;
+    conn.execute(
+        "INSERT INTO jstz_ordering (position, operation_hash, level, digest, signature) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            position,
+            operation_hash,
+            level,
+            digest.to_string(),
+            signature.to_base58()
+        ],
+    )?;
+    Ok(())
 }
 
 /// Reads a row using an existing database connection.
@@ -201,27 +1364,238 @@ pub fn exec_delete(conn: &Connection, key: &str) -> Result<usize> {
     Ok(conn.execute("DELETE FROM jstz_kv WHERE jstz_key = ?1", params![key])?)
 }
 
+/// Records the start of L1 level `level`, see [`exec_write_at`].
+pub fn exec_start_level(conn: &Connection, level: u32) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO jstz_levels (level) VALUES (?1)",
+        params![level],
+    )?;
+    exec_prune_history(conn, level.saturating_sub(HISTORY_RETENTION_LEVELS))
+}
+
+/// Forgets the history of the levels before `first`. The last value of each key
+/// before `first` is kept, as its value at the end of the level preceding `first`.
+fn exec_prune_history(conn: &Connection, first: u32) -> Result<()> {
+    let recorded: Option<u32> =
+        conn.query_row("SELECT MIN(level) FROM jstz_levels", [], |row| row.get(0))?;
+    let Some(recorded) = recorded.filter(|recorded| *recorded < first) else {
+        return Ok(());
+    };
+    // Values superseded by a value of a level being forgotten
+    conn.execute(
+        r#"
+        DELETE FROM jstz_history WHERE rowid IN (
+            SELECT older.rowid FROM jstz_history AS newer
+            JOIN jstz_history AS older
+            ON older.jstz_key = newer.jstz_key AND older.level < newer.level
+            WHERE newer.level BETWEEN ?1 AND ?2
+        )"#,
+        params![recorded, first - 1],
+    )?;
+    conn.execute("DELETE FROM jstz_levels WHERE level < ?1", params![first])?;
+    Ok(())
+}
+
+/// Inserts a record written at L1 level `level` using an existing database connection,
+/// recording its value in the history.
+pub fn exec_write_at(
+    conn: &Connection,
+    level: u32,
+    key: &str,
+    value: &str,
+) -> Result<()> {
+    exec_record_history_baseline(conn, key)?;
+    exec_write(conn, key, value)?;
+    exec_record_history(conn, level, key)
+}
+
+/// Deletes a row at L1 level `level` using an existing database connection, recording
+/// the deletion in the history.
+pub fn exec_delete_at(conn: &Connection, level: u32, key: &str) -> Result<usize> {
+    exec_record_history_baseline(conn, key)?;
+    let deleted = exec_delete(conn, key)?;
+    exec_record_history(conn, level, key)?;
+    Ok(deleted)
+}
+
+/// Records the value of a key before its first recorded write, as its value at the
+/// end of the level preceding the first recorded level.
+fn exec_record_history_baseline(conn: &Connection, key: &str) -> Result<()> {
+    conn.execute(
+        r#"
+        INSERT INTO jstz_history (level, jstz_key, jstz_value)
+        SELECT (SELECT MIN(level) FROM jstz_levels) - 1, ?1, (SELECT jstz_value FROM jstz_kv WHERE jstz_key = ?1)
+        WHERE NOT EXISTS (SELECT 1 FROM jstz_history WHERE jstz_key = ?1)"#,
+        params![key],
+    )?;
+    Ok(())
+}
+
+/// Records `value`, the value of a key before its first recorded write, like
+/// [`exec_record_history_baseline`] once the write is made.
+fn exec_record_history_baseline_value(
+    conn: &Connection,
+    key: &str,
+    value: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        r#"
+        INSERT INTO jstz_history (level, jstz_key, jstz_value)
+        SELECT (SELECT MIN(level) FROM jstz_levels) - 1, ?1, ?2
+        WHERE NOT EXISTS (SELECT 1 FROM jstz_history WHERE jstz_key = ?1)"#,
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+/// Records the current value of a key, `NULL` if it was deleted, as its value at
+/// the end of L1 level `level`.
+fn exec_record_history(conn: &Connection, level: u32, key: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO jstz_history (level, jstz_key, jstz_value) VALUES (?1, ?2, (SELECT jstz_value FROM jstz_kv WHERE jstz_key = ?2))",
+        params![level, key],
+    )?;
+    Ok(())
+}
+
 /// Deletes rows whose keys match a given prefix using an existing database connection.
 pub fn exec_delete_glob(conn: &Connection, path: &str) -> Result<()> {
-    let mut prefix = path.to_string();
-    if !prefix.ends_with("/") {
-        prefix += "/";
+    conn.execute(
+        "DELETE FROM jstz_kv WHERE jstz_key GLOB ?1",
+        params![subkey_glob(path)],
+    )?;
+    Ok(())
+}
+
+/// Forgets the messages provided to the RISCV PVM from the first one read from the
+/// inbox of an L1 level after `level`. Returns the forgotten operations injected in
+/// the node, in order, see [`Db::roll_back`].
+fn exec_roll_back_riscv_inputs(
+    conn: &Connection,
+    level: u32,
+) -> Result<Vec<PersistedOperation>> {
+    let from: Option<u64> = conn.query_row(
+        "SELECT MIN(seq) FROM jstz_riscv_inputs WHERE l1_level > ?1",
+        params![level],
+        |row| row.get(0),
+    )?;
+    let Some(from) = from else {
+        return Ok(vec![]);
+    };
+    let requeued = {
+        let mut stmt = conn.prepare(
+            "SELECT seq, operation FROM jstz_riscv_inputs WHERE seq >= ?1 AND operation IS NOT NULL ORDER BY seq",
+        )?;
+        let rows = stmt.query_map(params![from], |row| {
+            Ok(PersistedOperation {
+                seq: row.get(0)?,
+                operation: row.get(1)?,
+                inbox_id: None,
+            })
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+    conn.execute(
+        "DELETE FROM jstz_riscv_inputs WHERE seq >= ?1",
+        params![from],
+    )?;
+    Ok(requeued)
+}
+
+/// Adds column `column` of type `ty` to `table` if the table was created without it
+fn add_missing_column(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    ty: &str,
+) -> Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+        params![table, column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {table} ADD COLUMN {column} {ty}"), [])?;
     }
-    prefix += "*";
+    Ok(())
+}
+
+/// Address of the account a storage key belongs to: `/jstz_account/{address}`,
+/// `/jstz_kv/{address}/..` and `/ticket_table/{ticket}/{address}`
+fn key_account(key: &str) -> Option<&str> {
+    let mut segments = key.strip_prefix('/')?.split('/');
+    let address = match segments.next()? {
+        "jstz_account" | "jstz_kv" => segments.next(),
+        "ticket_table" => segments.nth(1),
+        _ => None,
+    };
+    address.filter(|address| !address.is_empty())
+}
 
+/// Records the value of a key before it is written or deleted in `journal`, so that the
+/// write can be rolled back if its operation does not complete.
+pub fn exec_record_undo(
+    conn: &Connection,
+    journal: Option<JournalId>,
+    key: &str,
+) -> Result<()> {
     conn.execute(
-        "DELETE FROM jstz_kv WHERE jstz_key GLOB ?1",
-        params![prefix],
+        "INSERT INTO jstz_undo (jstz_key, jstz_value, journal) VALUES (?1, (SELECT jstz_value FROM jstz_kv WHERE jstz_key = ?1), ?2)",
+        params![key, journal],
+    )?;
+    Ok(())
+}
+
+/// Writes `value` to a key in `journal`, recording the value of the key before it is
+/// written, see [`exec_record_undo`], and the value written by the operation of
+/// `journal`, see [`JournalEntry::operation_diff`].
+pub fn exec_record_write(
+    conn: &Connection,
+    journal: Option<JournalId>,
+    key: &str,
+    value: &str,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO jstz_undo (jstz_key, jstz_value, journal, written) VALUES (?1, (SELECT jstz_value FROM jstz_kv WHERE jstz_key = ?1), ?2, ?3)",
+        params![key, journal, value],
+    )?;
+    exec_write(conn, key, value)
+}
+
+/// Records the values of the keys matching a given prefix before they are deleted in
+/// `journal`.
+pub fn exec_record_undo_glob(
+    conn: &Connection,
+    journal: Option<JournalId>,
+    path: &str,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO jstz_undo (jstz_key, jstz_value, journal) SELECT jstz_key, jstz_value, ?2 FROM jstz_kv WHERE jstz_key GLOB ?1",
+        params![subkey_glob(path), journal],
     )?;
     Ok(())
 }
 
+fn subkey_glob(path: &str) -> String {
+    let mut prefix = path.to_string();
+    if !prefix.ends_with("/") {
+        prefix += "/";
+    }
+    prefix + "*"
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
     use rusqlite::{params, Connection, OptionalExtension};
     use tempfile::NamedTempFile;
 
-    use crate::sequencer::db::Db;
+    use crate::sequencer::db::{
+        AccountOperations, Db, JournalEntry, KeyUpdate, OperationDiff, OperationState,
+        OperationStatus, PersistedOperation,
+    };
+    use jstz_proto::operation::internal::InboxId;
 
     fn insert(conn: &Connection, key: &str, value: &str) {
         conn.execute(
@@ -369,6 +1743,400 @@ mod tests {
         assert_eq!(super::exec_delete(&conn, path).unwrap(), 0);
     }
 
+    #[test]
+    fn recover_rolls_back_writes_since_last_journal_entry() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let conn = db.connection().unwrap();
+
+        super::exec_record_undo(&conn, None, "/foo").unwrap();
+        super::exec_write(&conn, "/foo", "aa").unwrap();
+        super::exec_record_undo(&conn, None, "/bar").unwrap();
+        super::exec_write(&conn, "/bar", "bb").unwrap();
+        assert_eq!(db.commit_journal(None, Some("op1"), None).unwrap(), Some(1));
+
+        super::exec_record_undo(&conn, None, "/foo").unwrap();
+        super::exec_write(&conn, "/foo", "cc").unwrap();
+        super::exec_record_undo(&conn, None, "/foo").unwrap();
+        super::exec_write(&conn, "/foo", "dd").unwrap();
+        super::exec_record_undo(&conn, None, "/baz").unwrap();
+        super::exec_write(&conn, "/baz", "ee").unwrap();
+        super::exec_record_undo(&conn, None, "/bar").unwrap();
+        super::exec_delete(&conn, "/bar").unwrap();
+        drop(conn);
+
+        // reopening the database rolls back the writes of the incomplete operation
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        assert_eq!(db.read_key("/foo").unwrap().unwrap(), "aa");
+        assert_eq!(db.read_key("/bar").unwrap().unwrap(), "bb");
+        assert!(db.read_key("/baz").unwrap().is_none());
+        assert_eq!(db.recover().unwrap(), 0);
+    }
+
+    #[test]
+    fn commit_journal_appends_storage_diff() {
+        let db = Db::init(Some("")).unwrap();
+        let conn = db.connection().unwrap();
+        assert_eq!(db.commit_journal(None, None, None).unwrap(), None);
+
+        super::exec_write(&conn, "/foo/aa", "1").unwrap();
+        super::exec_record_write(&conn, None, "/bar", "2").unwrap();
+        super::exec_record_write(&conn, None, "/bar", "3").unwrap();
+        db.commit_journal(None, Some("op1"), None).unwrap();
+
+        super::exec_record_undo_glob(&conn, None, "/foo").unwrap();
+        super::exec_delete_glob(&conn, "/foo").unwrap();
+        db.commit_journal(None, None, None).unwrap();
+
+        assert_eq!(
+            db.journal_entries(0, 10).unwrap(),
+            vec![
+                JournalEntry {
+                    seq: 1,
+                    operation_hash: Some("op1".to_string()),
+                    diff: vec![("/bar".to_string(), Some("3".to_string()))],
+                    operation_diff: None,
+                },
+                JournalEntry {
+                    seq: 2,
+                    operation_hash: None,
+                    diff: vec![("/foo/aa".to_string(), None)],
+                    operation_diff: None,
+                },
+            ]
+        );
+        assert_eq!(db.journal_entries(2, 10).unwrap().len(), 1);
+        assert_eq!(db.journal_entries(1, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn apply_journal_entry_replicates_writes() {
+        let primary_file = NamedTempFile::new().unwrap();
+        let primary = Db::init(primary_file.path().to_str()).unwrap();
+        let conn = primary.connection().unwrap();
+        super::exec_record_write(&conn, None, "/foo", "1").unwrap();
+        super::exec_record_write(&conn, None, "/bar", "2").unwrap();
+        primary.commit_journal(None, Some("op1"), None).unwrap();
+        super::exec_record_undo(&conn, None, "/foo").unwrap();
+        super::exec_delete(&conn, "/foo").unwrap();
+        primary.commit_journal(None, Some("op2"), None).unwrap();
+
+        let replica_file = NamedTempFile::new().unwrap();
+        let replica = Db::init(replica_file.path().to_str()).unwrap();
+        assert_eq!(replica.last_journal_seq().unwrap(), None);
+        for entry in primary.journal_entries(0, 10).unwrap() {
+            replica.apply_journal_entry(&entry).unwrap();
+        }
+        assert_eq!(replica.last_journal_seq().unwrap(), Some(2));
+        assert_eq!(replica.read_key("/foo").unwrap(), None);
+        assert_eq!(replica.read_key("/bar").unwrap(), Some("2".to_string()));
+        assert_eq!(
+            replica.journal_entries(0, 10).unwrap(),
+            primary.journal_entries(0, 10).unwrap()
+        );
+        assert_eq!(
+            replica.operation_diff("op1").unwrap(),
+            primary.operation_diff("op1").unwrap()
+        );
+    }
+
+    #[test]
+    fn operation_diff_merges_journal_entries() {
+        let db = Db::init(Some("")).unwrap();
+        let conn = db.connection().unwrap();
+        assert_eq!(db.operation_diff("op1").unwrap(), None);
+
+        super::exec_record_write(&conn, None, "/foo", "1").unwrap();
+        super::exec_record_write(&conn, None, "/bar", "2").unwrap();
+        db.commit_journal(None, Some("op1"), None).unwrap();
+
+        super::exec_record_write(&conn, None, "/baz", "3").unwrap();
+        db.commit_journal(None, Some("op2"), None).unwrap();
+
+        super::exec_record_undo(&conn, None, "/foo").unwrap();
+        super::exec_delete(&conn, "/foo").unwrap();
+        db.commit_journal(None, Some("op1"), None).unwrap();
+
+        assert_eq!(
+            db.operation_diff("op1").unwrap(),
+            Some(OperationDiff {
+                updates: vec![
+                    KeyUpdate {
+                        key: "/bar".to_string(),
+                        value: Some("2".to_string()),
+                    },
+                    KeyUpdate {
+                        key: "/foo".to_string(),
+                        value: None,
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn operation_diff_is_scoped_to_journal() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let conn = db.connection().unwrap();
+        let (op1, op2) = (db.new_journal(), db.new_journal());
+        super::exec_record_write(&conn, Some(op1), "/foo", "1").unwrap();
+        super::exec_record_write(&conn, Some(op1), "/bar", "1").unwrap();
+        super::exec_record_write(&conn, Some(op2), "/foo", "2").unwrap();
+        db.commit_journal(Some(op1), Some("op1"), None).unwrap();
+        db.commit_journal(Some(op2), Some("op2"), None).unwrap();
+
+        let diff = |updates: &[(&str, &str)]| {
+            Some(OperationDiff {
+                updates: updates
+                    .iter()
+                    .map(|(key, value)| KeyUpdate {
+                        key: key.to_string(),
+                        value: Some(value.to_string()),
+                    })
+                    .collect(),
+            })
+        };
+        // the journal holds the current values, for replicas, and the operation diff
+        // the values written by the operation
+        let entries = db.journal_entries(0, 10).unwrap();
+        assert_eq!(
+            entries[0].diff,
+            vec![
+                ("/bar".to_string(), Some("1".to_string())),
+                ("/foo".to_string(), Some("2".to_string()))
+            ]
+        );
+        assert_eq!(
+            db.operation_diff("op1").unwrap(),
+            diff(&[("/bar", "1"), ("/foo", "1")])
+        );
+        assert_eq!(db.operation_diff("op2").unwrap(), diff(&[("/foo", "2")]));
+        assert_eq!(entries[1].operation_diff, None);
+    }
+
+    #[test]
+    fn commit_journal_removes_dequeued_operation() {
+        let db = Db::init(Some("")).unwrap();
+        let inbox_id = InboxId {
+            l1_level: 3,
+            l1_message_id: 1,
+        };
+        let first = db.enqueue("aa", None).unwrap();
+        let second = db.enqueue("bb", Some(&inbox_id)).unwrap();
+        assert_eq!(
+            db.queued_operations().unwrap(),
+            vec![
+                PersistedOperation {
+                    seq: first,
+                    operation: "aa".to_string(),
+                    inbox_id: None,
+                },
+                PersistedOperation {
+                    seq: second,
+                    operation: "bb".to_string(),
+                    inbox_id: Some(inbox_id),
+                },
+            ]
+        );
+
+        assert_eq!(db.commit_journal(None, None, Some(first)).unwrap(), None);
+        let queued = db.queued_operations().unwrap();
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].seq, second);
+    }
+
+    #[test]
+    fn commit_riscv_input_records_message() {
+        let db = Db::init(Some("")).unwrap();
+        let inbox_id = InboxId {
+            l1_level: 3,
+            l1_message_id: 1,
+        };
+        let seq = db.enqueue("aa", Some(&inbox_id)).unwrap();
+        assert!(db.riscv_inputs().unwrap().is_empty());
+
+        db.commit_riscv_input(inbox_id, b"message", Some(seq))
+            .unwrap();
+        let node_id = InboxId {
+            l1_level: 0,
+            l1_message_id: 0,
+        };
+        db.commit_riscv_input(node_id, b"", None).unwrap();
+        assert!(db.queued_operations().unwrap().is_empty());
+        assert_eq!(
+            db.riscv_inputs().unwrap(),
+            vec![(inbox_id, b"message".to_vec()), (node_id, vec![])]
+        );
+    }
+
+    #[test]
+    fn roll_back_forgets_riscv_inputs() {
+        let db = Db::init(Some("")).unwrap();
+        let inbox_id = |l1_level, l1_message_id| InboxId {
+            l1_level,
+            l1_message_id,
+        };
+        let seq = db.enqueue("aa", Some(&inbox_id(1, 0))).unwrap();
+        db.commit_riscv_input(inbox_id(1, 0), b"first", Some(seq))
+            .unwrap();
+        let seq = db.enqueue("bb", Some(&inbox_id(2, 0))).unwrap();
+        db.commit_riscv_input(inbox_id(2, 0), b"second", Some(seq))
+            .unwrap();
+        let seq = db.enqueue("cc", None).unwrap();
+        db.commit_riscv_input(inbox_id(0, 0), b"injected", Some(seq))
+            .unwrap();
+
+        let requeued = db.roll_back(1).unwrap();
+        assert_eq!(requeued.len(), 1);
+        assert_eq!(requeued[0].operation, "cc");
+        assert_eq!(requeued[0].inbox_id, None);
+        assert_eq!(
+            db.riscv_inputs().unwrap(),
+            vec![(inbox_id(1, 0), b"first".to_vec())]
+        );
+    }
+
+    #[test]
+    fn commit_journal_records_history_at_inbox_level() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let conn = db.connection().unwrap();
+        let execute = |operation: &str, l1_level: Option<u32>, value: &str| {
+            let inbox_id = l1_level.map(|l1_level| InboxId {
+                l1_level,
+                l1_message_id: 0,
+            });
+            let id = db.enqueue(operation, inbox_id.as_ref()).unwrap();
+            super::exec_record_undo(&conn, None, "/foo").unwrap();
+            super::exec_write(&conn, "/foo", value).unwrap();
+            db.commit_journal(None, Some(operation), Some(id)).unwrap();
+        };
+
+        // written before any level was started
+        execute("op1", None, "01");
+        assert_eq!(db.recorded_levels().unwrap(), None);
+
+        execute("op2", Some(5), "02");
+        // operations injected in the node are recorded at the last started level
+        execute("op3", None, "03");
+        execute("op4", Some(6), "04");
+        assert_eq!(db.recorded_levels().unwrap(), Some((5, 6)));
+        let read_at = |level| db.read_key_at("/foo", level).unwrap();
+        assert_eq!(read_at(4).as_deref(), Some("01"));
+        assert_eq!(read_at(5).as_deref(), Some("03"));
+        assert_eq!(read_at(6).as_deref(), Some("04"));
+
+        // the history of rolled back levels is forgotten, and operations executed
+        // again are recorded at the next level
+        db.roll_back(5).unwrap();
+        assert_eq!(db.recorded_levels().unwrap(), Some((5, 6)));
+        assert_eq!(read_at(5).as_deref(), Some("03"));
+        assert_eq!(read_at(6).as_deref(), Some("03"));
+        execute("op5", None, "05");
+        assert_eq!(read_at(5).as_deref(), Some("03"));
+        assert_eq!(read_at(6).as_deref(), Some("05"));
+    }
+
+    #[test]
+    fn roll_back_restores_values_before_reverted_level() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let conn = db.connection().unwrap();
+        let execute =
+            |operation: &str, inbox_id: Option<InboxId>, writes: &[(&str, &str)]| {
+                let id = db.enqueue(operation, inbox_id.as_ref()).unwrap();
+                for (key, value) in writes {
+                    super::exec_record_undo(&conn, None, key).unwrap();
+                    super::exec_write(&conn, key, value).unwrap();
+                }
+                db.set_operation_state(operation, OperationState::Committed, None)
+                    .unwrap();
+                db.commit_journal(None, Some(operation), Some(id))
+                    .unwrap()
+                    .unwrap()
+            };
+
+        execute("op1", None, &[("/foo", "01")]);
+        execute(
+            "op2",
+            Some(InboxId {
+                l1_level: 2,
+                l1_message_id: 1,
+            }),
+            &[("/foo", "02"), ("/bar", "02")],
+        );
+        let seq = execute("op3", None, &[("/foo", "03")]);
+
+        // no operation was read from the inbox of a later level
+        assert!(db.roll_back(2).unwrap().is_empty());
+        assert_eq!(db.read_key("/foo").unwrap(), Some("03".to_string()));
+
+        assert_eq!(
+            db.roll_back(1).unwrap(),
+            vec![PersistedOperation {
+                seq,
+                operation: "op3".to_string(),
+                inbox_id: None,
+            }]
+        );
+        assert_eq!(db.read_key("/foo").unwrap(), Some("01".to_string()));
+        assert_eq!(db.read_key("/bar").unwrap(), None);
+        assert!(db.operation_status("op1").unwrap().is_some());
+        assert!(db.operation_status("op2").unwrap().is_none());
+        assert!(db.operation_status("op3").unwrap().is_none());
+        // the rollback is journaled
+        let last = db.journal_entries(0, 10).unwrap().pop().unwrap();
+        assert_eq!(last.operation_hash, None);
+        assert_eq!(
+            last.diff,
+            vec![
+                ("/bar".to_string(), None),
+                ("/foo".to_string(), Some("01".to_string()))
+            ]
+        );
+        // rolled back operations are not rolled back again
+        assert!(db.roll_back(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn roll_back_forgets_ordering_entries() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let injector = crate::test::default_injector();
+        let execute = |operation: &str, l1_level: Option<u32>| {
+            let inbox_id = l1_level.map(|l1_level| InboxId {
+                l1_level,
+                l1_message_id: 1,
+            });
+            let seq = db.enqueue(operation, inbox_id.as_ref()).unwrap();
+            db.commit_ordered_journal(None, operation, seq, &injector)
+                .unwrap();
+        };
+        let hashes = |level| {
+            db.ordering_entries(level)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.operation_hash)
+                .collect::<Vec<_>>()
+        };
+
+        execute("op1", Some(1));
+        execute("op2", None);
+        execute("op3", Some(2));
+        execute("op4", None);
+
+        db.roll_back(1).unwrap();
+        assert_eq!(hashes(1), ["op1", "op2"]);
+        assert!(hashes(2).is_empty());
+
+        // operations executed again are appended after the remaining entries
+        execute("op3", Some(2));
+        let entries = db.ordering_entries(2).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].position, 3);
+    }
+
     #[test]
     fn get_subkeys() {
         let db_file = NamedTempFile::new().unwrap();
@@ -405,4 +2173,235 @@ mod tests {
 
         assert!(db.get_subkeys("nonsense").unwrap().is_none());
     }
+
+    #[test]
+    fn find_subkeys() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let conn = db.connection().unwrap();
+        for key in ["foo", "foo/ab", "foo/b*", "foo/ba", "foo/bb", "foo/bb/cc"] {
+            insert(&conn, key, "1");
+        }
+
+        let find = |prefix, subkey_prefix, limit| {
+            db.find_subkeys(prefix, subkey_prefix, limit).unwrap()
+        };
+        assert_eq!(
+            find("foo", None, None).unwrap(),
+            ["", "ab", "b*", "ba", "bb"]
+        );
+        assert_eq!(find("foo", Some("b"), Some(2)).unwrap(), ["b*", "ba"]);
+        // Prefixes are matched literally
+        assert_eq!(find("foo", Some("b*"), None).unwrap(), ["b*"]);
+        assert_eq!(find("foo", None, Some(0)).unwrap(), Vec::<String>::new());
+        assert_eq!(find("foo", Some("c"), None).unwrap(), Vec::<String>::new());
+        assert!(find("nonsense", None, None).is_none());
+    }
+
+    #[test]
+    fn read_key_at_returns_value_at_end_of_level() {
+        let db = Db::init(Some("")).unwrap();
+        let conn = db.connection().unwrap();
+        assert_eq!(db.recorded_levels().unwrap(), None);
+
+        // written before the history started
+        super::exec_write(&conn, "/foo", "aa").unwrap();
+        super::exec_write(&conn, "/bar", "bb").unwrap();
+
+        super::exec_start_level(&conn, 5).unwrap();
+        super::exec_write_at(&conn, 5, "/foo", "cc").unwrap();
+        super::exec_write_at(&conn, 5, "/foo", "dd").unwrap();
+        super::exec_write_at(&conn, 5, "/baz", "ee").unwrap();
+
+        super::exec_start_level(&conn, 6).unwrap();
+        super::exec_start_level(&conn, 7).unwrap();
+        super::exec_delete_at(&conn, 7, "/foo").unwrap();
+        assert_eq!(db.recorded_levels().unwrap(), Some((5, 7)));
+
+        let read_at = |key, level| db.read_key_at(key, level).unwrap();
+        assert_eq!(read_at("/foo", 4).as_deref(), Some("aa"));
+        assert_eq!(read_at("/foo", 5).as_deref(), Some("dd"));
+        assert_eq!(read_at("/foo", 6).as_deref(), Some("dd"));
+        assert_eq!(read_at("/foo", 7), None);
+        assert_eq!(read_at("/baz", 4), None);
+        assert_eq!(read_at("/baz", 7).as_deref(), Some("ee"));
+        assert_eq!(read_at("/bar", 5).as_deref(), Some("bb"));
+    }
+
+    #[test]
+    fn prune_history_keeps_values_at_first_level() {
+        let db = Db::init(Some("")).unwrap();
+        let conn = db.connection().unwrap();
+        super::exec_write(&conn, "/foo", "aa").unwrap();
+        for level in 5..=8 {
+            super::exec_start_level(&conn, level).unwrap();
+        }
+        super::exec_write_at(&conn, 5, "/foo", "bb").unwrap();
+        super::exec_write_at(&conn, 6, "/foo", "cc").unwrap();
+        super::exec_write_at(&conn, 6, "/bar", "dd").unwrap();
+        super::exec_write_at(&conn, 8, "/foo", "ee").unwrap();
+
+        super::exec_prune_history(&conn, 7).unwrap();
+        assert_eq!(db.recorded_levels().unwrap(), Some((7, 8)));
+        let read_at = |key, level| db.read_key_at(key, level).unwrap();
+        assert_eq!(read_at("/foo", 6).as_deref(), Some("cc"));
+        assert_eq!(read_at("/foo", 7).as_deref(), Some("cc"));
+        assert_eq!(read_at("/foo", 8).as_deref(), Some("ee"));
+        assert_eq!(read_at("/bar", 6).as_deref(), Some("dd"));
+        let history: u32 = conn
+            .query_row("SELECT COUNT(*) FROM jstz_history", [], |row| row.get(0))
+            .unwrap();
+        // /foo at levels 6 and 8, /bar at level 6
+        assert_eq!(history, 3);
+
+        // Keys first written once pruned start from the first level
+        super::exec_write_at(&conn, 8, "/baz", "ff").unwrap();
+        assert_eq!(read_at("/baz", 6), None);
+    }
+
+    #[test]
+    fn prune_receipts_keeps_recent_and_pinned_operations() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let injector = crate::test::default_injector();
+        // op4 was not read from the inbox yet
+        for (hash, level) in [
+            ("op1", Some(1)),
+            ("op2", Some(2)),
+            ("op3", Some(5)),
+            ("op4", None),
+        ] {
+            db.write(&format!("/jstz_receipt/{hash}"), "00").unwrap();
+            db.attest_receipt(hash, &[0], &injector).unwrap();
+            db.set_operation_state(hash, OperationState::Committed, level)
+                .unwrap();
+        }
+
+        let pinned = HashSet::from(["op1".to_string()]);
+        let pruned = db.prune_receipts(3, &pinned).unwrap();
+        assert_eq!(pruned, ["op2"]);
+        assert!(db.receipt_attestation("op2").unwrap().is_none());
+        assert!(db.operation_status("op2").unwrap().is_none());
+        // Receipts are pruned by the kernel
+        assert!(db.key_exists("/jstz_receipt/op2").unwrap());
+        for hash in ["op1", "op3", "op4"] {
+            assert!(db.key_exists(&format!("/jstz_receipt/{hash}")).unwrap());
+            assert!(db.receipt_attestation(hash).unwrap().is_some());
+        }
+
+        assert!(db.prune_receipts(3, &pinned).unwrap().is_empty());
+    }
+
+    #[test]
+    fn operation_state_only_moves_forward() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        assert_eq!(db.operation_status("op").unwrap(), None);
+
+        db.set_operation_state("op", OperationState::Included, Some(7))
+            .unwrap();
+        db.set_operation_state("op", OperationState::Executing, None)
+            .unwrap();
+        let status = db.operation_status("op").unwrap().unwrap();
+        assert_eq!(status.state, OperationState::Executing);
+        // the inbox level is kept
+        assert_eq!(status.level, Some(7));
+
+        db.set_operation_state("op", OperationState::Committed, None)
+            .unwrap();
+        // replaying the operation does not move it back
+        db.set_operation_state("op", OperationState::Queued, None)
+            .unwrap();
+        db.set_operation_state("op", OperationState::Executing, None)
+            .unwrap();
+        assert!(matches!(
+            db.operation_status("op").unwrap(),
+            Some(OperationStatus {
+                state: OperationState::Committed,
+                level: Some(7),
+                ..
+            })
+        ));
+
+        // the level of an operation read from the inbox after its execution is
+        // recorded
+        db.set_operation_state("other", OperationState::Committed, None)
+            .unwrap();
+        db.set_operation_state("other", OperationState::Included, Some(9))
+            .unwrap();
+        let status = db.operation_status("other").unwrap().unwrap();
+        assert_eq!(status.state, OperationState::Committed);
+        assert_eq!(status.level, Some(9));
+    }
+
+    #[test]
+    fn account_operations_are_paginated_most_recent_first() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let page = |cursor| db.account_operations("alice", 2, cursor).unwrap();
+        assert_eq!(
+            page(None),
+            AccountOperations {
+                operations: vec![],
+                next_cursor: None
+            }
+        );
+
+        let accounts =
+            |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        db.index_account_operation("op1", &accounts(&["alice", "bob"]))
+            .unwrap();
+        db.index_account_operation("op2", &accounts(&["bob"]))
+            .unwrap();
+        db.index_account_operation("op3", &accounts(&["alice", "alice"]))
+            .unwrap();
+        db.index_account_operation("op4", &accounts(&["alice"]))
+            .unwrap();
+        // indexing an operation again keeps its position
+        db.index_account_operation("op1", &accounts(&["alice"]))
+            .unwrap();
+
+        let first = page(None);
+        assert_eq!(first.operations, ["op4", "op3"]);
+        let second = page(first.next_cursor);
+        assert_eq!(
+            second,
+            AccountOperations {
+                operations: vec!["op1".to_string()],
+                next_cursor: None
+            }
+        );
+        assert_eq!(
+            db.account_operations("bob", 10, None).unwrap().operations,
+            ["op2", "op1"]
+        );
+    }
+
+    #[test]
+    fn journal_accounts_are_read_from_written_keys() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let journal = db.new_journal();
+        let conn = db.connection().unwrap();
+        for key in [
+            "/jstz_account/alice",
+            "/jstz_kv/bob/counter",
+            "/jstz_kv/bob/nested/key",
+            "/ticket_table/ticket/carol",
+            "/jstz_receipt/op1",
+            "/ticket_table/ticket",
+        ] {
+            super::exec_record_undo(&conn, Some(journal), key).unwrap();
+        }
+        super::exec_record_undo(&conn, None, "/jstz_account/dave").unwrap();
+        drop(conn);
+
+        assert_eq!(
+            db.journal_accounts(Some(journal)).unwrap(),
+            ["alice", "bob", "carol"]
+        );
+        assert_eq!(db.journal_accounts(None).unwrap(), ["dave"]);
+        db.commit_journal(Some(journal), Some("op1"), None).unwrap();
+        assert!(db.journal_accounts(Some(journal)).unwrap().is_empty());
+    }
 }
@@ -1,11 +1,14 @@
 use std::{
     fmt::Debug,
-    fs::{File, OpenOptions},
+    fs::OpenOptions,
     io::Write,
     path::PathBuf,
-    sync::Arc,
+    sync::{atomic::Ordering, Arc},
 };
 
+use jstz_core::BinEncodable;
+use jstz_proto::receipt::Receipt;
+use jstz_utils::KeyPair;
 use parking_lot::Mutex;
 use r2d2::PooledConnection;
 use r2d2_sqlite::SqliteConnectionManager;
@@ -18,13 +21,23 @@ use tezos_smart_rollup::{
     types::{Message, RollupDalParameters, RollupMetadata},
 };
 
-use super::db::{exec_delete, exec_delete_glob, exec_read, exec_write, Db};
+use super::db::{
+    exec_delete, exec_delete_glob, exec_read, exec_record_undo, exec_record_undo_glob,
+    exec_record_write, Db, JournalId, OperationState,
+};
+use super::watchdog::WorkerStats;
+
+type DebugLog = Arc<Mutex<dyn Write + Send>>;
 
 #[derive(Clone)]
 pub struct Host {
     db: Db,
     preimage_dir: PathBuf,
-    log_file: Option<Arc<Mutex<File>>>,
+    log_file: Option<DebugLog>,
+    read_only: bool,
+    ordering_signer: Option<KeyPair>,
+    journal: Option<JournalId>,
+    stats: Arc<WorkerStats>,
 }
 
 impl Host {
@@ -33,9 +46,51 @@ impl Host {
             db,
             preimage_dir,
             log_file: None,
+            read_only: false,
+            ordering_signer: None,
+            journal: None,
+            stats: Arc::default(),
         }
     }
 
+    /// Returns a host writing to a new journal of the database, see
+    /// [`Db::new_journal`], so that its writes are committed independently of the
+    /// writes of the operations executed concurrently.
+    pub fn with_new_journal(&self) -> Self {
+        let mut host = self.clone();
+        host.journal = Some(self.db.new_journal());
+        host
+    }
+
+    /// Makes writes to the store fail, for executions that must not modify the
+    /// state of the sequencer.
+    #[cfg(feature = "simulation")]
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Appends the operations taken from the queue to the ordering log of the
+    /// database when committed, signed by `signer`, see
+    /// [`Db::commit_ordered_journal`]. Receipts are signed by `signer` too, see
+    /// [`Host::attest_receipt`].
+    pub fn with_ordering_signer(mut self, signer: KeyPair) -> Self {
+        self.ordering_signer = Some(signer);
+        self
+    }
+
+    /// Counts the failures of the host in `stats`, see
+    /// [`WorkerStats::attestation_failures`]
+    pub fn with_worker_stats(mut self, stats: Arc<WorkerStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    pub fn with_debug_log(mut self, log: DebugLog) -> Self {
+        self.log_file.replace(log);
+        self
+    }
+
     pub fn with_debug_log_file(
         mut self,
         log_path: &std::path::Path,
@@ -46,8 +101,70 @@ impl Host {
             .create(true)
             .append(true)
             .open(log_path)?;
-        self.log_file.replace(Arc::new(Mutex::new(log_file)));
-        Ok(self)
+        Ok(self.with_debug_log(Arc::new(Mutex::new(log_file))))
+    }
+
+    /// Appends the writes made by the host since the last call to the journal of
+    /// the database and removes the `dequeued` operation from the persisted queue,
+    /// see [`Db::commit_journal`]. Should be called once the operation that
+    /// made them completes.
+    pub fn commit_journal(
+        &self,
+        operation_hash: Option<&str>,
+        dequeued: Option<u64>,
+    ) -> anyhow::Result<()> {
+        match (operation_hash, dequeued, &self.ordering_signer) {
+            (Some(operation_hash), Some(dequeued), Some(signer)) => self
+                .db
+                .commit_ordered_journal(self.journal, operation_hash, dequeued, signer)?,
+            _ => self
+                .db
+                .commit_journal(self.journal, operation_hash, dequeued)?,
+        };
+        Ok(())
+    }
+
+    /// Signs `receipt` with the ordering signer if any, see [`Db::attest_receipt`].
+    /// Failures are counted in [`WorkerStats::attestation_failures`].
+    pub fn attest_receipt(&self, receipt: &Receipt) -> anyhow::Result<()> {
+        let Some(signer) = &self.ordering_signer else {
+            return Ok(());
+        };
+        let result = receipt
+            .encode()
+            .map_err(anyhow::Error::from)
+            .and_then(|encoded| {
+                self.db
+                    .attest_receipt(&receipt.hash().to_string(), &encoded, signer)
+            });
+        if result.is_err() {
+            self.stats
+                .attestation_failures
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Records the state of an operation, see [`Db::set_operation_state`]
+    pub fn set_operation_state(
+        &self,
+        operation_hash: &str,
+        state: OperationState,
+    ) -> anyhow::Result<()> {
+        self.db.set_operation_state(operation_hash, state, None)
+    }
+
+    /// Records an operation in the history of `addresses` and of the accounts it wrote
+    /// to, e.g. in nested calls and transfers, see [`Db::index_account_operation`].
+    /// Should be called before the journal of the operation is committed.
+    pub fn index_account_operation(
+        &self,
+        operation_hash: &str,
+        addresses: &[String],
+    ) -> anyhow::Result<()> {
+        let mut accounts = addresses.to_vec();
+        accounts.extend(self.db.journal_accounts(self.journal)?);
+        self.db.index_account_operation(operation_hash, &accounts)
     }
 
     fn connection(
@@ -57,6 +174,17 @@ impl Host {
             .connection()
             .map_err(|e| log_error("db connection", e))
     }
+
+    /// Returns a connection for writing to the store
+    fn write_connection(
+        &self,
+        log_title: &str,
+    ) -> Result<PooledConnection<SqliteConnectionManager>, RuntimeError> {
+        if self.read_only {
+            return Err(log_error(log_title, "read-only host"));
+        }
+        self.connection()
+    }
 }
 
 fn log_error(name: &str, e: impl Debug) -> RuntimeError {
@@ -167,7 +295,7 @@ impl Runtime for Host {
         let log_title = format!("store_write({path})");
         trace!("{log_title}");
 
-        let mut client = self.connection()?;
+        let mut client = self.write_connection(&log_title)?;
         let tx = client.transaction().map_err(|e| log_error(&log_title, e))?;
         let read_output =
             exec_read(&tx, &path.to_string()).map_err(|e| log_error(&log_title, e))?;
@@ -187,7 +315,7 @@ impl Runtime for Host {
             value.extend_from_slice(src);
         };
 
-        exec_write(&tx, &path.to_string(), &hex::encode(value))
+        exec_record_write(&tx, self.journal, &path.to_string(), &hex::encode(value))
             .map_err(|e| log_error(&log_title, e))?;
         tx.commit().map_err(|e| log_error(&log_title, e))?;
         Ok(())
@@ -201,17 +329,23 @@ impl Runtime for Host {
         let log_title = format!("store_write_all({path})");
         trace!("{log_title}");
 
-        let client = self.connection()?;
-        exec_write(&client, &path.to_string(), &hex::encode(src))
-            .map_err(|e| log_error(&log_title, e))
+        let mut client = self.write_connection(&log_title)?;
+        let tx = client.transaction().map_err(|e| log_error(&log_title, e))?;
+        exec_record_write(&tx, self.journal, &path.to_string(), &hex::encode(src))
+            .map_err(|e| log_error(&log_title, e))?;
+        tx.commit().map_err(|e| log_error(&log_title, e))
     }
 
     fn store_delete<T: Path>(&mut self, path: &T) -> Result<(), RuntimeError> {
         let log_title = format!("store_delete({path})");
         trace!("{log_title}");
 
-        let mut client = self.connection()?;
+        let mut client = self.write_connection(&log_title)?;
         let tx = client.transaction().map_err(|e| log_error(&log_title, e))?;
+        exec_record_undo(&tx, self.journal, &path.to_string())
+            .map_err(|e| log_error(&log_title, e))?;
+        exec_record_undo_glob(&tx, self.journal, &path.to_string())
+            .map_err(|e| log_error(&log_title, e))?;
         match exec_delete(&tx, &path.to_string()).map_err(|e| log_error(&log_title, e))? {
             0 => return Err(RuntimeError::PathNotFound),
             1 => (),
@@ -226,8 +360,12 @@ impl Runtime for Host {
         let log_title = format!("store_delete_value({path})");
         trace!("{log_title}");
 
-        let client = self.connection()?;
-        exec_delete_glob(&client, &path.to_string()).map_err(|e| log_error(&log_title, e))
+        let mut client = self.write_connection(&log_title)?;
+        let tx = client.transaction().map_err(|e| log_error(&log_title, e))?;
+        exec_record_undo_glob(&tx, self.journal, &path.to_string())
+            .map_err(|e| log_error(&log_title, e))?;
+        exec_delete_glob(&tx, &path.to_string()).map_err(|e| log_error(&log_title, e))?;
+        tx.commit().map_err(|e| log_error(&log_title, e))
     }
 
     fn store_count_subkeys<T: Path>(&self, prefix: &T) -> Result<u64, RuntimeError> {
@@ -356,15 +494,21 @@ mod tests {
     use std::{
         cell::RefCell,
         io::{Read, Seek, Write},
+        sync::{atomic::Ordering, Arc},
     };
 
     use jstz_core::host::HostRuntime;
+    use jstz_mock::kt1_account1;
     use log::{Metadata, Record};
     use tempfile::{NamedTempFile, TempDir};
     use tezos_smart_rollup::host::ValueType;
     use tezos_smart_rollup::storage::path::RefPath;
 
-    use crate::sequencer::{db::Db, host::Host};
+    use crate::{
+        sequencer::{db::Db, host::Host, watchdog::WorkerStats},
+        services::utils::tests::dummy_receipt,
+        test::default_injector,
+    };
 
     thread_local! {
         static LOG_RECORDS: RefCell<Vec<String>> = const {RefCell::new(Vec::new())};
@@ -596,4 +740,82 @@ mod tests {
             "store_write takes too much time"
         );
     }
+
+    #[test]
+    fn journaled_writes() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db_path = db_file.path().to_str().unwrap();
+        let preimage_dir = TempDir::new().unwrap();
+        let mut host = Host::new(
+            Db::init(Some(db_path)).unwrap(),
+            preimage_dir.path().to_path_buf(),
+        );
+        let foo = RefPath::assert_from(b"/foo");
+        let bar = RefPath::assert_from(b"/foo/bar");
+
+        host.store_write_all(&foo, b"1").unwrap();
+        host.store_write_all(&bar, b"2").unwrap();
+        host.commit_journal(Some("op1"), None).unwrap();
+
+        host.store_write(&foo, b"3", 0).unwrap();
+        host.store_delete(&foo).unwrap();
+
+        // writes after the last journal entry are rolled back on recovery
+        let db = Db::init(Some(db_path)).unwrap();
+        assert_eq!(db.read_key("/foo").unwrap().unwrap(), hex::encode(b"1"));
+        assert_eq!(db.read_key("/foo/bar").unwrap().unwrap(), hex::encode(b"2"));
+        assert_eq!(db.journal_entries(0, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn concurrent_journals_are_committed_separately() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db_path = db_file.path().to_str().unwrap();
+        let preimage_dir = TempDir::new().unwrap();
+        let host = Host::new(
+            Db::init(Some(db_path)).unwrap(),
+            preimage_dir.path().to_path_buf(),
+        );
+        let mut op1 = host.with_new_journal();
+        let mut op2 = host.with_new_journal();
+        let foo = RefPath::assert_from(b"/foo");
+        let bar = RefPath::assert_from(b"/bar");
+
+        op1.store_write_all(&foo, b"1").unwrap();
+        op2.store_write_all(&bar, b"2").unwrap();
+        op1.commit_journal(Some("op1"), None).unwrap();
+
+        // the writes of the operation still executing are not committed
+        let db = Db::init(Some(db_path)).unwrap();
+        assert_eq!(db.read_key("/foo").unwrap().unwrap(), hex::encode(b"1"));
+        assert!(db.read_key("/bar").unwrap().is_none());
+        let entries = db.journal_entries(0, 10).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].diff,
+            vec![("/foo".to_string(), Some(hex::encode(b"1")))]
+        );
+    }
+
+    #[test]
+    fn counts_attestation_failures() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let preimage_dir = TempDir::new().unwrap();
+        let stats = Arc::new(WorkerStats::default());
+        let host = Host::new(db.clone(), preimage_dir.path().to_path_buf())
+            .with_ordering_signer(default_injector())
+            .with_worker_stats(stats.clone());
+        let receipt = dummy_receipt(kt1_account1());
+
+        host.attest_receipt(&receipt).unwrap();
+        assert_eq!(stats.attestation_failures.load(Ordering::Relaxed), 0);
+
+        db.connection()
+            .unwrap()
+            .execute("DROP TABLE jstz_receipt_attestation", [])
+            .unwrap();
+        assert!(host.attest_receipt(&receipt).is_err());
+        assert_eq!(stats.attestation_failures.load(Ordering::Relaxed), 1);
+    }
 }
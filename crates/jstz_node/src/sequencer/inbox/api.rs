@@ -9,8 +9,14 @@ use tokio_util::io::StreamReader;
 
 /// Response structure for block data containing inbox messages.
 /// Each message in the inbox is represented as a hex-encoded string.
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct BlockResponse {
+    /// Hash of the block, used to detect reorgs
+    #[serde(default)]
+    pub block_hash: Option<String>,
+    /// Hash of the previous block
+    #[serde(default)]
+    pub predecessor: Option<String>,
     pub messages: Vec<String>,
 }
 
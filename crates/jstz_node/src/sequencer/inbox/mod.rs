@@ -1,8 +1,10 @@
-use crate::sequencer::inbox::store::{CheckpointStore, FileCheckpointStore};
+use crate::sequencer::inbox::store::{
+    messages_digest, CheckpointStore, FileCheckpointStore,
+};
 use crate::sequencer::inbox::stream::{
     Error, PendingBlock, SequentialBlockStream, StreamFactory,
 };
-use crate::sequencer::queue::{OperationQueue, WrappedOperation};
+use crate::sequencer::queue::{self, OperationQueue, WrappedOperation};
 use anyhow::Result;
 use api::BlockResponse;
 use async_dropper_simple::AsyncDrop;
@@ -12,7 +14,8 @@ use jstz_core::host::WriteDebug;
 use jstz_kernel::inbox::parse_inbox_message_hex;
 use jstz_proto::operation::internal::InboxId;
 use jstz_proto::BlockLevel;
-use log::{debug, error};
+use log::{debug, error, warn};
+use octez::FailoverRollupClient;
 use std::collections::VecDeque;
 use std::future::Future;
 use std::path::PathBuf;
@@ -28,6 +31,10 @@ pub mod api;
 pub mod store;
 pub mod stream;
 
+/// Number of L1 levels a reorg is assumed to revert at most. Tenderbake finalizes
+/// blocks after two levels.
+pub const MAX_REORG_DEPTH: u32 = 16;
+
 #[derive(Default)]
 pub struct Monitor {
     inner: Option<JoinHandle<()>>,
@@ -50,6 +57,30 @@ impl AsyncDrop for Monitor {
     }
 }
 
+/// Endpoint of the rollup node blocks are read from. The endpoint of a failover
+/// client is resolved before each request, so that the monitor follows the active
+/// rollup node.
+#[derive(Clone)]
+pub enum RollupEndpoint {
+    Fixed(String),
+    Active(Arc<FailoverRollupClient>),
+}
+
+impl RollupEndpoint {
+    fn get(&self) -> String {
+        match self {
+            Self::Fixed(endpoint) => endpoint.clone(),
+            Self::Active(client) => client.active_endpoint(),
+        }
+    }
+}
+
+impl From<String> for RollupEndpoint {
+    fn from(endpoint: String) -> Self {
+        Self::Fixed(endpoint)
+    }
+}
+
 pub struct Logger;
 impl WriteDebug for Logger {
     fn write_debug(&self, msg: &str) {
@@ -58,9 +89,18 @@ impl WriteDebug for Logger {
 }
 
 /// Spawn a future that monitors the L1 blocks, parses inbox messages and pushes them into the queue.
+///
+/// Blocks that do not extend the last processed block, or live blocks that are not after it,
+/// are checked for reorgs against the hashes of the last processed blocks. On a reorg, the
+/// queue and the checkpoint are rolled back to the last block still in the chain, see
+/// [`OperationQueue::roll_back`], and the following blocks are processed again.
+///
+/// The checkpoint is verified against the rollup node and compacted before the
+/// monitor starts, see [`verify_checkpoint`].
+///
 /// precondition: the rollup node is healthy.
 pub async fn spawn_monitor(
-    rollup_endpoint: String,
+    rollup_endpoint: RollupEndpoint,
     rollup_address: SmartRollupHash,
     ticketer_address: ContractKt1Hash,
     queue: Arc<RwLock<OperationQueue>>,
@@ -70,12 +110,25 @@ pub async fn spawn_monitor(
 ) -> Result<Monitor> {
     let kill_sig = CancellationToken::new();
     let kill_sig_clone = kill_sig.clone();
-    let store = FileCheckpointStore::new(checkpoint_path);
-    let mut block_stream =
-        SequentialBlockStream::new(store, stream_factory(rollup_endpoint.clone()))
-            .boxed();
+    let mut store = FileCheckpointStore::new(checkpoint_path);
+    verify_checkpoint(&store, &rollup_endpoint).await?;
+    if store.compact().await? {
+        debug!("compacted inbox checkpoint");
+    }
+    let new_block_stream = {
+        let rollup_endpoint = rollup_endpoint.clone();
+        move |store: &FileCheckpointStore| {
+            SequentialBlockStream::new(
+                store.clone(),
+                stream_factory(rollup_endpoint.clone()),
+            )
+            .boxed()
+        }
+    };
+    let mut block_stream = new_block_stream(&store);
     let handle: JoinHandle<()> = tokio::spawn(async move {
         loop {
+            let mut reverted = None;
             select! {
                 _ = kill_sig_clone.cancelled() => {
                     break;
@@ -84,16 +137,29 @@ pub async fn spawn_monitor(
                     match result {
                         Some(Ok(mut block)) => {
                             let block_content = retry_fetch_block(&rollup_endpoint, block.level()).await;
-                            process_inbox_messages(&mut block, block_content, queue.clone(), &ticketer_address, &rollup_address).await;
+                            if !extends_checkpoint(&store, block.level(), &block_content).await {
+                                reverted = find_reorg(&store, &rollup_endpoint).await;
+                            }
+                            if reverted.is_none() {
+                                process_inbox_messages(&mut block, block_content, queue.clone(), &ticketer_address, &rollup_address).await;
+                            }
                         }
                         Some(Err(Error::CheckpointIo(e))) => {
                             error!("checkpoint io error: {e:?}");
                             tokio::time::sleep(Duration::from_millis(200)).await;
                         }
+                        Some(Err(Error::Rewound { .. })) => {
+                            reverted = find_reorg(&store, &rollup_endpoint).await;
+                        }
                         None => unreachable!("Should be unreachable as block stream is an infinite stream"),
                     }
                 }
             }
+            if let Some(level) = reverted {
+                roll_back(level, &mut store, &queue).await;
+                // Restart from the checkpoint
+                block_stream = new_block_stream(&store);
+            }
         }
     });
 
@@ -103,9 +169,9 @@ pub async fn spawn_monitor(
     })
 }
 
-fn stream_factory(endpoint: String) -> impl StreamFactory {
+fn stream_factory(endpoint: RollupEndpoint) -> impl StreamFactory {
     move || {
-        let endpoint = endpoint.clone();
+        let endpoint = endpoint.get();
         let stream = async move {
             api::monitor_blocks(&endpoint)
                 .await
@@ -115,10 +181,113 @@ fn stream_factory(endpoint: String) -> impl StreamFactory {
     }
 }
 
+/// Checks that the checkpoint block was processed from the chain of the rollup
+/// node, by comparing the digest of its messages. A checkpoint block reverted while
+/// the node was stopped is rolled back by the monitor, see [`find_reorg`].
+async fn verify_checkpoint<S: CheckpointStore>(
+    store: &S,
+    rollup_endpoint: &RollupEndpoint,
+) -> Result<()> {
+    let Some(level) = store.load().await? else {
+        return Ok(());
+    };
+    let Some(digest) = store.load_messages_digest().await? else {
+        return Ok(());
+    };
+    let block = retry_fetch_block(rollup_endpoint, level).await;
+    let hash = store.load_hashes().await?.remove(&level);
+    if let (Some(hash), Some(block_hash)) = (&hash, &block.block_hash) {
+        if hash != block_hash {
+            warn!("checkpoint block {level} was reverted while the node was stopped");
+            return Ok(());
+        }
+    }
+    if messages_digest(&block.messages) != digest {
+        anyhow::bail!(
+            "inbox checkpoint does not match the messages of block {level} of the rollup node"
+        );
+    }
+    Ok(())
+}
+
+/// Returns false if `block` does not extend the block saved with the checkpoint
+/// preceding it, when both hashes are known
+async fn extends_checkpoint<S: CheckpointStore>(
+    store: &S,
+    level: BlockLevel,
+    block: &BlockResponse,
+) -> bool {
+    let Some(predecessor) = &block.predecessor else {
+        return true;
+    };
+    match store.load_hashes().await {
+        Ok(hashes) => level
+            .checked_sub(1)
+            .and_then(|previous| hashes.get(&previous))
+            .is_none_or(|hash| hash == predecessor),
+        Err(e) => {
+            error!("failed to load block hashes: {e:?}");
+            true
+        }
+    }
+}
+
+/// Compares the hashes of the last processed blocks with the blocks of the rollup
+/// node. Returns the level of the last block still in the chain if a later one was
+/// reverted by a reorg, or `None` if no block was reverted.
+async fn find_reorg<S: CheckpointStore>(
+    store: &S,
+    rollup_endpoint: &RollupEndpoint,
+) -> Option<BlockLevel> {
+    let hashes = match store.load_hashes().await {
+        Ok(hashes) => hashes,
+        Err(e) => {
+            error!("failed to load block hashes: {e:?}");
+            return None;
+        }
+    };
+    let last = *hashes.keys().next_back()?;
+    for (&level, hash) in hashes.iter().rev() {
+        let block = retry_fetch_block(rollup_endpoint, level).await;
+        if block
+            .block_hash
+            .is_none_or(|block_hash| &block_hash == hash)
+        {
+            return (level < last).then_some(level);
+        }
+    }
+    // The reorg is deeper than the known blocks
+    let first = *hashes.keys().next()?;
+    Some(first.saturating_sub(1))
+}
+
+/// Rolls the queue and the checkpoint back to `level` after a reorg, so that the
+/// following blocks are processed again. Waits for the queue to roll back the
+/// executed operations before saving the checkpoint.
+async fn roll_back<S: CheckpointStore>(
+    level: BlockLevel,
+    store: &mut S,
+    queue: &RwLock<OperationQueue>,
+) {
+    warn!("L1 reorg detected, rolling back to level {level}");
+    while queue::roll_back(queue, level as u32).is_err() {
+        error!("Failed to roll back the queue, retrying...");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    while queue.read().is_ok_and(|q| q.is_rolling_back()) {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    while store.save(level).await.is_err() {
+        error!("Failed to save checkpoint, retrying...");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
 /// Process inbox msgs for the given block:
 /// 1. Filter out irrelevant msgs and parse valid ones into operations.
 /// 2. Push each operation into the shared queue, retrying on failure.
-/// 3. Commit the block as a checkpoint after all operations are queued.
+/// 3. Commit the block as a checkpoint after all operations are queued, along with
+///    its hash if known and the digest of its messages.
 async fn process_inbox_messages<S: CheckpointStore>(
     block: &mut PendingBlock<S>,
     block_content: BlockResponse,
@@ -126,9 +295,12 @@ async fn process_inbox_messages<S: CheckpointStore>(
     ticketer: &ContractKt1Hash,
     jstz: &SmartRollupHash,
 ) {
+    if let Some(hash) = block_content.block_hash.clone() {
+        block.set_hash(hash);
+    }
+    block.set_messages_digest(messages_digest(&block_content.messages));
     let mut ops = parse_inbox_messages(block.level(), block_content, ticketer, jstz);
-    let push =
-        |op: &WrappedOperation| queue.write().is_ok_and(|mut q| q.insert_ref(op).is_ok());
+    let push = |op: &WrappedOperation| queue::insert(&queue, op.clone()).is_ok();
 
     while let Some(op) = ops.pop_front() {
         while !push(&op) {
@@ -197,12 +369,13 @@ where
 // 2. The block data must eventually become available (it's part of the chain)
 // 3. Temporary network issues or API unavailability should not stop the sequencer
 // 4. The exponential backoff ensures we don't overwhelm the API
+// The endpoint is resolved on each attempt, so that a failed rollup node is replaced.
 async fn retry_fetch_block(
-    rollup_endpoint: &str,
+    rollup_endpoint: &RollupEndpoint,
     block_level: BlockLevel,
 ) -> BlockResponse {
     retry_expo(200, || async {
-        match api::fetch_block(rollup_endpoint, block_level).await {
+        match api::fetch_block(&rollup_endpoint.get(), block_level).await {
             Ok(block) => Ok(block),
             Err(e) => {
                 error!("Failed to fetch block {}: {:?}", block_level, e);
@@ -254,11 +427,14 @@ mod tests {
         let deploy_fn = DeployFunction {
             function_code: ParsedCode::try_from(code.to_string()).unwrap().into(),
             account_credit: 0,
+            interface: None,
         };
         let op = Operation {
             public_key: alice_pk.clone(),
             nonce: nonce.into(),
             content: deploy_fn.into(),
+            max_fee: None,
+            priority_fee: None,
         };
         SignedOperation::new(alice_sk.sign(op.hash()).unwrap(), op.clone())
     }
@@ -290,7 +466,7 @@ mod tests {
         let file = NamedTempFile::new().unwrap();
         let store = FileCheckpointStore::new(file.path().to_path_buf());
         let _monitor = spawn_monitor(
-            endpoint,
+            endpoint.into(),
             rollup_address,
             ticketer_address,
             q,
@@ -338,7 +514,7 @@ mod tests {
         let store: FileCheckpointStore =
             FileCheckpointStore::new(file.path().to_path_buf());
         let mut monitor = spawn_monitor(
-            endpoint.clone(),
+            endpoint.clone().into(),
             rollup_address.clone(),
             ticketer_address.clone(),
             q.clone(),
@@ -362,7 +538,7 @@ mod tests {
         // but the monitor should handle the missing blocks in between.
         let (endpoint, _server) = spawn_mock_server2();
         let _monitor = spawn_monitor(
-            endpoint.clone(),
+            endpoint.clone().into(),
             rollup_address,
             ticketer_address,
             q.clone(),
@@ -382,7 +558,7 @@ mod tests {
 
         for i in 123..=126 {
             assert!(set.contains(&i));
-            let op = q.write().unwrap().pop().unwrap();
+            let op = q.write().unwrap().pop().unwrap().operation;
             match op {
                 WrappedOperation::FromInbox {
                     original_inbox_message,
@@ -398,6 +574,112 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn find_reorg_returns_last_block_in_chain() {
+        // blocks after level 3 were reverted
+        let filter = warp::path!("global" / "block" / u32).map(|level: u32| {
+            let prefix = if level <= 3 { "B" } else { "C" };
+            warp::reply::json(&BlockResponse {
+                block_hash: Some(format!("{prefix}{level}")),
+                ..Default::default()
+            })
+        });
+        let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        let _server = task::spawn(server);
+        let endpoint = RollupEndpoint::from(format!("http://{addr}"));
+        let file = NamedTempFile::new().unwrap();
+        let mut store = FileCheckpointStore::new(file.path().to_path_buf());
+
+        for level in 1..=3 {
+            store
+                .save_with_hash(level, format!("B{level}"))
+                .await
+                .unwrap();
+        }
+        assert_eq!(find_reorg(&store, &endpoint).await, None);
+        let next = BlockResponse {
+            predecessor: Some("B3".to_string()),
+            ..Default::default()
+        };
+        assert!(extends_checkpoint(&store, 4, &next).await);
+
+        for level in 4..=5 {
+            store
+                .save_with_hash(level, format!("B{level}"))
+                .await
+                .unwrap();
+        }
+        let next = BlockResponse {
+            predecessor: Some("C5".to_string()),
+            ..Default::default()
+        };
+        assert!(!extends_checkpoint(&store, 6, &next).await);
+        assert_eq!(find_reorg(&store, &endpoint).await, Some(3));
+    }
+
+    #[tokio::test]
+    async fn verify_checkpoint_checks_messages_digest() {
+        let filter = warp::path!("global" / "block" / u32).map(|level: u32| {
+            warp::reply::json(&BlockResponse {
+                block_hash: Some(format!("B{level}")),
+                messages: vec!["0001".to_string()],
+                ..Default::default()
+            })
+        });
+        let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        let _server = task::spawn(server);
+        let endpoint = RollupEndpoint::from(format!("http://{addr}"));
+        let file = NamedTempFile::new().unwrap();
+        let mut store = FileCheckpointStore::new(file.path().to_path_buf());
+        assert!(verify_checkpoint(&store, &endpoint).await.is_ok());
+
+        let digest = messages_digest(&["0001".to_string()]);
+        let other_digest = messages_digest(&["0002".to_string()]);
+        store
+            .save_block(2, Some("B2".to_string()), Some(digest))
+            .await
+            .unwrap();
+        assert!(verify_checkpoint(&store, &endpoint).await.is_ok());
+
+        store
+            .save_block(2, Some("B2".to_string()), Some(other_digest.clone()))
+            .await
+            .unwrap();
+        assert_eq!(
+            verify_checkpoint(&store, &endpoint)
+                .await
+                .unwrap_err()
+                .to_string(),
+            "inbox checkpoint does not match the messages of block 2 of the rollup node"
+        );
+
+        // a reverted checkpoint block is left to the monitor
+        store
+            .save_block(2, Some("C2".to_string()), Some(other_digest))
+            .await
+            .unwrap();
+        assert!(verify_checkpoint(&store, &endpoint).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn rollup_endpoint_follows_active_endpoint() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/global/block/head/level")
+            .with_body("1")
+            .create();
+        // Nothing listens on the discard port
+        let client = Arc::new(
+            FailoverRollupClient::new(&["http://127.0.0.1:9".to_string(), server.url()])
+                .unwrap(),
+        );
+        let endpoint = RollupEndpoint::Active(client.clone());
+        assert_eq!(endpoint.get(), "http://127.0.0.1:9");
+
+        client.check_health().await;
+        assert_eq!(endpoint.get(), server.url());
+    }
+
     #[tokio::test]
     async fn test_parse_inbox_messages() {
         let op = mock_deploy_op(0);
@@ -406,6 +688,7 @@ mod tests {
         let raw_messages = vec![String::from("0001"), hex_external_message(op.clone())];
         let block_content = BlockResponse {
             messages: raw_messages.clone(),
+            ..Default::default()
         };
         let msgs = parse_inbox_messages(1, block_content, &ticketer, &jstz);
         assert_eq!(msgs.len(), 2);
@@ -467,6 +750,7 @@ mod tests {
                 hex_external_message(op2.clone()),
                 String::from("FOO"), // Noise to be ignored
             ],
+            ..Default::default()
         };
         let store = stream::tests::MockStore::new();
         let mut block = PendingBlock::new(store.clone(), 1);
@@ -484,8 +768,8 @@ mod tests {
         tokio::time::sleep(Duration::from_millis(10)).await;
         // only two messages should be in the queue to respect the queue limit
         assert_eq!(q.read().unwrap().len(), 2);
-        let sol = q.write().unwrap().pop().unwrap();
-        let op = q.write().unwrap().pop().unwrap();
+        let sol = q.write().unwrap().pop().unwrap().operation;
+        let op = q.write().unwrap().pop().unwrap().operation;
         match sol {
             WrappedOperation::FromInbox {
                 message,
@@ -506,7 +790,7 @@ mod tests {
         // the waiting operation should be added to the queue now that the previous one is processed
         tokio::time::sleep(Duration::from_millis(100)).await;
         assert_eq!(q.read().unwrap().len(), 1);
-        let op = q.write().unwrap().pop().unwrap();
+        let op = q.write().unwrap().pop().unwrap().operation;
         assert_eq!(hash_of(&op), op2.hash().to_string());
         // Checkpoint stores the block level 1
         assert_eq!(store.load().await.unwrap().unwrap(), 1);
@@ -523,7 +807,10 @@ mod tests {
         let q = Arc::new(RwLock::new(OperationQueue::new(3)));
         let ticketer = ContractKt1Hash::from_base58_check(TICKETER_ADDRESS).unwrap();
         let jstz = SmartRollupHash::from_base58_check(ROLLUP_ADDRESS).unwrap();
-        let block_content = BlockResponse { messages };
+        let block_content = BlockResponse {
+            messages,
+            ..Default::default()
+        };
         let mut store = stream::tests::MockStore::new();
         for i in 1..block_level {
             store.save(i).await.unwrap();
@@ -637,6 +924,7 @@ pub(crate) mod test_utils {
                     .into_iter()
                     .map(String::from)
                     .collect(),
+                ..Default::default()
             };
             warp::reply::json(&response)
         })
@@ -1,6 +1,9 @@
+use super::MAX_REORG_DEPTH;
 use futures_util::future::BoxFuture;
+use jstz_crypto::hash::Blake2b;
 use jstz_proto::BlockLevel;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::io::{self, Result};
 use std::{path::PathBuf, sync::Arc};
 use tokio::{fs, io::AsyncWriteExt};
@@ -15,6 +18,32 @@ pub trait CheckpointStore: Clone + Send + 'static {
     async fn load(&self) -> Result<Option<BlockLevel>>;
     /// Save the checkpoint block level.
     async fn save(&mut self, level: BlockLevel) -> Result<()>;
+    /// Save the checkpoint block level along with the hash of the block, so that
+    /// reorgs can be detected, and the digest of its inbox messages, see
+    /// [`messages_digest`], so that the checkpoint can be verified on restart.
+    /// Defaults to ignoring both.
+    async fn save_block(
+        &mut self,
+        level: BlockLevel,
+        _hash: Option<String>,
+        _messages_digest: Option<String>,
+    ) -> Result<()> {
+        self.save(level).await
+    }
+    /// Save the checkpoint block level along with the hash of the block, see
+    /// [`CheckpointStore::save_block`].
+    async fn save_with_hash(&mut self, level: BlockLevel, hash: String) -> Result<()> {
+        self.save_block(level, Some(hash), None).await
+    }
+    /// Load the hashes of the last saved blocks, by level. Defaults to no hashes.
+    async fn load_hashes(&self) -> Result<BTreeMap<BlockLevel, String>> {
+        Ok(BTreeMap::new())
+    }
+    /// Load the digest of the inbox messages of the checkpoint block, if saved.
+    /// Defaults to no digest.
+    async fn load_messages_digest(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
     /// Returns a boxed future for loading the checkpoint.
     fn load_fut(&self) -> CheckpointLoadFuture {
         let s = self.clone();
@@ -22,10 +51,40 @@ pub trait CheckpointStore: Clone + Send + 'static {
     }
 }
 
+/// Returns the hex-encoded digest of the inbox messages of a block, in order.
+pub fn messages_digest(messages: &[String]) -> String {
+    let mut bytes = Vec::new();
+    for message in messages {
+        bytes.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(message.as_bytes());
+    }
+    Blake2b::from(&bytes).to_string()
+}
+
 /// JSON structure written to disk
 #[derive(Serialize, Deserialize)]
 pub(super) struct CheckpointFile {
     pub(super) block_level: BlockLevel,
+    /// Hashes of the blocks of the last [`MAX_REORG_DEPTH`] levels up to the
+    /// checkpoint, when known
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(super) block_hashes: BTreeMap<BlockLevel, String>,
+    /// Digest of the inbox messages of the checkpoint block, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) messages_digest: Option<String>,
+}
+
+impl CheckpointFile {
+    /// Forgets the hashes of the blocks after the checkpoint, e.g. after it was
+    /// rewound, and of the blocks that are too old to be reverted. Returns true if
+    /// any hash was forgotten.
+    fn compact(&mut self) -> bool {
+        let level = self.block_level;
+        let len = self.block_hashes.len();
+        self.block_hashes
+            .retain(|l, _| *l <= level && l + MAX_REORG_DEPTH as u64 > level);
+        self.block_hashes.len() < len
+    }
 }
 
 /// Persists the last processed block level to a JSON file.
@@ -40,14 +99,8 @@ impl FileCheckpointStore {
             path: Arc::new(path.into()),
         }
     }
-}
 
-#[async_trait::async_trait]
-impl CheckpointStore for FileCheckpointStore {
-    /// Load the checkpoint from disk.
-    ///
-    /// If the file does not exist or is empty, returns `None`.
-    async fn load(&self) -> Result<Option<BlockLevel>> {
+    async fn read(&self) -> Result<Option<CheckpointFile>> {
         match fs::read(&*self.path).await {
             Ok(bytes) => {
                 if bytes.is_empty() {
@@ -56,18 +109,54 @@ impl CheckpointStore for FileCheckpointStore {
 
                 let chk: CheckpointFile = serde_json::from_slice(&bytes)
                     .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-                Ok(Some(chk.block_level))
+                Ok(Some(chk))
             }
             Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
             Err(e) => Err(e),
         }
     }
 
-    /// Save the checkpoint to disk safely.
-    async fn save(&mut self, level: BlockLevel) -> Result<()> {
+    /// Save the checkpoint to disk, keeping the hashes of the last blocks up to
+    /// `level`.
+    async fn write(
+        &mut self,
+        level: BlockLevel,
+        hash: Option<String>,
+        messages_digest: Option<String>,
+    ) -> Result<()> {
+        let block_hashes = match self.read().await {
+            Ok(Some(chk)) => chk.block_hashes,
+            _ => BTreeMap::new(),
+        };
+        let mut chk = CheckpointFile {
+            block_level: level,
+            block_hashes,
+            messages_digest,
+        };
+        chk.compact();
+        if let Some(hash) = hash {
+            chk.block_hashes.insert(level, hash);
+        }
+        self.write_file(&chk).await
+    }
+
+    /// Rewrites the checkpoint without the block hashes it no longer needs, e.g.
+    /// because it was written with a deeper reorg window. Returns true if the
+    /// checkpoint was rewritten.
+    pub async fn compact(&mut self) -> Result<bool> {
+        match self.read().await? {
+            Some(mut chk) if chk.compact() => {
+                self.write_file(&chk).await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Save the checkpoint file to disk safely.
+    async fn write_file(&mut self, chk: &CheckpointFile) -> Result<()> {
         let tmp = self.path.with_extension("tmp");
-        let json = serde_json::to_vec(&CheckpointFile { block_level: level })
-            .map_err(io::Error::other)?;
+        let json = serde_json::to_vec(chk).map_err(io::Error::other)?;
         // Write to temp file first and then atomically rename it over the old file.
         // This ensures that file is never left half-written
         {
@@ -83,6 +172,42 @@ impl CheckpointStore for FileCheckpointStore {
     }
 }
 
+#[async_trait::async_trait]
+impl CheckpointStore for FileCheckpointStore {
+    /// Load the checkpoint from disk.
+    ///
+    /// If the file does not exist or is empty, returns `None`.
+    async fn load(&self) -> Result<Option<BlockLevel>> {
+        Ok(self.read().await?.map(|chk| chk.block_level))
+    }
+
+    /// Save the checkpoint to disk, keeping the hashes of the blocks up to `level`.
+    async fn save(&mut self, level: BlockLevel) -> Result<()> {
+        self.write(level, None, None).await
+    }
+
+    async fn save_block(
+        &mut self,
+        level: BlockLevel,
+        hash: Option<String>,
+        messages_digest: Option<String>,
+    ) -> Result<()> {
+        self.write(level, hash, messages_digest).await
+    }
+
+    async fn load_hashes(&self) -> Result<BTreeMap<BlockLevel, String>> {
+        Ok(self
+            .read()
+            .await?
+            .map(|chk| chk.block_hashes)
+            .unwrap_or_default())
+    }
+
+    async fn load_messages_digest(&self) -> Result<Option<String>> {
+        Ok(self.read().await?.and_then(|chk| chk.messages_digest))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,6 +240,77 @@ mod tests {
         assert_eq!(got, Some(100));
     }
 
+    #[tokio::test]
+    async fn save_keeps_recent_block_hashes() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.json");
+        let mut store = FileCheckpointStore::new(&path);
+
+        for level in 1..=20 {
+            store
+                .save_with_hash(level, format!("B{level}"))
+                .await
+                .unwrap();
+        }
+        let hashes = store.load_hashes().await.unwrap();
+        assert_eq!(
+            hashes.keys().copied().collect::<Vec<_>>(),
+            (21 - MAX_REORG_DEPTH as u64..=20).collect::<Vec<_>>()
+        );
+        assert_eq!(hashes[&20], "B20");
+
+        // rewinding the checkpoint forgets the later blocks
+        store.save(18).await.unwrap();
+        assert_eq!(store.load().await.unwrap(), Some(18));
+        let hashes = store.load_hashes().await.unwrap();
+        assert_eq!(hashes.keys().next_back(), Some(&18));
+        assert_eq!(hashes[&18], "B18");
+    }
+
+    #[tokio::test]
+    async fn save_block_keeps_messages_digest_of_checkpoint() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.json");
+        let mut store = FileCheckpointStore::new(&path);
+
+        let digest = messages_digest(&["0001".to_string(), "0002".to_string()]);
+        assert_ne!(digest, messages_digest(&["00010002".to_string()]));
+        store
+            .save_block(3, Some("B3".to_string()), Some(digest.clone()))
+            .await
+            .unwrap();
+        assert_eq!(store.load_messages_digest().await.unwrap(), Some(digest));
+        assert_eq!(store.load_hashes().await.unwrap()[&3], "B3");
+
+        // the digest belongs to the checkpoint block only
+        store.save(4).await.unwrap();
+        assert_eq!(store.load_messages_digest().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn compact_forgets_stale_block_hashes() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.json");
+        let mut store = FileCheckpointStore::new(&path);
+
+        let chk = CheckpointFile {
+            block_level: 40,
+            block_hashes: (1..=41).map(|l| (l, format!("B{l}"))).collect(),
+            messages_digest: None,
+        };
+        fs::write(&path, serde_json::to_vec(&chk).unwrap())
+            .await
+            .unwrap();
+        assert!(store.compact().await.unwrap());
+        let hashes = store.load_hashes().await.unwrap();
+        assert_eq!(
+            hashes.keys().copied().collect::<Vec<_>>(),
+            (41 - MAX_REORG_DEPTH as u64..=40).collect::<Vec<_>>()
+        );
+        assert_eq!(store.load().await.unwrap(), Some(40));
+        assert!(!store.compact().await.unwrap());
+    }
+
     #[tokio::test]
     async fn corrupted_json_yields_error() {
         let dir = tempdir().unwrap();
@@ -24,22 +24,49 @@ pub enum Error {
     /// Error occurred during checkpoint I/O.
     #[error("Checkpoint I/O error: {0}")]
     CheckpointIo(#[from] io::Error),
+    /// The live block level is not after the checkpoint, either because the source
+    /// stream was reconnected or because of a reorg.
+    #[error("Live block {live} is not after checkpoint {checkpoint}")]
+    Rewound {
+        live: BlockLevel,
+        checkpoint: BlockLevel,
+    },
 }
 
 /// Represents a block level that must be committed once processed.
 pub struct PendingBlock<S> {
     level: BlockLevel,
+    hash: Option<String>,
+    messages_digest: Option<String>,
     store: S,
 }
 
 impl<S: CheckpointStore> PendingBlock<S> {
     pub fn new(store: S, level: BlockLevel) -> Self {
-        Self { level, store }
+        Self {
+            level,
+            hash: None,
+            messages_digest: None,
+            store,
+        }
+    }
+
+    /// Set the hash of the block, saved along with its checkpoint.
+    pub fn set_hash(&mut self, hash: String) {
+        self.hash = Some(hash);
+    }
+
+    /// Set the digest of the inbox messages of the block, saved along with its
+    /// checkpoint, see [`super::store::messages_digest`].
+    pub fn set_messages_digest(&mut self, messages_digest: String) {
+        self.messages_digest = Some(messages_digest);
     }
 
     /// Mark this block as processed by saving its checkpoint.
     pub async fn commit(&mut self) -> Result<()> {
-        self.store.save(self.level).await?;
+        self.store
+            .save_block(self.level, self.hash.clone(), self.messages_digest.clone())
+            .await?;
         Ok(())
     }
 
@@ -105,7 +132,7 @@ enum State {
 ///         |--> Backlog(chk+1 .. live+1)
 ///         |
 ///         | Ok(chk >= live)
-///         |--> Backoff
+///         |--> return Rewound error, go to Backoff
 ///         |
 ///         |
 ///         v
@@ -121,6 +148,8 @@ enum State {
 /// ## Error Handling
 /// - If the checkpoint store returns an error, the stream will retry indefinitely in LoadingCheckpoint.
 /// - The client is responsible for handling checkpoint store errors.
+/// - If the live block is not after the checkpoint, the stream returns a `Rewound` error, so
+///   that the client can check for reorgs, and waits before reconnecting.
 /// - If the source stream ends or errors, the stream will automatically reconnect with backoff.
 #[pin_project(project = SequentialBlockStreamProj)]
 pub struct SequentialBlockStream<S, F> {
@@ -280,12 +309,14 @@ where
                         // Behind: queue the remaining gap into the backlog
                         (State::Backlog((chk + 1, live + 1)), None)
                     }
-                    _ /* checkpoint >= live */ => {
-                        // Duplicate/rewind: simplest policy—wait and reconnect.
-                        // TODO: handle block reorg
-                        // lhttps://linear.app/tezos/issue/JSTZ-902/handle-inbox-monitors-block-reorgs
+                    Some(checkpoint) /* checkpoint >= live */ => {
+                        // Duplicate/rewind: let the client check for a reorg, then wait and reconnect.
                         error!("[handle_loading_checkpoint] Checkpoint duplicate/rewind, entering wait");
-                        (State::Backoff, None)
+                        self.fut.take();
+                        return (
+                            State::Backoff,
+                            Some(Poll::Ready(Some(Err(Error::Rewound { live, checkpoint })))),
+                        );
                     }
                 };
                 self.fut.take();
@@ -475,6 +506,24 @@ pub(crate) mod tests {
         assert_eq!(store.buffer(), (1..=7).collect::<Vec<_>>());
     }
 
+    #[tokio::test]
+    async fn stream_returns_rewound_error() {
+        let mut store = mock_store();
+        store.save(5).await.unwrap();
+        let factory = move || mock_stream(vec![Ok(4), Ok(6)]);
+        let stream = SequentialBlockStream::new(store.clone(), factory);
+        pin_mut!(stream);
+        match stream.next().await {
+            Some(Err(Error::Rewound { live, checkpoint })) => {
+                assert_eq!((live, checkpoint), (4, 5));
+            }
+            _ => panic!("should return a rewound error"),
+        }
+        // the stream goes on with the next live block
+        let block = stream.next().await.unwrap().unwrap();
+        assert_eq!(block.level(), 6);
+    }
+
     #[tokio::test]
     async fn stream_respects_backoff_delay() {
         let mut store = mock_store();
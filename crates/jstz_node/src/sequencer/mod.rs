@@ -1,22 +1,42 @@
+pub mod blueprint;
 pub mod db;
 mod host;
 pub mod inbox;
 pub mod queue;
+pub mod retention;
 mod riscv_pvm;
 pub mod runtime;
+pub mod snapshot;
+pub mod watchdog;
 pub mod worker;
 
 #[cfg(test)]
 pub mod tests {
+    use http::{HeaderMap, Method, Uri};
     use jstz_crypto::{public_key::PublicKey, secret_key::SecretKey};
     use jstz_proto::{
         context::account::Nonce,
-        operation::{Content, DeployFunction, Operation, SignedOperation},
+        operation::{Content, DeployFunction, Operation, RunFunction, SignedOperation},
+        HttpBody,
     };
-    use tezos_crypto_rs::hash::PublicKeyEd25519;
+    use jstz_utils::KeyPair;
+    use tezos_crypto_rs::hash::{PublicKeyEd25519, SeedEd25519};
 
     use crate::sequencer::queue::WrappedOperation;
 
+    fn sign(KeyPair(pk, sk): &KeyPair, nonce: u64, content: Content) -> SignedOperation {
+        let op = Operation {
+            public_key: pk.clone(),
+            nonce: Nonce(nonce),
+            content,
+            max_fee: None,
+            priority_fee: None,
+        };
+
+        let signature = sk.sign(op.hash()).unwrap();
+        SignedOperation::new(signature, op)
+    }
+
     pub fn dummy_signed_op() -> SignedOperation {
         let sk = SecretKey::from_base58(
             "edsk38mmuJeEfSYGiwLE1qHr16BPYKMT5Gg1mULT7dNUtg3ti4De3a",
@@ -29,17 +49,68 @@ pub mod tests {
             .unwrap()
             .into(),
         );
+        sign(
+            &KeyPair(pk, sk),
+            0,
+            Content::DeployFunction(DeployFunction {
+                account_credit: 0,
+                function_code: "export default async () => {}".to_string(),
+                interface: None,
+            }),
+        )
+    }
+
+    /// Key pair derived from `seed`, to sign operations of distinct accounts
+    pub fn signer(seed: u8) -> KeyPair {
+        let (pk, sk) = SeedEd25519::try_from(vec![seed; 32])
+            .unwrap()
+            .keypair()
+            .unwrap();
+        KeyPair(PublicKey::Ed25519(pk.into()), SecretKey::Ed25519(sk))
+    }
+
+    /// A `RunFunction` operation signed by `keys` paying `tip` as priority fee
+    pub fn tipped_op(
+        KeyPair(pk, sk): &KeyPair,
+        nonce: u64,
+        tip: u64,
+    ) -> WrappedOperation {
+        let op = Operation {
+            public_key: pk.clone(),
+            nonce: Nonce(nonce),
+            content: Content::RunFunction(RunFunction {
+                uri: Uri::from_static("jstz://KT1WjrJgoaEDHF2RmhhnpjjiwBkt4nA2MiMo/"),
+                method: Method::GET,
+                headers: HeaderMap::new(),
+                body: HttpBody::empty(),
+                gas_limit: 550000,
+            }),
+            max_fee: None,
+            priority_fee: Some(tip),
+        };
+        let signature = sk.sign(op.hash()).unwrap();
+        WrappedOperation::FromNode(SignedOperation::new(signature, op))
+    }
+
+    /// A `DeployFunction` operation signed by `keys` declaring fees
+    pub fn fee_op(
+        KeyPair(pk, sk): &KeyPair,
+        max_fee: Option<u64>,
+        priority_fee: Option<u64>,
+    ) -> WrappedOperation {
         let op = Operation {
-            public_key: pk,
+            public_key: pk.clone(),
             nonce: Nonce(0),
             content: Content::DeployFunction(DeployFunction {
                 account_credit: 0,
                 function_code: "export default async () => {}".to_string(),
+                interface: None,
             }),
+            max_fee,
+            priority_fee,
         };
-
         let signature = sk.sign(op.hash()).unwrap();
-        SignedOperation::new(signature, op)
+        WrappedOperation::FromNode(SignedOperation::new(signature, op))
     }
 
     pub fn dummy_op() -> WrappedOperation {
@@ -1,7 +1,28 @@
-use std::collections::VecDeque;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet, VecDeque},
+    sync::RwLock,
+};
 
-use jstz_kernel::inbox::{ParsedInboxMessage, ParsedInboxMessageWrapper};
-use jstz_proto::operation::SignedOperation;
+use anyhow::Context;
+use jstz_core::BinEncodable;
+use jstz_crypto::{hash::Blake2b, public_key_hash::PublicKeyHash};
+use jstz_kernel::inbox::{
+    parse_inbox_message_hex, ParsedInboxMessage, ParsedInboxMessageWrapper,
+};
+use jstz_proto::operation::{Blueprint, Content, SignedOperation};
+use log::warn;
+use tezos_crypto_rs::hash::{ContractKt1Hash, SmartRollupHash};
+
+use super::{
+    db::{Db, OperationState, PersistedOperation},
+    inbox::{Logger, MAX_REORG_DEPTH},
+};
+use crate::config::{AccountQuota, QueueOrdering};
+
+/// Number of times a queued operation can be overtaken by operations with higher
+/// tips before it is executed regardless of tips
+const MAX_OVERTAKES: u32 = 16;
 
 /// A wrapper for the actual parsed operations. The original inbox message is attached for
 /// operations coming from the rollup inbox.
@@ -23,11 +44,176 @@ impl WrappedOperation {
             }
         }
     }
+
+    /// Returns the L1 level of the inbox an inbox message was read from, `None` for
+    /// operations injected in the node
+    pub fn l1_level(&self) -> Option<u32> {
+        match self {
+            WrappedOperation::FromInbox { message, .. } => {
+                Some(message.inbox_id.l1_level)
+            }
+            WrappedOperation::FromNode(_) => None,
+        }
+    }
+
+    /// Returns the hash of the signed operation, or `None` for the other inbox
+    /// messages
+    pub fn operation_hash(&self) -> Option<String> {
+        match self {
+            WrappedOperation::FromInbox { message, .. } => match &message.content {
+                ParsedInboxMessage::JstzMessage(
+                    jstz_kernel::inbox::Message::External(op),
+                ) => Some(op.hash().to_string()),
+                _ => None,
+            },
+            WrappedOperation::FromNode(op) => Some(op.hash().to_string()),
+        }
+    }
+
+    /// Returns the hashes of the operations executed with this one: the ones of its
+    /// operations for a blueprint published by the sequencer, see
+    /// [`publish_blueprint`](super::blueprint::publish_blueprint)
+    fn executed_hashes(&self) -> Vec<String> {
+        let op = match self {
+            WrappedOperation::FromInbox { message, .. } => match &message.content {
+                ParsedInboxMessage::JstzMessage(
+                    jstz_kernel::inbox::Message::External(op),
+                ) => op,
+                _ => return vec![],
+            },
+            WrappedOperation::FromNode(op) => op,
+        };
+        match op.content() {
+            Content::Blueprint(Blueprint { operations }) => {
+                operations.iter().map(|op| op.hash().to_string()).collect()
+            }
+            _ => vec![op.hash().to_string()],
+        }
+    }
+
+    /// Returns true for a signed operation read from the inbox, i.e. submitted
+    /// directly to the L1 rather than injected in the node
+    pub fn is_direct(&self) -> bool {
+        matches!(
+            self,
+            WrappedOperation::FromInbox {
+                message: ParsedInboxMessageWrapper {
+                    content: ParsedInboxMessage::JstzMessage(
+                        jstz_kernel::inbox::Message::External(_)
+                    ),
+                    ..
+                },
+                ..
+            }
+        )
+    }
+
+    /// Returns the effective priority of an operation injected in the node, which is
+    /// the fee charged for executing it, see
+    /// [`Operation::fee`](jstz_proto::operation::Operation::fee). `None` for inbox
+    /// messages, which are never reordered
+    pub fn tip(&self) -> Option<u64> {
+        match self {
+            WrappedOperation::FromInbox { .. } => None,
+            WrappedOperation::FromNode(op) => Some(op.fee()),
+        }
+    }
+}
+
+/// An operation taken from the queue
+pub struct QueuedOperation {
+    /// The sequence number of the persisted operation, which should be passed
+    /// to [`Db::commit_journal`] once the operation is executed
+    pub id: Option<u64>,
+    pub operation: WrappedOperation,
+}
+
+struct Entry {
+    operation: QueuedOperation,
+    tip: Option<u64>,
+    /// Signer of an operation injected in the node
+    source: Option<PublicKeyHash>,
+    /// Hash of an operation injected in the node
+    hash: Option<Blake2b>,
+    /// Number of times operations queued after this one were taken before it
+    overtaken: u32,
+}
+
+impl Entry {
+    /// Returns the L1 level and message id of an inbox message
+    fn inbox_position(&self) -> Option<(u32, u32)> {
+        match &self.operation.operation {
+            WrappedOperation::FromInbox { message, .. } => {
+                Some((message.inbox_id.l1_level, message.inbox_id.l1_message_id))
+            }
+            WrappedOperation::FromNode(_) => None,
+        }
+    }
+}
+
+impl From<QueuedOperation> for Entry {
+    fn from(operation: QueuedOperation) -> Self {
+        let (source, hash) = match &operation.operation {
+            WrappedOperation::FromNode(op) => (Some(op.source()), Some(op.hash())),
+            WrappedOperation::FromInbox { .. } => (None, None),
+        };
+        Self {
+            tip: operation.operation.tip(),
+            source,
+            hash,
+            operation,
+            overtaken: 0,
+        }
+    }
+}
+
+/// Error returned when an operation cannot be queued
+#[derive(Debug, thiserror::Error)]
+pub enum QueueError {
+    #[error("queue is full")]
+    Full,
+    #[error("account {source} already has {limit} queued operations")]
+    PendingQuotaExceeded { source: PublicKeyHash, limit: usize },
+    #[error("account {source} already queued {limit} operations at level {level}")]
+    LevelQuotaExceeded {
+        source: PublicKeyHash,
+        level: u32,
+        limit: usize,
+    },
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl QueueError {
+    /// Returns true if the operation was rejected because of the quota of its
+    /// account, see [`OperationQueue::with_account_quota`]
+    pub fn is_quota_exceeded(&self) -> bool {
+        matches!(
+            self,
+            QueueError::PendingQuotaExceeded { .. }
+                | QueueError::LevelQuotaExceeded { .. }
+        )
+    }
 }
 
 pub struct OperationQueue {
     capacity: usize,
-    queue: VecDeque<WrappedOperation>,
+    queue: VecDeque<Entry>,
+    db: Option<Db>,
+    /// Ticketer and rollup addresses used to parse the persisted inbox messages
+    inbox_addresses: Option<(ContractKt1Hash, SmartRollupHash)>,
+    ordering: QueueOrdering,
+    /// L1 level to roll the executed operations back to, see [`OperationQueue::roll_back`]
+    rollback: Option<u32>,
+    /// Operations submitted directly to the L1, in inbox order, see
+    /// [`OperationQueue::with_force_inclusion_delay`]
+    delayed: VecDeque<Entry>,
+    force_inclusion_delay: Option<u32>,
+    account_quota: AccountQuota,
+    /// Latest L1 level of the queued inbox messages
+    level: u32,
+    /// Number of operations of each account queued during `level`
+    level_counts: HashMap<PublicKeyHash, usize>,
 }
 
 impl OperationQueue {
@@ -35,52 +221,693 @@ impl OperationQueue {
         Self {
             capacity,
             queue: VecDeque::with_capacity(capacity),
+            db: None,
+            inbox_addresses: None,
+            ordering: QueueOrdering::default(),
+            rollback: None,
+            delayed: VecDeque::new(),
+            force_inclusion_delay: None,
+            account_quota: AccountQuota::default(),
+            level: 0,
+            level_counts: HashMap::new(),
         }
     }
 
-    pub fn insert(&mut self, op: WrappedOperation) -> anyhow::Result<()> {
-        if self.is_full() {
-            anyhow::bail!("queue is full")
-        } else {
-            self.queue.push_back(op);
-            Ok(())
+    pub fn with_ordering(mut self, ordering: QueueOrdering) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    /// Delays the operations submitted directly to the L1 inbox, bypassing the
+    /// sequencer, see [`WrappedOperation::is_direct`], by `levels` L1 levels. Once
+    /// due, they are queued in inbox order right before the first inbox message of
+    /// the level, so that the sequencer cannot censor them and their position only
+    /// depends on the inbox. When `levels` is `None`, operations are taken in the
+    /// order they were queued.
+    pub fn with_force_inclusion_delay(mut self, levels: Option<u32>) -> Self {
+        self.force_inclusion_delay = levels;
+        for entry in std::mem::take(&mut self.queue) {
+            self.enqueue(entry);
         }
+        self
     }
 
-    pub fn insert_ref(&mut self, op: &WrappedOperation) -> anyhow::Result<()> {
+    /// Limits the operations injected in the node by each account, see
+    /// [`AccountQuota`]. An account cannot queue more than `max_pending` operations
+    /// at once, nor more than `max_per_level` operations while the inbox messages
+    /// of an L1 level are the latest queued ones.
+    pub fn with_account_quota(mut self, quota: AccountQuota) -> Self {
+        self.account_quota = quota;
+        self
+    }
+
+    /// Creates a queue that persists its operations in `db` until they are
+    /// executed. The operations that were not executed before the node stopped
+    /// are queued again, in order, even if they exceed `capacity`. Inbox
+    /// messages are parsed again with `ticketer` and `rollup_address`.
+    pub fn persistent(
+        capacity: usize,
+        db: Db,
+        ticketer: &ContractKt1Hash,
+        rollup_address: &SmartRollupHash,
+    ) -> anyhow::Result<Self> {
+        let mut queue = VecDeque::with_capacity(capacity);
+        for persisted in db
+            .queued_operations()
+            .context("failed to read queued operations")?
+        {
+            let seq = persisted.seq;
+            match parse_persisted(persisted, ticketer, rollup_address) {
+                Some(operation) => queue.push_back(
+                    QueuedOperation {
+                        id: Some(seq),
+                        operation,
+                    }
+                    .into(),
+                ),
+                None => {
+                    warn!("dropping invalid queued operation {seq}");
+                    db.commit_journal(None, None, Some(seq))?;
+                }
+            }
+        }
+        Ok(Self {
+            capacity,
+            queue,
+            db: Some(db),
+            inbox_addresses: Some((ticketer.clone(), rollup_address.clone())),
+            ordering: QueueOrdering::default(),
+            rollback: None,
+            delayed: VecDeque::new(),
+            force_inclusion_delay: None,
+            account_quota: AccountQuota::default(),
+            level: 0,
+            level_counts: HashMap::new(),
+        })
+    }
+
+    /// Inserts `op` at the back of the queue. An operation injected in the node
+    /// that is already queued, or that a persistent queue already took, is accepted
+    /// without being queued again, so that clients can safely retry injecting it.
+    /// Likewise, an operation read from the inbox that a persistent queue already
+    /// took, e.g. because the sequencer published it, is only recorded as included.
+    ///
+    /// Queues shared between threads should use [`insert`], which does not hold the
+    /// lock of the queue while the operation is persisted.
+    pub fn insert(&mut self, op: WrappedOperation) -> Result<(), QueueError> {
+        self.observe_level(&op);
+        let Some(db) = self.db.clone() else {
+            return self.push(op, None).map(drop);
+        };
+        let Some(id) = persist_new(&db, &op)? else {
+            return Ok(());
+        };
+        let state = queued_state(&op);
+        settle(&db, id, state, self.push(op, Some(id)))
+    }
+
+    pub fn insert_ref(&mut self, op: &WrappedOperation) -> Result<(), QueueError> {
+        self.insert(op.clone())
+    }
+
+    /// Starts a new level when `op` is the first inbox message queued from it
+    fn observe_level(&mut self, op: &WrappedOperation) {
+        if let WrappedOperation::FromInbox { message, .. } = op {
+            if message.inbox_id.l1_level > self.level {
+                self.level = message.inbox_id.l1_level;
+                self.level_counts.clear();
+            }
+        }
+    }
+
+    /// Queues `op`, persisted as `id` if the queue is persistent. Returns false if
+    /// `op` is already queued.
+    fn push(
+        &mut self,
+        op: WrappedOperation,
+        id: Option<u64>,
+    ) -> Result<bool, QueueError> {
+        if self.is_queued(&op) {
+            return Ok(false);
+        }
         if self.is_full() {
-            anyhow::bail!("queue is full")
+            return Err(QueueError::Full);
+        }
+        if let WrappedOperation::FromNode(op) = &op {
+            self.check_account_quota(&op.source())?;
+            *self.level_counts.entry(op.source()).or_default() += 1;
+        }
+        self.enqueue(QueuedOperation { id, operation: op }.into());
+        Ok(true)
+    }
+
+    /// Queues `entry`, or delays it if it was submitted directly to the L1, see
+    /// [`OperationQueue::with_force_inclusion_delay`]. An inbox message first queues
+    /// the delayed operations that are due at its level.
+    fn enqueue(&mut self, entry: Entry) {
+        let (Some(delay), Some(position)) =
+            (self.force_inclusion_delay, entry.inbox_position())
+        else {
+            self.queue.push_back(entry);
+            return;
+        };
+        while self.delayed.front().is_some_and(|delayed| {
+            delayed
+                .inbox_position()
+                .is_some_and(|(level, _)| level.saturating_add(delay) <= position.0)
+        }) {
+            self.queue.extend(self.delayed.pop_front());
+        }
+        if entry.operation.operation.is_direct() {
+            let index = self
+                .delayed
+                .partition_point(|delayed| delayed.inbox_position() < Some(position));
+            self.delayed.insert(index, entry);
         } else {
-            self.queue.push_back(op.clone());
-            Ok(())
+            self.queue.push_back(entry);
+        }
+    }
+
+    fn check_account_quota(&self, source: &PublicKeyHash) -> Result<(), QueueError> {
+        if let Some(limit) = self.account_quota.max_pending {
+            let pending = self
+                .queue
+                .iter()
+                .filter(|entry| entry.source.as_ref() == Some(source))
+                .count();
+            if pending >= limit {
+                return Err(QueueError::PendingQuotaExceeded {
+                    source: source.clone(),
+                    limit,
+                });
+            }
+        }
+        if let Some(limit) = self.account_quota.max_per_level {
+            if self.level_counts.get(source).copied().unwrap_or_default() >= limit {
+                return Err(QueueError::LevelQuotaExceeded {
+                    source: source.clone(),
+                    level: self.level,
+                    limit,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if `op` was injected in the node and is still queued
+    fn is_queued(&self, op: &WrappedOperation) -> bool {
+        let WrappedOperation::FromNode(op) = op else {
+            return false;
+        };
+        let hash = op.hash();
+        self.queue
+            .iter()
+            .any(|entry| entry.hash.as_ref() == Some(&hash))
+    }
+
+    /// Returns the database of a persistent queue
+    pub fn db(&self) -> Option<&Db> {
+        self.db.as_ref()
+    }
+
+    /// Takes the next operation to execute, see [`QueueOrdering`] and
+    /// [`OperationQueue::with_force_inclusion_delay`]. Returns `None` while a
+    /// rollback is pending, see [`OperationQueue::roll_back`].
+    ///
+    /// Queues shared between threads should use [`pop`], which does not hold the
+    /// lock of the queue while the executed operations are rolled back.
+    pub fn pop(&mut self) -> Option<QueuedOperation> {
+        if !self.complete_rollback() {
+            return None;
+        }
+        let index = match self.ordering {
+            QueueOrdering::Fifo => 0,
+            QueueOrdering::Fee => self.highest_tip(),
+        };
+        let entry = self.queue.remove(index)?;
+        for overtaken in self.queue.range_mut(..index) {
+            overtaken.overtaken += 1;
+        }
+        Some(entry.operation)
+    }
+
+    /// Rolls back the operations read from the inbox of an L1 level after `level`,
+    /// which was reverted by a reorg. Queued operations are dropped right away.
+    /// Executed operations are rolled back by the next [`OperationQueue::pop`] once
+    /// no operation is being executed, and the rolled back operations injected in
+    /// the node are queued again first, see [`Db::roll_back`].
+    ///
+    /// Queues shared between threads should use [`roll_back`], which does not hold
+    /// the lock of the queue while the dropped operations are forgotten.
+    pub fn roll_back(&mut self, level: u32) -> anyhow::Result<()> {
+        let dropped = self.drop_after(level);
+        match &self.db {
+            Some(db) => forget_dropped(db, dropped),
+            None => Ok(()),
+        }
+    }
+
+    /// Drops the queued operations read from the inbox of an L1 level after
+    /// `level` and marks the executed ones to be rolled back. Returns the persisted
+    /// sequence numbers and hashes of the dropped operations.
+    fn drop_after(&mut self, level: u32) -> Vec<(Option<u64>, Option<String>)> {
+        let mut dropped = vec![];
+        let mut retain = |entry: &Entry| match &entry.operation.operation {
+            WrappedOperation::FromInbox { message, .. }
+                if message.inbox_id.l1_level > level =>
+            {
+                dropped.push((
+                    entry.operation.id,
+                    entry.operation.operation.operation_hash(),
+                ));
+                false
+            }
+            _ => true,
+        };
+        self.queue.retain(&mut retain);
+        self.delayed.retain(&mut retain);
+        if self.db.is_some() {
+            self.rollback =
+                Some(self.rollback.map_or(level, |pending| pending.min(level)));
+        }
+        dropped
+    }
+
+    /// Returns true until the executed operations of a rollback are rolled back, see
+    /// [`OperationQueue::roll_back`]
+    pub fn is_rolling_back(&self) -> bool {
+        self.rollback.is_some()
+    }
+
+    /// Rolls back the executed operations of a pending rollback, unless an operation
+    /// is being executed. Returns false if the rollback is still pending.
+    fn complete_rollback(&mut self) -> bool {
+        let (Some(level), Some(db)) = (self.rollback, self.db.clone()) else {
+            return true;
+        };
+        match roll_back_executed(&db, level, self.inbox_addresses.as_ref()) {
+            Ok(Some(requeued)) => {
+                self.rollback = None;
+                self.requeue(requeued);
+                true
+            }
+            Ok(None) => false,
+            Err(e) => {
+                warn!("{e:?}");
+                false
+            }
         }
     }
 
-    pub fn pop(&mut self) -> Option<WrappedOperation> {
-        self.queue.pop_front()
+    /// Queues the rolled back operations first, in order
+    fn requeue(&mut self, requeued: Vec<Entry>) {
+        for entry in requeued.into_iter().rev() {
+            self.queue.push_front(entry);
+        }
+    }
+
+    /// Returns the index of the operation with the highest tip, the oldest one on
+    /// ties. Operations never overtake an inbox message queued before them, an
+    /// operation of the same signer, which would break the order of their nonces,
+    /// nor an operation that was already overtaken [`MAX_OVERTAKES`] times, so that
+    /// operations with low tips are not starved.
+    fn highest_tip(&self) -> usize {
+        // The front entry is overtaken whenever a later entry is, so it is the first
+        // one to reach the limit
+        if self
+            .queue
+            .front()
+            .is_some_and(|entry| entry.overtaken >= MAX_OVERTAKES)
+        {
+            return 0;
+        }
+        let mut sources = HashSet::new();
+        self.queue
+            .iter()
+            .map_while(|entry| Some((entry.tip?, entry.source.as_ref()?)))
+            .enumerate()
+            .filter(|(_, (_, source))| sources.insert(*source))
+            .max_by_key(|&(index, (tip, _))| (tip, Reverse(index)))
+            .map_or(0, |(index, _)| index)
     }
 
     pub fn is_full(&self) -> bool {
-        self.queue.len() >= self.capacity
+        self.queue.len() + self.delayed.len() >= self.capacity
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Changes the capacity of the queue. Operations already queued are kept when it
+    /// is lowered below their number, new ones are rejected until the queue drains.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
     }
 
     #[cfg(test)]
     #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
-        self.queue.len()
+        self.queue.len() + self.delayed.len()
+    }
+}
+
+/// Inserts `op` in `queue` like [`OperationQueue::insert`]. The lock of the queue is
+/// only held while `op` is pushed, not while it is persisted in the database of a
+/// persistent queue.
+pub fn insert(
+    queue: &RwLock<OperationQueue>,
+    op: WrappedOperation,
+) -> Result<(), QueueError> {
+    let lock = || {
+        queue
+            .write()
+            .map_err(|e| anyhow::anyhow!("failed to lock the queue: {e}"))
+    };
+    let db = {
+        let mut queue = lock()?;
+        // Spares persisting operations that cannot be queued
+        if queue.is_full() && !queue.is_queued(&op) {
+            return Err(QueueError::Full);
+        }
+        queue.observe_level(&op);
+        queue.db.clone()
+    };
+    let Some(db) = db else {
+        return lock()?.push(op, None).map(drop);
+    };
+    let Some(id) = persist_new(&db, &op)? else {
+        return Ok(());
+    };
+    let state = queued_state(&op);
+    let pushed = lock()?.push(op, Some(id));
+    settle(&db, id, state, pushed)
+}
+
+/// Rolls back `queue` to `level` like [`OperationQueue::roll_back`], without holding
+/// the lock of the queue while the dropped operations are forgotten
+pub fn roll_back(queue: &RwLock<OperationQueue>, level: u32) -> anyhow::Result<()> {
+    let (dropped, db) = {
+        let mut queue = queue
+            .write()
+            .map_err(|e| anyhow::anyhow!("failed to lock the queue: {e}"))?;
+        (queue.drop_after(level), queue.db.clone())
+    };
+    match db {
+        Some(db) => forget_dropped(&db, dropped),
+        None => Ok(()),
+    }
+}
+
+/// The next step of a worker taking operations from a queue shared between threads,
+/// see [`pop`]
+pub enum Next {
+    /// The executed operations of a pending rollback were rolled back, see
+    /// [`OperationQueue::roll_back`]
+    RolledBack,
+    Operation(QueuedOperation),
+}
+
+/// Takes the next operation of `queue` like [`OperationQueue::pop`], without holding
+/// the lock of the queue while the executed operations of a pending rollback are
+/// rolled back, see [`prune_rollback`]. Returns [`Next::RolledBack`] once they are, before the rolled back
+/// operations are taken again, so that the state not kept in the database, e.g. the
+/// one of the RISCV PVM, can be rolled back as well.
+pub fn pop(queue: &RwLock<OperationQueue>) -> anyhow::Result<Option<Next>> {
+    let lock = || {
+        queue
+            .write()
+            .map_err(|e| anyhow::anyhow!("failed to lock the queue: {e}"))
+    };
+    let (level, db, inbox_addresses) = {
+        let mut queue = lock()?;
+        match (queue.rollback, queue.db.clone()) {
+            (Some(level), Some(db)) => (level, db, queue.inbox_addresses.clone()),
+            (_, db) => {
+                let op = queue.pop();
+                drop(queue);
+                if let Some((db, op)) = db.as_ref().zip(op.as_ref()) {
+                    prune_rollback(db, op);
+                }
+                return Ok(op.map(Next::Operation));
+            }
+        }
+    };
+    let Some(requeued) = roll_back_executed(&db, level, inbox_addresses.as_ref())? else {
+        return Ok(None);
+    };
+    let mut queue = lock()?;
+    // The queue may have been rolled back to an earlier level meanwhile
+    queue.rollback = queue.rollback.filter(|pending| *pending < level);
+    queue.requeue(requeued);
+    Ok(Some(Next::RolledBack))
+}
+
+/// Removes the operations dropped from the queue from the database, see
+/// [`OperationQueue::roll_back`]
+fn forget_dropped(
+    db: &Db,
+    dropped: Vec<(Option<u64>, Option<String>)>,
+) -> anyhow::Result<()> {
+    for (id, operation_hash) in dropped {
+        if let Some(id) = id {
+            db.dequeue(id).context("failed to drop queued operation")?;
+        }
+        // The operation may be read again from the inbox of another level
+        if let Some(operation_hash) = operation_hash {
+            db.forget_operation_state(&operation_hash)
+                .context("failed to forget dropped operation")?;
+        }
+    }
+    Ok(())
+}
+
+/// Rolls back the operations executed after L1 level `level`, see [`Db::roll_back`],
+/// and persists the rolled back operations injected in the node again. Returns
+/// them, in order, or `None` while an operation is being executed.
+fn roll_back_executed(
+    db: &Db,
+    level: u32,
+    inbox_addresses: Option<&(ContractKt1Hash, SmartRollupHash)>,
+) -> anyhow::Result<Option<Vec<Entry>>> {
+    if db
+        .has_pending_writes()
+        .context("failed to check pending writes")?
+    {
+        return Ok(None);
+    }
+    let rolled_back = db
+        .roll_back(level)
+        .with_context(|| format!("failed to roll back to level {level}"))?;
+    let Some((ticketer, rollup_address)) = inbox_addresses else {
+        return Ok(Some(vec![]));
+    };
+    let mut requeued = vec![];
+    for persisted in rolled_back {
+        let seq = persisted.seq;
+        let Some(operation) = parse_persisted(persisted, ticketer, rollup_address) else {
+            warn!("dropping invalid rolled back operation {seq}");
+            continue;
+        };
+        match persist(db, &operation) {
+            Ok(id) => {
+                if let Some(state) = queued_state(&operation) {
+                    record_state(db, state);
+                }
+                requeued.push(
+                    QueuedOperation {
+                        id: Some(id),
+                        operation,
+                    }
+                    .into(),
+                );
+            }
+            Err(e) => warn!("failed to queue rolled back operation {seq}: {e:?}"),
+        }
+    }
+    Ok(Some(requeued))
+}
+
+/// Forgets how to roll back the operations before the finalized L1 levels, when
+/// the operation taken from the queue starts a level, see [`Db::prune_rollback`]
+fn prune_rollback(db: &Db, op: &QueuedOperation) {
+    if let WrappedOperation::FromInbox { message, .. } = &op.operation {
+        // Levels start with their first message
+        if message.inbox_id.l1_message_id == 0 {
+            let finalized = message.inbox_id.l1_level.saturating_sub(MAX_REORG_DEPTH);
+            if let Err(e) = db.prune_rollback(finalized) {
+                warn!("failed to prune rollback records: {e:?}");
+            }
+        }
+    }
+}
+
+/// Persists `op` before it is queued, unless it was queued before or read from the
+/// inbox and executed, in which case `None` is returned and an operation read from
+/// the inbox is recorded as included
+fn persist_new(db: &Db, op: &WrappedOperation) -> anyhow::Result<Option<u64>> {
+    let hashes = op.executed_hashes();
+    let recorded = !hashes.is_empty()
+        && hashes.iter().all(|hash| {
+            db.operation_status(hash)
+                .is_ok_and(|status| status.is_some())
+        });
+    if recorded {
+        if let WrappedOperation::FromInbox { message, .. } = op {
+            for hash in hashes {
+                let level = Some(message.inbox_id.l1_level);
+                record_state(db, (hash, OperationState::Included, level));
+            }
+        }
+        return Ok(None);
+    }
+    persist(db, op)
+        .map(Some)
+        .context("failed to persist operation")
+}
+
+/// Records the state of `op` once pushed to the queue as `id`, or removes it from
+/// the persisted queue if it was not queued
+fn settle(
+    db: &Db,
+    id: u64,
+    state: Option<QueuedState>,
+    pushed: Result<bool, QueueError>,
+) -> Result<(), QueueError> {
+    match pushed {
+        Ok(true) => {
+            if let Some(state) = state {
+                record_state(db, state);
+            }
+            Ok(())
+        }
+        Ok(false) => Ok(db.dequeue(id).context("failed to drop operation")?),
+        Err(e) => {
+            if let Err(e) = db.dequeue(id) {
+                warn!("failed to drop rejected operation {id}: {e:?}");
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Parses an operation persisted in the database, see [`PersistedOperation`]
+fn parse_persisted(
+    persisted: PersistedOperation,
+    ticketer: &ContractKt1Hash,
+    rollup_address: &SmartRollupHash,
+) -> Option<WrappedOperation> {
+    match persisted.inbox_id {
+        Some(inbox_id) => parse_inbox_message_hex(
+            &Logger,
+            inbox_id,
+            &persisted.operation,
+            ticketer,
+            rollup_address,
+        )
+        .map(|message| WrappedOperation::FromInbox {
+            message,
+            original_inbox_message: persisted.operation,
+        }),
+        None => hex::decode(&persisted.operation)
+            .ok()
+            .and_then(|bytes| SignedOperation::decode(&bytes).ok())
+            .map(WrappedOperation::FromNode),
+    }
+}
+
+fn persist(db: &Db, op: &WrappedOperation) -> anyhow::Result<u64> {
+    match op {
+        WrappedOperation::FromInbox {
+            message,
+            original_inbox_message,
+        } => db.enqueue(original_inbox_message, Some(&message.inbox_id)),
+        WrappedOperation::FromNode(op) => {
+            let encoded = op
+                .encode()
+                .map_err(|e| anyhow::anyhow!("failed to serialize operation: {e}"))?;
+            db.enqueue(&hex::encode(encoded), None)
+        }
+    }
+}
+
+/// The hash of a newly queued operation with its state and L1 level
+type QueuedState = (String, OperationState, Option<u32>);
+
+fn queued_state(op: &WrappedOperation) -> Option<QueuedState> {
+    let operation_hash = op.operation_hash()?;
+    Some(match op {
+        WrappedOperation::FromInbox { message, .. } => (
+            operation_hash,
+            OperationState::Included,
+            Some(message.inbox_id.l1_level),
+        ),
+        WrappedOperation::FromNode(_) => (operation_hash, OperationState::Queued, None),
+    })
+}
+
+/// Records the state of a newly queued operation, see [`Db::set_operation_state`]
+fn record_state(db: &Db, (operation_hash, state, level): QueuedState) {
+    if let Err(e) = db.set_operation_state(&operation_hash, state, level) {
+        warn!("failed to record the state of operation {operation_hash}: {e:?}");
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use jstz_proto::operation::internal::InboxId;
+    use std::sync::RwLock;
 
-    use super::OperationQueue;
-    use crate::sequencer::{
-        queue::WrappedOperation,
-        tests::{dummy_op, dummy_signed_op},
+    use jstz_proto::operation::{
+        internal::InboxId, Blueprint, Operation, SignedOperation,
     };
 
+    use jstz_kernel::inbox::{LevelInfo, ParsedInboxMessage, ParsedInboxMessageWrapper};
+    use jstz_mock::sr1_address;
+    use jstz_utils::KeyPair;
+    use tempfile::NamedTempFile;
+    use tezos_crypto_rs::hash::ContractKt1Hash;
+
+    use super::{Next, OperationQueue, QueueError, MAX_OVERTAKES};
+    use crate::{
+        config::{AccountQuota, QueueOrdering},
+        sequencer::{
+            db::{Db, OperationState},
+            queue::WrappedOperation,
+            tests::{dummy_op, dummy_signed_op, fee_op, signer, tipped_op},
+        },
+        test::default_injector,
+    };
+
+    fn level_start(l1_level: u32) -> WrappedOperation {
+        WrappedOperation::FromInbox {
+            message: ParsedInboxMessageWrapper {
+                content: ParsedInboxMessage::LevelInfo(LevelInfo::Start),
+                inbox_id: InboxId {
+                    l1_level,
+                    l1_message_id: 0,
+                },
+            },
+            original_inbox_message: "0001".to_string(),
+        }
+    }
+
+    fn level_end(l1_message_id: u32) -> WrappedOperation {
+        WrappedOperation::FromInbox {
+            message: ParsedInboxMessageWrapper {
+                content: ParsedInboxMessage::LevelInfo(LevelInfo::End),
+                inbox_id: InboxId {
+                    l1_level: 1,
+                    l1_message_id,
+                },
+            },
+            original_inbox_message: "0002".to_string(),
+        }
+    }
+
+    fn pop_tips(q: &mut OperationQueue, n: usize) -> Vec<Option<u64>> {
+        (0..n).map(|_| q.pop().unwrap().operation.tip()).collect()
+    }
+
     #[test]
     fn new_queue() {
         let q = OperationQueue::new(5);
@@ -94,7 +921,9 @@ mod tests {
         let mut q = OperationQueue::new(1);
         assert!(q.insert(dummy_op()).is_ok());
         assert_eq!(
-            q.insert(dummy_op()).unwrap_err().to_string(),
+            q.insert(tipped_op(&signer(0), 0, 0))
+                .unwrap_err()
+                .to_string(),
             "queue is full"
         );
     }
@@ -104,11 +933,197 @@ mod tests {
         let mut q = OperationQueue::new(1);
         assert!(q.insert_ref(&dummy_op()).is_ok());
         assert_eq!(
-            q.insert_ref(&dummy_op()).unwrap_err().to_string(),
+            q.insert_ref(&tipped_op(&signer(0), 0, 0))
+                .unwrap_err()
+                .to_string(),
             "queue is full"
         );
     }
 
+    #[test]
+    fn insert_duplicate() {
+        let mut q = OperationQueue::new(1);
+        q.insert(dummy_op()).unwrap();
+        // accepted even though the queue is full
+        q.insert(dummy_op()).unwrap();
+        assert_eq!(q.len(), 1);
+        q.pop().unwrap();
+        // the operation can be queued again once taken by a queue that is not persisted
+        q.insert(dummy_op()).unwrap();
+        assert_eq!(q.len(), 1);
+
+        // inbox messages are never deduplicated
+        let mut q = OperationQueue::new(2);
+        q.insert(level_end(0)).unwrap();
+        q.insert(level_end(0)).unwrap();
+        assert_eq!(q.len(), 2);
+    }
+
+    #[test]
+    fn persistent_queue_rejects_taken_operations() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let ticketer =
+            ContractKt1Hash::from_base58_check("KT1BRd2ka5q2cPRdXALtXD1QZ38CPam2j1ye")
+                .unwrap();
+        let mut q =
+            OperationQueue::persistent(1, db.clone(), &ticketer, &sr1_address()).unwrap();
+        q.insert(dummy_op()).unwrap();
+        let executed = q.pop().unwrap();
+        db.commit_journal(None, None, executed.id).unwrap();
+
+        q.insert(dummy_op()).unwrap();
+        assert_eq!(q.len(), 0);
+        assert!(db.queued_operations().unwrap().is_empty());
+    }
+
+    #[test]
+    fn insert_into_shared_queue() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let ticketer =
+            ContractKt1Hash::from_base58_check("KT1BRd2ka5q2cPRdXALtXD1QZ38CPam2j1ye")
+                .unwrap();
+        let q = RwLock::new(
+            OperationQueue::persistent(1, db.clone(), &ticketer, &sr1_address()).unwrap(),
+        );
+        super::insert(&q, dummy_op()).unwrap();
+        super::insert(&q, dummy_op()).unwrap();
+        assert_eq!(q.read().unwrap().len(), 1);
+        assert_eq!(db.queued_operations().unwrap().len(), 1);
+
+        // Rejected operations are not left in the database
+        assert!(matches!(
+            super::insert(&q, tipped_op(&signer(0), 0, 0)),
+            Err(QueueError::Full)
+        ));
+        assert_eq!(db.queued_operations().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn account_quota() {
+        let start_of_level = |l1_level| WrappedOperation::FromInbox {
+            message: ParsedInboxMessageWrapper {
+                content: ParsedInboxMessage::LevelInfo(LevelInfo::Start),
+                inbox_id: InboxId {
+                    l1_level,
+                    l1_message_id: 0,
+                },
+            },
+            original_inbox_message: "0001".to_string(),
+        };
+        let mut q = OperationQueue::new(10).with_account_quota(AccountQuota {
+            max_pending: Some(2),
+            max_per_level: Some(3),
+        });
+        q.insert(start_of_level(1)).unwrap();
+        q.insert(tipped_op(&signer(0), 0, 0)).unwrap();
+        q.insert(tipped_op(&signer(0), 1, 0)).unwrap();
+        let err = q.insert(tipped_op(&signer(0), 2, 0)).unwrap_err();
+        assert!(matches!(
+            err,
+            QueueError::PendingQuotaExceeded { limit: 2, .. }
+        ));
+        assert!(err.is_quota_exceeded());
+        // other accounts are not limited
+        q.insert(tipped_op(&signer(1), 0, 0)).unwrap();
+
+        // taking an operation frees a pending slot, but not a slot of the level
+        q.pop().unwrap();
+        q.pop().unwrap();
+        q.insert(tipped_op(&signer(0), 2, 0)).unwrap();
+        q.pop().unwrap();
+        let err = q.insert(tipped_op(&signer(0), 3, 0)).unwrap_err();
+        assert!(matches!(
+            err,
+            QueueError::LevelQuotaExceeded {
+                level: 1,
+                limit: 3,
+                ..
+            }
+        ));
+
+        // the slots of the level are reset at the next level
+        q.insert(start_of_level(2)).unwrap();
+        q.insert(tipped_op(&signer(0), 3, 0)).unwrap();
+
+        let mut q = OperationQueue::new(1).with_account_quota(AccountQuota {
+            max_pending: Some(0),
+            max_per_level: None,
+        });
+        assert!(matches!(
+            q.insert(tipped_op(&signer(0), 0, 0)).unwrap_err(),
+            QueueError::PendingQuotaExceeded { .. }
+        ));
+        // a full queue is reported before the quotas
+        q.insert(start_of_level(1)).unwrap();
+        let err = q.insert(tipped_op(&signer(0), 0, 0)).unwrap_err();
+        assert!(matches!(err, QueueError::Full));
+        assert!(!err.is_quota_exceeded());
+    }
+
+    #[test]
+    fn direct_operations_are_delayed() {
+        let direct = |l1_level, l1_message_id| WrappedOperation::FromInbox {
+            message: ParsedInboxMessageWrapper {
+                content: ParsedInboxMessage::JstzMessage(
+                    jstz_kernel::inbox::Message::External(dummy_signed_op()),
+                ),
+                inbox_id: InboxId {
+                    l1_level,
+                    l1_message_id,
+                },
+            },
+            original_inbox_message: "0001".to_string(),
+        };
+        let position = |op: Option<QueuedOperation>| match op.unwrap().operation {
+            WrappedOperation::FromInbox { message, .. } => {
+                Some((message.inbox_id.l1_level, message.inbox_id.l1_message_id))
+            }
+            WrappedOperation::FromNode(_) => None,
+        };
+        assert!(direct(1, 3).is_direct());
+        assert!(!dummy_op().is_direct());
+        assert!(!level_end(0).is_direct());
+        let mut q = OperationQueue::new(8).with_force_inclusion_delay(Some(2));
+
+        q.insert(level_start(1)).unwrap();
+        q.insert(direct(1, 1)).unwrap();
+        q.insert(tipped_op(&signer(0), 0, 0)).unwrap();
+        q.insert(direct(1, 2)).unwrap();
+        q.insert(level_start(2)).unwrap();
+        q.insert(direct(2, 1)).unwrap();
+        assert_eq!(q.len(), 6);
+
+        // not taken before they are due, even when nothing else is queued
+        assert_eq!(position(q.pop()), Some((1, 0)));
+        assert_eq!(position(q.pop()), None);
+        assert_eq!(position(q.pop()), Some((2, 0)));
+        assert!(q.pop().is_none());
+
+        // taken in inbox order right before the first inbox message of the level
+        // they are due at, regardless of when it is read
+        q.insert(tipped_op(&signer(1), 0, 0)).unwrap();
+        q.insert(level_start(3)).unwrap();
+        q.insert(tipped_op(&signer(2), 0, 0)).unwrap();
+        assert_eq!(position(q.pop()), None);
+        assert_eq!(position(q.pop()), Some((1, 1)));
+        assert_eq!(position(q.pop()), Some((1, 2)));
+        assert_eq!(position(q.pop()), Some((3, 0)));
+        assert_eq!(position(q.pop()), None);
+        assert!(q.pop().is_none());
+
+        // queued operations are delayed like new ones
+        let mut q = OperationQueue::new(8);
+        q.insert(level_start(1)).unwrap();
+        q.insert(direct(1, 1)).unwrap();
+        q.insert(level_start(2)).unwrap();
+        let mut q = q.with_force_inclusion_delay(Some(1));
+        assert_eq!(position(q.pop()), Some((1, 0)));
+        assert_eq!(position(q.pop()), Some((1, 1)));
+        assert_eq!(position(q.pop()), Some((2, 0)));
+    }
+
     #[test]
     fn is_full() {
         let q = OperationQueue::new(0);
@@ -119,6 +1134,23 @@ mod tests {
         q.insert(dummy_op()).unwrap();
     }
 
+    #[test]
+    fn set_capacity() {
+        let mut q = OperationQueue::new(2);
+        q.insert(tipped_op(&signer(0), 0, 0)).unwrap();
+        q.insert(tipped_op(&signer(1), 0, 0)).unwrap();
+
+        // queued operations are kept when the capacity is lowered
+        q.set_capacity(1);
+        assert_eq!(q.capacity(), 1);
+        assert_eq!(q.len(), 2);
+        assert!(q.insert(tipped_op(&signer(2), 0, 0)).is_err());
+
+        q.set_capacity(3);
+        q.insert(tipped_op(&signer(2), 0, 0)).unwrap();
+        assert!(q.is_full());
+    }
+
     #[test]
     fn pop() {
         let mut q = OperationQueue::new(1);
@@ -127,6 +1159,302 @@ mod tests {
         assert!(q.pop().is_some());
     }
 
+    #[test]
+    fn tip() {
+        assert_eq!(tipped_op(&signer(0), 0, 42).tip(), Some(42));
+        assert_eq!(dummy_op().tip(), Some(0));
+        assert_eq!(level_end(0).tip(), None);
+        // the fee is the priority of operations declaring one
+        assert_eq!(fee_op(&signer(0), None, Some(42)).tip(), Some(42));
+        assert_eq!(fee_op(&signer(0), Some(7), Some(42)).tip(), Some(7));
+        assert_eq!(fee_op(&signer(0), Some(7), None).tip(), Some(0));
+    }
+
+    #[test]
+    fn pop_highest_fee_first() {
+        let mut q = OperationQueue::new(3);
+        q.insert(tipped_op(&signer(0), 0, 5)).unwrap();
+        q.insert(fee_op(&signer(1), Some(3), Some(10))).unwrap();
+        q.insert(fee_op(&signer(2), None, Some(10))).unwrap();
+        assert_eq!(pop_tips(&mut q, 3), [Some(10), Some(5), Some(3)]);
+    }
+
+    #[test]
+    fn pop_highest_tip_first() {
+        let mut q = OperationQueue::new(4);
+        for (seed, tip) in [(0, 1), (1, 5), (2, 3), (3, 5)] {
+            q.insert(tipped_op(&signer(seed), 0, tip)).unwrap();
+        }
+        assert_eq!(pop_tips(&mut q, 4), [Some(5), Some(5), Some(3), Some(1)]);
+        assert!(q.pop().is_none());
+    }
+
+    #[test]
+    fn pop_fifo() {
+        let mut q = OperationQueue::new(3).with_ordering(QueueOrdering::Fifo);
+        for (seed, tip) in [(0, 1), (1, 5), (2, 3)] {
+            q.insert(tipped_op(&signer(seed), 0, tip)).unwrap();
+        }
+        assert_eq!(pop_tips(&mut q, 3), [Some(1), Some(5), Some(3)]);
+    }
+
+    #[test]
+    fn operations_of_a_signer_are_not_reordered() {
+        let mut q = OperationQueue::new(3);
+        q.insert(tipped_op(&signer(0), 0, 1)).unwrap();
+        q.insert(tipped_op(&signer(1), 0, 3)).unwrap();
+        q.insert(tipped_op(&signer(0), 1, 5)).unwrap();
+        assert_eq!(pop_tips(&mut q, 3), [Some(3), Some(1), Some(5)]);
+    }
+
+    #[test]
+    fn inbox_messages_are_not_overtaken() {
+        let mut q = OperationQueue::new(4);
+        q.insert(tipped_op(&signer(0), 0, 1)).unwrap();
+        q.insert(level_end(0)).unwrap();
+        q.insert(tipped_op(&signer(1), 0, 5)).unwrap();
+        q.insert(level_end(1)).unwrap();
+        assert_eq!(pop_tips(&mut q, 4), [Some(1), None, Some(5), None]);
+    }
+
+    #[test]
+    fn low_tips_are_not_starved() {
+        let mut q = OperationQueue::new(2);
+        q.insert(tipped_op(&signer(0), 0, 0)).unwrap();
+        // a steady stream of operations with higher tips
+        for seed in 1..=MAX_OVERTAKES as u8 {
+            q.insert(tipped_op(&signer(seed), 0, 10)).unwrap();
+            assert_eq!(q.pop().unwrap().operation.tip(), Some(10));
+        }
+        q.insert(tipped_op(&signer(100), 0, 10)).unwrap();
+        assert_eq!(pop_tips(&mut q, 2), [Some(0), Some(10)]);
+    }
+
+    #[test]
+    fn persistent_queue_restores_operations() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let ticketer =
+            ContractKt1Hash::from_base58_check("KT1BRd2ka5q2cPRdXALtXD1QZ38CPam2j1ye")
+                .unwrap();
+        let level_end = WrappedOperation::FromInbox {
+            message: ParsedInboxMessageWrapper {
+                content: ParsedInboxMessage::LevelInfo(LevelInfo::End),
+                inbox_id: InboxId {
+                    l1_level: 7,
+                    l1_message_id: 2,
+                },
+            },
+            original_inbox_message: "0002".to_string(),
+        };
+
+        let mut q =
+            OperationQueue::persistent(2, db.clone(), &ticketer, &sr1_address()).unwrap();
+        q.insert(dummy_op()).unwrap();
+        q.insert(level_end).unwrap();
+        let executed = q.pop().unwrap();
+        db.commit_journal(None, None, executed.id).unwrap();
+        drop(q);
+
+        // only the operation that was not executed is restored
+        let mut q = OperationQueue::persistent(2, db, &ticketer, &sr1_address()).unwrap();
+        assert_eq!(q.len(), 1);
+        match q.pop().unwrap().operation {
+            WrappedOperation::FromInbox {
+                message,
+                original_inbox_message,
+            } => {
+                assert_eq!(original_inbox_message, "0002");
+                assert_eq!(
+                    message.content,
+                    ParsedInboxMessage::LevelInfo(LevelInfo::End)
+                );
+                assert_eq!(message.inbox_id.l1_level, 7);
+                assert_eq!(message.inbox_id.l1_message_id, 2);
+            }
+            _ => panic!("should be from inbox"),
+        }
+    }
+
+    #[test]
+    fn persistent_queue_records_operation_state() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let ticketer =
+            ContractKt1Hash::from_base58_check("KT1BRd2ka5q2cPRdXALtXD1QZ38CPam2j1ye")
+                .unwrap();
+        let signed_op = dummy_signed_op();
+        let from_node = WrappedOperation::FromNode(signed_op.clone());
+        let from_inbox = WrappedOperation::FromInbox {
+            message: ParsedInboxMessageWrapper {
+                content: ParsedInboxMessage::JstzMessage(
+                    jstz_kernel::inbox::Message::External(signed_op),
+                ),
+                inbox_id: InboxId {
+                    l1_level: 7,
+                    l1_message_id: 2,
+                },
+            },
+            original_inbox_message: "0001".to_string(),
+        };
+        let hash = from_node.operation_hash().unwrap();
+        assert_eq!(from_inbox.operation_hash(), Some(hash.clone()));
+
+        let mut q =
+            OperationQueue::persistent(2, db.clone(), &ticketer, &sr1_address()).unwrap();
+        q.insert(from_node).unwrap();
+        let status = db.operation_status(&hash).unwrap().unwrap();
+        assert_eq!(status.state, OperationState::Queued);
+        assert_eq!(status.level, None);
+
+        // the operation is read from the inbox once posted to the L1, and only
+        // executed once
+        q.insert(from_inbox).unwrap();
+        assert_eq!(q.len(), 1);
+        let status = db.operation_status(&hash).unwrap().unwrap();
+        assert_eq!(status.state, OperationState::Included);
+        assert_eq!(status.level, Some(7));
+    }
+
+    #[test]
+    fn published_blueprints_are_not_queued() {
+        let db = Db::init(Some("")).unwrap();
+        let ticketer =
+            ContractKt1Hash::from_base58_check("KT1BRd2ka5q2cPRdXALtXD1QZ38CPam2j1ye")
+                .unwrap();
+        let signed_op = dummy_signed_op();
+        let hash = signed_op.hash().to_string();
+        let KeyPair(pk, sk) = default_injector();
+        let blueprint = |operations| {
+            let op = Operation {
+                public_key: pk.clone(),
+                nonce: 0.into(),
+                content: Blueprint { operations }.into(),
+                max_fee: None,
+                priority_fee: None,
+            };
+            let signature = sk.sign(op.hash()).unwrap();
+            WrappedOperation::FromInbox {
+                message: ParsedInboxMessageWrapper {
+                    content: ParsedInboxMessage::JstzMessage(
+                        jstz_kernel::inbox::Message::External(SignedOperation::new(
+                            signature, op,
+                        )),
+                    ),
+                    inbox_id: InboxId {
+                        l1_level: 7,
+                        l1_message_id: 2,
+                    },
+                },
+                original_inbox_message: "0001".to_string(),
+            }
+        };
+
+        let mut q =
+            OperationQueue::persistent(2, db.clone(), &ticketer, &sr1_address()).unwrap();
+        q.insert(dummy_op()).unwrap();
+        q.pop().unwrap();
+
+        // the operations of a blueprint are only recorded as included once they
+        // were all taken
+        q.insert(blueprint(vec![signed_op.clone()])).unwrap();
+        assert_eq!(q.len(), 0);
+        let status = db.operation_status(&hash).unwrap().unwrap();
+        assert_eq!(status.state, OperationState::Included);
+        assert_eq!(status.level, Some(7));
+
+        let other = match tipped_op(&signer(1), 0, 0) {
+            WrappedOperation::FromNode(op) => op,
+            _ => unreachable!(),
+        };
+        q.insert(blueprint(vec![signed_op, other])).unwrap();
+        assert_eq!(q.len(), 1);
+    }
+
+    #[test]
+    fn roll_back_requeues_operations_injected_in_the_node() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let conn = db.connection().unwrap();
+        let ticketer =
+            ContractKt1Hash::from_base58_check("KT1BRd2ka5q2cPRdXALtXD1QZ38CPam2j1ye")
+                .unwrap();
+        let mut q =
+            OperationQueue::persistent(3, db.clone(), &ticketer, &sr1_address()).unwrap();
+        let execute = |q: &mut OperationQueue, key: &str| {
+            let executed = q.pop().unwrap();
+            crate::sequencer::db::exec_record_undo(&conn, None, key).unwrap();
+            crate::sequencer::db::exec_write(&conn, key, "01").unwrap();
+            db.commit_journal(
+                None,
+                executed.operation.operation_hash().as_deref(),
+                executed.id,
+            )
+            .unwrap();
+        };
+        q.insert(level_start(2)).unwrap();
+        execute(&mut q, "/level");
+        q.insert(dummy_op()).unwrap();
+        execute(&mut q, "/op");
+        q.insert(level_start(3)).unwrap();
+
+        q.roll_back(1).unwrap();
+        // the queued inbox message is dropped right away
+        assert_eq!(q.len(), 0);
+        assert!(db.queued_operations().unwrap().is_empty());
+        assert!(q.is_rolling_back());
+
+        // the operation injected in the node is executed again
+        match q.pop().unwrap().operation {
+            WrappedOperation::FromNode(op) => assert_eq!(op, dummy_signed_op()),
+            _ => panic!("should be from node"),
+        }
+        assert!(!q.is_rolling_back());
+        assert_eq!(db.read_key("/level").unwrap(), None);
+        assert_eq!(db.read_key("/op").unwrap(), None);
+    }
+
+    #[test]
+    fn shared_queue_reports_completed_rollback() {
+        let db = Db::init(Some("")).unwrap();
+        let conn = db.connection().unwrap();
+        let ticketer =
+            ContractKt1Hash::from_base58_check("KT1BRd2ka5q2cPRdXALtXD1QZ38CPam2j1ye")
+                .unwrap();
+        let q = RwLock::new(
+            OperationQueue::persistent(3, db.clone(), &ticketer, &sr1_address()).unwrap(),
+        );
+        let execute = |key: &str| {
+            let Some(Next::Operation(executed)) = super::pop(&q).unwrap() else {
+                panic!("should take an operation");
+            };
+            crate::sequencer::db::exec_record_undo(&conn, None, key).unwrap();
+            crate::sequencer::db::exec_write(&conn, key, "01").unwrap();
+            db.commit_journal(
+                None,
+                executed.operation.operation_hash().as_deref(),
+                executed.id,
+            )
+            .unwrap();
+        };
+        super::insert(&q, level_start(2)).unwrap();
+        execute("/level");
+        super::insert(&q, dummy_op()).unwrap();
+        execute("/op");
+
+        super::roll_back(&q, 1).unwrap();
+        assert!(q.read().unwrap().is_rolling_back());
+        assert!(matches!(super::pop(&q).unwrap(), Some(Next::RolledBack)));
+        assert!(!q.read().unwrap().is_rolling_back());
+        assert_eq!(db.read_key("/op").unwrap(), None);
+        match super::pop(&q).unwrap() {
+            Some(Next::Operation(op)) => {
+                assert!(matches!(op.operation, WrappedOperation::FromNode(_)))
+            }
+            _ => panic!("should take the rolled back operation"),
+        }
+    }
+
     #[test]
     fn wrapped_operation_to_message() {
         let op = WrappedOperation::FromInbox {
@@ -0,0 +1,116 @@
+//! Pruning of the operation records of the sequencer.
+//!
+//! The receipt attestations and the states of the executed operations are kept in
+//! the runtime database, and their logs in the log database, forever by default.
+//! With a [`RetentionConfig`], a task removes them every [`PRUNE_INTERVAL`] once the
+//! operation is [`RetentionConfig::levels`] L1 levels old, except for the pinned
+//! operations, see [`Db::prune_receipts`]. The level of an operation is the level of
+//! the inbox it was read from, so operations injected in the node are only pruned
+//! once they are read back from the inbox.
+//!
+//! Receipts are part of the rollup storage and are only pruned by the kernel, under
+//! the retention window set by the injector, see
+//! [`jstz_proto::executor::receipt_retention`].
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+
+use super::db::Db;
+use crate::{
+    config::RetentionConfig, periodic::PeriodicTask, services::logs::db::Db as LogsDb,
+};
+
+/// Interval at which expired operation records are pruned
+pub const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+pub type Pruner = PeriodicTask;
+
+/// Spawns a task pruning the operation records of `runtime_db` and the logs of
+/// `logs_db` that expired under `config` every `interval`.
+pub fn spawn(
+    runtime_db: Db,
+    logs_db: LogsDb,
+    config: RetentionConfig,
+    interval: Duration,
+) -> Pruner {
+    PeriodicTask::spawn(interval, move || {
+        let (runtime_db, logs_db, config) =
+            (runtime_db.clone(), logs_db.clone(), config.clone());
+        async move {
+            match prune(&runtime_db, &logs_db, &config).await {
+                Ok(0) => {}
+                Ok(n) => debug!("pruned the records of {n} operations"),
+                Err(e) => warn!("failed to prune operation records: {e:?}"),
+            }
+        }
+    })
+}
+
+/// Prunes the operation records and the logs that expired under `config`. Returns
+/// the number of pruned operations.
+pub async fn prune(
+    runtime_db: &Db,
+    logs_db: &LogsDb,
+    config: &RetentionConfig,
+) -> Result<usize> {
+    let db = runtime_db.clone();
+    let RetentionConfig { levels, pinned } = config.clone();
+    let pruned = tokio::task::spawn_blocking(move || db.prune_receipts(levels, &pinned))
+        .await
+        .context("failed to wait for prune task")??;
+    // The logs of a request are recorded under the hash of its operation
+    logs_db
+        .prune_requests(&pruned)
+        .await
+        .context("failed to prune logs")?;
+    Ok(pruned.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, time::Duration};
+
+    use tempfile::NamedTempFile;
+
+    use super::spawn;
+    use crate::{
+        config::RetentionConfig,
+        sequencer::db::{Db, OperationState},
+        services::logs::db::Db as LogsDb,
+    };
+
+    #[tokio::test]
+    async fn prunes_expired_operations() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        for (hash, level) in [("op1", 1), ("op2", 10)] {
+            db.write(&format!("/jstz_receipt/{hash}"), "00").unwrap();
+            db.set_operation_state(hash, OperationState::Committed, Some(level))
+                .unwrap();
+        }
+
+        let mut pruner = spawn(
+            db.clone(),
+            LogsDb::init().await.unwrap(),
+            RetentionConfig {
+                levels: 5,
+                pinned: HashSet::new(),
+            },
+            Duration::from_millis(10),
+        );
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while db.operation_status("op1").unwrap().is_some() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("op1 should be pruned");
+        pruner.shut_down().await;
+
+        assert!(db.operation_status("op2").unwrap().is_some());
+        // Receipts are pruned by the kernel
+        assert!(db.key_exists("/jstz_receipt/op1").unwrap());
+    }
+}
@@ -1,13 +1,26 @@
 use std::path::PathBuf;
+#[cfg(feature = "simulation")]
+use std::sync::Arc;
 
 use anyhow::{anyhow, bail, Context};
 use jstz_core::kv::{Storage, Transaction};
+#[cfg(feature = "simulation")]
+use jstz_core::simulation::SimulationRequest;
 use jstz_crypto::{
     hash::Hash, public_key::PublicKey, smart_function_hash::SmartFunctionHash,
 };
 use jstz_kernel::inbox::Message;
-use jstz_proto::executor::{execute_internal_operation, execute_operation};
+#[cfg(feature = "simulation")]
+use jstz_proto::operation::SignedOperation;
+use jstz_proto::{
+    context::account::Address,
+    executor::{execute_internal_operation, execute_operation, receipt_retention},
+    operation::{Content, InternalOperation, Operation, RunFunction},
+    receipt::{DeployFunctionReceipt, Receipt, ReceiptContent, ReceiptResult},
+};
 use jstz_utils::KeyPair;
+#[cfg(feature = "simulation")]
+use parking_lot::Mutex;
 use tezos_smart_rollup::{
     prelude::{debug_msg, Runtime},
     storage::path::RefPath,
@@ -43,6 +56,8 @@ pub fn init_host(
             .context("failed to encode injector")?,
     )
     .context("failed to write injector to host store")?;
+    host.commit_journal(None, None)
+        .context("failed to commit host initialisation")?;
 
     Ok(host)
 }
@@ -55,7 +70,12 @@ fn read_injector(rt: &impl Runtime) -> Option<PublicKey> {
     Storage::get(rt, &INJECTOR_PATH).ok()?
 }
 
-pub async fn process_message(rt: &mut impl Runtime, op: Message) -> anyhow::Result<()> {
+/// Executes a message and commits its receipt and storage writes. Returns the
+/// receipt of the message.
+pub async fn process_message(
+    rt: &mut impl Runtime,
+    op: Message,
+) -> anyhow::Result<Receipt> {
     let ticketer = read_ticketer(rt).ok_or(anyhow!("Ticketer not found"))?;
     let injector = read_injector(rt).ok_or(anyhow!("Revealer not found"))?;
     let mut tx = Transaction::default();
@@ -67,6 +87,7 @@ pub async fn process_message(rt: &mut impl Runtime, op: Message) -> anyhow::Resu
         Message::Internal(op) => execute_internal_operation(rt, &mut tx, op).await,
     };
     receipt
+        .clone()
         .write(rt, &mut tx)
         .map_err(|e| anyhow!("failed to write receipt: {e}"))?;
 
@@ -75,7 +96,104 @@ pub async fn process_message(rt: &mut impl Runtime, op: Message) -> anyhow::Resu
         debug_msg!(rt, "{msg}\n");
         bail!(msg)
     }
-    Ok(())
+    Ok(receipt)
+}
+
+/// Addresses of the accounts that signed or are targeted by an operation: its signer
+/// and the smart function it calls
+pub fn operation_accounts(op: &Operation) -> Vec<String> {
+    let mut accounts = vec![op.source().to_string()];
+    if let Content::RunFunction(RunFunction { uri, .. }) = op.content() {
+        accounts.extend(
+            uri.host()
+                .and_then(|host| Address::from_base58(host).ok())
+                .map(|address| address.to_string()),
+        );
+    }
+    accounts
+}
+
+/// Addresses of the accounts that signed or are targeted by a message, see
+/// [`operation_accounts`], or that receive a deposit
+pub fn message_accounts(message: &Message) -> Vec<String> {
+    match message {
+        Message::External(op) => operation_accounts(op),
+        Message::Internal(InternalOperation::Deposit(deposit)) => {
+            vec![deposit.receiver.to_string()]
+        }
+        Message::Internal(InternalOperation::FaDeposit(deposit)) => {
+            let mut accounts = vec![deposit.receiver.to_string()];
+            accounts.extend(
+                deposit
+                    .proxy_smart_function
+                    .as_ref()
+                    .map(Address::to_string),
+            );
+            accounts
+        }
+    }
+}
+
+/// Addresses of the accounts targeted by an operation that are only known once it is
+/// executed, i.e. the deployed smart functions
+pub fn receipt_accounts(receipt: &Receipt) -> Vec<String> {
+    match &receipt.result {
+        ReceiptResult::Success(ReceiptContent::DeployFunction(
+            DeployFunctionReceipt { address },
+        )) => vec![address.to_string()],
+        _ => vec![],
+    }
+}
+
+/// Executes an operation against the state in `db` as a simulation, see
+/// [`SignedOperation::with_simulation_request`]. Returns the receipt and the debug
+/// log of the execution.
+#[cfg(feature = "simulation")]
+pub fn simulate(
+    db: Db,
+    preimage_dir: PathBuf,
+    op: SignedOperation,
+) -> anyhow::Result<(Receipt, String)> {
+    let debug_log = Arc::new(Mutex::new(Vec::<u8>::new()));
+    let mut host = Host::new(db, preimage_dir)
+        .read_only()
+        .with_debug_log(debug_log.clone());
+    let ticketer = read_ticketer(&host).ok_or(anyhow!("Ticketer not found"))?;
+    let injector = read_injector(&host).ok_or(anyhow!("Revealer not found"))?;
+    let tokio_rt = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .context("failed to build tokio runtime")?;
+
+    let op = op.with_simulation_request(SimulationRequest::new(0));
+    let mut tx = Transaction::default();
+    tx.begin();
+    let receipt = tokio_rt.block_on(execute_operation(
+        &mut host, &mut tx, op, &ticketer, &injector,
+    ));
+    let debug_log = String::from_utf8_lossy(&debug_log.lock()).into_owned();
+    Ok((receipt, debug_log))
+}
+
+/// Records the start of L1 level `level` and removes the receipts that expired, as
+/// the kernel does at the start of each level, see
+/// [`receipt_retention::start_level`]. Returns the number of removed receipts.
+pub fn start_level(rt: &mut impl Runtime, level: u32) -> anyhow::Result<usize> {
+    let mut tx = Transaction::default();
+    tx.begin();
+    let removed = receipt_retention::start_level(rt, &mut tx, level)
+        .map_err(|e| anyhow!("failed to remove expired receipts: {e}"))?;
+    tx.commit(rt)
+        .map_err(|e| anyhow!("failed to commit transaction: {e}"))?;
+    Ok(removed)
+}
+
+/// Writes the outbox transactions committed during the level, as the kernel
+/// does at the end of each level
+pub fn flush_outbox(rt: &mut impl Runtime) -> anyhow::Result<()> {
+    Transaction::default()
+        .flush_outbox(rt)
+        .map_err(|e| anyhow!("failed to flush outbox: {e}"))
 }
 
 #[cfg(test)]
@@ -89,7 +207,7 @@ mod tests {
     use axum::http::{HeaderMap, Method, StatusCode, Uri};
     use jstz_core::{host::HostRuntime, reveal_data::RevealData, BinEncodable};
     use jstz_crypto::{
-        hash::Hash,
+        hash::{Blake2b, Hash},
         public_key::PublicKey,
         secret_key::SecretKey,
         smart_function_hash::{Kt1Hash, SmartFunctionHash},
@@ -121,6 +239,8 @@ mod tests {
             public_key: jstz_mock::pk1(),
             nonce: Nonce(nonce),
             content,
+            max_fee: None,
+            priority_fee: None,
         };
         SignedOperation::new(jstz_mock::sk1().sign(operation.hash()).unwrap(), operation)
     }
@@ -163,7 +283,7 @@ mod tests {
 
         // This smart function has about 8k characters. The runtime is okay with it and simply
         // stores it in the data store, though this would not work with a rollup.
-        let deploy_op = dummy_op( 0, Content::DeployFunction(DeployFunction {function_code: format!("const handler = async () => {{ const s = \"{}\"; const myHeaders = new Headers();  myHeaders.append(\"X-JSTZ-TRANSFER\", \"1\"); return await fetch(new Request(\"jstz://tz1KqTpEZ7Yob7QbPE4Hy4Wo8fHG8LhKxZSx/\", {{ headers: myHeaders }})); }}; export default handler;", "a".repeat(8000)), account_credit: 1}));
+        let deploy_op = dummy_op( 0, Content::DeployFunction(DeployFunction {function_code: format!("const handler = async () => {{ const s = \"{}\"; const myHeaders = new Headers();  myHeaders.append(\"X-JSTZ-TRANSFER\", \"1\"); return await fetch(new Request(\"jstz://tz1KqTpEZ7Yob7QbPE4Hy4Wo8fHG8LhKxZSx/\", {{ headers: myHeaders }})); }}; export default handler;", "a".repeat(8000)), account_credit: 1, interface: None}));
 
         let call_op = dummy_op(
             1,
@@ -261,6 +381,35 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "simulation")]
+    #[test]
+    fn simulate() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        super::init_host(db.clone(), PathBuf::new(), &default_injector()).unwrap();
+        let deploy_op = dummy_op(
+            0,
+            Content::DeployFunction(DeployFunction {
+                function_code: "const handler = () => { console.log(\"deployed\"); return new Response(); }; export default handler;".to_string(),
+                account_credit: 0,
+                interface: None,
+            }),
+        );
+        let receipt_key = format!("/jstz_receipt/{}", deploy_op.hash());
+
+        let (receipt, _) =
+            super::simulate(db.clone(), PathBuf::new(), deploy_op).unwrap();
+        assert!(matches!(
+            receipt.result,
+            ReceiptResult::Success(ReceiptContent::DeployFunction(_))
+        ));
+        // neither the receipt nor the nonce of the source are written
+        assert!(!db.key_exists(&receipt_key).unwrap());
+        assert!(!db
+            .key_exists(&format!("/jstz_account/{}", jstz_mock::pkh1()))
+            .unwrap());
+    }
+
     #[tokio::test]
     async fn process_message_deposit() {
         // Using a slightly complicated scenario here to check if transaction works properly.
@@ -402,8 +551,11 @@ mod tests {
                         "a".repeat(5000))
                 ,
                 account_credit: 0,
+                interface: None,
             }
             .into(),
+            max_fee: None,
+            priority_fee: None,
         };
         let deploy_op_hash = hex::encode(deploy_fn.hash());
         let signature = injector_sk.sign(deploy_fn.hash()).unwrap();
@@ -431,6 +583,8 @@ mod tests {
                 original_op_hash: signed_deploy_fn.hash(),
             }
             .into(),
+            max_fee: None,
+            priority_fee: None,
         };
 
         let signature = injector_sk.sign(large_payload.hash()).unwrap();
@@ -468,6 +622,8 @@ mod tests {
                 body: HttpBody::empty(),
                 gas_limit: 550000,
             }),
+            max_fee: None,
+            priority_fee: None,
         };
         let sk = SecretKey::from_base58(
             "edsk4aBPdyDUC4V7RJ5dFTKDTpzMP2sGbAfXSRMPYGdFmXorj9RAYp",
@@ -496,4 +652,46 @@ mod tests {
                 headers: _
             })) if String::from_utf8(body.clone().unwrap()).unwrap() == "this is a big function"));
     }
+
+    #[test]
+    fn message_and_receipt_accounts() {
+        let sf = "KT1WjrJgoaEDHF2RmhhnpjjiwBkt4nA2MiMo";
+        let call_op = dummy_op(
+            0,
+            Content::RunFunction(RunFunction {
+                uri: Uri::try_from(format!("jstz://{sf}/path")).unwrap(),
+                method: Method::GET,
+                headers: HeaderMap::new(),
+                body: HttpBody::empty(),
+                gas_limit: 550000,
+            }),
+        );
+        assert_eq!(
+            message_accounts(&Message::External(call_op)),
+            [jstz_mock::pkh1().to_string(), sf.to_string()]
+        );
+
+        let deposit = Deposit {
+            inbox_id: InboxId {
+                l1_level: 1,
+                l1_message_id: 1,
+            },
+            amount: 10,
+            receiver: Address::User(jstz_mock::account2()),
+            source: jstz_mock::account1(),
+        };
+        assert_eq!(
+            message_accounts(&Message::Internal(InternalOperation::Deposit(deposit))),
+            [jstz_mock::account2().to_string()]
+        );
+
+        let address = SmartFunctionHash::from_base58(sf).unwrap();
+        let receipt = Receipt::new(
+            Blake2b::default(),
+            Ok(ReceiptContent::DeployFunction(DeployFunctionReceipt {
+                address: address.clone(),
+            })),
+        );
+        assert_eq!(receipt_accounts(&receipt), [address.to_string()]);
+    }
 }
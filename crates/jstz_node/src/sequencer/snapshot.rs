@@ -0,0 +1,251 @@
+//! Snapshots of the sequencer database, to bootstrap a sequencer node without
+//! replaying the inbox from genesis.
+//!
+//! A snapshot file starts with [`SNAPSHOT_MAGIC`], followed by the length of the
+//! JSON-encoded [`SnapshotHeader`] as a big-endian `u32`, the header, and the
+//! zstd-compressed copy of the database. The header holds the checksums of the
+//! compressed and decompressed database, which are verified on import, and the
+//! inbox checkpoint of the node, so that the imported node resumes reading the
+//! inbox after the blocks the database reflects.
+
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use jstz_crypto::hash::Blake2b;
+use rusqlite::{Connection, OpenFlags};
+use ruzstd::{
+    decoding::StreamingDecoder,
+    encoding::{compress_to_vec, CompressionLevel},
+};
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+
+/// Prefix of snapshot files
+pub const SNAPSHOT_MAGIC: &[u8; 8] = b"JSTZSNAP";
+/// Version of the snapshot format
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Header of a snapshot file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotHeader {
+    pub version: u32,
+    /// Size of the database, in bytes
+    pub size: u64,
+    /// Hex-encoded Blake2b digest of the database
+    pub checksum: String,
+    /// Hex-encoded Blake2b digest of the compressed database
+    pub compressed_checksum: String,
+    /// Contents of the inbox checkpoint file of the node, if any
+    pub inbox_checkpoint: Option<String>,
+}
+
+/// Writes a snapshot of the database at `db_path` to `out`, along with the inbox
+/// checkpoint at `inbox_checkpoint_path` if it exists. The database is only read,
+/// so the snapshot can be taken while the node is running.
+pub fn export(
+    db_path: &Path,
+    inbox_checkpoint_path: Option<&Path>,
+    out: &Path,
+) -> Result<SnapshotHeader> {
+    // The checkpoint is read first so that the database holds at least the
+    // operations of the blocks up to it
+    let inbox_checkpoint = match inbox_checkpoint_path {
+        Some(path) if path.exists() => {
+            Some(fs::read_to_string(path).context("failed to read inbox checkpoint")?)
+        }
+        _ => None,
+    };
+    let copy = NamedTempFile::new()?;
+    // VACUUM INTO refuses to overwrite a file
+    let copy_path = copy.into_temp_path();
+    fs::remove_file(&copy_path)?;
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .context("failed to open database")?;
+    conn.execute(
+        "VACUUM INTO ?1",
+        [copy_path.to_str().context("invalid temporary path")?],
+    )
+    .context("failed to copy database")?;
+    let db = fs::read(&copy_path)?;
+
+    let compressed = compress_to_vec(&db[..], CompressionLevel::Fastest);
+    let header = SnapshotHeader {
+        version: SNAPSHOT_VERSION,
+        size: db.len() as u64,
+        checksum: Blake2b::from(&db).to_string(),
+        compressed_checksum: Blake2b::from(&compressed).to_string(),
+        inbox_checkpoint,
+    };
+    let encoded_header = serde_json::to_vec(&header)?;
+    let mut bytes = Vec::with_capacity(
+        SNAPSHOT_MAGIC.len() + 4 + encoded_header.len() + compressed.len(),
+    );
+    bytes.extend_from_slice(SNAPSHOT_MAGIC);
+    bytes.extend_from_slice(&(encoded_header.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&encoded_header);
+    bytes.extend_from_slice(&compressed);
+    fs::write(out, bytes).context("failed to write snapshot")?;
+    Ok(header)
+}
+
+/// Restores the database of the snapshot at `snapshot` to `db_path`, and its inbox
+/// checkpoint to `inbox_checkpoint_path` if any, after verifying its checksums.
+/// Fails if a database already exists at `db_path`.
+pub fn import(
+    snapshot: &Path,
+    db_path: &Path,
+    inbox_checkpoint_path: Option<&Path>,
+) -> Result<SnapshotHeader> {
+    if fs::metadata(db_path).is_ok_and(|m| m.len() > 0) {
+        bail!("database {} already exists", db_path.display());
+    }
+    let bytes = fs::read(snapshot).context("failed to read snapshot")?;
+    let (header, compressed) = parse(&bytes)?;
+    if Blake2b::from(compressed).to_string() != header.compressed_checksum {
+        bail!("snapshot checksum mismatch");
+    }
+    let mut reader = compressed;
+    let mut db = Vec::new();
+    StreamingDecoder::new(&mut reader)
+        .context("failed to decompress snapshot")?
+        .take(header.size + 1)
+        .read_to_end(&mut db)
+        .context("failed to decompress snapshot")?;
+    if db.len() as u64 != header.size || Blake2b::from(&db).to_string() != header.checksum
+    {
+        bail!("snapshot database checksum mismatch");
+    }
+
+    write_atomically(db_path, &db).context("failed to write database")?;
+    if let (Some(path), Some(checkpoint)) =
+        (inbox_checkpoint_path, &header.inbox_checkpoint)
+    {
+        write_atomically(path, checkpoint.as_bytes())
+            .context("failed to write inbox checkpoint")?;
+    }
+    Ok(header)
+}
+
+/// Splits a snapshot into its header and compressed database
+fn parse(bytes: &[u8]) -> Result<(SnapshotHeader, &[u8])> {
+    let rest = bytes
+        .strip_prefix(SNAPSHOT_MAGIC.as_slice())
+        .context("not a snapshot")?;
+    let (len, rest) = rest
+        .split_first_chunk::<4>()
+        .context("truncated snapshot")?;
+    let len = u32::from_be_bytes(*len) as usize;
+    if rest.len() < len {
+        bail!("truncated snapshot");
+    }
+    let (header, compressed) = rest.split_at(len);
+    let header: SnapshotHeader =
+        serde_json::from_slice(header).context("invalid snapshot header")?;
+    if header.version != SNAPSHOT_VERSION {
+        bail!("unsupported snapshot version {}", header.version);
+    }
+    Ok((header, compressed))
+}
+
+/// Writes `contents` to a temporary file next to `path` and renames it over `path`
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    fs::create_dir_all(&dir)?;
+    let mut tmp = NamedTempFile::new_in(&dir)?;
+    std::io::Write::write_all(&mut tmp, contents)?;
+    tmp.persist(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use crate::sequencer::db::Db;
+
+    #[test]
+    fn export_then_import_restores_database_and_checkpoint() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("db.sqlite");
+        let checkpoint_path = dir.path().join("checkpoint.json");
+        let snapshot_path = dir.path().join("snapshot");
+        let db = Db::init(db_path.to_str()).unwrap();
+        db.write("/foo", "01").unwrap();
+        fs::write(&checkpoint_path, r#"{"block_level":42}"#).unwrap();
+
+        let header =
+            super::export(&db_path, Some(&checkpoint_path), &snapshot_path).unwrap();
+        assert_eq!(header.version, super::SNAPSHOT_VERSION);
+        assert_eq!(
+            header.inbox_checkpoint.as_deref(),
+            Some(r#"{"block_level":42}"#)
+        );
+
+        let imported_db_path = dir.path().join("imported/db.sqlite");
+        let imported_checkpoint_path = dir.path().join("imported/checkpoint.json");
+        let imported = super::import(
+            &snapshot_path,
+            &imported_db_path,
+            Some(&imported_checkpoint_path),
+        )
+        .unwrap();
+        assert_eq!(imported, header);
+        let imported_db = Db::init(imported_db_path.to_str()).unwrap();
+        assert_eq!(
+            imported_db.read_key("/foo").unwrap(),
+            Some("01".to_string())
+        );
+        assert_eq!(
+            fs::read_to_string(&imported_checkpoint_path).unwrap(),
+            r#"{"block_level":42}"#
+        );
+
+        // the imported database is never overwritten
+        assert_eq!(
+            super::import(&snapshot_path, &imported_db_path, None)
+                .unwrap_err()
+                .to_string(),
+            format!("database {} already exists", imported_db_path.display())
+        );
+    }
+
+    #[test]
+    fn import_rejects_corrupted_snapshot() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("db.sqlite");
+        let snapshot_path = dir.path().join("snapshot");
+        let db = Db::init(db_path.to_str()).unwrap();
+        db.write("/foo", "01").unwrap();
+        super::export(&db_path, None, &snapshot_path).unwrap();
+
+        let mut bytes = fs::read(&snapshot_path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        fs::write(&snapshot_path, &bytes).unwrap();
+        let imported_db_path = dir.path().join("imported.sqlite");
+        assert_eq!(
+            super::import(&snapshot_path, &imported_db_path, None)
+                .unwrap_err()
+                .to_string(),
+            "snapshot checksum mismatch"
+        );
+        assert!(!imported_db_path.exists());
+
+        fs::write(&snapshot_path, b"not a snapshot").unwrap();
+        assert_eq!(
+            super::import(&snapshot_path, &imported_db_path, None)
+                .unwrap_err()
+                .to_string(),
+            "not a snapshot"
+        );
+    }
+}
@@ -0,0 +1,394 @@
+//! Resource watchdog of the sequencer worker.
+//!
+//! The supervisor of the worker samples the memory and the CPU usage of the worker
+//! thread every [`WATCHDOG_INTERVAL`], see [`WorkerUsage`]. When they exceed the
+//! limits of the [`WatchdogConfig`] for [`WATCHDOG_STRIKES`] consecutive samples,
+//! the worker is recycled: it stops once the operation it executes is done,
+//! dropping its runtime, and a fresh worker is spawned in its place like a worker
+//! that died, which restores the state of the previous one. This reclaims the
+//! memory leaked by the runtime before the node gets killed for running out of
+//! memory.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    fs,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::config::WatchdogConfig;
+
+/// Interval at which the resource usage of the node is sampled
+pub const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+/// Number of consecutive samples over the limits after which the worker is recycled
+pub const WATCHDOG_STRIKES: u32 = 3;
+/// Minimum time between two recycles, so that the worker is not recycled over and
+/// over while the memory it released is being returned to the system
+pub const RECYCLE_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Clock ticks per second of the CPU times reported in `/proc`, which is fixed to
+/// 100 by the Linux ABI
+const CLOCK_TICKS_PER_SECOND: u64 = 100;
+
+/// Resource usage of the worker, as last sampled by the watchdog
+#[derive(Default)]
+pub struct WorkerStats {
+    /// Heap memory of the worker thread, in bytes, see [`WorkerUsage`]
+    pub memory_bytes: AtomicU64,
+    /// CPU usage of the worker thread since the previous sample, in percent of a
+    /// core
+    pub cpu_percent: AtomicU64,
+    /// Number of times the worker was recycled for exceeding its limits
+    pub recycles: AtomicU64,
+    /// Number of receipts the worker failed to sign, see [`Host::attest_receipt`]
+    ///
+    /// [`Host::attest_receipt`]: super::host::Host::attest_receipt
+    pub attestation_failures: AtomicU64,
+}
+
+thread_local! {
+    /// Heap memory counter of the worker running on the current thread, if any
+    static TRACKED_HEAP: Cell<*const AtomicI64> = const { Cell::new(std::ptr::null()) };
+}
+
+/// Global allocator of the node, which counts the heap memory allocated and freed
+/// by the worker thread, see [`WorkerUsage::track`]
+pub struct WorkerAllocator;
+
+impl WorkerAllocator {
+    fn count(delta: i64) {
+        let _ = TRACKED_HEAP.try_with(|heap| {
+            // Safety: the counter outlives the tracking, see `WorkerUsage::track`
+            if let Some(heap) = unsafe { heap.get().as_ref() } {
+                heap.fetch_add(delta, Ordering::Relaxed);
+            }
+        });
+    }
+}
+
+unsafe impl GlobalAlloc for WorkerAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::count(layout.size() as i64);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Self::count(layout.size() as i64);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Self::count(-(layout.size() as i64));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            Self::count(new_size as i64 - layout.size() as i64);
+        }
+        new_ptr
+    }
+}
+
+/// Resources used by the running worker thread, rather than by the whole node. The
+/// heap memory of the worker is the memory the worker thread allocated and did not
+/// free, counted by [`WorkerAllocator`], and its CPU time is read from
+/// `/proc/self/task`.
+#[derive(Default)]
+pub struct WorkerUsage {
+    heap_bytes: AtomicI64,
+    /// Linux thread id of the worker thread, 0 when no worker runs
+    thread_id: AtomicU64,
+}
+
+impl WorkerUsage {
+    /// Tracks the usage of the current thread until the returned guard is dropped.
+    /// Called by each worker thread when it starts, the memory of the previous
+    /// worker is not counted.
+    pub fn track(self: &Arc<Self>) -> UsageGuard {
+        self.heap_bytes.store(0, Ordering::Relaxed);
+        self.thread_id
+            .store(current_thread_id().unwrap_or_default(), Ordering::Relaxed);
+        TRACKED_HEAP.with(|heap| heap.set(&self.heap_bytes));
+        UsageGuard(self.clone())
+    }
+}
+
+/// Tracks the usage of the worker thread that holds it, see [`WorkerUsage::track`]
+pub struct UsageGuard(Arc<WorkerUsage>);
+
+impl Drop for UsageGuard {
+    fn drop(&mut self) {
+        TRACKED_HEAP.with(|heap| heap.set(std::ptr::null()));
+        self.0.thread_id.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Returns the Linux thread id of the current thread, from `/proc/thread-self`
+fn current_thread_id() -> Option<u64> {
+    fs::read_link("/proc/thread-self")
+        .ok()?
+        .file_name()?
+        .to_str()?
+        .parse()
+        .ok()
+}
+
+/// Resource usage of the worker thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ResourceUsage {
+    memory_bytes: u64,
+    /// CPU time spent by the worker thread since it started
+    cpu_time: Duration,
+}
+
+impl ResourceUsage {
+    /// Reads the resource usage of the running worker. Returns `None` when no worker
+    /// runs, or on systems without procfs.
+    fn read(usage: &WorkerUsage) -> Option<Self> {
+        let thread_id = usage.thread_id.load(Ordering::Relaxed);
+        if thread_id == 0 {
+            return None;
+        }
+        let stat =
+            fs::read_to_string(format!("/proc/self/task/{thread_id}/stat")).ok()?;
+        Some(Self {
+            memory_bytes: usage.heap_bytes.load(Ordering::Relaxed).max(0) as u64,
+            cpu_time: parse_cpu_time(&stat)?,
+        })
+    }
+}
+
+pub struct Watchdog {
+    config: WatchdogConfig,
+    stats: Arc<WorkerStats>,
+    usage: Arc<WorkerUsage>,
+    /// Time and CPU time of the previous sample
+    last_sample: Option<(Instant, Duration)>,
+    strikes: u32,
+    last_recycle: Option<Instant>,
+}
+
+impl Watchdog {
+    pub fn new(config: WatchdogConfig) -> Self {
+        Self {
+            config,
+            stats: Arc::default(),
+            usage: Arc::default(),
+            last_sample: None,
+            strikes: 0,
+            last_recycle: None,
+        }
+    }
+
+    pub fn stats(&self) -> Arc<WorkerStats> {
+        self.stats.clone()
+    }
+
+    /// Returns the usage the worker threads should track, see [`WorkerUsage::track`]
+    pub fn usage(&self) -> Arc<WorkerUsage> {
+        self.usage.clone()
+    }
+
+    /// Samples the resource usage of the worker if the last sample is older than
+    /// [`WATCHDOG_INTERVAL`], and returns true if the worker should be recycled
+    pub fn should_recycle(&mut self) -> bool {
+        let now = Instant::now();
+        if self
+            .last_sample
+            .is_some_and(|(at, _)| now.duration_since(at) < WATCHDOG_INTERVAL)
+        {
+            return false;
+        }
+        match ResourceUsage::read(&self.usage) {
+            Some(usage) => self.observe(now, usage),
+            None => false,
+        }
+    }
+
+    fn observe(&mut self, now: Instant, usage: ResourceUsage) -> bool {
+        let cpu_percent = self.last_sample.map(|(at, cpu_time)| {
+            let elapsed = now.duration_since(at).as_secs_f64();
+            usage.cpu_time.saturating_sub(cpu_time).as_secs_f64() / elapsed * 100.0
+        });
+        self.last_sample = Some((now, usage.cpu_time));
+        self.stats
+            .memory_bytes
+            .store(usage.memory_bytes, Ordering::Relaxed);
+        if let Some(cpu_percent) = cpu_percent {
+            self.stats
+                .cpu_percent
+                .store(cpu_percent.round() as u64, Ordering::Relaxed);
+        }
+
+        let over_memory = self
+            .config
+            .max_memory_mb
+            .is_some_and(|max| usage.memory_bytes > max.saturating_mul(1024 * 1024));
+        let over_cpu = cpu_percent
+            .zip(self.config.max_cpu_percent)
+            .is_some_and(|(cpu_percent, max)| cpu_percent > max);
+        if !(over_memory || over_cpu) {
+            self.strikes = 0;
+            return false;
+        }
+        self.strikes += 1;
+        let cooling_down = self
+            .last_recycle
+            .is_some_and(|at| now.duration_since(at) < RECYCLE_COOLDOWN);
+        if self.strikes < WATCHDOG_STRIKES || cooling_down {
+            return false;
+        }
+        self.strikes = 0;
+        // The CPU time of the next worker starts from zero
+        self.last_sample = None;
+        self.last_recycle = Some(now);
+        self.stats.recycles.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+}
+
+/// Parses the user and system CPU time from the contents of `/proc/<pid>/stat` or
+/// `/proc/<pid>/task/<tid>/stat`
+fn parse_cpu_time(stat: &str) -> Option<Duration> {
+    // The command name may contain spaces and parentheses, the fields after it
+    // start with the state, which is the third field
+    let mut fields = stat.get(stat.rfind(')')? + 1..)?.split_whitespace();
+    let utime = fields.nth(11)?.parse::<u64>().ok()?;
+    let stime = fields.next()?.parse::<u64>().ok()?;
+    Some(Duration::from_millis(
+        (utime + stime) * 1000 / CLOCK_TICKS_PER_SECOND,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::Ordering, Arc},
+        time::{Duration, Instant},
+    };
+
+    use super::{
+        ResourceUsage, Watchdog, WorkerUsage, RECYCLE_COOLDOWN, WATCHDOG_STRIKES,
+    };
+    use crate::config::WatchdogConfig;
+
+    const MB: u64 = 1024 * 1024;
+
+    #[test]
+    fn parse_proc() {
+        let stat = "42 (jstz (node)) S 1 42 42 0 -1 4194560 100 0 0 0 250 50 0 0 20 0";
+        assert_eq!(
+            super::parse_cpu_time(stat),
+            Some(Duration::from_millis(3000))
+        );
+        assert_eq!(super::parse_cpu_time("42 (jstz-node) S 1"), None);
+    }
+
+    #[test]
+    fn tracks_worker_thread() {
+        let usage = Arc::new(WorkerUsage::default());
+        assert_eq!(ResourceUsage::read(&usage), None);
+
+        let tracked = usage.clone();
+        let (sampled_tx, sampled_rx) = std::sync::mpsc::channel();
+        let (stop_tx, stop_rx) = std::sync::mpsc::channel::<()>();
+        let worker = std::thread::spawn(move || {
+            let _guard = tracked.track();
+            let buffer = vec![1u8; MB as usize];
+            sampled_tx.send(()).unwrap();
+            stop_rx.recv().unwrap();
+            drop(buffer);
+        });
+        sampled_rx.recv().unwrap();
+        // allocations of other threads are not counted
+        let other = vec![1u8; 4 * MB as usize];
+        #[cfg(target_os = "linux")]
+        {
+            let sample = ResourceUsage::read(&usage).unwrap();
+            assert!(sample.memory_bytes >= MB && sample.memory_bytes < 2 * MB);
+        }
+        drop(other);
+        stop_tx.send(()).unwrap();
+        worker.join().unwrap();
+        assert_eq!(ResourceUsage::read(&usage), None);
+    }
+
+    #[test]
+    fn recycles_after_consecutive_strikes() {
+        let mut watchdog = Watchdog::new(WatchdogConfig {
+            max_memory_mb: Some(100),
+            max_cpu_percent: Some(90.0),
+        });
+        let start = Instant::now();
+        let mut sample = |secs: u64, memory_mb: u64, cpu_ms: u64| {
+            watchdog.observe(
+                start + Duration::from_secs(secs),
+                ResourceUsage {
+                    memory_bytes: memory_mb * MB,
+                    cpu_time: Duration::from_millis(cpu_ms),
+                },
+            )
+        };
+
+        assert!(!sample(0, 50, 0));
+        // a single sample under the limits resets the strikes
+        assert!(!sample(1, 150, 100));
+        assert!(!sample(2, 150, 200));
+        assert!(!sample(3, 50, 300));
+        // CPU usage over the limit counts as well
+        assert!(!sample(4, 50, 1300));
+        assert!(!sample(5, 150, 1400));
+        assert!(sample(6, 50, 2400));
+        assert_eq!(watchdog.stats.recycles.load(Ordering::Relaxed), 1);
+        assert_eq!(watchdog.stats.memory_bytes.load(Ordering::Relaxed), 50 * MB);
+        assert_eq!(watchdog.stats.cpu_percent.load(Ordering::Relaxed), 100);
+
+        // the worker is not recycled again until the cooldown elapsed
+        let mut secs = 7;
+        for _ in 0..WATCHDOG_STRIKES {
+            assert!(!watchdog.observe(
+                start + Duration::from_secs(secs),
+                ResourceUsage {
+                    memory_bytes: 150 * MB,
+                    cpu_time: Duration::ZERO,
+                },
+            ));
+            secs += 1;
+        }
+        assert!(watchdog.observe(
+            start + Duration::from_secs(6) + RECYCLE_COOLDOWN,
+            ResourceUsage {
+                memory_bytes: 150 * MB,
+                cpu_time: Duration::ZERO,
+            },
+        ));
+        assert_eq!(watchdog.stats.recycles.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn no_limits() {
+        let mut watchdog = Watchdog::new(WatchdogConfig::default());
+        let start = Instant::now();
+        for secs in 0..10 {
+            assert!(!watchdog.observe(
+                start + Duration::from_secs(secs),
+                ResourceUsage {
+                    memory_bytes: u64::MAX,
+                    cpu_time: Duration::from_secs(secs * 4),
+                },
+            ));
+        }
+    }
+}
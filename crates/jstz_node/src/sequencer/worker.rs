@@ -1,45 +1,137 @@
 use crate::{
-    config::RuntimeEnv,
+    config::{RuntimeEnv, WatchdogConfig},
     sequencer::{
-        queue::WrappedOperation,
+        queue::{Next, QueuedOperation, WrappedOperation},
         riscv_pvm::JstzRiscvPvm,
-        runtime::{init_host, process_message},
+        runtime::{
+            flush_outbox, init_host, message_accounts, operation_accounts,
+            process_message, receipt_accounts, start_level,
+        },
     },
 };
 use std::{
     path::{Path, PathBuf},
     sync::{
-        atomic::AtomicU64,
-        mpsc::{channel, Sender, TryRecvError},
-        Arc, RwLock,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{channel, RecvTimeoutError, Sender, TryRecvError},
+        Arc, Condvar, Mutex, MutexGuard, PoisonError, RwLock,
     },
     thread::{spawn as spawn_thread, JoinHandle},
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::Context;
 use jstz_proto::operation::internal::InboxId;
 use jstz_utils::KeyPair;
 use log::{error, info, warn};
+use octez_riscv::stepper::StepperStatus;
 use tezos_crypto_rs::hash::SmartRollupHash;
 use tezos_smart_rollup::types::SmartRollupAddress;
+use tracing::{info_span, Instrument, Span};
 
-use super::{db::Db, queue::OperationQueue};
-use jstz_kernel::inbox::{encode_signed_operation, ParsedInboxMessage};
+use super::{
+    db::{Db, OperationState},
+    host::Host,
+    queue::{self, OperationQueue},
+    watchdog::{Watchdog, WorkerStats, WorkerUsage},
+};
+use jstz_kernel::inbox::{
+    encode_signed_operation, LevelInfo, Message, ParsedInboxMessage,
+};
 
-#[cfg(feature = "oracle")]
-use jstz_kernel::inbox::LevelInfo;
+/// Interval at which the supervisor checks that the worker thread is alive
+const SUPERVISOR_INTERVAL: Duration = Duration::from_millis(100);
+/// Delay before restarting a dead worker, doubled on each consecutive restart
+const RESTART_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const RESTART_BACKOFF_MAX: Duration = Duration::from_secs(30);
 
 pub struct Worker {
     thread_kill_sig: Sender<()>,
     inner: Option<JoinHandle<()>>,
     heartbeat: Arc<AtomicU64>,
+    paused: Arc<Pause>,
+    restarts: Arc<AtomicU64>,
+    stats: Arc<WorkerStats>,
 }
 
 impl Worker {
     pub fn heartbeat(&self) -> Arc<AtomicU64> {
         self.heartbeat.clone()
     }
+
+    /// Flag that stops the worker from taking operations from the queue while set.
+    /// Queued operations are kept and executed once the flag is cleared.
+    pub fn paused(&self) -> Arc<Pause> {
+        self.paused.clone()
+    }
+
+    /// Number of times the worker thread died and was restarted by the supervisor
+    pub fn restarts(&self) -> Arc<AtomicU64> {
+        self.restarts.clone()
+    }
+
+    /// Resource usage of the worker sampled by the watchdog of the supervisor, see
+    /// [`Watchdog`]
+    pub fn stats(&self) -> Arc<WorkerStats> {
+        self.stats.clone()
+    }
+
+    fn is_running(&self) -> bool {
+        self.inner.as_ref().is_some_and(|h| !h.is_finished())
+    }
+}
+
+/// Pause flag of the worker, see [`Worker::paused`]
+#[derive(Default)]
+pub struct Pause {
+    state: Mutex<PauseState>,
+    idle: Condvar,
+}
+
+#[derive(Default)]
+struct PauseState {
+    paused: bool,
+    /// Incremented whenever the flag is set
+    epoch: u64,
+    /// Last epoch in which the worker found no operation to take
+    #[cfg_attr(not(test), allow(dead_code))]
+    idle_epoch: Option<u64>,
+}
+
+impl Pause {
+    pub fn set(&self, paused: bool) {
+        let mut state = self.lock();
+        state.paused = paused;
+        state.epoch += 1;
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.lock().paused
+    }
+
+    /// Records that the worker found no operation to take in `epoch`, unless the
+    /// flag was set since
+    fn idle(&self, epoch: u64) {
+        let mut state = self.lock();
+        if state.epoch == epoch {
+            state.idle_epoch = Some(epoch);
+            self.idle.notify_all();
+        }
+    }
+
+    /// Blocks until the worker found no operation to take since the flag was last set
+    #[cfg(test)]
+    fn wait_idle(&self) {
+        let state = self.lock();
+        let _state = self
+            .idle
+            .wait_while(state, |state| state.idle_epoch != Some(state.epoch))
+            .unwrap();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PauseState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
 }
 
 impl Drop for Worker {
@@ -51,6 +143,9 @@ impl Drop for Worker {
     }
 }
 
+/// Spawns the worker under a supervisor that restarts the worker thread if it dies,
+/// e.g. because it panicked, see [`supervise`]. The worker is recycled when it
+/// exceeds the resource limits of `watchdog`, if any, see [`Watchdog`].
 #[allow(clippy::too_many_arguments)]
 pub fn spawn(
     queue: Arc<RwLock<OperationQueue>>,
@@ -60,15 +155,136 @@ pub fn spawn(
     preimage_dir: PathBuf,
     debug_log_path: Option<&Path>,
     runtime_env: &RuntimeEnv,
+    watchdog: Option<WatchdogConfig>,
     #[cfg(test)] on_exit: impl FnOnce() + Send + 'static,
+) -> anyhow::Result<Worker> {
+    let heartbeat = Arc::new(AtomicU64::default());
+    let paused = Arc::new(Pause::default());
+    let rollup_address = rollup_address.clone();
+    let injector = injector.clone();
+    let debug_log_path = debug_log_path.map(Path::to_path_buf);
+    let runtime_env = runtime_env.clone();
+    let (worker_heartbeat, worker_paused) = (heartbeat.clone(), paused.clone());
+    let watchdog = watchdog.map(Watchdog::new);
+    let usage = watchdog.as_ref().map(Watchdog::usage);
+    let stats = watchdog.as_ref().map(Watchdog::stats).unwrap_or_default();
+    supervise(
+        heartbeat,
+        paused,
+        watchdog,
+        move || {
+            // Roll back the writes of the operation a dead worker was executing
+            db.recover().context("failed to recover database")?;
+            spawn_worker(
+                queue.clone(),
+                db.clone(),
+                &rollup_address,
+                &injector,
+                preimage_dir.clone(),
+                debug_log_path.as_deref(),
+                &runtime_env,
+                worker_heartbeat.clone(),
+                worker_paused.clone(),
+                usage.clone(),
+                stats.clone(),
+            )
+        },
+        #[cfg(test)]
+        on_exit,
+    )
+}
+
+/// Runs the worker spawned by `spawn_worker` and spawns a new one with backoff
+/// whenever its thread dies, until the returned supervisor is dropped. The workers
+/// share the `heartbeat` and `paused` flags of the supervisor. A running worker is
+/// replaced right away when `watchdog` asks for it to be recycled, the same way as
+/// a dead one, so that the new worker restores its state.
+fn supervise(
+    heartbeat: Arc<AtomicU64>,
+    paused: Arc<Pause>,
+    mut watchdog: Option<Watchdog>,
+    mut spawn_worker: impl FnMut() -> anyhow::Result<Worker> + Send + 'static,
+    #[cfg(test)] on_exit: impl FnOnce() + Send + 'static,
+) -> anyhow::Result<Worker> {
+    let mut worker = Some(spawn_worker()?);
+    let (thread_kill_sig, rx) = channel();
+    let restarts = Arc::new(AtomicU64::default());
+    let stats = watchdog.as_ref().map(Watchdog::stats).unwrap_or_default();
+    Ok(Worker {
+        thread_kill_sig,
+        heartbeat,
+        paused,
+        restarts: restarts.clone(),
+        stats,
+        inner: Some(spawn_thread(move || {
+            let stopped = |timeout| {
+                !matches!(rx.recv_timeout(timeout), Err(RecvTimeoutError::Timeout))
+            };
+            let mut started_at = Instant::now();
+            let mut backoff = RESTART_BACKOFF_MIN;
+            while !stopped(SUPERVISOR_INTERVAL) {
+                if worker.as_ref().is_some_and(Worker::is_running) {
+                    if watchdog.as_mut().is_some_and(Watchdog::should_recycle) {
+                        warn!(
+                            "sequencer worker exceeded its resource limits, recycling it"
+                        );
+                        // Waits for the worker to finish the operation it executes
+                        worker = None;
+                        match spawn_worker() {
+                            Ok(w) => worker = Some(w),
+                            Err(e) => error!("failed to recycle sequencer worker: {e:?}"),
+                        }
+                    }
+                    continue;
+                }
+                worker = None;
+                if started_at.elapsed() > RESTART_BACKOFF_MAX {
+                    backoff = RESTART_BACKOFF_MIN;
+                }
+                error!("sequencer worker died, restarting it in {backoff:?}");
+                if stopped(backoff) {
+                    break;
+                }
+                restarts.fetch_add(1, Ordering::Relaxed);
+                started_at = Instant::now();
+                match spawn_worker() {
+                    Ok(w) => worker = Some(w),
+                    Err(e) => error!("failed to restart sequencer worker: {e:?}"),
+                }
+                backoff = (backoff * 2).min(RESTART_BACKOFF_MAX);
+            }
+            drop(worker);
+            #[cfg(test)]
+            on_exit();
+        })),
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_worker(
+    queue: Arc<RwLock<OperationQueue>>,
+    db: Db,
+    rollup_address: &SmartRollupHash,
+    injector: &KeyPair,
+    preimage_dir: PathBuf,
+    debug_log_path: Option<&Path>,
+    runtime_env: &RuntimeEnv,
+    heartbeat: Arc<AtomicU64>,
+    paused: Arc<Pause>,
+    usage: Option<Arc<WorkerUsage>>,
+    stats: Arc<WorkerStats>,
 ) -> anyhow::Result<Worker> {
     match runtime_env {
         RuntimeEnv::Riscv { kernel_path } => spawn_riscv_worker(
             queue,
+            db,
             preimage_dir,
             debug_log_path,
             kernel_path,
             rollup_address,
+            heartbeat,
+            paused,
+            usage,
         ),
         RuntimeEnv::Native => spawn_native_worker(
             queue,
@@ -76,23 +292,31 @@ pub fn spawn(
             injector,
             preimage_dir,
             debug_log_path,
-            #[cfg(test)]
-            on_exit,
+            heartbeat,
+            paused,
+            usage,
+            stats,
         ),
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn spawn_native_worker(
     queue: Arc<RwLock<OperationQueue>>,
     db: Db,
     injector: &KeyPair,
     preimage_dir: PathBuf,
     debug_log_path: Option<&Path>,
-    #[cfg(test)] on_exit: impl FnOnce() + Send + 'static,
+    heartbeat: Arc<AtomicU64>,
+    paused: Arc<Pause>,
+    usage: Option<Arc<WorkerUsage>>,
+    stats: Arc<WorkerStats>,
 ) -> anyhow::Result<Worker> {
     let (thread_kill_sig, rx) = channel();
-    let mut host_rt =
-        init_host(db, preimage_dir, injector).context("failed to init host")?;
+    let mut host_rt = init_host(db, preimage_dir, injector)
+        .context("failed to init host")?
+        .with_ordering_signer(injector.clone())
+        .with_worker_stats(stats);
     if let Some(p) = debug_log_path {
         host_rt = host_rt
             .with_debug_log_file(p)
@@ -102,58 +326,65 @@ fn spawn_native_worker(
         .enable_time()
         .build()
         .context("failed to build tokio runtime")?;
-    let heartbeat = Arc::new(AtomicU64::default());
     Ok(Worker {
         thread_kill_sig,
         heartbeat: heartbeat.clone(),
+        paused: paused.clone(),
+        restarts: Arc::default(),
+        stats: Arc::default(),
         inner: Some(spawn_thread(move || {
+            let _usage = usage.as_ref().map(WorkerUsage::track);
+
             #[cfg(feature = "oracle")]
-            run_event_loop(
-                tokio_rt,
-                host_rt,
-                queue,
-                heartbeat,
-                rx,
-                #[cfg(test)]
-                on_exit,
-            );
+            run_event_loop(tokio_rt, host_rt, queue, heartbeat, paused, rx);
 
             #[cfg(not(feature = "oracle"))]
             tokio_rt.block_on(async {
                 loop {
                     write_heartbeat(&heartbeat);
 
-                    let v = {
-                        match queue.write() {
-                            Ok(mut q) => q.pop(),
-                            Err(e) => {
-                                warn!("worker failed to read from queue: {e:?}");
-                                None
-                            }
-                        }
-                    };
+                    let v = next_operation(&queue, &paused);
 
                     match v {
-                        Some(op) => {
-                            if let ParsedInboxMessage::JstzMessage(message) =
-                                op.to_message()
-                            {
-                                if let Err(e) =
-                                    process_message(&mut host_rt, message).await
-                                {
-                                    warn!("error processing message: {e:?}");
+                        Some(Next::Operation(QueuedOperation { id, operation })) => {
+                            let level = operation.l1_level();
+                            match operation.to_message() {
+                                ParsedInboxMessage::JstzMessage(message) => {
+                                    let operation_hash = operation_hash(&message);
+                                    set_operation_state(
+                                        &host_rt,
+                                        operation_hash.as_deref(),
+                                        OperationState::Executing,
+                                    );
+                                    let span = execution_span(operation_hash.as_deref());
+                                    let state = execute_message(&mut host_rt, message)
+                                        .instrument(span)
+                                        .await;
+                                    commit_journal(&host_rt, operation_hash.clone(), id);
+                                    set_operation_state(
+                                        &host_rt,
+                                        operation_hash.as_deref(),
+                                        state,
+                                    );
                                 }
+                                ParsedInboxMessage::LevelInfo(LevelInfo::Start) => {
+                                    prune_receipts(&mut host_rt, level);
+                                    commit_journal(&host_rt, None, id);
+                                }
+                                ParsedInboxMessage::LevelInfo(LevelInfo::End) => {
+                                    if let Err(e) = flush_outbox(&mut host_rt) {
+                                        warn!("error flushing outbox: {e:?}");
+                                    }
+                                    commit_journal(&host_rt, None, id);
+                                }
+                                _ => commit_journal(&host_rt, None, id),
                             }
                         }
                         _ => tokio::time::sleep(Duration::from_millis(100)).await,
                     }
 
                     match rx.try_recv() {
-                        Ok(_) | Err(TryRecvError::Disconnected) => {
-                            #[cfg(test)]
-                            on_exit();
-                            break;
-                        }
+                        Ok(_) | Err(TryRecvError::Disconnected) => break,
                         Err(TryRecvError::Empty) => {}
                     }
                 }
@@ -166,68 +397,178 @@ fn spawn_native_worker(
 // See [jstz_kernel::riscv_kernel::run_event_loop]
 fn run_event_loop(
     tokio_rt: tokio::runtime::Runtime,
-    mut host: super::host::Host,
+    mut host: Host,
     queue: Arc<RwLock<OperationQueue>>,
     heartbeat: Arc<AtomicU64>,
+    paused: Arc<Pause>,
     rx: std::sync::mpsc::Receiver<()>,
-    #[cfg(test)] on_exit: impl FnOnce() + Send + 'static,
 ) {
     let local_set = tokio::task::LocalSet::new();
     jstz_proto::runtime::ProtocolContext::init_global(&mut host, 0).unwrap(); // unwrap to propagate error
     local_set.block_on(&tokio_rt, async {
+        let mut tasks = tokio::task::JoinSet::new();
         loop {
             write_heartbeat(&heartbeat);
-
-            let v = {
-                match queue.write() {
-                    Ok(mut q) => q.pop(),
-                    Err(e) => {
-                        warn!("worker failed to read from queue: {e:?}");
-                        None
+            // Tokio catches the panics of spawned tasks: panic the worker thread
+            // instead, so that the supervisor rolls back the writes of the operations
+            // and restarts the worker
+            while let Some(result) = tasks.try_join_next() {
+                if let Err(e) = result {
+                    if e.is_panic() {
+                        std::panic::resume_unwind(e.into_panic());
                     }
                 }
-            };
+            }
+
+            let v = next_operation(&queue, &paused);
 
             match v {
-                Some(wrapper) => match wrapper.to_message() {
-                    ParsedInboxMessage::JstzMessage(op) => {
-                        let mut hrt = host.clone();
-                        local_set.spawn_local(async move {
-                            if let Err(e) = process_message(&mut hrt, op).await {
-                                warn!("error processing message: {e:?}");
+                Some(Next::Operation(QueuedOperation { id, operation })) => {
+                    let level = operation.l1_level();
+                    match operation.to_message() {
+                        ParsedInboxMessage::JstzMessage(op) => {
+                            let mut hrt = host.with_new_journal();
+                            tasks.spawn_local(async move {
+                                let operation_hash = operation_hash(&op);
+                                set_operation_state(
+                                    &hrt,
+                                    operation_hash.as_deref(),
+                                    OperationState::Executing,
+                                );
+                                let span = execution_span(operation_hash.as_deref());
+                                let state =
+                                    execute_message(&mut hrt, op).instrument(span).await;
+                                commit_journal(&hrt, operation_hash.clone(), id);
+                                set_operation_state(
+                                    &hrt,
+                                    operation_hash.as_deref(),
+                                    state,
+                                );
+                            });
+                            tokio::task::yield_now().await;
+                            tokio::task::yield_now().await;
+                        }
+                        ParsedInboxMessage::LevelInfo(LevelInfo::Start) => {
+                            let mut hrt = host.with_new_journal();
+                            let ctx = jstz_proto::runtime::PROTOCOL_CONTEXT
+                                .get()
+                                .expect("Protocol context should be initialized");
+                            ctx.increment_level();
+                            prune_receipts(&mut hrt, level);
+                            let oracle_ctx = ctx.oracle();
+                            let mut oracle = oracle_ctx.lock();
+                            oracle.gc_timeout_requests(&mut hrt);
+                            commit_journal(&hrt, None, id);
+                            tokio::task::yield_now().await;
+                        }
+                        ParsedInboxMessage::LevelInfo(LevelInfo::End) => {
+                            let mut hrt = host.with_new_journal();
+                            if let Err(e) = flush_outbox(&mut hrt) {
+                                warn!("error flushing outbox: {e:?}");
                             }
-                        });
-                        tokio::task::yield_now().await;
-                        tokio::task::yield_now().await;
-                    }
-                    ParsedInboxMessage::LevelInfo(LevelInfo::Start) => {
-                        let mut hrt = host.clone();
-                        let ctx = jstz_proto::runtime::PROTOCOL_CONTEXT
-                            .get()
-                            .expect("Protocol context should be initialized");
-                        ctx.increment_level();
-                        let oracle_ctx = ctx.oracle();
-                        let mut oracle = oracle_ctx.lock();
-                        oracle.gc_timeout_requests(&mut hrt);
-                        tokio::task::yield_now().await;
+                            commit_journal(&hrt, None, id);
+                        }
+                        _ => commit_journal(&host, None, id),
                     }
-                    _ => (),
-                },
+                }
                 _ => tokio::time::sleep(Duration::from_millis(100)).await,
             };
 
             match rx.try_recv() {
-                Ok(_) | Err(TryRecvError::Disconnected) => {
-                    #[cfg(test)]
-                    on_exit();
-                    break;
-                }
+                Ok(_) | Err(TryRecvError::Disconnected) => break,
                 Err(TryRecvError::Empty) => {}
             }
         }
     })
 }
 
+/// Records the start of inbox level `level` and removes the expired receipts, see
+/// [`start_level`]
+fn prune_receipts(host: &mut Host, level: Option<u32>) {
+    let Some(level) = level else {
+        return;
+    };
+    match start_level(host, level) {
+        Ok(0) => {}
+        Ok(n) => info!("removed {n} expired receipts at level {level}"),
+        Err(e) => warn!("error removing expired receipts: {e:?}"),
+    }
+}
+
+/// Pops the next operation of the queue, unless the worker is paused, see
+/// [`queue::pop`]
+fn next_operation(queue: &RwLock<OperationQueue>, paused: &Pause) -> Option<Next> {
+    let (is_paused, epoch) = {
+        let state = paused.lock();
+        (state.paused, state.epoch)
+    };
+    let next = match is_paused {
+        true => None,
+        false => queue::pop(queue).unwrap_or_else(|e| {
+            warn!("worker failed to read from queue: {e:?}");
+            None
+        }),
+    };
+    if next.is_none() {
+        paused.idle(epoch);
+    }
+    next
+}
+
+fn operation_hash(message: &Message) -> Option<String> {
+    match message {
+        Message::External(op) => Some(op.hash().to_string()),
+        Message::Internal(_) => None,
+    }
+}
+
+/// Processes a message, signs its receipt (see [`Host::attest_receipt`]) and records
+/// it in the history of the accounts it touched, under the hash of its receipt.
+async fn execute_message(host: &mut Host, message: Message) -> OperationState {
+    let mut accounts = message_accounts(&message);
+    match process_message(host, message).await {
+        Ok(receipt) => {
+            accounts.extend(receipt_accounts(&receipt));
+            let hash = receipt.hash().to_string();
+            if let Err(e) = host.attest_receipt(&receipt) {
+                warn!("error attesting the receipt of operation {hash}: {e:?}");
+            }
+            if let Err(e) = host.index_account_operation(&hash, &accounts) {
+                warn!("error indexing the accounts of operation {hash}: {e:?}");
+            }
+            OperationState::Committed
+        }
+        Err(e) => {
+            warn!("error processing message: {e:?}");
+            OperationState::Failed
+        }
+    }
+}
+
+/// Span of the execution of a message, tagged with its operation hash if it is a
+/// signed operation
+fn execution_span(operation_hash: Option<&str>) -> Span {
+    info_span!("execute_operation", operation_hash)
+}
+
+/// Journals the writes of a processed message and removes it from the
+/// persisted queue, see [`Host::commit_journal`]
+fn commit_journal(host: &Host, operation_hash: Option<String>, dequeued: Option<u64>) {
+    if let Err(e) = host.commit_journal(operation_hash.as_deref(), dequeued) {
+        warn!("error committing journal: {e:?}");
+    }
+}
+
+/// Records the state of a processed message if it is a signed operation, see
+/// [`Host::set_operation_state`]
+fn set_operation_state(host: &Host, operation_hash: Option<&str>, state: OperationState) {
+    if let Some(operation_hash) = operation_hash {
+        if let Err(e) = host.set_operation_state(operation_hash, state) {
+            warn!("error recording the state of operation {operation_hash}: {e:?}");
+        }
+    }
+}
+
 pub(crate) fn write_heartbeat(heartbeat: &Arc<AtomicU64>) {
     let current_sec = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
@@ -237,46 +578,86 @@ pub(crate) fn write_heartbeat(heartbeat: &Arc<AtomicU64>) {
     heartbeat.store(current_sec, std::sync::atomic::Ordering::Relaxed);
 }
 
+#[allow(clippy::too_many_arguments)]
 fn spawn_riscv_worker(
     queue: Arc<RwLock<OperationQueue>>,
+    db: Db,
     preimages_dir: PathBuf,
     debug_log_path: Option<&Path>,
     kernel_path: &Path,
     rollup_address: &SmartRollupHash,
+    heartbeat: Arc<AtomicU64>,
+    paused: Arc<Pause>,
+    usage: Option<Arc<WorkerUsage>>,
 ) -> anyhow::Result<Worker> {
     let (thread_kill_sig, rx) = channel();
-    let heartbeat = Arc::new(AtomicU64::default());
     let debug_log_path = debug_log_path.map(|v| v.to_path_buf());
-    let mut pvm = JstzRiscvPvm::new(
-        kernel_path,
-        rollup_address,
-        0,
-        Some(preimages_dir.into_boxed_path()),
-        heartbeat.clone(),
-        debug_log_path,
-    )
-    .context("failed to launch RISCV PVM")?;
+    let kernel_path = kernel_path.to_path_buf();
+    let launch = {
+        let (db, rollup_address, heartbeat) =
+            (db.clone(), rollup_address.clone(), heartbeat.clone());
+        move || {
+            launch_riscv_pvm(
+                &db,
+                &kernel_path,
+                &rollup_address,
+                &preimages_dir,
+                &heartbeat,
+                debug_log_path.clone(),
+            )
+        }
+    };
+    let mut pvm = launch()?;
 
     let rollup_addr = SmartRollupAddress::new(rollup_address.clone());
     Ok(Worker {
         thread_kill_sig,
         heartbeat: heartbeat.clone(),
+        paused: paused.clone(),
+        restarts: Arc::default(),
+        stats: Arc::default(),
         inner: Some(spawn_thread(move || {
+            let _usage = usage.as_ref().map(WorkerUsage::track);
             info!("RISCV PVM launched");
 
             'worker: loop {
-                let operation = {
-                    match queue.write() {
-                        Ok(mut q) => q.pop(),
+                let operation = next_operation(&queue, &paused);
+                match operation {
+                    // The messages provided to the PVM after the rollback level were
+                    // forgotten, see `Db::roll_back`
+                    Some(Next::RolledBack) => match launch() {
+                        Ok(restored) => pvm = restored,
                         Err(e) => {
-                            warn!("worker failed to read from queue: {e:?}");
-                            None
+                            error!("worker failed to roll back the RISCV PVM: {e:?}");
+                            break 'worker;
                         }
-                    }
-                };
-                match operation {
-                    Some(op) => {
-                        let (inbox_id, encoded_message) = match op {
+                    },
+                    Some(Next::Operation(QueuedOperation { id, operation })) => {
+                        let operation_hash = operation.operation_hash();
+                        if let Some(hash) = &operation_hash {
+                            if let Err(e) = db.set_operation_state(
+                                hash,
+                                OperationState::Executing,
+                                None,
+                            ) {
+                                warn!("error recording the state of operation {hash}: {e:?}");
+                            }
+                        }
+                        // Receipts and storage are written by the kernel in the PVM, so
+                        // the accounts only known once the operation is executed are not
+                        // recorded
+                        let accounts = match &operation {
+                            WrappedOperation::FromInbox { message, .. } => {
+                                match &message.content {
+                                    ParsedInboxMessage::JstzMessage(message) => {
+                                        message_accounts(message)
+                                    }
+                                    _ => vec![],
+                                }
+                            }
+                            WrappedOperation::FromNode(op) => operation_accounts(op),
+                        };
+                        let (inbox_id, encoded_message) = match operation {
                             WrappedOperation::FromInbox {
                                 original_inbox_message,
                                 message,..
@@ -300,18 +681,58 @@ fn spawn_riscv_worker(
                                 )
                             }
                         };
-                        match encoded_message {
+                        let (state, input) = match encoded_message {
                             Ok(message) => {
-                                pvm.execute_operation(
-                                    inbox_id,
-                                    message,
-                                    std::ops::Bound::Unbounded,
-                                );
+                                let status = execution_span(operation_hash.as_deref())
+                                    .in_scope(|| {
+                                        pvm.execute_operation(
+                                            inbox_id,
+                                            message.clone(),
+                                            std::ops::Bound::Unbounded,
+                                        )
+                                    });
+                                let state = match status {
+                                    StepperStatus::Exited { success: true, .. } => {
+                                        OperationState::Committed
+                                    }
+                                    StepperStatus::Errored { cause, message, .. } => {
+                                        warn!("RISCV PVM failed to process message: {cause}: {message}");
+                                        OperationState::Failed
+                                    }
+                                    _ => {
+                                        warn!("RISCV PVM did not process message successfully");
+                                        OperationState::Failed
+                                    }
+                                };
+                                (state, Some(message))
                             }
                             Err(e) => {
                                 warn!("{e:?}");
+                                (OperationState::Failed, None)
                             }
                         };
+                        // Record the message provided to the PVM with the removal of the
+                        // operation from the queue, so that it is provided exactly once
+                        let committed = match &input {
+                            Some(message) => db.commit_riscv_input(inbox_id, message, id),
+                            None => db.commit_journal(None, None, id).map(|_| ()),
+                        };
+                        if let Err(e) = committed {
+                            warn!("error removing operation from queue: {e:?}");
+                        }
+                        if state == OperationState::Committed && !accounts.is_empty() {
+                            let hash = operation_hash
+                                .clone()
+                                .unwrap_or_else(|| inbox_id.hash().to_string());
+                            if let Err(e) = db.index_account_operation(&hash, &accounts) {
+                                warn!("error indexing the accounts of operation {hash}: {e:?}");
+                            }
+                        }
+                        if let Some(hash) = &operation_hash {
+                            if let Err(e) = db.set_operation_state(hash, state, None) {
+                                warn!("error recording the state of operation {hash}: {e:?}");
+                            }
+                        }
                     }
                     _ => std::thread::sleep(Duration::from_millis(100)),
                 };
@@ -327,17 +748,64 @@ fn spawn_riscv_worker(
     })
 }
 
+/// Launches a RISCV PVM and restores its state by providing the messages provided
+/// to the previous PVMs again, since it is not persisted, see [`Db::riscv_inputs`]
+fn launch_riscv_pvm(
+    db: &Db,
+    kernel_path: &Path,
+    rollup_address: &SmartRollupHash,
+    preimages_dir: &Path,
+    heartbeat: &Arc<AtomicU64>,
+    debug_log_path: Option<PathBuf>,
+) -> anyhow::Result<JstzRiscvPvm> {
+    let mut pvm = JstzRiscvPvm::new(
+        kernel_path,
+        rollup_address,
+        0,
+        Some(preimages_dir.into()),
+        heartbeat.clone(),
+        debug_log_path,
+    )
+    .context("failed to launch RISCV PVM")?;
+    let inputs = db
+        .riscv_inputs()
+        .context("failed to read RISCV PVM inputs")?;
+    if !inputs.is_empty() {
+        info!(
+            "restoring RISCV PVM state from {} inbox messages",
+            inputs.len()
+        );
+    }
+    for (inbox_id, message) in inputs {
+        if let StepperStatus::Errored { cause, message, .. } =
+            pvm.execute_operation(inbox_id, message, std::ops::Bound::Unbounded)
+        {
+            warn!("RISCV PVM failed to process restored message: {cause}: {message}");
+        }
+    }
+    Ok(pvm)
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
         io::Read,
         path::PathBuf,
-        sync::{Arc, Mutex, RwLock},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            mpsc::channel,
+            Arc, Mutex, RwLock,
+        },
         thread,
         time::Duration,
     };
 
-    use crate::sequencer::{db::Db, queue::OperationQueue, tests::dummy_op};
+    use super::Worker;
+    use crate::sequencer::{
+        db::{Db, OperationState},
+        queue::OperationQueue,
+        tests::dummy_op,
+    };
     use crate::{sequencer::inbox::test_utils::hash_of, test::default_injector};
     use jstz_mock::sr1_address;
     use tempfile::NamedTempFile;
@@ -355,6 +823,7 @@ mod tests {
             PathBuf::new(),
             None,
             &crate::config::RuntimeEnv::Native,
+            None,
             move || {
                 *cp.lock().unwrap() += 1;
             },
@@ -400,6 +869,7 @@ mod tests {
             PathBuf::new(),
             Some(log_file.path()),
             &crate::config::RuntimeEnv::Native,
+            None,
             move || {},
         );
 
@@ -409,6 +879,12 @@ mod tests {
         assert_eq!(wrapper.read().unwrap().len(), 0);
         // worker should process the message and the embedded runtime should produce a receipt
         assert!(db.key_exists(&receipt_key).unwrap());
+        // and the receipt should be signed by the injector
+        assert!(db.receipt_attestation(&hash_of(&op)).unwrap().is_some());
+        assert_eq!(
+            db.operation_status(&hash_of(&op)).unwrap().unwrap().state,
+            OperationState::Committed
+        );
         // check logs
         let mut buf = String::new();
         log_file.read_to_string(&mut buf).unwrap();
@@ -416,4 +892,84 @@ mod tests {
             buf.contains("Smart function deployed: KT1H4GfcBgx11M8ri6wwyDtbMUbqYfDQ7WmU")
         );
     }
+
+    #[test]
+    fn supervisor_restarts_dead_worker() {
+        let spawned = Arc::new(AtomicU64::default());
+        let count = spawned.clone();
+        let (spawned_tx, spawned_rx) = channel();
+        let exited = Arc::new(Mutex::new(false));
+        let cp = exited.clone();
+        let supervisor = super::supervise(
+            Arc::default(),
+            Arc::default(),
+            None,
+            move || {
+                let n = count.fetch_add(1, Ordering::Relaxed);
+                let _ = spawned_tx.send(n);
+                let (thread_kill_sig, rx) = channel::<()>();
+                Ok(Worker {
+                    thread_kill_sig,
+                    heartbeat: Arc::default(),
+                    paused: Arc::default(),
+                    restarts: Arc::default(),
+                    stats: Arc::default(),
+                    inner: Some(thread::spawn(move || {
+                        if n == 0 {
+                            panic!("worker died");
+                        }
+                        let _ = rx.recv();
+                    })),
+                })
+            },
+            move || {
+                *cp.lock().unwrap() = true;
+            },
+        )
+        .unwrap();
+
+        // the first worker dies right away and is restarted after the backoff
+        let timeout = super::RESTART_BACKOFF_MIN * 5;
+        assert_eq!(spawned_rx.recv_timeout(timeout), Ok(0));
+        assert_eq!(spawned_rx.recv_timeout(timeout), Ok(1));
+        assert_eq!(spawned.load(Ordering::Relaxed), 2);
+        assert_eq!(supervisor.restarts().load(Ordering::Relaxed), 1);
+
+        drop(supervisor);
+        assert!(*exited.lock().unwrap());
+        // the running worker is not restarted once the supervisor is dropped
+        assert_eq!(spawned.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn paused_worker_keeps_queue() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Db::init(Some(db_file.path().to_str().unwrap())).unwrap();
+        let wrapper = Arc::new(RwLock::new(OperationQueue::new(1)));
+        let worker = super::spawn(
+            wrapper.clone(),
+            db,
+            &sr1_address(),
+            &default_injector(),
+            PathBuf::new(),
+            None,
+            &crate::config::RuntimeEnv::Native,
+            None,
+            move || {},
+        )
+        .unwrap();
+
+        let paused = worker.paused();
+        paused.set(true);
+        wrapper.write().unwrap().insert(dummy_op()).unwrap();
+        // setting the flag again makes the worker check the queue after the insertion
+        paused.set(true);
+        paused.wait_idle();
+        // the operation stays in the queue while the worker is paused
+        assert_eq!(wrapper.read().unwrap().len(), 1);
+
+        paused.set(false);
+        paused.wait_idle();
+        assert_eq!(wrapper.read().unwrap().len(), 0);
+    }
 }
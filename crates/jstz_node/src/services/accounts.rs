@@ -1,27 +1,53 @@
-use anyhow::{anyhow, Context};
+use anyhow::anyhow;
 use axum::{
     extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
+use futures_util::future::try_join_all;
+use jstz_client::models::AccountBalance;
 use jstz_core::BinEncodable;
+use jstz_crypto::hash::Blake2b;
 use jstz_proto::{
-    context::account::{
-        Account, Nonce, SmartFunctionAccount, UserAccount, ACCOUNTS_PATH_PREFIX,
+    context::{
+        account::{
+            Account, Amount, Nonce, SmartFunctionAccount, UserAccount,
+            ACCOUNTS_PATH_PREFIX,
+        },
+        code::{CodeHash, CODE_PATH_PREFIX},
+        interface::{FunctionInterface, INTERFACES_PATH_PREFIX},
     },
     runtime::{KvValue, ParsedCode},
 };
-use serde::Deserialize;
-use utoipa::IntoParams;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 use super::{
     error::{ServiceError, ServiceResult},
     Service,
 };
-use crate::{utils::StoreWrapper, AppState, RunMode};
+use crate::{
+    sequencer::db::AccountOperations,
+    utils::{read_db, StorageProof, StoreWrapper},
+    AppState, RunMode,
+};
 
 const ACCOUNTS_TAG: &str = "Accounts";
 
+/// Maximum number of addresses of a batch balance query
+pub const MAX_BATCH_BALANCES: usize = 100;
+
+/// Number of operations of an account history page if no limit is given
+pub const DEFAULT_OPERATIONS_LIMIT: usize = 20;
+
+/// Maximum number of operations of an account history page
+pub const MAX_OPERATIONS_LIMIT: usize = 100;
+
+/// Header giving the L1 level of the state to read, like the `level` query parameter
+pub const X_JSTZ_LEVEL: &str = "X-Jstz-Level";
+
 fn construct_storage_key(address: &str, key: &Option<String>) -> String {
     match key {
         Some(value) if !value.is_empty() => format!("/jstz_kv/{address}/{value}"),
@@ -33,24 +59,173 @@ fn deserialize_account(data: &[u8]) -> ServiceResult<Account> {
     Ok(Account::decode(data).map_err(|_| anyhow!("Failed to deserialize account"))?)
 }
 
+/// Reads the code of the smart function account `account` with its encoding. Accounts
+/// persisted before code was stored by hash, and not written since, hold their code.
+async fn read_code(
+    store: &StoreWrapper,
+    account: &[u8],
+    code_hash: &CodeHash,
+    level: Option<u32>,
+) -> ServiceResult<(ParsedCode, Vec<u8>)> {
+    if let Some(code) = SmartFunctionAccount::decode_unversioned_code(account) {
+        return Ok((code, account.to_vec()));
+    }
+    let key = format!("{CODE_PATH_PREFIX}/{code_hash}");
+    let Some(value) = store.get_value_at(key, level).await? else {
+        return Err(anyhow!("Missing code {code_hash}").into());
+    };
+    let code = ParsedCode::decode(value.as_slice())
+        .map_err(|_| anyhow!("Failed to deserialize code"))?;
+    Ok((code, value))
+}
+
+/// An account as served by the node
+///
+/// Smart function accounts were served with their code before it was stored by
+/// hash. They are still served with it under `functionCode`, so that existing
+/// clients keep working.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = Account)]
+pub enum AccountView {
+    User(UserAccount),
+    SmartFunction(SmartFunctionAccountView),
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = SmartFunctionAccount)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartFunctionAccountView {
+    pub amount: Amount,
+    pub nonce: Nonce,
+    /// Hash of the code of the smart function, which is served by `/code/{hash}`
+    pub code_hash: CodeHash,
+    /// Code of the smart function. Deprecated: get the code by its `codeHash`.
+    pub function_code: ParsedCode,
+}
+
 fn construct_accounts_key(address: &str) -> String {
     format!("{ACCOUNTS_PATH_PREFIX}/{address}")
 }
 
+/// L1 level of the state to read: the `level` query parameter if given, the
+/// [`X_JSTZ_LEVEL`] header otherwise, or `None` to read the current state
+fn pinned_level(level: Option<u32>, headers: &HeaderMap) -> ServiceResult<Option<u32>> {
+    match (level, headers.get(X_JSTZ_LEVEL)) {
+        (None, Some(value)) => value
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .map(Some)
+            .ok_or_else(|| {
+                ServiceError::BadRequest(format!("Invalid {X_JSTZ_LEVEL} header"))
+            }),
+        (level, _) => Ok(level),
+    }
+}
+
+fn account_nonce(account: Account) -> Nonce {
+    match account {
+        Account::User(UserAccount { nonce, .. }) => nonce,
+        Account::SmartFunction(SmartFunctionAccount { nonce, .. }) => nonce,
+    }
+}
+
+fn account_balance(account: Account) -> u64 {
+    match account {
+        Account::User(UserAccount { amount, .. }) => amount,
+        Account::SmartFunction(SmartFunctionAccount { amount, .. }) => amount,
+    }
+}
+
+/// A response computed from a stored value, tagged with a weak ETag derived from the
+/// value. When the `If-None-Match` header of the request matches the tag, the body
+/// is replaced by `304 Not Modified`, so that clients polling the value only
+/// download it when it changes.
+enum Conditional<T> {
+    Modified { etag: String, body: T },
+    NotModified { etag: String },
+}
+
+impl<T> Conditional<T> {
+    fn new(headers: &HeaderMap, value: &[u8], body: T) -> Self {
+        let etag = format!("W/\"{}\"", Blake2b::from(value));
+        match if_none_match(headers, &etag) {
+            true => Conditional::NotModified { etag },
+            false => Conditional::Modified { etag, body },
+        }
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for Conditional<T> {
+    fn into_response(self) -> Response {
+        let (etag, mut res) = match self {
+            Conditional::Modified { etag, body } => (etag, body.into_response()),
+            Conditional::NotModified { etag } => {
+                (etag, StatusCode::NOT_MODIFIED.into_response())
+            }
+        };
+        if let Ok(etag) = HeaderValue::from_str(&etag) {
+            res.headers_mut().insert(header::ETAG, etag);
+        }
+        res
+    }
+}
+
+/// Returns true if the `If-None-Match` header matches `etag`, using the weak
+/// comparison
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || weak(tag) == weak(etag))
+}
+
 #[derive(Deserialize, IntoParams)]
 struct KvQuery {
     key: Option<String>,
 }
 
+#[derive(Deserialize, IntoParams)]
+struct KvSubkeysQuery {
+    key: Option<String>,
+    /// Only return the subkeys starting with this prefix
+    prefix: Option<String>,
+    /// Maximum number of subkeys to return, in lexicographic order
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize, IntoParams)]
+struct LevelQuery {
+    /// Read the state as of the end of this L1 level instead of the current state, so
+    /// that several requests read the same state. It can also be given with the
+    /// `X-Jstz-Level` header. Only available in sequencer mode or if the node syncs
+    /// its storage from the kernel, and once the next level started.
+    level: Option<u32>,
+}
+
+#[derive(Deserialize, IntoParams)]
+struct OperationsQuery {
+    /// Maximum number of operations to return, 20 by default and at most 100
+    limit: Option<usize>,
+    /// The `next_cursor` of the previous page
+    cursor: Option<u64>,
+}
+
 pub struct AccountsService;
 
 /// Get account
 #[utoipa::path(
     get,
     path = "/{address}",
+    params(LevelQuery),
     tag = ACCOUNTS_TAG,
     responses(
-        (status = 200, body = Account),
+        (status = 200, body = AccountView),
+        (status = 304),
+        (status = 400),
         (status = 404),
         (status = 500)
     )
@@ -65,7 +240,10 @@ async fn get_account(
         ..
     }): State<AppState>,
     Path(address): Path<String>,
-) -> ServiceResult<Json<Account>> {
+    Query(LevelQuery { level }): Query<LevelQuery>,
+    headers: HeaderMap,
+) -> ServiceResult<Conditional<Json<AccountView>>> {
+    let level = pinned_level(level, &headers)?;
     let key = format!("/jstz_account/{address}");
     let store = StoreWrapper::new(
         mode,
@@ -74,25 +252,54 @@ async fn get_account(
         runtime_db,
         storage_sync_db,
     );
-    let value = store.get_value(key).await?;
-    let account = match value {
-        Some(value) => deserialize_account(value.as_slice())?,
-        None => Err(ServiceError::NotFound)?,
+    let Some(value) = store.get_value_at(key, level).await? else {
+        return Err(ServiceError::NotFound);
+    };
+    let account = match deserialize_account(value.as_slice())? {
+        Account::User(account) => AccountView::User(account),
+        Account::SmartFunction(SmartFunctionAccount {
+            amount,
+            nonce,
+            code_hash,
+        }) => {
+            // The code is addressed by the hash in the account, so the tag of the
+            // account also tags the code
+            let (function_code, _) = read_code(&store, &value, &code_hash, level).await?;
+            AccountView::SmartFunction(SmartFunctionAccountView {
+                amount,
+                nonce,
+                code_hash,
+                function_code,
+            })
+        }
     };
-    Ok(Json(account))
+    Ok(Conditional::new(&headers, &value, Json(account)))
 }
 
 pub(crate) async fn get_account_nonce(
     store: &StoreWrapper,
     address: &str,
+    level: Option<u32>,
 ) -> ServiceResult<Option<Nonce>> {
     let key = construct_accounts_key(address);
-    let value = store.get_value(key).await?;
+    let value = store.get_value_at(key, level).await?;
     match value {
-        Some(value) => match deserialize_account(value.as_slice())? {
-            Account::User(UserAccount { nonce, .. }) => Ok(Some(nonce)),
-            Account::SmartFunction(SmartFunctionAccount { nonce, .. }) => Ok(Some(nonce)),
-        },
+        Some(value) => Ok(Some(account_nonce(deserialize_account(value.as_slice())?))),
+        None => Ok(None),
+    }
+}
+
+pub(crate) async fn get_account_balance(
+    store: &StoreWrapper,
+    address: &str,
+    level: Option<u32>,
+) -> ServiceResult<Option<u64>> {
+    let key = construct_accounts_key(address);
+    let value = store.get_value_at(key, level).await?;
+    match value {
+        Some(value) => Ok(Some(account_balance(deserialize_account(
+            value.as_slice(),
+        )?))),
         None => Ok(None),
     }
 }
@@ -101,9 +308,11 @@ pub(crate) async fn get_account_nonce(
 #[utoipa::path(
     get,
     path = "/{address}/nonce",
+    params(LevelQuery),
     tag = ACCOUNTS_TAG,
     responses(
         (status = 200, body = Nonce),
+        (status = 304),
         (status = 404),
         (status = 500)
     )
@@ -118,7 +327,10 @@ async fn get_nonce(
         ..
     }): State<AppState>,
     Path(address): Path<String>,
-) -> ServiceResult<Json<Nonce>> {
+    Query(LevelQuery { level }): Query<LevelQuery>,
+    headers: HeaderMap,
+) -> ServiceResult<Conditional<Json<Nonce>>> {
+    let level = pinned_level(level, &headers)?;
     let store = StoreWrapper::new(
         mode,
         storage_sync,
@@ -126,20 +338,23 @@ async fn get_nonce(
         runtime_db,
         storage_sync_db,
     );
-    let account_nonce = get_account_nonce(&store, &address).await?;
-    match account_nonce {
-        Some(nonce) => Ok(Json(nonce)),
-        None => Err(ServiceError::NotFound)?,
-    }
+    let key = construct_accounts_key(&address);
+    let Some(value) = store.get_value_at(key, level).await? else {
+        return Err(ServiceError::NotFound);
+    };
+    let nonce = account_nonce(deserialize_account(value.as_slice())?);
+    Ok(Conditional::new(&headers, &value, Json(nonce)))
 }
 
 /// Get code of an account
 #[utoipa::path(
     get,
     path = "/{address}/code",
+    params(LevelQuery),
     tag = ACCOUNTS_TAG,
     responses(
         (status = 200, body = ParsedCode),
+        (status = 304),
         (status = 400),
         (status = 404),
         (status = 500)
@@ -155,7 +370,10 @@ async fn get_code(
         ..
     }): State<AppState>,
     Path(address): Path<String>,
-) -> ServiceResult<Json<ParsedCode>> {
+    Query(LevelQuery { level }): Query<LevelQuery>,
+    headers: HeaderMap,
+) -> ServiceResult<Conditional<Json<ParsedCode>>> {
+    let level = pinned_level(level, &headers)?;
     let key = construct_accounts_key(&address);
     let store = StoreWrapper::new(
         mode,
@@ -164,32 +382,76 @@ async fn get_code(
         runtime_db,
         storage_sync_db,
     );
-    let value = store.get_value(key).await?;
-    let account_code = match value {
-        Some(value) => {
-            if let Account::SmartFunction(SmartFunctionAccount {
-                function_code, ..
-            }) = deserialize_account(value.as_slice())?
-            {
-                function_code
-            } else {
-                Err(ServiceError::BadRequest(
-                    "Account is not a smart function".to_string(),
-                ))?
-            }
-        }
-        None => Err(ServiceError::NotFound)?,
+    let Some(value) = store.get_value_at(key, level).await? else {
+        return Err(ServiceError::NotFound);
+    };
+    let Account::SmartFunction(SmartFunctionAccount { code_hash, .. }) =
+        deserialize_account(value.as_slice())?
+    else {
+        return Err(ServiceError::BadRequest(
+            "Account is not a smart function".to_string(),
+        ));
+    };
+    let (code, value) = read_code(&store, &value, &code_hash, level).await?;
+    Ok(Conditional::new(&headers, &value, Json(code)))
+}
+
+/// Get interface description of a smart function
+///
+/// Returns the routes of the smart function with the JSON schemas of their request
+/// and response bodies, as given when it was deployed. Returns 404 if the smart
+/// function was deployed without an interface.
+#[utoipa::path(
+    get,
+    path = "/{address}/interface",
+    params(LevelQuery),
+    tag = ACCOUNTS_TAG,
+    responses(
+        (status = 200, body = FunctionInterface),
+        (status = 304),
+        (status = 404),
+        (status = 500)
+    )
+)]
+async fn get_interface(
+    State(AppState {
+        mode,
+        rollup_client,
+        runtime_db,
+        storage_sync,
+        storage_sync_db,
+        ..
+    }): State<AppState>,
+    Path(address): Path<String>,
+    Query(LevelQuery { level }): Query<LevelQuery>,
+    headers: HeaderMap,
+) -> ServiceResult<Conditional<Json<FunctionInterface>>> {
+    let level = pinned_level(level, &headers)?;
+    let store = StoreWrapper::new(
+        mode,
+        storage_sync,
+        rollup_client,
+        runtime_db,
+        storage_sync_db,
+    );
+    let key = format!("{INTERFACES_PATH_PREFIX}/{address}");
+    let Some(value) = store.get_value_at(key, level).await? else {
+        return Err(ServiceError::NotFound);
     };
-    Ok(Json(account_code))
+    let interface = FunctionInterface::decode(value.as_slice())
+        .map_err(|_| anyhow!("Failed to deserialize interface"))?;
+    Ok(Conditional::new(&headers, &value, Json(interface)))
 }
 
 /// Get balance of an account
 #[utoipa::path(
     get,
     path = "/{address}/balance",
+    params(LevelQuery),
     tag = ACCOUNTS_TAG,
     responses(
         (status = 200, body = u64),
+        (status = 304),
         (status = 404),
         (status = 500)
     )
@@ -204,8 +466,10 @@ async fn get_balance(
         ..
     }): State<AppState>,
     Path(address): Path<String>,
-) -> ServiceResult<Json<u64>> {
-    let key = construct_accounts_key(&address);
+    Query(LevelQuery { level }): Query<LevelQuery>,
+    headers: HeaderMap,
+) -> ServiceResult<Conditional<Json<u64>>> {
+    let level = pinned_level(level, &headers)?;
     let store = StoreWrapper::new(
         mode,
         storage_sync,
@@ -213,15 +477,70 @@ async fn get_balance(
         runtime_db,
         storage_sync_db,
     );
-    let value = store.get_value(key).await?;
-    let account_balance = match value {
-        Some(value) => match deserialize_account(value.as_slice())? {
-            Account::User(UserAccount { amount, .. }) => amount,
-            Account::SmartFunction(SmartFunctionAccount { amount, .. }) => amount,
-        },
-        None => Err(ServiceError::NotFound)?,
+    let key = construct_accounts_key(&address);
+    let Some(value) = store.get_value_at(key, level).await? else {
+        return Err(ServiceError::NotFound);
     };
-    Ok(Json(account_balance))
+    let balance = account_balance(deserialize_account(value.as_slice())?);
+    Ok(Conditional::new(&headers, &value, Json(balance)))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BalancesRequest {
+    /// Addresses of the accounts, at most 100
+    pub addresses: Vec<String>,
+}
+
+/// Get balances of accounts
+///
+/// Get the balances of up to 100 accounts in one request. The balances are returned
+/// in the order of the requested addresses.
+#[utoipa::path(
+    post,
+    path = "/balances",
+    params(LevelQuery),
+    tag = ACCOUNTS_TAG,
+    responses(
+        (status = 200, body = Vec<AccountBalance>),
+        (status = 400),
+        (status = 500)
+    )
+)]
+async fn get_balances(
+    State(AppState {
+        mode,
+        rollup_client,
+        runtime_db,
+        storage_sync,
+        storage_sync_db,
+        ..
+    }): State<AppState>,
+    Query(LevelQuery { level }): Query<LevelQuery>,
+    headers: HeaderMap,
+    Json(BalancesRequest { addresses }): Json<BalancesRequest>,
+) -> ServiceResult<Json<Vec<AccountBalance>>> {
+    let level = pinned_level(level, &headers)?;
+    if addresses.len() > MAX_BATCH_BALANCES {
+        return Err(ServiceError::BadRequest(format!(
+            "At most {MAX_BATCH_BALANCES} addresses can be queried at once"
+        )));
+    }
+    let store = StoreWrapper::new(
+        mode,
+        storage_sync,
+        rollup_client,
+        runtime_db,
+        storage_sync_db,
+    );
+    let balances = try_join_all(addresses.into_iter().map(|address| {
+        let store = &store;
+        async move {
+            let balance = get_account_balance(store, &address, level).await?;
+            ServiceResult::Ok(AccountBalance { address, balance })
+        }
+    }))
+    .await?;
+    Ok(Json(balances))
 }
 
 /// Get KV value under a given key path
@@ -230,11 +549,13 @@ async fn get_balance(
 /// the empty key path will be used.
 #[utoipa::path(
     get,
-    params(KvQuery),
+    params(KvQuery, LevelQuery),
     path = "/{address}/kv",
     tag = ACCOUNTS_TAG,
     responses(
         (status = 200, body = KvValue),
+        (status = 304),
+        (status = 400),
         (status = 404),
         (status = 500)
     )
@@ -250,7 +571,10 @@ async fn get_kv_value(
     }): State<AppState>,
     Path(address): Path<String>,
     Query(KvQuery { key }): Query<KvQuery>,
-) -> ServiceResult<Json<KvValue>> {
+    Query(LevelQuery { level }): Query<LevelQuery>,
+    headers: HeaderMap,
+) -> ServiceResult<Conditional<Json<KvValue>>> {
+    let level = pinned_level(level, &headers)?;
     let key = construct_storage_key(&address, &key);
     let store = StoreWrapper::new(
         mode,
@@ -259,22 +583,57 @@ async fn get_kv_value(
         runtime_db,
         storage_sync_db,
     );
-    let value = store.get_value(key).await?;
-    let kv_value = match value {
-        Some(value) => KvValue::decode(value.as_slice())
-            .map_err(|_| anyhow!("Failed to deserialize kv value"))?,
-        None => Err(ServiceError::NotFound)?,
+    let Some(value) = store.get_value_at(key, level).await? else {
+        return Err(ServiceError::NotFound);
     };
-    Ok(Json(kv_value))
+    let kv_value = KvValue::decode(value.as_slice())
+        .map_err(|_| anyhow!("Failed to deserialize kv value"))?;
+    Ok(Conditional::new(&headers, &value, Json(kv_value)))
+}
+
+/// A KV value with the Merkle proof of its storage
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct KvValueProof {
+    pub value: KvValue,
+    pub proof: StorageProof,
+}
+
+/// Get KV value under a given key path with its Merkle proof
+///
+/// Reads the KV value from the rollup node with the proof of its storage, so that
+/// light clients can verify it against the state hash committed on L1. If `key` is
+/// not provided, the empty key path will be used.
+#[utoipa::path(
+    get,
+    params(KvQuery),
+    path = "/{address}/kv/proof",
+    tag = ACCOUNTS_TAG,
+    responses(
+        (status = 200, body = KvValueProof),
+        (status = 404),
+        (status = 500)
+    )
+)]
+async fn get_kv_value_proof(
+    State(AppState { rollup_client, .. }): State<AppState>,
+    Path(address): Path<String>,
+    Query(KvQuery { key }): Query<KvQuery>,
+) -> ServiceResult<Json<KvValueProof>> {
+    let key = construct_storage_key(&address, &key);
+    let (value, proof) = StorageProof::read(rollup_client.as_ref(), &key).await?;
+    let value = KvValue::decode(value.as_slice())
+        .map_err(|_| anyhow!("Failed to deserialize kv value"))?;
+    Ok(Json(KvValueProof { value, proof }))
 }
 
 /// Get array of KV subkeys under a given key path
 ///
 /// Get array of KV subkeys under a given key path for an account. If `key` is not provided,
-/// the empty key path will be used.
+/// the empty key path will be used. The subkeys can be filtered by `prefix` and are
+/// returned in lexicographic order, up to `limit` subkeys.
 #[utoipa::path(
     get,
-    params(KvQuery),
+    params(KvSubkeysQuery),
     path = "/{address}/kv/subkeys",
     tag = ACCOUNTS_TAG,
     responses(
@@ -288,26 +647,69 @@ async fn get_kv_subkeys(
         mode,
         rollup_client,
         runtime_db,
+        storage_sync,
+        storage_sync_db,
         ..
     }): State<AppState>,
     Path(address): Path<String>,
-    Query(KvQuery { key }): Query<KvQuery>,
+    Query(KvSubkeysQuery { key, prefix, limit }): Query<KvSubkeysQuery>,
 ) -> ServiceResult<Json<Vec<String>>> {
     let key = construct_storage_key(&address, &key);
-    let value = match mode {
-        RunMode::Default => rollup_client.get_subkeys(&key).await?,
-        RunMode::Sequencer { .. } => {
-            tokio::task::spawn_blocking(move || runtime_db.get_subkeys(&key))
-                .await
-                .context("failed to wait for db read task")?
-                .context("failed to read subkeys from db")?
-        }
-    };
-    let subkeys = match value {
-        Some(value) => value,
-        None => Err(ServiceError::NotFound)?,
-    };
-    Ok(Json(subkeys))
+    let store = StoreWrapper::new(
+        mode,
+        storage_sync,
+        rollup_client,
+        runtime_db,
+        storage_sync_db,
+    );
+    match store.get_subkeys(key, prefix, limit).await? {
+        Some(subkeys) => Ok(Json(subkeys)),
+        None => Err(ServiceError::NotFound),
+    }
+}
+
+/// Get the operation history of an account
+///
+/// Returns the hashes of the operations the account signed, was targeted by or was
+/// written by, e.g. in nested calls and transfers, most recent first. Pages are requested with the `next_cursor` of the previous page.
+/// Only available in sequencer mode.
+#[utoipa::path(
+    get,
+    params(OperationsQuery),
+    path = "/{address}/operations",
+    tag = ACCOUNTS_TAG,
+    responses(
+        (status = 200, body = AccountOperations),
+        (status = 400),
+        (status = 500)
+    )
+)]
+async fn get_operations(
+    State(AppState {
+        mode, runtime_db, ..
+    }): State<AppState>,
+    Path(address): Path<String>,
+    Query(OperationsQuery { limit, cursor }): Query<OperationsQuery>,
+) -> ServiceResult<Json<AccountOperations>> {
+    if !matches!(mode, RunMode::Sequencer { .. }) {
+        return Err(ServiceError::BadRequest(
+            "operation history is only available in sequencer mode".to_string(),
+        ));
+    }
+    let limit = limit.unwrap_or(DEFAULT_OPERATIONS_LIMIT);
+    if limit > MAX_OPERATIONS_LIMIT {
+        return Err(ServiceError::BadRequest(format!(
+            "At most {MAX_OPERATIONS_LIMIT} operations can be queried at once"
+        )));
+    }
+    if cursor.is_some_and(|cursor| i64::try_from(cursor).is_err()) {
+        return Err(ServiceError::BadRequest("Invalid cursor".to_string()));
+    }
+    let operations = read_db(&runtime_db, move |db| {
+        db.account_operations(&address, limit, cursor)
+    })
+    .await?;
+    Ok(Json(operations))
 }
 
 impl Service for AccountsService {
@@ -316,9 +718,13 @@ impl Service for AccountsService {
             .routes(routes!(get_account))
             .routes(routes!(get_nonce))
             .routes(routes!(get_code))
+            .routes(routes!(get_interface))
             .routes(routes!(get_balance))
+            .routes(routes!(get_balances))
             .routes(routes!(get_kv_value))
-            .routes(routes!(get_kv_subkeys));
+            .routes(routes!(get_kv_value_proof))
+            .routes(routes!(get_kv_subkeys))
+            .routes(routes!(get_operations));
 
         OpenApiRouter::new().nest("/accounts", routes)
     }
@@ -326,23 +732,38 @@ impl Service for AccountsService {
 
 #[cfg(test)]
 mod tests {
-    use std::{borrow::BorrowMut, convert::Infallible, path::PathBuf};
+    use std::{borrow::BorrowMut, convert::Infallible, path::PathBuf, sync::Arc};
 
-    use axum::{body::Body, extract::Request, response::Response, Router};
+    use axum::{body::Body, extract::Request, http::header, response::Response, Router};
     use jstz_core::BinEncodable;
     use jstz_mock::{kt1_account1, sr1_address};
     use jstz_proto::{
-        context::account::{Account, Nonce, SmartFunctionAccount, UserAccount},
+        context::{
+            account::{Account, Nonce, SmartFunctionAccount, UserAccount},
+            code::{CodeHash, CodeStore},
+            interface::FunctionInterface,
+        },
         runtime::{KvValue, ParsedCode},
     };
     use mockito::Matcher;
-    use octez::OctezRollupClient;
+    use octez::{
+        mock::{verify_proof, MockRollupRpc},
+        OctezRollupClient,
+    };
     use tempfile::NamedTempFile;
+    use tezos_smart_rollup::types::SmartRollupAddress;
     use tower::ServiceExt;
 
     use crate::{
         config::RuntimeEnv,
-        services::{accounts::AccountsService, Service},
+        sequencer::db::{self, AccountOperations},
+        services::{
+            accounts::{
+                AccountBalance, AccountsService, KvValueProof, MAX_BATCH_BALANCES,
+                MAX_OPERATIONS_LIMIT, X_JSTZ_LEVEL,
+            },
+            Service,
+        },
         utils::tests::mock_app_state,
         RunMode,
     };
@@ -427,7 +848,7 @@ mod tests {
         let smart_function_account = Account::SmartFunction(SmartFunctionAccount {
             amount: 0,
             nonce: Nonce(50),
-            function_code: ParsedCode::default(),
+            code_hash: CodeHash::default(),
         });
         let smart_function_hash = "KT19GXucGUitURBXXeEMMfqqhSQ5byt4P1zX";
         let mut server = mockito::Server::new_async().await;
@@ -465,33 +886,33 @@ mod tests {
         let store = super::StoreWrapper::new(
             RunMode::Default,
             false,
-            OctezRollupClient::new(server.url()),
+            Arc::new(OctezRollupClient::new(server.url())),
             crate::sequencer::db::Db::init(Some("")).unwrap(),
             crate::sequencer::db::Db::init(Some("")).unwrap(),
         );
-        assert!(super::get_account_nonce(&store, user_account_hash)
+        assert!(super::get_account_nonce(&store, user_account_hash, None)
             .await
             .is_ok_and(|v| matches!(v.unwrap(), Nonce(42))));
 
         let store = super::StoreWrapper::new(
             RunMode::Default,
             false,
-            OctezRollupClient::new(server.url()),
+            Arc::new(OctezRollupClient::new(server.url())),
             crate::sequencer::db::Db::init(Some("")).unwrap(),
             crate::sequencer::db::Db::init(Some("")).unwrap(),
         );
-        assert!(super::get_account_nonce(&store, smart_function_hash)
+        assert!(super::get_account_nonce(&store, smart_function_hash, None)
             .await
             .is_ok_and(|v| matches!(v.unwrap(), Nonce(50))));
 
         let store = super::StoreWrapper::new(
             RunMode::Default,
             false,
-            OctezRollupClient::new(server.url()),
+            Arc::new(OctezRollupClient::new(server.url())),
             crate::sequencer::db::Db::init(Some("")).unwrap(),
             crate::sequencer::db::Db::init(Some("")).unwrap(),
         );
-        assert!(super::get_account_nonce(&store, "bad_hash")
+        assert!(super::get_account_nonce(&store, "bad_hash", None)
             .await
             .is_ok_and(|v| v.is_none()));
 
@@ -553,17 +974,11 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn get_code_sequencer() {
-        let user_account = Account::User(UserAccount {
-            amount: 0,
-            nonce: Nonce(42),
-        });
-        let user_account_hash = "tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV";
-        let smart_function_account = Account::SmartFunction(SmartFunctionAccount {
-            amount: 0,
-            nonce: Nonce(50),
-            function_code: ParsedCode("dummy_code".to_string()),
-        });
+    async fn get_interface_sequencer() {
+        let interface = serde_json::from_value::<FunctionInterface>(serde_json::json!({
+            "routes": [{ "method": "GET", "path": "/counter", "response": { "type": "integer" } }]
+        }))
+        .unwrap();
         let smart_function_hash = "KT19GXucGUitURBXXeEMMfqqhSQ5byt4P1zX";
         let db_file = NamedTempFile::new().unwrap();
         let state = mock_app_state(
@@ -583,15 +998,8 @@ mod tests {
         state
             .runtime_db
             .write(
-                &format!("/jstz_account/{smart_function_hash}"),
-                &hex::encode(smart_function_account.encode().unwrap()),
-            )
-            .unwrap();
-        state
-            .runtime_db
-            .write(
-                &format!("/jstz_account/{user_account_hash}"),
-                &hex::encode(user_account.encode().unwrap()),
+                &format!("/jstz_interface/{smart_function_hash}"),
+                &hex::encode(interface.encode().unwrap()),
             )
             .unwrap();
 
@@ -600,49 +1008,40 @@ mod tests {
             .split_for_parts();
         let res = send_simple_get_request(
             router.borrow_mut(),
-            format!("/accounts/{smart_function_hash}/code"),
+            format!("/accounts/{smart_function_hash}/interface"),
         )
         .await
         .unwrap();
         assert_eq!(res.status(), 200);
         let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
-        let code = serde_json::from_slice::<String>(&bytes).unwrap();
-        assert_eq!(code, "dummy_code");
+        assert_eq!(
+            serde_json::from_slice::<FunctionInterface>(&bytes).unwrap(),
+            interface
+        );
 
-        // non-smart function address
+        // smart function deployed without an interface
         let res = send_simple_get_request(
             router.borrow_mut(),
-            format!("/accounts/{user_account_hash}/code"),
+            "/accounts/KT1CnQ9bFiw5wSQXvFRCnJcMCvbWgHHwcPza/interface",
         )
         .await
         .unwrap();
-        assert_eq!(res.status(), 400);
-        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
-        let error_message = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap();
-        assert_eq!(
-            error_message,
-            serde_json::json!({"error": "Account is not a smart function"})
-        );
-
-        // non-existent address
-        let res =
-            send_simple_get_request(router.borrow_mut(), "/accounts/bad_address/code")
-                .await
-                .unwrap();
         assert_eq!(res.status(), 404);
     }
 
     #[tokio::test]
-    async fn get_balance_sequencer() {
+    async fn get_code_sequencer() {
         let user_account = Account::User(UserAccount {
-            amount: 999,
+            amount: 0,
             nonce: Nonce(42),
         });
         let user_account_hash = "tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV";
+        let code = ParsedCode("dummy_code".to_string());
+        let code_hash = CodeStore::hash(&code);
         let smart_function_account = Account::SmartFunction(SmartFunctionAccount {
-            amount: 888,
+            amount: 0,
             nonce: Nonce(50),
-            function_code: ParsedCode::default(),
+            code_hash: code_hash.clone(),
         });
         let smart_function_hash = "KT19GXucGUitURBXXeEMMfqqhSQ5byt4P1zX";
         let db_file = NamedTempFile::new().unwrap();
@@ -667,6 +1066,13 @@ mod tests {
                 &hex::encode(smart_function_account.encode().unwrap()),
             )
             .unwrap();
+        state
+            .runtime_db
+            .write(
+                &format!("/jstz_code/{code_hash}"),
+                &hex::encode(code.encode().unwrap()),
+            )
+            .unwrap();
         state
             .runtime_db
             .write(
@@ -678,39 +1084,428 @@ mod tests {
         let (mut router, _) = AccountsService::router_with_openapi()
             .with_state(state)
             .split_for_parts();
-
-        // user account
         let res = send_simple_get_request(
             router.borrow_mut(),
-            format!("/accounts/{user_account_hash}/balance"),
+            format!("/accounts/{smart_function_hash}/code"),
         )
         .await
         .unwrap();
         assert_eq!(res.status(), 200);
         let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
-        let balance = serde_json::from_slice::<u64>(&bytes).unwrap();
-        assert_eq!(balance, 999);
+        let code = serde_json::from_slice::<String>(&bytes).unwrap();
+        assert_eq!(code, "dummy_code");
 
-        // smart function account
+        // accounts are served with their code hash and, for earlier clients, their code
         let res = send_simple_get_request(
             router.borrow_mut(),
-            format!("/accounts/{smart_function_hash}/balance"),
+            format!("/accounts/{smart_function_hash}"),
         )
         .await
         .unwrap();
         assert_eq!(res.status(), 200);
         let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
-        let balance = serde_json::from_slice::<u64>(&bytes).unwrap();
-        assert_eq!(balance, 888);
+        let account = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap();
+        assert_eq!(account["SmartFunction"]["functionCode"], "dummy_code");
+        assert!(matches!(
+            serde_json::from_value::<Account>(account).unwrap(),
+            Account::SmartFunction(SmartFunctionAccount { code_hash: hash, .. })
+                if hash == code_hash
+        ));
 
-        // non-existent address
-        let res =
-            send_simple_get_request(router.borrow_mut(), "/accounts/bad_addr/nonce")
-                .await
-                .unwrap();
+        // non-smart function address
+        let res = send_simple_get_request(
+            router.borrow_mut(),
+            format!("/accounts/{user_account_hash}/code"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), 400);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let error_message = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap();
+        assert_eq!(
+            error_message,
+            serde_json::json!({"error": "Account is not a smart function"})
+        );
+
+        // non-existent address
+        let res =
+            send_simple_get_request(router.borrow_mut(), "/accounts/bad_address/code")
+                .await
+                .unwrap();
         assert_eq!(res.status(), 404);
     }
 
+    #[tokio::test]
+    async fn get_code_of_unversioned_account() {
+        // Smart function account persisted with its code, before code was stored by
+        // hash: variant, amount, nonce and code
+        let mut unversioned = vec![1, 0, 0, 0];
+        unversioned.extend_from_slice(&7u64.to_le_bytes());
+        unversioned.extend_from_slice(&50u64.to_le_bytes());
+        unversioned.extend_from_slice(&10u64.to_le_bytes());
+        unversioned.extend_from_slice(b"dummy_code");
+        let smart_function_hash = "KT19GXucGUitURBXXeEMMfqqhSQ5byt4P1zX";
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::default(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: PathBuf::new(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: PathBuf::new(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        state
+            .runtime_db
+            .write(
+                &format!("/jstz_account/{smart_function_hash}"),
+                &hex::encode(&unversioned),
+            )
+            .unwrap();
+
+        let (mut router, _) = AccountsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let res = send_simple_get_request(
+            router.borrow_mut(),
+            format!("/accounts/{smart_function_hash}/code"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<String>(&bytes).unwrap(),
+            "dummy_code"
+        );
+
+        let res = send_simple_get_request(
+            router.borrow_mut(),
+            format!("/accounts/{smart_function_hash}"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let account = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap();
+        assert_eq!(account["SmartFunction"]["amount"], 7);
+        assert_eq!(account["SmartFunction"]["functionCode"], "dummy_code");
+    }
+
+    #[tokio::test]
+    async fn get_balance_sequencer() {
+        let user_account = Account::User(UserAccount {
+            amount: 999,
+            nonce: Nonce(42),
+        });
+        let user_account_hash = "tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV";
+        let smart_function_account = Account::SmartFunction(SmartFunctionAccount {
+            amount: 888,
+            nonce: Nonce(50),
+            code_hash: CodeHash::default(),
+        });
+        let smart_function_hash = "KT19GXucGUitURBXXeEMMfqqhSQ5byt4P1zX";
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::default(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: PathBuf::new(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: PathBuf::new(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        state
+            .runtime_db
+            .write(
+                &format!("/jstz_account/{smart_function_hash}"),
+                &hex::encode(smart_function_account.encode().unwrap()),
+            )
+            .unwrap();
+        state
+            .runtime_db
+            .write(
+                &format!("/jstz_account/{user_account_hash}"),
+                &hex::encode(user_account.encode().unwrap()),
+            )
+            .unwrap();
+
+        let (mut router, _) = AccountsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+
+        // user account
+        let res = send_simple_get_request(
+            router.borrow_mut(),
+            format!("/accounts/{user_account_hash}/balance"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let balance = serde_json::from_slice::<u64>(&bytes).unwrap();
+        assert_eq!(balance, 999);
+
+        // smart function account
+        let res = send_simple_get_request(
+            router.borrow_mut(),
+            format!("/accounts/{smart_function_hash}/balance"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let balance = serde_json::from_slice::<u64>(&bytes).unwrap();
+        assert_eq!(balance, 888);
+
+        // non-existent address
+        let res =
+            send_simple_get_request(router.borrow_mut(), "/accounts/bad_addr/nonce")
+                .await
+                .unwrap();
+        assert_eq!(res.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn get_balance_conditional() {
+        let addr = "tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV";
+        let write_account = |db: &crate::sequencer::db::Db, amount| {
+            let account = Account::User(UserAccount {
+                amount,
+                nonce: Nonce(42),
+            });
+            db.write(
+                &format!("/jstz_account/{addr}"),
+                &hex::encode(account.encode().unwrap()),
+            )
+            .unwrap();
+        };
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::default(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: PathBuf::new(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: PathBuf::new(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        let db = state.runtime_db.clone();
+        write_account(&db, 999);
+        let (router, _) = AccountsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let get = |if_none_match: Option<&str>| {
+            let mut request = Request::get(format!("/accounts/{addr}/balance"));
+            if let Some(etag) = if_none_match {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            router.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let res = get(None).await.unwrap();
+        assert_eq!(res.status(), 200);
+        let etag = res.headers()[header::ETAG].to_str().unwrap().to_string();
+        assert!(etag.starts_with("W/\""));
+
+        // unchanged balance
+        let res = get(Some(&etag)).await.unwrap();
+        assert_eq!(res.status(), 304);
+        assert_eq!(res.headers()[header::ETAG], etag.as_str());
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        assert!(bytes.is_empty());
+        let res = get(Some(&format!("\"foo\", {}", etag.trim_start_matches("W/"))))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 304);
+
+        // updated balance
+        write_account(&db, 1000);
+        let res = get(Some(&etag)).await.unwrap();
+        assert_eq!(res.status(), 200);
+        assert_ne!(res.headers()[header::ETAG], etag.as_str());
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        assert_eq!(serde_json::from_slice::<u64>(&bytes).unwrap(), 1000);
+    }
+
+    #[tokio::test]
+    async fn get_balances_sequencer() {
+        let user_account = Account::User(UserAccount {
+            amount: 999,
+            nonce: Nonce(42),
+        });
+        let user_account_hash = "tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV";
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::default(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: PathBuf::new(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: PathBuf::new(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        state
+            .runtime_db
+            .write(
+                &format!("/jstz_account/{user_account_hash}"),
+                &hex::encode(user_account.encode().unwrap()),
+            )
+            .unwrap();
+
+        let (router, _) = AccountsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let send_request = |addresses: Vec<String>| {
+            router.clone().oneshot(
+                Request::builder()
+                    .uri("/accounts/balances")
+                    .method("POST")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(
+                        serde_json::json!({ "addresses": addresses }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+        };
+
+        let res =
+            send_request(vec!["bad_addr".to_string(), user_account_hash.to_string()])
+                .await
+                .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let balances = serde_json::from_slice::<Vec<AccountBalance>>(&bytes).unwrap();
+        assert_eq!(
+            balances
+                .into_iter()
+                .map(|b| (b.address, b.balance))
+                .collect::<Vec<_>>(),
+            vec![
+                ("bad_addr".to_string(), None),
+                (user_account_hash.to_string(), Some(999))
+            ]
+        );
+
+        // too many addresses
+        let res =
+            send_request(vec![user_account_hash.to_string(); MAX_BATCH_BALANCES + 1])
+                .await
+                .unwrap();
+        assert_eq!(res.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn get_state_at_level_sequencer() {
+        let addr = "tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV";
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::default(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: PathBuf::new(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: PathBuf::new(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        let conn = state.runtime_db.connection().unwrap();
+        for (level, amount) in [(5, 100), (6, 200)] {
+            let account = Account::User(UserAccount {
+                amount,
+                nonce: Nonce(0),
+            });
+            db::exec_start_level(&conn, level).unwrap();
+            db::exec_write_at(
+                &conn,
+                level,
+                &format!("/jstz_account/{addr}"),
+                &hex::encode(account.encode().unwrap()),
+            )
+            .unwrap();
+        }
+        // level 6 is over once level 7 starts
+        db::exec_start_level(&conn, 7).unwrap();
+
+        let (router, _) = AccountsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let get_balance = |uri: String, level: Option<&str>| {
+            let mut request = Request::builder().uri(uri).method("GET");
+            if let Some(level) = level {
+                request = request.header(X_JSTZ_LEVEL, level);
+            }
+            let router = router.clone();
+            async move {
+                let res = router
+                    .oneshot(request.body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                let status = res.status().as_u16();
+                let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+                (status, serde_json::from_slice::<u64>(&bytes).ok())
+            }
+        };
+
+        let uri = format!("/accounts/{addr}/balance");
+        assert_eq!(get_balance(uri.clone(), None).await, (200, Some(200)));
+        assert_eq!(
+            get_balance(format!("{uri}?level=5"), None).await,
+            (200, Some(100))
+        );
+        assert_eq!(get_balance(uri.clone(), Some("5")).await, (200, Some(100)));
+        // the query parameter takes precedence over the header
+        assert_eq!(
+            get_balance(format!("{uri}?level=6"), Some("5")).await,
+            (200, Some(200))
+        );
+        assert_eq!(get_balance(uri.clone(), Some("five")).await.0, 400);
+        assert_eq!(get_balance(uri, Some("7")).await.0, 400);
+
+        let res = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/accounts/balances")
+                    .method("POST")
+                    .header(X_JSTZ_LEVEL, "5")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(
+                        serde_json::json!({ "addresses": [addr] }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let balances = serde_json::from_slice::<Vec<AccountBalance>>(&bytes).unwrap();
+        assert_eq!(balances[0].balance, Some(100));
+    }
+
     #[tokio::test]
     async fn get_kv_value_sequencer() {
         let address = "tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV";
@@ -827,6 +1622,54 @@ mod tests {
         assert_eq!(res.status(), 404);
     }
 
+    #[tokio::test]
+    async fn get_kv_value_proof() {
+        let address = "tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV";
+        let rpc = Arc::new(MockRollupRpc::new(SmartRollupAddress::new(sr1_address())));
+        let encoded = KvValue(serde_json::json!({"bar": "bar!"}))
+            .encode()
+            .unwrap();
+        rpc.insert(&format!("/jstz_kv/{address}/foo"), encoded.clone());
+        let db_file = NamedTempFile::new().unwrap();
+        let mut state = mock_app_state(
+            "",
+            PathBuf::new(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Default,
+        )
+        .await;
+        state.rollup_client = rpc;
+        let (mut router, _) = AccountsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+
+        let res = send_simple_get_request(
+            router.borrow_mut(),
+            format!("/accounts/{address}/kv/proof?key=foo"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let KvValueProof { value, proof } = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(value.0, serde_json::json!({"bar": "bar!"}));
+        assert_eq!(proof.value, hex::encode(&encoded));
+        assert!(verify_proof(
+            &proof.state_hash,
+            &format!("/jstz_kv/{address}/foo"),
+            &encoded,
+            &hex::decode(proof.proof).unwrap()
+        ));
+
+        let res = send_simple_get_request(
+            router.borrow_mut(),
+            format!("/accounts/{address}/kv/proof?key=missing"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), 404);
+    }
+
     #[tokio::test]
     async fn get_kv_subkeys_sequencer() {
         let address = "tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV";
@@ -921,6 +1764,18 @@ mod tests {
         let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
         let keys = serde_json::from_slice::<Vec<String>>(&bytes).unwrap();
         assert_eq!(keys, ["d"]);
+
+        // a, filtered by prefix and limited
+        let res = send_simple_get_request(
+            router.borrow_mut(),
+            format!("/accounts/{address}/kv/subkeys?key=a&prefix=b&limit=2"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let keys = serde_json::from_slice::<Vec<String>>(&bytes).unwrap();
+        assert_eq!(keys, ["b1", "b2"]);
     }
 
     #[tokio::test]
@@ -955,4 +1810,101 @@ mod tests {
 
         mock_subkey_endpoint_ok.assert();
     }
+
+    #[tokio::test]
+    async fn get_operations_sequencer() {
+        let address = "tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV";
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::new(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: PathBuf::new(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: PathBuf::new(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        for hash in ["op1", "op2", "op3"] {
+            state
+                .runtime_db
+                .index_account_operation(hash, &[address.to_string()])
+                .unwrap();
+        }
+        let (mut router, _) = AccountsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        async fn get_page(router: &mut Router, uri: String) -> AccountOperations {
+            let res = send_simple_get_request(router, uri).await.unwrap();
+            assert_eq!(res.status(), 200);
+            let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+            serde_json::from_slice::<AccountOperations>(&bytes).unwrap()
+        }
+
+        let page = get_page(&mut router, format!("/accounts/{address}/operations")).await;
+        assert_eq!(page.operations, ["op3", "op2", "op1"]);
+        assert_eq!(page.next_cursor, None);
+
+        let page = get_page(
+            &mut router,
+            format!("/accounts/{address}/operations?limit=2"),
+        )
+        .await;
+        assert_eq!(page.operations, ["op3", "op2"]);
+        let cursor = page.next_cursor.unwrap();
+        let page = get_page(
+            &mut router,
+            format!("/accounts/{address}/operations?limit=2&cursor={cursor}"),
+        )
+        .await;
+        assert_eq!(page.operations, ["op1"]);
+        assert_eq!(page.next_cursor, None);
+
+        let page = get_page(
+            &mut router,
+            "/accounts/tz1ZvXcDBWMAys2ro6kJXrgiWUcUF8RvCHYy/operations".to_string(),
+        )
+        .await;
+        assert!(page.operations.is_empty());
+
+        // limit too large
+        let res = send_simple_get_request(
+            router.borrow_mut(),
+            format!(
+                "/accounts/{address}/operations?limit={}",
+                MAX_OPERATIONS_LIMIT + 1
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), 400);
+
+        // cursor out of range
+        let res = send_simple_get_request(
+            router.borrow_mut(),
+            format!("/accounts/{address}/operations?cursor={}", u64::MAX),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn get_operations_default() {
+        let state = mock_app_state("", PathBuf::new(), "", RunMode::Default).await;
+        let (mut router, _) = AccountsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let res = send_simple_get_request(
+            router.borrow_mut(),
+            "/accounts/tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV/operations",
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), 400);
+    }
 }
@@ -0,0 +1,140 @@
+use anyhow::anyhow;
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use jstz_core::BinEncodable;
+use jstz_proto::{
+    context::code::{CodeHash, CODE_PATH_PREFIX},
+    runtime::ParsedCode,
+};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use super::{
+    error::{ServiceError, ServiceResult},
+    Service,
+};
+use crate::{utils::StoreWrapper, AppState};
+
+const CODE_TAG: &str = "Code";
+
+pub struct CodeService;
+
+/// Get code by hash
+///
+/// Returns the code stored under `hash`, the hex-encoded Blake2b hash of its source
+/// referenced by the accounts of the smart functions deployed with it.
+#[utoipa::path(
+    get,
+    path = "/{hash}",
+    tag = CODE_TAG,
+    responses(
+        (status = 200, body = ParsedCode),
+        (status = 400),
+        (status = 404),
+        (status = 500)
+    )
+)]
+async fn get_code_by_hash(
+    State(AppState {
+        mode,
+        rollup_client,
+        runtime_db,
+        storage_sync,
+        storage_sync_db,
+        ..
+    }): State<AppState>,
+    Path(hash): Path<String>,
+) -> ServiceResult<Json<ParsedCode>> {
+    let hash = CodeHash::try_parse(hash)
+        .map_err(|_| ServiceError::BadRequest("Invalid code hash".to_string()))?;
+    let store = StoreWrapper::new(
+        mode,
+        storage_sync,
+        rollup_client,
+        runtime_db,
+        storage_sync_db,
+    );
+    let Some(value) = store
+        .get_value(format!("{CODE_PATH_PREFIX}/{hash}"))
+        .await?
+    else {
+        return Err(ServiceError::NotFound);
+    };
+    let code = ParsedCode::decode(value.as_slice())
+        .map_err(|_| anyhow!("Failed to deserialize code"))?;
+    Ok(Json(code))
+}
+
+impl Service for CodeService {
+    fn router_with_openapi() -> OpenApiRouter<AppState> {
+        let routes = OpenApiRouter::new().routes(routes!(get_code_by_hash));
+
+        OpenApiRouter::new().nest("/code", routes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use axum::{body::Body, extract::Request};
+    use jstz_core::BinEncodable;
+    use jstz_mock::{kt1_account1, sr1_address};
+    use jstz_proto::{context::code::CodeStore, runtime::ParsedCode};
+    use tempfile::NamedTempFile;
+    use tower::ServiceExt;
+
+    use super::CodeService;
+    use crate::{
+        config::RuntimeEnv, services::Service, utils::tests::mock_app_state, RunMode,
+    };
+
+    #[tokio::test]
+    async fn get_code() {
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::new(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: PathBuf::new(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: PathBuf::new(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        let code = ParsedCode("export default () => new Response()".to_string());
+        let hash = CodeStore::hash(&code);
+        state
+            .runtime_db
+            .write(
+                &format!("/jstz_code/{hash}"),
+                &hex::encode(code.encode().unwrap()),
+            )
+            .unwrap();
+
+        let (router, _) = CodeService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let get = |uri: String| {
+            router
+                .clone()
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+        };
+
+        let res = get(format!("/code/{hash}")).await.unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        assert_eq!(serde_json::from_slice::<ParsedCode>(&bytes).unwrap(), code);
+
+        let res = get(format!("/code/{}", "0".repeat(64))).await.unwrap();
+        assert_eq!(res.status(), 404);
+
+        let res = get("/code/not_a_hash".to_string()).await.unwrap();
+        assert_eq!(res.status(), 400);
+    }
+}
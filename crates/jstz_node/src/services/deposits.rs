@@ -0,0 +1,160 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use jstz_proto::{operation::internal::InboxId, receipt::Receipt};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use super::{error::ServiceResult, operations::read_receipt, Service};
+use crate::{utils::StoreWrapper, AppState};
+
+const DEPOSITS_TAG: &str = "Deposits";
+
+pub struct DepositsService;
+
+/// Get the receipt of a deposit
+///
+/// Deposits are identified by the L1 inbox message that carried them. The receipt
+/// confirms whether the deposit was credited to the L2 account.
+#[utoipa::path(
+    get,
+    path = "/{l1_level}/{message_id}",
+    tag = DEPOSITS_TAG,
+    params(
+        ("l1_level" = u32, description = "L1 level of the inbox message"),
+        ("message_id" = u32, description = "Index of the inbox message in the level")
+    ),
+    responses(
+        (status = 200, body = Receipt),
+        (status = 400),
+        (status = 404),
+        (status = 500)
+    )
+)]
+async fn get_deposit_receipt(
+    State(AppState {
+        mode,
+        rollup_client,
+        runtime_db,
+        storage_sync,
+        storage_sync_db,
+        ..
+    }): State<AppState>,
+    Path((l1_level, l1_message_id)): Path<(u32, u32)>,
+) -> ServiceResult<Json<Receipt>> {
+    let hash = InboxId {
+        l1_level,
+        l1_message_id,
+    }
+    .hash();
+    let store = StoreWrapper::new(
+        mode,
+        storage_sync,
+        rollup_client,
+        runtime_db,
+        storage_sync_db,
+    );
+    Ok(Json(read_receipt(&store, &hash.to_string()).await?))
+}
+
+impl Service for DepositsService {
+    fn router_with_openapi() -> OpenApiRouter<AppState> {
+        let routes = OpenApiRouter::new().routes(routes!(get_deposit_receipt));
+
+        OpenApiRouter::new().nest("/deposits", routes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use axum::{body::Body, extract::Request};
+    use jstz_core::BinEncodable;
+    use jstz_mock::{kt1_account1, sr1_address};
+    use jstz_proto::{
+        context::account::Address,
+        operation::internal::InboxId,
+        receipt::{DepositReceipt, Receipt, ReceiptContent, ReceiptResult},
+    };
+    use tempfile::NamedTempFile;
+    use tower::ServiceExt;
+
+    use crate::{
+        config::RuntimeEnv,
+        services::{deposits::DepositsService, Service},
+        utils::tests::mock_app_state,
+        RunMode,
+    };
+
+    #[tokio::test]
+    async fn get_deposit_receipt_sequencer() {
+        let receiver = Address::User(jstz_mock::account1());
+        let inbox_id = InboxId {
+            l1_level: 12,
+            l1_message_id: 3,
+        };
+        let receipt = Receipt::new(
+            inbox_id.hash(),
+            Ok(ReceiptContent::Deposit(DepositReceipt {
+                account: receiver.clone(),
+                updated_balance: 100,
+            })),
+        );
+
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::default(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: NamedTempFile::new().unwrap().path().to_path_buf(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: NamedTempFile::new().unwrap().path().to_path_buf(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        state
+            .runtime_db
+            .write(
+                &format!("/jstz_receipt/{}", inbox_id.hash()),
+                &hex::encode(receipt.encode().unwrap()),
+            )
+            .unwrap();
+        let (router, _) = DepositsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let get = |uri: &str| {
+            router.clone().oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .method("GET")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        let res = get("/deposits/12/3").await.unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let receipt = serde_json::from_slice::<Receipt>(&bytes).unwrap();
+        assert!(matches!(
+            receipt.result,
+            ReceiptResult::Success(ReceiptContent::Deposit(DepositReceipt {
+                account,
+                updated_balance: 100,
+            })) if account == receiver
+        ));
+
+        // deposit of another inbox message
+        let res = get("/deposits/12/4").await.unwrap();
+        assert_eq!(res.status(), 404);
+
+        // invalid inbox id
+        let res = get("/deposits/12/abc").await.unwrap();
+        assert_eq!(res.status(), 400);
+    }
+}
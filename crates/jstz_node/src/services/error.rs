@@ -13,6 +13,7 @@ pub enum ServiceError {
     BadRequest(String),
     PersistentLogsDisabled,
     ServiceUnavailable(Option<anyhow::Error>),
+    TooManyRequests(String),
 }
 
 pub type ServiceResult<T> = anyhow::Result<T, ServiceError>;
@@ -37,6 +38,9 @@ impl IntoResponse for ServiceError {
                 }
                 None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
             },
+            ServiceError::TooManyRequests(error) => {
+                (StatusCode::TOO_MANY_REQUESTS, error_body(error)).into_response()
+            }
         }
     }
 }
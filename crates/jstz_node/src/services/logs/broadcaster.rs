@@ -3,18 +3,28 @@ use std::{collections::HashMap, convert::Infallible, sync::Arc, time::Duration};
 use axum::response::{sse, Sse};
 use futures_util::future;
 use jstz_crypto::smart_function_hash::SmartFunctionHash;
+use jstz_proto::runtime::LogRecord;
 use parking_lot::Mutex;
 use tokio::sync::mpsc::{self, Sender};
 use tokio::time::interval;
 use tokio_stream::wrappers::ReceiverStream;
 
+use super::LogFilter;
+
 type InfallibleSseEvent = Result<sse::Event, Infallible>;
 pub type InfallibleSSeStream = ReceiverStream<Result<sse::Event, Infallible>>;
 
+/// A connected client and the logs it subscribed to.
+#[derive(Clone)]
+struct Client {
+    sender: Sender<InfallibleSseEvent>,
+    filter: LogFilter,
+}
+
 /// Broadcasts messages to all connected clients through Server-sent Events
 /// <https://developer.mozilla.org/en-US/docs/Web/API/Server-sent_events/Using_server-sent_events>.
 pub struct Broadcaster {
-    clients: Mutex<HashMap<SmartFunctionHash, Vec<Sender<InfallibleSseEvent>>>>, // TODO: Use a read-write lock instead?
+    clients: Mutex<HashMap<SmartFunctionHash, Vec<Client>>>, // TODO: Use a read-write lock instead?
 }
 
 // Pings clients every 10 seconds
@@ -47,24 +57,23 @@ impl Broadcaster {
     async fn remove_stale_clients(&self) {
         let clients = self.clients.lock().clone();
 
-        let mut responsive_clients: HashMap<
-            SmartFunctionHash,
-            Vec<Sender<InfallibleSseEvent>>,
-        > = HashMap::new();
+        let mut responsive_clients: HashMap<SmartFunctionHash, Vec<Client>> =
+            HashMap::new();
 
-        for (function_address, senders) in clients {
-            let mut responsive_senders = Vec::new();
-            for sender in senders {
-                if sender
+        for (function_address, clients) in clients {
+            let mut responsive = Vec::new();
+            for client in clients {
+                if client
+                    .sender
                     .send(Ok(sse::Event::default().data("ping")))
                     .await
                     .is_ok()
                 {
-                    responsive_senders.push(sender);
+                    responsive.push(client);
                 }
             }
-            if !responsive_senders.is_empty() {
-                responsive_clients.insert(function_address, responsive_senders);
+            if !responsive.is_empty() {
+                responsive_clients.insert(function_address, responsive);
             }
         }
 
@@ -72,9 +81,11 @@ impl Broadcaster {
     }
 
     /// Registers client with broadcaster, returning an SSE response body.
+    /// Only the logs matching `filter` are sent to the client.
     pub async fn new_client(
         &self,
         function_address: SmartFunctionHash,
+        filter: LogFilter,
     ) -> Sse<InfallibleSSeStream> {
         let (tx, rx) = mpsc::channel(10);
 
@@ -86,7 +97,7 @@ impl Broadcaster {
             .lock()
             .entry(function_address)
             .or_default()
-            .push(tx);
+            .push(Client { sender: tx, filter });
 
         let stream = ReceiverStream::new(rx);
         let sse_response = Sse::new(stream);
@@ -97,14 +108,16 @@ impl Broadcaster {
         )
     }
 
-    /// Broadcasts `msg` to all clients.
-    pub async fn broadcast(&self, function_address: &SmartFunctionHash, msg: &str) {
+    /// Broadcasts `msg`, the serialized `log`, to all clients of the smart function
+    /// whose filter matches `log`.
+    pub async fn broadcast(&self, log: &LogRecord, msg: &str) {
         let clients = self.clients.lock().clone();
 
-        if let Some(clients) = clients.get(function_address) {
+        if let Some(clients) = clients.get(&log.address) {
             let send_futures = clients
                 .iter()
-                .map(|client| client.send(Ok(sse::Event::default().data(msg))));
+                .filter(|client| client.filter.matches(log))
+                .map(|client| client.sender.send(Ok(sse::Event::default().data(msg))));
             // try to send to all clients, ignoring failures
             // disconnected clients will get swept up by `remove_stale_clients`
             let _ = future::join_all(send_futures).await;
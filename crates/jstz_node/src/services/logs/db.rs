@@ -15,7 +15,8 @@ pub type SqliteConnectionPool = Pool<SqliteConnectionManager>;
 pub type SqliteConnection = PooledConnection<r2d2_sqlite::SqliteConnectionManager>;
 type QueryResponseResult = Result<Vec<LogRecord>>;
 
-const DB_PATH: &str = ".jstz/log.db";
+const DB_DIR: &str = ".jstz";
+const DB_FILE: &str = "log.db";
 
 #[derive(Clone)]
 pub struct Db {
@@ -26,9 +27,16 @@ impl Db {
     // Initialize the sql databse by createing a connection pool.
     // if the database does not exist, it will be created.
     pub async fn init() -> Result<Self> {
+        Self::init_file(DB_FILE).await
+    }
+
+    /// Initializes the database in the file `name` of the `.jstz` directory of the
+    /// home directory
+    pub async fn init_file(name: &str) -> Result<Self> {
         let db_path = dirs::home_dir()
             .expect("failed to get home directory")
-            .join(DB_PATH);
+            .join(DB_DIR)
+            .join(name);
 
         if let Some(parent) = db_path.parent() {
             if !parent.exists() {
@@ -126,6 +134,21 @@ impl Db {
         Self::collect_logs(stmt, [function_address.to_string(), request_id])
     }
 
+    /// Removes the logs of the requests `request_ids`. Returns the number of removed
+    /// logs.
+    pub async fn prune_requests(&self, request_ids: &[String]) -> Result<usize> {
+        let mut conn = self.connection().await?;
+        let tx = conn.transaction()?;
+        let mut pruned = 0;
+        for request_id in request_ids {
+            pruned +=
+                tx.execute("DELETE FROM log WHERE request_id = ?1", [request_id])?;
+            tx.execute("DELETE FROM request WHERE id = ?1", [request_id])?;
+        }
+        tx.commit()?;
+        Ok(pruned)
+    }
+
     fn collect_logs<P: Params>(
         mut stmt: Statement<'_>,
         params: P,
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use anyhow;
 use axum::{
@@ -10,10 +10,10 @@ use broadcaster::InfallibleSSeStream;
 use jstz_crypto::{hash::Hash, smart_function_hash::SmartFunctionHash};
 #[cfg(feature = "persistent-logging")]
 use jstz_proto::logger::{RequestEvent, REQUEST_END_PREFIX, REQUEST_START_PREFIX};
-use jstz_proto::runtime::{LogRecord, LOG_PREFIX};
+use jstz_proto::runtime::{LogLevel, LogRecord, LOG_PREFIX};
 use jstz_utils::tailed_file::TailedFile;
 use serde::Deserialize;
-use tokio::task::JoinHandle;
+use tokio::{sync::watch, task::JoinHandle};
 use tokio_util::sync::CancellationToken;
 use utoipa::IntoParams;
 use utoipa_axum::{router::OpenApiRouter, routes};
@@ -33,6 +33,17 @@ pub mod db {
         pub async fn init() -> anyhow::Result<Self> {
             Ok(Db {})
         }
+
+        pub async fn init_file(_name: &str) -> anyhow::Result<Self> {
+            Ok(Db {})
+        }
+
+        pub async fn prune_requests(
+            &self,
+            _request_ids: &[String],
+        ) -> anyhow::Result<usize> {
+            Ok(0)
+        }
     }
 }
 
@@ -103,19 +114,51 @@ impl LogsService {
     // Initalise the LogService by spawning a future that reads and broadcasts the file
     pub async fn init(
         path: &std::path::Path,
+    ) -> anyhow::Result<(Arc<Broadcaster>, Db, Self)> {
+        Self::start(path, vec![], None, Db::init().await?).await
+    }
+
+    /// Initialises the LogService with the database `db` instead of the database of
+    /// the node
+    pub async fn init_with_db(
+        path: &std::path::Path,
+        db: Db,
+    ) -> anyhow::Result<(Arc<Broadcaster>, Db, Self)> {
+        Self::start(path, vec![], None, db).await
+    }
+
+    /// Initialises the LogService with the kernel log files of several rollup nodes,
+    /// in the order of their endpoints, of which the file of the active rollup node
+    /// is read. The file of the first rollup node is read first, and a file is read
+    /// from its end once its rollup node becomes active. The current file is kept
+    /// when the active rollup node has none.
+    pub async fn follow(
+        paths: Vec<Option<PathBuf>>,
+        active: watch::Receiver<usize>,
+    ) -> anyhow::Result<(Arc<Broadcaster>, Db, Self)> {
+        let Some(Some(path)) = paths.first().cloned() else {
+            anyhow::bail!("the first rollup node has no kernel log file");
+        };
+        Self::start(&path, paths, Some(active), Db::init().await?).await
+    }
+
+    async fn start(
+        path: &std::path::Path,
+        paths: Vec<Option<PathBuf>>,
+        active: Option<watch::Receiver<usize>>,
+        db: Db,
     ) -> anyhow::Result<(Arc<Broadcaster>, Db, Self)> {
         // Create a broadcaster for streaming logs.
         let broadcaster = Broadcaster::new();
 
-        // Create a connection with the sqlite database.
-        let db = Db::init().await?;
-
         let cancellation_token = CancellationToken::new();
         let file = TailedFile::init(path).await?;
         // Spawn a future that reads from the log file.
         // The line is broadcast to client / flushed to storage.
         let inner = Self::tail_file(
             file,
+            paths,
+            active,
             broadcaster.clone(),
             db.clone(),
             cancellation_token.clone(),
@@ -137,19 +180,25 @@ impl LogsService {
         self.inner.await?
     }
 
-    /// Spawn a future that tails log file.
+    /// Spawn a future that tails log file, switching to the file of `paths` at the
+    /// index sent by `active`.
     /// The line is broadcast to client / flushed to storage.
     async fn tail_file(
-        file: TailedFile,
+        mut file: TailedFile,
+        paths: Vec<Option<PathBuf>>,
+        mut active: Option<watch::Receiver<usize>>,
         broadcaster: Arc<Broadcaster>,
         #[allow(unused_variables)] db: Db,
         cancellation_token: CancellationToken,
     ) -> JoinHandle<std::io::Result<()>> {
         tokio::task::spawn(async move {
-            let mut lines = file.lines();
             loop {
                 tokio::select! {
-                    current_line = lines.next_line() => {
+                    current_line = file.next_line() => {
+                        if let Ok(None) = current_line {
+                            // EOF – wait a bit and try again
+                            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                        }
                         if let Ok(Some(line_str)) = current_line {
                             // CLIPPY
                             // The collapsible-match lint gives a false positive for this line since
@@ -171,12 +220,24 @@ impl LogsService {
                                 #[allow(irrefutable_let_patterns)]
                                 if let Line::Js(log) = line {
                                     broadcaster
-                                        .broadcast(&log.address.clone(), &line_str[LOG_PREFIX.len()..])
-                                       .await;
+                                        .broadcast(&log, &line_str[LOG_PREFIX.len()..])
+                                        .await;
                                 }
                             }
                         }
                     },
+                    index = next_active(&mut active) => {
+                        let Some(path) = paths.get(index).and_then(Option::as_ref) else {
+                            continue;
+                        };
+                        match TailedFile::init(path).await {
+                            Ok(next) => file = next,
+                            Err(e) => log::warn!(
+                                "Failed to open log file {}: {e}",
+                                path.display()
+                            ),
+                        }
+                    },
                     _ = cancellation_token.cancelled() => {
                         // The stop signal has been triggered.
                         break;
@@ -208,6 +269,16 @@ impl LogsService {
     }
 }
 
+/// Waits for the next index of the active rollup node, forever if there is none
+async fn next_active(active: &mut Option<watch::Receiver<usize>>) -> usize {
+    if let Some(active) = active {
+        if active.changed().await.is_ok() {
+            return *active.borrow_and_update();
+        }
+    }
+    std::future::pending().await
+}
+
 #[derive(Deserialize, Debug, IntoParams)]
 #[serde(default)]
 pub struct Pagination {
@@ -224,12 +295,32 @@ impl Default for Pagination {
     }
 }
 
+/// Selects the console logs streamed to a client
+#[derive(Deserialize, Debug, Clone, Default, IntoParams)]
+pub struct LogFilter {
+    /// Only stream the logs of this request
+    request_id: Option<String>,
+    /// Only stream the logs of this level or more severe
+    level: Option<LogLevel>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, log: &LogRecord) -> bool {
+        self.request_id
+            .as_ref()
+            .is_none_or(|request_id| *request_id == log.request_id)
+            && self.level.as_ref().is_none_or(|level| log.level <= *level)
+    }
+}
+
 /// Stream console logs
 ///
 /// Returns a stream of console logs from the given Smart Function as Server-Sent Events.
+/// The stream can be restricted to the logs of a single request and to a minimum severity.
 #[utoipa::path(
     get,
     path = "/{address}/stream",
+    params(LogFilter),
     tag = "Logs",
     responses(
         (status = 200, description = "Successfully connected to log stream as Server-Sent Events"),
@@ -240,10 +331,11 @@ impl Default for Pagination {
 async fn stream_log(
     State(AppState { broadcaster, .. }): State<AppState>,
     Path(address): Path<String>,
+    Query(filter): Query<LogFilter>,
 ) -> ServiceResult<Sse<InfallibleSSeStream>> {
     let address = SmartFunctionHash::from_base58(&address)
         .map_err(|e| ServiceError::BadRequest(e.to_string()))?;
-    Ok(broadcaster.new_client(address).await)
+    Ok(broadcaster.new_client(address, filter).await)
 }
 
 /// Fetch console logs by address
@@ -340,4 +432,89 @@ mod tests {
         assert!(result.is_ok(), "shutdown did not complete in time");
         assert!(result.unwrap().is_ok(), "shutdown returned an error");
     }
+
+    #[cfg(not(feature = "persistent-logging"))]
+    #[tokio::test]
+    async fn logs_service_follows_active_rollup_node() {
+        use std::io::Write;
+
+        use axum::response::IntoResponse;
+        use futures_util::StreamExt;
+
+        let files = [NamedTempFile::new().unwrap(), NamedTempFile::new().unwrap()];
+        let paths = files.iter().map(|f| Some(f.path().to_path_buf())).collect();
+        let (active, rx) = watch::channel(0);
+        let (broadcaster, _db, _logs_service) =
+            LogsService::follow(paths, rx).await.unwrap();
+        let address =
+            SmartFunctionHash::from_base58("KT1WjrJgoaEDHF2RmhhnpjjiwBkt4nA2MiMo")
+                .unwrap();
+        let mut body = broadcaster
+            .new_client(address, LogFilter::default())
+            .await
+            .into_response()
+            .into_body()
+            .into_data_stream();
+        // Events of the stream, without the pings and keep-alive comments
+        async fn next_event(body: &mut axum::body::BodyDataStream) -> Option<String> {
+            loop {
+                let event = timeout(Duration::from_millis(200), body.next())
+                    .await
+                    .ok()?;
+                let event = String::from_utf8(event?.unwrap().to_vec()).unwrap();
+                if event != "data: ping\n\n" && !event.starts_with(':') {
+                    return Some(event);
+                }
+            }
+        }
+        assert!(next_event(&mut body).await.unwrap().contains("connected"));
+
+        let write = |file: &NamedTempFile, text: &str| {
+            let mut file = file.as_file();
+            writeln!(
+                file,
+                r#"{LOG_PREFIX}{{"address":"KT1WjrJgoaEDHF2RmhhnpjjiwBkt4nA2MiMo","requestId":"req1","level":"WARN","text":"{text}"}}"#
+            )
+            .unwrap();
+        };
+        // the file of the next rollup node is read once it becomes active, which the
+        // service notices asynchronously
+        active.send(1).unwrap();
+        let event = loop {
+            write(&files[1], "second");
+            if let Some(event) = next_event(&mut body).await {
+                break event;
+            }
+        };
+        assert!(event.contains(r#""text":"second""#));
+
+        // the file of the previous rollup node is not read anymore
+        write(&files[0], "first");
+        assert_eq!(next_event(&mut body).await, None);
+    }
+
+    #[test]
+    fn log_filter_matches_request_id_and_level() {
+        let log = LogRecord::try_from_string(
+            r#"{"address":"KT1WjrJgoaEDHF2RmhhnpjjiwBkt4nA2MiMo","requestId":"req1","level":"WARN","text":"hello"}"#,
+        )
+        .unwrap();
+
+        assert!(LogFilter::default().matches(&log));
+
+        let by_request = |request_id: &str| LogFilter {
+            request_id: Some(request_id.to_string()),
+            level: None,
+        };
+        assert!(by_request("req1").matches(&log));
+        assert!(!by_request("req2").matches(&log));
+
+        let by_level = |level| LogFilter {
+            request_id: None,
+            level: Some(level),
+        };
+        assert!(by_level(LogLevel::DEBUG).matches(&log));
+        assert!(by_level(LogLevel::WARN).matches(&log));
+        assert!(!by_level(LogLevel::ERROR).matches(&log));
+    }
 }
@@ -3,9 +3,13 @@ use crate::AppState;
 use utoipa_axum::router::OpenApiRouter;
 
 pub mod accounts;
+pub mod code;
+pub mod deposits;
 pub mod error;
 pub mod logs;
+pub mod names;
 pub mod operations;
+pub mod sequencer;
 pub mod utils;
 
 pub trait Service {
@@ -0,0 +1,232 @@
+use anyhow::anyhow;
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use jstz_client::models::NameRecord;
+use jstz_core::BinEncodable;
+use jstz_crypto::smart_function_hash::SmartFunctionHash;
+use jstz_proto::runtime::KvValue;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use super::{
+    error::{ServiceError, ServiceResult},
+    Service,
+};
+use crate::{utils::StoreWrapper, AppState};
+
+const NAMES_TAG: &str = "Names";
+
+pub struct NamesService;
+
+/// Reads the string stored under `<dir>/<entry>` in the storage of the name registry,
+/// which is the address registered under a name for `names/<name>` and the name
+/// registered by an address for `addresses/<address>`
+async fn read_registry(state: AppState, dir: &str, entry: &str) -> ServiceResult<String> {
+    // `entry` is a decoded path parameter, which must not address other keys
+    if entry.is_empty() || entry.contains('/') {
+        return Err(ServiceError::BadRequest(format!(
+            "invalid name registry entry '{entry}'"
+        )));
+    }
+    let key = format!("{dir}/{entry}");
+    let AppState {
+        mode,
+        rollup_client,
+        runtime_db,
+        storage_sync,
+        storage_sync_db,
+        name_registry,
+        ..
+    } = state;
+    let registry = name_registry.ok_or_else(|| {
+        ServiceError::BadRequest("name registry is not configured".to_string())
+    })?;
+    let store = StoreWrapper::new(
+        mode,
+        storage_sync,
+        rollup_client,
+        runtime_db,
+        storage_sync_db,
+    );
+    let Some(value) = store.get_value(registry_key(&registry, &key)).await? else {
+        return Err(ServiceError::NotFound);
+    };
+    let KvValue(value) = KvValue::decode(value.as_slice())
+        .map_err(|_| anyhow!("Failed to deserialize kv value"))?;
+    match value {
+        serde_json::Value::String(value) => Ok(value),
+        _ => Err(anyhow!("Invalid name registry entry under '{key}'").into()),
+    }
+}
+
+fn registry_key(registry: &SmartFunctionHash, key: &str) -> String {
+    format!("/jstz_kv/{registry}/{key}")
+}
+
+/// Resolve a name to an address
+///
+/// Returns the address registered under `name` in the name registry of the node.
+#[utoipa::path(
+    get,
+    path = "/names/{name}",
+    tag = NAMES_TAG,
+    responses(
+        (status = 200, body = NameRecord),
+        (status = 400, description = "The name registry is not configured, or the name contains `/`"),
+        (status = 404),
+        (status = 500)
+    )
+)]
+async fn resolve_name(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> ServiceResult<Json<NameRecord>> {
+    let address = read_registry(state, "names", &name).await?;
+    Ok(Json(NameRecord { name, address }))
+}
+
+/// Look up the name of an address
+///
+/// Returns the name registered by `address` in the name registry of the node.
+#[utoipa::path(
+    get,
+    path = "/addresses/{address}/name",
+    tag = NAMES_TAG,
+    responses(
+        (status = 200, body = NameRecord),
+        (status = 400, description = "The name registry is not configured, or the address contains `/`"),
+        (status = 404),
+        (status = 500)
+    )
+)]
+async fn lookup_name(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+) -> ServiceResult<Json<NameRecord>> {
+    let name = read_registry(state, "addresses", &address).await?;
+    Ok(Json(NameRecord { name, address }))
+}
+
+impl Service for NamesService {
+    fn router_with_openapi() -> OpenApiRouter<AppState> {
+        OpenApiRouter::new()
+            .routes(routes!(resolve_name))
+            .routes(routes!(lookup_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use axum::{body::Body, extract::Request};
+    use jstz_core::BinEncodable;
+    use jstz_crypto::{hash::Hash, smart_function_hash::SmartFunctionHash};
+    use jstz_mock::{kt1_account1, sr1_address};
+    use jstz_proto::runtime::KvValue;
+    use tempfile::NamedTempFile;
+    use tower::ServiceExt;
+
+    use super::{NameRecord, NamesService};
+    use crate::{
+        config::RuntimeEnv, services::Service, utils::tests::mock_app_state, AppState,
+        RunMode,
+    };
+
+    const REGISTRY: &str = "KT19GXucGUitURBXXeEMMfqqhSQ5byt4P1zX";
+    const ADDRESS: &str = "tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV";
+
+    async fn state(db_file: &NamedTempFile) -> AppState {
+        mock_app_state(
+            "",
+            PathBuf::new(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: PathBuf::new(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: PathBuf::new(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await
+    }
+
+    fn write(state: &AppState, key: &str, value: serde_json::Value) {
+        state
+            .runtime_db
+            .write(
+                &format!("/jstz_kv/{REGISTRY}/{key}"),
+                &hex::encode(KvValue(value).encode().unwrap()),
+            )
+            .unwrap();
+    }
+
+    async fn get(state: AppState, uri: &str) -> (u16, Option<NameRecord>) {
+        let (router, _) = NamesService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let res = router
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = res.status().as_u16();
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        (status, serde_json::from_slice(&bytes).ok())
+    }
+
+    #[tokio::test]
+    async fn resolve_and_lookup_names() {
+        let db_file = NamedTempFile::new().unwrap();
+        let mut state = state(&db_file).await;
+        state.name_registry = Some(SmartFunctionHash::from_base58(REGISTRY).unwrap());
+        write(&state, "names/alice", serde_json::json!(ADDRESS));
+        write(
+            &state,
+            &format!("addresses/{ADDRESS}"),
+            serde_json::json!("alice"),
+        );
+        write(&state, "names/bad", serde_json::json!(42));
+
+        let record = NameRecord {
+            name: "alice".to_string(),
+            address: ADDRESS.to_string(),
+        };
+        assert_eq!(
+            get(state.clone(), "/names/alice").await,
+            (200, Some(record.clone()))
+        );
+        assert_eq!(
+            get(state.clone(), &format!("/addresses/{ADDRESS}/name")).await,
+            (200, Some(record))
+        );
+        assert_eq!(get(state.clone(), "/names/bob").await.0, 404);
+        assert_eq!(
+            get(
+                state.clone(),
+                "/addresses/tz1cD5CuvAALcxgypqBXcBQEA8dkLJivoFjU/name"
+            )
+            .await
+            .0,
+            404
+        );
+        assert_eq!(get(state.clone(), "/names/bad").await.0, 500);
+        // names cannot address other keys of the registry
+        assert_eq!(
+            get(state, &format!("/names/..%2Faddresses%2F{ADDRESS}"))
+                .await
+                .0,
+            400
+        );
+    }
+
+    #[tokio::test]
+    async fn registry_not_configured() {
+        let db_file = NamedTempFile::new().unwrap();
+        let state = state(&db_file).await;
+        write(&state, "names/alice", serde_json::json!(ADDRESS));
+        assert_eq!(get(state, "/names/alice").await.0, 400);
+    }
+}
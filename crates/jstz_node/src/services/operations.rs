@@ -3,14 +3,17 @@ use std::path;
 use std::sync::Arc;
 use std::sync::RwLock;
 
-use crate::sequencer::queue::{OperationQueue, WrappedOperation};
+use crate::sequencer::db::{OperationDiff, OperationStatus, ReceiptAttestation};
+use crate::sequencer::queue::{self, OperationQueue, WrappedOperation};
+#[cfg(feature = "simulation")]
+use crate::sequencer::runtime::simulate as simulate_operation;
 #[cfg(feature = "inject_inbox")]
 use crate::sequencer::runtime::{JSTZ_ROLLUP_ADDRESS, TICKETER};
-use crate::services::accounts::get_account_nonce;
+use crate::services::accounts::{get_account_balance, get_account_nonce};
 use crate::RunMode;
 
 use super::error::{ServiceError, ServiceResult};
-use super::utils::StoreWrapper;
+use super::utils::{read_db, StorageProof, StoreWrapper};
 use super::{AppState, Service};
 use anyhow::anyhow;
 use anyhow::Context;
@@ -18,39 +21,55 @@ use anyhow::Context;
 use axum::routing::post;
 use axum::{
     extract::{Path, State},
+    http::{header::AUTHORIZATION, HeaderMap},
     Json,
 };
 
-use jstz_core::reveal_data::{PreimageHash, RevealData, MAX_REVEAL_SIZE};
+use jstz_client::{ApiError, JstzClient};
+use jstz_core::reveal_data::{
+    ContentEncoding, PreimageHash, RevealData, MAX_DECOMPRESSED_REVEAL_SIZE,
+    MAX_REVEAL_SIZE,
+};
 use jstz_core::BinEncodable;
-use jstz_proto::operation::{Content, Operation, SignedOperation};
+use jstz_kernel::inbox::{
+    dead_letter_key, DeadLetter, DEAD_LETTER_CAPACITY, DEAD_LETTER_COUNT_KEY,
+};
+use jstz_proto::executor::smart_function::X_JSTZ_TRANSFER;
+use jstz_proto::operation::{
+    Content, Operation, SignedOperation, MAX_DIRECT_OPERATION_SIZE,
+};
 use jstz_proto::receipt::Receipt;
+use jstz_proto::runtime::ParsedCode;
+#[cfg(feature = "simulation")]
+use jstz_proto::runtime::{LogRecord, LOG_PREFIX};
 use jstz_utils::KeyPair;
-use octez::OctezRollupClient;
+use octez::RollupRpc;
+use serde::{Deserialize, Serialize};
 #[cfg(feature = "inject_inbox")]
 use tezos_crypto_rs::hash::{ContractKt1Hash, SmartRollupHash};
 use tezos_data_encoding::enc::BinWriter;
 use tezos_smart_rollup::inbox::ExternalMessageFrame;
 
 use tokio::task::JoinSet;
+use tracing::instrument;
+use utoipa::ToSchema;
 use utoipa_axum::router::OpenApiRouter;
 use utoipa_axum::routes;
 
-/// The maximum operation size in bytes that can be directly included without using the reveal mechanism.
-const MAX_DIRECT_OPERATION_SIZE: usize = 3915;
-
 pub struct OperationsService;
 
 const OPERATIONS_TAG: &str = "Operations";
 
 type HexEncodedOperationHash = String;
 
-// Given a large operation, encode it into preimages and store them in the rollup's preimages directory
+// Given a large operation, encode it into preimages and store them in the preimages directory
+// of each rollup node
 async fn prepare_rlp_operation(
     operation: &SignedOperation,
+    content_encoding: ContentEncoding,
     signer: &KeyPair,
     store: &StoreWrapper,
-    rollup_preimages_dir: &path::Path,
+    rollup_preimages_dirs: &[&path::Path],
 ) -> ServiceResult<SignedOperation> {
     let reveal_type = operation
         .verify_ref()
@@ -65,13 +84,20 @@ async fn prepare_rlp_operation(
 
     let mut write_tasks = JoinSet::new();
     let save_preimages = |hash: PreimageHash, preimage: Vec<u8>| {
-        let path = rollup_preimages_dir.join(hash.to_string());
-        write_tasks.spawn(async move { fs::write(&path, preimage) });
+        for dir in rollup_preimages_dirs {
+            let path = dir.join(hash.to_string());
+            let preimage = preimage.clone();
+            write_tasks.spawn(async move { fs::write(&path, preimage) });
+        }
     };
     let KeyPair(public_key, secret_key) = signer;
-    let root_hash = RevealData::encode_and_prepare_preimages(operation, save_preimages)
-        .map_err(|e| anyhow::anyhow!("{}", e))
-        .context("failed to prepare reveal large payload operation")?;
+    let root_hash = RevealData::encode_and_prepare_preimages_with(
+        operation,
+        content_encoding,
+        save_preimages,
+    )
+    .map_err(|e| anyhow::anyhow!("{}", e))
+    .context("failed to prepare reveal large payload operation")?;
     write_tasks
         .join_all()
         .await
@@ -79,7 +105,7 @@ async fn prepare_rlp_operation(
         .collect::<Result<Vec<()>, _>>()
         .map_err(|e| anyhow!("failed to save preimages: {e}"))?;
 
-    let nonce = get_account_nonce(store, &public_key.hash())
+    let nonce = get_account_nonce(store, &public_key.hash(), None)
         .await?
         .unwrap_or_default();
     let rlp_operation = Operation {
@@ -90,6 +116,8 @@ async fn prepare_rlp_operation(
             reveal_type,
             operation.hash(),
         ),
+        max_fee: None,
+        priority_fee: None,
     };
     let signature = secret_key
         .sign(rlp_operation.hash())
@@ -97,12 +125,13 @@ async fn prepare_rlp_operation(
     Ok(SignedOperation::new(signature, rlp_operation))
 }
 
-// Encode an operation. if the operation is too large, encode it into a reveal large payload operation
+// Encode an operation. if the operation is too large, encode it into a reveal large payload operation,
+// whose preimages are compressed if the operation exceeds the maximum reveal size
 async fn encode_operation(
     operation: SignedOperation,
     injector: &KeyPair,
     store: &StoreWrapper,
-    rollup_preimages_dir: &path::Path,
+    rollup_preimages_dirs: &[&path::Path],
 ) -> ServiceResult<(SignedOperation, Vec<u8>)> {
     let encoded_op = operation
         .encode()
@@ -110,10 +139,20 @@ async fn encode_operation(
 
     let (op, contents) = match encoded_op.len() {
         size if size <= MAX_DIRECT_OPERATION_SIZE => (operation, encoded_op),
-        size if size <= MAX_REVEAL_SIZE => {
-            let op =
-                prepare_rlp_operation(&operation, injector, store, rollup_preimages_dir)
-                    .await?;
+        size if size <= MAX_DECOMPRESSED_REVEAL_SIZE => {
+            let content_encoding = if size <= MAX_REVEAL_SIZE {
+                ContentEncoding::Identity
+            } else {
+                ContentEncoding::Zstd
+            };
+            let op = prepare_rlp_operation(
+                &operation,
+                content_encoding,
+                injector,
+                store,
+                rollup_preimages_dirs,
+            )
+            .await?;
             let encoded_op = op
                 .encode()
                 .map_err(|e| anyhow!("Failed to encode rlp operation: {e}"))?;
@@ -122,7 +161,7 @@ async fn encode_operation(
         size => Err(anyhow!(
             "Operation size exceeds maximum allowed size ({} bytes > {} MB)",
             size,
-            MAX_REVEAL_SIZE / 1024 / 1024
+            MAX_DECOMPRESSED_REVEAL_SIZE / 1024 / 1024
         ))?,
     };
 
@@ -130,20 +169,26 @@ async fn encode_operation(
 }
 
 /// Inject an operation into Jstz
+///
+/// Returns the hex encoded hash of the operation. Injecting an operation that the
+/// sequencer already queued returns the same hash without queueing it again.
 #[utoipa::path(
         post,
         path = "",
         tag = OPERATIONS_TAG,
         responses(
-            (status = 200, description = "Operation successfully injected"),
+            (status = 200, description = "Operation successfully injected", body = HexEncodedOperationHash),
             (status = 400),
+            (status = 429, description = "The account of the operation exceeded its quota"),
             (status = 500)
         )
     )]
+#[instrument(skip_all, fields(operation_hash = %operation.hash()))]
 async fn inject(
     State(AppState {
         rollup_client,
         rollup_preimages_dir,
+        rollup_fallback_preimages_dirs,
         injector,
         mode,
         queue,
@@ -152,8 +197,20 @@ async fn inject(
         storage_sync_db,
         ..
     }): State<AppState>,
+    headers: HeaderMap,
     Json(operation): Json<SignedOperation>,
-) -> ServiceResult<()> {
+) -> ServiceResult<Json<HexEncodedOperationHash>> {
+    #[cfg(feature = "simulation")]
+    if operation.is_simulation() {
+        return Err(ServiceError::BadRequest(
+            "simulated operations cannot be injected".to_string(),
+        ));
+    }
+    let operation_hash = operation.hash().to_string();
+    if let RunMode::Replica { primary_endpoint } = &mode {
+        forward_operation(primary_endpoint, &headers, &operation).await?;
+        return Ok(Json(operation_hash));
+    }
     let store = StoreWrapper::new(
         mode.clone(),
         storage_sync,
@@ -161,22 +218,66 @@ async fn inject(
         runtime_db,
         storage_sync_db,
     );
+    let preimages_dirs: Vec<&path::Path> =
+        std::iter::once(rollup_preimages_dir.as_path())
+            .chain(
+                rollup_fallback_preimages_dirs
+                    .iter()
+                    .map(|dir| dir.as_path()),
+            )
+            .collect();
     let (operation, encoded_operation) =
-        encode_operation(operation, &injector, &store, &rollup_preimages_dir).await?;
+        encode_operation(operation, &injector, &store, &preimages_dirs).await?;
     match mode {
         RunMode::Default => {
-            inject_rollup_message(encoded_operation, &rollup_client).await?;
+            inject_rollup_message(encoded_operation, rollup_client.as_ref()).await?;
         }
         RunMode::Sequencer { .. } => {
             insert_operation_queue(&queue, WrappedOperation::FromNode(operation)).await?;
         }
+        // Forwarded to the primary above
+        RunMode::Replica { .. } => unreachable!(),
     }
-    Ok(())
+    Ok(Json(operation_hash))
+}
+
+/// Forwards an operation injected in a replica to its primary node, along with the
+/// authorization of the request. Errors of the primary are returned as is.
+async fn forward_operation(
+    primary_endpoint: &str,
+    headers: &HeaderMap,
+    operation: &SignedOperation,
+) -> ServiceResult<()> {
+    let mut client = JstzClient::new(primary_endpoint.to_string());
+    if let Some(token) = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        client = client.with_auth_token(token.to_string());
+    }
+    let e = match client.post_operation(operation).await {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+    let Some(ApiError { status, message }) = e.downcast_ref::<ApiError>().cloned() else {
+        return Err(e
+            .context("failed to forward operation to the primary node")
+            .into());
+    };
+    Err(match status {
+        reqwest::StatusCode::BAD_REQUEST => ServiceError::BadRequest(message),
+        reqwest::StatusCode::SERVICE_UNAVAILABLE => {
+            ServiceError::ServiceUnavailable(Some(anyhow!(message)))
+        }
+        reqwest::StatusCode::TOO_MANY_REQUESTS => ServiceError::TooManyRequests(message),
+        _ => anyhow!("primary node responded with {status}: {message}").into(),
+    })
 }
 
 async fn inject_rollup_message(
     contents: Vec<u8>,
-    rollup_client: &OctezRollupClient,
+    rollup_client: &dyn RollupRpc,
 ) -> ServiceResult<()> {
     let address = rollup_client.get_rollup_address().await?;
     let message_frame = ExternalMessageFrame::Targetted { address, contents };
@@ -184,7 +285,9 @@ async fn inject_rollup_message(
     message_frame
         .bin_write(&mut binary_contents)
         .map_err(|_| anyhow!("Failed to write binary frame"))?;
-    rollup_client.batcher_injection([binary_contents]).await?;
+    rollup_client
+        .batcher_injection(vec![binary_contents])
+        .await?;
     Ok(())
 }
 
@@ -192,15 +295,13 @@ async fn insert_operation_queue(
     queue: &Arc<RwLock<OperationQueue>>,
     message: WrappedOperation,
 ) -> ServiceResult<()> {
-    queue
-        .write()
-        .map_err(|e| {
-            ServiceError::FromAnyhow(anyhow::anyhow!(
-                "failed to insert operation to the queue: {e}"
-            ))
-        })?
-        .insert(message)
-        .map_err(|e| ServiceError::ServiceUnavailable(Some(e)))?;
+    queue::insert(queue, message).map_err(|e| {
+        if e.is_quota_exceeded() {
+            ServiceError::TooManyRequests(e.to_string())
+        } else {
+            ServiceError::ServiceUnavailable(Some(e.into()))
+        }
+    })?;
     Ok(())
 }
 
@@ -291,7 +392,7 @@ async fn handle_inbox_message(
     Ok(match message.content {
         ParsedInboxMessage::JstzMessage(Message::External(m)) => {
             let (op, _) =
-                encode_operation(m, injector, store, rollup_preimages_dir).await?;
+                encode_operation(m, injector, store, &[rollup_preimages_dir]).await?;
 
             let buf = encode_signed_operation(
                 &op,
@@ -339,8 +440,6 @@ async fn receipt(
     }): State<AppState>,
     Path(hash): Path<String>,
 ) -> ServiceResult<Json<Receipt>> {
-    let key = format!("/jstz_receipt/{hash}");
-
     let store = StoreWrapper::new(
         mode,
         storage_sync,
@@ -348,15 +447,411 @@ async fn receipt(
         runtime_db,
         storage_sync_db,
     );
+    Ok(Json(read_receipt(&store, &hash).await?))
+}
+
+/// The receipt of an operation with the Merkle proof of its storage
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ReceiptProof {
+    pub receipt: Receipt,
+    pub proof: StorageProof,
+}
+
+/// Get the receipt of an operation with its Merkle proof
+///
+/// Reads the receipt from the rollup node with the proof of its storage, so that
+/// light clients can verify it against the state hash committed on L1. Returns 404
+/// until the operation is executed by the rollup.
+#[utoipa::path(
+        get,
+        path = "/{operation_hash}/receipt/proof",
+        tag = OPERATIONS_TAG,
+        params(
+            ("operation_hash" = String, description = "Operation hash")
+        ),
+        responses(
+            (status = 200, body = ReceiptProof),
+            (status = 404),
+            (status = 500)
+        )
+    )]
+async fn receipt_proof(
+    State(AppState { rollup_client, .. }): State<AppState>,
+    Path(hash): Path<String>,
+) -> ServiceResult<Json<ReceiptProof>> {
+    let key = format!("/jstz_receipt/{hash}");
+    let (value, proof) = StorageProof::read(rollup_client.as_ref(), &key).await?;
+    let receipt = Receipt::decode(value.as_slice())
+        .map_err(|_| anyhow!("Failed to deserialize receipt"))?;
+    Ok(Json(ReceiptProof { receipt, proof }))
+}
+
+/// The receipt of an operation with its signature by the sequencer
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AttestedReceipt {
+    pub receipt: Receipt,
+    /// Base58-encoded public key of the sequencer, which signs the receipt
+    pub public_key: String,
+    pub attestation: ReceiptAttestation,
+}
+
+/// Get the receipt of an operation signed by the sequencer
+///
+/// The sequencer signs the Blake2b digest of the binary-encoded receipt of each
+/// operation it executes, prefixed with the tag `jstz:receipt_attestation:`.
+/// Clients accepting receipts before they are confirmed on L1 can keep the signed
+/// receipt, so that the sequencer can be held accountable if it later commits to a
+/// different receipt. Only available in sequencer mode.
+#[utoipa::path(
+        get,
+        path = "/{operation_hash}/receipt/attestation",
+        tag = OPERATIONS_TAG,
+        params(
+            ("operation_hash" = String, description = "Operation hash")
+        ),
+        responses(
+            (status = 200, body = AttestedReceipt),
+            (status = 400),
+            (status = 404),
+            (status = 500)
+        )
+    )]
+async fn receipt_attestation(
+    State(AppState {
+        mode,
+        injector,
+        runtime_db,
+        ..
+    }): State<AppState>,
+    Path(hash): Path<String>,
+) -> ServiceResult<Json<AttestedReceipt>> {
+    if !matches!(mode, RunMode::Sequencer { .. }) {
+        return Err(ServiceError::BadRequest(
+            "receipt attestations are only available in sequencer mode".to_string(),
+        ));
+    }
+    let attestation = read_db(&runtime_db, {
+        let hash = hash.clone();
+        move |db| db.receipt_attestation(&hash)
+    })
+    .await?
+    .ok_or(ServiceError::NotFound)?;
+    let receipt = read_receipt(&StoreWrapper::Db(Arc::new(runtime_db)), &hash).await?;
+    Ok(Json(AttestedReceipt {
+        receipt,
+        public_key: injector.0.to_string(),
+        attestation,
+    }))
+}
+
+/// Reads the receipt stored under `hash`
+pub(crate) async fn read_receipt(
+    store: &StoreWrapper,
+    hash: &str,
+) -> ServiceResult<Receipt> {
+    let key = format!("/jstz_receipt/{hash}");
     let value = store.get_value(key).await?;
+    match value {
+        Some(value) => Ok(Receipt::decode(value.as_slice())
+            .map_err(|_| anyhow!("Failed to deserialize receipt"))?),
+        None => Err(ServiceError::NotFound),
+    }
+}
+
+/// Get the status of an operation in the sequencer
+///
+/// Operations injected through the node are `queued` and become `included` once read
+/// from an L1 inbox. Operations are `executing` while the sequencer executes them, then
+/// `committed`, or `failed` if the sequencer failed to execute them.
+#[utoipa::path(
+        get,
+        path = "/{operation_hash}/status",
+        tag = OPERATIONS_TAG,
+        params(
+            ("operation_hash" = String, description = "Operation hash")
+        ),
+        responses(
+            (status = 200, body = OperationStatus),
+            (status = 400),
+            (status = 404),
+            (status = 500)
+        )
+    )]
+async fn status(
+    State(AppState {
+        mode, runtime_db, ..
+    }): State<AppState>,
+    Path(hash): Path<String>,
+) -> ServiceResult<Json<OperationStatus>> {
+    if !matches!(mode, RunMode::Sequencer { .. }) {
+        return Err(ServiceError::BadRequest(
+            "operation status is only available in sequencer mode".to_string(),
+        ));
+    }
+    match read_db(&runtime_db, move |db| db.operation_status(&hash)).await? {
+        Some(status) => Ok(Json(status)),
+        None => Err(ServiceError::NotFound),
+    }
+}
+
+/// Get the storage diff of an operation
+///
+/// Returns the keys the operation wrote in the sequencer storage, with their values
+/// after the operation. Operations executed concurrently share their diffs. Only
+/// recorded for operations executed by the native runtime of the sequencer.
+#[utoipa::path(
+        get,
+        path = "/{operation_hash}/diff",
+        tag = OPERATIONS_TAG,
+        params(
+            ("operation_hash" = String, description = "Operation hash")
+        ),
+        responses(
+            (status = 200, body = OperationDiff),
+            (status = 400),
+            (status = 404),
+            (status = 500)
+        )
+    )]
+async fn diff(
+    State(AppState {
+        mode, runtime_db, ..
+    }): State<AppState>,
+    Path(hash): Path<String>,
+) -> ServiceResult<Json<OperationDiff>> {
+    if let RunMode::Default = mode {
+        return Err(ServiceError::BadRequest(
+            "operation diff is only available in sequencer mode".to_string(),
+        ));
+    }
+    match read_db(&runtime_db, move |db| db.operation_diff(&hash)).await? {
+        Some(diff) => Ok(Json(diff)),
+        None => Err(ServiceError::NotFound),
+    }
+}
 
-    let receipt = match value {
-        Some(value) => Receipt::decode(value.as_slice())
-            .map_err(|_| anyhow!("Failed to deserialize receipt"))?,
-        None => Err(ServiceError::NotFound)?,
+/// Get the external messages quarantined by the kernel, oldest first
+///
+/// External messages are quarantined if they cannot be decoded as an operation or
+/// if their signature is invalid. Only the most recent dead letters are kept.
+#[utoipa::path(
+        get,
+        path = "/dead_letters",
+        tag = OPERATIONS_TAG,
+        responses(
+            (status = 200, body = Vec<DeadLetter>),
+            (status = 500)
+        )
+    )]
+async fn dead_letters(
+    State(AppState {
+        rollup_client,
+        mode,
+        runtime_db,
+        storage_sync,
+        storage_sync_db,
+        ..
+    }): State<AppState>,
+) -> ServiceResult<Json<Vec<DeadLetter>>> {
+    let store = StoreWrapper::new(
+        mode,
+        storage_sync,
+        rollup_client,
+        runtime_db,
+        storage_sync_db,
+    );
+    let count = match store.get_value(DEAD_LETTER_COUNT_KEY.to_string()).await? {
+        Some(value) => u64::decode(value.as_slice())
+            .map_err(|_| anyhow!("Failed to deserialize dead letter count"))?,
+        None => 0,
     };
 
-    Ok(Json(receipt))
+    let mut dead_letters = vec![];
+    for index in count.saturating_sub(DEAD_LETTER_CAPACITY)..count {
+        if let Some(value) = store.get_value(dead_letter_key(index)).await? {
+            let dead_letter = DeadLetter::decode(value.as_slice())
+                .map_err(|_| anyhow!("Failed to deserialize dead letter"))?;
+            dead_letters.push(dead_letter);
+        }
+    }
+
+    Ok(Json(dead_letters))
+}
+
+#[cfg(feature = "simulation")]
+#[derive(Serialize, ToSchema)]
+pub struct SimulationReceipt {
+    pub receipt: Receipt,
+    /// Logs of the smart functions called by the operation
+    pub logs: Vec<LogRecord>,
+}
+
+/// Simulate an operation against the current state of the sequencer
+///
+/// The operation is executed but not queued, and none of its effects are persisted.
+/// Large payload and oracle response operations cannot be simulated.
+#[cfg(feature = "simulation")]
+#[utoipa::path(
+        post,
+        path = "/simulate",
+        tag = OPERATIONS_TAG,
+        responses(
+            (status = 200, body = SimulationReceipt),
+            (status = 400),
+            (status = 500)
+        )
+    )]
+async fn simulate(
+    State(AppState {
+        rollup_preimages_dir,
+        mode,
+        runtime_db,
+        ..
+    }): State<AppState>,
+    Json(operation): Json<SignedOperation>,
+) -> ServiceResult<Json<SimulationReceipt>> {
+    if let RunMode::Default = mode {
+        return Err(ServiceError::BadRequest(
+            "simulating operations is not available in default mode".to_string(),
+        ));
+    }
+    operation
+        .verify()
+        .map_err(|e| ServiceError::BadRequest(format!("Invalid operation: {e}")))?;
+
+    let (receipt, debug_log) = tokio::task::spawn_blocking(move || {
+        simulate_operation(runtime_db, rollup_preimages_dir, operation)
+    })
+    .await
+    .context("simulation task failed")??;
+    let logs = debug_log
+        .lines()
+        .filter_map(|line| line.strip_prefix(LOG_PREFIX))
+        .filter_map(LogRecord::try_from_string)
+        .collect();
+
+    Ok(Json(SimulationReceipt { receipt, logs }))
+}
+
+/// A reason an operation would be rejected
+#[derive(Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ValidationError {
+    /// The signature does not match the operation and its public key
+    InvalidSignature,
+    /// The nonce is not the next nonce of the source account
+    InvalidNonce { expected: u64, actual: u64 },
+    /// The source account cannot afford the amount credited or transferred
+    InsufficientFunds { balance: u64, required: u64 },
+    /// The code of the deployed smart function does not parse
+    InvalidCode { reason: String },
+    /// The encoded operation exceeds the maximum operation size
+    TooLarge { size: usize, max_size: usize },
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OperationValidation {
+    /// Empty if the operation is valid
+    pub errors: Vec<ValidationError>,
+}
+
+/// Amount of mutez the operation takes from its source
+fn required_amount(content: &Content) -> u64 {
+    match content {
+        Content::DeployFunction(deploy) => deploy.account_credit,
+        Content::RunFunction(run) => run
+            .headers
+            .get(X_JSTZ_TRANSFER)
+            .and_then(|amount| amount.to_str().ok())
+            .and_then(|amount| amount.parse().ok())
+            .unwrap_or_default(),
+        _ => 0,
+    }
+}
+
+/// Validate an operation against the current state without executing it
+///
+/// Checks the signature, nonce, balance, size and, for deployments, that the code
+/// parses. The operation is neither executed nor queued.
+#[utoipa::path(
+        post,
+        path = "/validate",
+        tag = OPERATIONS_TAG,
+        responses(
+            (status = 200, body = OperationValidation),
+            (status = 400),
+            (status = 500)
+        )
+    )]
+async fn validate(
+    State(AppState {
+        rollup_client,
+        mode,
+        runtime_db,
+        storage_sync,
+        storage_sync_db,
+        ..
+    }): State<AppState>,
+    Json(operation): Json<SignedOperation>,
+) -> ServiceResult<Json<OperationValidation>> {
+    let store = StoreWrapper::new(
+        mode,
+        storage_sync,
+        rollup_client,
+        runtime_db,
+        storage_sync_db,
+    );
+    let mut errors = vec![];
+
+    if operation.verify().is_err() {
+        errors.push(ValidationError::InvalidSignature);
+    }
+
+    let source = operation.source().to_string();
+    let expected = get_account_nonce(&store, &source, None)
+        .await?
+        .unwrap_or_default();
+    if *operation.nonce() != expected {
+        errors.push(ValidationError::InvalidNonce {
+            expected: expected.0,
+            actual: operation.nonce().0,
+        });
+    }
+
+    let required = required_amount(operation.content());
+    if required > 0 {
+        let balance = get_account_balance(&store, &source, None)
+            .await?
+            .unwrap_or_default();
+        if balance < required {
+            errors.push(ValidationError::InsufficientFunds { balance, required });
+        }
+    }
+
+    if let Content::DeployFunction(deploy) = operation.content() {
+        let code = deploy.function_code.clone();
+        let parsed = tokio::task::spawn_blocking(move || ParsedCode::try_from(code))
+            .await
+            .context("code parsing task failed")?;
+        if let Err(e) = parsed {
+            errors.push(ValidationError::InvalidCode {
+                reason: e.to_string(),
+            });
+        }
+    }
+
+    let size = operation
+        .encode()
+        .map_err(|e| anyhow!("Failed to serialize operation: {e}"))?
+        .len();
+    if size > MAX_DECOMPRESSED_REVEAL_SIZE {
+        errors.push(ValidationError::TooLarge {
+            size,
+            max_size: MAX_DECOMPRESSED_REVEAL_SIZE,
+        });
+    }
+
+    Ok(Json(OperationValidation { errors }))
 }
 
 /// Returns the hex encoded hash of an Operation
@@ -381,8 +876,17 @@ impl Service for OperationsService {
         let routes = OpenApiRouter::new()
             .routes(routes!(inject))
             .routes(routes!(receipt))
+            .routes(routes!(receipt_proof))
+            .routes(routes!(receipt_attestation))
+            .routes(routes!(status))
+            .routes(routes!(diff))
+            .routes(routes!(dead_letters))
+            .routes(routes!(validate))
             .routes(routes!(hash_operation));
 
+        #[cfg(feature = "simulation")]
+        let routes = routes.routes(routes!(simulate));
+
         #[cfg(feature = "inject_inbox")]
         let routes = routes.route("/inbox", post(inject_inbox_messages));
 
@@ -395,21 +899,27 @@ mod tests {
 
     use std::borrow::BorrowMut;
     use std::path::PathBuf;
+    use std::sync::Arc;
     use std::{fs, path::Path};
 
     use axum::{
         body::Body,
         http::{HeaderMap, Method, Request, Uri},
     };
-    use jstz_core::reveal_data::MAX_REVEAL_SIZE;
+    use jstz_core::reveal_data::{MAX_DECOMPRESSED_REVEAL_SIZE, MAX_REVEAL_SIZE};
     use jstz_core::BinEncodable;
     use jstz_crypto::{
-        hash::Hash,
+        hash::{Blake2b, Hash},
         public_key::PublicKey,
         public_key_hash::PublicKeyHash,
         secret_key::SecretKey,
+        signature::Signature,
         smart_function_hash::{Kt1Hash, SmartFunctionHash},
     };
+    use jstz_kernel::inbox::{
+        dead_letter_key, DeadLetter, DeadLetterReason, DEAD_LETTER_CAPACITY,
+        DEAD_LETTER_COUNT_KEY,
+    };
     use jstz_mock::{kt1_account1, sr1_address};
     use jstz_proto::operation::{RevealLargePayload, RevealType};
     use jstz_proto::receipt::{ReceiptContent, ReceiptResult};
@@ -420,25 +930,36 @@ mod tests {
         receipt::{DeployFunctionReceipt, Receipt},
     };
     use jstz_utils::KeyPair;
-    use octez::OctezRollupClient;
+    use octez::{
+        mock::{verify_proof, MockRollupRpc},
+        OctezRollupClient,
+    };
     use tempfile::{NamedTempFile, TempDir};
     use tezos_crypto_rs::hash::ContractKt1Hash;
+    use tezos_smart_rollup::types::SmartRollupAddress;
     use tower::ServiceExt;
 
     use crate::config::RuntimeEnv;
+    use crate::sequencer::db::{
+        receipt_attestation_message, receipt_digest, OperationState,
+    };
     use crate::sequencer::queue::WrappedOperation;
     use crate::services::utils::StoreWrapper;
     use crate::{
         services::{
             error::ServiceError,
-            operations::{encode_operation, OperationsService},
+            operations::{
+                encode_operation, AttestedReceipt, OperationValidation,
+                OperationsService, ReceiptProof, ValidationError,
+            },
             Service,
         },
+        test::default_injector,
         utils::tests::{dummy_receipt, mock_app_state},
         RunMode,
     };
 
-    use super::MAX_DIRECT_OPERATION_SIZE;
+    use jstz_proto::operation::MAX_DIRECT_OPERATION_SIZE;
 
     fn bootstrap1() -> (PublicKeyHash, PublicKey, SecretKey) {
         (
@@ -460,6 +981,8 @@ mod tests {
             public_key: pk,
             nonce: Nonce(0),
             content,
+            max_fee: None,
+            priority_fee: None,
         };
         let sig = sk.sign(deploy_op.hash()).unwrap();
         SignedOperation::new(sig, deploy_op)
@@ -503,12 +1026,13 @@ mod tests {
         let operation = make_signed_op(Content::DeployFunction(DeployFunction {
             account_credit: Amount::default(),
             function_code: code,
+            interface: None,
         }));
         let key_pair = KeyPair(pk, sk);
         let temp_dir = tempfile::tempdir().unwrap();
-        let store = StoreWrapper::Rollup(client);
+        let store = StoreWrapper::Rollup(Arc::new(client));
         let result =
-            encode_operation(operation, &key_pair, &store, temp_dir.path()).await;
+            encode_operation(operation, &key_pair, &store, &[temp_dir.path()]).await;
         assert!(result.is_ok());
     }
 
@@ -532,11 +1056,12 @@ mod tests {
         let operation = make_signed_op(Content::DeployFunction(DeployFunction {
             account_credit: Amount::default(),
             function_code: code,
+            interface: None,
         }));
         let key_pair = KeyPair(pk, sk);
-        let store = StoreWrapper::Rollup(client);
+        let store = StoreWrapper::Rollup(Arc::new(client));
         let result =
-            encode_operation(operation, &key_pair, &store, temp_dir.path()).await;
+            encode_operation(operation, &key_pair, &store, &[temp_dir.path()]).await;
         assert!(result.is_ok());
         let dir_size = get_dir_size(temp_dir.path());
         assert!(
@@ -545,20 +1070,64 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn encodes_operation_larger_than_max_reveal_size_compressed() {
+        let (pkh, pk, sk) = bootstrap1();
+        let mut server = mockito::Server::new_async().await;
+        let url = format!(
+            "/global/block/head/durable/wasm_2_0_0/value?key=/jstz_account/{pkh}"
+        );
+        server
+            .mock("GET", url.as_str())
+            .with_status(200)
+            .with_body(r#""01000000000000000000000000000000000000000901000000000000636f6e7374204b4559203d2022636f756e746572223b0a0a636f6e73742068616e646c6572203d202829203d3e207b0a20206c657420636f756e746572203d204b762e676574284b4559293b0a2020636f6e736f6c652e6c6f672860436f756e7465723a20247b636f756e7465727d60293b0a202069662028636f756e746572203d3d3d206e756c6c29207b0a20202020636f756e746572203d20303b0a20207d20656c7365207b0a20202020636f756e7465722b2b3b0a20207d0a20204b762e736574284b45592c20636f756e746572293b0a202072657475726e206e657720526573706f6e736528293b0a7d3b0a0a6578706f72742064656661756c742068616e646c65723b0a""#)
+            .create();
+        let client = OctezRollupClient::new(server.url());
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let fallback_dir = tempfile::tempdir().unwrap();
+        let code = mock_code(MAX_REVEAL_SIZE + 1);
+        let code_size: u64 = code.len() as u64;
+        let operation = make_signed_op(Content::DeployFunction(DeployFunction {
+            account_credit: Amount::default(),
+            function_code: code,
+            interface: None,
+        }));
+        let key_pair = KeyPair(pk, sk);
+        let store = StoreWrapper::Rollup(Arc::new(client));
+        let (op, _) = encode_operation(
+            operation,
+            &key_pair,
+            &store,
+            &[temp_dir.path(), fallback_dir.path()],
+        )
+        .await
+        .unwrap();
+        assert!(matches!(op.content(), Content::RevealLargePayload(_)));
+        let dir_size = get_dir_size(temp_dir.path());
+        assert!(
+            dir_size > 0 && dir_size < code_size,
+            "Expected compressed preimages, but got size = {dir_size}"
+        );
+        // the preimages are written for every rollup node
+        assert_eq!(get_dir_size(fallback_dir.path()), dir_size);
+    }
+
     #[tokio::test]
     async fn encodes_operation_throws_if_operation_is_too_large() {
         let (_, pk, sk) = bootstrap1();
         let client = OctezRollupClient::new("http://localhost:8732".to_string());
-        let code = mock_code(MAX_REVEAL_SIZE + 1);
+        let code = mock_code(MAX_DECOMPRESSED_REVEAL_SIZE + 1);
         let operation = make_signed_op(Content::DeployFunction(DeployFunction {
             account_credit: Amount::default(),
             function_code: code,
+            interface: None,
         }));
         let key_pair = KeyPair(pk, sk);
         let temp_dir = tempfile::tempdir().unwrap();
-        let store = StoreWrapper::Rollup(client);
+        let store = StoreWrapper::Rollup(Arc::new(client));
         let result =
-            encode_operation(operation, &key_pair, &store, temp_dir.path()).await;
+            encode_operation(operation, &key_pair, &store, &[temp_dir.path()]).await;
         assert!(result.is_err());
     }
 
@@ -580,11 +1149,12 @@ mod tests {
         let operation = make_signed_op(Content::DeployFunction(DeployFunction {
             account_credit: Amount::default(),
             function_code: code,
+            interface: None,
         }));
         let key_pair = KeyPair(pk, sk);
-        let store = StoreWrapper::Rollup(client);
+        let store = StoreWrapper::Rollup(Arc::new(client));
         let result =
-            encode_operation(operation, &key_pair, &store, Path::new("invalid path"))
+            encode_operation(operation, &key_pair, &store, &[Path::new("invalid path")])
                 .await;
         assert!(result.is_err_and(|e| {
             matches!(
@@ -635,14 +1205,89 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn inject_sequencer() {
+    async fn inject_default_mock_rollup() {
+        let rpc = Arc::new(MockRollupRpc::new(SmartRollupAddress::new(sr1_address())));
         let db_file = NamedTempFile::new().unwrap();
-        let state = mock_app_state(
+        let mut state = mock_app_state(
             "",
             PathBuf::default(),
             db_file.path().to_str().unwrap(),
-            RunMode::Sequencer {
-                capacity: 0,
+            RunMode::Default,
+        )
+        .await;
+        state.rollup_client = rpc.clone();
+        let (router, _) = OperationsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let res = router
+            .oneshot(inject_operation_request(make_signed_op(
+                Content::RunFunction(RunFunction {
+                    uri: Uri::from_static("http://http://"),
+                    method: Method::HEAD,
+                    headers: HeaderMap::new(),
+                    body: HttpBody::empty(),
+                    gas_limit: 0,
+                }),
+            )))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(rpc.injected_messages().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn receipt_proof_mock_rollup() {
+        let rpc = Arc::new(MockRollupRpc::new(SmartRollupAddress::new(sr1_address())));
+        let receipt = dummy_receipt(kt1_account1());
+        let encoded = receipt.encode().unwrap();
+        rpc.insert("/jstz_receipt/foo", encoded.clone());
+        let db_file = NamedTempFile::new().unwrap();
+        let mut state = mock_app_state(
+            "",
+            PathBuf::default(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Default,
+        )
+        .await;
+        state.rollup_client = rpc;
+        let (router, _) = OperationsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let get = |uri: &'static str| {
+            router
+                .clone()
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+        };
+
+        let res = get("/operations/foo/receipt/proof").await.unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 10000).await.unwrap();
+        let ReceiptProof {
+            receipt: got,
+            proof,
+        } = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(got.hash(), receipt.hash());
+        assert_eq!(proof.value, hex::encode(&encoded));
+        assert!(verify_proof(
+            &proof.state_hash,
+            "/jstz_receipt/foo",
+            &encoded,
+            &hex::decode(proof.proof).unwrap()
+        ));
+
+        let res = get("/operations/bar/receipt/proof").await.unwrap();
+        assert_eq!(res.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn inject_sequencer() {
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::default(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
                 debug_log_path: NamedTempFile::new().unwrap().path().to_path_buf(),
                 runtime_env: RuntimeEnv::Native,
                 inbox_checkpoint_path: NamedTempFile::new().unwrap().path().to_path_buf(),
@@ -669,17 +1314,101 @@ mod tests {
             .await
             .unwrap();
         assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let hash = serde_json::from_slice::<String>(&bytes).unwrap();
+        assert_eq!(hash, dummy_op.hash().to_string());
         assert_eq!(queue.read().unwrap().len(), 1);
 
-        // sending the operation again should fail because the queue is full
+        // sending the operation again returns its hash without queueing it again
         let res = router
             .borrow_mut()
             .oneshot(inject_operation_request(dummy_op))
             .await
             .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        assert_eq!(serde_json::from_slice::<String>(&bytes).unwrap(), hash);
+        assert_eq!(queue.read().unwrap().len(), 1);
+
+        // sending another operation should fail because the queue is full
+        let other_op = make_signed_op(Content::RunFunction(RunFunction {
+            uri: Uri::from_static("http://http://"),
+            method: Method::GET,
+            headers: HeaderMap::new(),
+            body: HttpBody::empty(),
+            gas_limit: 0,
+        }));
+        let res = router
+            .borrow_mut()
+            .oneshot(inject_operation_request(other_op))
+            .await
+            .unwrap();
         assert_eq!(res.status(), 503);
     }
 
+    #[tokio::test]
+    async fn inject_replica() {
+        let mut server = mockito::Server::new_async().await;
+        let dummy_op = make_signed_op(Content::RunFunction(RunFunction {
+            uri: Uri::from_static("http://http://"),
+            method: Method::HEAD,
+            headers: HeaderMap::new(),
+            body: HttpBody::empty(),
+            gas_limit: 0,
+        }));
+        let mock_primary = server
+            .mock("POST", "/operations")
+            .match_header("authorization", "Bearer secret")
+            .match_body(mockito::Matcher::Json(
+                serde_json::to_value(&dummy_op).unwrap(),
+            ))
+            .with_body(serde_json::to_string(&dummy_op.hash().to_string()).unwrap())
+            .create();
+
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::default(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Replica {
+                primary_endpoint: server.url(),
+            },
+        )
+        .await;
+        let queue = state.queue.clone();
+        let (mut router, _) = OperationsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let mut request = inject_operation_request(dummy_op.clone());
+        request
+            .headers_mut()
+            .insert("authorization", "Bearer secret".parse().unwrap());
+        let res = router.borrow_mut().oneshot(request).await.unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<String>(&bytes).unwrap(),
+            dummy_op.hash().to_string()
+        );
+        mock_primary.assert();
+        assert_eq!(queue.read().unwrap().len(), 0);
+
+        // errors of the primary are returned as is
+        server
+            .mock("POST", "/operations")
+            .with_status(400)
+            .with_body(r#"{"error":"invalid nonce"}"#)
+            .create();
+        let res = router
+            .borrow_mut()
+            .oneshot(inject_operation_request(dummy_op))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 400);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        assert_eq!(bytes, r#"{"error":"invalid nonce"}"#);
+    }
+
     #[tokio::test]
     async fn inject_large_operation_sequencer() {
         let db_file = NamedTempFile::new().unwrap();
@@ -706,6 +1435,7 @@ mod tests {
         let dummy_op = make_signed_op(Content::DeployFunction(DeployFunction {
             function_code: "a".repeat(4000),
             account_credit: 0,
+            interface: None,
         }));
         let res = router
             .borrow_mut()
@@ -714,7 +1444,7 @@ mod tests {
             .unwrap();
         assert_eq!(res.status(), 200);
         assert_eq!(queue.read().unwrap().len(), 1);
-        let injected_op = match queue.write().unwrap().pop().unwrap() {
+        let injected_op = match queue.write().unwrap().pop().unwrap().operation {
             WrappedOperation::FromNode(op) => op,
             _ => panic!("invalid message type"),
         };
@@ -826,4 +1556,456 @@ mod tests {
             .unwrap();
         assert_eq!(res.status(), 404);
     }
+
+    #[tokio::test]
+    async fn get_receipt_attestation() {
+        let receipt = dummy_receipt(kt1_account1());
+        let encoded = receipt.encode().unwrap();
+        let op_hash = receipt.hash().to_string();
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::default(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: PathBuf::new(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: PathBuf::new(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        let injector = default_injector();
+        state
+            .runtime_db
+            .write(&format!("/jstz_receipt/{op_hash}"), &hex::encode(&encoded))
+            .unwrap();
+        state
+            .runtime_db
+            .attest_receipt(&op_hash, &encoded, &injector)
+            .unwrap();
+
+        let (router, _) = OperationsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let get = |uri: String| {
+            router.clone().oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .method("GET")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        let res = get(format!("/operations/{op_hash}/receipt/attestation"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 10000).await.unwrap();
+        let AttestedReceipt {
+            receipt: got,
+            public_key,
+            attestation,
+        } = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(public_key, injector.0.to_string());
+        // the digest is recomputed from the receipt and signed by the sequencer
+        let digest = receipt_digest(&got.encode().unwrap());
+        assert_eq!(attestation.digest, digest.to_string());
+        Signature::from_base58(&attestation.signature)
+            .unwrap()
+            .verify(&injector.0, &receipt_attestation_message(&digest))
+            .unwrap();
+        // the signature does not verify as a signature of the bare digest
+        assert!(Signature::from_base58(&attestation.signature)
+            .unwrap()
+            .verify(&injector.0, digest.as_ref())
+            .is_err());
+
+        let res = get("/operations/foo/receipt/attestation".to_string())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 404);
+
+        // not available in default mode
+        let state = mock_app_state("", PathBuf::new(), "", RunMode::Default).await;
+        let (router, _) = OperationsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let res = router
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/operations/{op_hash}/receipt/attestation"))
+                    .method("GET")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn get_status_sequencer() {
+        let op_hash = "9b15976cc8162fe39458739de340a1a95c59a9bcff73bd3c83402fad6352396e";
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::default(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: NamedTempFile::new().unwrap().path().to_path_buf(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: NamedTempFile::new().unwrap().path().to_path_buf(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        state
+            .runtime_db
+            .set_operation_state(op_hash, OperationState::Included, Some(12))
+            .unwrap();
+        state
+            .runtime_db
+            .set_operation_state(op_hash, OperationState::Committed, None)
+            .unwrap();
+
+        let (mut router, _) = OperationsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+
+        let res = router
+            .borrow_mut()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/operations/{op_hash}/status"))
+                    .method("GET")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let status = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap();
+        assert_eq!(status["state"], "committed");
+        assert_eq!(status["level"], 12);
+
+        // unknown operation
+        let res = router
+            .borrow_mut()
+            .oneshot(
+                Request::builder()
+                    .uri("/operations/bad_hash/status")
+                    .method("GET")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn get_diff_sequencer() {
+        let op_hash = "9b15976cc8162fe39458739de340a1a95c59a9bcff73bd3c83402fad6352396e";
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::default(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: NamedTempFile::new().unwrap().path().to_path_buf(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: NamedTempFile::new().unwrap().path().to_path_buf(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        {
+            let conn = state.runtime_db.connection().unwrap();
+            crate::sequencer::db::exec_record_write(&conn, None, "/foo", "01").unwrap();
+        }
+        state
+            .runtime_db
+            .commit_journal(None, Some(op_hash), None)
+            .unwrap();
+
+        let (mut router, _) = OperationsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+
+        let res = router
+            .borrow_mut()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/operations/{op_hash}/diff"))
+                    .method("GET")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let diff = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap();
+        assert_eq!(
+            diff,
+            serde_json::json!({"updates": [{"key": "/foo", "value": "01"}]})
+        );
+
+        // unknown operation
+        let res = router
+            .borrow_mut()
+            .oneshot(
+                Request::builder()
+                    .uri("/operations/bad_hash/diff")
+                    .method("GET")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn validate_operation() {
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::default(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: NamedTempFile::new().unwrap().path().to_path_buf(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: NamedTempFile::new().unwrap().path().to_path_buf(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        let (mut router, _) = OperationsService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        async fn validate(
+            router: &mut axum::Router,
+            op: SignedOperation,
+        ) -> Vec<ValidationError> {
+            let res = router
+                .oneshot(
+                    Request::builder()
+                        .uri("/operations/validate")
+                        .method("POST")
+                        .header("content-type", "application/json")
+                        .body(Body::from(serde_json::to_string(&op).unwrap()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(res.status(), 200);
+            let bytes = axum::body::to_bytes(res.into_body(), 10000).await.unwrap();
+            serde_json::from_slice::<OperationValidation>(&bytes)
+                .unwrap()
+                .errors
+        }
+
+        let valid = make_signed_op(Content::DeployFunction(DeployFunction {
+            function_code: "export default () => new Response();".to_string(),
+            account_credit: 0,
+            interface: None,
+        }));
+        let signature = bootstrap1().2.sign(valid.hash()).unwrap();
+        assert!(validate(router.borrow_mut(), valid).await.is_empty());
+
+        // signed with the signature of another operation
+        let (_, pk, _) = bootstrap1();
+        let invalid = SignedOperation::new(
+            signature,
+            Operation {
+                public_key: pk,
+                nonce: Nonce(3),
+                content: Content::DeployFunction(DeployFunction {
+                    function_code: "export default () =>".to_string(),
+                    account_credit: 10,
+                    interface: None,
+                }),
+                max_fee: None,
+                priority_fee: None,
+            },
+        );
+        let errors = validate(router.borrow_mut(), invalid).await;
+        assert_eq!(errors.len(), 4);
+        assert_eq!(errors[0], ValidationError::InvalidSignature);
+        assert_eq!(
+            errors[1],
+            ValidationError::InvalidNonce {
+                expected: 0,
+                actual: 3
+            }
+        );
+        assert_eq!(
+            errors[2],
+            ValidationError::InsufficientFunds {
+                balance: 0,
+                required: 10
+            }
+        );
+        assert!(matches!(errors[3], ValidationError::InvalidCode { .. }));
+    }
+
+    #[tokio::test]
+    async fn get_dead_letters_sequencer() {
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::default(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: NamedTempFile::new().unwrap().path().to_path_buf(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: NamedTempFile::new().unwrap().path().to_path_buf(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        let (mut router, _) = OperationsService::router_with_openapi()
+            .with_state(state.clone())
+            .split_for_parts();
+        let get_dead_letters = || {
+            Request::builder()
+                .uri("/operations/dead_letters")
+                .method("GET")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // no dead letters
+        let res = router
+            .borrow_mut()
+            .oneshot(get_dead_letters())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let dead_letters = serde_json::from_slice::<Vec<DeadLetter>>(&bytes).unwrap();
+        assert!(dead_letters.is_empty());
+
+        // the oldest dead letters are overwritten once the capacity is reached
+        let count = DEAD_LETTER_CAPACITY + 2;
+        for index in 0..count {
+            let dead_letter = DeadLetter {
+                hash: Blake2b::from(index.to_string().as_bytes()),
+                reason: DeadLetterReason::MalformedOperation,
+                level: index as u32,
+            };
+            state
+                .runtime_db
+                .write(
+                    &dead_letter_key(index),
+                    &hex::encode(dead_letter.encode().unwrap()),
+                )
+                .unwrap();
+        }
+        state
+            .runtime_db
+            .write(DEAD_LETTER_COUNT_KEY, &hex::encode(count.encode().unwrap()))
+            .unwrap();
+
+        let res = router
+            .borrow_mut()
+            .oneshot(get_dead_letters())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 100_000)
+            .await
+            .unwrap();
+        let dead_letters = serde_json::from_slice::<Vec<DeadLetter>>(&bytes).unwrap();
+        assert_eq!(dead_letters.len() as u64, DEAD_LETTER_CAPACITY);
+        assert_eq!(dead_letters.first().unwrap().level, 2);
+        assert_eq!(dead_letters.last().unwrap().level, count as u32 - 1);
+    }
+
+    #[cfg(feature = "simulation")]
+    #[tokio::test]
+    async fn simulate_sequencer() {
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::default(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: NamedTempFile::new().unwrap().path().to_path_buf(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: NamedTempFile::new().unwrap().path().to_path_buf(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        crate::sequencer::runtime::init_host(
+            state.runtime_db.clone(),
+            PathBuf::default(),
+            &state.injector,
+        )
+        .unwrap();
+        let (mut router, _) = OperationsService::router_with_openapi()
+            .with_state(state.clone())
+            .split_for_parts();
+        let simulate = |body: String| {
+            Request::builder()
+                .uri("/operations/simulate")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let op = crate::sequencer::tests::dummy_signed_op();
+        let res = router
+            .borrow_mut()
+            .oneshot(simulate(serde_json::to_string(&op).unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 100_000)
+            .await
+            .unwrap();
+        let body = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap();
+        let receipt = serde_json::from_value::<Receipt>(body["receipt"].clone()).unwrap();
+        assert!(matches!(
+            receipt.result,
+            ReceiptResult::Success(ReceiptContent::DeployFunction(_))
+        ));
+        // nothing is queued nor written
+        assert_eq!(state.queue.read().unwrap().len(), 0);
+        assert!(!state
+            .runtime_db
+            .key_exists(&format!("/jstz_receipt/{}", op.hash()))
+            .unwrap());
+
+        // the signature is checked
+        let operation: Operation = op.into();
+        let forged = SignedOperation::new(
+            jstz_mock::sk1().sign(operation.hash()).unwrap(),
+            operation,
+        );
+        let res = router
+            .borrow_mut()
+            .oneshot(simulate(serde_json::to_string(&forged).unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 400);
+    }
 }
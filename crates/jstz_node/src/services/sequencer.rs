@@ -0,0 +1,409 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use super::{
+    error::{ServiceError, ServiceResult},
+    utils::read_db,
+    Service,
+};
+use crate::{
+    sequencer::db::{JournalEntry, KeyUpdate, OrderingEntry},
+    AppState, RunMode,
+};
+
+const SEQUENCER_TAG: &str = "Sequencer";
+
+/// Number of journal entries returned if no limit is given
+pub const DEFAULT_JOURNAL_LIMIT: usize = 100;
+
+/// Maximum number of journal entries returned at once
+pub const MAX_JOURNAL_LIMIT: usize = 1000;
+
+pub struct SequencerService;
+
+#[derive(Deserialize, IntoParams)]
+struct OrderingQuery {
+    /// L1 level of the entries
+    level: u32,
+}
+
+#[derive(Deserialize, IntoParams)]
+struct JournalQuery {
+    /// Sequence number of the first entry, 0 by default
+    from: Option<u64>,
+    /// Maximum number of entries to return, 100 by default and at most 1000
+    limit: Option<usize>,
+}
+
+/// An entry of the journal of the sequencer: the storage writes of an executed
+/// operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct JournalUpdate {
+    pub seq: u64,
+    /// Hash of the operation that made the writes, if any
+    pub operation_hash: Option<String>,
+    /// The keys written by the operation, in lexicographic order
+    pub updates: Vec<KeyUpdate>,
+    /// The values the operation wrote to the keys, when they differ from `updates`
+    /// because operations executed concurrently overwrote them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation_updates: Option<Vec<KeyUpdate>>,
+}
+
+impl From<JournalEntry> for JournalUpdate {
+    fn from(entry: JournalEntry) -> Self {
+        Self {
+            seq: entry.seq,
+            operation_hash: entry.operation_hash,
+            updates: entry
+                .diff
+                .into_iter()
+                .map(|(key, value)| KeyUpdate { key, value })
+                .collect(),
+            operation_updates: entry.operation_diff.map(|diff| {
+                diff.into_iter()
+                    .map(|(key, value)| KeyUpdate { key, value })
+                    .collect()
+            }),
+        }
+    }
+}
+
+impl From<JournalUpdate> for JournalEntry {
+    fn from(update: JournalUpdate) -> Self {
+        Self {
+            seq: update.seq,
+            operation_hash: update.operation_hash,
+            diff: update
+                .updates
+                .into_iter()
+                .map(|KeyUpdate { key, value }| (key, value))
+                .collect(),
+            operation_diff: update.operation_updates.map(|updates| {
+                updates
+                    .into_iter()
+                    .map(|KeyUpdate { key, value }| (key, value))
+                    .collect()
+            }),
+        }
+    }
+}
+
+/// Entries of the ordering log of an L1 level
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct OrderingLog {
+    /// Base58-encoded public key of the sequencer, which signs the entries
+    pub public_key: String,
+    /// The entries of the level, in execution order
+    pub entries: Vec<OrderingEntry>,
+}
+
+/// Get the ordering log of an L1 level
+///
+/// Returns the operations the sequencer executed at the level, with their position
+/// in the execution order. Each entry is chained to the previous one and signed by
+/// the sequencer, so that third parties can audit that operations were executed in
+/// the order the sequencer committed to. Only available in sequencer mode.
+#[utoipa::path(
+    get,
+    params(OrderingQuery),
+    path = "/ordering",
+    tag = SEQUENCER_TAG,
+    responses(
+        (status = 200, body = OrderingLog),
+        (status = 400),
+        (status = 500)
+    )
+)]
+async fn get_ordering(
+    State(AppState {
+        mode,
+        injector,
+        runtime_db,
+        ..
+    }): State<AppState>,
+    Query(OrderingQuery { level }): Query<OrderingQuery>,
+) -> ServiceResult<Json<OrderingLog>> {
+    if !matches!(mode, RunMode::Sequencer { .. }) {
+        return Err(ServiceError::BadRequest(
+            "ordering log is only available in sequencer mode".to_string(),
+        ));
+    }
+    let entries = read_db(&runtime_db, move |db| db.ordering_entries(level)).await?;
+    Ok(Json(OrderingLog {
+        public_key: injector.0.to_string(),
+        entries,
+    }))
+}
+
+/// Get the journal of the sequencer storage
+///
+/// Returns the storage writes of the executed operations, in execution order,
+/// starting from sequence number `from`. Replicas follow the storage of the
+/// sequencer by requesting the entries after the last one they applied. Not
+/// available in default mode.
+#[utoipa::path(
+    get,
+    params(JournalQuery),
+    path = "/journal",
+    tag = SEQUENCER_TAG,
+    responses(
+        (status = 200, body = Vec<JournalUpdate>),
+        (status = 400),
+        (status = 500)
+    )
+)]
+async fn get_journal(
+    State(AppState {
+        mode, runtime_db, ..
+    }): State<AppState>,
+    Query(JournalQuery { from, limit }): Query<JournalQuery>,
+) -> ServiceResult<Json<Vec<JournalUpdate>>> {
+    if let RunMode::Default = mode {
+        return Err(ServiceError::BadRequest(
+            "journal is not available in default mode".to_string(),
+        ));
+    }
+    let limit = limit.unwrap_or(DEFAULT_JOURNAL_LIMIT);
+    if limit > MAX_JOURNAL_LIMIT {
+        return Err(ServiceError::BadRequest(format!(
+            "At most {MAX_JOURNAL_LIMIT} journal entries can be queried at once"
+        )));
+    }
+    let entries = read_db(&runtime_db, move |db| {
+        db.journal_entries(from.unwrap_or_default(), limit)
+    })
+    .await?;
+    Ok(Json(entries.into_iter().map(JournalUpdate::from).collect()))
+}
+
+impl Service for SequencerService {
+    fn router_with_openapi() -> OpenApiRouter<AppState> {
+        let routes = OpenApiRouter::new()
+            .routes(routes!(get_ordering))
+            .routes(routes!(get_journal));
+
+        OpenApiRouter::new().nest("/sequencer", routes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use axum::{body::Body, extract::Request};
+    use jstz_crypto::{hash::Blake2b, signature::Signature};
+    use jstz_mock::{kt1_account1, sr1_address};
+    use jstz_proto::operation::internal::InboxId;
+    use tempfile::NamedTempFile;
+    use tower::ServiceExt;
+
+    use crate::{
+        config::RuntimeEnv,
+        sequencer::db::{ordering_digest, KeyUpdate},
+        services::{
+            sequencer::{
+                JournalUpdate, OrderingLog, SequencerService, MAX_JOURNAL_LIMIT,
+            },
+            Service,
+        },
+        test::default_injector,
+        utils::tests::mock_app_state,
+        RunMode,
+    };
+
+    #[tokio::test]
+    async fn get_ordering_sequencer() {
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::new(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: PathBuf::new(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: PathBuf::new(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        let db = &state.runtime_db;
+        let injector = default_injector();
+        let inbox_id = InboxId {
+            l1_level: 5,
+            l1_message_id: 2,
+        };
+        let seq = db.enqueue("00", Some(&inbox_id)).unwrap();
+        db.commit_ordered_journal(None, "op1", seq, &injector)
+            .unwrap();
+        // operations injected in the node take the level of the previous entry
+        let seq = db.enqueue("01", None).unwrap();
+        db.commit_ordered_journal(None, "op2", seq, &injector)
+            .unwrap();
+        let seq = db
+            .enqueue(
+                "02",
+                Some(&InboxId {
+                    l1_level: 6,
+                    l1_message_id: 0,
+                }),
+            )
+            .unwrap();
+        db.commit_ordered_journal(None, "op3", seq, &injector)
+            .unwrap();
+
+        let (router, _) = SequencerService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let get = |uri: &str| {
+            router.clone().oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .method("GET")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        let res = get("/sequencer/ordering?level=5").await.unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 10000).await.unwrap();
+        let log = serde_json::from_slice::<OrderingLog>(&bytes).unwrap();
+        assert_eq!(log.public_key, injector.0.to_string());
+        let entries = log
+            .entries
+            .iter()
+            .map(|e| (e.position, e.operation_hash.as_str(), e.level))
+            .collect::<Vec<_>>();
+        assert_eq!(entries, [(1, "op1", 5), (2, "op2", 5)]);
+
+        // entries are chained and signed by the sequencer
+        let mut previous: Option<Blake2b> = None;
+        for entry in &log.entries {
+            let digest = ordering_digest(
+                previous.as_ref(),
+                entry.position,
+                entry.level,
+                &entry.operation_hash,
+            );
+            assert_eq!(entry.digest, digest.to_string());
+            Signature::from_base58(&entry.signature)
+                .unwrap()
+                .verify(&injector.0, digest.as_ref())
+                .unwrap();
+            previous = Some(digest);
+        }
+
+        let res = get("/sequencer/ordering?level=6").await.unwrap();
+        let bytes = axum::body::to_bytes(res.into_body(), 10000).await.unwrap();
+        let log = serde_json::from_slice::<OrderingLog>(&bytes).unwrap();
+        assert_eq!(log.entries.len(), 1);
+        assert_eq!(log.entries[0].position, 3);
+
+        let res = get("/sequencer/ordering?level=7").await.unwrap();
+        let bytes = axum::body::to_bytes(res.into_body(), 10000).await.unwrap();
+        let log = serde_json::from_slice::<OrderingLog>(&bytes).unwrap();
+        assert!(log.entries.is_empty());
+
+        // missing level
+        let res = get("/sequencer/ordering").await.unwrap();
+        assert_eq!(res.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn get_ordering_default() {
+        let state = mock_app_state("", PathBuf::new(), "", RunMode::Default).await;
+        let (router, _) = SequencerService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let res = router
+            .oneshot(
+                Request::builder()
+                    .uri("/sequencer/ordering?level=1")
+                    .method("GET")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn get_journal_sequencer() {
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::new(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: PathBuf::new(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: PathBuf::new(),
+                ticketer_address: kt1_account1(),
+                rollup_address: sr1_address(),
+            },
+        )
+        .await;
+        let conn = state.runtime_db.connection().unwrap();
+        for (hash, value) in [("op1", "01"), ("op2", "02")] {
+            crate::sequencer::db::exec_record_write(&conn, None, "/foo", value).unwrap();
+            state
+                .runtime_db
+                .commit_journal(None, Some(hash), None)
+                .unwrap();
+        }
+        let (router, _) = SequencerService::router_with_openapi()
+            .with_state(state)
+            .split_for_parts();
+        let get = |uri: &str| {
+            router.clone().oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .method("GET")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        let res = get("/sequencer/journal?from=2").await.unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 10000).await.unwrap();
+        let entries = serde_json::from_slice::<Vec<JournalUpdate>>(&bytes).unwrap();
+        assert_eq!(
+            entries,
+            [JournalUpdate {
+                seq: 2,
+                operation_hash: Some("op2".to_string()),
+                updates: vec![KeyUpdate {
+                    key: "/foo".to_string(),
+                    value: Some("02".to_string()),
+                }],
+                operation_updates: None,
+            }]
+        );
+
+        let res = get("/sequencer/journal?limit=1").await.unwrap();
+        let bytes = axum::body::to_bytes(res.into_body(), 10000).await.unwrap();
+        let entries = serde_json::from_slice::<Vec<JournalUpdate>>(&bytes).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].seq, 1);
+
+        // limit too large
+        let res = get(&format!(
+            "/sequencer/journal?limit={}",
+            MAX_JOURNAL_LIMIT + 1
+        ))
+        .await
+        .unwrap();
+        assert_eq!(res.status(), 400);
+    }
+}
@@ -1,9 +1,19 @@
-use std::sync::Arc;
+use std::sync::{atomic::Ordering, Arc};
 
-use crate::{sequencer::db::Db, services::AppState, RunMode};
+use crate::{
+    sequencer::db::Db,
+    services::{
+        error::{ServiceError, ServiceResult},
+        AppState,
+    },
+    RunMode,
+};
 use anyhow::Context;
-use axum::{extract::State, http::StatusCode, response::IntoResponse};
-use octez::OctezRollupClient;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use jstz_proto::constants::Constants;
+use octez::{r#async::rollup::log::RollupNodeHealthReport, EndpointStatus, RollupRpc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 pub async fn get_mode(
     State(AppState { mode, .. }): State<AppState>,
@@ -13,15 +23,176 @@ pub async fn get_mode(
         .into_response()
 }
 
+#[derive(Serialize)]
+pub struct WorkerHealth {
+    healthy: bool,
+    /// Number of times the worker died and was restarted
+    restarts: u64,
+    /// Number of times the worker was recycled for exceeding its resource limits
+    recycles: u64,
+    /// Heap memory of the worker, in bytes, as last sampled by the watchdog
+    memory_bytes: u64,
+    /// CPU usage of the worker thread, in percent of a core, as last sampled by the
+    /// watchdog
+    cpu_percent: u64,
+    /// Number of receipts the worker executed but failed to sign, which have no
+    /// attestation
+    attestation_failures: u64,
+}
+
 pub async fn worker_health(State(state): State<AppState>) -> impl IntoResponse {
-    match state.is_worker_healthy() {
+    let healthy = state.is_worker_healthy();
+    let status = match healthy {
         true => StatusCode::OK,
         false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    let stats = &state.worker_stats;
+    (
+        status,
+        Json(WorkerHealth {
+            healthy,
+            restarts: state.worker_restarts.load(Ordering::Relaxed),
+            recycles: stats.recycles.load(Ordering::Relaxed),
+            memory_bytes: stats.memory_bytes.load(Ordering::Relaxed),
+            cpu_percent: stats.cpu_percent.load(Ordering::Relaxed),
+            attestation_failures: stats.attestation_failures.load(Ordering::Relaxed),
+        }),
+    )
+}
+
+#[derive(Serialize)]
+pub struct Health {
+    /// Status of the rollup node endpoints, in order of preference
+    rollup_endpoints: Vec<EndpointStatus>,
+}
+
+pub async fn health(State(state): State<AppState>) -> (StatusCode, Json<Health>) {
+    let status = match &state.rollup_failover {
+        Some(client) if !client.is_healthy() => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::OK,
+    };
+    (
+        status,
+        Json(Health {
+            rollup_endpoints: state
+                .rollup_failover
+                .as_ref()
+                .map(|client| client.status())
+                .unwrap_or_default(),
+        }),
+    )
+}
+
+#[derive(Serialize)]
+pub struct HealthDetails {
+    worker_healthy: bool,
+    /// Number of receipts the worker failed to sign, see [`WorkerHealth`]
+    attestation_failures: u64,
+    rollup: Option<RollupNodeHealthReport>,
+}
+
+pub async fn health_details(State(state): State<AppState>) -> Json<HealthDetails> {
+    // Report the rollup node the node currently reads from
+    let active = state
+        .rollup_failover
+        .as_ref()
+        .map(|client| client.active())
+        .unwrap_or_default();
+    Json(HealthDetails {
+        worker_healthy: state.is_worker_healthy(),
+        attestation_failures: state
+            .worker_stats
+            .attestation_failures
+            .load(Ordering::Relaxed),
+        rollup: state
+            .rollup_log_monitors
+            .get(active)
+            .and_then(Option::as_ref)
+            .map(|m| m.health().report()),
+    })
+}
+
+pub async fn constants(State(state): State<AppState>) -> Json<Constants> {
+    Json(Constants {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        queue_capacity: state.queue.read().map(|q| q.capacity()).unwrap_or_default(),
+        ..Constants::default()
+    })
+}
+
+pub async fn pause_worker(State(state): State<AppState>) -> ServiceResult<()> {
+    set_worker_paused(&state, true)
+}
+
+pub async fn resume_worker(State(state): State<AppState>) -> ServiceResult<()> {
+    set_worker_paused(&state, false)
+}
+
+/// Stops or restarts the execution of queued operations by the sequencer worker
+fn set_worker_paused(state: &AppState, paused: bool) -> ServiceResult<()> {
+    if !matches!(state.mode, RunMode::Sequencer { .. }) {
+        return Err(ServiceError::BadRequest(
+            "the worker only runs in sequencer mode".to_string(),
+        ));
     }
+    state.worker_paused.set(paused);
+    Ok(())
+}
+
+/// Merkle proof of a value of the durable storage of the rollup node, which light
+/// clients verify against the state hash committed on L1
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct StorageProof {
+    /// Hash of the rollup node block the value was read from
+    pub block_hash: String,
+    /// L1 level of the block
+    pub level: u32,
+    /// Hash of the PVM state of the block, which the commitments published on L1
+    /// refer to
+    pub state_hash: String,
+    /// Hex-encoded value, as stored in the durable storage
+    pub value: String,
+    /// Hex-encoded Merkle proof of the value against `state_hash`
+    pub proof: String,
+}
+
+impl StorageProof {
+    /// Reads the value of `key` with its proof from the rollup node, returning the
+    /// value with the proof. Proofs are always read from the rollup node at its last
+    /// commitment, even if the node serves a more recent state, so the value can lag
+    /// behind the other endpoints.
+    pub async fn read(
+        rollup_client: &dyn RollupRpc,
+        key: &str,
+    ) -> ServiceResult<(Vec<u8>, Self)> {
+        let Some(proof) = rollup_client.get_value_proof(key).await? else {
+            return Err(ServiceError::NotFound);
+        };
+        let storage_proof = Self {
+            block_hash: proof.block_hash,
+            level: proof.level,
+            state_hash: proof.state_hash,
+            value: hex::encode(&proof.value),
+            proof: hex::encode(&proof.proof),
+        };
+        Ok((proof.value, storage_proof))
+    }
+}
+
+/// Runs `read` against `db` on the blocking thread pool, since database reads block
+pub async fn read_db<T, F>(db: &Db, read: F) -> anyhow::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Db) -> anyhow::Result<T> + Send + 'static,
+{
+    let db = db.clone();
+    tokio::task::spawn_blocking(move || read(&db))
+        .await
+        .context("failed to wait for db read task")?
 }
 
 pub enum StoreWrapper {
-    Rollup(OctezRollupClient),
+    Rollup(Arc<dyn RollupRpc>),
     Db(Arc<Db>),
 }
 
@@ -29,14 +200,16 @@ impl StoreWrapper {
     pub fn new(
         mode: RunMode,
         storage_sync: bool,
-        rollup_client: OctezRollupClient,
+        rollup_client: Arc<dyn RollupRpc>,
         runtime_db: Db,
         storage_sync_db: Db,
     ) -> Self {
         match (mode, storage_sync) {
             (RunMode::Default, false) => Self::Rollup(rollup_client),
             (RunMode::Default, true) => Self::Db(Arc::new(storage_sync_db)),
-            (RunMode::Sequencer { .. }, _) => Self::Db(Arc::new(runtime_db)),
+            (RunMode::Sequencer { .. } | RunMode::Replica { .. }, _) => {
+                Self::Db(Arc::new(runtime_db))
+            }
         }
     }
 
@@ -57,36 +230,122 @@ impl StoreWrapper {
             }
         })
     }
+
+    /// Reads the immediate subkeys of `key` that start with `prefix`, in
+    /// lexicographic order and up to `limit` subkeys, see [`Db::find_subkeys`].
+    pub async fn get_subkeys(
+        &self,
+        key: String,
+        prefix: Option<String>,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Option<Vec<String>>> {
+        Ok(match self {
+            Self::Rollup(rollup_client) => {
+                rollup_client.get_subkeys(&key).await?.map(|mut subkeys| {
+                    if let Some(prefix) = prefix {
+                        subkeys.retain(|subkey| subkey.starts_with(&prefix));
+                    }
+                    subkeys.sort();
+                    if let Some(limit) = limit {
+                        subkeys.truncate(limit);
+                    }
+                    subkeys
+                })
+            }
+            Self::Db(db) => {
+                let copy = db.clone();
+                tokio::task::spawn_blocking(move || {
+                    copy.find_subkeys(&key, prefix.as_deref(), limit)
+                })
+                .await
+                .context("failed to wait for db read task")?
+                .context("failed to read subkeys from db")?
+            }
+        })
+    }
+
+    /// Reads the value of `key` as of the end of L1 level `level`, or its current value
+    /// if `level` is `None`. Only databases synced from the kernel storage updates record
+    /// the history of their values. The last recorded level cannot be read until the
+    /// next level starts, since its state still changes.
+    pub async fn get_value_at(
+        &self,
+        key: String,
+        level: Option<u32>,
+    ) -> ServiceResult<Option<Vec<u8>>> {
+        let (db, level) = match (self, level) {
+            (_, None) => return Ok(self.get_value(key).await?),
+            (Self::Db(db), Some(level)) => (db.clone(), level),
+            (Self::Rollup(_), Some(_)) => {
+                return Err(ServiceError::BadRequest(
+                    "Historical state requires storage sync".to_string(),
+                ))
+            }
+        };
+        let value = tokio::task::spawn_blocking(move || match db.recorded_levels()? {
+            Some((first, last)) if (first.saturating_sub(1)..last).contains(&level) => {
+                db.read_key_at(&key, level).map(Some)
+            }
+            _ => Ok(None),
+        })
+        .await
+        .context("failed to wait for db read task")??;
+        match value {
+            Some(Some(v)) => Ok(Some(
+                hex::decode(v).context("failed to decode value string")?,
+            )),
+            Some(None) => Ok(None),
+            None => Err(ServiceError::BadRequest(format!(
+                "State at level {level} is not available"
+            ))),
+        }
+    }
 }
 
 #[cfg(test)]
 pub(crate) mod tests {
     use std::{
+        io::Write,
         path::PathBuf,
-        sync::{atomic::AtomicU64, Arc, RwLock},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, RwLock,
+        },
         time::SystemTime,
     };
 
     use axum::{body::Body, http::Request};
-    use jstz_core::BinEncodable;
+    use jstz_core::{
+        reveal_data::{MAX_DECOMPRESSED_REVEAL_SIZE, MAX_REVEAL_SIZE},
+        BinEncodable,
+    };
     use jstz_crypto::{
         hash::Blake2b,
         smart_function_hash::{Kt1Hash, SmartFunctionHash},
     };
     use jstz_mock::kt1_account1;
-    use jstz_proto::receipt::{
-        DeployFunctionReceipt, Receipt, ReceiptContent, ReceiptResult,
+    use jstz_proto::{
+        constants::{GAS_PRICE, STORAGE_FEE_RATE},
+        context::interface::MAX_INTERFACE_SIZE,
+        operation::MAX_DIRECT_OPERATION_SIZE,
+        receipt::{DeployFunctionReceipt, Receipt, ReceiptContent, ReceiptResult},
     };
     use mockito::Matcher;
-    use octez::OctezRollupClient;
+    use octez::{
+        mock::MockRollupRpc, r#async::rollup::log::RollupLogMonitor,
+        FailoverRollupClient, OctezRollupClient,
+    };
     use tempfile::NamedTempFile;
     use tezos_crypto_rs::hash::{ContractKt1Hash, SmartRollupHash};
+    use tezos_smart_rollup::types::SmartRollupAddress;
     use tower::util::ServiceExt;
 
     use crate::{
         config::RuntimeEnv,
-        sequencer::queue::OperationQueue,
-        services::{logs::broadcaster::Broadcaster, utils::StoreWrapper},
+        sequencer::{db, queue::OperationQueue},
+        services::{
+            error::ServiceError, logs::broadcaster::Broadcaster, utils::StoreWrapper,
+        },
         temp_db,
         test::default_injector,
         AppState, RunMode,
@@ -110,8 +369,9 @@ pub(crate) mod tests {
         mode: RunMode,
     ) -> AppState {
         AppState {
-            rollup_client: OctezRollupClient::new(rollup_endpoint.to_string()),
+            rollup_client: Arc::new(OctezRollupClient::new(rollup_endpoint.to_string())),
             rollup_preimages_dir,
+            rollup_fallback_preimages_dirs: vec![],
             broadcaster: Broadcaster::new(),
             db: crate::services::logs::db::Db::init().await.unwrap(),
             injector: default_injector(),
@@ -119,8 +379,14 @@ pub(crate) mod tests {
             queue: Arc::new(RwLock::new(OperationQueue::new(1))),
             runtime_db: crate::sequencer::db::Db::init(Some(runtime_db_path)).unwrap(),
             worker_heartbeat: Arc::default(),
+            worker_paused: Arc::default(),
+            worker_restarts: Arc::default(),
+            worker_stats: Arc::default(),
             storage_sync: false,
             storage_sync_db: crate::sequencer::db::Db::init(Some("")).unwrap(),
+            rollup_log_monitors: vec![],
+            name_registry: None,
+            rollup_failover: None,
         }
     }
 
@@ -139,7 +405,7 @@ pub(crate) mod tests {
         let store = StoreWrapper::new(
             RunMode::Default,
             false,
-            OctezRollupClient::new(String::new()),
+            Arc::new(OctezRollupClient::new(String::new())),
             runtime_db.clone(),
             storage_sync_db.clone(),
         );
@@ -149,7 +415,7 @@ pub(crate) mod tests {
         let store = StoreWrapper::new(
             RunMode::Default,
             true,
-            OctezRollupClient::new(String::new()),
+            Arc::new(OctezRollupClient::new(String::new())),
             runtime_db.clone(),
             storage_sync_db.clone(),
         );
@@ -173,7 +439,7 @@ pub(crate) mod tests {
                 .unwrap(),
             },
             false,
-            OctezRollupClient::new(String::new()),
+            Arc::new(OctezRollupClient::new(String::new())),
             runtime_db.clone(),
             storage_sync_db.clone(),
         );
@@ -197,7 +463,7 @@ pub(crate) mod tests {
                 .unwrap(),
             },
             false,
-            OctezRollupClient::new(String::new()),
+            Arc::new(OctezRollupClient::new(String::new())),
             runtime_db.clone(),
             storage_sync_db.clone(),
         );
@@ -208,6 +474,45 @@ pub(crate) mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn store_wrapper_get_value_at() {
+        let (db, _db_file) = temp_db().unwrap();
+        let conn = db.connection().unwrap();
+        db::exec_write(&conn, "/test", &hex::encode("before")).unwrap();
+        let store = StoreWrapper::Db(Arc::new(db));
+        let read_at = |level| store.get_value_at("/test".to_string(), level);
+
+        // no level was recorded yet
+        assert!(matches!(
+            read_at(Some(3)).await,
+            Err(ServiceError::BadRequest(_))
+        ));
+
+        db::exec_start_level(&conn, 3).unwrap();
+        db::exec_write_at(&conn, 3, "/test", &hex::encode("after")).unwrap();
+        assert_eq!(read_at(Some(2)).await.unwrap(), Some(b"before".to_vec()));
+        assert_eq!(read_at(None).await.unwrap(), Some(b"after".to_vec()));
+        // level 3 is still open
+        assert!(matches!(
+            read_at(Some(3)).await,
+            Err(ServiceError::BadRequest(_))
+        ));
+
+        db::exec_start_level(&conn, 4).unwrap();
+        assert_eq!(read_at(Some(3)).await.unwrap(), Some(b"after".to_vec()));
+        assert!(matches!(
+            read_at(Some(4)).await,
+            Err(ServiceError::BadRequest(_))
+        ));
+
+        // the rollup node does not record the history
+        let store = StoreWrapper::Rollup(Arc::new(OctezRollupClient::new(String::new())));
+        assert!(matches!(
+            store.get_value_at("/test".to_string(), Some(3)).await,
+            Err(ServiceError::BadRequest(_))
+        ));
+    }
+
     #[tokio::test]
     async fn store_wrapper_rollup() {
         let smart_function_hash =
@@ -233,7 +538,7 @@ pub(crate) mod tests {
             .with_body("null")
             .create();
 
-        let store = StoreWrapper::Rollup(OctezRollupClient::new(server.url()));
+        let store = StoreWrapper::Rollup(Arc::new(OctezRollupClient::new(server.url())));
         let bytes = store
             .get_value(format!("/jstz_receipt/{op_hash}"))
             .await
@@ -248,7 +553,7 @@ pub(crate) mod tests {
         ));
 
         // non-existent path
-        let store = StoreWrapper::Rollup(OctezRollupClient::new(server.url()));
+        let store = StoreWrapper::Rollup(Arc::new(OctezRollupClient::new(server.url())));
         assert!(store
             .get_value("/jstz_receipt/bad_hash".to_string(),)
             .await
@@ -259,6 +564,29 @@ pub(crate) mod tests {
         mock_value_endpoint_bad.assert();
     }
 
+    #[tokio::test]
+    async fn store_wrapper_mock_rollup() {
+        let rpc = MockRollupRpc::new(
+            SmartRollupAddress::from_b58check("sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK")
+                .unwrap(),
+        );
+        rpc.insert("/jstz_receipt/foo", b"receipt".to_vec());
+
+        let store = StoreWrapper::Rollup(Arc::new(rpc));
+        assert_eq!(
+            store
+                .get_value("/jstz_receipt/foo".to_string())
+                .await
+                .unwrap(),
+            Some(b"receipt".to_vec())
+        );
+        assert!(store
+            .get_value("/jstz_receipt/bar".to_string())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
     #[tokio::test]
     async fn store_wrapper_db() {
         let smart_function_hash =
@@ -337,6 +665,16 @@ pub(crate) mod tests {
         let mut state =
             mock_app_state("", PathBuf::default(), "", RunMode::Default).await;
         state.worker_heartbeat = Arc::new(AtomicU64::new(now - 5));
+        state.worker_restarts = Arc::new(AtomicU64::new(2));
+        state.worker_stats.recycles.store(1, Ordering::Relaxed);
+        state
+            .worker_stats
+            .memory_bytes
+            .store(1024 * 1024, Ordering::Relaxed);
+        state
+            .worker_stats
+            .attestation_failures
+            .store(3, Ordering::Relaxed);
         let router = axum::Router::new()
             .route("/worker/health", axum::routing::get(super::worker_health))
             .with_state(state);
@@ -347,5 +685,179 @@ pub(crate) mod tests {
             .unwrap();
         // heartbeat is recent enough
         assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap(),
+            serde_json::json!({
+                "healthy": true,
+                "restarts": 2,
+                "recycles": 1,
+                "memory_bytes": 1048576,
+                "cpu_percent": 0,
+                "attestation_failures": 3
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn health_details() {
+        let mut log_file = NamedTempFile::new().unwrap();
+        let mut state =
+            mock_app_state("", PathBuf::default(), "", RunMode::Default).await;
+        let monitor = Arc::new(RollupLogMonitor::spawn(log_file.path()));
+        state.rollup_log_monitors = vec![Some(monitor.clone())];
+        writeln!(log_file, "Refutation game started").unwrap();
+        log_file.flush().unwrap();
+        monitor
+            .wait_for(|health| health.refutations_started == 1)
+            .await;
+
+        let router = axum::Router::new()
+            .route("/health/details", axum::routing::get(super::health_details))
+            .with_state(state);
+        let res = router
+            .oneshot(Request::get("/health/details").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let body = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap();
+        assert_eq!(body["worker_healthy"], false);
+        assert_eq!(body["attestation_failures"], 0);
+        assert_eq!(body["rollup"]["status"], "degraded");
+        assert_eq!(body["rollup"]["refutations_started"], 1);
+    }
+
+    #[tokio::test]
+    async fn health() {
+        let mut state =
+            mock_app_state("", PathBuf::default(), "", RunMode::Default).await;
+        let rpc = Arc::new(MockRollupRpc::new(
+            SmartRollupAddress::from_b58check("sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK")
+                .unwrap(),
+        ));
+        state.rollup_failover = Some(Arc::new(
+            FailoverRollupClient::from_rpcs(vec![
+                ("http://primary".to_string(), rpc.clone()),
+                ("http://fallback".to_string(), rpc),
+            ])
+            .unwrap(),
+        ));
+
+        let router = axum::Router::new()
+            .route("/health", axum::routing::get(super::health))
+            .with_state(state.clone());
+        let res = router
+            .oneshot(Request::get("/health").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let body = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "rollup_endpoints": [
+                    { "endpoint": "http://primary", "healthy": true, "active": true },
+                    { "endpoint": "http://fallback", "healthy": true, "active": false }
+                ]
+            })
+        );
+
+        // Nothing listens on the discard port
+        let client = Arc::new(
+            FailoverRollupClient::new(&["http://127.0.0.1:9".to_string()]).unwrap(),
+        );
+        client.check_health().await;
+        state.rollup_failover = Some(client);
+        let router = axum::Router::new()
+            .route("/health", axum::routing::get(super::health))
+            .with_state(state);
+        let res = router
+            .oneshot(Request::get("/health").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 503);
+    }
+
+    #[tokio::test]
+    async fn constants() {
+        let state = mock_app_state("", PathBuf::default(), "", RunMode::Default).await;
+        state.queue.write().unwrap().set_capacity(42);
+        let router = axum::Router::new()
+            .route("/constants", axum::routing::get(super::constants))
+            .with_state(state);
+        let res = router
+            .oneshot(Request::get("/constants").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        let body = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap();
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        let runtime_version = if cfg!(feature = "v2_runtime") {
+            "v2"
+        } else {
+            "v1"
+        };
+        assert_eq!(body["runtime_version"], runtime_version);
+        assert_eq!(body["gas_price"], GAS_PRICE);
+        assert_eq!(body["storage_fee_rate"], STORAGE_FEE_RATE);
+        assert_eq!(body["max_interface_size"], MAX_INTERFACE_SIZE);
+        assert_eq!(body["max_reveal_size"], MAX_REVEAL_SIZE);
+        assert_eq!(body["max_operation_size"], MAX_DECOMPRESSED_REVEAL_SIZE);
+        assert_eq!(body["max_direct_operation_size"], MAX_DIRECT_OPERATION_SIZE);
+        assert_eq!(body["queue_capacity"], 42);
+    }
+
+    #[tokio::test]
+    async fn pause_and_resume_worker() {
+        let request = |path: &str| Request::post(path).body(Body::empty()).unwrap();
+        let router = |state: AppState| {
+            axum::Router::new()
+                .route(
+                    "/admin/sequencer/pause",
+                    axum::routing::post(super::pause_worker),
+                )
+                .route(
+                    "/admin/sequencer/resume",
+                    axum::routing::post(super::resume_worker),
+                )
+                .with_state(state)
+        };
+
+        let state = mock_app_state("", PathBuf::default(), "", RunMode::Default).await;
+        let res = router(state)
+            .oneshot(request("/admin/sequencer/pause"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 400);
+
+        let mode = RunMode::Sequencer {
+            capacity: 0,
+            debug_log_path: PathBuf::new(),
+            runtime_env: RuntimeEnv::Native,
+            inbox_checkpoint_path: PathBuf::new(),
+            ticketer_address: kt1_account1(),
+            rollup_address: SmartRollupHash::from_base58_check(
+                "sr1Uuiucg1wk5aovEY2dj1ZBsqjwxndrSaao",
+            )
+            .unwrap(),
+        };
+        let state = mock_app_state("", PathBuf::default(), "", mode).await;
+        let paused = state.worker_paused.clone();
+        let res = router(state.clone())
+            .oneshot(request("/admin/sequencer/pause"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        assert!(paused.is_set());
+
+        let res = router(state)
+            .oneshot(request("/admin/sequencer/resume"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        assert!(!paused.is_set());
     }
 }
@@ -39,6 +39,8 @@ impl Future for StorageSync {
 }
 /// Spawns a new storage sync worker.
 /// The thread will read the event stream file and apply the storage updates to the database.
+/// Once the start of an L1 level is read, the updates are also recorded in the history of
+/// the database, see [`sequencer::db::exec_write_at`].
 ///
 /// # Arguments
 ///
@@ -66,6 +68,8 @@ pub fn spawn(
                             return Err(e);
                         }
                     };
+                    // The L1 level of the updates, unknown until the next start of level
+                    let mut level = None;
                     loop {
                         tokio::select! {
                             _ = &mut kill_rx => {
@@ -76,9 +80,12 @@ pub fn spawn(
                             next_item = stream.next() => {
                                 match next_item {
                                     Some(Ok(updates)) => {
-                                        if let Err(e) = apply_batch_tx_with_retry(&db, updates).await {
-                                            error!("db error, aborting: {e}");
-                                            return Err(e);
+                                        match apply_batch_tx_with_retry(&db, updates, level).await {
+                                            Ok(next_level) => level = next_level,
+                                            Err(e) => {
+                                                error!("db error, aborting: {e}");
+                                                return Err(e);
+                                            }
                                         }
                                     }
                                     Some(Err(e)) => {
@@ -125,7 +132,11 @@ impl StorageSync {
 
 /// Applies a batch of storage updates with exponential backoff.
 /// The retry is limited to 6 attempts with a maximum delay of 8 seconds.
-async fn apply_batch_tx_with_retry(db: &Db, updates: BatchStorageUpdate) -> Result<()> {
+async fn apply_batch_tx_with_retry(
+    db: &Db,
+    updates: BatchStorageUpdate,
+    level: Option<u32>,
+) -> Result<Option<u32>> {
     #[cfg(test)]
     let attempts = 0;
     #[cfg(not(test))]
@@ -133,7 +144,7 @@ async fn apply_batch_tx_with_retry(db: &Db, updates: BatchStorageUpdate) -> Resu
 
     retry_async(
         exponential_backoff(50, attempts, Duration::from_secs(8)),
-        || async { apply_batch_tx(db, updates.clone()) },
+        || async { apply_batch_tx(db, updates.clone(), level) },
         |_| true,
     )
     .await
@@ -141,20 +152,38 @@ async fn apply_batch_tx_with_retry(db: &Db, updates: BatchStorageUpdate) -> Resu
 
 /// Executes a batch of storage updates in a single transaction.
 /// This function is blocking but it's called from a separate thread so it's ok.
-fn apply_batch_tx(db: &Db, updates: BatchStorageUpdate) -> Result<()> {
+///
+/// The updates are recorded in the history at L1 level `level`, if known. Returns
+/// the level of the updates that follow the batch.
+fn apply_batch_tx(
+    db: &Db,
+    updates: BatchStorageUpdate,
+    mut level: Option<u32>,
+) -> Result<Option<u32>> {
     let mut conn = db.connection()?;
     let tx = conn.transaction()?;
     for update in updates {
-        let res = match update {
-            StorageUpdate::Insert { ref key, ref value } => {
+        let res = match (&update, level) {
+            (StorageUpdate::Insert { key, value }, Some(level)) => {
+                sequencer::db::exec_write_at(&tx, level, key, &hex::encode(value))
+            }
+            (StorageUpdate::Insert { key, value }, None) => {
                 sequencer::db::exec_write(&tx, key, &hex::encode(value))
             }
-            StorageUpdate::Remove { ref key } => {
+            (StorageUpdate::Remove { key }, level) => {
                 #[cfg(test)]
                 if key == tests::KILL_KEY {
                     return Err(anyhow::anyhow!("received test kill signal"));
                 }
-                sequencer::db::exec_delete(&tx, key).map(|_| ())
+                match level {
+                    Some(level) => sequencer::db::exec_delete_at(&tx, level, key),
+                    None => sequencer::db::exec_delete(&tx, key),
+                }
+                .map(|_| ())
+            }
+            (StorageUpdate::StartOfLevel { level: next }, _) => {
+                level = Some(*next);
+                sequencer::db::exec_start_level(&tx, *next)
             }
         };
         if let Err(e) = res {
@@ -163,7 +192,7 @@ fn apply_batch_tx(db: &Db, updates: BatchStorageUpdate) -> Result<()> {
         }
     }
     tx.commit()?;
-    Ok(())
+    Ok(level)
 }
 
 #[cfg(test)]
@@ -309,7 +338,7 @@ pub(crate) mod tests {
         let _ = batch.push_insert(&key2, &value2);
         batch.push_remove(&key1);
         // Should succeed
-        apply_batch_tx(&db, batch)?;
+        apply_batch_tx(&db, batch, None)?;
         // After transaction, key1 should not exist, key2 should exist with correct value
         assert!(db.read_key(&key1.to_string())?.is_none());
         let value = db.read_key(&key2.to_string())?.expect("key2 should exist");
@@ -319,6 +348,36 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_apply_batch_tx_records_history() -> Result<()> {
+        let (db, _db_file) = temp_db().unwrap();
+        let key = mock_key().to_string();
+
+        // updates made before the first start of level are not versioned
+        let level = apply_batch_tx(&db, mock_insert_event(), None)?;
+        assert_eq!(level, None);
+        assert_eq!(db.recorded_levels()?, None);
+
+        let level = apply_batch_tx(&db, BatchStorageUpdate::start_of_level(10), level)?;
+        assert_eq!(level, Some(10));
+        let mut batch = BatchStorageUpdate::new(1);
+        batch.push_insert(&mock_key(), &DummyValue(7))?;
+        let level = apply_batch_tx(&db, batch, level)?;
+        let level = apply_batch_tx(&db, BatchStorageUpdate::start_of_level(11), level)?;
+        apply_batch_tx(&db, mock_remove_event(), level)?;
+        assert_eq!(db.recorded_levels()?, Some((10, 11)));
+
+        let read_at = |level| -> Option<DummyValue> {
+            db.read_key_at(&key, level)
+                .unwrap()
+                .map(|v| BinEncodable::decode(&hex::decode(v).unwrap()).unwrap())
+        };
+        assert_eq!(read_at(9), Some(DummyValue(42)));
+        assert_eq!(read_at(10), Some(DummyValue(7)));
+        assert_eq!(read_at(11), None);
+        Ok(())
+    }
+
     #[test]
     fn test_apply_batch_tx_atomicity() -> Result<()> {
         let (db, _db_file) = temp_db().unwrap();
@@ -331,7 +390,7 @@ pub(crate) mod tests {
             unsafe { OwnedPath::from_bytes_unchecked(vec![0; 1_000_000_000]) };
         let value2 = DummyValue(456);
         let _ = batch.push_insert(&invalid_key, &value2);
-        let result = apply_batch_tx(&db, batch);
+        let result = apply_batch_tx(&db, batch, None);
         assert!(
             result.is_err(),
             "Transaction with invalid update should fail"
@@ -0,0 +1,99 @@
+//! Export of traces to an OpenTelemetry collector.
+//!
+//! The HTTP router, the sequencer worker and the rollup client record `tracing`
+//! spans. When telemetry is configured, the spans are exported over OTLP/HTTP so that
+//! the latency of an operation can be followed from its injection to its execution.
+//! Spans of the same operation are tagged with its `operation_hash`.
+//!
+//! The spans are exported in batches, so the node handles `SIGINT` and `SIGTERM`
+//! to export the last batch before exiting.
+
+use anyhow::Context;
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+const DEFAULT_SERVICE_NAME: &str = "jstz-node";
+
+fn default_service_name() -> String {
+    DEFAULT_SERVICE_NAME.to_string()
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP endpoint of the collector, e.g. `http://localhost:4318/v1/traces`
+    pub otlp_endpoint: String,
+    /// Name of the service the traces are reported under
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+}
+
+impl TelemetryConfig {
+    pub fn new(otlp_endpoint: String) -> Self {
+        Self {
+            otlp_endpoint,
+            service_name: default_service_name(),
+        }
+    }
+}
+
+/// Exporter of the spans, which must be shut down for the spans it buffers to be
+/// exported
+pub struct Telemetry(TracerProvider);
+
+impl Telemetry {
+    /// Exports the buffered spans and stops the exporter
+    pub async fn shut_down(self) {
+        let provider = self.0;
+        // Blocks until the batch exporter, which runs on the runtime, is flushed
+        match tokio::task::spawn_blocking(move || provider.shutdown()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => log::warn!("failed to shut down telemetry: {e}"),
+            Err(e) => log::warn!("failed to shut down telemetry: {e}"),
+        }
+    }
+}
+
+/// Installs the global subscriber that exports spans to the collector. Must be
+/// called from a tokio runtime, which runs the batch exporter.
+pub fn init(config: &TelemetryConfig) -> anyhow::Result<Telemetry> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&config.otlp_endpoint)
+        .build()
+        .context("failed to build OTLP exporter")?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            config.service_name.clone(),
+        )]))
+        .build();
+    let tracer = provider.tracer("jstz_node");
+    tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()
+        .context("failed to install tracing subscriber")?;
+    opentelemetry::global::set_tracer_provider(provider.clone());
+    Ok(Telemetry(provider))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TelemetryConfig;
+
+    #[test]
+    fn deserialize_default_service_name() {
+        let config: TelemetryConfig = serde_json::from_value(serde_json::json!({
+            "otlp_endpoint": "http://localhost:4318/v1/traces"
+        }))
+        .unwrap();
+        assert_eq!(
+            config,
+            TelemetryConfig::new("http://localhost:4318/v1/traces".to_string())
+        );
+        assert_eq!(config.service_name, "jstz-node");
+    }
+}
@@ -0,0 +1,268 @@
+//! Rollups served by a node besides its own.
+//!
+//! Every route of the node is also served for each [`RollupContext`] of the
+//! configuration under `/r/{rollup_address}`, with a state of its own: reads and
+//! injected operations go to the rollup node of the context, whose address must be
+//! the address of the context, and logs are read from the kernel log file of the
+//! context. The rollups share the tokio runtime and the injector of the node. They
+//! run in the default mode, as the sequencer only executes the operations of the
+//! rollup of the node.
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{bail, Context, Result};
+use axum::Router;
+use octez::OctezRollupClient;
+use tempfile::NamedTempFile;
+use tezos_crypto_rs::hash::SmartRollupHash;
+
+use crate::{
+    config::RollupContext,
+    sequencer::queue::OperationQueue,
+    services::logs::{broadcaster::Broadcaster, db::Db, LogsService},
+    temp_db, AppState, RunMode,
+};
+
+/// A rollup served under `/r/{address}`
+pub struct Tenant {
+    pub address: SmartRollupHash,
+    state: AppState,
+    /// Reads the kernel log file of the rollup, if any
+    logs: Option<LogsService>,
+    /// Keeps the temporary databases of the rollup until it is dropped
+    _db_files: Vec<NamedTempFile>,
+}
+
+impl Tenant {
+    /// Creates the state of the rollup of `context`, sharing the injector of `node`.
+    /// Fails if the rollup node of `context` does not report the address of
+    /// `context`.
+    pub async fn init(context: RollupContext, node: &AppState) -> Result<Self> {
+        let rollup_client = OctezRollupClient::new(context.rollup_endpoint.to_string());
+        let address = rollup_client
+            .get_rollup_address()
+            .await
+            .context("failed to get the address of the rollup node")?;
+        if address.to_b58check() != context.rollup_address.to_base58_check() {
+            bail!(
+                "the rollup node at {} runs rollup {}",
+                context.rollup_endpoint,
+                address.to_b58check()
+            );
+        }
+
+        // The logs of the rollup are kept apart from the logs of the node
+        let db = Db::init_file(&format!("log-{}.db", context.rollup_address)).await?;
+        let (broadcaster, db, logs) = match &context.kernel_log_file {
+            Some(path) => {
+                let (broadcaster, db, logs) = LogsService::init_with_db(path, db).await?;
+                (broadcaster, db, Some(logs))
+            }
+            None => (Broadcaster::new(), db, None),
+        };
+        let mut db_files = vec![];
+        let runtime_db = match &context.runtime_db_path {
+            Some(path) => crate::sequencer::db::Db::init(path.to_str())?,
+            None => {
+                let (db, file) = temp_db()?;
+                db_files.push(file);
+                db
+            }
+        };
+        let (storage_sync_db, file) = temp_db()?;
+        db_files.push(file);
+        Ok(Self {
+            address: context.rollup_address,
+            state: AppState {
+                rollup_client: Arc::new(rollup_client),
+                rollup_preimages_dir: context.rollup_preimages_dir,
+                rollup_fallback_preimages_dirs: vec![],
+                broadcaster,
+                db,
+                injector: node.injector.clone(),
+                mode: RunMode::Default,
+                queue: Arc::new(RwLock::new(OperationQueue::new(0))),
+                runtime_db,
+                worker_heartbeat: Arc::default(),
+                worker_paused: Arc::default(),
+                worker_restarts: Arc::default(),
+                worker_stats: Arc::default(),
+                storage_sync: false,
+                storage_sync_db,
+                rollup_log_monitors: vec![],
+                name_registry: None,
+                rollup_failover: None,
+            },
+            logs,
+            _db_files: db_files,
+        })
+    }
+
+    /// Stops reading the kernel log file of the rollup
+    pub async fn shut_down(self) -> std::io::Result<()> {
+        match self.logs {
+            Some(logs) => logs.shutdown().await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Creates the tenants of `contexts`. Fails if a rollup is given twice.
+pub async fn init(contexts: Vec<RollupContext>, node: &AppState) -> Result<Vec<Tenant>> {
+    let mut addresses = HashSet::new();
+    let mut tenants = vec![];
+    for context in contexts {
+        if !addresses.insert(context.rollup_address.clone()) {
+            bail!("rollup {} is configured twice", context.rollup_address);
+        }
+        let address = context.rollup_address.clone();
+        tenants.push(
+            Tenant::init(context, node)
+                .await
+                .with_context(|| format!("failed to init rollup {address}"))?,
+        );
+    }
+    Ok(tenants)
+}
+
+/// Returns `path` without the `/r/{address}` prefix of the tenant routes, so that
+/// the layers of the node apply to the routes of the tenants too
+pub fn route_path(path: &str) -> &str {
+    match path.strip_prefix("/r/") {
+        Some(rest) => rest.find('/').map_or("", |i| &rest[i..]),
+        None => path,
+    }
+}
+
+/// Serves the routes of `router` for each of `tenants` under `/r/{address}`.
+pub fn nest(
+    mut router: Router,
+    tenants: &[Tenant],
+    routes: fn() -> Router<AppState>,
+) -> Router {
+    for tenant in tenants {
+        router = router.nest(
+            &format!("/r/{}", tenant.address),
+            routes().with_state(tenant.state.clone()),
+        );
+    }
+    router
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, str::FromStr};
+
+    use axum::{body::Body, extract::Request, routing::get, Router};
+    use octez::r#async::endpoint::Endpoint;
+    use tempfile::NamedTempFile;
+    use tezos_crypto_rs::hash::SmartRollupHash;
+    use tower::ServiceExt;
+
+    use super::{init, nest, route_path};
+    use crate::{
+        config::{RollupContext, RuntimeEnv},
+        services::utils::{self, tests::mock_app_state},
+        AppState, RunMode,
+    };
+
+    const ADDRESS: &str = "sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK";
+
+    fn context(rollup_endpoint: &str) -> RollupContext {
+        RollupContext {
+            rollup_address: SmartRollupHash::from_base58_check(ADDRESS).unwrap(),
+            rollup_endpoint: Endpoint::from_str(rollup_endpoint).unwrap(),
+            rollup_preimages_dir: PathBuf::from("/tmp/preimages"),
+            runtime_db_path: None,
+            kernel_log_file: None,
+        }
+    }
+
+    fn routes() -> Router<AppState> {
+        Router::new().route("/mode", get(utils::get_mode))
+    }
+
+    async fn get(router: &Router, uri: &str) -> (u16, String) {
+        let res = router
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = res.status().as_u16();
+        let bytes = axum::body::to_bytes(res.into_body(), 1000).await.unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn serves_tenants_under_their_address() {
+        let db_file = NamedTempFile::new().unwrap();
+        let state = mock_app_state(
+            "",
+            PathBuf::new(),
+            db_file.path().to_str().unwrap(),
+            RunMode::Sequencer {
+                capacity: 0,
+                debug_log_path: PathBuf::new(),
+                runtime_env: RuntimeEnv::Native,
+                inbox_checkpoint_path: PathBuf::new(),
+                ticketer_address: jstz_mock::kt1_account1(),
+                rollup_address: jstz_mock::sr1_address(),
+            },
+        )
+        .await;
+        let mut server = mockito::Server::new_async().await;
+        let rollup_address = server
+            .mock("GET", "/global/smart_rollup_address")
+            .with_body(format!("\"{ADDRESS}\""))
+            .create();
+        let tenants = init(vec![context(&server.url())], &state).await.unwrap();
+        let router = nest(routes().with_state(state.clone()), &tenants, routes);
+
+        assert_eq!(
+            get(&router, "/mode").await,
+            (200, "\"sequencer\"".to_string())
+        );
+        assert_eq!(
+            get(&router, &format!("/r/{ADDRESS}/mode")).await,
+            (200, "\"default\"".to_string())
+        );
+        assert_eq!(
+            get(&router, "/r/sr1Uuiucg1wk5aovEY2dj1ZBsqjwxndrSaao/mode")
+                .await
+                .0,
+            404
+        );
+
+        assert!(
+            init(vec![context(&server.url()), context(&server.url())], &state)
+                .await
+                .is_err()
+        );
+
+        // the rollup node must run the rollup of the context
+        rollup_address.remove();
+        server
+            .mock("GET", "/global/smart_rollup_address")
+            .with_body("\"sr1Uuiucg1wk5aovEY2dj1ZBsqjwxndrSaao\"")
+            .create();
+        assert!(init(vec![context(&server.url())], &state).await.is_err());
+    }
+
+    #[test]
+    fn strips_tenant_prefix() {
+        assert_eq!(route_path("/operations"), "/operations");
+        assert_eq!(
+            route_path(&format!("/r/{ADDRESS}/operations")),
+            "/operations"
+        );
+        assert_eq!(
+            route_path(&format!("/r/{ADDRESS}/admin/sequencer/pause")),
+            "/admin/sequencer/pause"
+        );
+        assert_eq!(route_path(&format!("/r/{ADDRESS}")), "");
+        assert_eq!(route_path("/rollups"), "/rollups");
+    }
+}
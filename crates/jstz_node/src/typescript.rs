@@ -0,0 +1,261 @@
+//! TypeScript definitions of the public jstz types, generated from the schemas of
+//! the OpenAPI document so that JS clients stay in lockstep with the Rust types.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+const HEADER: &str = "// Generated by `jstz-node types`. Do not edit.\n";
+
+/// Renders every component schema of the OpenAPI document as an exported
+/// TypeScript type
+pub fn typescript_definitions(spec: &Value) -> Result<String> {
+    let schemas = spec
+        .pointer("/components/schemas")
+        .and_then(Value::as_object)
+        .context("OpenAPI document has no component schemas")?;
+
+    let mut out = String::from(HEADER);
+    for (name, schema) in schemas {
+        out.push('\n');
+        push_doc(&mut out, schema, 0);
+        out.push_str(&format!("export type {name} = {};\n", ts_type(schema, 0)));
+    }
+    Ok(out)
+}
+
+pub fn typescript_definitions_raw() -> Result<String> {
+    let spec = serde_json::from_str(&crate::openapi_json_raw()?)?;
+    typescript_definitions(&spec)
+}
+
+fn push_doc(out: &mut String, schema: &Value, indent: usize) {
+    let Some(description) = schema.get("description").and_then(Value::as_str) else {
+        return;
+    };
+    let pad = "  ".repeat(indent);
+    let lines: Vec<&str> = description.lines().collect();
+    if let [line] = lines.as_slice() {
+        out.push_str(&format!("{pad}/** {line} */\n"));
+        return;
+    }
+    out.push_str(&format!("{pad}/**\n"));
+    for line in lines {
+        out.push_str(&format!("{pad} * {line}\n").replace(" * \n", " *\n"));
+    }
+    out.push_str(&format!("{pad} */\n"));
+}
+
+fn ts_type(schema: &Value, indent: usize) -> String {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        return reference
+            .rsplit('/')
+            .next()
+            .unwrap_or(reference)
+            .to_string();
+    }
+    if let Some(variants) = schema.get("enum").and_then(Value::as_array) {
+        return join(variants.iter().map(Value::to_string), " | ");
+    }
+    if let Some(schemas) = schema.get("oneOf").and_then(Value::as_array) {
+        return join(schemas.iter().map(|s| ts_type(s, indent)), " | ");
+    }
+    if let Some(schemas) = schema.get("allOf").and_then(Value::as_array) {
+        return join(
+            schemas.iter().map(|s| parenthesize(ts_type(s, indent))),
+            " & ",
+        );
+    }
+    match schema.get("type") {
+        Some(Value::String(ty)) => primitive(ty, schema, indent),
+        Some(Value::Array(types)) => join(
+            types
+                .iter()
+                .filter_map(Value::as_str)
+                .map(|ty| primitive(ty, schema, indent)),
+            " | ",
+        ),
+        _ => "unknown".to_string(),
+    }
+}
+
+fn primitive(ty: &str, schema: &Value, indent: usize) -> String {
+    match ty {
+        "string" => "string".to_string(),
+        "integer" | "number" => "number".to_string(),
+        "boolean" => "boolean".to_string(),
+        "null" => "null".to_string(),
+        "array" => {
+            let items = schema
+                .get("items")
+                .map_or_else(|| "unknown".to_string(), |s| ts_type(s, indent));
+            format!("{}[]", parenthesize(items))
+        }
+        "object" => object(schema, indent),
+        _ => "unknown".to_string(),
+    }
+}
+
+fn object(schema: &Value, indent: usize) -> String {
+    let properties = schema.get("properties").and_then(Value::as_object);
+    let additional = match schema.get("additionalProperties") {
+        Some(Value::Bool(false)) => None,
+        Some(Value::Bool(true)) => Some("unknown".to_string()),
+        Some(s) => Some(ts_type(s, indent + 1)),
+        // Objects without any properties are free-form
+        None if properties.is_none() => Some("unknown".to_string()),
+        None => None,
+    };
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|r| r.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+
+    let pad = "  ".repeat(indent + 1);
+    let mut out = String::from("{\n");
+    for (name, property) in properties.into_iter().flatten() {
+        push_doc(&mut out, property, indent + 1);
+        let optional = if required.contains(&name.as_str()) {
+            ""
+        } else {
+            "?"
+        };
+        out.push_str(&format!(
+            "{pad}{}{optional}: {};\n",
+            property_name(name),
+            ts_type(property, indent + 1)
+        ));
+    }
+    if let Some(value) = additional {
+        out.push_str(&format!("{pad}[key: string]: {value};\n"));
+    }
+    out.push_str(&format!("{}}}", "  ".repeat(indent)));
+    out
+}
+
+fn property_name(name: &str) -> String {
+    let is_identifier = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if is_identifier {
+        name.to_string()
+    } else {
+        Value::from(name).to_string()
+    }
+}
+
+fn parenthesize(ty: String) -> String {
+    if ty.contains(" | ") || ty.contains(" & ") {
+        format!("({ty})")
+    } else {
+        ty
+    }
+}
+
+fn join(types: impl Iterator<Item = String>, separator: &str) -> String {
+    let types: Vec<String> = types.collect();
+    if types.is_empty() {
+        "never".to_string()
+    } else {
+        types.join(separator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{typescript_definitions, typescript_definitions_raw};
+
+    #[test]
+    fn renders_component_schemas() {
+        let spec = json!({
+            "components": {
+                "schemas": {
+                    "Address": {
+                        "oneOf": [
+                            { "$ref": "#/components/schemas/PublicKeyHash" },
+                            { "$ref": "#/components/schemas/SmartFunctionHash" }
+                        ],
+                        "description": "Tezos Address"
+                    },
+                    "HttpBody": { "type": ["string", "null"] },
+                    "LogLevel": { "type": "string", "enum": ["ERROR", "WARN"] },
+                    "RunFunction": {
+                        "type": "object",
+                        "required": ["uri", "headers"],
+                        "properties": {
+                            "body": { "$ref": "#/components/schemas/HttpBody" },
+                            "headers": {
+                                "type": "object",
+                                "properties": {
+                                    "X-JSTZ-TRANSFER": { "type": "integer" }
+                                },
+                                "additionalProperties": true
+                            },
+                            "tags": {
+                                "type": "array",
+                                "items": { "type": ["string", "null"] }
+                            },
+                            "uri": {
+                                "type": "string",
+                                "description": "Smart function URI"
+                            }
+                        }
+                    }
+                }
+            }
+        });
+
+        assert_eq!(
+            typescript_definitions(&spec).unwrap(),
+            r#"// Generated by `jstz-node types`. Do not edit.
+
+/** Tezos Address */
+export type Address = PublicKeyHash | SmartFunctionHash;
+
+export type HttpBody = string | null;
+
+export type LogLevel = "ERROR" | "WARN";
+
+export type RunFunction = {
+  body?: HttpBody;
+  headers: {
+    "X-JSTZ-TRANSFER"?: number;
+    [key: string]: unknown;
+  };
+  tags?: (string | null)[];
+  /** Smart function URI */
+  uri: string;
+};
+"#
+        );
+    }
+
+    #[test]
+    fn renders_public_types() {
+        let definitions = typescript_definitions_raw().unwrap();
+        for name in [
+            "Operation",
+            "SignedOperation",
+            "Receipt",
+            "Account",
+            "LogRecord",
+        ] {
+            assert!(
+                definitions.contains(&format!("export type {name} = ")),
+                "missing {name}"
+            );
+        }
+    }
+
+    #[test]
+    fn fails_without_schemas() {
+        assert!(typescript_definitions(&json!({})).is_err());
+    }
+}
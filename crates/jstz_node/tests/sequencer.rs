@@ -475,6 +475,8 @@ fn raw_operation(nonce: u64, content: Content) -> Operation {
         public_key: jstz_mock::pk1(),
         nonce: Nonce(nonce),
         content,
+        max_fee: None,
+        priority_fee: None,
     }
 }
 
@@ -536,7 +538,7 @@ async fn poll_receipt(client: &Client, base_uri: &str, hash: &str) -> Receipt {
 }
 
 async fn deploy_function(client: &Client, base_uri: &str) {
-    let deploy_op = raw_operation(0, Content::DeployFunction(DeployFunction {function_code: format!("const handler = async () => {{ const s = \"{}\"; console.log(\"debug message here\"); return new Response(\"this is a big function\"); }}; export default handler;\n", "a".repeat(8000)), account_credit: 0}));
+    let deploy_op = raw_operation(0, Content::DeployFunction(DeployFunction {function_code: format!("const handler = async () => {{ const s = \"{}\"; console.log(\"debug message here\"); return new Response(\"this is a big function\"); }}; export default handler;\n", "a".repeat(8000)), account_credit: 0, interface: None}));
 
     let receipt = submit_operation(
         client,
@@ -716,11 +718,14 @@ fn mock_deploy_op() -> SignedOperation {
     let deploy_fn = DeployFunction {
         function_code: code.to_string(),
         account_credit: 0,
+        interface: None,
     };
     let op = Operation {
         public_key: alice_pk.clone(),
         nonce: 0.into(),
         content: deploy_fn.into(),
+        max_fee: None,
+        priority_fee: None,
     };
     SignedOperation::new(alice_sk.sign(op.hash()).unwrap(), op.clone())
 }
@@ -0,0 +1,309 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use jstz_proto::runtime::v2::fetch::http::{Body, Request as HttpRequest, Response};
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
+
+use super::provider::Provider;
+
+const CACHE_CONTROL: &[u8] = b"cache-control";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Seconds for which responses are served from the cache, unless the request
+    /// asks for another freshness with `Cache-Control: max-age=<seconds>`. With
+    /// 0, only identical requests in flight at the same time share a fetch.
+    #[serde(default)]
+    pub ttl_secs: u64,
+}
+
+/// Cache key. Requests only share a response if they are identical, including
+/// the freshness they ask for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    method: Vec<u8>,
+    url: String,
+    headers: Vec<(Vec<u8>, Vec<u8>)>,
+    ttl: Duration,
+}
+
+/// Response as stored in the cache
+#[derive(Clone)]
+struct Cached {
+    status: u16,
+    status_text: String,
+    headers: Vec<(Vec<u8>, Vec<u8>)>,
+    body: Vec<u8>,
+}
+
+impl From<Response> for Cached {
+    fn from(response: Response) -> Self {
+        Self {
+            status: response.status,
+            status_text: response.status_text,
+            headers: response
+                .headers
+                .iter()
+                .map(|(name, value)| (name.to_vec(), value.to_vec()))
+                .collect(),
+            body: response.body.to_vec(),
+        }
+    }
+}
+
+impl From<Cached> for Response {
+    fn from(cached: Cached) -> Self {
+        Self {
+            status: cached.status,
+            status_text: cached.status_text,
+            headers: cached
+                .headers
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+            body: Body::Vector(cached.body),
+        }
+    }
+}
+
+/// Fetched at most once. Errors are shared with the requests waiting for the
+/// fetch but are not cached.
+type Entry = Arc<OnceCell<(Instant, Result<Cached, String>)>>;
+
+/// Caches the responses of `P` and deduplicates identical requests in flight.
+///
+/// Only GET and HEAD requests are cached. Requests with `Cache-Control: no-cache`
+/// or `Cache-Control: no-store` bypass the cache.
+pub struct Cache<P> {
+    inner: P,
+    ttl: Duration,
+    entries: Mutex<HashMap<Key, Entry>>,
+}
+
+impl<P: Provider> Cache<P> {
+    pub fn new(inner: P, config: &CacheConfig) -> Self {
+        Self {
+            inner,
+            ttl: Duration::from_secs(config.ttl_secs),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn key(&self, request: &HttpRequest) -> Option<Key> {
+        if !matches!(request.method.as_slice(), b"GET" | b"HEAD") {
+            return None;
+        }
+
+        let mut ttl = self.ttl;
+        let mut headers: Vec<(Vec<u8>, Vec<u8>)> = request
+            .headers
+            .iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value.to_vec()))
+            .collect();
+        headers.sort();
+        for (_, value) in headers.iter().filter(|(name, _)| name == CACHE_CONTROL) {
+            for directive in String::from_utf8_lossy(value).split(',') {
+                let directive = directive.trim().to_ascii_lowercase();
+                if directive == "no-cache" || directive == "no-store" {
+                    return None;
+                }
+                if let Some(max_age) = directive
+                    .strip_prefix("max-age=")
+                    .and_then(|s| s.parse().ok())
+                {
+                    ttl = Duration::from_secs(max_age);
+                }
+            }
+        }
+
+        Some(Key {
+            method: request.method.to_vec(),
+            url: request.url.to_string(),
+            headers,
+            ttl,
+        })
+    }
+
+    /// Returns the entry of `key`, replacing it if it is stale
+    fn entry(&self, key: &Key) -> Entry {
+        let now = Instant::now();
+        let is_fresh = |key: &Key, entry: &Entry| match entry.get() {
+            Some((fetched_at, _)) => now.duration_since(*fetched_at) < key.ttl,
+            // In flight
+            None => true,
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get(key).filter(|entry| is_fresh(key, entry)) {
+            return entry.clone();
+        }
+        entries.retain(|key, entry| is_fresh(key, entry));
+        let entry = Entry::default();
+        entries.insert(key.clone(), entry.clone());
+        entry
+    }
+
+    fn evict(&self, key: &Key, entry: &Entry) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.get(key).is_some_and(|e| Arc::ptr_eq(e, entry)) {
+            entries.remove(key);
+        }
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for Cache<P> {
+    async fn fetch(&self, request: &HttpRequest) -> Result<Response> {
+        let Some(key) = self.key(request) else {
+            return self.inner.fetch(request).await;
+        };
+
+        let entry = self.entry(&key);
+        let (_, result) = entry
+            .get_or_init(|| async {
+                let result = self.inner.fetch(request).await;
+                (
+                    Instant::now(),
+                    result.map(Cached::from).map_err(|e| e.to_string()),
+                )
+            })
+            .await;
+        match result {
+            Ok(cached) => Ok(cached.clone().into()),
+            Err(e) => {
+                self.evict(&key, &entry);
+                Err(anyhow!("{e}"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use anyhow::{bail, Result};
+    use async_trait::async_trait;
+    use jstz_proto::runtime::v2::fetch::http::{Request as HttpRequest, Response};
+    use url::Url;
+
+    use super::{Cache, CacheConfig, Provider};
+    use crate::data_provider::provider::response;
+
+    /// Counts fetches and responds with the count after `delay`
+    #[derive(Default)]
+    struct Counter {
+        fetches: AtomicUsize,
+        delay: Duration,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl Provider for Counter {
+        async fn fetch(&self, _request: &HttpRequest) -> Result<Response> {
+            let count = self.fetches.fetch_add(1, Ordering::SeqCst) + 1;
+            tokio::time::sleep(self.delay).await;
+            if self.fail {
+                bail!("upstream unavailable");
+            }
+            Ok(response(200, count.to_string().into()))
+        }
+    }
+
+    fn request(method: &str, headers: &[(&str, &str)]) -> HttpRequest {
+        HttpRequest {
+            method: method.into(),
+            url: Url::parse("https://prices.internal/eur/usd").unwrap(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| ((*name).into(), (*value).into()))
+                .collect(),
+            body: None,
+        }
+    }
+
+    fn cache(ttl_secs: u64, counter: Counter) -> Cache<Counter> {
+        Cache::new(counter, &CacheConfig { ttl_secs })
+    }
+
+    async fn body(cache: &Cache<Counter>, request: &HttpRequest) -> String {
+        let response = cache.fetch(request).await.unwrap();
+        String::from_utf8(response.body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn deduplicates_concurrent_requests() {
+        let cache = cache(
+            0,
+            Counter {
+                delay: Duration::from_millis(50),
+                ..Default::default()
+            },
+        );
+        let req = request("GET", &[]);
+
+        let bodies = futures::future::join_all((0..10).map(|_| body(&cache, &req))).await;
+        assert!(bodies.iter().all(|b| b == "1"), "{bodies:?}");
+
+        // Without a TTL, responses are not kept once the fetch completes
+        assert_eq!(body(&cache, &req).await, "2");
+    }
+
+    #[tokio::test]
+    async fn serves_fresh_responses() {
+        let cache = cache(60, Counter::default());
+        let req = request("GET", &[("Accept", "text/plain")]);
+
+        assert_eq!(body(&cache, &req).await, "1");
+        assert_eq!(body(&cache, &req).await, "1");
+        // Different headers or freshness are different requests
+        assert_eq!(body(&cache, &request("GET", &[])).await, "2");
+        let short = request(
+            "GET",
+            &[("Accept", "text/plain"), ("Cache-Control", "max-age=0")],
+        );
+        assert_eq!(body(&cache, &short).await, "3");
+        assert_eq!(body(&cache, &short).await, "4");
+    }
+
+    #[tokio::test]
+    async fn bypasses_cache() {
+        let cache = cache(60, Counter::default());
+
+        assert_eq!(body(&cache, &request("GET", &[])).await, "1");
+        for req in [
+            request("GET", &[("Cache-Control", "no-cache")]),
+            request("GET", &[("cache-control", "max-age=10, No-Store")]),
+            request("POST", &[]),
+        ] {
+            let first = body(&cache, &req).await;
+            assert_ne!(body(&cache, &req).await, first);
+        }
+    }
+
+    #[tokio::test]
+    async fn does_not_cache_errors() {
+        let cache = cache(
+            60,
+            Counter {
+                fail: true,
+                ..Default::default()
+            },
+        );
+        let req = request("GET", &[]);
+
+        assert!(cache.fetch(&req).await.is_err());
+        assert!(cache.fetch(&req).await.is_err());
+        assert_eq!(cache.inner.fetches.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,117 @@
+use std::{io::ErrorKind, process::Stdio};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use jstz_proto::runtime::v2::fetch::http::{Body, Request as HttpRequest, Response};
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use super::provider::{response, Provider};
+
+/// Environment variable holding the method of the request
+pub const METHOD_ENV: &str = "JSTZ_ORACLE_METHOD";
+/// Environment variable holding the URL of the request
+pub const URL_ENV: &str = "JSTZ_ORACLE_URL";
+
+/// Runs a command for every request. The method and the URL of the request are
+/// passed in [`METHOD_ENV`] and [`URL_ENV`] and its body on stdin. The stdout of
+/// a successful run is the body of the response.
+pub struct ExecProvider {
+    command: String,
+    args: Vec<String>,
+}
+
+impl ExecProvider {
+    pub fn new(command: String, args: Vec<String>) -> Self {
+        Self { command, args }
+    }
+}
+
+#[async_trait]
+impl Provider for ExecProvider {
+    async fn fetch(&self, request: &HttpRequest) -> Result<Response> {
+        let mut child = Command::new(&self.command)
+            .args(&self.args)
+            .env(
+                METHOD_ENV,
+                String::from_utf8_lossy(&request.method).as_ref(),
+            )
+            .env(URL_ENV, request.url.as_str())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("failed to run '{}'", self.command))?;
+
+        let mut stdin = child.stdin.take().context("failed to open stdin")?;
+        if let Some(body) = request.body.clone() {
+            match stdin.write_all(&body.to_vec()).await {
+                // The command is free to ignore the body
+                Err(e) if e.kind() != ErrorKind::BrokenPipe => return Err(e.into()),
+                _ => {}
+            }
+        }
+        // Close stdin so that the command sees the end of the body
+        drop(stdin);
+
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            bail!(
+                "'{}' failed with {}: {}",
+                self.command,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(response(200, Body::Vector(output.stdout)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jstz_proto::runtime::v2::fetch::http::{Body, Request as HttpRequest};
+    use url::Url;
+
+    use super::{ExecProvider, Provider};
+
+    fn request(method: &str, body: Option<Body>) -> HttpRequest {
+        HttpRequest {
+            method: method.into(),
+            url: Url::parse("https://ledger.internal/balance").unwrap(),
+            headers: vec![],
+            body,
+        }
+    }
+
+    fn sh(script: &str) -> ExecProvider {
+        ExecProvider::new("sh".to_string(), vec!["-c".to_string(), script.to_string()])
+    }
+
+    #[tokio::test]
+    async fn responds_with_stdout() {
+        let provider =
+            sh(r#"printf '%s %s ' "$JSTZ_ORACLE_METHOD" "$JSTZ_ORACLE_URL"; cat"#);
+        let response = provider
+            .fetch(&request("POST", Some(Body::Vector(b"42".to_vec()))))
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            String::from_utf8(response.body.to_vec()).unwrap(),
+            "POST https://ledger.internal/balance 42"
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_on_non_zero_exit() {
+        let provider = sh("echo 'ledger unavailable' >&2; exit 3");
+        let err = provider.fetch(&request("GET", None)).await.unwrap_err();
+        assert!(
+            err.to_string().contains("ledger unavailable"),
+            "unexpected error: {err}"
+        );
+
+        let provider = ExecProvider::new("/non/existent/command".to_string(), vec![]);
+        assert!(provider.fetch(&request("GET", None)).await.is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use std::{io::ErrorKind, path::PathBuf};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use jstz_proto::runtime::v2::fetch::http::{Body, Request as HttpRequest, Response};
+use reqwest::Method;
+
+use super::provider::{response, Provider};
+
+/// Serves static files from `root`. The path of the requested URL is resolved
+/// relative to `root`, e.g. `https://prices.internal/eur/usd` with root
+/// `/srv/prices` serves `/srv/prices/eur/usd`.
+pub struct FileProvider {
+    root: PathBuf,
+}
+
+impl FileProvider {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn resolve(&self, request: &HttpRequest) -> Option<PathBuf> {
+        let mut path = self.root.clone();
+        for segment in request.url.path_segments()? {
+            // Urls are normalised on parsing, but stay on the safe side and never
+            // leave the root
+            if segment == ".." || segment.contains(['/', '\\']) {
+                return None;
+            }
+            if !segment.is_empty() && segment != "." {
+                path.push(segment);
+            }
+        }
+        Some(path)
+    }
+}
+
+#[async_trait]
+impl Provider for FileProvider {
+    async fn fetch(&self, request: &HttpRequest) -> Result<Response> {
+        let method =
+            Method::from_bytes(&request.method).context("invalid HTTP method")?;
+        if method != Method::GET && method != Method::HEAD {
+            return Ok(response(405, Body::zero_capacity()));
+        }
+        let Some(path) = self.resolve(request) else {
+            return Ok(response(404, Body::zero_capacity()));
+        };
+
+        match tokio::fs::read(&path).await {
+            Ok(_) if method == Method::HEAD => Ok(response(200, Body::zero_capacity())),
+            Ok(content) => Ok(response(200, Body::Vector(content))),
+            Err(e)
+                if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::IsADirectory) =>
+            {
+                Ok(response(404, Body::zero_capacity()))
+            }
+            Err(e) => Err(e).with_context(|| format!("failed to read {path:?}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jstz_proto::runtime::v2::fetch::http::{Body, Request as HttpRequest};
+    use tempfile::TempDir;
+    use url::Url;
+
+    use super::{FileProvider, Provider};
+
+    fn request(method: &str, url: &str) -> HttpRequest {
+        HttpRequest {
+            method: method.into(),
+            url: Url::parse(url).unwrap(),
+            headers: vec![],
+            body: None::<Body>,
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_files_under_root() {
+        let root = TempDir::new().unwrap();
+        std::fs::create_dir(root.path().join("eur")).unwrap();
+        std::fs::write(root.path().join("eur/usd"), "1.08").unwrap();
+        let provider = FileProvider::new(root.path().to_path_buf());
+
+        let response = provider
+            .fetch(&request("GET", "https://prices.internal/eur/usd"))
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body.to_vec(), b"1.08");
+
+        let response = provider
+            .fetch(&request("HEAD", "https://prices.internal/eur/usd"))
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert!(response.body.to_vec().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_files_and_other_methods() {
+        let root = TempDir::new().unwrap();
+        let provider = FileProvider::new(root.path().join("prices"));
+        std::fs::write(root.path().join("secret"), "").unwrap();
+
+        for (method, url, status) in [
+            ("GET", "https://prices.internal/eur/chf", 404),
+            ("GET", "https://prices.internal/", 404),
+            ("GET", "https://prices.internal/../secret", 404),
+            ("POST", "https://prices.internal/eur/usd", 405),
+        ] {
+            let response = provider.fetch(&request(method, url)).await.unwrap();
+            assert_eq!(response.status, status, "{method} {url}");
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use jstz_proto::runtime::v2::fetch::http::{
+    convert_header_map, Body, Request as HttpRequest, Response,
+};
+use reqwest::header::{HeaderMap as ReqwestHeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Method};
+
+use super::provider::{response, Provider};
+
+/// Fetches the requested URL over HTTP(S) in a single attempt. Timeouts and
+/// retries are applied by [`super::Retry`].
+///
+/// Bodies are read until `max_body_size` and the response is replaced by 502
+/// Bad Gateway past it, so that an oversized body is never held in memory.
+pub struct HttpProvider {
+    client: Client,
+    max_body_size: Option<usize>,
+}
+
+impl HttpProvider {
+    pub fn new(client: Client, max_body_size: Option<usize>) -> Self {
+        Self {
+            client,
+            max_body_size,
+        }
+    }
+
+    async fn execute(&self, method: &Method, request: &HttpRequest) -> Result<Response> {
+        let mut builder = self.client.request(method.clone(), request.url.clone());
+
+        // Headers
+        let mut headers = ReqwestHeaderMap::new();
+        for (name, value) in &request.headers {
+            headers.append(
+                HeaderName::from_bytes(name)?,
+                HeaderValue::from_bytes(value)?,
+            );
+        }
+        builder = builder.headers(headers);
+
+        // Body
+        if let Some(body) = request.body.clone() {
+            builder = builder.body::<Vec<u8>>(body.into());
+        }
+
+        let mut resp = builder.send().await?;
+
+        let status = resp.status().as_u16();
+        let status_text = resp
+            .status()
+            .canonical_reason()
+            .unwrap_or("Unknown")
+            .to_string();
+
+        // TODO: Update reqwest and simplify this
+        let headers = convert_header_map(http::HeaderMap::from_iter(
+            resp.headers().iter().map(|(name, value)| {
+                (
+                    http::HeaderName::from_bytes(name.as_str().as_bytes()).unwrap(),
+                    http::HeaderValue::from_bytes(value.as_bytes()).unwrap(),
+                )
+            }),
+        ));
+
+        let mut body = Vec::new();
+        while let Some(chunk) = resp.chunk().await? {
+            if let Some(max) = self.max_body_size {
+                if body.len() + chunk.len() > max {
+                    return Ok(too_large(max));
+                }
+            }
+            body.extend_from_slice(&chunk);
+        }
+        let body = Body::Vector(body);
+
+        Ok(Response {
+            status,
+            status_text,
+            headers,
+            body,
+        })
+    }
+}
+
+fn too_large(max: usize) -> Response {
+    response(
+        502,
+        format!("response exceeds the limit of {max} bytes").into(),
+    )
+}
+
+#[async_trait]
+impl Provider for HttpProvider {
+    async fn fetch(&self, request: &HttpRequest) -> Result<Response> {
+        let method =
+            Method::from_bytes(&request.method).context("invalid HTTP method")?;
+        self.execute(&method, request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use jstz_proto::runtime::v2::fetch::http::Request as HttpRequest;
+    use reqwest::Client;
+    use url::Url;
+
+    use super::{HttpProvider, Provider};
+
+    #[tokio::test]
+    async fn caps_body_while_reading() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/")
+            .with_chunked_body(|w| {
+                for _ in 0..64 {
+                    w.write_all(&[b'x'; 1024])?;
+                }
+                Ok(())
+            })
+            .create_async()
+            .await;
+        let request = HttpRequest {
+            method: "GET".into(),
+            url: Url::parse(&server.url()).unwrap(),
+            headers: vec![],
+            body: None,
+        };
+
+        let capped = HttpProvider::new(Client::new(), Some(4096));
+        let response = capped.fetch(&request).await.unwrap();
+        assert_eq!(response.status, 502);
+
+        let uncapped = HttpProvider::new(Client::new(), None);
+        let response = uncapped.fetch(&request).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(Vec::<u8>::from(response.body).len(), 64 * 1024);
+    }
+}
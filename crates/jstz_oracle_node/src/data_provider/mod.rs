@@ -1,189 +1,215 @@
-use anyhow::{Context, Result};
-use jstz_client::JstzClient;
+use anyhow::Result;
+use jstz_client::{is_transient, JstzClient};
 use jstz_crypto::{
     public_key::PublicKey, public_key_hash::PublicKeyHash, secret_key::SecretKey,
 };
 use jstz_proto::context::account::Address;
-use jstz_proto::operation::{Content, Operation, OracleResponse, SignedOperation};
-use jstz_proto::receipt::{ReceiptContent, ReceiptResult};
-use jstz_proto::runtime::v2::fetch::http::{
-    convert_header_map, Body, Request as HttpRequest, Response,
+use jstz_proto::operation::{
+    Content, Operation, OracleResponse, RotateOracleKey, SignedOperation,
 };
+use jstz_proto::receipt::{ReceiptContent, ReceiptResult};
+use jstz_proto::runtime::v2::fetch::http::Response;
 use jstz_proto::runtime::v2::oracle::request::OracleRequest;
-use jstz_utils::retry::{exponential_backoff, retry_async};
+use jstz_utils::{
+    retry::{exponential_backoff, retry_async},
+    KeyPair,
+};
 use log::{error, info};
-use reqwest::header::{HeaderMap as ReqwestHeaderMap, HeaderName, HeaderValue};
 use reqwest::Client;
-use reqwest::Method;
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::broadcast::Receiver;
-use tokio::task::AbortHandle;
+use tokio::sync::{
+    broadcast::{error::RecvError, Receiver},
+    mpsc,
+};
+use tokio::task::{AbortHandle, JoinSet};
 use tokio_retry2::strategy::ExponentialBackoff;
 
+use crate::{
+    keys::{KeyRing, ROTATION_OVERLAP},
+    metrics::{Metered, Metrics},
+};
+
+mod cache;
+mod exec;
+mod file;
+mod http;
+mod policy;
+mod provider;
+mod retry;
+
+pub use cache::{Cache, CacheConfig};
+pub use policy::{Policy, PolicyConfig, RateLimit};
+pub use provider::{Provider, ProviderConfig, Providers, ProvidersConfig, Route};
+pub use retry::{Retry, RetryConfig};
+
 #[allow(dead_code)]
 pub struct DataProvider {
-    abort_handle: AbortHandle,
+    fetcher: AbortHandle,
+    injector: AbortHandle,
 }
 
 impl DataProvider {
     #[allow(dead_code)]
     pub async fn spawn(
-        public_key: PublicKey,
-        secret_key: SecretKey,
+        keys: KeyRing,
         node_endpoint: String,
+        providers: &ProvidersConfig,
+        metrics: Arc<Metrics>,
         mut relay_rx: Receiver<OracleRequest>,
     ) -> Result<Self> {
         let client = Client::builder()
             .user_agent("jstz-oracle-data-provider/0.1")
+            .redirect(providers.policy.redirect_policy())
             .build()?;
+        let provider = Arc::new(Policy::new(
+            Cache::new(
+                Metered::new(
+                    Retry::new(Providers::new(client, providers)?, &providers.retry),
+                    metrics.clone(),
+                ),
+                &providers.cache,
+            ),
+            &providers.policy,
+        ));
+        let (response_tx, mut response_rx) = mpsc::unbounded_channel();
+
+        // Requests are fetched concurrently so that identical requests share a
+        // fetch, while responses are injected one at a time because every
+        // injection uses the next nonce of the oracle account
+        let fetcher = tokio::spawn({
+            let metrics = metrics.clone();
+            async move {
+                let mut fetches = JoinSet::new();
+                loop {
+                    tokio::select! {
+                        req = relay_rx.recv() => {
+                            let req = match req {
+                                Ok(req) => req,
+                                Err(RecvError::Lagged(skipped)) => {
+                                    error!(
+                                        "Data provider lagged, dropped {skipped} oracle requests"
+                                    );
+                                    metrics.requests_dropped(skipped);
+                                    continue;
+                                }
+                                Err(RecvError::Closed) => break,
+                            };
+                            metrics.request_received();
+                            let provider = provider.clone();
+                            let response_tx = response_tx.clone();
+                            fetches.spawn(async move {
+                                let response =
+                                    get_oracle_response(provider.as_ref(), &req).await;
+                                let _ = response_tx.send((req, response));
+                            });
+                        }
+                        Some(_) = fetches.join_next() => {}
+                    }
+                }
+                while fetches.join_next().await.is_some() {}
+            }
+        });
 
-        let abort_handle = {
-            let task = tokio::spawn(async move {
-                while let Ok(req) = relay_rx.recv().await {
-                    if let Err(e) = handle_request(
-                        &client,
-                        &req,
-                        &public_key,
-                        &secret_key,
-                        &node_endpoint,
-                    )
-                    .await
-                    {
+        let jstz_client = JstzClient::new(node_endpoint);
+        let injector = tokio::spawn(async move {
+            let mut keys = keys;
+            let mut signer = None;
+            while let Some((req, response)) = response_rx.recv().await {
+                // Scheduled keys are only used once the rollup accepts them
+                while let Some(KeyPair(next, _)) = keys.due().cloned() {
+                    match register_key(keys.active(), &next, &jstz_client).await {
+                        Ok(()) => keys.advance(),
+                        Err(e) => {
+                            error!("Failed to rotate the oracle key: {e:#}");
+                            break;
+                        }
+                    }
+                }
+                let KeyPair(public_key, secret_key) = keys.active();
+                if signer.as_ref() != Some(public_key) {
+                    info!("Signing oracle responses with {}", public_key.to_base58());
+                    signer = Some(public_key.clone());
+                }
+                match handle_response(
+                    &req,
+                    response,
+                    public_key,
+                    secret_key,
+                    &jstz_client,
+                )
+                .await
+                {
+                    Ok(()) => metrics.response_injected(req.id),
+                    Err(e) => {
                         error!("Data provider error: {e:#}");
+                        metrics.injection_failed();
                     }
                 }
-            });
-            task.abort_handle()
-        };
+            }
+        });
 
         Ok(Self {
-            abort_handle: abort_handle,
+            fetcher: fetcher.abort_handle(),
+            injector: injector.abort_handle(),
         })
     }
-}
 
-impl Drop for DataProvider {
-    fn drop(&mut self) {
-        self.abort_handle.abort();
+    pub fn is_running(&self) -> bool {
+        !self.fetcher.is_finished() && !self.injector.is_finished()
     }
 }
 
-fn extract_status_code(msg: &str) -> Option<u16> {
-    msg.find("Status:")
-        .and_then(|pos| msg[pos + 7..].trim().split_whitespace().next())
-        .and_then(|code_str| code_str.parse::<u16>().ok())
-}
-
-async fn execute_http_request(
-    client: &reqwest::Client,
-    method: &Method,
-    request: &HttpRequest,
-) -> Result<Response> {
-    let mut builder = client.request(method.clone(), request.url.clone());
-
-    // Headers
-    let mut headers = ReqwestHeaderMap::new();
-    for (name, value) in &request.headers {
-        headers.append(
-            HeaderName::from_bytes(name)?,
-            HeaderValue::from_bytes(value)?,
-        );
-    }
-    builder = builder.headers(headers);
-
-    // Body
-    if let Some(body) = request.body.clone() {
-        builder = builder.body::<Vec<u8>>(body.into());
+impl Drop for DataProvider {
+    fn drop(&mut self) {
+        self.fetcher.abort();
+        self.injector.abort();
     }
-
-    let resp = builder.send().await?;
-
-    let status = resp.status().as_u16();
-    let status_text = resp
-        .status()
-        .canonical_reason()
-        .unwrap_or("Unknown")
-        .to_string();
-
-    // TODO: Update reqwest and simplify this
-    let headers = convert_header_map(http::HeaderMap::from_iter(
-        resp.headers().iter().map(|(name, value)| {
-            (
-                http::HeaderName::from_bytes(name.as_str().as_bytes()).unwrap(),
-                http::HeaderValue::from_bytes(value.as_bytes()).unwrap(),
-            )
-        }),
-    ));
-
-    let body = Body::Vector(resp.bytes().await?.to_vec());
-
-    Ok(Response {
-        status,
-        status_text,
-        headers,
-        body,
-    })
 }
 
-async fn handle_request(
-    client: &Client,
+async fn handle_response(
     oracle_req: &OracleRequest,
+    response: Result<Response>,
     public_key: &PublicKey,
     signing_key: &SecretKey,
-    node_endpoint: &String,
+    jstz_client: &JstzClient,
 ) -> Result<()> {
-    let response = get_oracle_response(client, oracle_req).await?;
-    inject_oracle_response(oracle_req, public_key, signing_key, node_endpoint, response)
+    inject_oracle_response(oracle_req, public_key, signing_key, jstz_client, response?)
         .await?;
 
     Ok(())
 }
 
-fn is_transient_error(e: &anyhow::Error) -> bool {
-    e.downcast_ref::<reqwest::Error>()
-        .map(|re| re.is_timeout() || re.is_connect() || re.is_request())
-        .unwrap_or(false)
-}
-
 async fn get_oracle_response(
-    client: &Client,
+    provider: &impl Provider,
     oracle_req: &OracleRequest,
 ) -> Result<Response> {
     let OracleRequest { request, .. } = oracle_req;
-
-    let method = Method::from_bytes(&request.method).context("invalid HTTP method")?;
-
-    // Retry only when it's safe and likely transient
-    let should_retry = |e: &anyhow::Error| is_transient_error(e);
-
-    // Execute
-    let send_result =
-        if method == Method::GET || method == Method::HEAD || method == Method::OPTIONS {
-            // 5 attempts: 100 ms → 1.6 s (total back‑off time ≈ 3.1 s)
-            retry_async(
-                exponential_backoff(100, 5, Duration::from_secs(8)),
-                || execute_http_request(client, &method, &request),
-                should_retry,
-            )
-            .await
-        } else {
-            // single attempt
-            execute_http_request(client, &method, &request).await
-        };
-
-    send_result.or_else(|e| Ok(bad_gateway_error_response(e.to_string().as_bytes())))
+    Ok(provider
+        .fetch(request)
+        .await
+        .unwrap_or_else(|e| error_response(&e)))
 }
 
-// MSDN reference: https://developer.mozilla.org/en-US/docs/Web/HTTP/Reference/Status/502
-// The HTTP 502 Bad Gateway server error response status code
-// indicates that a server was acting as a gateway or proxy
-// and that it received an invalid response from the upstream server.
-fn bad_gateway_error_response(body: &[u8]) -> Response {
+/// Response reporting a failed fetch to the smart function, so that it fails
+/// deterministically instead of waiting for the request to time out. The body is
+/// a JSON object `{ "status": <status>, "reason": <error> }`.
+///
+/// MDN references:
+/// - <https://developer.mozilla.org/en-US/docs/Web/HTTP/Reference/Status/502>
+/// - <https://developer.mozilla.org/en-US/docs/Web/HTTP/Reference/Status/504>
+fn error_response(e: &anyhow::Error) -> Response {
+    let (status, status_text) = if retry::is_timeout(e) {
+        (504, "Gateway Timeout")
+    } else {
+        (502, "Bad Gateway")
+    };
+    let body = serde_json::json!({ "status": status, "reason": format!("{e:#}") });
     Response {
-        status: 502,
-        status_text: "Bad Gateway".into(),
-        headers: vec![],
-        body: body.to_vec().into(),
+        status,
+        status_text: status_text.into(),
+        headers: vec![("content-type".into(), "application/json".into())],
+        body: body.to_string().into_bytes().into(),
     }
 }
 
@@ -191,7 +217,7 @@ async fn inject_oracle_response(
     oracle_req: &OracleRequest,
     public_key: &PublicKey,
     signing_key: &SecretKey,
-    node_endpoint: &String,
+    jstz_client: &JstzClient,
     response: Response,
 ) -> Result<()> {
     let OracleRequest { id, .. } = oracle_req;
@@ -201,21 +227,75 @@ async fn inject_oracle_response(
         request_id: id.clone(),
         response,
     };
+    let result = inject_operation(
+        Content::OracleResponse(oracle_response),
+        public_key,
+        signing_key,
+        jstz_client,
+    )
+    .await?;
 
-    let jstz_client = JstzClient::new(node_endpoint.clone());
-
-    let oracle_address = Address::User(PublicKeyHash::from(public_key));
-    let should_retry = |e: &anyhow::Error| {
-        if is_transient_error(e) {
-            return true;
+    match result {
+        ReceiptResult::Success(ReceiptContent::OracleResponse(deploy)) => {
+            info!("Oracle response injected for id={}", deploy.request_id);
+            Ok(())
         }
+        ReceiptResult::Success(_) => Err(anyhow::anyhow!(
+            "Expected a `OracleResponse` receipt, but got something else."
+        )),
+        ReceiptResult::Failed(err) => Err(anyhow::anyhow!(
+            "Failed to inject oracle response with error {err}"
+        )),
+    }
+}
 
-        let msg = e.to_string();
-        if let Some(code) = extract_status_code(&msg) {
-            return code == 429 || (500 <= code && code <= 599);
-        }
-        false
+/// Registers `next` as the oracle key on the rollup with an operation signed by
+/// the active key pair `current`
+async fn register_key(
+    current: &KeyPair,
+    next: &PublicKey,
+    jstz_client: &JstzClient,
+) -> Result<()> {
+    let KeyPair(public_key, secret_key) = current;
+    let rotation = RotateOracleKey {
+        public_key: next.clone(),
+        overlap: ROTATION_OVERLAP,
     };
+    let result = inject_operation(
+        Content::RotateOracleKey(rotation),
+        public_key,
+        secret_key,
+        jstz_client,
+    )
+    .await?;
+
+    match result {
+        ReceiptResult::Success(ReceiptContent::RotateOracleKey(receipt)) => {
+            info!(
+                "Oracle key {} registered, {} accepted until level {}",
+                receipt.public_key.to_base58(),
+                public_key.to_base58(),
+                receipt.retiring_until
+            );
+            Ok(())
+        }
+        ReceiptResult::Success(_) => Err(anyhow::anyhow!(
+            "Expected a `RotateOracleKey` receipt, but got something else."
+        )),
+        ReceiptResult::Failed(err) => Err(anyhow::anyhow!(
+            "Failed to register oracle key with error {err}"
+        )),
+    }
+}
+
+/// Signs an operation with `content`, posts it to the node and waits for its receipt
+async fn inject_operation(
+    content: Content,
+    public_key: &PublicKey,
+    signing_key: &SecretKey,
+    jstz_client: &JstzClient,
+) -> Result<ReceiptResult> {
+    let oracle_address = Address::User(PublicKeyHash::from(public_key));
     let nonce = retry_async(
         exponential_backoff(200, 6, Duration::from_secs(5)),
         || async {
@@ -224,14 +304,16 @@ async fn inject_oracle_response(
                 .await
                 .map_err(Into::into)
         },
-        should_retry,
+        is_transient,
     )
     .await?;
 
     let op = Operation {
         public_key: public_key.clone(),
         nonce,
-        content: Content::OracleResponse(oracle_response),
+        content,
+        max_fee: None,
+        priority_fee: None,
     };
 
     let op_hash = op.hash();
@@ -249,23 +331,11 @@ async fn inject_oracle_response(
                 .await
                 .map_err(Into::into)
         },
-        should_retry,
+        is_transient,
     )
     .await?;
     let receipt = jstz_client.wait_for_operation_receipt(&op_hash).await?;
-
-    match receipt.result {
-        ReceiptResult::Success(ReceiptContent::OracleResponse(deploy)) => {
-            info!("Oracle response injected for id={}", deploy.request_id);
-            Ok(())
-        }
-        ReceiptResult::Success(_) => Err(anyhow::anyhow!(
-            "Expected a `OracleResponse` receipt, but got something else."
-        )),
-        ReceiptResult::Failed(err) => Err(anyhow::anyhow!(
-            "Failed to inject oracle response with error {err}"
-        )),
-    }
+    Ok(receipt.result)
 }
 
 #[cfg(test)]
@@ -287,6 +357,12 @@ mod tests {
             .expect("reqwest client")
     });
 
+    fn http_providers(client: &Client) -> Retry<Providers> {
+        let providers =
+            Providers::new(client.clone(), &ProvidersConfig::default()).unwrap();
+        Retry::new(providers, &RetryConfig::default())
+    }
+
     fn oracle_req(method: &str, url: Url, body: Option<Body>) -> OracleRequest {
         OracleRequest {
             id: 99,
@@ -317,7 +393,7 @@ mod tests {
         let url = Url::parse(&format!("{}/", server.url()))?;
         let req = oracle_req("GET", url, None);
 
-        let response = super::get_oracle_response(&CLIENT, &req).await?;
+        let response = super::get_oracle_response(&http_providers(&CLIENT), &req).await?;
         let binding = response.body.to_vec();
         let html = std::str::from_utf8(&binding)?;
         assert!(
@@ -332,13 +408,39 @@ mod tests {
         let url = Url::parse(&format!("{}/", "http://abc123"))?;
         let req = oracle_req("GET", url, None);
 
-        let response = super::get_oracle_response(&CLIENT, &req).await?;
+        let response = super::get_oracle_response(&http_providers(&CLIENT), &req).await?;
         assert_eq!(response.status, 502);
         assert_eq!(response.status_text, "Bad Gateway");
         // We do not compare body because the actual error in CI and local differ
         Ok(())
     }
 
+    #[tokio::test]
+    async fn handles_provider_errors() -> Result<()> {
+        let providers = Providers::new(
+            CLIENT.clone(),
+            &ProvidersConfig {
+                routes: vec![Route {
+                    pattern: "^https://ledger\\.internal/".to_string(),
+                    provider: ProviderConfig::Exec {
+                        command: "false".to_string(),
+                        args: vec![],
+                    },
+                }],
+                ..Default::default()
+            },
+        )?;
+        let req = oracle_req("GET", Url::parse("https://ledger.internal/balance")?, None);
+
+        let response = super::get_oracle_response(&providers, &req).await?;
+        assert_eq!(response.status, 502);
+        assert_eq!(response.status_text, "Bad Gateway");
+        let body: serde_json::Value = serde_json::from_slice(&response.body.to_vec())?;
+        assert_eq!(body["status"], 502);
+        assert!(body["reason"].is_string());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn echoes_post_body_via_httpbin() -> Result<()> {
         let mut server = mockito::Server::new_async().await;
@@ -355,7 +457,7 @@ mod tests {
         let url = Url::parse(&format!("{}/post", server.url()))?;
         let req = oracle_req("POST", url, Some(Body::Vector(payload)));
 
-        let response = super::get_oracle_response(&CLIENT, &req).await?;
+        let response = super::get_oracle_response(&http_providers(&CLIENT), &req).await?;
         let binding = response.body.to_vec();
         let text = std::str::from_utf8(&binding)?;
 
@@ -413,7 +515,7 @@ mod tests {
             "edsk3gUfUPyBSfrS9CCgmCiQsTCHGkviBDusMxDJstFtojtc1zcpsh",
         )?;
 
-        let node_config = String::from(server.url().as_str());
+        let jstz_client = JstzClient::new(server.url());
 
         let oracle_req = oracle_req("GET", Url::parse("https://example.com")?, None);
         let response = Response {
@@ -428,7 +530,7 @@ mod tests {
             &oracle_req,
             &public_key,
             &secret_key,
-            &node_config,
+            &jstz_client,
             response,
         )
         .await;
@@ -490,7 +592,7 @@ mod tests {
         let secret_key = SecretKey::from_base58(
             "edsk3gUfUPyBSfrS9CCgmCiQsTCHGkviBDusMxDJstFtojtc1zcpsh",
         )?;
-        let node_config = String::from(server.url().as_str());
+        let jstz_client = JstzClient::new(server.url());
 
         let oracle_req = oracle_req("GET", Url::parse("https://example.com")?, None);
         let response = Response {
@@ -505,7 +607,7 @@ mod tests {
             &oracle_req,
             &public_key,
             &secret_key,
-            &node_config,
+            &jstz_client,
             response,
         )
         .await;
@@ -571,7 +673,7 @@ mod tests {
             "edsk3gUfUPyBSfrS9CCgmCiQsTCHGkviBDusMxDJstFtojtc1zcpsh",
         )?;
 
-        let node_cfg = String::from(server.url().as_str());
+        let jstz_client = JstzClient::new(server.url());
 
         let oracle_req = oracle_req("GET", Url::parse("https://example.com")?, None);
         let fake_http_resp = Response {
@@ -585,7 +687,7 @@ mod tests {
             &oracle_req,
             &public_key,
             &secret_key,
-            &node_cfg,
+            &jstz_client,
             fake_http_resp,
         )
         .await?;
@@ -638,7 +740,8 @@ mod tests {
         let url = Url::parse(&format!("{}/data", server.url()))?;
         let req = oracle_req("GET", url, None);
 
-        let resp = super::get_oracle_response(&FAST_CLIENT, &req).await?;
+        let resp =
+            super::get_oracle_response(&http_providers(&FAST_CLIENT), &req).await?;
         assert_eq!(resp.status, 200);
         assert_eq!(String::from_utf8(resp.body.to_vec())?, "ok");
 
@@ -678,8 +781,10 @@ mod tests {
         let payload = Body::Vector(br#"{"msg":"hello"}"#.to_vec());
         let req = oracle_req("POST", url, Some(payload));
 
-        let resp = super::get_oracle_response(&FAST_CLIENT, &req).await?;
-        assert_eq!(resp.status, 502);
+        let resp =
+            super::get_oracle_response(&http_providers(&FAST_CLIENT), &req).await?;
+        assert_eq!(resp.status, 504);
+        assert_eq!(resp.status_text, "Gateway Timeout");
         Ok(())
     }
 }
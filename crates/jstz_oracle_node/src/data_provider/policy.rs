@@ -0,0 +1,337 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use jstz_proto::runtime::v2::fetch::http::{Request as HttpRequest, Response};
+use reqwest::redirect;
+use serde::{Deserialize, Serialize};
+
+use super::provider::{response, Provider};
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Limits the requests the oracle answers, and what it attests to. Responses are
+/// signed with the oracle key, so anything that is not explicitly allowed is
+/// refused.
+///
+/// Domains are either exact hosts, e.g. `api.example.com`, or wildcards matching
+/// any subdomain, e.g. `*.example.com`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyConfig {
+    /// Domains requests may target. All domains are allowed if unset.
+    #[serde(default)]
+    pub allowed_domains: Option<Vec<String>>,
+    /// Maximum number of requests per minute to the matching domains. The
+    /// domains of a rule share its budget and the first matching rule applies.
+    #[serde(default)]
+    pub rate_limits: Vec<RateLimit>,
+    /// Maximum size of response bodies in bytes
+    #[serde(default)]
+    pub max_response_size: Option<usize>,
+    /// Response headers removed before the response is signed, e.g. `set-cookie`
+    #[serde(default)]
+    pub redacted_headers: Vec<String>,
+}
+
+/// Maximum number of redirects followed, as with the default policy of reqwest
+const MAX_REDIRECTS: usize = 10;
+
+impl PolicyConfig {
+    fn is_allowed(&self, host: &str) -> bool {
+        match &self.allowed_domains {
+            Some(domains) => domains.iter().any(|domain| matches(domain, host)),
+            None => true,
+        }
+    }
+
+    /// Redirect policy of the HTTP client. Redirects are only followed to allowed
+    /// domains, otherwise the redirect response itself is returned.
+    pub fn redirect_policy(&self) -> redirect::Policy {
+        let config = self.clone();
+        redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if config.is_allowed(attempt.url().host_str().unwrap_or_default()) {
+                attempt.follow()
+            } else {
+                attempt.stop()
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub domain: String,
+    pub requests_per_minute: u32,
+}
+
+struct Window {
+    start: Instant,
+    requests: u32,
+}
+
+/// Enforces a [`PolicyConfig`] on the requests answered by `P`.
+///
+/// Refused requests are answered with 403 Forbidden if their domain is not
+/// allowed and 429 Too Many Requests if they exceed a rate limit. Responses
+/// exceeding the size cap are replaced by 502 Bad Gateway. Requests served from
+/// a cache count against rate limits too.
+///
+/// HTTP clients must be built with [`PolicyConfig::redirect_policy`] so that
+/// redirects do not escape the allowlist.
+pub struct Policy<P> {
+    inner: P,
+    config: PolicyConfig,
+    windows: Mutex<Vec<Option<Window>>>,
+}
+
+impl<P: Provider> Policy<P> {
+    pub fn new(inner: P, config: &PolicyConfig) -> Self {
+        Self {
+            inner,
+            config: config.clone(),
+            windows: Mutex::new(config.rate_limits.iter().map(|_| None).collect()),
+        }
+    }
+
+    /// Counts the request against the rate limit of `host`. Returns false if the
+    /// limit is exceeded.
+    fn acquire(&self, host: &str) -> bool {
+        let Some(rule) = self
+            .config
+            .rate_limits
+            .iter()
+            .position(|limit| matches(&limit.domain, host))
+        else {
+            return true;
+        };
+
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        let window = &mut windows[rule];
+        if window
+            .as_ref()
+            .is_some_and(|w| now.duration_since(w.start) >= RATE_LIMIT_WINDOW)
+        {
+            *window = None;
+        }
+        let window = window.get_or_insert_with(|| Window {
+            start: now,
+            requests: 0,
+        });
+        if window.requests >= self.config.rate_limits[rule].requests_per_minute {
+            return false;
+        }
+        window.requests += 1;
+        true
+    }
+
+    fn redact(&self, response: &mut Response) {
+        response.headers.retain(|(name, _)| {
+            !self
+                .config
+                .redacted_headers
+                .iter()
+                .any(|redacted| redacted.as_bytes().eq_ignore_ascii_case(name))
+        });
+    }
+}
+
+/// Whether `host` matches the domain pattern `domain`
+fn matches(domain: &str, host: &str) -> bool {
+    let domain = domain.to_ascii_lowercase();
+    let host = host.to_ascii_lowercase();
+    match domain.strip_prefix("*.") {
+        Some(parent) => host
+            .strip_suffix(parent)
+            .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+        None => domain == host,
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for Policy<P> {
+    async fn fetch(&self, request: &HttpRequest) -> Result<Response> {
+        let host = request.url.host_str().unwrap_or_default();
+        if !self.config.is_allowed(host) {
+            return Ok(response(
+                403,
+                format!("domain '{host}' is not allowed by the oracle").into(),
+            ));
+        }
+        if !self.acquire(host) {
+            return Ok(response(
+                429,
+                format!("rate limit of domain '{host}' exceeded").into(),
+            ));
+        }
+
+        let mut fetched = self.inner.fetch(request).await?;
+        if let Some(max) = self.config.max_response_size {
+            let size = fetched.body.len();
+            if size > max {
+                return Ok(response(
+                    502,
+                    format!("response of {size} bytes exceeds the limit of {max} bytes")
+                        .into(),
+                ));
+            }
+        }
+        self.redact(&mut fetched);
+        Ok(fetched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use jstz_proto::runtime::v2::fetch::http::{Request as HttpRequest, Response};
+    use reqwest::Client;
+    use url::Url;
+
+    use super::{matches, Policy, PolicyConfig, Provider, RateLimit};
+
+    /// Responds with a body of the size given by the path of the URL
+    struct Filler;
+
+    #[async_trait]
+    impl Provider for Filler {
+        async fn fetch(&self, request: &HttpRequest) -> Result<Response> {
+            let size = request.url.path()[1..].parse().unwrap_or(0);
+            Ok(Response {
+                status: 200,
+                status_text: "OK".to_string(),
+                headers: vec![
+                    ("Set-Cookie".into(), "session=secret".into()),
+                    ("content-type".into(), "text/plain".into()),
+                ],
+                body: vec![b'x'; size].into(),
+            })
+        }
+    }
+
+    async fn status(policy: &Policy<Filler>, url: &str) -> u16 {
+        let request = HttpRequest {
+            method: "GET".into(),
+            url: Url::parse(url).unwrap(),
+            headers: vec![],
+            body: None,
+        };
+        policy.fetch(&request).await.unwrap().status
+    }
+
+    #[test]
+    fn matches_domains() {
+        assert!(matches("api.example.com", "api.example.com"));
+        assert!(matches("API.example.com", "api.example.com"));
+        assert!(!matches("api.example.com", "example.com"));
+        assert!(matches("*.example.com", "api.example.com"));
+        assert!(matches("*.example.com", "v1.api.example.com"));
+        assert!(!matches("*.example.com", "example.com"));
+        assert!(!matches("*.example.com", "badexample.com"));
+    }
+
+    #[tokio::test]
+    async fn enforces_allowlist() {
+        let policy = Policy::new(
+            Filler,
+            &PolicyConfig {
+                allowed_domains: Some(vec![
+                    "prices.internal".to_string(),
+                    "*.example.com".to_string(),
+                ]),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(status(&policy, "https://prices.internal/1").await, 200);
+        assert_eq!(status(&policy, "https://api.example.com/1").await, 200);
+        assert_eq!(status(&policy, "https://example.com/1").await, 403);
+        assert_eq!(status(&policy, "https://evil.com/1").await, 403);
+    }
+
+    #[tokio::test]
+    async fn enforces_rate_limits() {
+        let policy = Policy::new(
+            Filler,
+            &PolicyConfig {
+                rate_limits: vec![
+                    RateLimit {
+                        domain: "*.example.com".to_string(),
+                        requests_per_minute: 2,
+                    },
+                    RateLimit {
+                        domain: "prices.internal".to_string(),
+                        requests_per_minute: 1,
+                    },
+                ],
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(status(&policy, "https://a.example.com/").await, 200);
+        assert_eq!(status(&policy, "https://b.example.com/").await, 200);
+        assert_eq!(status(&policy, "https://a.example.com/").await, 429);
+        assert_eq!(status(&policy, "https://prices.internal/").await, 200);
+        assert_eq!(status(&policy, "https://prices.internal/").await, 429);
+        // Domains without rule are not limited
+        for _ in 0..5 {
+            assert_eq!(status(&policy, "https://other.com/").await, 200);
+        }
+    }
+
+    #[tokio::test]
+    async fn does_not_follow_redirects_to_disallowed_domains() {
+        let mut server = mockito::Server::new_async().await;
+        let _redirect = server
+            .mock("GET", "/")
+            .with_status(302)
+            .with_header("location", "http://evil.localhost/")
+            .create_async()
+            .await;
+        let config = PolicyConfig {
+            allowed_domains: Some(vec!["127.0.0.1".to_string()]),
+            ..Default::default()
+        };
+        let client = Client::builder()
+            .redirect(config.redirect_policy())
+            .build()
+            .unwrap();
+
+        let response = client.get(server.url()).send().await.unwrap();
+        assert_eq!(response.status(), 302);
+    }
+
+    #[tokio::test]
+    async fn caps_response_size_and_redacts_headers() {
+        let policy = Policy::new(
+            Filler,
+            &PolicyConfig {
+                max_response_size: Some(4),
+                redacted_headers: vec!["set-cookie".to_string()],
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(status(&policy, "https://example.com/4").await, 200);
+        assert_eq!(status(&policy, "https://example.com/5").await, 502);
+
+        let request = HttpRequest {
+            method: "GET".into(),
+            url: Url::parse("https://example.com/1").unwrap(),
+            headers: vec![],
+            body: None,
+        };
+        let response = policy.fetch(&request).await.unwrap();
+        let names: Vec<Vec<u8>> =
+            response.headers.iter().map(|(n, _)| n.to_vec()).collect();
+        assert_eq!(names, vec![b"content-type".to_vec()]);
+    }
+}
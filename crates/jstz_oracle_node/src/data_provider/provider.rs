@@ -0,0 +1,259 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use jstz_proto::runtime::v2::fetch::http::{Body, Request as HttpRequest, Response};
+use regex::Regex;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    cache::CacheConfig, exec::ExecProvider, file::FileProvider, http::HttpProvider,
+    policy::PolicyConfig, retry::RetryConfig,
+};
+
+/// A source of oracle data.
+///
+/// Providers answer the HTTP requests of smart functions. Errors are reported to
+/// the smart function as a 502 Bad Gateway response, or 504 Gateway Timeout if
+/// the provider did not respond in time.
+#[async_trait]
+pub trait Provider: Send + Sync {
+    async fn fetch(&self, request: &HttpRequest) -> Result<Response>;
+}
+
+/// Backend of a route
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ProviderConfig {
+    /// Fetches the requested URL over HTTP(S)
+    Http,
+    /// Serves static files from `root`
+    File { root: PathBuf },
+    /// Runs `command` for every request
+    Exec {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Route {
+    /// Regular expression matched against the full URL of the request
+    pub pattern: String,
+    pub provider: ProviderConfig,
+}
+
+/// Selects the provider of each request. Routes are tried in order and the
+/// first route whose pattern matches the URL of the request wins. Requests that
+/// match no route are fetched over HTTP(S).
+///
+/// ```json
+/// {
+///   "routes": [
+///     { "pattern": "^https://prices\\.internal/", "provider": { "type": "file", "root": "/srv/prices" } },
+///     { "pattern": "^https://ledger\\.internal/", "provider": { "type": "exec", "command": "ledger-query" } }
+///   ],
+///   "retry": { "timeout_ms": 5000, "max_attempts": 3 },
+///   "cache": { "ttl_secs": 10 },
+///   "policy": { "allowed_domains": ["prices.internal", "ledger.internal"] }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProvidersConfig {
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub policy: PolicyConfig,
+}
+
+impl ProvidersConfig {
+    pub fn from_file(path: &std::path::Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {path:?}"))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse providers config {path:?}"))
+    }
+}
+
+pub struct Providers {
+    routes: Vec<(Regex, Box<dyn Provider>)>,
+    default: HttpProvider,
+}
+
+impl Providers {
+    pub fn new(client: Client, config: &ProvidersConfig) -> Result<Self> {
+        let routes = config
+            .routes
+            .iter()
+            .map(|route| {
+                let pattern = Regex::new(&route.pattern).with_context(|| {
+                    format!("invalid route pattern '{}'", route.pattern)
+                })?;
+                let provider: Box<dyn Provider> = match &route.provider {
+                    ProviderConfig::Http => Box::new(HttpProvider::new(
+                        client.clone(),
+                        config.policy.max_response_size,
+                    )),
+                    ProviderConfig::File { root } => {
+                        Box::new(FileProvider::new(root.clone()))
+                    }
+                    ProviderConfig::Exec { command, args } => {
+                        Box::new(ExecProvider::new(command.clone(), args.clone()))
+                    }
+                };
+                Ok((pattern, provider))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            routes,
+            default: HttpProvider::new(client, config.policy.max_response_size),
+        })
+    }
+
+    fn select(&self, request: &HttpRequest) -> &dyn Provider {
+        self.routes
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(request.url.as_str()))
+            .map_or(&self.default as &dyn Provider, |(_, provider)| {
+                provider.as_ref()
+            })
+    }
+}
+
+#[async_trait]
+impl Provider for Providers {
+    async fn fetch(&self, request: &HttpRequest) -> Result<Response> {
+        self.select(request).fetch(request).await
+    }
+}
+
+/// Response with the canonical status text of `status` and no headers
+pub(super) fn response(status: u16, body: Body) -> Response {
+    Response {
+        status,
+        status_text: http::StatusCode::from_u16(status)
+            .ok()
+            .and_then(|s| s.canonical_reason())
+            .unwrap_or("Unknown")
+            .to_string(),
+        headers: vec![],
+        body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jstz_proto::runtime::v2::fetch::http::Request as HttpRequest;
+    use reqwest::Client;
+    use tempfile::TempDir;
+    use url::Url;
+
+    use super::{Provider, ProviderConfig, Providers, ProvidersConfig, Route};
+
+    fn request(url: &str) -> HttpRequest {
+        HttpRequest {
+            method: "GET".into(),
+            url: Url::parse(url).unwrap(),
+            headers: vec![],
+            body: None,
+        }
+    }
+
+    #[test]
+    fn deserialises_config() {
+        let config: ProvidersConfig = serde_json::from_str(
+            r#"{
+                "routes": [
+                    { "pattern": "^https://prices\\.internal/", "provider": { "type": "file", "root": "/srv/prices" } },
+                    { "pattern": "^https://ledger\\.internal/", "provider": { "type": "exec", "command": "ledger-query" } },
+                    { "pattern": ".*", "provider": { "type": "http" } }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.routes,
+            vec![
+                Route {
+                    pattern: "^https://prices\\.internal/".to_string(),
+                    provider: ProviderConfig::File {
+                        root: "/srv/prices".into()
+                    },
+                },
+                Route {
+                    pattern: "^https://ledger\\.internal/".to_string(),
+                    provider: ProviderConfig::Exec {
+                        command: "ledger-query".to_string(),
+                        args: vec![]
+                    },
+                },
+                Route {
+                    pattern: ".*".to_string(),
+                    provider: ProviderConfig::Http,
+                },
+            ]
+        );
+        assert_eq!(
+            serde_json::from_str::<ProvidersConfig>("{}").unwrap(),
+            ProvidersConfig::default()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        let config = ProvidersConfig {
+            routes: vec![Route {
+                pattern: "(".to_string(),
+                provider: ProviderConfig::Http,
+            }],
+            ..Default::default()
+        };
+        assert!(Providers::new(Client::new(), &config).is_err());
+    }
+
+    #[tokio::test]
+    async fn routes_by_first_matching_pattern() {
+        let root = TempDir::new().unwrap();
+        std::fs::write(root.path().join("usd"), "file").unwrap();
+        let exec = |output: &str| ProviderConfig::Exec {
+            command: "echo".to_string(),
+            args: vec!["-n".to_string(), output.to_string()],
+        };
+        let config = ProvidersConfig {
+            routes: vec![
+                Route {
+                    pattern: "^https://prices\\.internal/".to_string(),
+                    provider: ProviderConfig::File {
+                        root: root.path().to_path_buf(),
+                    },
+                },
+                Route {
+                    pattern: "\\.internal/".to_string(),
+                    provider: exec("first"),
+                },
+                Route {
+                    pattern: "^https://ledger\\.internal/".to_string(),
+                    provider: exec("second"),
+                },
+            ],
+            ..Default::default()
+        };
+        let providers = Providers::new(Client::new(), &config).unwrap();
+
+        for (url, body) in [
+            ("https://prices.internal/usd", "file"),
+            ("https://ledger.internal/balance", "first"),
+        ] {
+            let response = providers.fetch(&request(url)).await.unwrap();
+            assert_eq!(String::from_utf8(response.body.to_vec()).unwrap(), body);
+        }
+    }
+}
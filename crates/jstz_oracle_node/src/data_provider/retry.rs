@@ -0,0 +1,210 @@
+use std::{fmt, time::Duration};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use jstz_proto::runtime::v2::fetch::http::{Request as HttpRequest, Response};
+use jstz_utils::retry::{retry_if, RetryPolicy};
+use serde::{Deserialize, Serialize};
+
+use super::{is_transient_error, provider::Provider};
+
+/// Bounds the time spent on a request. Failed attempts of idempotent requests
+/// (GET, HEAD and OPTIONS) are retried if the error is transient, e.g. a timeout
+/// or a refused connection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    /// Milliseconds after which an attempt is abandoned
+    pub timeout_ms: u64,
+    /// Maximum number of attempts, including the first one
+    pub max_attempts: usize,
+    /// Milliseconds before the first retry. The delay doubles after every retry.
+    pub initial_delay_ms: u64,
+    /// Maximum delay between two attempts in milliseconds
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 10_000,
+            max_attempts: 5,
+            initial_delay_ms: 200,
+            max_delay_ms: 8_000,
+        }
+    }
+}
+
+/// An attempt that did not complete in time
+#[derive(Debug)]
+pub struct Timeout(pub Duration);
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no response within {} ms", self.0.as_millis())
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+/// Applies a [`RetryConfig`] to the requests answered by `P`
+pub struct Retry<P> {
+    inner: P,
+    timeout: Duration,
+    policy: RetryPolicy,
+}
+
+impl<P: Provider> Retry<P> {
+    pub fn new(inner: P, config: &RetryConfig) -> Self {
+        Self {
+            inner,
+            timeout: Duration::from_millis(config.timeout_ms),
+            policy: RetryPolicy::exponential(Duration::from_millis(
+                config.initial_delay_ms,
+            ))
+            .with_max_delay(Duration::from_millis(config.max_delay_ms))
+            .with_max_attempts(config.max_attempts.max(1)),
+        }
+    }
+
+    async fn attempt(&self, request: &HttpRequest) -> Result<Response> {
+        tokio::time::timeout(self.timeout, self.inner.fetch(request))
+            .await
+            .map_err(|_| Timeout(self.timeout))?
+    }
+}
+
+pub(super) fn is_timeout(e: &anyhow::Error) -> bool {
+    e.is::<Timeout>()
+        || e.downcast_ref::<reqwest::Error>()
+            .is_some_and(reqwest::Error::is_timeout)
+}
+
+#[async_trait]
+impl<P: Provider> Provider for Retry<P> {
+    async fn fetch(&self, request: &HttpRequest) -> Result<Response> {
+        if !matches!(request.method.as_slice(), b"GET" | b"HEAD" | b"OPTIONS") {
+            return self.attempt(request).await;
+        }
+        retry_if(
+            self.policy,
+            || self.attempt(request),
+            |e| is_timeout(e) || is_transient_error(e),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use anyhow::{bail, Result};
+    use async_trait::async_trait;
+    use jstz_proto::runtime::v2::fetch::http::{Request as HttpRequest, Response};
+    use url::Url;
+
+    use super::{is_timeout, Retry, RetryConfig};
+    use crate::data_provider::provider::{response, Provider};
+
+    /// Hangs on the first `slow` fetches, then responds with the attempt count
+    #[derive(Default)]
+    struct Flaky {
+        attempts: AtomicUsize,
+        slow: usize,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl Provider for Flaky {
+        async fn fetch(&self, _request: &HttpRequest) -> Result<Response> {
+            let attempt = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            if self.fail {
+                bail!("permanent failure");
+            }
+            if attempt <= self.slow {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+            Ok(response(200, attempt.to_string().into()))
+        }
+    }
+
+    fn retry(flaky: Flaky) -> Retry<Flaky> {
+        Retry::new(
+            flaky,
+            &RetryConfig {
+                timeout_ms: 20,
+                max_attempts: 3,
+                initial_delay_ms: 1,
+                max_delay_ms: 5,
+            },
+        )
+    }
+
+    fn request(method: &str) -> HttpRequest {
+        HttpRequest {
+            method: method.into(),
+            url: Url::parse("https://prices.internal/eur/usd").unwrap(),
+            headers: vec![],
+            body: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_timed_out_attempts() {
+        let provider = retry(Flaky {
+            slow: 2,
+            ..Default::default()
+        });
+        let response = provider.fetch(&request("GET")).await.unwrap();
+        assert_eq!(response.body.to_vec(), b"3");
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let provider = retry(Flaky {
+            slow: 3,
+            ..Default::default()
+        });
+        let e = provider.fetch(&request("GET")).await.unwrap_err();
+        assert!(is_timeout(&e), "{e}");
+        assert_eq!(provider.inner.attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_non_idempotent_requests() {
+        let provider = retry(Flaky {
+            slow: 1,
+            ..Default::default()
+        });
+        assert!(provider.fetch(&request("POST")).await.is_err());
+        assert_eq!(provider.inner.attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_permanent_errors() {
+        let provider = retry(Flaky {
+            fail: true,
+            ..Default::default()
+        });
+        let e = provider.fetch(&request("GET")).await.unwrap_err();
+        assert!(!is_timeout(&e));
+        assert_eq!(provider.inner.attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn deserialises_partial_config() {
+        let config: RetryConfig =
+            serde_json::from_str(r#"{ "timeout_ms": 500 }"#).unwrap();
+        assert_eq!(
+            config,
+            RetryConfig {
+                timeout_ms: 500,
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use jstz_utils::KeyPair;
+use serde::Serialize;
+
+/// Key pair the oracle signs with from `active_from` on
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScheduledKeyPair {
+    pub key_pair: KeyPair,
+    /// Unix timestamp in seconds
+    pub active_from: u64,
+}
+
+/// Number of levels during which the rollup still accepts responses signed with
+/// the key being rotated out. Matches the time to live of oracle requests, so
+/// that responses to requests in flight during the rotation are not rejected.
+pub const ROTATION_OVERLAP: u64 = 20;
+
+/// Key pairs of the oracle ordered by activation.
+///
+/// Responses are signed with the active key pair. A scheduled key pair does not
+/// become active when its activation passes but once the rollup has registered
+/// it, see [`KeyRing::due`]: it is registered with a `RotateOracleKey` operation
+/// signed with the active key pair, after which the rollup accepts the replaced
+/// key pair for [`ROTATION_OVERLAP`] levels.
+#[derive(Debug, Clone)]
+pub struct KeyRing {
+    keys: Vec<ScheduledKeyPair>,
+    /// Index of the active key pair, the last one registered on the rollup
+    active: usize,
+}
+
+impl KeyRing {
+    /// Key ring signing with `key_pair` until the first scheduled rotation
+    pub fn new(key_pair: KeyPair, rotation: Vec<ScheduledKeyPair>) -> Self {
+        let mut keys = vec![ScheduledKeyPair {
+            key_pair,
+            active_from: 0,
+        }];
+        keys.extend(rotation);
+        // Stable, so the configuration order breaks ties
+        keys.sort_by_key(|key| key.active_from);
+        Self { keys, active: 0 }
+    }
+
+    pub fn active(&self) -> &KeyPair {
+        &self.keys[self.active].key_pair
+    }
+
+    /// The next key pair to register on the rollup, if its activation has passed
+    pub fn due(&self) -> Option<&KeyPair> {
+        self.due_at(now())
+    }
+
+    /// Switches to the key pair returned by [`KeyRing::due`] once it is registered
+    pub fn advance(&mut self) {
+        if self.active + 1 < self.keys.len() {
+            self.active += 1;
+        }
+    }
+
+    fn due_at(&self, timestamp: u64) -> Option<&KeyPair> {
+        self.keys
+            .get(self.active + 1)
+            .filter(|key| key.active_from <= timestamp)
+            .map(|key| &key.key_pair)
+    }
+}
+
+impl From<KeyPair> for KeyRing {
+    fn from(key_pair: KeyPair) -> Self {
+        Self::new(key_pair, vec![])
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use jstz_crypto::keypair_from_mnemonic;
+    use jstz_utils::KeyPair;
+
+    use super::{KeyRing, ScheduledKeyPair};
+
+    const MNEMONIC: &str =
+        "donate kidney style loyal nose core inflict cup symptom speed giant polar";
+
+    fn key_pair(index: u32) -> KeyPair {
+        let (pk, sk) = keypair_from_mnemonic(MNEMONIC, &index.to_string()).unwrap();
+        KeyPair(pk, sk)
+    }
+
+    #[test]
+    fn rotates_on_schedule_once_registered() {
+        let mut ring = KeyRing::new(
+            key_pair(0),
+            vec![
+                ScheduledKeyPair {
+                    key_pair: key_pair(2),
+                    active_from: 200,
+                },
+                ScheduledKeyPair {
+                    key_pair: key_pair(1),
+                    active_from: 100,
+                },
+            ],
+        );
+
+        assert_eq!(ring.active(), &key_pair(0));
+        assert_eq!(ring.due_at(99), None);
+        // The due key pair is not used before it is registered
+        assert_eq!(ring.due_at(250), Some(&key_pair(1)));
+        assert_eq!(ring.active(), &key_pair(0));
+
+        ring.advance();
+        assert_eq!(ring.active(), &key_pair(1));
+        assert_eq!(ring.due_at(199), None);
+        assert_eq!(ring.due_at(250), Some(&key_pair(2)));
+
+        ring.advance();
+        assert_eq!(ring.active(), &key_pair(2));
+        assert_eq!(ring.due_at(u64::MAX), None);
+        ring.advance();
+        assert_eq!(ring.active(), &key_pair(2));
+    }
+
+    #[test]
+    fn signs_with_single_key_pair() {
+        let ring = KeyRing::from(key_pair(0));
+        assert_eq!(ring.active(), &key_pair(0));
+        assert_eq!(ring.due(), None);
+    }
+}
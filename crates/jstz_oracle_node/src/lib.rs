@@ -3,18 +3,30 @@
 use std::path::PathBuf;
 
 use jstz_utils::KeyPair;
+use keys::ScheduledKeyPair;
 use octez::r#async::endpoint::Endpoint;
 use serde::Serialize;
 mod data_provider;
+pub mod keys;
+pub mod metrics;
 pub mod node;
 pub mod relay;
 
+pub use data_provider::{
+    CacheConfig, PolicyConfig, ProviderConfig, ProvidersConfig, RateLimit, RetryConfig,
+    Route,
+};
+
 #[derive(Clone, Serialize)]
 pub struct OracleNodeConfig {
     /// The Oracle signer used to authenticate valid oracle responses
     pub key_pair: Option<KeyPair>,
+    /// Key pairs replacing `key_pair` on schedule
+    pub key_rotation: Vec<ScheduledKeyPair>,
     pub log_path: PathBuf,
     pub jstz_node_endpoint: Endpoint,
+    /// Routes requests to the backends serving them
+    pub providers: ProvidersConfig,
 }
 
 #[cfg(test)]
@@ -32,8 +44,10 @@ mod tests {
 
         let cfg = super::OracleNodeConfig {
             key_pair: Some(KeyPair(oracle_pk, oracle_sk)),
+            key_rotation: vec![],
             log_path: PathBuf::from("/tmp/debug.log"),
             jstz_node_endpoint: Endpoint::localhost(1234),
+            providers: Default::default(),
         };
 
         let json = serde_json::to_value(&cfg).unwrap();
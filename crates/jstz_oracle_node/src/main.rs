@@ -1,11 +1,16 @@
-use std::path::PathBuf;
+use std::{net::SocketAddr, path::PathBuf};
 
 use anyhow::Context;
 use clap::Parser;
 use env_logger::Env;
 #[cfg(feature = "v2_runtime")]
-use jstz_oracle_node::node::OracleNode;
-use jstz_utils::key_pair::{parse_key_file, KeyPair};
+use jstz_oracle_node::{
+    keys::{KeyRing, ScheduledKeyPair},
+    metrics,
+    node::OracleNode,
+    ProvidersConfig,
+};
+use jstz_utils::key_pair::{KeyPair, KeySource};
 
 const DEFAULT_JSTZ_NODE_ENDPOINT: &str = "http://127.0.0.1:8933";
 
@@ -21,9 +26,31 @@ struct Args {
     #[arg(long, default_value = DEFAULT_JSTZ_NODE_ENDPOINT)]
     node_endpoint: String,
 
-    /// Path to file containing key pair (format: {"public_key": ..., "secret_key": ...})
+    /// Key pair, either a path to a key file or `env:<VAR>` (format: {"public_key": ..., "secret_key": ...}).
+    /// Encrypted key files are decrypted with the password in `JSTZ_KEYSTORE_PASSWORD`.
     #[arg(long)]
-    key_file: PathBuf,
+    key_file: KeySource,
+
+    /// Key pair replacing the one of `--key-file` at `--next-key-active-from`, in the
+    /// same format. The new key is registered on the rollup with the current key before
+    /// it is used.
+    #[arg(long, requires = "next_key_active_from")]
+    next_key_file: Option<KeySource>,
+
+    /// Unix timestamp in seconds from which the key pair of `--next-key-file` is
+    /// registered and used
+    #[arg(long, requires = "next_key_file")]
+    next_key_active_from: Option<u64>,
+
+    /// Path to a JSON file routing requests to data providers (HTTP(S) fetcher,
+    /// static files or commands). Requests are fetched over HTTP(S) if unset.
+    #[arg(long)]
+    providers_config: Option<PathBuf>,
+
+    /// Address serving `/health` and Prometheus metrics at `/metrics`, e.g.
+    /// `127.0.0.1:9090`. Not served if unset.
+    #[arg(long)]
+    metrics_addr: Option<SocketAddr>,
 }
 
 #[tokio::main]
@@ -44,8 +71,13 @@ async fn main() -> anyhow::Result<()> {
         .context("Failed to canonicalize log path")?;
 
     // Parse key file
-    let KeyPair(public_key, _secret_key) =
-        parse_key_file(args.key_file).context("failed to parse key file")?;
+    let key_pair = KeyPair::load(&args.key_file).context("failed to parse key file")?;
+
+    #[cfg(feature = "v2_runtime")]
+    let providers = match &args.providers_config {
+        Some(path) => ProvidersConfig::from_file(path)?,
+        None => ProvidersConfig::default(),
+    };
 
     log::info!("Starting JSTZ Oracle Node");
     log::info!(
@@ -53,20 +85,49 @@ async fn main() -> anyhow::Result<()> {
         canonical_log_path
     );
     log::info!("Node endpoint: {}", args.node_endpoint);
-    log::info!("Public key: {}", public_key.to_base58());
+    log::info!("Public key: {}", key_pair.0.to_base58());
 
     // Spawn the oracle node
     #[cfg(feature = "v2_runtime")]
     {
-        let _oracle_node = OracleNode::spawn(
+        let mut key_rotation = vec![];
+        if let (Some(next_key_file), Some(active_from)) =
+            (&args.next_key_file, args.next_key_active_from)
+        {
+            let next_key_pair =
+                KeyPair::load(next_key_file).context("failed to parse next key file")?;
+            log::info!(
+                "Rotating to public key {} at {active_from}",
+                next_key_pair.0.to_base58()
+            );
+            key_rotation.push(ScheduledKeyPair {
+                key_pair: next_key_pair,
+                active_from,
+            });
+        }
+
+        let oracle_node = OracleNode::spawn(
             canonical_log_path,
-            public_key,
-            _secret_key,
+            KeyRing::new(key_pair, key_rotation),
             args.node_endpoint,
+            &providers,
         )
         .await
         .context("Failed to spawn oracle node")?;
 
+        if let Some(addr) = args.metrics_addr {
+            let listener = tokio::net::TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("Failed to bind metrics address {addr}"))?;
+            log::info!("Serving health and metrics on: {addr}");
+            let node_metrics = oracle_node.metrics();
+            tokio::spawn(async move {
+                if let Err(e) = metrics::serve(listener, node_metrics).await {
+                    log::error!("Metrics server error: {e:#}");
+                }
+            });
+        }
+
         log::info!("Oracle node started successfully");
 
         // Keep the node running. The node will keep running until this task is dropped
@@ -0,0 +1,296 @@
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use axum::{
+    extract::State, http::header, response::IntoResponse, routing::get, Json, Router,
+};
+use jstz_proto::runtime::v2::{
+    fetch::http::{Request as HttpRequest, Response},
+    oracle::RequestId,
+};
+use serde::Serialize;
+use tokio::net::TcpListener;
+
+use crate::data_provider::Provider;
+
+/// Counters of the oracle node, exposed by [`serve`]
+#[derive(Debug, Default)]
+pub struct Metrics {
+    requests: AtomicU64,
+    dropped_requests: AtomicU64,
+    injected_responses: AtomicU64,
+    failed_injections: AtomicU64,
+    fetches: AtomicU64,
+    fetch_errors: AtomicU64,
+    last_processed: Mutex<Option<ProcessedRequest>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ProcessedRequest {
+    pub id: RequestId,
+    /// Unix timestamp in seconds
+    pub at: u64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Health {
+    /// Requests relayed to the data provider that are not answered yet
+    pub relay_lag: u64,
+    /// Requests dropped because the data provider fell too far behind the relay
+    pub dropped_requests: u64,
+    pub last_processed_request: Option<ProcessedRequest>,
+    /// Share of provider fetches that failed, 0 if nothing was fetched yet
+    pub provider_error_rate: f64,
+}
+
+impl Metrics {
+    pub fn request_received(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn requests_dropped(&self, count: u64) {
+        self.dropped_requests.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn response_injected(&self, id: RequestId) {
+        self.injected_responses.fetch_add(1, Ordering::Relaxed);
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        self.last_processed
+            .lock()
+            .unwrap()
+            .replace(ProcessedRequest { id, at });
+    }
+
+    pub fn injection_failed(&self) {
+        self.failed_injections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn health(&self) -> Health {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let answered = self.injected_responses.load(Ordering::Relaxed)
+            + self.failed_injections.load(Ordering::Relaxed);
+        let fetches = self.fetches.load(Ordering::Relaxed);
+        let fetch_errors = self.fetch_errors.load(Ordering::Relaxed);
+        Health {
+            relay_lag: requests.saturating_sub(answered),
+            dropped_requests: self.dropped_requests.load(Ordering::Relaxed),
+            last_processed_request: *self.last_processed.lock().unwrap(),
+            provider_error_rate: match fetches {
+                0 => 0.0,
+                _ => fetch_errors as f64 / fetches as f64,
+            },
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let health = self.health();
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            let _ = write!(
+                out,
+                "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"
+            );
+        };
+        let counter = |c: &AtomicU64| c.load(Ordering::Relaxed).to_string();
+
+        metric(
+            "jstz_oracle_requests_total",
+            "counter",
+            "Oracle requests relayed to the data provider",
+            counter(&self.requests),
+        );
+        metric(
+            "jstz_oracle_dropped_requests_total",
+            "counter",
+            "Oracle requests dropped because the data provider lagged behind",
+            counter(&self.dropped_requests),
+        );
+        metric(
+            "jstz_oracle_injected_responses_total",
+            "counter",
+            "Oracle responses injected into the rollup",
+            counter(&self.injected_responses),
+        );
+        metric(
+            "jstz_oracle_failed_injections_total",
+            "counter",
+            "Oracle responses that could not be injected",
+            counter(&self.failed_injections),
+        );
+        metric(
+            "jstz_oracle_provider_fetches_total",
+            "counter",
+            "Fetches from data providers",
+            counter(&self.fetches),
+        );
+        metric(
+            "jstz_oracle_provider_errors_total",
+            "counter",
+            "Failed fetches from data providers",
+            counter(&self.fetch_errors),
+        );
+        metric(
+            "jstz_oracle_relay_lag",
+            "gauge",
+            "Oracle requests relayed to the data provider that are not answered yet",
+            health.relay_lag.to_string(),
+        );
+        if let Some(last) = health.last_processed_request {
+            metric(
+                "jstz_oracle_last_processed_timestamp_seconds",
+                "gauge",
+                "Unix timestamp of the last injected oracle response",
+                last.at.to_string(),
+            );
+        }
+        out
+    }
+}
+
+/// Counts the fetches of `P` and their failures
+pub struct Metered<P> {
+    inner: P,
+    metrics: Arc<Metrics>,
+}
+
+impl<P: Provider> Metered<P> {
+    pub fn new(inner: P, metrics: Arc<Metrics>) -> Self {
+        Self { inner, metrics }
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for Metered<P> {
+    async fn fetch(&self, request: &HttpRequest) -> Result<Response> {
+        self.metrics.fetches.fetch_add(1, Ordering::Relaxed);
+        let result = self.inner.fetch(request).await;
+        if result.is_err() {
+            self.metrics.fetch_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+fn router(metrics: Arc<Metrics>) -> Router {
+    Router::new()
+        .route("/health", get(health_handler))
+        .route("/metrics", get(metrics_handler))
+        .with_state(metrics)
+}
+
+async fn health_handler(State(metrics): State<Arc<Metrics>>) -> Json<Health> {
+    Json(metrics.health())
+}
+
+async fn metrics_handler(State(metrics): State<Arc<Metrics>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(),
+    )
+}
+
+/// Serves `/health` and the Prometheus metrics at `/metrics` on `listener`
+pub async fn serve(listener: TcpListener, metrics: Arc<Metrics>) -> Result<()> {
+    axum::serve(listener, router(metrics)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use anyhow::{bail, Result};
+    use async_trait::async_trait;
+    use jstz_proto::runtime::v2::fetch::http::{Request as HttpRequest, Response};
+    use tokio::net::TcpListener;
+
+    use super::{Metered, Metrics, ProcessedRequest, Provider};
+
+    struct Failing;
+
+    #[async_trait]
+    impl Provider for Failing {
+        async fn fetch(&self, _request: &HttpRequest) -> Result<Response> {
+            bail!("upstream unavailable")
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_health() {
+        let metrics = Arc::new(Metrics::default());
+        assert_eq!(metrics.health().relay_lag, 0);
+        assert_eq!(metrics.health().provider_error_rate, 0.0);
+        assert_eq!(metrics.health().last_processed_request, None);
+
+        for _ in 0..3 {
+            metrics.request_received();
+        }
+        metrics.response_injected(7);
+        metrics.injection_failed();
+        let provider = Metered::new(Failing, metrics.clone());
+        let request = HttpRequest {
+            method: "GET".into(),
+            url: "https://example.com".parse().unwrap(),
+            headers: vec![],
+            body: None,
+        };
+        assert!(provider.fetch(&request).await.is_err());
+
+        let health = metrics.health();
+        assert_eq!(health.relay_lag, 1);
+        assert_eq!(health.provider_error_rate, 1.0);
+        assert!(matches!(
+            health.last_processed_request,
+            Some(ProcessedRequest { id: 7, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn serves_health_and_metrics() {
+        let metrics = Arc::new(Metrics::default());
+        metrics.request_received();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(super::serve(listener, metrics));
+
+        let health: serde_json::Value = reqwest::get(format!("http://{addr}/health"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(
+            health,
+            serde_json::json!({
+                "relay_lag": 1,
+                "dropped_requests": 0,
+                "last_processed_request": null,
+                "provider_error_rate": 0.0
+            })
+        );
+
+        let body = reqwest::get(format!("http://{addr}/metrics"))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(body.contains(
+            "# TYPE jstz_oracle_requests_total counter\njstz_oracle_requests_total 1\n"
+        ));
+        assert!(body.contains("jstz_oracle_relay_lag 1\n"));
+        assert!(!body.contains("jstz_oracle_last_processed_timestamp_seconds"));
+    }
+}
@@ -1,10 +1,14 @@
 use jstz_proto::runtime::v2::oracle::OracleRequest;
 #[cfg(feature = "v2_runtime")]
 use {
-    crate::{data_provider::DataProvider, relay::Relay},
+    crate::{
+        data_provider::{DataProvider, ProvidersConfig},
+        keys::KeyRing,
+        metrics::Metrics,
+        relay::Relay,
+    },
     anyhow::Result,
-    jstz_crypto::{public_key::PublicKey, secret_key::SecretKey},
-    std::path::PathBuf,
+    std::{path::PathBuf, sync::Arc},
     tokio::sync::broadcast::Receiver,
 };
 
@@ -18,43 +22,59 @@ pub struct OracleNode {
     _relay: Relay,
     /// Ditto for the data‑provider.
     _provider: DataProvider,
+    metrics: Arc<Metrics>,
 }
 
 #[cfg(feature = "v2_runtime")]
 impl OracleNode {
     pub async fn spawn(
         log_path: PathBuf,
-        public_key: PublicKey,
-        secret_key: SecretKey,
+        keys: KeyRing,
         node_endpoint: String,
+        providers: &ProvidersConfig,
     ) -> Result<Self> {
         let relay = Relay::spawn(log_path).await?;
         let rx: Receiver<OracleRequest> = relay.subscribe()?;
+        let metrics = Arc::new(Metrics::default());
         let provider =
-            DataProvider::spawn(public_key, secret_key, node_endpoint, rx).await?;
+            DataProvider::spawn(keys, node_endpoint, providers, metrics.clone(), rx)
+                .await?;
 
         Ok(Self {
             _relay: relay,
             _provider: provider,
+            metrics,
         })
     }
+
+    /// Whether the relay and the data provider are still processing requests
+    pub fn is_running(&self) -> bool {
+        self._relay.is_running() && self._provider.is_running()
+    }
+
+    /// Metrics of the node, served by [`crate::metrics::serve`]
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use anyhow::Result;
+    use jstz_crypto::{public_key::PublicKey, secret_key::SecretKey};
+    use jstz_utils::KeyPair;
     use tempfile::NamedTempFile;
     use tokio::time::{sleep, Duration};
 
-    fn create_test_keys() -> Result<(PublicKey, SecretKey)> {
+    fn create_test_keys() -> Result<KeyRing> {
         let public_key = PublicKey::from_base58(
             "edpkuBknW28nW72KG6RoHtYW7p12T6GKc7nAbwYX5m8Wd9sDVC9yav",
         )?;
         let secret_key = SecretKey::from_base58(
             "edsk3gUfUPyBSfrS9CCgmCiQsTCHGkviBDusMxDJstFtojtc1zcpsh",
         )?;
-        Ok((public_key, secret_key))
+        Ok(KeyPair(public_key, secret_key).into())
     }
 
     #[tokio::test]
@@ -62,14 +82,17 @@ mod tests {
         let tmp = NamedTempFile::new()?;
         let log_path = tmp.path().to_path_buf();
 
-        let (public_key, secret_key) = create_test_keys()?;
+        let keys = create_test_keys()?;
 
         let node_endpoint = "http://localhost:8080".to_string();
 
         let oracle_node =
-            OracleNode::spawn(log_path, public_key, secret_key, node_endpoint).await?;
+            OracleNode::spawn(log_path, keys, node_endpoint, &ProvidersConfig::default())
+                .await?;
 
         assert!(oracle_node._relay.tx.receiver_count() > 0);
+        assert!(oracle_node.is_running());
+        assert_eq!(oracle_node.metrics().health().relay_lag, 0);
 
         Ok(())
     }
@@ -79,16 +102,16 @@ mod tests {
         let tmp = NamedTempFile::new()?;
         let log_path = tmp.path().to_path_buf();
 
-        let (public_key, secret_key) = create_test_keys()?;
+        let keys = create_test_keys()?;
 
         let node_endpoint = "http://localhost:8080".to_string();
 
         {
             let oracle_node = OracleNode::spawn(
                 log_path.clone(),
-                public_key,
-                secret_key,
+                keys,
                 node_endpoint,
+                &ProvidersConfig::default(),
             )
             .await?;
 
@@ -102,14 +125,18 @@ mod tests {
 
     #[tokio::test]
     async fn handles_invalid_log_path() -> Result<()> {
-        let (public_key, secret_key) = create_test_keys()?;
+        let keys = create_test_keys()?;
 
         let invalid_log_path = PathBuf::from("/non/existent/path.log");
         let node_endpoint = "http://localhost:8080".to_string();
 
-        let result =
-            OracleNode::spawn(invalid_log_path, public_key, secret_key, node_endpoint)
-                .await;
+        let result = OracleNode::spawn(
+            invalid_log_path,
+            keys,
+            node_endpoint,
+            &ProvidersConfig::default(),
+        )
+        .await;
 
         assert!(result.is_err());
 
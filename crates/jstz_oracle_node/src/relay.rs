@@ -59,6 +59,10 @@ impl Relay {
         }
         Ok(self.tx.subscribe())
     }
+
+    pub fn is_running(&self) -> bool {
+        !self.abort_handle.is_finished()
+    }
 }
 
 impl Drop for Relay {
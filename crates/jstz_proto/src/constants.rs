@@ -0,0 +1,94 @@
+//! Constants of the protocol served by the node on `/constants`, so that clients
+//! do not hardcode values that differ between deployments.
+
+use jstz_core::reveal_data::{MAX_DECOMPRESSED_REVEAL_SIZE, MAX_REVEAL_SIZE};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    context::{account::Amount, interface::MAX_INTERFACE_SIZE},
+    operation::MAX_DIRECT_OPERATION_SIZE,
+};
+
+/// Fee, in mutez, charged per unit of gas of the gas limit of an operation. Gas is
+/// not charged by the protocol yet.
+pub const GAS_PRICE: Amount = 0;
+
+/// Fee, in mutez, charged per byte of an encoded operation. Storage is not charged
+/// by the protocol yet.
+pub const STORAGE_FEE_RATE: Amount = 0;
+
+/// Maximum number of host calls the smart functions called by an operation make in
+/// total. Operations exceeding it fail with [`crate::Error::ExecutionBudgetExceeded`].
+pub const MAX_HOST_CALLS: u64 = 100_000;
+
+/// Constants of the protocol and limits of the node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Constants {
+    /// Version of the node
+    pub version: String,
+    /// Version of the runtime executing smart functions, `v1` or `v2`
+    pub runtime_version: String,
+    /// Fee, in mutez, charged per unit of gas of the gas limit of an operation
+    pub gas_price: Amount,
+    /// Fee, in mutez, charged per byte of an encoded operation
+    pub storage_fee_rate: Amount,
+    /// Maximum size, in bytes, of an encoded operation, including the code it deploys
+    pub max_operation_size: usize,
+    /// Maximum size, in bytes, of an encoded operation sent as is to the rollup.
+    /// Larger operations are revealed by the node.
+    pub max_direct_operation_size: usize,
+    /// Maximum size, in bytes, of revealed data
+    pub max_reveal_size: usize,
+    /// Maximum size, in bytes, of the interface of a smart function
+    pub max_interface_size: usize,
+    /// Number of operations the sequencer queue holds, 0 in other modes
+    pub queue_capacity: usize,
+}
+
+impl Default for Constants {
+    /// The constants this crate was built with, for a node in default mode
+    fn default() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            runtime_version: if cfg!(feature = "v2_runtime") {
+                "v2"
+            } else {
+                "v1"
+            }
+            .to_string(),
+            gas_price: GAS_PRICE,
+            storage_fee_rate: STORAGE_FEE_RATE,
+            max_operation_size: MAX_DECOMPRESSED_REVEAL_SIZE,
+            max_direct_operation_size: MAX_DIRECT_OPERATION_SIZE,
+            max_reveal_size: MAX_REVEAL_SIZE,
+            max_interface_size: MAX_INTERFACE_SIZE,
+            queue_capacity: 0,
+        }
+    }
+}
+
+impl Constants {
+    /// Returns the fee, in mutez, charged for the gas limit and the size of an
+    /// operation, on top of the fees it declares
+    pub fn base_fee(&self, gas_limit: usize, size: usize) -> Amount {
+        self.gas_price
+            .saturating_mul(gas_limit as Amount)
+            .saturating_add(self.storage_fee_rate.saturating_mul(size as Amount))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Constants;
+
+    #[test]
+    fn base_fee() {
+        let constants = Constants {
+            gas_price: 2,
+            storage_fee_rate: 3,
+            ..Constants::default()
+        };
+        assert_eq!(constants.base_fee(10, 100), 320);
+        assert_eq!(Constants::default().base_fee(10, 100), 0);
+    }
+}
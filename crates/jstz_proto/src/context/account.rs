@@ -5,11 +5,17 @@ use std::{
     str::FromStr,
 };
 
+use super::code::{CodeHash, CodeStore};
 use crate::{
     error::{Error, Result},
     runtime::ParsedCode,
 };
-use bincode::{Decode, Encode};
+use bincode::{
+    de::Decoder,
+    enc::Encoder,
+    error::{DecodeError, EncodeError},
+    Decode, Encode,
+};
 use boa_gc::{empty_trace, Finalize, Trace};
 use derive_more::From;
 use jstz_core::kv::{
@@ -20,6 +26,7 @@ use jstz_core::kv::{
 use jstz_core::{
     host::HostRuntime,
     kv::{Entry, Transaction},
+    BinEncodable, Versioned, VersionedValue, UNVERSIONED,
 };
 use jstz_crypto::hash::Hash;
 use jstz_crypto::public_key_hash::PublicKeyHash;
@@ -213,15 +220,103 @@ pub struct UserAccount {
 pub struct SmartFunctionAccount {
     pub amount: Amount,
     pub nonce: Nonce,
-    pub function_code: ParsedCode,
+    /// Hash of the code of the smart function, which is stored under `/jstz_code`
+    #[bincode(with_serde)]
+    pub code_hash: CodeHash,
+}
+
+impl SmartFunctionAccount {
+    /// Decodes the code of a smart function account persisted before code was
+    /// stored by hash. Returns `None` if `bytes` are not such an account.
+    pub fn decode_unversioned_code(bytes: &[u8]) -> Option<ParsedCode> {
+        match <UnversionedAccount as BinEncodable>::decode(bytes) {
+            Ok(UnversionedAccount::SmartFunction(account)) => Some(account.function_code),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode, ToSchema)]
+/// Accounts are persisted as a [`VersionedValue`], so that accounts persisted by
+/// earlier kernels can still be decoded
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub enum Account {
     User(UserAccount),
     SmartFunction(SmartFunctionAccount),
 }
 
+#[derive(Debug, Clone, Encode, Decode)]
+enum AccountLayout {
+    User(UserAccount),
+    SmartFunction(SmartFunctionAccount),
+}
+
+impl Versioned for AccountLayout {
+    const VERSION: u8 = 1;
+
+    fn migrate<D: Decoder>(
+        version: u8,
+        decoder: &mut D,
+    ) -> std::result::Result<Self, DecodeError> {
+        match version {
+            // The code is moved to the code store when the account is loaded, see
+            // `Account::migrate_code`
+            UNVERSIONED => Ok(match <UnversionedAccount as Decode>::decode(decoder)? {
+                UnversionedAccount::User(account) => Self::User(account),
+                UnversionedAccount::SmartFunction(account) => {
+                    Self::SmartFunction(SmartFunctionAccount {
+                        amount: account.amount,
+                        nonce: account.nonce,
+                        code_hash: CodeStore::hash(&account.function_code),
+                    })
+                }
+            }),
+            _ => Err(DecodeError::OtherString(format!(
+                "unknown account version {version}"
+            ))),
+        }
+    }
+}
+
+/// Layout of the accounts persisted before smart function code was stored by hash
+#[derive(Debug, Clone, Encode, Decode)]
+enum UnversionedAccount {
+    User(UserAccount),
+    SmartFunction(UnversionedSmartFunctionAccount),
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+struct UnversionedSmartFunctionAccount {
+    amount: Amount,
+    nonce: Nonce,
+    function_code: ParsedCode,
+}
+
+impl Encode for Account {
+    fn encode<E: Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> std::result::Result<(), EncodeError> {
+        let layout = match self.clone() {
+            Self::User(account) => AccountLayout::User(account),
+            Self::SmartFunction(account) => AccountLayout::SmartFunction(account),
+        };
+        Encode::encode(&VersionedValue(layout), encoder)
+    }
+}
+
+impl Decode for Account {
+    fn decode<D: Decoder>(decoder: &mut D) -> std::result::Result<Self, DecodeError> {
+        Ok(
+            match <VersionedValue<AccountLayout> as Decode>::decode(decoder)?.0 {
+                AccountLayout::User(account) => Self::User(account),
+                AccountLayout::SmartFunction(account) => Self::SmartFunction(account),
+            },
+        )
+    }
+}
+
+bincode::impl_borrow_decode!(Account);
+
 impl Account {
     fn path(addr: &impl Addressable) -> Result<OwnedPath> {
         let account_path = OwnedPath::try_from(format!("/{}", addr.to_base58()))?;
@@ -285,10 +380,35 @@ impl Account {
         tx: &'a mut Transaction,
         addr: &impl Addressable,
     ) -> Result<GuardedMut<'a, Account>> {
-        let account_entry = tx.entry::<Self>(hrt, Self::path(addr)?)?;
+        let path = Self::path(addr)?;
+        if let AddressKind::SmartFunction = addr.kind() {
+            Self::migrate_code(hrt, tx, &path)?;
+        }
+        let account_entry = tx.entry::<Self>(hrt, path)?;
         Ok(account_entry.or_insert_with(|| Self::default_account(addr)))
     }
 
+    /// Moves the code of a smart function account persisted before code was stored
+    /// by hash to the code store. Loading the account in a transaction writes it
+    /// back with only the hash of its code, so this must happen before. Moving the
+    /// code again is harmless.
+    fn migrate_code(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        path: &OwnedPath,
+    ) -> Result<()> {
+        if !Storage::contains_key(hrt, path)? {
+            return Ok(());
+        }
+        let bytes = hrt
+            .store_read_all(path)
+            .map_err(jstz_core::error::Error::from)?;
+        if let Some(code) = SmartFunctionAccount::decode_unversioned_code(&bytes) {
+            CodeStore::pin(hrt, tx, code)?;
+        }
+        Ok(())
+    }
+
     pub fn exists(
         hrt: &impl HostRuntime,
         tx: &Transaction,
@@ -384,9 +504,10 @@ impl Account {
         let account = SmartFunctionAccount {
             amount,
             nonce: Nonce::default(),
-            function_code,
+            code_hash: CodeStore::hash(&function_code),
         };
         Self::SmartFunction(account).try_insert(hrt, tx, Self::path(&address)?)?;
+        CodeStore::insert(hrt, tx, function_code)?;
         tx.set_dirty(is_dirty);
         Ok(address)
     }
@@ -398,12 +519,28 @@ impl Account {
     ) -> Result<Guarded<'a, str>> {
         let is_dirty = tx.get_dirty();
         let account = Self::get_mut(hrt, tx, addr)?;
-        let result: Result<Guarded<'a, str>> = match account.deref() {
-            Self::SmartFunction(SmartFunctionAccount { function_code, .. }) => {
-                Ok(Guarded::new(account.clone_guard(), &function_code.0))
+        let guard = account.clone_guard();
+        let code_hash = match account.deref() {
+            Self::SmartFunction(SmartFunctionAccount { code_hash, .. }) => {
+                Some(code_hash.clone())
             }
-            Self::User(_) => Err(Error::AddressTypeMismatch),
+            Self::User(_) => None,
         };
+        drop(account);
+        let Some(code_hash) = code_hash else {
+            tx.set_dirty(is_dirty);
+            return Err(Error::AddressTypeMismatch);
+        };
+        let result = CodeStore::get(hrt, tx, &code_hash).and_then(|code| match code {
+            Some(code) => {
+                let guard = code.clone_guard();
+                Ok(Guarded::new(guard, code.deref().as_str()))
+            }
+            // Accounts created without code, such as the default account of an
+            // unknown smart function, have an empty code
+            None if code_hash == CodeHash::default() => Ok(Guarded::new(guard, "")),
+            None => Err(Error::MissingCode),
+        });
         tx.set_dirty(is_dirty);
         result
     }
@@ -416,14 +553,19 @@ impl Account {
         addr: &SmartFunctionHash,
         new_function_code: String,
     ) -> Result<()> {
+        if let Self::User(_) = Self::get_mut(hrt, tx, addr)?.deref() {
+            return Err(Error::AddressTypeMismatch);
+        }
+        let new_code_hash = CodeStore::insert(hrt, tx, new_function_code.try_into()?)?;
         let mut account = Self::get_mut(hrt, tx, addr)?;
-        match account.deref_mut() {
-            Self::SmartFunction(SmartFunctionAccount { function_code, .. }) => {
-                *function_code = new_function_code.try_into()?;
-                Ok(())
+        let old_code_hash = match account.deref_mut() {
+            Self::SmartFunction(SmartFunctionAccount { code_hash, .. }) => {
+                std::mem::replace(code_hash, new_code_hash)
             }
-            Self::User(_) => Err(Error::AddressTypeMismatch),
-        }
+            Self::User(_) => return Err(Error::AddressTypeMismatch),
+        };
+        drop(account);
+        CodeStore::release(hrt, tx, &old_code_hash)
     }
 
     pub fn balance(
@@ -735,7 +877,7 @@ mod test {
                 Account::SmartFunction(account) => {
                     assert_eq!(account.amount, 0);
                     assert_eq!(account.nonce.0, 0);
-                    assert_eq!(account.function_code.0, "");
+                    assert_eq!(account.code_hash, CodeHash::default());
                 }
                 _ => panic!("Expected SmartFunction account"),
             }
@@ -760,7 +902,7 @@ mod test {
                 Account::SmartFunction(account) => {
                     assert_eq!(account.amount, 0);
                     assert_eq!(account.nonce.0, 0);
-                    assert_eq!(account.function_code.0, "");
+                    assert_eq!(account.code_hash, CodeHash::default());
                 }
                 _ => panic!("Expected SmartFunction account"),
             }
@@ -884,10 +1026,77 @@ mod test {
             let updated_code = Account::function_code(&host, &mut tx, sf_hash).unwrap();
             assert_eq!(updated_code.deref(), valid_code);
 
+            // The replaced code is removed with its last reference
+            let replaced_hash =
+                CodeStore::hash(&ParsedCode("export default () => {}".to_string()));
+            assert!(CodeStore::get(&host, &tx, &replaced_hash)
+                .unwrap()
+                .is_none());
+
             let account = Account::get_mut(&host, &mut tx, &user_addr).unwrap();
             assert!(matches!(account.deref(), Account::User(_)));
         }
 
+        #[test]
+        fn test_function_code_missing() {
+            let (host, mut tx) = setup_test_env();
+            let (_, sf_addr) = create_test_addresses();
+            let Address::SmartFunction(sf_hash) = &sf_addr else {
+                panic!("Expected SmartFunction address")
+            };
+
+            Account::SmartFunction(SmartFunctionAccount {
+                amount: 0,
+                nonce: Nonce(0),
+                code_hash: CodeStore::hash(&ParsedCode("missing_code".to_string())),
+            })
+            .try_insert(&host, &mut tx, Account::path(&sf_addr).unwrap())
+            .unwrap();
+            assert!(matches!(
+                Account::function_code(&host, &mut tx, sf_hash),
+                Err(Error::MissingCode)
+            ));
+        }
+
+        #[test]
+        fn test_migrate_unversioned_smart_function() {
+            let (mut host, mut tx) = setup_test_env();
+            let (_, sf_addr) = create_test_addresses();
+            let Address::SmartFunction(sf_hash) = &sf_addr else {
+                panic!("Expected SmartFunction address")
+            };
+            let code = ParsedCode("export default () => {}".to_string());
+            let path = Account::path(&sf_addr).unwrap();
+            let unversioned =
+                UnversionedAccount::SmartFunction(UnversionedSmartFunctionAccount {
+                    amount: 10,
+                    nonce: Nonce(2),
+                    function_code: code.clone(),
+                });
+            Storage::insert(&mut host, &path, &unversioned).unwrap();
+
+            match Storage::get::<Account>(&host, &path).unwrap() {
+                Some(Account::SmartFunction(account)) => {
+                    assert_eq!(account.amount, 10);
+                    assert_eq!(account.nonce, Nonce(2));
+                    assert_eq!(account.code_hash, CodeStore::hash(&code));
+                }
+                _ => panic!("Expected SmartFunction account"),
+            }
+
+            let function_code = Account::function_code(&host, &mut tx, sf_hash).unwrap();
+            assert_eq!(function_code.deref(), code.0);
+            drop(function_code);
+            tx.commit(&mut host).unwrap();
+
+            // The account is written back with only the hash of its code
+            let bytes = host.store_read_all(&path).unwrap();
+            assert!(SmartFunctionAccount::decode_unversioned_code(&bytes).is_none());
+            tx.begin();
+            let function_code = Account::function_code(&host, &mut tx, sf_hash).unwrap();
+            assert_eq!(function_code.deref(), code.0);
+        }
+
         #[test]
         fn test_try_insert() {
             let (host, mut tx) = setup_test_env();
@@ -915,7 +1124,7 @@ mod test {
             let sf_account = Account::SmartFunction(SmartFunctionAccount {
                 amount: 200,
                 nonce: Nonce(0),
-                function_code: ParsedCode("test_code".to_string()),
+                code_hash: CodeStore::hash(&ParsedCode("test_code".to_string())),
             });
             assert!(sf_account
                 .try_insert(&host, &mut tx, Account::path(&sf_addr).unwrap())
@@ -926,7 +1135,10 @@ mod test {
                 Account::SmartFunction(account) => {
                     assert_eq!(account.amount, 200);
                     assert_eq!(account.nonce.0, 0);
-                    assert_eq!(account.function_code.0, "test_code");
+                    assert_eq!(
+                        account.code_hash,
+                        CodeStore::hash(&ParsedCode("test_code".to_string()))
+                    );
                 }
                 _ => panic!("Expected SmartFunction account"),
             }
@@ -948,7 +1160,7 @@ mod test {
             let duplicate_sf = Account::SmartFunction(SmartFunctionAccount {
                 amount: 400,
                 nonce: Nonce(1),
-                function_code: ParsedCode("another_code".to_string()),
+                code_hash: CodeStore::hash(&ParsedCode("another_code".to_string())),
             });
             assert!(matches!(
                 duplicate_sf.try_insert(&host, &mut tx, Account::path(&sf_addr).unwrap()),
@@ -991,16 +1203,31 @@ mod test {
             )
             .unwrap();
 
-            let sf_addr = Address::SmartFunction(sf_hash);
+            let sf_addr = Address::SmartFunction(sf_hash.clone());
             let account = Account::get_mut(&host, &mut tx, &sf_addr).unwrap();
             match account.deref() {
                 Account::SmartFunction(sf_account) => {
                     assert_eq!(sf_account.amount, amount);
                     assert_eq!(sf_account.nonce.0, 0);
-                    assert_eq!(sf_account.function_code.0, code.0);
+                    assert_eq!(sf_account.code_hash, CodeStore::hash(&code));
                 }
                 _ => panic!("Expected SmartFunction account"),
             }
+            drop(account);
+            let function_code = Account::function_code(&host, &mut tx, &sf_hash).unwrap();
+            assert_eq!(function_code.deref(), code.0);
+
+            // Smart functions deployed with the same code share it
+            Account::nonce(&host, &mut tx, &creator)
+                .unwrap()
+                .increment();
+            let other_hash =
+                Account::create_smart_function(&host, &mut tx, &creator, 0, code.clone())
+                    .unwrap();
+            assert_ne!(other_hash, sf_hash);
+            let function_code =
+                Account::function_code(&host, &mut tx, &other_hash).unwrap();
+            assert_eq!(function_code.deref(), code.0);
         }
     }
 }
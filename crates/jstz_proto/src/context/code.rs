@@ -0,0 +1,177 @@
+//! Content-addressed storage of smart function code.
+//!
+//! The code of smart functions is stored under the hash of its source, and smart
+//! function accounts only keep that hash. Smart functions deployed with the same
+//! bundle, which is common for those created from a template, share a single copy
+//! of their code in durable storage. The references to each code are counted, and
+//! the code is removed with its last reference.
+
+use jstz_core::{
+    host::HostRuntime,
+    kv::{transaction::Guarded, Transaction},
+};
+use jstz_crypto::hash::Blake2b;
+use tezos_smart_rollup::storage::path::{self, OwnedPath, RefPath};
+
+use crate::{error::Result, runtime::ParsedCode};
+
+pub const CODE_PATH_PREFIX: &str = "/jstz_code";
+const CODE_PATH: RefPath = RefPath::assert_from(CODE_PATH_PREFIX.as_bytes());
+pub const CODE_REFS_PATH_PREFIX: &str = "/jstz_code_refs";
+const CODE_REFS_PATH: RefPath = RefPath::assert_from(CODE_REFS_PATH_PREFIX.as_bytes());
+
+/// Reference count of code that is never removed. The references of code moved
+/// from accounts persisted before code was stored by hash are not known.
+const PINNED: u64 = u64::MAX;
+
+/// Hash of the source of a smart function, under which its code is stored
+pub type CodeHash = Blake2b;
+
+pub struct CodeStore;
+
+impl CodeStore {
+    fn path(hash: &CodeHash) -> Result<OwnedPath> {
+        let code_path = OwnedPath::try_from(format!("/{hash}"))?;
+        Ok(path::concat(&CODE_PATH, &code_path)?)
+    }
+
+    fn refs_path(hash: &CodeHash) -> Result<OwnedPath> {
+        let refs_path = OwnedPath::try_from(format!("/{hash}"))?;
+        Ok(path::concat(&CODE_REFS_PATH, &refs_path)?)
+    }
+
+    pub fn hash(code: &ParsedCode) -> CodeHash {
+        Blake2b::from(code.as_bytes())
+    }
+
+    /// Stores `code` under its hash, unless the same code is already stored, adds a
+    /// reference to it and returns the hash
+    pub fn insert(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        code: ParsedCode,
+    ) -> Result<CodeHash> {
+        let hash = Self::store(hrt, tx, code)?;
+        let refs = Self::refs(hrt, tx, &hash)?;
+        tx.insert(Self::refs_path(&hash)?, refs.saturating_add(1))?;
+        Ok(hash)
+    }
+
+    /// Stores `code` under its hash, unless the same code is already stored, and
+    /// never removes it
+    pub fn pin(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        code: ParsedCode,
+    ) -> Result<CodeHash> {
+        let hash = Self::store(hrt, tx, code)?;
+        tx.insert(Self::refs_path(&hash)?, PINNED)?;
+        Ok(hash)
+    }
+
+    /// Removes a reference to the code stored under `hash`, and the code with its
+    /// last reference
+    pub fn release(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        hash: &CodeHash,
+    ) -> Result<()> {
+        match Self::refs(hrt, tx, hash)? {
+            PINNED => {}
+            0 | 1 => {
+                tx.remove(Self::refs_path(hash)?)?;
+                tx.remove(Self::path(hash)?)?;
+            }
+            refs => tx.insert(Self::refs_path(hash)?, refs - 1)?,
+        }
+        Ok(())
+    }
+
+    fn store(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        code: ParsedCode,
+    ) -> Result<CodeHash> {
+        let hash = Self::hash(&code);
+        let path = Self::path(&hash)?;
+        if !tx.contains_key(hrt, &path)? {
+            tx.insert(path, code)?;
+        }
+        Ok(hash)
+    }
+
+    fn refs(hrt: &impl HostRuntime, tx: &Transaction, hash: &CodeHash) -> Result<u64> {
+        let refs = tx.get::<u64>(hrt, Self::refs_path(hash)?)?;
+        Ok(refs.map(|refs| *refs).unwrap_or_default())
+    }
+
+    pub fn get<'a>(
+        hrt: &impl HostRuntime,
+        tx: &'a Transaction,
+        hash: &CodeHash,
+    ) -> Result<Option<Guarded<'a, ParsedCode>>> {
+        let is_dirty = tx.get_dirty();
+        let result = tx.get::<ParsedCode>(hrt, Self::path(hash)?);
+        tx.set_dirty(is_dirty);
+        Ok(result?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use jstz_core::kv::Transaction;
+    use jstz_mock::host::JstzMockHost;
+
+    use super::*;
+
+    #[test]
+    fn stores_code_once() {
+        let mut host = JstzMockHost::default();
+        let hrt = host.rt();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let code = ParsedCode("export default () => new Response()".to_string());
+        let hash = CodeStore::insert(hrt, &mut tx, code.clone()).unwrap();
+        assert_eq!(hash, CodeStore::hash(&code));
+        assert_eq!(
+            CodeStore::path(&hash).unwrap().to_string(),
+            format!("/jstz_code/{hash}")
+        );
+        assert_eq!(CodeStore::insert(hrt, &mut tx, code.clone()).unwrap(), hash);
+        tx.commit(hrt).unwrap();
+
+        tx.begin();
+        assert_eq!(
+            CodeStore::get(hrt, &tx, &hash).unwrap().as_deref(),
+            Some(&code)
+        );
+        assert!(CodeStore::get(hrt, &tx, &CodeHash::default())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn removes_code_with_its_last_reference() {
+        let mut host = JstzMockHost::default();
+        let hrt = host.rt();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let code = ParsedCode("export default () => new Response()".to_string());
+        let hash = CodeStore::insert(hrt, &mut tx, code.clone()).unwrap();
+        CodeStore::insert(hrt, &mut tx, code.clone()).unwrap();
+        assert_eq!(CodeStore::refs(hrt, &tx, &hash).unwrap(), 2);
+
+        CodeStore::release(hrt, &mut tx, &hash).unwrap();
+        assert!(CodeStore::get(hrt, &tx, &hash).unwrap().is_some());
+        CodeStore::release(hrt, &mut tx, &hash).unwrap();
+        assert!(CodeStore::get(hrt, &tx, &hash).unwrap().is_none());
+        assert_eq!(CodeStore::refs(hrt, &tx, &hash).unwrap(), 0);
+
+        // Pinned code is never removed
+        CodeStore::pin(hrt, &mut tx, code.clone()).unwrap();
+        CodeStore::insert(hrt, &mut tx, code).unwrap();
+        CodeStore::release(hrt, &mut tx, &hash).unwrap();
+        CodeStore::release(hrt, &mut tx, &hash).unwrap();
+        assert!(CodeStore::get(hrt, &tx, &hash).unwrap().is_some());
+    }
+}
@@ -0,0 +1,351 @@
+//! Interface descriptions of smart functions.
+//!
+//! A smart function can be deployed with a [`FunctionInterface`] listing the routes
+//! it serves, with the JSON schemas of their request and response bodies. It is
+//! stored next to the account of the smart function, so that clients can fetch it
+//! and generate typed bindings, much like the ABI of a contract.
+
+use std::{collections::BTreeSet, ops::Deref};
+
+use bincode::{
+    de::Decoder,
+    enc::Encoder,
+    error::{DecodeError, EncodeError},
+    Decode, Encode,
+};
+use derive_more::{Display, Error};
+use jstz_core::{host::HostRuntime, kv::Transaction};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use tezos_smart_rollup::storage::path::{self, OwnedPath, RefPath};
+use utoipa::ToSchema;
+
+use super::account::Addressable;
+use crate::error::Result;
+
+pub const INTERFACES_PATH_PREFIX: &str = "/jstz_interface";
+const INTERFACES_PATH: RefPath = RefPath::assert_from(INTERFACES_PATH_PREFIX.as_bytes());
+
+/// Maximum size, in bytes, of the JSON encoding of an interface
+pub const MAX_INTERFACE_SIZE: usize = 32 * 1024;
+
+const METHODS: [&str; 7] = ["GET", "HEAD", "POST", "PUT", "DELETE", "PATCH", "OPTIONS"];
+
+#[derive(Display, Debug, Error, PartialEq, Eq)]
+pub enum InterfaceError {
+    #[display(fmt = "interface is larger than {MAX_INTERFACE_SIZE} bytes")]
+    TooLarge,
+    #[display(fmt = "invalid method '{method}'")]
+    InvalidMethod { method: String },
+    #[display(fmt = "invalid path '{path}'")]
+    InvalidPath { path: String },
+    #[display(fmt = "invalid route name '{name}'")]
+    InvalidName { name: String },
+    #[display(fmt = "schema of {method} {path} is not a JSON schema")]
+    InvalidSchema { method: String, path: String },
+    #[display(fmt = "route {method} {path} is declared more than once")]
+    DuplicateRoute { method: String, path: String },
+}
+
+/// A JSON schema describing the body of a request or a response
+///
+/// Encoded as a JSON string in binary formats, which cannot encode arbitrary JSON
+/// values.
+#[derive(Debug, Clone, PartialEq, Eq, ToSchema)]
+#[schema(value_type = Value)]
+pub struct JsonSchema(pub serde_json::Value);
+
+impl Serialize for JsonSchema {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            self.0.serialize(serializer)
+        } else {
+            serializer.serialize_str(&self.0.to_string())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonSchema {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            serde_json::Value::deserialize(deserializer).map(Self)
+        } else {
+            let json = String::deserialize(deserializer)?;
+            serde_json::from_str(&json)
+                .map(Self)
+                .map_err(de::Error::custom)
+        }
+    }
+}
+
+impl Encode for JsonSchema {
+    fn encode<E: Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> std::result::Result<(), EncodeError> {
+        Encode::encode(&self.0.to_string(), encoder)
+    }
+}
+
+impl Decode for JsonSchema {
+    fn decode<D: Decoder>(decoder: &mut D) -> std::result::Result<Self, DecodeError> {
+        let json: String = Decode::decode(decoder)?;
+        let value = serde_json::from_str(&json).map_err(|e| {
+            DecodeError::OtherString(format!("error deserializing json schema: {e}"))
+        })?;
+        Ok(Self(value))
+    }
+}
+
+/// A route served by a smart function
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode, ToSchema,
+)]
+pub struct RouteInterface {
+    /// Name of the route in generated clients, derived from the method and the path
+    /// if not set. Must be a valid identifier.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// HTTP method of the route
+    pub method: String,
+    /// Path of the route. Segments starting with `:` are parameters, e.g.
+    /// `/tokens/:id`.
+    pub path: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// JSON schema of the request body, if the route takes one
+    #[serde(default)]
+    pub request: Option<JsonSchema>,
+    /// JSON schema of the response body, if the route returns one
+    #[serde(default)]
+    pub response: Option<JsonSchema>,
+}
+
+/// Interface description of a smart function
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode, ToSchema,
+)]
+pub struct FunctionInterface {
+    #[serde(default)]
+    pub description: Option<String>,
+    pub routes: Vec<RouteInterface>,
+}
+
+impl RouteInterface {
+    /// Names of the path parameters of the route, in order
+    pub fn path_params(&self) -> impl Iterator<Item = &str> {
+        self.path
+            .split('/')
+            .filter_map(|segment| segment.strip_prefix(':'))
+    }
+
+    fn validate(&self) -> std::result::Result<(), InterfaceError> {
+        if !METHODS.contains(&self.method.as_str()) {
+            return Err(InterfaceError::InvalidMethod {
+                method: self.method.clone(),
+            });
+        }
+        let valid_path = self.path.starts_with('/')
+            && !self.path.contains(['?', '#'])
+            && self.path_params().all(is_identifier);
+        if !valid_path {
+            return Err(InterfaceError::InvalidPath {
+                path: self.path.clone(),
+            });
+        }
+        if let Some(name) = self.name.as_ref().filter(|name| !is_identifier(name)) {
+            return Err(InterfaceError::InvalidName { name: name.clone() });
+        }
+        let is_schema =
+            |schema: &JsonSchema| schema.0.is_object() || schema.0.is_boolean();
+        if !self.request.iter().chain(&self.response).all(is_schema) {
+            return Err(InterfaceError::InvalidSchema {
+                method: self.method.clone(),
+                path: self.path.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl FunctionInterface {
+    fn path(addr: &impl Addressable) -> Result<OwnedPath> {
+        let interface_path = OwnedPath::try_from(format!("/{}", addr.to_base58()))?;
+        Ok(path::concat(&INTERFACES_PATH, &interface_path)?)
+    }
+
+    /// Checks that the routes of the interface are well-formed and unique, and that
+    /// the interface is at most [`MAX_INTERFACE_SIZE`] bytes
+    pub fn validate(&self) -> std::result::Result<(), InterfaceError> {
+        let size = serde_json::to_vec(self).map_or(usize::MAX, |json| json.len());
+        if size > MAX_INTERFACE_SIZE {
+            return Err(InterfaceError::TooLarge);
+        }
+        let mut routes = BTreeSet::new();
+        for route in &self.routes {
+            route.validate()?;
+            if !routes.insert((route.method.as_str(), route.path.as_str())) {
+                return Err(InterfaceError::DuplicateRoute {
+                    method: route.method.clone(),
+                    path: route.path.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Encoding of the interface in the hash of the operation deploying it. Unlike
+    /// serialization, it cannot fail, so every interface is signed.
+    pub(crate) fn preimage(&self) -> String {
+        let schema = |schema: &Option<JsonSchema>| {
+            schema.as_ref().map(|schema| schema.0.to_string())
+        };
+        let mut preimage = format!("{:?}", self.description);
+        for route in &self.routes {
+            preimage.push_str(&format!(
+                "{:?}{:?}{:?}{:?}{:?}{:?}",
+                route.name,
+                route.method,
+                route.path,
+                route.description,
+                schema(&route.request),
+                schema(&route.response)
+            ));
+        }
+        preimage
+    }
+
+    pub fn get(
+        hrt: &impl HostRuntime,
+        tx: &Transaction,
+        addr: &impl Addressable,
+    ) -> Result<Option<Self>> {
+        let is_dirty = tx.get_dirty();
+        let result = tx
+            .get::<Self>(hrt, Self::path(addr)?)
+            .map(|interface| interface.map(|interface| interface.deref().clone()));
+        tx.set_dirty(is_dirty);
+        Ok(result?)
+    }
+
+    pub fn insert(self, tx: &mut Transaction, addr: &impl Addressable) -> Result<()> {
+        Ok(tx.insert(Self::path(addr)?, self)?)
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod test {
+    use jstz_core::{kv::Transaction, BinEncodable};
+    use jstz_mock::host::JstzMockHost;
+    use serde_json::json;
+
+    use super::*;
+    use crate::context::account::Address;
+
+    fn interface() -> FunctionInterface {
+        serde_json::from_value(json!({
+            "description": "NFT collection",
+            "routes": [
+                {
+                    "method": "GET",
+                    "path": "/tokens/:id",
+                    "response": {
+                        "type": "object",
+                        "properties": { "owner": { "type": "string" } },
+                        "required": ["owner"]
+                    }
+                },
+                {
+                    "name": "mint",
+                    "method": "POST",
+                    "path": "/tokens",
+                    "request": { "type": "object" }
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn encoding_round_trip() {
+        let interface = interface();
+        assert_eq!(
+            interface.routes[0].path_params().collect::<Vec<_>>(),
+            ["id"]
+        );
+        let decoded = FunctionInterface::decode(&interface.encode().unwrap()).unwrap();
+        assert_eq!(decoded, interface);
+    }
+
+    #[test]
+    fn validate() {
+        assert_eq!(interface().validate(), Ok(()));
+
+        let invalid = |f: fn(&mut RouteInterface)| {
+            let mut interface = interface();
+            f(&mut interface.routes[0]);
+            interface.validate().unwrap_err()
+        };
+        assert!(matches!(
+            invalid(|r| r.method = "get".to_string()),
+            InterfaceError::InvalidMethod { .. }
+        ));
+        assert!(matches!(
+            invalid(|r| r.path = "tokens".to_string()),
+            InterfaceError::InvalidPath { .. }
+        ));
+        assert!(matches!(
+            invalid(|r| r.path = "/tokens/:".to_string()),
+            InterfaceError::InvalidPath { .. }
+        ));
+        assert!(matches!(
+            invalid(|r| r.name = Some("get-token".to_string())),
+            InterfaceError::InvalidName { .. }
+        ));
+        assert!(matches!(
+            invalid(|r| r.request = Some(JsonSchema(json!("string")))),
+            InterfaceError::InvalidSchema { .. }
+        ));
+        assert!(matches!(
+            invalid(|r| {
+                r.method = "POST".to_string();
+                r.path = "/tokens".to_string();
+            }),
+            InterfaceError::DuplicateRoute { .. }
+        ));
+        assert!(matches!(
+            invalid(|r| r.description = Some("a".repeat(MAX_INTERFACE_SIZE))),
+            InterfaceError::TooLarge
+        ));
+    }
+
+    #[test]
+    fn storage() {
+        let mut host = JstzMockHost::default();
+        let hrt = host.rt();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let address = Address::SmartFunction(jstz_mock::sf_account1());
+        assert_eq!(FunctionInterface::get(hrt, &tx, &address).unwrap(), None);
+
+        interface().insert(&mut tx, &address).unwrap();
+        tx.commit(hrt).unwrap();
+        tx.begin();
+        assert_eq!(
+            FunctionInterface::get(hrt, &tx, &address).unwrap(),
+            Some(interface())
+        );
+    }
+}
@@ -1,3 +1,5 @@
 pub mod account;
+pub mod code;
+pub mod interface;
 pub mod receipt;
 pub mod ticket_table;
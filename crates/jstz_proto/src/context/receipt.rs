@@ -2,6 +2,7 @@ use jstz_core::{host::HostRuntime, kv::Transaction};
 use tezos_smart_rollup::storage::path::{self, OwnedPath, RefPath};
 
 use crate::{
+    executor::receipt_retention,
     receipt::{Receipt, ReceiptResult},
     Result,
 };
@@ -10,8 +11,7 @@ const RECEIPTS_PATH: RefPath = RefPath::assert_from(b"/jstz_receipt");
 
 impl Receipt {
     pub fn write(self, hrt: &impl HostRuntime, tx: &mut Transaction) -> Result<()> {
-        let receipt_path = OwnedPath::try_from(format!("/{}", self.hash()))?;
-        let path = path::concat(&RECEIPTS_PATH, &receipt_path)?;
+        let path = self.path()?;
         let skip = match &self.result {
             ReceiptResult::Failed(err) if err == "NoncePassed" => {
                 tx.contains_key(hrt, &path)?
@@ -20,10 +20,33 @@ impl Receipt {
         };
 
         if !skip {
+            let hash = self.hash().clone();
             tx.insert(path, self)?;
+            receipt_retention::index(hrt, tx, &hash)?;
         }
         Ok(())
     }
+
+    /// Writes the receipt unless the operation already has one, so that copies of
+    /// an operation with an invalid signature cannot overwrite its receipt
+    pub fn write_if_absent(
+        self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+    ) -> Result<()> {
+        let path = self.path()?;
+        if !tx.contains_key(hrt, &path)? {
+            let hash = self.hash().clone();
+            tx.insert(path, self)?;
+            receipt_retention::index(hrt, tx, &hash)?;
+        }
+        Ok(())
+    }
+
+    fn path(&self) -> Result<OwnedPath> {
+        let receipt_path = OwnedPath::try_from(format!("/{}", self.hash()))?;
+        Ok(path::concat(&RECEIPTS_PATH, &receipt_path)?)
+    }
 }
 
 #[cfg(test)]
@@ -3,7 +3,7 @@ use derive_more::{Display, Error, From};
 use tezos_smart_rollup::michelson::ticket::TicketHashError;
 
 use crate::{
-    context::ticket_table,
+    context::{interface, ticket_table},
     executor::{fa_deposit, fa_withdraw},
 };
 
@@ -24,6 +24,7 @@ pub enum Error {
     InvalidScheme,
     RefererShouldNotBeSet,
     GasLimitExceeded,
+    ExecutionBudgetExceeded,
     UnsupportedPath,
     InvalidHost,
     InvalidHttpRequest,
@@ -35,6 +36,9 @@ pub enum Error {
     TicketTableError {
         source: ticket_table::TicketTableError,
     },
+    InterfaceError {
+        source: interface::InterfaceError,
+    },
     FaDepositError {
         source: fa_deposit::FaDepositError,
     },
@@ -46,6 +50,7 @@ pub enum Error {
     ZeroAmountNotAllowed,
     AddressTypeMismatch,
     AccountExists,
+    MissingCode,
     RevealTypeMismatch,
     RevealNotSupported,
     InvalidInjector,
@@ -80,6 +85,9 @@ impl From<Error> for JsError {
             Error::GasLimitExceeded => JsNativeError::eval()
                 .with_message("GasLimitExceeded")
                 .into(),
+            Error::ExecutionBudgetExceeded => JsNativeError::eval()
+                .with_message("ExecutionBudgetExceeded")
+                .into(),
             Error::InvalidHttpRequest => JsNativeError::eval()
                 .with_message("InvalidHttpRequest")
                 .into(),
@@ -98,6 +106,9 @@ impl From<Error> for JsError {
             Error::TicketTableError { source } => JsNativeError::eval()
                 .with_message(format!("TicketTableError: {source}"))
                 .into(),
+            Error::InterfaceError { source } => JsNativeError::eval()
+                .with_message(format!("InterfaceError: {source}"))
+                .into(),
             Error::FaDepositError { source } => JsNativeError::eval()
                 .with_message(format!("FaDepositError: {source}"))
                 .into(),
@@ -126,6 +137,9 @@ impl From<Error> for JsError {
             Error::AccountExists => {
                 JsNativeError::eval().with_message("AccountExists").into()
             }
+            Error::MissingCode => {
+                JsNativeError::eval().with_message("MissingCode").into()
+            }
             Error::RevealTypeMismatch => JsNativeError::eval()
                 .with_message("RevealTypeMismatch")
                 .into(),
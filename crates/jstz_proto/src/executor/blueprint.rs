@@ -0,0 +1,32 @@
+use futures::future::FutureExt;
+use jstz_core::{host::HostRuntime, kv::Transaction};
+use jstz_crypto::public_key::PublicKey;
+use tezos_crypto_rs::hash::ContractKt1Hash;
+
+use crate::{operation::Blueprint, receipt::BlueprintReceipt, Error, Result};
+
+/// Executes the operations of a blueprint published by the sequencer, in order,
+/// and writes their receipts. Only the injector may publish blueprints. The
+/// operations are verified like the ones read from the inbox, so an operation that
+/// was already executed fails without overwriting its receipt.
+pub async fn execute(
+    hrt: &mut impl HostRuntime,
+    tx: &mut Transaction,
+    public_key: &PublicKey,
+    ticketer: &ContractKt1Hash,
+    injector: &PublicKey,
+    Blueprint { operations }: Blueprint,
+) -> Result<BlueprintReceipt> {
+    if public_key != injector {
+        return Err(Error::InvalidInjector);
+    }
+    let mut hashes = Vec::with_capacity(operations.len());
+    for operation in operations {
+        let receipt = super::execute_operation(hrt, tx, operation, ticketer, injector)
+            .boxed_local()
+            .await;
+        hashes.push(receipt.hash().clone());
+        receipt.write(hrt, tx)?;
+    }
+    Ok(BlueprintReceipt { operations: hashes })
+}
@@ -4,12 +4,12 @@ use http::{header::CONTENT_TYPE, HeaderMap, Method, Uri};
 use jstz_core::{host::HostRuntime, kv::Transaction};
 use jstz_crypto::{hash::Hash, public_key_hash::PublicKeyHash};
 use serde::{Deserialize, Serialize};
-use tezos_smart_rollup::{michelson::ticket::TicketHash, prelude::debug_msg};
+use tezos_smart_rollup::michelson::ticket::TicketHash;
 use utoipa::ToSchema;
 
 use crate::{
     context::{account::Address, account::Amount, ticket_table::TicketTable},
-    executor::smart_function,
+    executor::{log_level::log_debug, smart_function},
     operation::{internal::FaDeposit, RunFunction},
     receipt::Receipt,
     HttpBody, Result,
@@ -116,7 +116,7 @@ async fn deposit_to_proxy_contract(
             }
         }
         Err(error) => {
-            debug_msg!(
+            log_debug!(
                 rt,
                 "Failed to execute proxy function when performing fa deposit: {error:?}\n"
             );
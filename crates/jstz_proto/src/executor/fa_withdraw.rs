@@ -2,7 +2,10 @@ use bincode::{Decode, Encode};
 use derive_more::{Display, Error, From};
 use jstz_core::{
     host::HostRuntime,
-    kv::{outbox::OutboxMessage, Transaction},
+    kv::{
+        outbox::{new_withdrawal_transaction, OutboxTransaction},
+        Transaction,
+    },
 };
 use jstz_crypto::smart_function_hash::Kt1Hash;
 use serde::{Deserialize, Serialize};
@@ -102,24 +105,24 @@ pub enum FaWithdrawError {
     ProxySmartFunctionCannotBeSource,
 }
 
-fn create_fa_withdrawal_message(
+fn create_fa_withdrawal_transaction(
     routing_info: &RoutingInfo,
     ticket: FA2_1Ticket,
-) -> Result<OutboxMessage> {
+) -> Result<OutboxTransaction> {
     let receiver_pkh = routing_info.receiver.to_base58();
     let destination = Contract::Originated(routing_info.proxy_l1_contract.clone().into());
-    let message = OutboxMessage::new_withdrawal_message(
+    let transaction = new_withdrawal_transaction(
         &Contract::try_from(receiver_pkh).unwrap(),
         &destination,
         ticket,
         WITHDRAW_ENTRYPOINT,
     )?;
-    Ok(message)
+    Ok(transaction)
 }
 
 // Deducts `amount` from the ticket balance of `ticket_owner` for `ticket.hash`
-// and pushes a withdraw outbox message to the outbox queue, returning the outbox
-// message id.
+// and pushes a withdraw outbox transaction to the outbox queue, returning the
+// outbox message id.
 fn withdraw_from_ticket_owner(
     rt: &mut impl HostRuntime,
     tx: &mut Transaction,
@@ -129,8 +132,8 @@ fn withdraw_from_ticket_owner(
     ticket: Ticket,
 ) -> Result<()> {
     TicketTable::sub(rt, tx, ticket_owner, &ticket.hash, amount)?;
-    let message = create_fa_withdrawal_message(routing_info, ticket.value)?;
-    tx.queue_outbox_message(rt, message)?;
+    let transaction = create_fa_withdrawal_transaction(routing_info, ticket.value)?;
+    tx.queue_outbox_transaction(rt, transaction)?;
     // TODO: https://linear.app/tezos/issue/JSTZ-113/implement-outbox-message-id
     // Implement outbox message id
     Ok(())
@@ -191,6 +194,7 @@ impl FaWithdraw {
 
 #[cfg(test)]
 mod test {
+    use jstz_core::kv::outbox::OutboxMessage;
     use tezos_data_encoding::nom::NomReader;
     use tezos_smart_rollup::{
         michelson::MichelsonContract,
@@ -248,6 +252,7 @@ mod test {
             .execute(&mut rt, &mut tx, &source, 100)
             .expect("Should succeed");
         tx.commit(&mut rt).unwrap();
+        tx.flush_outbox(&mut rt).unwrap();
         assert_eq!(
             FaWithdrawReceipt {
                 source,
@@ -0,0 +1,100 @@
+use jstz_core::{
+    host::HostRuntime,
+    kv::{Storage, Transaction},
+};
+use jstz_crypto::public_key::PublicKey;
+use tezos_smart_rollup::storage::path::OwnedPath;
+
+use crate::{
+    operation::UpgradeKernel, receipt::UpgradeKernelReceipt,
+    storage::KERNEL_UPGRADE_PATH, Error, Result,
+};
+
+/// Schedules a kernel upgrade. Only the injector may upgrade the kernel. A
+/// later upgrade replaces the scheduled one, so an upgrade can be cancelled by
+/// rescheduling the current kernel.
+pub fn execute(
+    tx: &mut Transaction,
+    public_key: &PublicKey,
+    injector: &PublicKey,
+    upgrade: UpgradeKernel,
+) -> Result<UpgradeKernelReceipt> {
+    if public_key != injector {
+        return Err(Error::InvalidInjector);
+    }
+    let receipt = UpgradeKernelReceipt {
+        root_hash: upgrade.root_hash.clone(),
+        activation_level: upgrade.activation_level,
+    };
+    tx.insert(OwnedPath::from(&KERNEL_UPGRADE_PATH), upgrade)?;
+    Ok(receipt)
+}
+
+/// Returns the scheduled kernel upgrade, if any
+pub fn scheduled(rt: &impl HostRuntime) -> Result<Option<UpgradeKernel>> {
+    Ok(Storage::get(rt, &KERNEL_UPGRADE_PATH)?)
+}
+
+#[cfg(test)]
+mod test {
+    use jstz_core::{kv::Transaction, reveal_data::PreimageHash};
+    use jstz_utils::{
+        test_util::{alice_keys, bob_keys},
+        KeyPair,
+    };
+    use tezos_smart_rollup_mock::MockHost;
+
+    use super::{execute, scheduled};
+    use crate::{operation::UpgradeKernel, Error};
+
+    fn upgrade(activation_level: u32) -> UpgradeKernel {
+        UpgradeKernel {
+            root_hash: PreimageHash([1; 33]),
+            activation_level,
+        }
+    }
+
+    #[test]
+    fn execute_schedules_upgrade() {
+        let mut host = MockHost::default();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let KeyPair(injector, _) = alice_keys();
+
+        let receipt = execute(&mut tx, &injector, &injector, upgrade(10)).unwrap();
+        tx.commit(&mut host).unwrap();
+
+        assert_eq!(receipt.root_hash, PreimageHash([1; 33]));
+        assert_eq!(receipt.activation_level, 10);
+        assert_eq!(scheduled(&host).unwrap(), Some(upgrade(10)));
+    }
+
+    #[test]
+    fn execute_replaces_scheduled_upgrade() {
+        let mut host = MockHost::default();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let KeyPair(injector, _) = alice_keys();
+
+        execute(&mut tx, &injector, &injector, upgrade(10)).unwrap();
+        execute(&mut tx, &injector, &injector, upgrade(20)).unwrap();
+        tx.commit(&mut host).unwrap();
+
+        assert_eq!(scheduled(&host).unwrap(), Some(upgrade(20)));
+    }
+
+    #[test]
+    fn execute_fails_if_not_signed_by_injector() {
+        let mut host = MockHost::default();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let KeyPair(injector, _) = alice_keys();
+        let KeyPair(other, _) = bob_keys();
+
+        let result = execute(&mut tx, &other, &injector, upgrade(10));
+        tx.commit(&mut host).unwrap();
+
+        assert!(matches!(result, Err(Error::InvalidInjector)));
+        assert_eq!(scheduled(&host).unwrap(), None);
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use jstz_core::{
+    host::HostRuntime,
+    kv::{Storage, Transaction},
+};
+use jstz_crypto::public_key::PublicKey;
+use tezos_smart_rollup::storage::path::OwnedPath;
+
+use crate::{
+    operation::{KernelLogLevel, SetLogLevel},
+    receipt::SetLogLevelReceipt,
+    storage::LOG_LEVEL_PATH,
+    Error, Result,
+};
+
+/// Sets the verbosity of the kernel debug log. Only the injector may set it.
+pub fn execute(
+    tx: &mut Transaction,
+    public_key: &PublicKey,
+    injector: &PublicKey,
+    SetLogLevel { level }: SetLogLevel,
+) -> Result<SetLogLevelReceipt> {
+    if public_key != injector {
+        return Err(Error::InvalidInjector);
+    }
+    tx.insert(OwnedPath::from(&LOG_LEVEL_PATH), level)?;
+    Ok(SetLogLevelReceipt { level })
+}
+
+/// Returns the verbosity of the kernel debug log, [`KernelLogLevel::Debug`] unless set
+pub fn current(rt: &impl HostRuntime) -> Result<KernelLogLevel> {
+    Ok(Storage::get(rt, &LOG_LEVEL_PATH)?.unwrap_or_default())
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(KernelLogLevel::Debug as u8);
+
+/// Returns whether messages of `level` are written to the debug log
+pub fn enabled(level: KernelLogLevel) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// Sets the verbosity of the debug log of the running kernel, see [`current`]
+pub fn set_level(level: KernelLogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Writes a message to the debug log of `$rt` if `$level` is enabled
+macro_rules! log {
+    ($level:expr, $rt:expr, $($arg:tt)*) => {
+        if $crate::executor::log_level::enabled($level) {
+            tezos_smart_rollup::prelude::debug_msg!($rt, $($arg)*);
+        }
+    };
+}
+
+macro_rules! log_debug {
+    ($rt:expr, $($arg:tt)*) => {
+        $crate::executor::log_level::log!(
+            $crate::operation::KernelLogLevel::Debug, $rt, $($arg)*
+        )
+    };
+}
+
+macro_rules! log_trace {
+    ($rt:expr, $($arg:tt)*) => {
+        $crate::executor::log_level::log!(
+            $crate::operation::KernelLogLevel::Trace, $rt, $($arg)*
+        )
+    };
+}
+
+pub(crate) use {log, log_debug, log_trace};
+
+#[cfg(test)]
+mod test {
+    use jstz_core::kv::Transaction;
+    use jstz_utils::{
+        test_util::{alice_keys, bob_keys},
+        KeyPair,
+    };
+    use tezos_smart_rollup_mock::MockHost;
+
+    use super::{current, execute};
+    use crate::{
+        operation::{KernelLogLevel, SetLogLevel},
+        Error,
+    };
+
+    #[test]
+    fn current_defaults_to_debug() {
+        let host = MockHost::default();
+        assert_eq!(current(&host).unwrap(), KernelLogLevel::Debug);
+    }
+
+    #[test]
+    fn execute_sets_log_level() {
+        let mut host = MockHost::default();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let KeyPair(injector, _) = alice_keys();
+
+        let receipt = execute(
+            &mut tx,
+            &injector,
+            &injector,
+            SetLogLevel {
+                level: KernelLogLevel::Error,
+            },
+        )
+        .unwrap();
+        tx.commit(&mut host).unwrap();
+
+        assert_eq!(receipt.level, KernelLogLevel::Error);
+        assert_eq!(current(&host).unwrap(), KernelLogLevel::Error);
+    }
+
+    #[test]
+    fn execute_fails_if_not_signed_by_injector() {
+        let mut host = MockHost::default();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let KeyPair(injector, _) = alice_keys();
+        let KeyPair(other, _) = bob_keys();
+
+        let result = execute(
+            &mut tx,
+            &other,
+            &injector,
+            SetLogLevel {
+                level: KernelLogLevel::Trace,
+            },
+        );
+        tx.commit(&mut host).unwrap();
+
+        assert!(matches!(result, Err(Error::InvalidInjector)));
+        assert_eq!(current(&host).unwrap(), KernelLogLevel::Debug);
+    }
+}
@@ -1,24 +1,31 @@
 #[cfg(feature = "v2_runtime")]
 use crate::{
-    operation::OracleResponse,
-    receipt::{OracleResponseReceipt, ReceiptContent},
+    operation::{OracleResponse, RotateOracleKey},
+    receipt::{OracleResponseReceipt, ReceiptContent, RotateOracleKeyReceipt},
     runtime::PROTOCOL_CONTEXT,
 };
 
 use crate::{
+    constants::MAX_HOST_CALLS,
+    context::account::{Account, Amount},
     operation::{
         self, Content, InternalOperation, Operation, OperationHash, SignedOperation,
     },
     receipt::{self, Receipt},
+    runtime::with_execution_budget,
     Error, Result,
 };
 use futures::future::FutureExt;
 use jstz_core::{host::HostRuntime, kv::Transaction, reveal_data::RevealData};
-use jstz_crypto::{hash::Blake2b, public_key::PublicKey};
+use jstz_crypto::{hash::Blake2b, public_key::PublicKey, public_key_hash::PublicKeyHash};
 use tezos_crypto_rs::hash::ContractKt1Hash;
+pub mod blueprint;
 pub mod deposit;
 pub mod fa_deposit;
 pub mod fa_withdraw;
+pub mod kernel_upgrade;
+pub mod log_level;
+pub mod receipt_retention;
 pub mod smart_function;
 pub mod withdraw;
 
@@ -71,19 +78,47 @@ async fn execute_operation_inner(
             }
             Err(Error::RevealTypeMismatch)
         }
+        operation::Content::UpgradeKernel(upgrade) => {
+            let result = kernel_upgrade::execute(tx, &op.public_key, injector, upgrade)?;
+            Ok((op_hash, receipt::ReceiptContent::UpgradeKernel(result)))
+        }
+        operation::Content::SetLogLevel(set_log_level) => {
+            let result = log_level::execute(tx, &op.public_key, injector, set_log_level)?;
+            Ok((op_hash, receipt::ReceiptContent::SetLogLevel(result)))
+        }
+        operation::Content::SetReceiptRetention(retention) => {
+            let result =
+                receipt_retention::execute(hrt, tx, &op.public_key, injector, retention)?;
+            Ok((
+                op_hash,
+                receipt::ReceiptContent::SetReceiptRetention(result),
+            ))
+        }
+        operation::Content::Blueprint(blueprint) => {
+            let result = blueprint::execute(
+                hrt,
+                tx,
+                &op.public_key,
+                _ticketer,
+                injector,
+                blueprint,
+            )
+            .await?;
+            Ok((op_hash, receipt::ReceiptContent::Blueprint(result)))
+        }
         #[cfg(feature = "v2_runtime")]
         operation::Content::OracleResponse(OracleResponse {
             request_id,
             response,
         }) => {
-            let oracle_ctx = PROTOCOL_CONTEXT
+            let protocol_ctx = PROTOCOL_CONTEXT
                 .get()
-                .expect("Protocol context should be initialized")
-                .oracle();
+                .expect("Protocol context should be initialized");
+            let oracle_ctx = protocol_ctx.oracle();
             let mut oracle = oracle_ctx.lock();
-            if &op.public_key != oracle.public_key() {
+            if !oracle.accepts(&op.public_key, protocol_ctx.current_level()) {
                 // [execute_operation] verifies SignedOperation signature
-                // so we only need to check pk equality
+                // so we only need to check that the key is accepted
                 return Err(Error::InvalidOracleKey);
             }
             oracle
@@ -95,6 +130,33 @@ async fn execute_operation_inner(
                 ReceiptContent::OracleResponse(OracleResponseReceipt { request_id }),
             ))
         }
+        #[cfg(feature = "v2_runtime")]
+        operation::Content::RotateOracleKey(RotateOracleKey {
+            public_key,
+            overlap,
+        }) => {
+            let protocol_ctx = PROTOCOL_CONTEXT
+                .get()
+                .expect("Protocol context should be initialized");
+            let oracle_ctx = protocol_ctx.oracle();
+            let mut oracle = oracle_ctx.lock();
+            // Only the current key may register its successor
+            if &op.public_key != oracle.public_key() {
+                return Err(Error::InvalidOracleKey);
+            }
+            let retiring_until = protocol_ctx.current_level().saturating_add(overlap);
+            oracle
+                .rotate(tx, public_key.clone(), retiring_until)
+                .map_err(|e| Error::V2Error(e.into()))?;
+
+            Ok((
+                op_hash,
+                ReceiptContent::RotateOracleKey(RotateOracleKeyReceipt {
+                    public_key,
+                    retiring_until,
+                }),
+            ))
+        }
     }
 }
 
@@ -117,29 +179,74 @@ pub async fn execute_operation(
     signed_operation: SignedOperation,
     ticketer: &ContractKt1Hash,
     injector: &PublicKey,
+) -> Receipt {
+    if let Err(e) = signed_operation.verify() {
+        return Receipt::new(resolve_operation_hash(&signed_operation), Err(e));
+    }
+    execute_verified_operation(hrt, tx, signed_operation, ticketer, injector).await
+}
+
+/// Like [`execute_operation`], for operations whose signature has already been
+/// verified, e.g. when reading them from the inbox
+pub async fn execute_verified_operation(
+    hrt: &mut impl HostRuntime,
+    tx: &mut Transaction,
+    signed_operation: SignedOperation,
+    ticketer: &ContractKt1Hash,
+    injector: &PublicKey,
 ) -> Receipt {
     #[cfg(feature = "simulation")]
     if signed_operation.is_simulation() {
         tx.set_simulation();
     }
 
-    let validity = signed_operation.verify().and_then(|_| {
-        signed_operation.verify_and_increment_nonce(
+    // Blueprints are not subject to the nonce of the injector, see `Blueprint`
+    let validity = match signed_operation.content() {
+        Content::Blueprint(_) => Ok(()),
+        _ => signed_operation.verify_and_increment_nonce(
             hrt,
             #[cfg(feature = "simulation")]
             tx,
-        )
-    });
+        ),
+    };
     let op = signed_operation.into();
     let op_hash = resolve_operation_hash(&op);
-    let result = match validity {
-        Ok(_) => execute_operation_inner(hrt, tx, op, ticketer, injector).await,
-        Err(err) => Err(err),
+    let (result, fee) = match validity.and_then(|_| charge_fee(hrt, tx, &op, injector)) {
+        Ok(fee) => (
+            with_execution_budget(
+                MAX_HOST_CALLS,
+                execute_operation_inner(hrt, tx, op, ticketer, injector),
+            )
+            .await,
+            fee,
+        ),
+        Err(err) => (Err(err), None),
     };
-    result.map_or_else(
+    let mut receipt = result.map_or_else(
         |e| Receipt::new(op_hash, Err(e)),
         |(hash, content)| Receipt::new(hash, Ok(content)),
-    )
+    );
+    receipt.fee = fee;
+    receipt
+}
+
+/// Transfers the fee of `op`, see [`Operation::fee`], from its source to the
+/// injector. Returns the charged fee, or `None` if the operation declares no fee.
+/// The fee is charged even if the execution of the operation fails.
+fn charge_fee(
+    hrt: &impl HostRuntime,
+    tx: &mut Transaction,
+    op: &Operation,
+    injector: &PublicKey,
+) -> Result<Option<Amount>> {
+    if !op.declares_fee() {
+        return Ok(None);
+    }
+    let fee = op.fee();
+    if fee > 0 {
+        Account::transfer(hrt, tx, &op.source(), &PublicKeyHash::from(injector), fee)?;
+    }
+    Ok(Some(fee))
 }
 
 fn resolve_operation_hash(op: &Operation) -> Blake2b {
@@ -173,7 +280,9 @@ mod tests {
     use crate::runtime::v2::fetch::http::Request;
     use crate::{
         context::account::Nonce,
-        operation::{Content, DeployFunction, RevealLargePayload, RunFunction},
+        operation::{
+            Blueprint, Content, DeployFunction, RevealLargePayload, RunFunction,
+        },
         receipt::{ReceiptContent, ReceiptResult},
         HttpBody,
     };
@@ -231,6 +340,8 @@ mod tests {
                     response: resp,
                 }
                 .into(),
+                max_fee: None,
+                priority_fee: None,
             };
             SignedOperation::new(sk.sign(response_op.hash()).unwrap(), response_op)
         }
@@ -290,6 +401,7 @@ mod tests {
         Content::DeployFunction(DeployFunction {
             function_code,
             account_credit,
+            interface: None,
         })
     }
 
@@ -298,6 +410,8 @@ mod tests {
             public_key: pk,
             nonce: Nonce(0),
             content,
+            max_fee: None,
+            priority_fee: None,
         };
         let sig = sk.sign(deploy_op.hash()).unwrap();
         SignedOperation::new(sig, deploy_op)
@@ -319,6 +433,8 @@ mod tests {
             public_key: pk,
             nonce: Nonce(0),
             content: Content::RevealLargePayload(rdc_op_content),
+            max_fee: None,
+            priority_fee: None,
         };
         let sig = sk.sign(rdc_op.hash()).unwrap();
         SignedOperation::new(sig, rdc_op)
@@ -406,6 +522,8 @@ mod tests {
             public_key: pk.clone(),
             nonce: Nonce(2),
             content: deploy_function_content(),
+            max_fee: None,
+            priority_fee: None,
         };
         let sig = sk.sign(deploy_op.hash()).unwrap();
         let op = SignedOperation::new(sig, deploy_op);
@@ -416,6 +534,69 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn charges_fees_to_injector() {
+        let mut host = MockHost::default();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let (injector_pkh, injector, _) = bootstrap1();
+        let (pkh, pk, sk) = bootstrap2();
+        Account::add_balance(&host, &mut tx, &pkh, 100).unwrap();
+        let ticketer = ContractKt1Hash::try_from_bytes(&[0; 20]).unwrap();
+        let signed_op = |nonce, max_fee, priority_fee| {
+            let op = Operation {
+                public_key: pk.clone(),
+                nonce: Nonce(nonce),
+                content: deploy_function_content(),
+                max_fee,
+                priority_fee,
+            };
+            SignedOperation::new(sk.sign(op.hash()).unwrap(), op)
+        };
+
+        // the priority fee is charged up to the maximum fee
+        let receipt = execute_operation(
+            &mut host,
+            &mut tx,
+            signed_op(0, Some(70), Some(90)),
+            &ticketer,
+            &injector,
+        )
+        .await;
+        assert!(matches!(receipt.result, ReceiptResult::Success(_)));
+        assert_eq!(receipt.fee, Some(70));
+        assert_eq!(Account::balance(&host, &mut tx, &pkh).unwrap(), 30);
+        assert_eq!(Account::balance(&host, &mut tx, &injector_pkh).unwrap(), 70);
+
+        // operations whose fee cannot be paid are not executed
+        let receipt = execute_operation(
+            &mut host,
+            &mut tx,
+            signed_op(1, None, Some(50)),
+            &ticketer,
+            &injector,
+        )
+        .await;
+        assert!(
+            matches!(receipt.result, ReceiptResult::Failed(e) if e.contains("InsufficientFunds"))
+        );
+        assert_eq!(receipt.fee, None);
+        assert_eq!(Account::balance(&host, &mut tx, &pkh).unwrap(), 30);
+
+        // operations declaring no fee are not charged
+        let receipt = execute_operation(
+            &mut host,
+            &mut tx,
+            signed_op(2, None, None),
+            &ticketer,
+            &injector,
+        )
+        .await;
+        assert!(matches!(receipt.result, ReceiptResult::Success(_)));
+        assert_eq!(receipt.fee, None);
+        assert_eq!(Account::balance(&host, &mut tx, &pkh).unwrap(), 30);
+    }
+
     #[tokio::test]
     async fn throws_if_injector_is_invalid() {
         let mut host = MockHost::default();
@@ -435,6 +616,141 @@ mod tests {
         assert_eq!(receipt.hash().to_string(), deploy_op.hash().to_string());
     }
 
+    fn signed_blueprint(
+        operations: Vec<SignedOperation>,
+        pk: PublicKey,
+        sk: SecretKey,
+    ) -> SignedOperation {
+        make_signed_op(Blueprint { operations }.into(), pk, sk)
+    }
+
+    #[tokio::test]
+    async fn executes_blueprint_operations_in_order() {
+        let mut host = MockHost::default();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let (_, injector, injector_sk) = bootstrap1();
+        let (_, pk, sk) = bootstrap2();
+        let deploy_op = |nonce| {
+            let op = Operation {
+                public_key: pk.clone(),
+                nonce: Nonce(nonce),
+                content: deploy_function_content(),
+                max_fee: None,
+                priority_fee: None,
+            };
+            SignedOperation::new(sk.sign(op.hash()).unwrap(), op)
+        };
+        let operations = vec![deploy_op(0), deploy_op(1)];
+        let hashes = operations.iter().map(|op| op.hash()).collect::<Vec<_>>();
+        let blueprint = signed_blueprint(operations, injector.clone(), injector_sk);
+        let ticketer = ContractKt1Hash::try_from_bytes(&[0; 20]).unwrap();
+
+        let receipt = execute_operation(
+            &mut host,
+            &mut tx,
+            blueprint.clone(),
+            &ticketer,
+            &injector,
+        )
+        .await;
+        assert!(matches!(
+            receipt.result,
+            ReceiptResult::Success(ReceiptContent::Blueprint(ref executed))
+                if executed.operations == hashes
+        ));
+
+        // publishing a blueprint again does not execute its operations again
+        let receipt =
+            execute_operation(&mut host, &mut tx, blueprint, &ticketer, &injector).await;
+        assert!(matches!(receipt.result, ReceiptResult::Success(_)));
+        for hash in hashes {
+            let path = OwnedPath::try_from(format!("/jstz_receipt/{hash}")).unwrap();
+            let stored: Guarded<Receipt> = tx.get(&host, path).unwrap().unwrap();
+            assert!(matches!(stored.result, ReceiptResult::Success(_)));
+        }
+    }
+
+    #[tokio::test]
+    async fn throws_if_blueprint_is_not_signed_by_injector() {
+        let mut host = MockHost::default();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let (_, injector, _) = bootstrap1();
+        let (_, pk, sk) = bootstrap2();
+        let deploy_op = make_signed_op(deploy_function_content(), pk.clone(), sk.clone());
+        let hash = deploy_op.hash();
+        let blueprint = signed_blueprint(vec![deploy_op], pk, sk);
+        let ticketer = ContractKt1Hash::try_from_bytes(&[0; 20]).unwrap();
+
+        let receipt =
+            execute_operation(&mut host, &mut tx, blueprint, &ticketer, &injector).await;
+        assert!(
+            matches!(receipt.result, ReceiptResult::Failed(e) if e.contains("InvalidInjector"))
+        );
+        let path = OwnedPath::try_from(format!("/jstz_receipt/{hash}")).unwrap();
+        assert!(!tx.contains_key(&host, &path).unwrap());
+    }
+
+    #[cfg(feature = "v2_runtime")]
+    #[tokio::test]
+    async fn fails_operation_exceeding_max_host_calls() {
+        let mut host = MockHost::default();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let (_, pk, sk) = bootstrap1();
+        let ticketer = ContractKt1Hash::try_from_bytes(&[0; 20]).unwrap();
+        let deploy_op = make_signed_op(
+            Content::DeployFunction(DeployFunction {
+                function_code: r#"
+                export default () => {
+                    while (true) {
+                        Kv.contains("key");
+                    }
+                };
+                "#
+                .to_string(),
+                account_credit: 0,
+                interface: None,
+            }),
+            pk.clone(),
+            sk.clone(),
+        );
+        let receipt =
+            execute_operation(&mut host, &mut tx, deploy_op, &ticketer, &pk).await;
+        let ReceiptResult::Success(ReceiptContent::DeployFunction(deployed)) =
+            receipt.result
+        else {
+            panic!("expected a deploy receipt, got {:?}", receipt.result)
+        };
+
+        let run_op = Operation {
+            public_key: pk.clone(),
+            nonce: Nonce(1),
+            content: Content::RunFunction(RunFunction {
+                uri: format!("jstz://{}/", deployed.address).try_into().unwrap(),
+                method: Method::GET,
+                headers: HeaderMap::new(),
+                body: HttpBody::empty(),
+                gas_limit: 10000,
+            }),
+            max_fee: None,
+            priority_fee: None,
+        };
+        let sig = sk.sign(run_op.hash()).unwrap();
+        let receipt = execute_operation(
+            &mut host,
+            &mut tx,
+            SignedOperation::new(sig, run_op),
+            &ticketer,
+            &pk,
+        )
+        .await;
+        assert!(
+            matches!(receipt.result, ReceiptResult::Failed(e) if e.contains("ExecutionBudgetExceeded"))
+        );
+    }
+
     #[tokio::test]
     async fn run_function_with_invalid_scheme_fails() {
         let mut host = MockHost::default();
@@ -512,7 +828,7 @@ mod tests {
                 .await;
         let received_resp = rx.await.unwrap();
         assert_eq!(resp, received_resp);
-        assert_eq!(format!("{:?}", receipt), "Receipt { hash: Blake2b([43, 173, 229, 86, 39, 53, 239, 75, 89, 125, 160, 162, 17, 118, 230, 15, 219, 184, 198, 23, 222, 64, 225, 230, 221, 14, 103, 28, 175, 82, 199, 222]), result: Success(OracleResponse(OracleResponseReceipt { request_id: 0 })) }")
+        assert_eq!(format!("{:?}", receipt), "Receipt { hash: Blake2b([43, 173, 229, 86, 39, 53, 239, 75, 89, 125, 160, 162, 17, 118, 230, 15, 219, 184, 198, 23, 222, 64, 225, 230, 221, 14, 103, 28, 175, 82, 199, 222]), result: Success(OracleResponse(OracleResponseReceipt { request_id: 0 })), fee: None }")
     }
 
     #[cfg(feature = "v2_runtime")]
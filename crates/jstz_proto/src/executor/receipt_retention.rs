@@ -0,0 +1,296 @@
+//! Retention of receipts in durable storage.
+//!
+//! Receipts are kept forever unless the injector sets a retention window with a
+//! [`SetReceiptRetention`] operation. While a window is set, the receipts written
+//! are indexed under [`RECEIPT_INDEX_PATH`] by the L1 level recorded at the start
+//! of the level, see [`start_level`], and removed `levels` levels later, except for
+//! the ones of the pinned operations. Receipts written while no window is set are
+//! kept.
+
+use std::collections::HashSet;
+
+use jstz_core::{host::HostRuntime, kv::Transaction};
+use jstz_crypto::public_key::PublicKey;
+use tezos_smart_rollup::storage::path::OwnedPath;
+
+use crate::{
+    operation::{OperationHash, SetReceiptRetention},
+    receipt::SetReceiptRetentionReceipt,
+    storage::{
+        LEVEL_PATH, RECEIPT_INDEX_FLOOR_PATH, RECEIPT_INDEX_PATH, RECEIPT_RETENTION_PATH,
+    },
+    Error, Result,
+};
+
+/// Maximum number of levels whose receipts are removed at the start of a level, so
+/// that shortening the window by many levels does not remove every expired receipt
+/// at once
+const MAX_PRUNED_LEVELS: u32 = 16;
+
+/// Sets the receipt retention window. Only the injector may set it.
+pub fn execute(
+    hrt: &impl HostRuntime,
+    tx: &mut Transaction,
+    public_key: &PublicKey,
+    injector: &PublicKey,
+    retention: SetReceiptRetention,
+) -> Result<SetReceiptRetentionReceipt> {
+    if public_key != injector {
+        return Err(Error::InvalidInjector);
+    }
+    // Receipts are indexed from the current level on
+    let floor = OwnedPath::from(&RECEIPT_INDEX_FLOOR_PATH);
+    if !tx.contains_key(hrt, &floor)? {
+        tx.insert(floor, current_level(hrt, tx)?)?;
+    }
+    let receipt = SetReceiptRetentionReceipt {
+        levels: retention.levels,
+        pinned: retention.pinned.clone(),
+    };
+    tx.insert(OwnedPath::from(&RECEIPT_RETENTION_PATH), retention)?;
+    Ok(receipt)
+}
+
+/// Returns the receipt retention window, if any
+pub fn current(
+    hrt: &impl HostRuntime,
+    tx: &Transaction,
+) -> Result<Option<SetReceiptRetention>> {
+    Ok(tx
+        .get::<SetReceiptRetention>(hrt, OwnedPath::from(&RECEIPT_RETENTION_PATH))?
+        .map(|retention| retention.clone()))
+}
+
+fn current_level(hrt: &impl HostRuntime, tx: &Transaction) -> Result<u32> {
+    Ok(tx
+        .get::<u32>(hrt, OwnedPath::from(&LEVEL_PATH))?
+        .map(|level| *level)
+        .unwrap_or_default())
+}
+
+fn level_index_path(level: u32) -> Result<OwnedPath> {
+    Ok(OwnedPath::try_from(format!(
+        "{RECEIPT_INDEX_PATH}/{level}"
+    ))?)
+}
+
+/// Indexes the receipt of operation `hash` under the current level if a retention
+/// window is set, so that it is removed once it expires
+pub fn index(
+    hrt: &impl HostRuntime,
+    tx: &mut Transaction,
+    hash: &OperationHash,
+) -> Result<()> {
+    if !matches!(
+        current(hrt, tx)?,
+        Some(SetReceiptRetention {
+            levels: Some(_),
+            ..
+        })
+    ) {
+        return Ok(());
+    }
+    let level = current_level(hrt, tx)?;
+    let path = OwnedPath::try_from(format!("{RECEIPT_INDEX_PATH}/{level}/{hash}"))?;
+    tx.insert(path, ())?;
+    Ok(())
+}
+
+/// Records `level` as the current L1 level and removes the receipts that expired at
+/// the start of the level. Returns the number of removed receipts.
+///
+/// The index of the expired levels is removed along with their receipts, so the
+/// receipts of operations pinned after they expired are kept too.
+pub fn start_level(
+    hrt: &impl HostRuntime,
+    tx: &mut Transaction,
+    level: u32,
+) -> Result<usize> {
+    tx.insert(OwnedPath::from(&LEVEL_PATH), level)?;
+    let Some(SetReceiptRetention {
+        levels: Some(levels),
+        pinned,
+    }) = current(hrt, tx)?
+    else {
+        return Ok(0);
+    };
+    // Receipts indexed at `expired` or before are removed
+    let Some(expired) = level.checked_sub(levels) else {
+        return Ok(0);
+    };
+    let floor_path = OwnedPath::from(&RECEIPT_INDEX_FLOOR_PATH);
+    let floor = tx
+        .get::<u32>(hrt, floor_path.clone())?
+        .map(|floor| *floor)
+        .unwrap_or(expired);
+    let last = expired.min(floor.saturating_add(MAX_PRUNED_LEVELS - 1));
+    if floor > last {
+        return Ok(0);
+    }
+
+    let pinned: HashSet<String> = pinned.iter().map(ToString::to_string).collect();
+    let mut removed = 0;
+    for level in floor..=last {
+        for key in tx.scan(hrt, &level_index_path(level)?)? {
+            let key_str = key.to_string();
+            let hash = key_str.rsplit('/').next().unwrap_or_default();
+            if !pinned.contains(hash) {
+                let receipt = OwnedPath::try_from(format!("/jstz_receipt/{hash}"))?;
+                if tx.contains_key(hrt, &receipt)? {
+                    tx.remove(receipt)?;
+                    removed += 1;
+                }
+            }
+            tx.remove(key)?;
+        }
+    }
+    tx.insert(floor_path, last + 1)?;
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod test {
+    use jstz_core::kv::{Storage, Transaction};
+    use jstz_crypto::hash::Blake2b;
+    use jstz_utils::{
+        test_util::{alice_keys, bob_keys},
+        KeyPair,
+    };
+    use tezos_smart_rollup::storage::path::OwnedPath;
+    use tezos_smart_rollup_mock::MockHost;
+
+    use super::{current, execute, start_level};
+    use crate::{operation::SetReceiptRetention, receipt::Receipt, Error};
+
+    fn retention(levels: Option<u32>, pinned: Vec<Blake2b>) -> SetReceiptRetention {
+        SetReceiptRetention { levels, pinned }
+    }
+
+    fn receipt_path(hash: &Blake2b) -> OwnedPath {
+        OwnedPath::try_from(format!("/jstz_receipt/{hash}")).unwrap()
+    }
+
+    /// Writes a receipt at the start of `level`
+    fn write_receipt(host: &mut MockHost, level: u32, name: &[u8]) -> Blake2b {
+        let hash = Blake2b::from(name);
+        let mut tx = Transaction::default();
+        tx.begin();
+        start_level(host, &mut tx, level).unwrap();
+        Receipt::new(hash.clone(), Err(Error::InvalidInjector))
+            .write(host, &mut tx)
+            .unwrap();
+        tx.commit(host).unwrap();
+        hash
+    }
+
+    fn start(host: &mut MockHost, level: u32) -> usize {
+        let mut tx = Transaction::default();
+        tx.begin();
+        let removed = start_level(host, &mut tx, level).unwrap();
+        tx.commit(host).unwrap();
+        removed
+    }
+
+    fn set_retention(host: &mut MockHost, retention: SetReceiptRetention) {
+        let KeyPair(injector, _) = alice_keys();
+        let mut tx = Transaction::default();
+        tx.begin();
+        execute(host, &mut tx, &injector, &injector, retention).unwrap();
+        tx.commit(host).unwrap();
+    }
+
+    #[test]
+    fn keeps_receipts_without_retention() {
+        let mut host = MockHost::default();
+        let hash = write_receipt(&mut host, 1, b"op");
+
+        assert_eq!(start(&mut host, 100), 0);
+        assert!(Storage::contains_key(&host, &receipt_path(&hash)).unwrap());
+    }
+
+    #[test]
+    fn removes_expired_receipts() {
+        let mut host = MockHost::default();
+        start(&mut host, 1);
+        let pinned = Blake2b::from(b"pinned".as_ref());
+        set_retention(&mut host, retention(Some(3), vec![pinned.clone()]));
+        let old = write_receipt(&mut host, 2, b"old");
+        write_receipt(&mut host, 2, b"pinned");
+        let recent = write_receipt(&mut host, 3, b"recent");
+
+        assert_eq!(start(&mut host, 4), 0);
+        assert_eq!(start(&mut host, 5), 1);
+        assert!(!Storage::contains_key(&host, &receipt_path(&old)).unwrap());
+        assert!(Storage::contains_key(&host, &receipt_path(&pinned)).unwrap());
+        assert!(Storage::contains_key(&host, &receipt_path(&recent)).unwrap());
+
+        assert_eq!(start(&mut host, 6), 1);
+        assert!(!Storage::contains_key(&host, &receipt_path(&recent)).unwrap());
+        assert!(Storage::contains_key(&host, &receipt_path(&pinned)).unwrap());
+    }
+
+    #[test]
+    fn catches_up_with_shortened_window() {
+        let mut host = MockHost::default();
+        set_retention(&mut host, retention(Some(1000), vec![]));
+        let hashes: Vec<_> = (0..20u8)
+            .map(|level| write_receipt(&mut host, level.into(), &[level]))
+            .collect();
+
+        set_retention(&mut host, retention(Some(1), vec![]));
+        assert_eq!(start(&mut host, 21), super::MAX_PRUNED_LEVELS as usize);
+        assert_eq!(start(&mut host, 22), 4);
+        for hash in hashes {
+            assert!(!Storage::contains_key(&host, &receipt_path(&hash)).unwrap());
+        }
+    }
+
+    #[test]
+    fn execute_sets_retention() {
+        let mut host = MockHost::default();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let KeyPair(injector, _) = alice_keys();
+
+        let receipt = execute(
+            &host,
+            &mut tx,
+            &injector,
+            &injector,
+            retention(Some(10), vec![]),
+        )
+        .unwrap();
+        tx.commit(&mut host).unwrap();
+
+        assert_eq!(receipt.levels, Some(10));
+        let tx = Transaction::default();
+        tx.begin();
+        assert_eq!(
+            current(&host, &tx).unwrap(),
+            Some(retention(Some(10), vec![]))
+        );
+    }
+
+    #[test]
+    fn execute_fails_if_not_signed_by_injector() {
+        let mut host = MockHost::default();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let KeyPair(injector, _) = alice_keys();
+        let KeyPair(other, _) = bob_keys();
+
+        let result = execute(
+            &host,
+            &mut tx,
+            &other,
+            &injector,
+            retention(Some(10), vec![]),
+        );
+        tx.commit(&mut host).unwrap();
+
+        assert!(matches!(result, Err(Error::InvalidInjector)));
+        let tx = Transaction::default();
+        tx.begin();
+        assert_eq!(current(&host, &tx).unwrap(), None);
+    }
+}
@@ -1,10 +1,10 @@
 use jstz_core::{host::HostRuntime, kv::Transaction};
 use jstz_crypto::smart_function_hash::SmartFunctionHash;
-use tezos_smart_rollup::prelude::debug_msg;
 
 use crate::{
     context::account::{Account, Addressable},
     error::Result,
+    executor::log_level::log_debug,
     operation::DeployFunction,
     receipt::DeployFunctionReceipt,
     runtime::ParsedCode,
@@ -34,24 +34,36 @@ pub fn execute(
     let DeployFunction {
         function_code,
         account_credit,
+        interface,
     } = deployment;
+    if let Some(interface) = &interface {
+        interface.validate()?;
+    }
 
-    // SAFETY: Smart function creation and sub_balance must be atomic
+    // SAFETY: Smart function creation, sub_balance and the interface insertion must
+    // be atomic
     tx.begin();
-    match deploy_smart_function(hrt, tx, source, function_code, account_credit) {
+    let result = deploy_smart_function(hrt, tx, source, function_code, account_credit)
+        .and_then(|address| {
+            if let Some(interface) = interface {
+                interface.insert(tx, &address)?;
+            }
+            Ok(address)
+        });
+    match result {
         Ok(address) => {
             tx.commit(hrt)?;
-            debug_msg!(hrt, "[📜] Smart function deployed: {}\n", address);
+            log_debug!(hrt, "[📜] Smart function deployed: {}\n", address);
             Ok(DeployFunctionReceipt { address })
         }
         Err(err @ Error::AccountExists) => {
             tx.rollback()?;
-            debug_msg!(hrt, "[📜] Smart function was already deployed\n");
+            log_debug!(hrt, "[📜] Smart function was already deployed\n");
             Err(err)
         }
         Err(err) => {
             tx.rollback()?;
-            debug_msg!(hrt, "[📜] Smart function deployment failed. \n");
+            log_debug!(hrt, "[📜] Smart function deployment failed. \n");
             Err(err)
         }
     }
@@ -61,7 +73,10 @@ pub fn execute(
 mod test {
     use jstz_mock::host::JstzMockHost;
 
-    use crate::{context::account::Address, executor::smart_function};
+    use crate::{
+        context::{account::Address, interface::FunctionInterface},
+        executor::smart_function,
+    };
 
     use super::*;
 
@@ -76,6 +91,7 @@ mod test {
         let deployment = DeployFunction {
             function_code: "export default () => {}".to_string(),
             account_credit: 0,
+            interface: None,
         };
         let result = smart_function::deploy::execute(hrt, &mut tx, &source, deployment);
         assert!(result.is_ok());
@@ -95,8 +111,45 @@ mod test {
         let deployment = DeployFunction {
             function_code: "export default () => {}".to_string(),
             account_credit: 10000,
+            interface: None,
         };
         let result = smart_function::deploy::execute(hrt, &mut tx, &source, deployment);
         assert!(result.is_err_and(|e| { e.to_string().contains("InsufficientFunds") }));
     }
+
+    #[test]
+    fn execute_deploy_stores_interface() {
+        let mut host = JstzMockHost::default();
+        let mut tx = Transaction::default();
+        let source = Address::User(jstz_mock::account1());
+        let hrt = host.rt();
+        tx.begin();
+
+        let interface: FunctionInterface = serde_json::from_value(serde_json::json!({
+            "routes": [{ "method": "GET", "path": "/counter" }]
+        }))
+        .unwrap();
+        let deployment = DeployFunction {
+            function_code: "export default () => {}".to_string(),
+            account_credit: 0,
+            interface: Some(interface.clone()),
+        };
+        let receipt =
+            smart_function::deploy::execute(hrt, &mut tx, &source, deployment).unwrap();
+        assert_eq!(
+            FunctionInterface::get(hrt, &tx, &receipt.address).unwrap(),
+            Some(interface.clone())
+        );
+
+        // invalid interfaces are rejected
+        let mut invalid = interface;
+        invalid.routes[0].method = "FETCH".to_string();
+        let deployment = DeployFunction {
+            function_code: "export default () => 1".to_string(),
+            account_credit: 0,
+            interface: Some(invalid),
+        };
+        let result = smart_function::deploy::execute(hrt, &mut tx, &source, deployment);
+        assert!(matches!(result, Err(Error::InterfaceError { .. })));
+    }
 }
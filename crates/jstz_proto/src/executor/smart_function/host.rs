@@ -256,6 +256,7 @@ mod test {
         tx.begin();
         assert_eq!(0, Account::balance(&host, &mut tx, &source).unwrap());
 
+        tx.flush_outbox(&mut host).unwrap();
         let level = host.run_level(|_| {});
         assert_eq!(1, host.outbox_at(level).len());
     }
@@ -279,6 +280,7 @@ mod test {
         tx.begin();
         assert_eq!(0, Account::balance(rt, &mut tx, &source).unwrap());
 
+        tx.flush_outbox(rt).unwrap();
         let level = rt.run_level(|_| {});
         assert_eq!(1, rt.outbox_at(level).len());
     }
@@ -354,6 +356,7 @@ mod test {
         tx.begin();
         assert_eq!(0, Account::balance(&host, &mut tx, &source).unwrap());
 
+        tx.flush_outbox(&mut host).unwrap();
         let level = host.run_level(|_| {});
         assert_eq!(1, host.outbox_at(level).len());
     }
@@ -571,6 +571,7 @@ mod test {
         .await
         .expect("Withdrawal expected to succeed");
         tx.commit(host).unwrap();
+        tx.flush_outbox(host).unwrap();
 
         let level = host.run_level(|_| {});
         let outbox = host.outbox_at(level);
@@ -1435,6 +1436,7 @@ mod test {
         .expect("Fa withdraw expected");
 
         tx.commit(host).unwrap();
+        tx.flush_outbox(host).unwrap();
 
         let level = host.run_level(|_| {});
         let outbox = host.outbox_at(level);
@@ -1663,4 +1665,39 @@ mod test {
         let body = String::from_utf8(response.body.unwrap()).unwrap();
         assert_eq!(&body, "true");
     }
+
+    #[cfg(feature = "v2_runtime")]
+    #[tokio::test]
+    async fn terminates_run_when_execution_budget_is_exhausted() {
+        let source = Address::User(jstz_mock::account1());
+        let mut jstz_mock_host = JstzMockHost::default();
+        let host = jstz_mock_host.rt();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let code = r#"
+        const handler = () => {
+            while (true) {
+                Kv.contains("key");
+            }
+        };
+        export default handler;
+        "#;
+        let smart_function =
+            smart_function::deploy(host, &mut tx, &source, code.to_string(), 0).unwrap();
+
+        let run_function = RunFunction {
+            uri: format!("jstz://{}/", &smart_function).try_into().unwrap(),
+            method: Method::GET,
+            headers: HeaderMap::new(),
+            body: HttpBody::empty(),
+            gas_limit: 1000,
+        };
+        let fake_op_hash = Blake2b::from(b"fake_op_hash".as_ref());
+        let result = crate::runtime::with_execution_budget(
+            100,
+            execute(host, &mut tx, &source, run_function, fake_op_hash),
+        )
+        .await;
+        assert!(matches!(result, Err(crate::Error::ExecutionBudgetExceeded)));
+    }
 }
@@ -1,7 +1,10 @@
 #![cfg_attr(feature = "v2_runtime", allow(unused))]
 use jstz_core::{
     host::HostRuntime,
-    kv::{outbox::OutboxMessage, Transaction},
+    kv::{
+        outbox::{new_withdrawal_transaction, OutboxTransaction},
+        Transaction,
+    },
 };
 use serde::{Deserialize, Serialize};
 use tezos_smart_rollup::{
@@ -28,7 +31,7 @@ fn create_withdrawal(
     amount: Amount,
     receiver: &Address,
     ticketer: &ContractKt1Hash,
-) -> Result<OutboxMessage> {
+) -> Result<OutboxTransaction> {
     let receiver_pkh = receiver.to_base58();
     let ticket = FA2_1Ticket::new(
         Contract::Originated(ticketer.clone()),
@@ -36,13 +39,13 @@ fn create_withdrawal(
         amount,
     )
     .map_err(|_| Error::InvalidTicketType)?;
-    let message = OutboxMessage::new_withdrawal_message(
+    let transaction = new_withdrawal_transaction(
         &Contract::try_from(receiver_pkh).unwrap(),
         &Contract::Originated(ticketer.clone()),
         ticket,
         BURN_ENTRYPOINT,
     )?;
-    Ok(message)
+    Ok(transaction)
 }
 
 fn withdraw(
@@ -54,8 +57,8 @@ fn withdraw(
 ) -> Result<()> {
     let Withdrawal { amount, receiver } = withdrawal;
     Account::sub_balance(rt, tx, source, amount)?;
-    let message = create_withdrawal(amount, &receiver, ticketer)?;
-    tx.queue_outbox_message(rt, message)?;
+    let transaction = create_withdrawal(amount, &receiver, ticketer)?;
+    tx.queue_outbox_transaction(rt, transaction)?;
     Ok(())
 }
 
@@ -143,6 +146,7 @@ mod test {
         execute_withdraw(&mut host, &mut tx, &source, withdrawal, &ticketer).unwrap();
 
         tx.commit(&mut host).unwrap();
+        tx.flush_outbox(&mut host).unwrap();
         let level = host.run_level(|_| {});
         assert_eq!(1, host.outbox_at(level).len());
 
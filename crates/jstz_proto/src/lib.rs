@@ -1,5 +1,6 @@
 mod error;
 
+pub mod constants;
 pub mod context;
 pub mod executor;
 pub mod logger;
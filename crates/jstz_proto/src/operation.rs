@@ -1,20 +1,30 @@
 #[cfg(feature = "v2_runtime")]
 use crate::runtime::v2::fetch::http::Response;
 use crate::{
-    context::account::{Account, Address, Addressable, Amount, Nonce},
+    context::{
+        account::{Account, Address, Addressable, Amount, Nonce},
+        interface::FunctionInterface,
+    },
     Error, HttpBody, Result,
 };
-use bincode::{Decode, Encode};
+use bincode::{
+    de::Decoder,
+    enc::Encoder,
+    error::{DecodeError, EncodeError},
+    Decode, Encode,
+};
 use derive_more::{Deref, Display, From};
 use http::{HeaderMap, Method, Uri};
 
 #[cfg(feature = "v2_runtime")]
-use crate::runtime::v2::oracle::request::RequestId;
+use crate::{runtime::v2::oracle::request::RequestId, BlockLevel};
 
 #[cfg(feature = "simulation")]
 use jstz_core::kv::Transaction;
 
-use jstz_core::{host::HostRuntime, reveal_data::PreimageHash};
+use jstz_core::{
+    host::HostRuntime, reveal_data::PreimageHash, Versioned, VersionedValue, UNVERSIONED,
+};
 
 #[cfg(feature = "simulation")]
 use jstz_core::simulation::SimulationRequest;
@@ -26,22 +36,112 @@ use jstz_crypto::{
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-#[derive(
-    Debug, Serialize, Deserialize, PartialEq, Eq, ToSchema, Encode, Decode, Clone,
-)]
+/// Operations are encoded as a [`VersionedValue`], so that operations encoded
+/// before fees were introduced can still be decoded
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, ToSchema, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Operation {
     /// The public key of the account which was used to sign the operation
     pub public_key: PublicKey,
-    #[bincode(with_serde)]
     /// Nonce is used to avoid replay attacks.
     pub nonce: Nonce,
     /// The content of the operation
     pub content: Content,
+    /// Maximum fee, in mutez, that the source agrees to pay for the operation
+    #[serde(default)]
+    pub max_fee: Option<Amount>,
+    /// Fee, in mutez, offered to the sequencer to execute the operation before the
+    /// operations offering less. It is charged up to `max_fee`.
+    #[serde(default)]
+    pub priority_fee: Option<Amount>,
+}
+
+#[derive(Encode, Decode)]
+struct OperationLayout {
+    public_key: PublicKey,
+    #[bincode(with_serde)]
+    nonce: Nonce,
+    content: Content,
+    max_fee: Option<Amount>,
+    priority_fee: Option<Amount>,
 }
 
+impl Versioned for OperationLayout {
+    const VERSION: u8 = 1;
+
+    fn migrate<D: Decoder>(
+        version: u8,
+        decoder: &mut D,
+    ) -> std::result::Result<Self, DecodeError> {
+        match version {
+            UNVERSIONED => {
+                let op = <UnversionedOperation as Decode>::decode(decoder)?;
+                Ok(Self {
+                    public_key: op.public_key,
+                    nonce: op.nonce,
+                    content: op.content,
+                    max_fee: None,
+                    priority_fee: None,
+                })
+            }
+            _ => Err(DecodeError::OtherString(format!(
+                "unknown operation version {version}"
+            ))),
+        }
+    }
+}
+
+/// Layout of the operations encoded before fees were introduced
+#[derive(Encode, Decode)]
+struct UnversionedOperation {
+    public_key: PublicKey,
+    #[bincode(with_serde)]
+    nonce: Nonce,
+    content: Content,
+}
+
+impl Encode for Operation {
+    fn encode<E: Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> std::result::Result<(), EncodeError> {
+        let layout = OperationLayout {
+            public_key: self.public_key.clone(),
+            nonce: self.nonce.clone(),
+            content: self.content.clone(),
+            max_fee: self.max_fee,
+            priority_fee: self.priority_fee,
+        };
+        Encode::encode(&VersionedValue(layout), encoder)
+    }
+}
+
+impl Decode for Operation {
+    fn decode<D: Decoder>(decoder: &mut D) -> std::result::Result<Self, DecodeError> {
+        let OperationLayout {
+            public_key,
+            nonce,
+            content,
+            max_fee,
+            priority_fee,
+        } = <VersionedValue<OperationLayout> as Decode>::decode(decoder)?.0;
+        Ok(Self {
+            public_key,
+            nonce,
+            content,
+            max_fee,
+            priority_fee,
+        })
+    }
+}
+
+bincode::impl_borrow_decode!(Operation);
+
 pub type OperationHash = Blake2b;
 
+/// The maximum operation size in bytes that can be directly included without using the reveal mechanism.
+pub const MAX_DIRECT_OPERATION_SIZE: usize = 3915;
+
 pub(crate) trait NoncePolicy {
     fn get_nonce(
         &mut self,
@@ -115,6 +215,19 @@ impl Operation {
         &self.content
     }
 
+    /// Returns true if the operation declares a maximum or a priority fee
+    pub fn declares_fee(&self) -> bool {
+        self.max_fee.is_some() || self.priority_fee.is_some()
+    }
+
+    /// Returns the fee charged to the source of the operation, which is its priority
+    /// fee capped at its maximum fee. It is also the effective priority of the
+    /// operation in the sequencer.
+    pub fn fee(&self) -> Amount {
+        let fee = self.priority_fee.unwrap_or_default();
+        self.max_fee.map_or(fee, |max_fee| fee.min(max_fee))
+    }
+
     /// Verify and increment the nonce of the operation based
     /// on the nonce policy
     pub(crate) fn verify_and_increment_nonce<S: NoncePolicy>(
@@ -141,39 +254,70 @@ impl Operation {
             public_key,
             nonce,
             content,
+            max_fee,
+            priority_fee,
         } = self;
-        match content {
+        let mut preimage = match content {
             Content::DeployFunction(DeployFunction {
                 function_code,
                 account_credit,
-            }) => Blake2b::from(
-                format!("{public_key}{nonce}{function_code}{account_credit}").as_bytes(),
-            ),
+                interface,
+            }) => {
+                // Deployments without an interface keep their hash
+                let interface = interface
+                    .as_ref()
+                    .map(FunctionInterface::preimage)
+                    .unwrap_or_default();
+                format!("{public_key}{nonce}{function_code}{account_credit}{interface}")
+            }
             Content::RunFunction(RunFunction {
                 uri,
                 method,
                 headers,
                 body,
                 ..
-            }) => Blake2b::from(
-                format!("{public_key}{nonce}{uri}{method}{headers:?}{body:?}").as_bytes(),
-            ),
+            }) => format!("{public_key}{nonce}{uri}{method}{headers:?}{body:?}"),
             Content::RevealLargePayload(RevealLargePayload {
                 root_hash,
                 reveal_type,
                 original_op_hash,
-            }) => Blake2b::from(
-                format!("{public_key}{nonce}{root_hash}{reveal_type}{original_op_hash}",)
-                    .as_bytes(),
-            ),
+            }) => {
+                format!("{public_key}{nonce}{root_hash}{reveal_type}{original_op_hash}")
+            }
+            Content::UpgradeKernel(UpgradeKernel {
+                root_hash,
+                activation_level,
+            }) => format!("{public_key}{nonce}{root_hash}{activation_level}"),
+            Content::SetLogLevel(SetLogLevel { level }) => {
+                format!("{public_key}{nonce}{level}")
+            }
+            Content::SetReceiptRetention(SetReceiptRetention { levels, pinned }) => {
+                let pinned = pinned.iter().map(ToString::to_string).collect::<String>();
+                format!("{public_key}{nonce}{levels:?}{pinned}")
+            }
+            Content::Blueprint(Blueprint { operations }) => {
+                let hashes = operations
+                    .iter()
+                    .map(|op| op.hash().to_string())
+                    .collect::<String>();
+                format!("{public_key}{nonce}{hashes}")
+            }
             #[cfg(feature = "v2_runtime")]
             Content::OracleResponse(OracleResponse {
                 request_id,
                 response,
-            }) => Blake2b::from(
-                format!("{}{}{}{:?}", public_key, nonce, request_id, response).as_bytes(),
-            ),
+            }) => format!("{}{}{}{:?}", public_key, nonce, request_id, response),
+            #[cfg(feature = "v2_runtime")]
+            Content::RotateOracleKey(RotateOracleKey {
+                public_key: new_public_key,
+                overlap,
+            }) => format!("{public_key}{nonce}{new_public_key}{overlap}"),
+        };
+        // Operations declaring no fee keep their hash
+        if self.declares_fee() {
+            preimage.push_str(&format!("{max_fee:?}{priority_fee:?}"));
         }
+        Blake2b::from(preimage.as_bytes())
     }
 }
 
@@ -184,6 +328,9 @@ pub struct DeployFunction {
     pub function_code: String,
     /// Amount of tez to credit to the smart function account, debited from the sender
     pub account_credit: Amount,
+    /// Interface description of the smart function
+    #[serde(default)]
+    pub interface: Option<FunctionInterface>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, ToSchema)]
@@ -248,6 +395,97 @@ pub struct RevealLargePayload {
     pub original_op_hash: OperationHash,
 }
 
+#[derive(
+    Debug, PartialEq, Eq, Clone, ToSchema, Serialize, Deserialize, Encode, Decode,
+)]
+#[schema(
+    description = "An operation to upgrade the kernel, signed by the injector. \
+            The new kernel is revealed and installed at the first level at or after the activation level."
+)]
+#[serde(rename_all = "camelCase")]
+pub struct UpgradeKernel {
+    /// The root hash of the preimages of the new kernel
+    #[schema(value_type = String)]
+    #[bincode(with_serde)]
+    pub root_hash: PreimageHash,
+    /// The L1 level from which the new kernel is installed
+    pub activation_level: u32,
+}
+
+/// Verbosity of the kernel debug log. Every level includes the levels before it.
+#[derive(
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Clone,
+    Copy,
+    ToSchema,
+    Serialize,
+    Deserialize,
+    Encode,
+    Decode,
+    Display,
+)]
+pub enum KernelLogLevel {
+    /// Failures of the kernel
+    Error,
+    /// Rare events such as kernel upgrades
+    Info,
+    /// Inbox messages and their outcome
+    #[default]
+    Debug,
+    /// Full dumps of operations and receipts
+    Trace,
+}
+
+#[derive(
+    Debug, PartialEq, Eq, Clone, ToSchema, Serialize, Deserialize, Encode, Decode,
+)]
+#[schema(
+    description = "An operation to set the verbosity of the kernel debug log, \
+            signed by the injector."
+)]
+#[serde(rename_all = "camelCase")]
+pub struct SetLogLevel {
+    /// The most verbose level of messages written to the debug log
+    pub level: KernelLogLevel,
+}
+
+#[derive(
+    Debug, PartialEq, Eq, Clone, ToSchema, Serialize, Deserialize, Encode, Decode,
+)]
+#[schema(
+    description = "An operation to set how long the kernel keeps receipts, signed by the \
+            injector. The receipts written while a retention window is set are removed \
+            once they are `levels` levels old, except for the ones of the pinned operations."
+)]
+#[serde(rename_all = "camelCase")]
+pub struct SetReceiptRetention {
+    /// Number of L1 levels receipts are kept for, `null` to keep them forever
+    pub levels: Option<u32>,
+    /// Hashes of the operations whose receipts are never removed
+    #[serde(default)]
+    #[bincode(with_serde)]
+    pub pinned: Vec<OperationHash>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, ToSchema, Serialize, Deserialize)]
+#[schema(
+    description = "A batch of operations executed by the sequencer, published to the \
+            rollup inbox and signed by the injector. The operations are executed in order, \
+            each with its own receipt. Blueprints are not subject to the nonce of the \
+            injector, since their operations cannot be executed twice."
+)]
+#[serde(rename_all = "camelCase")]
+pub struct Blueprint {
+    /// The signed operations, in the order the sequencer executed them
+    #[schema(value_type = Vec<Object>)]
+    pub operations: Vec<SignedOperation>,
+}
+
 #[cfg(feature = "v2_runtime")]
 #[derive(Debug, PartialEq, Eq, Clone, ToSchema, Serialize, Deserialize)]
 #[schema(description = "Response to an OracleRequest sent by the enshrined Oracle node")]
@@ -260,6 +498,21 @@ pub struct OracleResponse {
     pub response: Response,
 }
 
+#[cfg(feature = "v2_runtime")]
+#[derive(Debug, PartialEq, Eq, Clone, ToSchema, Serialize, Deserialize)]
+#[schema(
+    description = "An operation to rotate the key of the enshrined Oracle node, signed \
+            with its current key. Responses signed with the current key are still accepted \
+            for `overlap` levels."
+)]
+#[serde(rename_all = "camelCase")]
+pub struct RotateOracleKey {
+    /// The key the Oracle node signs its responses with from now on
+    pub public_key: PublicKey,
+    /// Number of levels during which the current key is still accepted
+    pub overlap: BlockLevel,
+}
+
 #[derive(
     Debug, From, Serialize, Deserialize, PartialEq, Eq, Clone, ToSchema, Encode, Decode,
 )]
@@ -274,6 +527,17 @@ pub enum Content {
     #[cfg(feature = "v2_runtime")]
     #[schema(title = "OracleResponse")]
     OracleResponse(#[bincode(with_serde)] OracleResponse),
+    #[cfg(feature = "v2_runtime")]
+    #[schema(title = "RotateOracleKey")]
+    RotateOracleKey(#[bincode(with_serde)] RotateOracleKey),
+    #[schema(title = "UpgradeKernel")]
+    UpgradeKernel(#[bincode(with_serde)] UpgradeKernel),
+    #[schema(title = "SetLogLevel")]
+    SetLogLevel(#[bincode(with_serde)] SetLogLevel),
+    #[schema(title = "Blueprint")]
+    Blueprint(#[bincode(with_serde)] Blueprint),
+    #[schema(title = "SetReceiptRetention")]
+    SetReceiptRetention(#[bincode(with_serde)] SetReceiptRetention),
 }
 
 impl Content {
@@ -364,6 +628,14 @@ impl SignedOperation {
     pub fn is_simulation(&self) -> bool {
         self.simulation_request.is_some()
     }
+
+    /// Marks the operation as simulated, so that executing it does not write to
+    /// durable storage
+    #[cfg(feature = "simulation")]
+    pub fn with_simulation_request(mut self, request: SimulationRequest) -> Self {
+        self.simulation_request = Some(request);
+        self
+    }
 }
 
 impl From<SignedOperation> for Operation {
@@ -502,9 +774,12 @@ pub mod openapi {
 
 #[cfg(test)]
 mod test {
-    use super::{Content, DeployFunction, RevealLargePayload, RevealType, RunFunction};
-    use super::{Operation, SignedOperation};
+    use super::{
+        Blueprint, Content, DeployFunction, RevealLargePayload, RevealType, RunFunction,
+    };
+    use super::{Operation, SignedOperation, UnversionedOperation};
     use crate::context::account::{Account, Address, Nonce};
+    use crate::context::interface::FunctionInterface;
     use crate::operation::internal::{FaDeposit, InboxId};
     #[cfg(feature = "simulation")]
     use crate::operation::TransactionNoncePolicy;
@@ -549,6 +824,7 @@ mod test {
         Content::DeployFunction(DeployFunction {
             function_code,
             account_credit,
+            interface: None,
         })
     }
 
@@ -593,7 +869,8 @@ mod test {
             json!({
                 "_type":"DeployFunction",
                 "accountCredit":100000,
-                "functionCode":"export default () => new Response(\"hello world!\");"
+                "functionCode":"export default () => new Response(\"hello world!\");",
+                "interface":null
             })
         );
         let decoded = serde_json::from_value::<Content>(json).unwrap();
@@ -608,6 +885,94 @@ mod test {
         assert_eq!(deploy_function, bin_decoded);
     }
 
+    #[test]
+    fn test_deploy_function_without_interface_bin_round_trip() {
+        let deploy_function = Content::DeployFunction(DeployFunction {
+            function_code: "export default () => new Response();".to_string(),
+            account_credit: 0,
+            interface: None,
+        });
+        let binary = deploy_function.encode().unwrap();
+        let bin_decoded = Content::decode(binary.as_slice()).unwrap();
+        assert_eq!(deploy_function, bin_decoded);
+
+        // deploys without an interface are read from JSON without the field
+        let json = json!({
+            "_type": "DeployFunction",
+            "functionCode": "export default () => new Response();",
+            "accountCredit": 0
+        });
+        assert_eq!(
+            serde_json::from_value::<Content>(json).unwrap(),
+            deploy_function
+        );
+    }
+
+    #[test]
+    fn test_deploy_function_with_interface_round_trip() {
+        let interface = serde_json::from_value::<FunctionInterface>(json!({
+            "routes": [{
+                "method": "POST",
+                "path": "/greet",
+                "request": { "type": "object", "properties": { "name": { "type": "string" } } }
+            }]
+        }))
+        .unwrap();
+        let Content::DeployFunction(deploy) = deploy_function_content() else {
+            unreachable!()
+        };
+        let deploy_function = Content::DeployFunction(DeployFunction {
+            interface: Some(interface),
+            ..deploy
+        });
+        let binary = deploy_function.encode().unwrap();
+        let bin_decoded = Content::decode(binary.as_slice()).unwrap();
+        assert_eq!(deploy_function, bin_decoded);
+        let json = serde_json::to_value(&deploy_function).unwrap();
+        assert_eq!(
+            json["interface"]["routes"][0]["request"]["type"],
+            json!("object")
+        );
+        assert_eq!(
+            serde_json::from_value::<Content>(json).unwrap(),
+            deploy_function
+        );
+
+        // the interface is signed along with the code
+        let operation = |content| Operation {
+            public_key: jstz_mock::pk1(),
+            nonce: Nonce(0),
+            content,
+            max_fee: None,
+            priority_fee: None,
+        };
+        assert_ne!(
+            operation(deploy_function).hash(),
+            operation(deploy_function_content()).hash()
+        );
+    }
+
+    #[test]
+    fn test_operation_bin_round_trip() {
+        let mut operation = dummy_operation(jstz_mock::pk1(), Nonce(3));
+        operation.max_fee = Some(10);
+        operation.priority_fee = Some(2);
+        let binary = operation.encode().unwrap();
+        assert_eq!(Operation::decode(binary.as_slice()).unwrap(), operation);
+
+        // operations encoded before fees were introduced decode without fees
+        let unversioned = UnversionedOperation {
+            public_key: jstz_mock::pk1(),
+            nonce: Nonce(3),
+            content: dummy_content(),
+        };
+        let binary = unversioned.encode().unwrap();
+        assert_eq!(
+            Operation::decode(binary.as_slice()).unwrap(),
+            dummy_operation(jstz_mock::pk1(), Nonce(3))
+        );
+    }
+
     fn mock_hrt_with_nonces<'a>(
         nonces: impl IntoIterator<Item = &'a (PublicKeyHash, Nonce)>,
     ) -> JstzMockHost {
@@ -625,9 +990,60 @@ mod test {
             public_key,
             nonce,
             content: dummy_content(),
+            max_fee: None,
+            priority_fee: None,
         }
     }
 
+    #[test]
+    fn fees() {
+        let operation = dummy_operation(jstz_mock::pk1(), Nonce(0));
+        assert!(!operation.declares_fee());
+        assert_eq!(operation.fee(), 0);
+
+        let with_fees = |max_fee, priority_fee| Operation {
+            max_fee,
+            priority_fee,
+            ..operation.clone()
+        };
+        assert_eq!(with_fees(None, Some(10)).fee(), 10);
+        assert_eq!(with_fees(Some(5), Some(10)).fee(), 5);
+        assert_eq!(with_fees(Some(5), None).fee(), 0);
+        assert!(with_fees(Some(5), None).declares_fee());
+
+        // fees are signed along with the operation
+        assert_ne!(with_fees(None, Some(10)).hash(), operation.hash());
+        assert_ne!(
+            with_fees(None, Some(10)).hash(),
+            with_fees(Some(10), None).hash()
+        );
+        assert_eq!(with_fees(None, None).hash(), operation.hash());
+
+        let json = serde_json::to_value(with_fees(Some(5), Some(10))).unwrap();
+        assert_eq!(json["maxFee"], 5);
+        assert_eq!(json["priorityFee"], 10);
+        assert_eq!(
+            serde_json::to_value(&operation).unwrap()["maxFee"],
+            serde_json::Value::Null
+        );
+    }
+
+    #[test]
+    fn test_blueprint_without_fees_bin_round_trip() {
+        let operations = [Nonce(0), Nonce(1)]
+            .into_iter()
+            .map(|nonce| {
+                let operation = dummy_operation(jstz_mock::pk1(), nonce);
+                let signature = jstz_mock::sk1().sign(operation.hash()).unwrap();
+                SignedOperation::new(signature, operation)
+            })
+            .collect();
+        let blueprint = Content::Blueprint(Blueprint { operations });
+        let binary = blueprint.encode().unwrap();
+        let bin_decoded = Content::decode(binary.as_slice()).unwrap();
+        assert_eq!(blueprint, bin_decoded);
+    }
+
     #[test]
     fn test_verify_nonce_checks_and_increments_nonce() {
         let nonce = Nonce(42);
@@ -808,6 +1224,8 @@ mod test {
                     body: vec![].into(),
                 },
             }),
+            max_fee: None,
+            priority_fee: None,
         };
         let signature = alice_sk.sign(op.hash()).unwrap();
         let signed_op = SignedOperation::new(signature, op);
@@ -946,7 +1364,9 @@ mod simulation_tests {
                     headers: HeaderMap::new(),
                     body: HttpBody(None),
                     gas_limit: 1000,
-                }.into()
+                }.into(),
+                max_fee: None,
+                priority_fee: None,
             },
             verifier: None,
             simulation_request: Some(SimulationRequest::new(10))
@@ -1,13 +1,21 @@
-#[cfg(feature = "v2_runtime")]
-use crate::runtime::v2::oracle::RequestId;
 use crate::{
-    context::account::Address,
+    context::account::{Address, Amount},
     executor::{fa_deposit::FaDepositReceipt, fa_withdraw::FaWithdrawReceipt},
-    operation::OperationHash,
+    operation::{KernelLogLevel, OperationHash},
     HttpBody, Result,
 };
-use bincode::{Decode, Encode};
+#[cfg(feature = "v2_runtime")]
+use crate::{runtime::v2::oracle::RequestId, BlockLevel};
+use bincode::{
+    de::Decoder,
+    enc::Encoder,
+    error::{DecodeError, EncodeError},
+    Decode, Encode,
+};
 use http::{HeaderMap, StatusCode};
+use jstz_core::{reveal_data::PreimageHash, Versioned, VersionedValue, UNVERSIONED};
+#[cfg(feature = "v2_runtime")]
+use jstz_crypto::public_key::PublicKey;
 use jstz_crypto::smart_function_hash::SmartFunctionHash;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
@@ -30,18 +38,87 @@ impl From<Result<ReceiptContent>> for ReceiptResult {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, Encode, Decode)]
+/// Receipts are persisted as a [`VersionedValue`], so that receipts persisted by
+/// earlier kernels can still be decoded
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Receipt {
-    #[bincode(with_serde)]
     hash: OperationHash,
     pub result: ReceiptResult,
+    /// Fee, in mutez, charged to the source of the operation. Not set for operations
+    /// declaring no fee.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee: Option<Amount>,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+struct ReceiptLayout {
+    #[bincode(with_serde)]
+    hash: OperationHash,
+    result: ReceiptResult,
+    fee: Option<Amount>,
+}
+
+impl Versioned for ReceiptLayout {
+    const VERSION: u8 = 1;
+
+    fn migrate<D: Decoder>(
+        version: u8,
+        decoder: &mut D,
+    ) -> std::result::Result<Self, DecodeError> {
+        match version {
+            UNVERSIONED => {
+                let receipt = <UnversionedReceipt as Decode>::decode(decoder)?;
+                Ok(Self {
+                    hash: receipt.hash,
+                    result: receipt.result,
+                    fee: None,
+                })
+            }
+            _ => Err(DecodeError::OtherString(format!(
+                "unknown receipt version {version}"
+            ))),
+        }
+    }
 }
 
+/// Layout of the receipts persisted before operations paid fees
+#[derive(Debug, Clone, Encode, Decode)]
+struct UnversionedReceipt {
+    #[bincode(with_serde)]
+    hash: OperationHash,
+    result: ReceiptResult,
+}
+
+impl Encode for Receipt {
+    fn encode<E: Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> std::result::Result<(), EncodeError> {
+        let layout = ReceiptLayout {
+            hash: self.hash.clone(),
+            result: self.result.clone(),
+            fee: self.fee,
+        };
+        Encode::encode(&VersionedValue(layout), encoder)
+    }
+}
+
+impl Decode for Receipt {
+    fn decode<D: Decoder>(decoder: &mut D) -> std::result::Result<Self, DecodeError> {
+        let ReceiptLayout { hash, result, fee } =
+            <VersionedValue<ReceiptLayout> as Decode>::decode(decoder)?.0;
+        Ok(Self { hash, result, fee })
+    }
+}
+
+bincode::impl_borrow_decode!(Receipt);
+
 impl Receipt {
     pub fn new(hash: OperationHash, inner: Result<ReceiptContent>) -> Self {
         Self {
             hash,
             result: inner.into(),
+            fee: None,
         }
     }
 
@@ -83,6 +160,44 @@ pub struct OracleResponseReceipt {
     pub request_id: RequestId,
 }
 
+#[cfg(feature = "v2_runtime")]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, Encode, Decode)]
+#[serde(rename_all = "camelCase")]
+pub struct RotateOracleKeyReceipt {
+    #[bincode(with_serde)]
+    pub public_key: PublicKey,
+    /// Last level at which the previous key is accepted
+    pub retiring_until: BlockLevel,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UpgradeKernelReceipt {
+    #[schema(value_type = String)]
+    pub root_hash: PreimageHash,
+    pub activation_level: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, Encode, Decode)]
+#[serde(rename_all = "camelCase")]
+pub struct SetLogLevelReceipt {
+    pub level: KernelLogLevel,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SetReceiptRetentionReceipt {
+    pub levels: Option<u32>,
+    pub pinned: Vec<OperationHash>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BlueprintReceipt {
+    /// Hashes of the executed operations, whose receipts are recorded separately
+    pub operations: Vec<OperationHash>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, Encode, Decode)]
 #[serde(tag = "_type")]
 pub enum ReceiptContent {
@@ -99,4 +214,44 @@ pub enum ReceiptContent {
     #[cfg(feature = "v2_runtime")]
     #[schema(title = "OracleResponse")]
     OracleResponse(OracleResponseReceipt),
+    #[cfg(feature = "v2_runtime")]
+    #[schema(title = "RotateOracleKey")]
+    RotateOracleKey(RotateOracleKeyReceipt),
+    #[schema(title = "UpgradeKernel")]
+    UpgradeKernel(#[bincode(with_serde)] UpgradeKernelReceipt),
+    #[schema(title = "SetLogLevel")]
+    SetLogLevel(SetLogLevelReceipt),
+    #[schema(title = "Blueprint")]
+    Blueprint(#[bincode(with_serde)] BlueprintReceipt),
+    #[schema(title = "SetReceiptRetention")]
+    SetReceiptRetention(#[bincode(with_serde)] SetReceiptRetentionReceipt),
+}
+
+#[cfg(test)]
+mod tests {
+    use jstz_core::BinEncodable;
+    use jstz_crypto::hash::Blake2b;
+
+    use super::{Receipt, ReceiptResult, UnversionedReceipt};
+
+    #[test]
+    fn decodes_unversioned_receipt() {
+        let hash = Blake2b::from(b"op".as_slice());
+        let legacy = UnversionedReceipt {
+            hash: hash.clone(),
+            result: ReceiptResult::Failed("boom".to_string()),
+        };
+        let bytes = BinEncodable::encode(&legacy).unwrap();
+        let receipt = <Receipt as BinEncodable>::decode(&bytes).unwrap();
+        assert_eq!(receipt.hash(), &hash);
+        assert!(matches!(receipt.result, ReceiptResult::Failed(e) if e == "boom"));
+        assert_eq!(receipt.fee, None);
+
+        let mut receipt = Receipt::new(hash.clone(), Err(crate::Error::InvalidNonce));
+        receipt.fee = Some(7);
+        let bytes = BinEncodable::encode(&receipt).unwrap();
+        let decoded = <Receipt as BinEncodable>::decode(&bytes).unwrap();
+        assert_eq!(decoded.hash(), &hash);
+        assert_eq!(decoded.fee, Some(7));
+    }
 }
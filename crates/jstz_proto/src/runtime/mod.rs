@@ -1,12 +1,27 @@
 #[cfg(not(feature = "v2_runtime"))]
 pub mod v1;
 #[cfg(not(feature = "v2_runtime"))]
-pub use v1::{run_toplevel_fetch, Kv, KvValue, LogRecord, ParsedCode, LOG_PREFIX};
+pub use v1::{
+    run_toplevel_fetch, Kv, KvValue, LogLevel, LogRecord, ParsedCode, LOG_PREFIX,
+};
 
 #[cfg(feature = "v2_runtime")]
 pub mod v2;
 #[cfg(feature = "v2_runtime")]
 pub use v2::{
     fetch::fetch_handler::ProtoFetchHandler, protocol_context::*, run_toplevel_fetch, Kv,
-    KvValue, LogRecord, ParsedCode, LOG_PREFIX, SNAPSHOT,
+    KvValue, LogLevel, LogRecord, ParsedCode, LOG_PREFIX, SNAPSHOT,
 };
+
+#[cfg(feature = "v2_runtime")]
+pub use jstz_runtime::budget::scope as with_execution_budget;
+
+/// Runs `fut` without a budget on its host calls, the boa runtime is only bounded
+/// by gas
+#[cfg(not(feature = "v2_runtime"))]
+pub async fn with_execution_budget<F: std::future::Future>(
+    _host_calls: u64,
+    fut: F,
+) -> F::Output {
+    fut.await
+}
@@ -65,6 +65,7 @@ impl SmartFunction {
             DeployFunction {
                 function_code,
                 account_credit: initial_balance,
+                interface: None,
             },
         )?;
 
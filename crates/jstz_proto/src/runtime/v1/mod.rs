@@ -11,11 +11,11 @@ pub use script::ParsedCode;
 
 use jstz_api::http::response::Response;
 use jstz_core::{host::HostRuntime, kv::Transaction, runtime};
-use tezos_smart_rollup::prelude::debug_msg;
 
 use crate::{
     context::account::Addressable,
     error::Result,
+    executor::log_level::log_trace,
     operation::{OperationHash, RunFunction},
     receipt::RunFunctionReceipt,
     Error,
@@ -46,7 +46,7 @@ pub async fn run_toplevel_fetch(
         }
     })?;
 
-    debug_msg!(hrt, "🚀 Smart function executed successfully with value: {:?} (in {:?} instructions)\n", result, gas_limit - rt.instructions_remaining());
+    log_trace!(hrt, "🚀 Smart function executed successfully with value: {:?} (in {:?} instructions)\n", result, gas_limit - rt.instructions_remaining());
 
     let response = Response::try_from_js(&result)?;
     let (http_parts, body) = Response::to_http_response(&response).into_parts();
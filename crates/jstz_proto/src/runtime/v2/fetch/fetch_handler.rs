@@ -133,6 +133,7 @@ fn fetch(
     headers: Vec<(ByteString, ByteString)>,
     body: Option<Body>,
 ) -> Result<FetchReturn> {
+    jstz_runtime::budget::charge();
     let url = Url::try_from(url.as_str())?;
     let (tx, from, host, limiter) = {
         let rt_context = state.borrow_mut::<RuntimeContext>();
@@ -15,6 +15,7 @@ fn op_self_address(state: &mut OpState) -> String {
 #[op2(fast)]
 #[number]
 fn op_balance(state: &mut OpState, #[string] address: String) -> Result<u64> {
+    jstz_runtime::budget::charge();
     let RuntimeContext { host, tx, .. } = state.borrow_mut::<RuntimeContext>();
     let address = Address::from_base58(&address)?;
     Ok(Account::balance(host, tx, &address)?)
@@ -26,6 +27,7 @@ fn op_transfer(
     #[string] dest_address: String,
     #[number] amount: u64,
 ) -> Result<()> {
+    jstz_runtime::budget::charge();
     let RuntimeContext {
         host, tx, address, ..
     } = state.borrow_mut::<RuntimeContext>();
@@ -17,7 +17,7 @@ use jstz_core::{
 use jstz_runtime::runtime::Limiter;
 use url::Url;
 pub mod fetch;
-pub use jstz_core::log_record::{LogRecord, LOG_PREFIX};
+pub use jstz_core::log_record::{LogLevel, LogRecord, LOG_PREFIX};
 pub use jstz_runtime::{Kv, KvValue};
 mod parsed_code;
 pub use parsed_code::ParsedCode;
@@ -34,7 +34,11 @@ pub async fn run_toplevel_fetch(
     run_operation: RunFunction,
     operation_hash: OperationHash,
 ) -> Result<RunFunctionReceipt, crate::Error> {
-    Ok(run(hrt, tx, source_address, run_operation, operation_hash).await?)
+    let receipt = run(hrt, tx, source_address, run_operation, operation_hash).await;
+    if jstz_runtime::budget::exhausted() {
+        return Err(crate::Error::ExecutionBudgetExceeded);
+    }
+    Ok(receipt?)
 }
 
 async fn run(
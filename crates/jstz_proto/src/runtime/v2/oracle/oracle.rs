@@ -1,4 +1,5 @@
 #![allow(unused)]
+use bincode::{Decode, Encode};
 use deno_core::ByteString;
 use futures::{
     channel::oneshot::{channel, Receiver, Sender},
@@ -10,6 +11,7 @@ use jstz_core::{
     kv::{Storage, Transaction},
 };
 use jstz_crypto::public_key::PublicKey;
+use serde::{Deserialize, Serialize};
 use std::{collections::BTreeMap, fmt::Display, ops::Deref};
 use tezos_smart_rollup::storage::path::{concat, OwnedPath};
 
@@ -20,7 +22,7 @@ use crate::{
         fetch::http::{Request, Response},
         protocol_context::PROTOCOL_CONTEXT,
     },
-    storage::{ORACLE_PUBLIC_KEY_PATH, ORACLE_REQUESTS_PATH},
+    storage::{ORACLE_PUBLIC_KEY_PATH, ORACLE_REQUESTS_PATH, ORACLE_RETIRING_KEY_PATH},
     BlockLevel, Gas,
 };
 
@@ -30,10 +32,22 @@ static X_JSTZ_ORACLE_GAS_LIMIT: std::sync::LazyLock<ByteString> =
 // FIXME(https://linear.app/tezos/issue/JSTZ-744/make-ttl-configurable)
 const ORACLE_REQUEST_TTL: u64 = 20;
 
+/// Key the oracle signed with before rotating to the current key. Responses
+/// signed with it are accepted up to and including level `until`, so that
+/// responses to requests in flight during the rotation are not rejected.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct RetiringOracleKey {
+    #[bincode(with_serde)]
+    pub public_key: PublicKey,
+    pub until: BlockLevel,
+}
+
 #[derive(Debug)]
 pub struct Oracle {
     /// Oracle's public key
     public_key: PublicKey,
+    /// Oracle's previous public key, if it is being rotated out
+    retiring_key: Option<RetiringOracleKey>,
     /// Holds cached metadata that is checked often
     ///
     /// Notes on timeout: The relationship between request id and timeout is such that
@@ -52,13 +66,18 @@ impl Oracle {
     /// Instantiates the oracle
     ///
     /// [`ORACLE_PUBLIC_KEY_PATH`] must be set before this function is called. This function
-    /// should only be called once throughout the lifetime of Jstz
+    /// should only be called once throughout the lifetime of Jstz. The key being rotated
+    /// out, if any, is read from [`ORACLE_RETIRING_KEY_PATH`].
     pub fn new(rt: &impl HostRuntime, config: Option<OracleConfig>) -> Result<Self> {
         let public_key = Storage::get::<PublicKey>(rt, &ORACLE_PUBLIC_KEY_PATH)
             .map_err(|e| OracleError::V1Error(e.to_string()))?
             .ok_or(OracleError::PublicKeyNotFound)?;
+        let retiring_key =
+            Storage::get::<RetiringOracleKey>(rt, &ORACLE_RETIRING_KEY_PATH)
+                .map_err(|e| OracleError::V1Error(e.to_string()))?;
         Ok(Self {
             public_key,
+            retiring_key,
             active_requests: Default::default(),
             next_request_id: 0,
             config: config.unwrap_or_default(),
@@ -191,6 +210,41 @@ impl Oracle {
     pub fn public_key(&self) -> &PublicKey {
         &self.public_key
     }
+
+    /// Rotates the oracle to `public_key`. Responses signed with the current key are
+    /// still accepted up to and including level `until`.
+    pub fn rotate(
+        &mut self,
+        tx: &mut Transaction,
+        public_key: PublicKey,
+        until: BlockLevel,
+    ) -> Result<()> {
+        let retiring_key = RetiringOracleKey {
+            public_key: self.public_key.clone(),
+            until,
+        };
+        tx.insert(OwnedPath::from(&ORACLE_PUBLIC_KEY_PATH), public_key.clone())
+            .and_then(|_| {
+                tx.insert(
+                    OwnedPath::from(&ORACLE_RETIRING_KEY_PATH),
+                    retiring_key.clone(),
+                )
+            })
+            .map_err(|e| OracleError::V1Error(e.to_string()))?;
+        self.public_key = public_key;
+        self.retiring_key = Some(retiring_key);
+        Ok(())
+    }
+
+    /// Whether responses signed with `public_key` are accepted at `level`. Besides the
+    /// current key, the retiring key is accepted until the end of its overlap window.
+    pub fn accepts(&self, public_key: &PublicKey, level: BlockLevel) -> bool {
+        public_key == &self.public_key
+            || self
+                .retiring_key
+                .as_ref()
+                .is_some_and(|key| &key.public_key == public_key && level <= key.until)
+    }
 }
 
 #[derive(Debug, Default)]
@@ -337,6 +391,71 @@ mod test {
         assert!(oracle.active_requests.is_empty());
     }
 
+    #[test]
+    fn oracle_accepts_retiring_key_until_end_of_overlap() {
+        let pk = PublicKey::from_base58(
+            "edpkuBknW28nW72KG6RoHtYW7p12T6GKc7nAbwYX5m8Wd9sDVC9yav",
+        )
+        .unwrap();
+        let retiring_pk = PublicKey::from_base58(
+            "edpkukK9ecWxib28zi52nvbXTdsYt8rYcvmt5bdH8KjipWXm8sH3Qi",
+        )
+        .unwrap();
+        let other_pk = PublicKey::from_base58(
+            "edpkuSLWfVU1Vq7Jg9FucPyKmma6otcMHac9zG4oU1KMHSTBpJuGQ2",
+        )
+        .unwrap();
+        let mut host = setup_host_with_pk(&pk, None);
+
+        let oracle = Oracle::new(&host, None).unwrap();
+        assert!(oracle.accepts(&pk, 0));
+        assert!(!oracle.accepts(&retiring_pk, 0));
+
+        Storage::insert(
+            &mut host,
+            &ORACLE_RETIRING_KEY_PATH,
+            &RetiringOracleKey {
+                public_key: retiring_pk.clone(),
+                until: 10,
+            },
+        )
+        .unwrap();
+        let oracle = Oracle::new(&host, None).unwrap();
+        assert!(oracle.accepts(&pk, 11));
+        assert!(oracle.accepts(&retiring_pk, 10));
+        assert!(!oracle.accepts(&retiring_pk, 11));
+        assert!(!oracle.accepts(&other_pk, 0));
+    }
+
+    #[test]
+    fn oracle_rotate_persists_keys() {
+        let pk = PublicKey::from_base58(
+            "edpkuBknW28nW72KG6RoHtYW7p12T6GKc7nAbwYX5m8Wd9sDVC9yav",
+        )
+        .unwrap();
+        let next_pk = PublicKey::from_base58(
+            "edpkukK9ecWxib28zi52nvbXTdsYt8rYcvmt5bdH8KjipWXm8sH3Qi",
+        )
+        .unwrap();
+        let mut host = setup_host_with_pk(&pk, None);
+        let mut oracle = Oracle::new(&host, None).unwrap();
+
+        let mut tx = Transaction::default();
+        tx.begin();
+        oracle.rotate(&mut tx, next_pk.clone(), 10).unwrap();
+        tx.commit(&mut host).unwrap();
+        assert!(oracle.accepts(&next_pk, 11));
+        assert!(oracle.accepts(&pk, 10));
+        assert!(!oracle.accepts(&pk, 11));
+
+        // The rotation survives a restart of the kernel
+        ProtocolContext::init_global(&mut host, 0).unwrap();
+        let oracle = Oracle::new(&host, None).unwrap();
+        assert_eq!(oracle.public_key(), &next_pk);
+        assert!(oracle.accepts(&pk, 10));
+        assert!(!oracle.accepts(&pk, 11));
+    }
+
     #[test]
     fn oracle_new_missing_public_key() {
         let host = MockHost::default();
@@ -45,7 +45,8 @@ impl ProtocolContext {
     ) -> Result<(), ProtocolContextError> {
         // FIXME(https://linear.app/tezos/issue/JSTZ-746/make-oracle-pk-configurable)
         // Make configurable
-        // Hardcode oracle value pk to injector pk for now
+        // Hardcode oracle value pk to injector pk for now. A key set by a rotation
+        // is kept.
         let oracle_key = if cfg!(test) {
             // TODO: Hard code in tests. Remove later by always Initializing store
             // with an injector
@@ -59,7 +60,9 @@ impl ProtocolContext {
                 .flatten()
                 .expect("Injector not found")
         };
-        Storage::insert(rt, &ORACLE_PUBLIC_KEY_PATH, &oracle_key).unwrap();
+        if !Storage::contains_key(rt, &ORACLE_PUBLIC_KEY_PATH).unwrap_or_default() {
+            Storage::insert(rt, &ORACLE_PUBLIC_KEY_PATH, &oracle_key).unwrap();
+        }
         let current_level = Arc::new(Mutex::new(current_level));
         let oracle = Oracle::new(rt, None)?;
         PROTOCOL_CONTEXT.get_or_init(|| ProtocolContext {
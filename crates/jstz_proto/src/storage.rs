@@ -1,4 +1,15 @@
 use tezos_smart_rollup::storage::path::RefPath;
 
 pub const ORACLE_PUBLIC_KEY_PATH: RefPath = RefPath::assert_from(b"/oracle/public_key");
+pub const ORACLE_RETIRING_KEY_PATH: RefPath =
+    RefPath::assert_from(b"/oracle/retiring_key");
 pub const ORACLE_REQUESTS_PATH: RefPath = RefPath::assert_from(b"/oracle/requests");
+pub const KERNEL_UPGRADE_PATH: RefPath =
+    RefPath::assert_from(b"/kernel_upgrade/scheduled");
+pub const LOG_LEVEL_PATH: RefPath = RefPath::assert_from(b"/kernel_config/log_level");
+pub const RECEIPT_RETENTION_PATH: RefPath =
+    RefPath::assert_from(b"/kernel_config/receipt_retention");
+pub const RECEIPT_INDEX_PATH: RefPath = RefPath::assert_from(b"/jstz_receipt_index");
+pub const RECEIPT_INDEX_FLOOR_PATH: RefPath =
+    RefPath::assert_from(b"/jstz_receipt_index_floor");
+pub const LEVEL_PATH: RefPath = RefPath::assert_from(b"/jstz_level");
@@ -0,0 +1,121 @@
+//! Deterministic bound on the host calls of runtimes.
+//!
+//! Gas bounds the instructions a smart function executes, but not its host calls:
+//! host calls that are cheap in gas can still be slow. Every [`JstzRuntime`] created
+//! while a future runs in [`scope`] draws from the budget of the scope on each host
+//! call, including the runtimes of nested smart function calls, and is terminated
+//! once the budget is exhausted. Unlike a wall-clock bound, the budget is exhausted
+//! at the same point of the execution on every node.
+//!
+//! [`JstzRuntime`]: crate::JstzRuntime
+
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use deno_core::v8::IsolateHandle;
+
+tokio::task_local! {
+    static EXECUTION_BUDGET: Arc<Mutex<Budget>>;
+}
+
+struct Budget {
+    remaining: u64,
+    exhausted: bool,
+    isolates: Vec<IsolateHandle>,
+}
+
+/// Runs `fut`, terminating the runtimes it creates once they made more than
+/// `host_calls` host calls in total
+pub async fn scope<F: Future>(host_calls: u64, fut: F) -> F::Output {
+    let budget = Budget {
+        remaining: host_calls,
+        exhausted: false,
+        isolates: vec![],
+    };
+    EXECUTION_BUDGET
+        .scope(Arc::new(Mutex::new(budget)), fut)
+        .await
+}
+
+/// Returns true if the budget of the current [`scope`] is exhausted
+pub fn exhausted() -> bool {
+    EXECUTION_BUDGET
+        .try_with(|budget| budget.lock().unwrap().exhausted)
+        .unwrap_or(false)
+}
+
+/// Draws a host call from the budget of the current [`scope`], if any, terminating
+/// its runtimes if the budget is exhausted
+pub fn charge() {
+    let _ = EXECUTION_BUDGET.try_with(|budget| {
+        let mut budget = budget.lock().unwrap();
+        match budget.remaining.checked_sub(1) {
+            Some(remaining) => budget.remaining = remaining,
+            None => {
+                budget.exhausted = true;
+                for isolate in &budget.isolates {
+                    isolate.terminate_execution();
+                }
+            }
+        }
+    });
+}
+
+/// Subjects the isolate of a new runtime to the budget of the current [`scope`], if any
+pub(crate) fn register(isolate: IsolateHandle) {
+    let _ = EXECUTION_BUDGET.try_with(|budget| {
+        let mut budget = budget.lock().unwrap();
+        if budget.exhausted {
+            isolate.terminate_execution();
+        }
+        budget.isolates.push(isolate);
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use jstz_utils::test_util::TOKIO;
+
+    use super::{exhausted, scope};
+    use crate::init_test_setup;
+
+    #[test]
+    fn terminates_runtime_when_budget_is_exhausted() {
+        TOKIO.block_on(scope(10, async {
+            init_test_setup! {
+                runtime = runtime;
+            };
+            let code = r#"
+                while (true) {
+                    Kv.contains("key");
+                }
+            "#;
+            assert!(runtime.execute(code).is_err());
+            assert!(exhausted());
+        }));
+        assert!(!exhausted());
+    }
+
+    #[test]
+    fn keeps_runtime_within_budget() {
+        TOKIO.block_on(scope(10, async {
+            init_test_setup! {
+                runtime = runtime;
+            };
+            let calls = runtime
+                .execute_with_result::<u32>(
+                    r#"
+                    for (let i = 0; i < 10; i++) {
+                        Kv.contains("key");
+                    }
+                    10
+                    "#,
+                )
+                .unwrap();
+            assert_eq!(calls, 10);
+            assert!(!exhausted());
+        }));
+    }
+}
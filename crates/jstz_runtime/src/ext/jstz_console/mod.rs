@@ -25,6 +25,7 @@ pub fn op_debug_msg(
     #[string] msg: &str,
     level: u32,
 ) -> Result<(), NotSupported> {
+    crate::budget::charge();
     let proto = op_state.try_borrow_mut::<RuntimeContext>();
     match proto {
         Some(proto) => {
@@ -15,6 +15,7 @@ pub(crate) mod extension {
             op_state: &mut OpState,
             #[string] key: &str,
         ) -> Result<Option<serde_json::Value>> {
+            crate::budget::charge();
             let maybe_proto = op_state.try_borrow_mut::<RuntimeContext>();
             match maybe_proto {
                 Some(RuntimeContext { host, tx, kv, .. }) => {
@@ -33,6 +34,7 @@ pub(crate) mod extension {
             #[string] key: &str,
             #[serde] value: serde_json::Value,
         ) -> Result<()> {
+            crate::budget::charge();
             let maybe_proto = op_state.try_borrow_mut::<RuntimeContext>();
             match maybe_proto {
                 Some(RuntimeContext { tx, kv, .. }) => kv
@@ -45,6 +47,7 @@ pub(crate) mod extension {
         #[fast]
         #[static_method]
         fn delete(op_state: &mut OpState, #[string] key: &str) -> Result<()> {
+            crate::budget::charge();
             let maybe_proto = op_state.try_borrow_mut::<RuntimeContext>();
             match maybe_proto {
                 Some(RuntimeContext { tx, kv, .. }) => kv
@@ -57,6 +60,7 @@ pub(crate) mod extension {
         #[fast]
         #[static_method]
         fn contains(op_state: &mut OpState, #[string] key: &str) -> Result<bool> {
+            crate::budget::charge();
             let maybe_proto = op_state.try_borrow_mut::<RuntimeContext>();
             match maybe_proto {
                 Some(RuntimeContext { tx, kv, host, .. }) => kv
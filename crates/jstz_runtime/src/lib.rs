@@ -2,6 +2,7 @@ pub mod error;
 pub mod ext;
 pub use ext::jstz_kv::kv::*;
 
+pub mod budget;
 pub mod runtime;
 pub mod sys;
 
@@ -180,6 +180,7 @@ impl JstzRuntime {
         // SAFETY: See `impl Drop for JstzRuntime`
         let mut runtime = ManuallyDrop::new(JsRuntime::new(js_runtime_options));
         unsafe { runtime.v8_isolate().exit() };
+        crate::budget::register(runtime.v8_isolate().thread_safe_handle());
         // Give protocol access to the running script
         let op_state = runtime.op_state();
         if let Some(protocol) = protocol {
@@ -19,6 +19,10 @@ use jstz_core::{host::HostRuntime, kv::Transaction};
 use jstz_crypto::{hash::Hash, smart_function_hash::SmartFunctionHash};
 use serde::{Deserialize, Serialize};
 
+mod watchdog;
+
+pub use watchdog::*;
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
 pub enum WptTestStatus {
     Ok = 0,
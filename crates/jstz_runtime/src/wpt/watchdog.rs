@@ -0,0 +1,127 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::JstzRuntime;
+
+/// Limits the maximum size of the V8 heap (in MiB) of every runtime created
+/// afterwards. Runtimes approaching the limit are terminated by their [`Watchdog`]
+/// instead of crashing the process.
+///
+/// NOTE: V8 flags are global and must be set before the first runtime is created.
+pub fn set_max_heap_size(mib: usize) {
+    let _ = deno_core::v8_set_flags(vec![
+        String::new(),
+        format!("--max-old-space-size={mib}"),
+    ]);
+}
+
+/// Watchdog terminates the execution of a runtime once it runs for longer
+/// than its timeout or once its heap approaches the configured limit.
+///
+/// The watchdog is disarmed when dropped.
+pub struct Watchdog {
+    tripped: Arc<AtomicBool>,
+    disarm: Option<mpsc::Sender<()>>,
+    timer: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub fn arm(runtime: &mut JstzRuntime, timeout: Option<Duration>) -> Self {
+        let tripped = Arc::new(AtomicBool::new(false));
+        let handle = runtime.v8_isolate().thread_safe_handle();
+
+        runtime.add_near_heap_limit_callback({
+            let tripped = tripped.clone();
+            let handle = handle.clone();
+            move |current_limit, _initial_limit| {
+                tripped.store(true, Ordering::SeqCst);
+                handle.terminate_execution();
+                // Leave enough room for the isolate to unwind
+                current_limit * 2
+            }
+        });
+
+        let (disarm, timer) = match timeout {
+            Some(timeout) => {
+                let (tx, rx) = mpsc::channel::<()>();
+                let tripped = tripped.clone();
+                let timer = thread::spawn(move || {
+                    // The sender is dropped when the watchdog is disarmed
+                    if let Err(mpsc::RecvTimeoutError::Timeout) = rx.recv_timeout(timeout)
+                    {
+                        tripped.store(true, Ordering::SeqCst);
+                        handle.terminate_execution();
+                    }
+                });
+                (Some(tx), Some(timer))
+            }
+            None => (None, None),
+        };
+
+        Self {
+            tripped,
+            disarm,
+            timer,
+        }
+    }
+
+    /// Returns true if the watchdog terminated the runtime
+    pub fn tripped(&self) -> bool {
+        self.tripped.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.disarm.take();
+        if let Some(timer) = self.timer.take() {
+            let _ = timer.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use jstz_core::kv::Transaction;
+    use tezos_smart_rollup_mock::MockHost;
+
+    use super::Watchdog;
+    use crate::wpt::init_runtime;
+
+    #[test]
+    fn terminates_after_timeout() {
+        let mut host = MockHost::default();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let mut rt = init_runtime(&mut host, &mut tx);
+
+        let watchdog = Watchdog::arm(&mut rt, Some(Duration::from_millis(100)));
+        let start = Instant::now();
+        let result = rt.execute_script("native code", "while (true) {}");
+        assert!(result.is_err());
+        assert!(watchdog.tripped());
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn disarmed_on_drop() {
+        let mut host = MockHost::default();
+        let mut tx = Transaction::default();
+        tx.begin();
+        let mut rt = init_runtime(&mut host, &mut tx);
+
+        let watchdog = Watchdog::arm(&mut rt, Some(Duration::from_secs(60)));
+        rt.execute_script("native code", "1 + 1").unwrap();
+        let start = Instant::now();
+        drop(watchdog);
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,199 @@
+//! Runs WPT bundles through a live jstz node. Each test is deployed as a smart
+//! function and executed with a `RunFunction` operation, so that the full
+//! operation -> kernel -> receipt pipeline is exercised.
+
+use std::{
+    cell::RefCell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use jstz_client::JstzClient;
+use jstz_crypto::{keypair_from_mnemonic, public_key::PublicKey, secret_key::SecretKey};
+use jstz_proto::{
+    context::account::Address,
+    operation::{Content, DeployFunction, Operation, RunFunction, SignedOperation},
+    receipt::{ReceiptContent, ReceiptResult},
+    HttpBody,
+};
+use jstz_runtime::wpt::{TestHarnessReport, WptSubtest, WptSubtestStatus, WptTestStatus};
+use serde::Deserialize;
+
+/// Endpoint of the jstz node to run tests against. Tests run in-process if unset.
+pub const JSTZ_NODE_ENDPOINT_ENV: &str = "WPT_JSTZ_NODE_ENDPOINT";
+
+const MNEMONIC: &str =
+    "donate kidney style loyal nose core inflict cup symptom speed giant polar";
+const GAS_LIMIT: usize = 550_000;
+
+static NEXT_ACCOUNT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // Every worker signs with its own account so that nonces never race
+    static ACCOUNT: RefCell<Option<(PublicKey, SecretKey)>> = const { RefCell::new(None) };
+}
+
+pub fn endpoint() -> Option<String> {
+    std::env::var(JSTZ_NODE_ENDPOINT_ENV).ok()
+}
+
+fn account() -> Result<(PublicKey, SecretKey)> {
+    ACCOUNT.with(|account| {
+        let mut account = account.borrow_mut();
+        if account.is_none() {
+            let index = NEXT_ACCOUNT.fetch_add(1, Ordering::Relaxed);
+            account.replace(keypair_from_mnemonic(MNEMONIC, &format!("wpt-{index}"))?);
+        }
+        Ok(account.clone().expect("account should be initialised"))
+    })
+}
+
+/// Wraps the test source into a smart function that evaluates it as a classic
+/// script and responds with the collected report once the harness completes
+fn smart_function_code(source: &str) -> Result<String> {
+    Ok(format!(
+        r#"const SOURCE = {};
+const handler = async () => {{
+  const report = {{ status: null, subtests: [] }};
+  const completed = new Promise((resolve) => {{
+    globalThis.test_result_callback = (test) => {{
+      report.subtests.push({{
+        name: String(test.name),
+        status: test.status,
+        message: test.message ?? null,
+      }});
+    }};
+    globalThis.test_completion_callback = (_tests, harness) => {{
+      report.status = harness.status;
+      resolve();
+    }};
+  }});
+  try {{
+    (0, eval)(SOURCE);
+  }} catch (e) {{
+    return Response.json(report);
+  }}
+  await completed;
+  return Response.json(report);
+}};
+export default handler;
+"#,
+        serde_json::to_string(source)?
+    ))
+}
+
+#[derive(Deserialize)]
+struct LiveSubtest {
+    name: String,
+    status: u8,
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LiveReport {
+    status: Option<u8>,
+    subtests: Vec<LiveSubtest>,
+}
+
+impl TryFrom<LiveReport> for TestHarnessReport {
+    type Error = anyhow::Error;
+
+    fn try_from(report: LiveReport) -> Result<Self> {
+        let status = report
+            .status
+            .map(|s| WptTestStatus::try_from(s).map_err(|_| anyhow!("bad status {s}")))
+            .transpose()?;
+        let subtests = report
+            .subtests
+            .into_iter()
+            .map(|t| {
+                Ok(WptSubtest {
+                    status: WptSubtestStatus::try_from(t.status)
+                        .map_err(|_| anyhow!("bad subtest status {}", t.status))?,
+                    name: t.name,
+                    message: t.message,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { status, subtests })
+    }
+}
+
+async fn submit(client: &JstzClient, content: Content) -> Result<ReceiptContent> {
+    let (public_key, secret_key) = account()?;
+    let address = Address::from_base58(&public_key.hash())?;
+    let nonce = client.get_nonce(&address).await?;
+
+    let operation = Operation {
+        public_key,
+        nonce,
+        content,
+        max_fee: None,
+        priority_fee: None,
+    };
+    let signature = secret_key.sign(operation.hash())?;
+    let operation = SignedOperation::new(signature, operation);
+    let hash = operation.hash();
+
+    client.post_operation(&operation).await?;
+    match client.wait_for_operation_receipt(&hash).await?.result {
+        ReceiptResult::Success(content) => Ok(content),
+        ReceiptResult::Failed(e) => bail!("operation {hash} failed: {e}"),
+    }
+}
+
+async fn run(endpoint: &str, source: &str) -> Result<TestHarnessReport> {
+    let client = JstzClient::new(endpoint.to_string());
+
+    let address = match submit(
+        &client,
+        Content::DeployFunction(DeployFunction {
+            function_code: smart_function_code(source)?,
+            account_credit: 0,
+            interface: None,
+        }),
+    )
+    .await?
+    {
+        ReceiptContent::DeployFunction(receipt) => receipt.address,
+        _ => bail!("unexpected receipt for deploy function"),
+    };
+
+    let body = match submit(
+        &client,
+        Content::RunFunction(RunFunction {
+            uri: format!("jstz://{address}/").parse()?,
+            method: "GET".parse()?,
+            headers: Default::default(),
+            body: HttpBody::empty(),
+            gas_limit: GAS_LIMIT,
+        }),
+    )
+    .await?
+    {
+        ReceiptContent::RunFunction(receipt) => receipt.body,
+        _ => bail!("unexpected receipt for run function"),
+    };
+
+    let body = Option::<Vec<u8>>::from(body).context("empty response body")?;
+    serde_json::from_slice::<LiveReport>(&body)
+        .context("failed to parse report")?
+        .try_into()
+}
+
+/// Runs the test source on the jstz node at `endpoint`
+pub async fn run_wpt_test_harness_live(
+    endpoint: &str,
+    source: &str,
+) -> TestHarnessReport {
+    run(endpoint, source)
+        .await
+        .unwrap_or_else(|e| TestHarnessReport {
+            status: Some(WptTestStatus::Err),
+            subtests: vec![WptSubtest {
+                name: "Live execution failed".to_string(),
+                status: WptSubtestStatus::Fail,
+                message: Some(e.to_string()),
+            }],
+        })
+}
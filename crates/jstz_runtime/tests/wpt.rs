@@ -1,5 +1,7 @@
 use anyhow::Context;
 
+#[path = "live_runner.rs"]
+mod live_runner;
 #[path = "report_parser.rs"]
 mod report_parser;
 #[cfg(feature = "wpt-in-riscv")]
@@ -8,10 +10,11 @@ use report_parser::parse_report_from_log_line;
 #[cfg(not(feature = "wpt-in-riscv"))]
 use jstz_core::kv::Transaction;
 #[cfg(not(feature = "wpt-in-riscv"))]
-use jstz_runtime::wpt::init_runtime;
+use jstz_runtime::wpt::{init_runtime, set_max_heap_size, Watchdog};
 use jstz_runtime::wpt::{TestHarnessReport, WptSubtest, WptSubtestStatus, WptTestStatus};
 use jstz_wpt::{
-    Bundle, BundleItem, TestFilter, TestToRun, Wpt, WptMetrics, WptReportTest, WptServe,
+    Bundle, BundleItem, TestFilter, TestToRun, Wpt, WptFyiRunInfo, WptMetrics,
+    WptReportTest, WptRunOptions, WptServe,
 };
 use regex::Regex;
 use serde::Deserialize;
@@ -23,6 +26,7 @@ use std::{
     future::IntoFuture,
     path::{Path, PathBuf},
     str::FromStr,
+    time::Duration,
 };
 use tezos_smart_rollup_mock::MockHost;
 use tokio::io::AsyncWriteExt;
@@ -44,8 +48,11 @@ fn should_skip_test(test_path: &str) -> bool {
 }
 
 #[cfg(feature = "wpt-in-riscv")]
-fn run_wpt_test_harness_in_riscv_sandbox(source: String) -> TestHarnessReport {
-    use jstz_utils::inbox_builder::InboxBuilder;
+fn run_wpt_test_harness_in_riscv_sandbox(
+    source: String,
+    timeout: Option<Duration>,
+) -> TestHarnessReport {
+    use jstz_utils::{inbox_builder::InboxBuilder, riscv_sandbox::RiscvSandbox};
     use tezos_smart_rollup::types::SmartRollupAddress;
 
     // Build inbox messages by chunking the source code and wrapping each chunk in a deploy function operation
@@ -71,32 +78,27 @@ fn run_wpt_test_harness_in_riscv_sandbox(source: String) -> TestHarnessReport {
         .deploy_function(&mut account, "STOP".to_string(), 1_000_000)
         .unwrap();
 
-    let temp_file = std::env::temp_dir().join("jstz_inbox_message.json");
-    let inbox_file = inbox_builder.build();
-    inbox_file.save(temp_file.as_path()).unwrap();
-
     let kernel_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(
         "../../target/riscv64gc-unknown-linux-musl/release/wpt-test-kernel-executable",
     );
-
-    let output = std::process::Command::new("riscv-sandbox")
-        .args([
-            "run",
-            "--timings",
-            "--address",
-            "sr1FXevDx86EyU1BBwhn94gtKvVPTNwoVxUC",
-            "--inbox-file",
-            temp_file.to_str().unwrap(),
-            "--input",
-            kernel_path.to_str().unwrap(),
-        ])
-        .output()
+    let mut sandbox = RiscvSandbox::new(kernel_path);
+    if let Some(timeout) = timeout {
+        sandbox = sandbox.with_timeout(timeout);
+    }
+    let output = sandbox
+        .run(&inbox_builder.build())
         .expect("Failed to execute riscv-sandbox");
 
-    if !output.status.success() {
+    if output.timed_out() {
+        return TestHarnessReport {
+            status: Some(WptTestStatus::Timeout),
+            subtests: vec![],
+        };
+    }
+    if !output.success() {
         println!(
             "riscv-sandbox failed with exit code: {}",
-            output.status.code().unwrap_or(-1)
+            output.status.and_then(|status| status.code()).unwrap_or(-1)
         );
         return TestHarnessReport {
             status: Some(WptTestStatus::Err),
@@ -117,15 +119,16 @@ fn run_wpt_test_harness_in_riscv_sandbox(source: String) -> TestHarnessReport {
         }],
     };
 
-    let data = parse_report_from_log_line(
-        format!("{}", String::from_utf8_lossy(&output.stdout)).as_str(),
-    )
-    .unwrap()
-    .unwrap_or(err_report);
+    let data = parse_report_from_log_line(&output.stdout)
+        .unwrap()
+        .unwrap_or(err_report);
     data
 }
 
-pub async fn run_wpt_test_harness(bundle: &Bundle) -> TestHarnessReport {
+pub async fn run_wpt_test_harness(
+    bundle: &Bundle,
+    timeout: Option<Duration>,
+) -> TestHarnessReport {
     let mut host = MockHost::default();
     host.set_debug_handler(std::io::empty());
 
@@ -142,11 +145,17 @@ pub async fn run_wpt_test_harness(bundle: &Bundle) -> TestHarnessReport {
         }
     }
 
+    if let Some(endpoint) = live_runner::endpoint() {
+        return live_runner::run_wpt_test_harness_live(&endpoint, &source).await;
+    }
+
     #[cfg(not(feature = "wpt-in-riscv"))]
     {
         let mut tx = Transaction::default();
         tx.begin();
         let mut rt = init_runtime(&mut host, &mut tx);
+        // Terminates the isolate if the test hangs or exhausts its heap
+        let watchdog = Watchdog::arm(&mut rt, timeout);
 
         // Somehow each `execute_script` call has some strange side effect such that the global
         // test suite object is completed prematurely before all test cases are registered.
@@ -160,13 +169,16 @@ pub async fn run_wpt_test_harness(bundle: &Bundle) -> TestHarnessReport {
 
         // Take the test harness report out of the runtime and return it
         // Need to store data temporarily so that the borrow can be dropped
-        let data = rt.op_state().borrow().borrow::<TestHarnessReport>().clone();
+        let mut data = rt.op_state().borrow().borrow::<TestHarnessReport>().clone();
+        if watchdog.tripped() {
+            data.status = Some(WptTestStatus::Timeout);
+        }
         data
     }
 
     #[cfg(feature = "wpt-in-riscv")]
     {
-        run_wpt_test_harness_in_riscv_sandbox(source)
+        run_wpt_test_harness_in_riscv_sandbox(source, timeout)
     }
 }
 
@@ -230,7 +242,7 @@ fn run_wpt_test(
                 ));
             }
         };
-        let report = run_wpt_test_harness(&bundle).await;
+        let report = run_wpt_test_harness(&bundle, test.timeout).await;
         println!("Run test {} => {:?}", &test.url_path, &report.status);
         // Each test suite should have a status code attached after it completes.
         // When unwrap fails, it means something is wrong, e.g. some tests failed because
@@ -395,19 +407,34 @@ async fn dump_stats(
 }
 
 #[cfg_attr(feature = "skip-wpt", ignore)]
-#[tokio::test]
+#[tokio::test(flavor = "multi_thread")]
 async fn test_wpt() -> anyhow::Result<()> {
     let mut filter = TestFilter::default();
     let deno_report = DenoReport::load(
         Path::new(std::env!("CARGO_MANIFEST_DIR")).join("tests/deno_report.json"),
     )?;
     filter.set_expected_tests(deno_report.test_paths().as_slice())?;
+    filter.apply_env()?;
+
+    let options = WptRunOptions::from_env();
+    #[cfg(not(feature = "wpt-in-riscv"))]
+    if let Some(mib) = options.max_heap_size {
+        set_max_heap_size(mib);
+    }
 
     let report = {
         let wpt = Wpt::new().await?;
         let manifest = Wpt::read_manifest()?;
         let wpt_serve = wpt.serve(false).await?;
-        WptServe::run_test_harness(&wpt_serve, &manifest, &filter, run_wpt_test).await?
+        tokio::task::block_in_place(|| {
+            WptServe::run_test_harness_parallel(
+                &wpt_serve,
+                &manifest,
+                &filter,
+                &options,
+                run_wpt_test,
+            )
+        })?
     };
 
     let path = Path::new(std::env!("CARGO_MANIFEST_DIR")).join("tests/wptreport.json");
@@ -422,5 +449,19 @@ async fn test_wpt() -> anyhow::Result<()> {
     if let Ok(v) = std::env::var("STATS_PATH") {
         dump_stats(deno_report.stats(), report.stats(), &v).await?;
     }
+    if let Ok(v) = std::env::var("JUNIT_PATH") {
+        tokio::fs::write(v, report.to_junit_xml()).await?;
+    }
+    if let Ok(v) = std::env::var("HTML_REPORT_PATH") {
+        tokio::fs::write(v, report.to_html()).await?;
+    }
+    // Results in the format expected by wpt.fyi
+    if let Ok(v) = std::env::var("WPTFYI_REPORT_PATH") {
+        let run_info = WptFyiRunInfo {
+            revision: std::env::var("GITHUB_SHA").ok(),
+            ..Default::default()
+        };
+        tokio::fs::write(v, serde_json::to_vec(&report.to_wptfyi(run_info))?).await?;
+    }
     Ok(())
 }
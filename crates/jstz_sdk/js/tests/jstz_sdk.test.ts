@@ -1,11 +1,22 @@
 import { describe, it, expect } from "vitest";
 import {
   sign_operation,
+  sign_operations,
   hash_operation,
+  hash_operations,
   convert_passkey_signature,
+  passkey_challenge,
+  passkey_public_key,
+  passkey_address,
+  build_deploy_function,
+  build_run_function,
+  build_transfer,
+  simulate_run_function,
+  estimate_fee,
 } from "../../pkg/jstz_sdk.js";
+import type { Operation } from "../../pkg/jstz_types";
 
-const operation = {
+const operation: Operation = {
   content: {
     _type: "DeployFunction",
     functionCode:
@@ -36,6 +47,47 @@ describe("Convert passkey signature", () => {
   });
 });
 
+describe("Passkey registration", () => {
+  const coseKey =
+    "a50102032620012158201143a9c56748c29adb6a85863484ffe3f42299acca8470575ae177ec988ff28b225820ec29d9429a3196d3925a1dcc56779e231c645dcd168f6f2a3d9039271e1f0882";
+  // rpIdHash | flags (UP, UV, AT) | signCount | aaguid | credential id | COSE key
+  const authenticatorData = Uint8Array.from(
+    Buffer.from(
+      "00".repeat(32) +
+        "45" +
+        "00".repeat(4) +
+        "00".repeat(16) +
+        "000401020304" +
+        coseKey,
+      "hex",
+    ),
+  );
+
+  it("builds the challenge from the operation hash", () => {
+    const challenge = passkey_challenge(operation);
+    expect(new TextDecoder().decode(challenge)).toEqual(
+      hash_operation(operation),
+    );
+  });
+
+  it("derives the account of the attested credential", () => {
+    expect(passkey_public_key(authenticatorData)).toEqual(
+      "p2pk64hmP1AMx1kcVAUhLTAj21y1CDEkjn4qY7rUXqmDWLoTqidYhhW",
+    );
+    expect(passkey_address(authenticatorData)).toEqual(
+      "tz3WGyV7iRfiyDZoiWzKpVWZqVef6v69HXhB",
+    );
+  });
+
+  it("fails without attested credential data", () => {
+    const assertionData = authenticatorData.slice();
+    assertionData[32] = 0x05;
+    expect(() => passkey_address(assertionData)).toThrowError(
+      "no attested credential data",
+    );
+  });
+});
+
 describe("Sign operation", () => {
   const secretKey = "edsk38mmuJeEfSYGiwLE1qHr16BPYKMT5Gg1mULT7dNUtg3ti4De3a";
 
@@ -54,6 +106,36 @@ describe("Sign operation", () => {
     expect(() => sign_operation(badOperation, secretKey)).toThrowError();
   });
 
+  it("signs Jstz operations with tz2 and tz3 keys", () => {
+    const keys = [
+      {
+        publicKey: "sppk7cNntyqNMmQXjqwZVDYt7cktbTp4uysStbDDQADBNWboKPAFsWc",
+        secretKey: "spsk2C1cZdLkqE6WGbmJZy4Qhf3n3pNFQa5KPJc8UHE6KXAbaTC7D6",
+        prefix: "spsig1",
+      },
+      {
+        publicKey: "p2pk64hmP1AMx1kcVAUhLTAj21y1CDEkjn4qY7rUXqmDWLoTqidYhhW",
+        secretKey: "p2sk3C12H6vXkE9GxsUs8Pv2f5MMKhcmM8iNrWwgmcTU9Rcjr5bEtC",
+        prefix: "p2sig",
+      },
+    ];
+    for (const { publicKey, secretKey, prefix } of keys) {
+      const signature = sign_operation({ ...operation, publicKey }, secretKey);
+      expect(signature.startsWith(prefix)).toBe(true);
+    }
+  });
+
+  it("fails to sign with the secret key of another account", () => {
+    expect(() =>
+      sign_operation(
+        operation,
+        "p2sk3C12H6vXkE9GxsUs8Pv2f5MMKhcmM8iNrWwgmcTU9Rcjr5bEtC",
+      ),
+    ).toThrowError(
+      "secret key does not match the public key of the operation",
+    );
+  });
+
   it("fails to sign using unsupported secret keys", () => {
     // BLS keys are not supported
     let badOperation = Object.assign({}, operation);
@@ -83,3 +165,224 @@ describe("Hash operation", () => {
     expect(() => hash_operation(badOperation)).toThrowError();
   });
 });
+
+describe("Build operation", () => {
+  const publicKey = operation.publicKey;
+
+  it("builds deploy function operations", () => {
+    const built = build_deploy_function({
+      publicKey,
+      code: operation.content.functionCode,
+    });
+    expect(built).toEqual({
+      ...operation,
+      content: { ...operation.content, interface: null },
+      maxFee: null,
+      priorityFee: null,
+    });
+    expect(hash_operation(built)).toEqual(hash_operation(operation));
+  });
+
+  it("builds run function operations with defaults", () => {
+    const built = build_run_function({
+      publicKey,
+      uri: "jstz://tz1cD5CuvAALcxgypqBXcBQEA8dkLJivoFjU/nfts?status=sold",
+      headers: { "Content-Type": "text/plain" },
+      body: "hello",
+      nonce: 3,
+    });
+    expect(built).toEqual({
+      content: {
+        _type: "RunFunction",
+        uri: "jstz://tz1cD5CuvAALcxgypqBXcBQEA8dkLJivoFjU/nfts?status=sold",
+        method: "GET",
+        headers: { "content-type": "text/plain" },
+        body: "aGVsbG8=",
+        gasLimit: 550000,
+      },
+      nonce: 3,
+      publicKey,
+      maxFee: null,
+      priorityFee: null,
+    });
+    expect(() => hash_operation(built)).not.toThrowError();
+  });
+
+  it("builds transfer operations", () => {
+    const built = build_transfer({
+      publicKey,
+      to: "KT1WEAA8whopt6FqPodVErxnQysYSkTan4wS",
+      amount: 1000,
+    });
+    expect(built.content).toEqual({
+      _type: "RunFunction",
+      uri: "jstz://KT1WEAA8whopt6FqPodVErxnQysYSkTan4wS/-/noop",
+      method: "POST",
+      headers: { "x-jstz-transfer": "1000" },
+      body: null,
+      gasLimit: 550000,
+    });
+  });
+
+  it("builds operations declaring fees", () => {
+    const built = build_transfer({
+      publicKey,
+      to: "tz1cD5CuvAALcxgypqBXcBQEA8dkLJivoFjU",
+      amount: 1000,
+      maxFee: 50,
+      priorityFee: 20,
+    });
+    expect(built.maxFee).toEqual(50);
+    expect(built.priorityFee).toEqual(20);
+    expect(estimate_fee(built).fee).toEqual(20);
+    expect(hash_operation(built)).not.toEqual(
+      hash_operation({ ...built, priorityFee: 30 }),
+    );
+  });
+
+  it("rejects invalid input", () => {
+    expect(() =>
+      build_run_function({ publicKey, uri: "http://example.com" }),
+    ).toThrowError("URL scheme must be 'jstz'");
+    expect(() =>
+      build_transfer({
+        publicKey,
+        to: "tz1cD5CuvAALcxgypqBXcBQEA8dkLJivoFjU",
+        amount: 0,
+      }),
+    ).toThrowError("transfer amount must be positive");
+    expect(() =>
+      build_deploy_function({ publicKey: "edpk", code: "" }),
+    ).toThrowError("invalid public key");
+    expect(() =>
+      build_deploy_function({ publicKey, functionCode: "" }),
+    ).toThrowError();
+  });
+});
+
+describe("Simulate run function", () => {
+  const address = "KT1WEAA8whopt6FqPodVErxnQysYSkTan4wS";
+  const snapshot = {
+    accounts: {
+      [address]: {
+        functionCode:
+          'export default () => new Response(`${Kv.get("count") + 1}`);',
+      },
+    },
+    kv: { [address]: { count: 41 } },
+  };
+  const run = build_run_function({
+    publicKey: operation.publicKey,
+    uri: `jstz://${address}/`,
+  });
+
+  it("runs smart functions against the snapshot", () => {
+    const receipt = simulate_run_function(run, snapshot);
+    expect(receipt.statusCode).toEqual(200);
+    // "42"
+    expect(receipt.body).toEqual("NDI=");
+  });
+
+  it("rejects other operations", () => {
+    expect(() => simulate_run_function(operation, snapshot)).toThrowError(
+      "only run function operations can be simulated",
+    );
+  });
+
+  it("rejects smart functions without code", () => {
+    expect(() =>
+      simulate_run_function(run, { accounts: { [address]: {} } }),
+    ).toThrowError("is missing its function code");
+  });
+});
+
+describe("Batch operations", () => {
+  const secretKey = "edsk38mmuJeEfSYGiwLE1qHr16BPYKMT5Gg1mULT7dNUtg3ti4De3a";
+  const operations = [operation, { ...operation, nonce: 1 }];
+
+  it("signs operations in order", () => {
+    expect(sign_operations(operations, secretKey)).toEqual(
+      operations.map((op) => sign_operation(op, secretKey)),
+    );
+  });
+
+  it("hashes operations in order", () => {
+    expect(hash_operations(operations)).toEqual(
+      operations.map((op) => hash_operation(op)),
+    );
+    expect(hash_operations([])).toEqual([]);
+  });
+
+  it("reports the invalid operation", () => {
+    expect(() =>
+      hash_operations([operation, { ...operation, content: {} }]),
+    ).toThrowError("operation 1");
+    expect(() => sign_operations([operation, "abc123"], secretKey)).toThrowError(
+      "operation 1",
+    );
+  });
+});
+
+describe("Estimate fee", () => {
+  it("charges nothing to operations declaring no fee", () => {
+    const estimate = estimate_fee(operation);
+    expect(estimate.gasLimit).toEqual(0);
+    expect(estimate.largePayload).toBe(false);
+    expect(estimate.fee).toEqual(0);
+  });
+
+  it("charges the priority fee up to the maximum fee", () => {
+    expect(estimate_fee({ ...operation, priorityFee: 80 }).fee).toEqual(80);
+    expect(
+      estimate_fee({ ...operation, maxFee: 50, priorityFee: 80 }).fee,
+    ).toEqual(50);
+    expect(estimate_fee({ ...operation, maxFee: 50 }).fee).toEqual(0);
+  });
+
+  it("reports the gas limit of run function operations", () => {
+    const run = build_run_function({
+      publicKey: operation.publicKey,
+      uri: "jstz://tz1cD5CuvAALcxgypqBXcBQEA8dkLJivoFjU/",
+      gasLimit: 1000,
+    });
+    const estimate = estimate_fee(run);
+    expect(estimate.gasLimit).toEqual(1000);
+    expect(estimate.fee).toEqual(0);
+  });
+
+  it("flags large payloads", () => {
+    const large = build_deploy_function({
+      publicKey: operation.publicKey,
+      code: "a".repeat(4000),
+    });
+    expect(estimate_fee(large).largePayload).toBe(true);
+  });
+
+  it("reads the fee rates and limits of the node", () => {
+    const run = build_run_function({
+      publicKey: operation.publicKey,
+      uri: "jstz://tz1cD5CuvAALcxgypqBXcBQEA8dkLJivoFjU/",
+      gasLimit: 1000,
+      priorityFee: 5,
+    });
+    const { size } = estimate_fee(run);
+    // the response of the `/constants` endpoint of the node
+    const constants = {
+      version: "0.1.0",
+      runtime_version: "v2",
+      gas_price: 2,
+      storage_fee_rate: 1,
+      max_operation_size: size,
+      max_direct_operation_size: size - 1,
+      max_reveal_size: 10000,
+      max_interface_size: 1000,
+      queue_capacity: 0,
+    };
+    const estimate = estimate_fee(run, constants);
+    expect(estimate.fee).toEqual(5 + 2000 + size);
+    expect(estimate.largePayload).toBe(true);
+    expect(() =>
+      estimate_fee(run, { ...constants, max_operation_size: size - 1 }),
+    ).toThrowError("operation size exceeds maximum allowed size");
+  });
+});
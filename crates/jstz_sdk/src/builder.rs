@@ -0,0 +1,197 @@
+//! Builders assembling jstz operations from plain JS objects.
+//!
+//! The returned operations are in the canonical JSON form accepted by
+//! `sign_operation` and `hash_operation`. Unless given, the nonce is set to 0 and
+//! must be replaced with the account's next nonce before signing. All builders
+//! accept the optional `maxFee` and `priorityFee` of the operation, in mutez.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use http::{HeaderMap, HeaderName, HeaderValue, Method, Uri};
+use jstz_crypto::public_key::PublicKey;
+use jstz_proto::{
+    context::account::{Address, Amount, Nonce},
+    executor::smart_function::{NOOP_PATH, X_JSTZ_TRANSFER},
+    operation::{Content, DeployFunction, Operation, RunFunction},
+    HttpBody,
+};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::js_error;
+
+/// Gas limit of run function operations unless given
+pub const DEFAULT_GAS_LIMIT: usize = 550_000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct DeployFunctionArgs {
+    public_key: String,
+    nonce: Option<u64>,
+    code: String,
+    #[serde(default)]
+    account_credit: Amount,
+    max_fee: Option<Amount>,
+    priority_fee: Option<Amount>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RunFunctionArgs {
+    public_key: String,
+    nonce: Option<u64>,
+    uri: String,
+    method: Option<String>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body: Option<String>,
+    gas_limit: Option<usize>,
+    max_fee: Option<Amount>,
+    priority_fee: Option<Amount>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct TransferArgs {
+    public_key: String,
+    nonce: Option<u64>,
+    to: String,
+    amount: Amount,
+    gas_limit: Option<usize>,
+    max_fee: Option<Amount>,
+    priority_fee: Option<Amount>,
+}
+
+fn operation(
+    public_key: &str,
+    nonce: Option<u64>,
+    max_fee: Option<Amount>,
+    priority_fee: Option<Amount>,
+    content: Content,
+) -> Result<Operation, JsValue> {
+    let public_key = PublicKey::from_base58(public_key)
+        .map_err(|e| js_error(format!("invalid public key: {e}")))?;
+    Ok(Operation {
+        public_key,
+        nonce: Nonce(nonce.unwrap_or_default()),
+        content,
+        max_fee,
+        priority_fee,
+    })
+}
+
+fn to_js(operation: &Operation) -> Result<JsValue, JsValue> {
+    let json = serde_json::to_value(operation).map_err(js_error)?;
+    Ok(json.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+fn parse_uri(uri: &str) -> Result<Uri, JsValue> {
+    let uri = Uri::from_str(uri).map_err(|e| js_error(format!("invalid uri: {e}")))?;
+    if uri.scheme_str() != Some("jstz") {
+        return Err(js_error("URL scheme must be 'jstz'"));
+    }
+    let address = uri
+        .host()
+        .ok_or_else(|| js_error("missing smart function address"))?;
+    Address::from_base58(address)
+        .map_err(|e| js_error(format!("invalid address '{address}': {e}")))?;
+    Ok(uri)
+}
+
+fn parse_headers(headers: BTreeMap<String, String>) -> Result<HeaderMap, JsValue> {
+    headers
+        .into_iter()
+        .map(|(name, value)| {
+            let name = HeaderName::from_str(&name)
+                .map_err(|e| js_error(format!("invalid header name '{name}': {e}")))?;
+            let value = HeaderValue::from_str(&value).map_err(|e| {
+                js_error(format!("invalid value of header '{name}': {e}"))
+            })?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+/// Builds a deploy function operation from
+/// `{ publicKey, code, accountCredit?, nonce?, maxFee?, priorityFee? }`
+#[wasm_bindgen]
+pub fn build_deploy_function(args: JsValue) -> Result<JsValue, JsValue> {
+    let args: DeployFunctionArgs = serde_wasm_bindgen::from_value(args)?;
+    let operation = operation(
+        &args.public_key,
+        args.nonce,
+        args.max_fee,
+        args.priority_fee,
+        Content::DeployFunction(DeployFunction {
+            function_code: args.code,
+            account_credit: args.account_credit,
+            interface: None,
+        }),
+    )?;
+    to_js(&operation)
+}
+
+/// Builds a run function operation from
+/// `{ publicKey, uri, method?, headers?, body?, gasLimit?, nonce?, maxFee?,
+/// priorityFee? }`.
+/// The method defaults to GET and the body is sent as UTF-8 text.
+#[wasm_bindgen]
+pub fn build_run_function(args: JsValue) -> Result<JsValue, JsValue> {
+    let args: RunFunctionArgs = serde_wasm_bindgen::from_value(args)?;
+    let method = match args.method {
+        Some(method) => Method::from_str(&method)
+            .map_err(|_| js_error(format!("invalid HTTP method: {method}")))?,
+        None => Method::GET,
+    };
+    let operation = operation(
+        &args.public_key,
+        args.nonce,
+        args.max_fee,
+        args.priority_fee,
+        Content::RunFunction(RunFunction {
+            uri: parse_uri(&args.uri)?,
+            method,
+            headers: parse_headers(args.headers)?,
+            body: args
+                .body
+                .map(HttpBody::from_string)
+                .unwrap_or_else(HttpBody::empty),
+            gas_limit: args.gas_limit.unwrap_or(DEFAULT_GAS_LIMIT),
+        }),
+    )?;
+    to_js(&operation)
+}
+
+/// Builds an operation transferring `amount` mutez from
+/// `{ publicKey, to, amount, gasLimit?, nonce?, maxFee?, priorityFee? }`. Transfers to smart functions
+/// do not run the receiving smart function.
+#[wasm_bindgen]
+pub fn build_transfer(args: JsValue) -> Result<JsValue, JsValue> {
+    let args: TransferArgs = serde_wasm_bindgen::from_value(args)?;
+    if args.amount == 0 {
+        return Err(js_error("transfer amount must be positive"));
+    }
+    let to = Address::from_base58(&args.to)
+        .map_err(|e| js_error(format!("invalid address '{}': {e}", args.to)))?;
+    let uri = match &to {
+        Address::User(_) => format!("jstz://{to}"),
+        Address::SmartFunction(_) => format!("jstz://{to}{NOOP_PATH}"),
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(X_JSTZ_TRANSFER, HeaderValue::from(args.amount));
+    let operation = operation(
+        &args.public_key,
+        args.nonce,
+        args.max_fee,
+        args.priority_fee,
+        Content::RunFunction(RunFunction {
+            uri: parse_uri(&uri)?,
+            method: Method::POST,
+            headers,
+            body: HttpBody::empty(),
+            gas_limit: args.gas_limit.unwrap_or(DEFAULT_GAS_LIMIT),
+        }),
+    )?;
+    to_js(&operation)
+}
@@ -0,0 +1,86 @@
+//! Offline estimation of the cost of an operation.
+//!
+//! An operation declaring fees pays its priority fee, capped at its maximum fee,
+//! to the injector before it runs, see [`Operation::fee`]. On top of it, the
+//! estimate charges the gas limit and the size of the operation at the gas price
+//! and the storage fee rate of the node, see [`Constants`]. The estimate also
+//! sizes the operation as encoded by the node, which decides whether the node
+//! injects it through the reveal mechanism.
+
+use jstz_core::BinEncodable;
+use jstz_crypto::{signature::Signature, HashTrait};
+use jstz_proto::{
+    constants::Constants,
+    context::account::Amount,
+    operation::{Content, Operation, SignedOperation},
+};
+use serde::Serialize;
+use tezos_crypto_rs::hash::Ed25519Signature;
+use wasm_bindgen::prelude::*;
+
+use crate::{js_error, parse_operation};
+
+/// All Tezos signatures are encoded on 64 bytes
+const SIGNATURE_SIZE: usize = 64;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FeeEstimate {
+    /// Size in bytes of the signed operation
+    size: usize,
+    /// Whether the node injects the operation through the reveal mechanism
+    large_payload: bool,
+    gas_limit: usize,
+    /// Fee in mutez charged to the source of the operation
+    fee: Amount,
+}
+
+fn encoded_size(operation: Operation) -> Result<usize, JsValue> {
+    // The signature does not depend on the curve size-wise, so a placeholder
+    // is enough to size the operation before it is signed
+    let signature =
+        Ed25519Signature::try_from_bytes(&[0; SIGNATURE_SIZE]).map_err(js_error)?;
+    SignedOperation::new(Signature::Ed25519(signature.into()), operation)
+        .encode()
+        .map(|bytes| bytes.len())
+        .map_err(js_error)
+}
+
+/// Estimates the fee of an operation under `constants`, the response of the
+/// `/constants` endpoint of the node, and returns
+/// `{ size, largePayload, gasLimit, fee }`. The constants the SDK was built with
+/// are used if `constants` is `undefined` or `null`.
+///
+/// Operations signed with passkeys carry the authenticator data on top of the
+/// signature, which is not accounted for in the size.
+#[wasm_bindgen]
+pub fn estimate_fee(operation: JsValue, constants: JsValue) -> Result<JsValue, JsValue> {
+    let json: serde_json::Value = serde_wasm_bindgen::from_value(operation)?;
+    let operation = parse_operation(json)?;
+    let constants: Constants = if constants.is_undefined() || constants.is_null() {
+        Constants::default()
+    } else {
+        serde_wasm_bindgen::from_value(constants)?
+    };
+
+    let gas_limit = match &operation.content {
+        Content::RunFunction(run) => run.gas_limit,
+        _ => 0,
+    };
+    let declared_fee = operation.fee();
+    let size = encoded_size(operation)?;
+    let max_size = constants.max_operation_size;
+    if size > max_size {
+        return Err(js_error(format!(
+            "operation size exceeds maximum allowed size ({size} bytes > {max_size} bytes)"
+        )));
+    }
+
+    let estimate = FeeEstimate {
+        size,
+        large_payload: size > constants.max_direct_operation_size,
+        gas_limit,
+        fee: declared_fee.saturating_add(constants.base_fee(gas_limit, size)),
+    };
+    Ok(estimate.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
@@ -1,33 +1,120 @@
+use jstz_crypto::public_key::PublicKey;
 use jstz_crypto::secret_key::SecretKey;
-use jstz_crypto::verifier::passkey::parse_passkey_signature as parse_passkey_signature_inner;
+use jstz_crypto::verifier::passkey::{
+    parse_attested_public_key, parse_passkey_signature as parse_passkey_signature_inner,
+    passkey_challenge as passkey_challenge_inner,
+};
 use jstz_proto::operation::Operation;
 use wasm_bindgen::prelude::*;
 
-#[wasm_bindgen]
-pub fn sign_operation(operation: JsValue, secret_key: &str) -> Result<String, JsValue> {
-    let json: serde_json::Value = serde_wasm_bindgen::from_value(operation)?;
-    let operation: Operation =
-        serde_json::from_value(json).map_err(|e| JsValue::from_str(&e.to_string()))?;
-    let hash = operation.hash();
-    let secret_key = SecretKey::from_base58(secret_key)
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+mod builder;
+mod fee;
+mod simulation;
+
+pub use builder::{build_deploy_function, build_run_function, build_transfer};
+pub use fee::estimate_fee;
+pub use simulation::simulate_run_function;
+
+fn js_error(e: impl ToString) -> JsValue {
+    JsValue::from_str(&e.to_string())
+}
+
+/// Parses an unencrypted tz1 (`edsk`), tz2 (`spsk`) or tz3 (`p2sk`) secret key
+fn parse_secret_key(secret_key: &str) -> Result<SecretKey, JsValue> {
+    match secret_key.get(..4) {
+        Some("BLsk") => Err(js_error(
+            "InvalidSecretKey: BLS secret keys are not supported yet",
+        )),
+        Some("edes" | "spes" | "p2es") => Err(js_error(
+            "InvalidSecretKey: encrypted secret keys are not supported",
+        )),
+        _ => SecretKey::from_base58(secret_key).map_err(js_error),
+    }
+}
 
+fn sign(operation: &Operation, secret_key: &SecretKey) -> Result<String, JsValue> {
+    let hash = operation.hash();
     let signature = secret_key
-        .sign(hash)
+        .sign(&hash)
         .map_err(|e| JsValue::from_str(&e.to_string()))?;
 
+    // The rollup would reject the operation as invalid
+    signature
+        .verify(&operation.public_key, hash.as_ref())
+        .map_err(|_| {
+            js_error("secret key does not match the public key of the operation")
+        })?;
+
     Ok(signature.to_base58())
 }
 
+fn parse_operation(json: serde_json::Value) -> Result<Operation, JsValue> {
+    serde_json::from_value(json).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Parses an array of operations. Errors point at the offending operation.
+fn parse_operations(operations: JsValue) -> Result<Vec<Operation>, JsValue> {
+    let json: Vec<serde_json::Value> = serde_wasm_bindgen::from_value(operations)?;
+    json.into_iter()
+        .enumerate()
+        .map(|(i, json)| {
+            serde_json::from_value(json)
+                .map_err(|e| js_error(format!("operation {i}: {e}")))
+        })
+        .collect()
+}
+
+/// Signs the operation with a tz1, tz2 or tz3 secret key. The secret key must
+/// belong to the public key of the operation.
+#[wasm_bindgen]
+pub fn sign_operation(operation: JsValue, secret_key: &str) -> Result<String, JsValue> {
+    let json: serde_json::Value = serde_wasm_bindgen::from_value(operation)?;
+    let operation = parse_operation(json)?;
+    let secret_key = parse_secret_key(secret_key)?;
+    sign(&operation, &secret_key)
+}
+
+/// Signs all operations with the same secret key in a single call. Signatures
+/// are returned in the order of the operations.
+#[wasm_bindgen]
+pub fn sign_operations(
+    operations: JsValue,
+    secret_key: &str,
+) -> Result<Vec<String>, JsValue> {
+    let operations = parse_operations(operations)?;
+    let secret_key = parse_secret_key(secret_key)?;
+    operations
+        .iter()
+        .enumerate()
+        .map(|(i, operation)| {
+            sign(operation, &secret_key).map_err(|e| {
+                js_error(format!(
+                    "operation {i}: {}",
+                    e.as_string().unwrap_or_default()
+                ))
+            })
+        })
+        .collect()
+}
+
 #[wasm_bindgen]
 pub fn hash_operation(operation: JsValue) -> Result<String, JsValue> {
     let json: serde_json::Value = serde_wasm_bindgen::from_value(operation)?;
-    let operation: Operation =
-        serde_json::from_value(json).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let operation = parse_operation(json)?;
     let hash = operation.hash();
     Ok(hash.to_string())
 }
 
+/// Hashes all operations in a single call. Hashes are returned in the order of
+/// the operations.
+#[wasm_bindgen]
+pub fn hash_operations(operations: JsValue) -> Result<Vec<String>, JsValue> {
+    Ok(parse_operations(operations)?
+        .iter()
+        .map(|operation| operation.hash().to_string())
+        .collect())
+}
+
 /// Converts signature returned from the passkey device into a valid base58
 /// Tezos P256 signature. The passkey signature must use P256 (alg = -7)
 #[wasm_bindgen]
@@ -36,3 +123,34 @@ pub fn convert_passkey_signature(signature: &str) -> Result<String, JsValue> {
         .map_err(|e| JsValue::from_str(&e.to_string()))?;
     Ok(parsed_signature.to_base58_check())
 }
+
+/// Returns the challenge to pass to `navigator.credentials.get` for signing the
+/// operation with a passkey
+#[wasm_bindgen]
+pub fn passkey_challenge(operation: JsValue) -> Result<Vec<u8>, JsValue> {
+    let json: serde_json::Value = serde_wasm_bindgen::from_value(operation)?;
+    let operation = parse_operation(json)?;
+    Ok(passkey_challenge_inner(operation.hash().as_ref()))
+}
+
+fn attested_public_key(authenticator_data: &[u8]) -> Result<PublicKey, JsValue> {
+    parse_attested_public_key(authenticator_data)
+        .map(PublicKey::P256)
+        .map_err(js_error)
+}
+
+/// Returns the base58 P256 public key of the passkey credential created by
+/// `navigator.credentials.create`, given the authenticator data of the
+/// registration response (`response.getAuthenticatorData()`)
+#[wasm_bindgen]
+pub fn passkey_public_key(authenticator_data: &[u8]) -> Result<String, JsValue> {
+    Ok(attested_public_key(authenticator_data)?.to_base58())
+}
+
+/// Returns the tz3 address of the passkey credential created by
+/// `navigator.credentials.create`, given the authenticator data of the
+/// registration response (`response.getAuthenticatorData()`)
+#[wasm_bindgen]
+pub fn passkey_address(authenticator_data: &[u8]) -> Result<String, JsValue> {
+    Ok(attested_public_key(authenticator_data)?.hash())
+}
@@ -0,0 +1,148 @@
+//! Local simulation of run function operations.
+//!
+//! Operations are executed by the protocol's runtime against an in-memory host
+//! seeded from a snapshot of the accounts and KV entries the smart functions
+//! touch. Simulation is meant for instant previews: it does not check
+//! signatures or nonces, nothing is persisted, and smart functions can only
+//! reach accounts present in the snapshot. The node remains authoritative.
+
+use std::collections::BTreeMap;
+
+use jstz_core::kv::Transaction;
+use jstz_proto::{
+    context::{
+        account::{
+            Account, Address, Amount, Nonce, SmartFunctionAccount, UserAccount,
+            ACCOUNTS_PATH_PREFIX,
+        },
+        code::CodeStore,
+    },
+    operation::{Content, Operation},
+    runtime::{run_toplevel_fetch, Kv, KvValue, ParsedCode},
+};
+use serde::{Deserialize, Serialize};
+use tezos_smart_rollup::storage::path::OwnedPath;
+use tezos_smart_rollup_mock::MockHost;
+use wasm_bindgen::prelude::*;
+
+use crate::js_error;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct AccountSnapshot {
+    #[serde(default)]
+    balance: Amount,
+    #[serde(default)]
+    nonce: u64,
+    /// Required for smart functions
+    function_code: Option<String>,
+}
+
+/// State the simulation runs against
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Snapshot {
+    /// Accounts by address
+    #[serde(default)]
+    accounts: BTreeMap<String, AccountSnapshot>,
+    /// KV entries by smart function address and key
+    #[serde(default)]
+    kv: BTreeMap<String, BTreeMap<String, serde_json::Value>>,
+}
+
+impl Snapshot {
+    fn load(self, host: &MockHost, tx: &mut Transaction) -> Result<(), JsValue> {
+        for (address, snapshot) in self.accounts {
+            let account = match (Self::address(&address)?, snapshot.function_code) {
+                (Address::User(_), None) => Account::User(UserAccount {
+                    amount: snapshot.balance,
+                    nonce: Nonce(snapshot.nonce),
+                }),
+                (Address::SmartFunction(_), Some(code)) => {
+                    let code = ParsedCode::try_from(code).map_err(|e| {
+                        js_error(format!("invalid code of '{address}': {e}"))
+                    })?;
+                    Account::SmartFunction(SmartFunctionAccount {
+                        amount: snapshot.balance,
+                        nonce: Nonce(snapshot.nonce),
+                        code_hash: CodeStore::insert(host, tx, code).map_err(js_error)?,
+                    })
+                }
+                (Address::User(_), Some(_)) => {
+                    return Err(js_error(format!(
+                        "user account '{address}' cannot have function code"
+                    )))
+                }
+                (Address::SmartFunction(_), None) => {
+                    return Err(js_error(format!(
+                        "smart function '{address}' is missing its function code"
+                    )))
+                }
+            };
+            let path = OwnedPath::try_from(format!("{ACCOUNTS_PATH_PREFIX}/{address}"))
+                .map_err(js_error)?;
+            tx.insert(path, account).map_err(js_error)?;
+        }
+
+        for (address, entries) in self.kv {
+            if Self::address(&address)?.as_smart_function().is_none() {
+                return Err(js_error(format!(
+                    "KV entries must belong to a smart function, got '{address}'"
+                )));
+            }
+            let kv = Kv::new(address);
+            for (key, value) in entries {
+                kv.set(tx, &key, KvValue(value)).map_err(js_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn address(address: &str) -> Result<Address, JsValue> {
+        Address::from_base58(address)
+            .map_err(|e| js_error(format!("invalid address '{address}': {e}")))
+    }
+}
+
+/// Simulates a run function operation, as returned by `build_run_function`,
+/// against `snapshot` and returns the receipt of the run, i.e.
+/// `{ statusCode, headers, body }`.
+///
+/// The snapshot has the shape
+/// `{ accounts?: { [address]: { balance?, nonce?, functionCode? } },
+///    kv?: { [address]: { [key]: value } } }`.
+#[wasm_bindgen]
+pub fn simulate_run_function(
+    operation: JsValue,
+    snapshot: JsValue,
+) -> Result<JsValue, JsValue> {
+    let json: serde_json::Value = serde_wasm_bindgen::from_value(operation)?;
+    let operation: Operation = serde_json::from_value(json).map_err(js_error)?;
+    let snapshot: Snapshot = serde_wasm_bindgen::from_value(snapshot)?;
+
+    let source = operation.source();
+    let operation_hash = operation.hash();
+    let run = match operation.content {
+        Content::RunFunction(run) => run,
+        _ => return Err(js_error("only run function operations can be simulated")),
+    };
+
+    let mut host = MockHost::default();
+    let mut tx = Transaction::default();
+    tx.begin();
+    snapshot.load(&host, &mut tx)?;
+
+    // Smart functions have no access to I/O besides the in-memory host, so the
+    // run never waits on anything external
+    let receipt = futures::executor::block_on(run_toplevel_fetch(
+        &mut host,
+        &mut tx,
+        &source,
+        run,
+        operation_hash,
+    ))
+    .map_err(js_error)?;
+
+    let json = serde_json::to_value(&receipt).map_err(js_error)?;
+    Ok(json.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
@@ -9,7 +9,7 @@ use jstz_proto::{
 };
 use jstz_utils::{
     inbox_builder::{Account, InboxBuilder},
-    key_pair::{parse_key_file, KeyPair},
+    key_pair::{KeyPair, KeySource},
 };
 
 use clap::Parser;
@@ -33,9 +33,10 @@ struct Args {
     #[arg(long, default_value = "inbox.json")]
     inbox_file: Box<Path>,
 
-    /// Public-private key pair representing the oracle response signer. (format: {"public_key": ..., "secret_key": ...})
+    /// Public-private key pair representing the oracle response signer, either a path to a
+    /// key file or `env:<VAR>`. (format: {"public_key": ..., "secret_key": ...})
     #[arg(long)]
-    oracle_key_file: Option<Box<Path>>,
+    oracle_key_file: Option<KeySource>,
 }
 
 fn main() -> jstz_tps_bench::Result<()> {
@@ -45,8 +46,8 @@ fn main() -> jstz_tps_bench::Result<()> {
     let ticketer_addr = ContractKt1Hash::from_base58_check(&args.ticketer_address)
         .context("failed to parse ticketer address")?;
     let oracle_signer = match args.oracle_key_file {
-        Some(path) => {
-            let KeyPair(pk, sk) = parse_key_file(path.to_path_buf())?;
+        Some(source) => {
+            let KeyPair(pk, sk) = KeyPair::load(&source)?;
             Some(Account {
                 // FIXME: nonce needs to start from 1 because currently the oracle signer is also
                 // the injector and there is one large payload operation before the oracle call,
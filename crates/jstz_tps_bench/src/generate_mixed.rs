@@ -0,0 +1,376 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+use std::path::Path;
+
+use http::{HeaderMap, Method, Uri};
+use jstz_proto::context::account::Address;
+use jstz_proto::HttpBody;
+use serde::Deserialize;
+use tezos_smart_rollup::utils::inbox::file::InboxFile;
+
+use crate::generate_other::prepare_builder_and_accounts;
+use jstz_utils::inbox_builder::{Account, InboxBuilder, Result};
+
+/// Smart function serving every kind of call of a mixed workload.
+///
+/// - `/transfer?to=<address>` moves one unit of a KV ledger from the caller to `to`
+/// - `/call` answers with the size of the request body
+/// - `/kv?keys=<n>` writes the request body under `n` keys
+/// - `/check` logs the markers expected by `results`
+pub(crate) const WORKLOAD_FUNCTION: &str = r#"
+const INITIAL_BALANCE = 1000000;
+
+const balance = (address) => parseInt(Kv.get(`balance/${address}`) ?? INITIAL_BALANCE);
+
+export default async (request) => {
+  const url = new URL(request.url);
+  const caller = request.headers.get("Referer");
+  switch (url.pathname) {
+    case "/init":
+      Kv.set("initialised", "true");
+      return new Response();
+    case "/transfer": {
+      const to = url.searchParams.get("to");
+      Kv.set(`balance/${caller}`, (balance(caller) - 1).toString());
+      Kv.set(`balance/${to}`, (balance(to) + 1).toString());
+      return new Response();
+    }
+    case "/call": {
+      const body = await request.text();
+      return new Response(body.length.toString());
+    }
+    case "/kv": {
+      const keys = parseInt(url.searchParams.get("keys"));
+      const value = await request.text();
+      for (let i = 0; i < keys; i++) {
+        Kv.set(`kv/${caller}/${i}`, value);
+      }
+      return new Response();
+    }
+    case "/check":
+      console.log("Checking...");
+      if (Kv.get("initialised") !== "true") {
+        throw new Error("Workload smart function was not initialised");
+      }
+      console.log("Checks succeeded.");
+      return new Response();
+    default:
+      return new Response(null, { status: 404 });
+  }
+};
+"#;
+
+/// Smart function deployed by the deploy operations of a mixed workload
+pub(crate) const DEPLOYED_FUNCTION: &str = r#"
+export default () => new Response("deployed");
+"#;
+
+/// Mix of operations of a benchmark. Operations are drawn in proportion to their
+/// weight, and interleaved evenly so that generated inboxes are reproducible.
+/// Omitted weights are 0.
+///
+/// ```json
+/// {
+///   "transfers": 50,
+///   "deploys": 5,
+///   "calls": 30,
+///   "call_payload_sizes": [0, 1024, 16384],
+///   "kv_writes": 15,
+///   "kv_keys_per_write": 20,
+///   "kv_value_size": 64
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Workload {
+    /// Weight of transfers between accounts
+    #[serde(default)]
+    pub transfers: u32,
+    /// Weight of smart function deployments
+    #[serde(default)]
+    pub deploys: u32,
+    /// Weight of smart function calls
+    #[serde(default)]
+    pub calls: u32,
+    /// Sizes in bytes of the bodies of smart function calls, used in turn
+    #[serde(default = "default_call_payload_sizes")]
+    pub call_payload_sizes: Vec<usize>,
+    /// Weight of KV-write-heavy calls
+    #[serde(default)]
+    pub kv_writes: u32,
+    /// Keys written by every KV-write-heavy call
+    #[serde(default = "default_kv_keys_per_write")]
+    pub kv_keys_per_write: usize,
+    /// Size in bytes of the values written by KV-write-heavy calls
+    #[serde(default = "default_kv_value_size")]
+    pub kv_value_size: usize,
+}
+
+fn default_call_payload_sizes() -> Vec<usize> {
+    vec![0, 1024, 16384]
+}
+
+fn default_kv_keys_per_write() -> usize {
+    20
+}
+
+fn default_kv_value_size() -> usize {
+    64
+}
+
+impl Default for Workload {
+    fn default() -> Self {
+        Self {
+            transfers: 50,
+            deploys: 5,
+            calls: 30,
+            call_payload_sizes: default_call_payload_sizes(),
+            kv_writes: 15,
+            kv_keys_per_write: default_kv_keys_per_write(),
+            kv_value_size: default_kv_value_size(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum OperationKind {
+    Transfer,
+    Deploy,
+    Call,
+    KvWrite,
+}
+
+impl Workload {
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Kinds of `num_operations` operations, interleaved with a smooth weighted
+    /// round robin
+    pub(crate) fn schedule(&self, num_operations: usize) -> Result<Vec<OperationKind>> {
+        let weights: Vec<_> = [
+            (OperationKind::Transfer, self.transfers),
+            (OperationKind::Deploy, self.deploys),
+            (OperationKind::Call, self.calls),
+            (OperationKind::KvWrite, self.kv_writes),
+        ]
+        .into_iter()
+        .filter(|(_, weight)| *weight > 0)
+        .map(|(kind, weight)| (kind, weight as i64))
+        .collect();
+        let total: i64 = weights.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return Err("Workload must contain at least one operation kind".into());
+        }
+
+        let mut current = vec![0; weights.len()];
+        let mut schedule = Vec::with_capacity(num_operations);
+        for _ in 0..num_operations {
+            for (current, (_, weight)) in current.iter_mut().zip(&weights) {
+                *current += weight;
+            }
+            let (next, _) = current
+                .iter()
+                .enumerate()
+                .max_by_key(|(i, current)| (**current, std::cmp::Reverse(*i)))
+                .expect("weights are not empty");
+            current[next] -= total;
+            schedule.push(weights[next].0);
+        }
+        Ok(schedule)
+    }
+}
+
+/// Generate `num_operations` operations following `workload`, writing to `./inbox.json`.
+///
+/// This includes the deployment and initialisation of the workload smart function,
+/// and a check at the end. Deployments are not counted as executions by `results`,
+/// so the expected number of transfers is the number of other operations.
+pub fn handle_generate_mixed(
+    rollup_addr: &str,
+    inbox_file: &Path,
+    num_operations: usize,
+    workload: &Workload,
+) -> Result<()> {
+    let inbox = generate_inbox(rollup_addr, num_operations, workload)?;
+    inbox.save(inbox_file)?;
+    Ok(())
+}
+
+fn generate_inbox(
+    rollup_addr: &str,
+    num_operations: usize,
+    workload: &Workload,
+) -> Result<InboxFile> {
+    let schedule = workload.schedule(num_operations)?;
+    let (mut builder, mut accounts) =
+        prepare_builder_and_accounts(rollup_addr, num_operations)?;
+
+    let function_address =
+        builder.deploy_function(&mut accounts[0], WORKLOAD_FUNCTION.into(), 0)?;
+    call(
+        &mut builder,
+        &mut accounts[0],
+        &function_address,
+        "init",
+        Method::POST,
+        HttpBody::empty(),
+    )?;
+
+    let len = accounts.len();
+    let mut calls = 0;
+    for (i, kind) in schedule.into_iter().enumerate() {
+        let to = accounts[(i + 1) % len].address.clone();
+        let account = &mut accounts[i % len];
+        match kind {
+            OperationKind::Transfer => call(
+                &mut builder,
+                account,
+                &function_address,
+                &format!("transfer?to={to}"),
+                Method::POST,
+                HttpBody::empty(),
+            )?,
+            OperationKind::Deploy => {
+                builder.deploy_function(account, DEPLOYED_FUNCTION.into(), 0)?;
+            }
+            OperationKind::Call => {
+                let sizes = &workload.call_payload_sizes;
+                let size = sizes
+                    .get(calls % sizes.len().max(1))
+                    .copied()
+                    .unwrap_or_default();
+                calls += 1;
+                call(
+                    &mut builder,
+                    account,
+                    &function_address,
+                    "call",
+                    Method::POST,
+                    HttpBody::from_bytes(vec![b'x'; size]),
+                )?
+            }
+            OperationKind::KvWrite => call(
+                &mut builder,
+                account,
+                &function_address,
+                &format!("kv?keys={}", workload.kv_keys_per_write),
+                Method::POST,
+                HttpBody::from_bytes(vec![b'v'; workload.kv_value_size]),
+            )?,
+        }
+    }
+
+    call(
+        &mut builder,
+        &mut accounts[0],
+        &function_address,
+        "check",
+        Method::GET,
+        HttpBody::empty(),
+    )?;
+
+    Ok(builder.build())
+}
+
+fn call(
+    builder: &mut InboxBuilder,
+    account: &mut Account,
+    function_address: &Address,
+    path: &str,
+    method: Method,
+    body: HttpBody,
+) -> Result<()> {
+    builder.run_function(
+        account,
+        Uri::try_from(format!("jstz://{function_address}/{path}"))?,
+        method,
+        HeaderMap::default(),
+        body,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_inbox, OperationKind, Workload};
+
+    const ROLLUP_ADDRESS: &str = "sr163Lv22CdE8QagCwf48PWDTquk6isQwv57";
+
+    #[test]
+    fn schedule_interleaves_by_weight() {
+        let workload = Workload {
+            transfers: 2,
+            deploys: 0,
+            calls: 1,
+            kv_writes: 1,
+            ..Default::default()
+        };
+
+        let schedule = workload.schedule(8).unwrap();
+        assert_eq!(
+            schedule,
+            [
+                OperationKind::Transfer,
+                OperationKind::Call,
+                OperationKind::KvWrite,
+                OperationKind::Transfer,
+            ]
+            .repeat(2)
+        );
+    }
+
+    #[test]
+    fn schedule_follows_default_weights() {
+        let schedule = Workload::default().schedule(100).unwrap();
+        let count = |kind| schedule.iter().filter(|k| **k == kind).count();
+
+        assert_eq!(count(OperationKind::Transfer), 50);
+        assert_eq!(count(OperationKind::Deploy), 5);
+        assert_eq!(count(OperationKind::Call), 30);
+        assert_eq!(count(OperationKind::KvWrite), 15);
+    }
+
+    #[test]
+    fn schedule_requires_an_operation_kind() {
+        let workload = Workload {
+            transfers: 0,
+            deploys: 0,
+            calls: 0,
+            kv_writes: 0,
+            ..Default::default()
+        };
+        assert!(workload.schedule(1).is_err());
+    }
+
+    #[test]
+    fn workload_defaults_omitted_fields() {
+        let workload: Workload = serde_json::from_str(r#"{ "calls": 1 }"#).unwrap();
+        assert_eq!(
+            workload,
+            Workload {
+                transfers: 0,
+                deploys: 0,
+                calls: 1,
+                kv_writes: 0,
+                ..Default::default()
+            }
+        );
+        assert!(serde_json::from_str::<Workload>(r#"{ "reads": 1 }"#).is_err());
+    }
+
+    #[test]
+    fn generates_one_message_per_operation() {
+        let workload = Workload {
+            call_payload_sizes: vec![0, 16],
+            ..Default::default()
+        };
+
+        let inbox = generate_inbox(ROLLUP_ADDRESS, 20, &workload).unwrap();
+        assert_eq!(inbox.0.len(), 1);
+        // Deployment and initialisation of the workload function, then the
+        // operations and the final check
+        assert_eq!(inbox.0[0].len(), 2 + 20 + 1);
+    }
+}
@@ -1,4 +1,7 @@
 pub mod fa2_bench_generator;
+pub mod generate_mixed;
 pub mod generate_other;
+pub mod live;
+pub mod report;
 pub mod results;
 pub use jstz_utils::inbox_builder::Result;
@@ -0,0 +1,342 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail};
+use http::{HeaderMap, Method, Uri};
+use jstz_client::JstzClient;
+use jstz_proto::{
+    context::account::Address,
+    operation::{Content, DeployFunction, RunFunction, SignedOperation},
+    receipt::{ReceiptContent, ReceiptResult},
+    HttpBody,
+};
+use jstz_utils::inbox_builder::{Account, DEFAULT_GAS_LIMIT};
+use tokio::task::JoinSet;
+
+use crate::generate_mixed::{
+    OperationKind, Workload, DEPLOYED_FUNCTION, WORKLOAD_FUNCTION,
+};
+use crate::results::{Latencies, HISTOGRAM_BUCKETS};
+use crate::Result;
+
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How operations are submitted to the node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadMode {
+    /// Submits operations at a fixed rate per second, whether or not previous
+    /// operations completed
+    Open { rate: f64 },
+    /// Keeps `concurrency` operations in flight, each client submitting its next
+    /// operation once the previous one has a receipt
+    Closed { concurrency: usize },
+}
+
+/// Benchmarks a running jstz node by submitting `num_operations` operations of
+/// `workload` over HTTP and measuring the time from submission to receipt.
+///
+/// The workload smart function is deployed and initialised first, which is not
+/// measured. Operations are signed by `accounts` accounts in turn, or by one
+/// account per client in closed loop mode.
+pub fn handle_live(
+    endpoint: &str,
+    num_operations: usize,
+    workload: &Workload,
+    mode: LoadMode,
+    accounts: usize,
+    receipt_timeout: Duration,
+) -> Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    let report = runtime.block_on(run(
+        endpoint,
+        num_operations,
+        workload,
+        mode,
+        accounts,
+        receipt_timeout,
+    ))?;
+    println!("{report}");
+    print!("{}", report.latencies.histogram(HISTOGRAM_BUCKETS));
+    Ok(())
+}
+
+struct Report {
+    submitted: usize,
+    failed: usize,
+    duration: Duration,
+    latencies: Latencies,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let succeeded = self.submitted - self.failed;
+        write!(
+            f,
+            "{succeeded} / {} operations succeeded in {:?} @ {:.3} TPS, latency p50 {:?} / p95 {:?} / p99 {:?}",
+            self.submitted,
+            self.duration,
+            succeeded as f64 / self.duration.as_secs_f64(),
+            self.latencies.percentile(50.0),
+            self.latencies.percentile(95.0),
+            self.latencies.percentile(99.0),
+        )
+    }
+}
+
+async fn run(
+    endpoint: &str,
+    num_operations: usize,
+    workload: &Workload,
+    mode: LoadMode,
+    accounts: usize,
+    receipt_timeout: Duration,
+) -> Result<Report> {
+    let schedule = workload.schedule(num_operations)?;
+    let client = Arc::new(JstzClient::new(endpoint.to_string()));
+
+    let accounts = match mode {
+        LoadMode::Open { .. } => accounts,
+        LoadMode::Closed { concurrency } => concurrency,
+    };
+    if accounts == 0 {
+        return Err("At least one account is required".into());
+    }
+    let mut accounts = (0..accounts)
+        .map(Account::from_index)
+        .collect::<Result<Vec<_>>>()?;
+    for account in accounts.iter_mut() {
+        account.nonce = client.get_nonce(&account.address).await?;
+    }
+
+    let function_address = setup(&client, &mut accounts[0], receipt_timeout).await?;
+
+    // Operations are signed upfront so that signing does not count against the
+    // submission rate
+    let len = accounts.len();
+    let mut operations: Vec<Vec<SignedOperation>> = (0..len).map(|_| vec![]).collect();
+    let mut calls = 0;
+    for (i, kind) in schedule.into_iter().enumerate() {
+        let to = accounts[(i + 1) % len].address.clone();
+        let account = &mut accounts[i % len];
+        let content = match kind {
+            OperationKind::Transfer => {
+                run_function(&function_address, &format!("transfer?to={to}"), vec![])?
+            }
+            OperationKind::Deploy => Content::DeployFunction(DeployFunction {
+                function_code: DEPLOYED_FUNCTION.to_string(),
+                account_credit: 0,
+                interface: None,
+            }),
+            OperationKind::Call => {
+                let sizes = &workload.call_payload_sizes;
+                let size = sizes
+                    .get(calls % sizes.len().max(1))
+                    .copied()
+                    .unwrap_or_default();
+                calls += 1;
+                run_function(&function_address, "call", vec![b'x'; size])?
+            }
+            OperationKind::KvWrite => run_function(
+                &function_address,
+                &format!("kv?keys={}", workload.kv_keys_per_write),
+                vec![b'v'; workload.kv_value_size],
+            )?,
+        };
+        operations[i % len].push(account.sign_operation(content)?);
+        account.nonce = account.nonce.next();
+    }
+
+    let start = Instant::now();
+    let results = match mode {
+        LoadMode::Open { rate } => {
+            open_loop(client, operations, rate, receipt_timeout).await?
+        }
+        LoadMode::Closed { .. } => closed_loop(client, operations, receipt_timeout).await,
+    };
+    let duration = start.elapsed();
+
+    let submitted = results.len();
+    let mut latencies = vec![];
+    for result in results {
+        match result {
+            Ok(latency) => latencies.push(latency),
+            Err(e) => eprintln!("Operation failed: {e:#}"),
+        }
+    }
+    Ok(Report {
+        submitted,
+        failed: submitted - latencies.len(),
+        duration,
+        latencies: Latencies::new(latencies),
+    })
+}
+
+/// Deploys and initialises the workload smart function
+async fn setup(
+    client: &JstzClient,
+    account: &mut Account,
+    receipt_timeout: Duration,
+) -> Result<Address> {
+    let deploy = account.sign_operation(Content::DeployFunction(DeployFunction {
+        function_code: WORKLOAD_FUNCTION.to_string(),
+        account_credit: 0,
+        interface: None,
+    }))?;
+    account.nonce = account.nonce.next();
+    let function_address = match submit(client, &deploy, receipt_timeout).await? {
+        ReceiptContent::DeployFunction(receipt) => {
+            Address::SmartFunction(receipt.address)
+        }
+        _ => return Err("Expected a deploy function receipt".into()),
+    };
+
+    let init =
+        account.sign_operation(run_function(&function_address, "init", vec![])?)?;
+    account.nonce = account.nonce.next();
+    submit(client, &init, receipt_timeout).await?;
+    Ok(function_address)
+}
+
+fn run_function(
+    function_address: &Address,
+    path: &str,
+    body: Vec<u8>,
+) -> Result<Content> {
+    Ok(Content::RunFunction(RunFunction {
+        uri: Uri::try_from(format!("jstz://{function_address}/{path}"))?,
+        method: Method::POST,
+        headers: HeaderMap::default(),
+        body: if body.is_empty() {
+            HttpBody::empty()
+        } else {
+            HttpBody::from_bytes(body)
+        },
+        gas_limit: DEFAULT_GAS_LIMIT.try_into()?,
+    }))
+}
+
+/// Submits `operation` and waits for its receipt
+async fn submit(
+    client: &JstzClient,
+    operation: &SignedOperation,
+    receipt_timeout: Duration,
+) -> anyhow::Result<ReceiptContent> {
+    client.post_operation(operation).await?;
+    wait_for_receipt(client, operation, receipt_timeout).await
+}
+
+/// Polls the receipt of `operation` more often than
+/// [`JstzClient::wait_for_operation_receipt`] so that latencies are not rounded
+/// up to its polling interval
+async fn wait_for_receipt(
+    client: &JstzClient,
+    operation: &SignedOperation,
+    receipt_timeout: Duration,
+) -> anyhow::Result<ReceiptContent> {
+    let hash = operation.hash();
+    let deadline = Instant::now() + receipt_timeout;
+    loop {
+        if let Some(receipt) = client.get_operation_receipt(&hash).await? {
+            return match receipt.result {
+                ReceiptResult::Success(content) => Ok(content),
+                ReceiptResult::Failed(e) => Err(anyhow!("operation {hash} failed: {e}")),
+            };
+        }
+        if Instant::now() >= deadline {
+            bail!("no receipt for operation {hash} within {receipt_timeout:?}");
+        }
+        tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+    }
+}
+
+/// Submits the operations of all accounts in turn at `rate` per second
+async fn open_loop(
+    client: Arc<JstzClient>,
+    operations: Vec<Vec<SignedOperation>>,
+    rate: f64,
+    receipt_timeout: Duration,
+) -> Result<Vec<anyhow::Result<Duration>>> {
+    if rate.is_nan() || rate <= 0.0 {
+        return Err("Rate must be greater than zero".into());
+    }
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / rate));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Burst);
+
+    // Interleave the accounts, keeping the nonce order of every account
+    let mut queues: Vec<_> = operations.into_iter().map(Vec::into_iter).collect();
+    let mut pending = JoinSet::new();
+    let mut results = vec![];
+    loop {
+        let mut submitted = false;
+        for queue in queues.iter_mut() {
+            let Some(operation) = queue.next() else {
+                continue;
+            };
+            submitted = true;
+            interval.tick().await;
+            let start = Instant::now();
+            // Posted in order, so that no operation arrives before the previous
+            // nonce of its account
+            if let Err(e) = client.post_operation(&operation).await {
+                results.push(Err(e));
+                continue;
+            }
+            let client = client.clone();
+            pending.spawn(async move {
+                wait_for_receipt(&client, &operation, receipt_timeout)
+                    .await
+                    .map(|_| start.elapsed())
+            });
+        }
+        if !submitted {
+            break;
+        }
+    }
+    while let Some(result) = pending.join_next().await {
+        match result {
+            Ok(result) => results.push(result),
+            Err(e) => results.push(Err(e.into())),
+        }
+    }
+    Ok(results)
+}
+
+/// Runs one client per account, each submitting its next operation once the
+/// previous one has a receipt
+async fn closed_loop(
+    client: Arc<JstzClient>,
+    operations: Vec<Vec<SignedOperation>>,
+    receipt_timeout: Duration,
+) -> Vec<anyhow::Result<Duration>> {
+    let mut clients = JoinSet::new();
+    for operations in operations {
+        let client = client.clone();
+        clients.spawn(async move {
+            let mut results = vec![];
+            for operation in operations {
+                let start = Instant::now();
+                let result = submit(&client, &operation, receipt_timeout)
+                    .await
+                    .map(|_| start.elapsed());
+                results.push(result);
+            }
+            results
+        });
+    }
+
+    let mut results = vec![];
+    while let Some(client_results) = clients.join_next().await {
+        match client_results {
+            Ok(client_results) => results.extend(client_results),
+            Err(e) => results.push(Err(e.into())),
+        }
+    }
+    results
+}
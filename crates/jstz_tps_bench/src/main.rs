@@ -2,13 +2,18 @@
 //
 // SPDX-License-Identifier: MIT
 use std::path::Path;
+use std::time::Duration;
 
 use clap::{Parser, Subcommand};
 use jstz_tps_bench::fa2_bench_generator::{handle_generate, handle_generate_script};
+use jstz_tps_bench::generate_mixed::{handle_generate_mixed, Workload};
 use jstz_tps_bench::generate_other::handle_generate_other;
+use jstz_tps_bench::live::{handle_live, LoadMode};
+use jstz_tps_bench::report::{handle_compare, Format};
 use jstz_tps_bench::results::handle_results;
 
 const DEFAULT_ROLLUP_ADDRESS: &str = "sr163Lv22CdE8QagCwf48PWDTquk6isQwv57";
+const DEFAULT_JSTZ_NODE_ENDPOINT: &str = "http://127.0.0.1:8933";
 
 #[derive(Debug, Parser)]
 #[command(long_about = None)]
@@ -41,6 +46,49 @@ enum Commands {
         log_file: Vec<Box<Path>>,
         #[arg(long)]
         expected_transfers: usize,
+        /// Also write the results and metadata of the runs to this file
+        #[arg(long)]
+        output: Option<Box<Path>>,
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+    },
+    #[command(about = "Compare two JSON result files and flag regressions")]
+    Compare {
+        #[arg(long)]
+        baseline: Box<Path>,
+        #[arg(long)]
+        candidate: Box<Path>,
+        /// Change in percent beyond which a worse TPS or latency is a regression
+        #[arg(long, default_value_t = 5.0)]
+        threshold: f64,
+    },
+    #[command(
+        about = "Submit operations to a running jstz node and measure receipt latency"
+    )]
+    Live {
+        #[arg(long, default_value = DEFAULT_JSTZ_NODE_ENDPOINT)]
+        endpoint: String,
+        #[arg(long)]
+        num_operations: usize,
+        /// JSON file with the weights of every kind of operation (see `generate mixed`)
+        #[arg(long)]
+        workload: Option<Box<Path>>,
+        /// Submit operations at this rate per second (open loop)
+        #[arg(
+            long,
+            required_unless_present = "concurrency",
+            conflicts_with = "concurrency"
+        )]
+        rate: Option<f64>,
+        /// Keep this many operations in flight (closed loop)
+        #[arg(long)]
+        concurrency: Option<usize>,
+        /// Accounts signing the operations in open loop
+        #[arg(long, default_value_t = 10)]
+        accounts: usize,
+        /// Seconds to wait for the receipt of an operation
+        #[arg(long, default_value_t = 60)]
+        receipt_timeout: u64,
     },
 }
 
@@ -72,6 +120,19 @@ enum GenerateCommands {
         #[arg(long)]
         check_endpoint: Option<String>,
     },
+    #[command(about = "Generate a mix of transfers, deployments, calls and KV writes")]
+    Mixed {
+        #[arg(long, default_value = DEFAULT_ROLLUP_ADDRESS)]
+        address: String,
+        #[arg(long)]
+        num_operations: usize,
+        #[arg(long, default_value = "inbox.json")]
+        inbox_file: Box<Path>,
+        /// JSON file with the weights of every kind of operation. Uses a mix of
+        /// all kinds if unset.
+        #[arg(long)]
+        workload: Option<Box<Path>>,
+    },
 }
 
 fn main() -> jstz_tps_bench::Result<()> {
@@ -99,6 +160,18 @@ fn main() -> jstz_tps_bench::Result<()> {
                 run_endpoint.as_deref(),
                 check_endpoint.as_deref(),
             )?,
+            GenerateCommands::Mixed {
+                address,
+                num_operations,
+                inbox_file,
+                workload,
+            } => {
+                let workload = match workload {
+                    Some(path) => Workload::load(&path)?,
+                    None => Workload::default(),
+                };
+                handle_generate_mixed(&address, &inbox_file, num_operations, &workload)?
+            }
         },
         Commands::GenerateScript {
             address,
@@ -109,7 +182,46 @@ fn main() -> jstz_tps_bench::Result<()> {
             inbox_file,
             log_file,
             expected_transfers,
-        } => handle_results(inbox_file, log_file, expected_transfers)?,
+            output,
+            format,
+        } => handle_results(
+            inbox_file,
+            log_file,
+            expected_transfers,
+            output.as_deref().map(|path| (path, format)),
+        )?,
+        Commands::Compare {
+            baseline,
+            candidate,
+            threshold,
+        } => handle_compare(&baseline, &candidate, threshold)?,
+        Commands::Live {
+            endpoint,
+            num_operations,
+            workload,
+            rate,
+            concurrency,
+            accounts,
+            receipt_timeout,
+        } => {
+            let workload = match workload {
+                Some(path) => Workload::load(&path)?,
+                None => Workload::default(),
+            };
+            let mode = match (rate, concurrency) {
+                (Some(rate), _) => LoadMode::Open { rate },
+                (None, Some(concurrency)) => LoadMode::Closed { concurrency },
+                (None, None) => unreachable!("clap requires --rate or --concurrency"),
+            };
+            handle_live(
+                &endpoint,
+                num_operations,
+                &workload,
+                mode,
+                accounts,
+                Duration::from_secs(receipt_timeout),
+            )?
+        }
     }
 
     Ok(())
@@ -0,0 +1,306 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+use std::fmt::Write as _;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+
+/// Format of exported results
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+/// Results of a benchmark, as exported by `results --output` and read by `compare`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub metadata: Metadata,
+    pub runs: Vec<Summary>,
+    pub aggregate: Summary,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Unix timestamp in seconds at which the results were extracted
+    pub timestamp: u64,
+    /// Commit of the working tree, if it is a git repository
+    pub commit: Option<String>,
+    pub runtime: String,
+    pub inbox_file: String,
+    pub log_files: Vec<String>,
+    pub expected_transfers: usize,
+}
+
+impl Metadata {
+    pub fn new(
+        inbox_file: &Path,
+        log_files: &[Box<Path>],
+        expected_transfers: usize,
+    ) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            commit: git_commit(),
+            runtime: if cfg!(feature = "v2_runtime") {
+                "v2"
+            } else {
+                "v1"
+            }
+            .to_string(),
+            inbox_file: inbox_file.display().to_string(),
+            log_files: log_files.iter().map(|p| p.display().to_string()).collect(),
+            expected_transfers,
+        }
+    }
+}
+
+fn git_commit() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Metrics of a run, durations in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub transfers: usize,
+    pub duration_ms: f64,
+    pub tps: f64,
+    pub latency_p50_ms: f64,
+    pub latency_p95_ms: f64,
+    pub latency_p99_ms: f64,
+}
+
+const CSV_HEADER: &str =
+    "run,transfers,duration_ms,tps,latency_p50_ms,latency_p95_ms,latency_p99_ms";
+
+impl Report {
+    /// Writes the report to `path`. CSV files start with the metadata as `#`
+    /// comments, followed by a row per run and a final `aggregate` row.
+    pub fn save(&self, path: &Path, format: Format) -> Result<()> {
+        let content = match format {
+            Format::Json => serde_json::to_string_pretty(self)?,
+            Format::Csv => self.to_csv()?,
+        };
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    fn to_csv(&self) -> Result<String> {
+        let mut csv = String::new();
+        let metadata = serde_json::to_value(&self.metadata)?;
+        for (key, value) in metadata.as_object().into_iter().flatten() {
+            writeln!(csv, "# {key}: {value}")?;
+        }
+        writeln!(csv, "{CSV_HEADER}")?;
+        let rows = self
+            .runs
+            .iter()
+            .enumerate()
+            .map(|(i, run)| ((i + 1).to_string(), run))
+            .chain([("aggregate".to_string(), &self.aggregate)]);
+        for (run, s) in rows {
+            writeln!(
+                csv,
+                "{run},{},{:.3},{:.3},{:.3},{:.3},{:.3}",
+                s.transfers,
+                s.duration_ms,
+                s.tps,
+                s.latency_p50_ms,
+                s.latency_p95_ms,
+                s.latency_p99_ms
+            )?;
+        }
+        Ok(csv)
+    }
+}
+
+/// Compares the aggregates of two exported result files. A metric regresses if
+/// it is worse in `candidate` than in `baseline` by more than `threshold`
+/// percent: lower TPS or higher latency. Fails if any metric regressed.
+pub fn handle_compare(baseline: &Path, candidate: &Path, threshold: f64) -> Result<()> {
+    let baseline = Report::load(baseline)?;
+    let candidate = Report::load(candidate)?;
+    let (before, after) = (&baseline.aggregate, &candidate.aggregate);
+
+    let metrics = [
+        ("TPS", before.tps, after.tps, true),
+        (
+            "latency p50 (ms)",
+            before.latency_p50_ms,
+            after.latency_p50_ms,
+            false,
+        ),
+        (
+            "latency p95 (ms)",
+            before.latency_p95_ms,
+            after.latency_p95_ms,
+            false,
+        ),
+        (
+            "latency p99 (ms)",
+            before.latency_p99_ms,
+            after.latency_p99_ms,
+            false,
+        ),
+    ];
+
+    println!(
+        "Baseline: {} @ {}",
+        baseline
+            .metadata
+            .commit
+            .as_deref()
+            .unwrap_or("unknown commit"),
+        baseline.metadata.timestamp
+    );
+    println!(
+        "Candidate: {} @ {}",
+        candidate
+            .metadata
+            .commit
+            .as_deref()
+            .unwrap_or("unknown commit"),
+        candidate.metadata.timestamp
+    );
+
+    let mut regressions = vec![];
+    for (name, before, after, higher_is_better) in metrics {
+        let change = relative_change(before, after);
+        let regressed = if higher_is_better {
+            change < -threshold
+        } else {
+            change > threshold
+        };
+        println!(
+            "{name:<18} {before:>12.3} -> {after:>12.3} ({change:+.2}%){}",
+            if regressed { "  REGRESSION" } else { "" }
+        );
+        if regressed {
+            regressions.push(name);
+        }
+    }
+
+    if !regressions.is_empty() {
+        return Err(format!(
+            "{} regressed by more than {threshold}%",
+            regressions.join(", ")
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Change from `before` to `after` in percent
+fn relative_change(before: f64, after: f64) -> f64 {
+    if before == 0.0 {
+        return if after == 0.0 { 0.0 } else { f64::INFINITY };
+    }
+    (after - before) / before * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::{handle_compare, relative_change, Format, Metadata, Report, Summary};
+
+    fn summary(tps: f64, latency_ms: f64) -> Summary {
+        Summary {
+            transfers: 10,
+            duration_ms: 1000.0,
+            tps,
+            latency_p50_ms: latency_ms,
+            latency_p95_ms: latency_ms * 2.0,
+            latency_p99_ms: latency_ms * 3.0,
+        }
+    }
+
+    fn report(tps: f64, latency_ms: f64) -> Report {
+        Report {
+            metadata: Metadata {
+                timestamp: 1,
+                commit: Some("abc".to_string()),
+                runtime: "v2".to_string(),
+                inbox_file: "inbox.json".to_string(),
+                log_files: vec!["log.txt".to_string()],
+                expected_transfers: 10,
+            },
+            runs: vec![summary(tps, latency_ms)],
+            aggregate: summary(tps, latency_ms),
+        }
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("report.json");
+        let report = report(10.0, 5.0);
+
+        report.save(&path, Format::Json).unwrap();
+        assert_eq!(Report::load(&path).unwrap(), report);
+    }
+
+    #[test]
+    fn csv_has_metadata_runs_and_aggregate() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("report.csv");
+
+        report(10.0, 5.0).save(&path, Format::Csv).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert!(lines.contains(&r#"# commit: "abc""#));
+        let header = lines.iter().position(|l| l.starts_with("run,")).unwrap();
+        assert_eq!(
+            &lines[header + 1..],
+            [
+                "1,10,1000.000,10.000,5.000,10.000,15.000",
+                "aggregate,10,1000.000,10.000,5.000,10.000,15.000",
+            ]
+        );
+    }
+
+    #[test]
+    fn compare_fails_on_regressions() {
+        let dir = TempDir::new().unwrap();
+        let save = |name: &str, report: Report| {
+            let path = dir.path().join(name);
+            report.save(&path, Format::Json).unwrap();
+            path
+        };
+        let baseline = save("baseline.json", report(100.0, 10.0));
+        let faster = save("faster.json", report(120.0, 8.0));
+        let slower = save("slower.json", report(80.0, 10.0));
+        let laggier = save("laggier.json", report(100.0, 12.0));
+
+        assert!(handle_compare(&baseline, &faster, 5.0).is_ok());
+        assert!(handle_compare(&baseline, &slower, 5.0).is_err());
+        assert!(handle_compare(&baseline, &slower, 25.0).is_ok());
+        assert!(handle_compare(&baseline, &laggier, 5.0).is_err());
+    }
+
+    #[test]
+    fn relative_change_handles_zero() {
+        assert_eq!(relative_change(100.0, 110.0), 10.0);
+        assert_eq!(relative_change(0.0, 0.0), 0.0);
+        assert_eq!(relative_change(0.0, 1.0), f64::INFINITY);
+    }
+}
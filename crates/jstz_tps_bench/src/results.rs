@@ -11,6 +11,7 @@ use regex::Regex;
 use serde::Deserialize;
 use tezos_smart_rollup::utils::inbox::file::{InboxFile, Message};
 
+use crate::report::{Format, Metadata, Report, Summary};
 use crate::Result;
 
 // Three sets of messages:
@@ -20,12 +21,18 @@ use crate::Result;
 // ... but all contained in one level
 const EXPECTED_LEVELS: usize = 1;
 
+pub(crate) const HISTOGRAM_BUCKETS: usize = 10;
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Extracts the results of the benchmark runs logged in `all_logs`. If `output` is
+/// given, the results and metadata of the runs are also written there.
 pub fn handle_results(
-    inbox: Box<Path>,
+    inbox_file: Box<Path>,
     all_logs: Vec<Box<Path>>,
     expected_transfers: usize,
+    output: Option<(&Path, Format)>,
 ) -> Result<()> {
-    let inbox = InboxFile::load(&inbox)?;
+    let inbox = InboxFile::load(&inbox_file)?;
 
     let all_metrics = all_logs
         .iter()
@@ -61,6 +68,7 @@ pub fn handle_results(
         })
         .collect::<Result<Vec<_>>>()?;
 
+    let agg_metrics = TransferMetrics::aggregate(&all_metrics);
     if all_metrics.len() > 1 {
         let len = all_metrics.len();
 
@@ -68,17 +76,28 @@ pub fn handle_results(
             println!("Run {} / {len} => {metrics}", num + 1);
         }
 
-        let agg_metrics = TransferMetrics::aggregate(&all_metrics);
         println!("\nAggregate => {agg_metrics}");
+        print!("{}", agg_metrics.latencies.histogram(HISTOGRAM_BUCKETS));
     } else if let Some(metrics) = all_metrics.first() {
         println!("{metrics}");
+        print!("{}", metrics.latencies.histogram(HISTOGRAM_BUCKETS));
+    }
+
+    if let Some((path, format)) = output {
+        let report = Report {
+            metadata: Metadata::new(&inbox_file, &all_logs, expected_transfers),
+            runs: all_metrics.iter().map(TransferMetrics::summary).collect(),
+            aggregate: agg_metrics.summary(),
+        };
+        report.save(path, format)?;
     }
 
     Ok(())
 }
 
 fn check_deploy(level: &Level) -> Result<()> {
-    if level.deployments.len() != 1 {
+    // Mixed workloads deploy further smart functions during the benchmark
+    if level.deployments.is_empty() {
         return Err("Expected contract deployment".into());
     }
 
@@ -94,18 +113,38 @@ struct TransferMetrics {
     transfers: usize,
     duration: Duration,
     tps: f64,
+    latencies: Latencies,
 }
 
 impl TransferMetrics {
+    fn summary(&self) -> Summary {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        Summary {
+            transfers: self.transfers,
+            duration_ms: ms(self.duration),
+            tps: self.tps,
+            latency_p50_ms: ms(self.latencies.percentile(50.0)),
+            latency_p95_ms: ms(self.latencies.percentile(95.0)),
+            latency_p99_ms: ms(self.latencies.percentile(99.0)),
+        }
+    }
+
     fn aggregate(metrics: &[TransferMetrics]) -> TransferMetrics {
         let summed = metrics.iter().fold(Self::default(), |acc, m| Self {
             transfers: acc.transfers + m.transfers,
             duration: acc.duration + m.duration,
             tps: acc.tps + m.tps,
+            latencies: Latencies::default(),
         });
 
         Self {
             tps: summed.tps / metrics.len() as f64,
+            latencies: Latencies::new(
+                metrics
+                    .iter()
+                    .flat_map(|m| m.latencies.0.iter().copied())
+                    .collect(),
+            ),
             ..summed
         }
     }
@@ -115,12 +154,79 @@ impl fmt::Display for TransferMetrics {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{} transfers took {:?} @ {:.3} TPS",
-            self.transfers, self.duration, self.tps
+            "{} transfers took {:?} @ {:.3} TPS, latency p50 {:?} / p95 {:?} / p99 {:?}",
+            self.transfers,
+            self.duration,
+            self.tps,
+            self.latencies.percentile(50.0),
+            self.latencies.percentile(95.0),
+            self.latencies.percentile(99.0),
         )
     }
 }
 
+/// Latencies of operations, from the start of their inbox level to their receipt,
+/// sorted in ascending order
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Latencies(Vec<Duration>);
+
+impl Latencies {
+    pub(crate) fn new(mut latencies: Vec<Duration>) -> Self {
+        latencies.sort();
+        Self(latencies)
+    }
+
+    /// Nearest-rank percentile, `p` being between 0 and 100
+    pub(crate) fn percentile(&self, p: f64) -> Duration {
+        if self.0.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (p / 100.0 * self.0.len() as f64).ceil() as usize;
+        self.0[rank.clamp(1, self.0.len()) - 1]
+    }
+
+    /// Counts the latencies in `buckets` buckets of equal width, the last one
+    /// ending at the maximum latency
+    pub(crate) fn histogram(&self, buckets: usize) -> Histogram {
+        let Some(max) = self.0.last() else {
+            return Histogram(vec![]);
+        };
+        let width = (max.as_nanos() / buckets as u128).max(1);
+        let mut counts = vec![0; buckets];
+        for latency in &self.0 {
+            let bucket = (latency.as_nanos() / width) as usize;
+            counts[bucket.min(buckets - 1)] += 1;
+        }
+        Histogram(
+            counts
+                .into_iter()
+                .enumerate()
+                .map(|(i, count)| {
+                    let upper = match i + 1 {
+                        n if n == buckets => *max,
+                        n => Duration::from_nanos((width * n as u128) as u64),
+                    };
+                    (upper, count)
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Upper bound and count of every bucket
+pub(crate) struct Histogram(Vec<(Duration, usize)>);
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let max_count = self.0.iter().map(|(_, count)| *count).max().unwrap_or(0);
+        for (upper, count) in &self.0 {
+            let bar = "#".repeat(count * HISTOGRAM_WIDTH / max_count.max(1));
+            writeln!(f, "  <= {:>12} | {count:>6} {bar}", format!("{upper:.3?}"))?;
+        }
+        Ok(())
+    }
+}
+
 fn check_transfer_metrics(
     level: &Level,
     expected_transfers: usize,
@@ -139,10 +245,22 @@ fn check_transfer_metrics(
     let duration = level.executions[transfers].elapsed - level.executions[0].elapsed;
     let tps = (transfers as f64) / duration.as_secs_f64();
 
+    let start = level
+        .start
+        .as_ref()
+        .ok_or("Level is missing its StartOfLevel message")?;
+    let latencies = Latencies::new(
+        level.executions[1..]
+            .iter()
+            .map(|execution| execution.elapsed.saturating_sub(start.elapsed))
+            .collect(),
+    );
+
     Ok(TransferMetrics {
         transfers,
         duration,
         tps,
+        latencies,
     })
 }
 
@@ -245,13 +363,14 @@ fn logs_to_levels(logs: Vec<LogType>) -> Result<Vec<Level>> {
     let mut checks = Vec::new();
     for line in logs.into_iter() {
         match line {
-            LogType::StartOfLevel(_) => {
+            LogType::StartOfLevel(l) => {
                 if level != Level::default() {
                     return Err(format!(
                         "StartOfLevel message not at start of level {level:?}"
                     )
                     .into());
                 }
+                level.start = Some(l);
             }
             LogType::EndOfLevel(_) => {
                 levels.push(level);
@@ -306,7 +425,7 @@ impl LogLine {
 
 #[derive(Debug)]
 enum LogType {
-    StartOfLevel(#[allow(unused)] LogLine),
+    StartOfLevel(LogLine),
     Deploy(LogLine),
     Success(LogLine),
     EndOfLevel(#[allow(unused)] LogLine),
@@ -324,7 +443,90 @@ const LOG: &str = "[JSTZ:SMART_FUNCTION:LOG]";
 
 #[derive(Default, Debug, PartialEq)]
 struct Level {
+    start: Option<LogLine>,
     deployments: Vec<LogLine>,
     executions: Vec<LogLine>,
     checks: Vec<LogLine>,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{check_transfer_metrics, Latencies, Level, LogLine, TransferMetrics};
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn line(elapsed: u64) -> LogLine {
+        LogLine {
+            elapsed: ms(elapsed),
+            message: String::new(),
+        }
+    }
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let latencies = Latencies::new((1..=100).rev().map(ms).collect());
+
+        assert_eq!(latencies.percentile(0.0), ms(1));
+        assert_eq!(latencies.percentile(50.0), ms(50));
+        assert_eq!(latencies.percentile(95.0), ms(95));
+        assert_eq!(latencies.percentile(99.0), ms(99));
+        assert_eq!(latencies.percentile(100.0), ms(100));
+        assert_eq!(Latencies::default().percentile(50.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn histogram_counts_every_latency() {
+        let latencies = Latencies::new(vec![ms(1), ms(2), ms(3), ms(9), ms(10)]);
+
+        let histogram = latencies.histogram(2);
+        assert_eq!(histogram.0, vec![(ms(5), 3), (ms(10), 2)]);
+        assert!(Latencies::default().histogram(2).0.is_empty());
+
+        let output = histogram.to_string();
+        assert_eq!(output.lines().count(), 2);
+        assert!(output.lines().next().unwrap().ends_with(&"#".repeat(40)));
+    }
+
+    #[test]
+    fn transfer_latencies_start_at_level_start() {
+        let level = Level {
+            start: Some(line(100)),
+            deployments: vec![line(110)],
+            executions: vec![line(120), line(130), line(150), line(200)],
+            checks: vec![],
+        };
+
+        let metrics = check_transfer_metrics(&level, 3).unwrap();
+        assert_eq!(metrics.transfers, 3);
+        assert_eq!(metrics.duration, ms(80));
+        assert_eq!(metrics.latencies.0, vec![ms(30), ms(50), ms(100)]);
+
+        assert!(check_transfer_metrics(&level, 2).is_err());
+        let level = Level {
+            start: None,
+            ..level
+        };
+        assert!(check_transfer_metrics(&level, 3).is_err());
+    }
+
+    #[test]
+    fn aggregate_merges_latencies() {
+        let run = |latencies: Vec<u64>, tps: f64| TransferMetrics {
+            transfers: latencies.len(),
+            duration: ms(10),
+            tps,
+            latencies: Latencies::new(latencies.into_iter().map(ms).collect()),
+        };
+
+        let aggregate =
+            TransferMetrics::aggregate(&[run(vec![3, 1], 10.0), run(vec![2], 20.0)]);
+        assert_eq!(aggregate.transfers, 3);
+        assert_eq!(aggregate.duration, ms(20));
+        assert_eq!(aggregate.tps, 15.0);
+        assert_eq!(aggregate.latencies.0, vec![ms(1), ms(2), ms(3)]);
+    }
+}
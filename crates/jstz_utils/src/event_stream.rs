@@ -1,65 +1,204 @@
 use crate::filtered_log_stream::FilteredLogStream;
-use anyhow::Result;
-use futures::{StreamExt, TryStreamExt};
+use anyhow::{anyhow, Result};
+use futures::{
+    future::{self, BoxFuture},
+    FutureExt, StreamExt,
+};
 use futures_core::{stream::BoxStream, Stream};
 use jstz_core::event::{decode_line, Event};
 use regex::Regex;
 use std::path::PathBuf;
 use std::{
-    marker::PhantomData,
     pin::Pin,
     task::{Context, Poll},
 };
+use tokio::{sync::mpsc, task::JoinHandle};
 
 /// A stream of logs.
 pub type LogStream<'a> = BoxStream<'a, anyhow::Result<String>>;
 
+/// Default capacity of the channel of each [`EventMux`] subscription.
+pub const DEFAULT_EVENT_CHANNEL_CAPACITY: usize = 1024;
+
 /// A stream of Jstz Events, decoded from a log stream.
 pub struct EventStream<'a, E: Event> {
-    log_stream: LogStream<'a>,
-    _marker: PhantomData<E>,
+    events: BoxStream<'a, anyhow::Result<E>>,
 }
 
-fn filter_pattern<E: Event>() -> anyhow::Result<Regex> {
-    Regex::new(&format!(r"^\[{}\].*$", E::tag())).map_err(anyhow::Error::from)
+fn mux_pattern(tags: &[&str]) -> anyhow::Result<Regex> {
+    let tags = tags.iter().map(|t| regex::escape(t)).collect::<Vec<_>>();
+    Regex::new(&format!(r"^\[({})\].*$", tags.join("|"))).map_err(anyhow::Error::from)
 }
 
-impl<'a, E: Event> EventStream<'a, E> {
+/// Returns the tag of a line with the schema "[tag]<payload>"
+fn line_tag(line: &str) -> Option<&str> {
+    let line = line.trim_start().strip_prefix('[')?;
+    line.find(']').map(|end| &line[..end])
+}
+
+impl<'a, E: Event + Send + 'static> EventStream<'a, E> {
     /// Create an [`EventStream`] from a kernel log file.
     ///
     /// Lines that match the prefix `E::tag()` will be decoded into event of type `E`. Otherwise, they will be ignored
     pub async fn from_file(path: PathBuf) -> Result<Self> {
-        let pattern = filter_pattern::<E>()?;
-        let stream = FilteredLogStream::new(pattern, path).await?;
-        let stream = EventStream::<E> {
-            log_stream: stream.map_err(anyhow::Error::from).boxed(),
-            _marker: PhantomData,
-        };
+        let mut mux = EventMux::default();
+        let stream = mux.subscribe::<E>();
+        // The mux task stops once the stream is dropped
+        mux.spawn_from_file(path).await?;
         Ok(stream)
     }
 }
 
-impl<'a, E: Event + Unpin> Stream for EventStream<'a, E> {
+impl<'a, E: Event> Stream for EventStream<'a, E> {
     type Item = anyhow::Result<E>;
 
     fn poll_next(
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Option<Self::Item>> {
-        match self.log_stream.poll_next_unpin(cx) {
-            Poll::Ready(Some(Ok(line))) => match decode_line::<E>(&line) {
-                Ok(event) => Poll::Ready(Some(Ok(event))),
-                Err(e) => {
-                    log::warn!("Failed to decode Event {}: {}", E::tag(), e);
-                    Poll::Pending
-                }
-            },
-            // An unrecoverable I/O error occurred while reading the file.
-            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e))),
-            Poll::Ready(None) => Poll::Ready(None),
-            Poll::Pending => Poll::Pending,
+        self.events.poll_next_unpin(cx)
+    }
+}
+
+/// A subscription of an [`EventMux`] to events of one type
+trait Route: Send {
+    fn tag(&self) -> &'static str;
+
+    /// Decodes `line` and sends the event to the subscriber, waiting for
+    /// capacity. Returns false if the subscriber is gone.
+    fn dispatch<'a>(&'a self, line: &'a str) -> BoxFuture<'a, bool>;
+
+    /// Forwards an unrecoverable error of the log stream to the subscriber
+    fn fail<'a>(&'a self, error: String) -> BoxFuture<'a, ()>;
+
+    /// Resolves once the subscriber is gone
+    fn closed(&self) -> BoxFuture<'_, ()>;
+}
+
+struct TypedRoute<E: Event> {
+    tx: mpsc::Sender<Result<E>>,
+}
+
+impl<E: Event + Send + 'static> Route for TypedRoute<E> {
+    fn tag(&self) -> &'static str {
+        E::tag()
+    }
+
+    fn dispatch<'a>(&'a self, line: &'a str) -> BoxFuture<'a, bool> {
+        match decode_line::<E>(line) {
+            Ok(event) => self.tx.send(Ok(event)).map(|r| r.is_ok()).boxed(),
+            Err(e) => {
+                log::warn!("Failed to decode Event {}: {}", E::tag(), e);
+                future::ready(true).boxed()
+            }
+        }
+    }
+
+    fn fail<'a>(&'a self, error: String) -> BoxFuture<'a, ()> {
+        self.tx.send(Err(anyhow!(error))).map(|_| ()).boxed()
+    }
+
+    fn closed(&self) -> BoxFuture<'_, ()> {
+        self.tx.closed().boxed()
+    }
+}
+
+/// Decodes events of different types from a single log stream and dispatches
+/// them to one bounded channel per subscription.
+///
+/// Each line is read and routed by its tag once, so consumers of different
+/// events can share a log stream instead of each tailing and filtering it. A
+/// full channel pauses the whole mux until the slow subscriber catches up, so
+/// that no event is dropped.
+///
+/// ```ignore
+/// let mut mux = EventMux::default();
+/// let storage_updates = mux.subscribe::<BatchStorageUpdate>();
+/// let oracle_requests = mux.subscribe::<OracleRequest>();
+/// let handle = mux.spawn_from_file(kernel_log_path).await?;
+/// ```
+pub struct EventMux {
+    capacity: usize,
+    routes: Vec<Box<dyn Route>>,
+}
+
+impl Default for EventMux {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENT_CHANNEL_CAPACITY)
+    }
+}
+
+impl EventMux {
+    /// Creates a mux buffering up to `capacity` events per subscription
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            routes: vec![],
         }
     }
+
+    /// Subscribes to events of type `E`. Events that fail to decode are
+    /// logged and skipped.
+    pub fn subscribe<E: Event + Send + 'static>(&mut self) -> EventStream<'static, E> {
+        let (tx, rx) = mpsc::channel(self.capacity);
+        self.routes.push(Box::new(TypedRoute::<E> { tx }));
+        let events = futures::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|event| (event, rx))
+        })
+        .boxed();
+        EventStream { events }
+    }
+
+    fn pattern(&self) -> Result<Regex> {
+        let tags = self.routes.iter().map(|r| r.tag()).collect::<Vec<_>>();
+        mux_pattern(&tags)
+    }
+
+    /// Tails the kernel log file at `path` and dispatches the events appended
+    /// to it from now on
+    pub async fn spawn_from_file(self, path: PathBuf) -> Result<JoinHandle<()>> {
+        let stream = FilteredLogStream::new(self.pattern()?, path).await?;
+        Ok(self.spawn(stream.boxed()))
+    }
+
+    /// Dispatches the events of `log_stream` in a background task. The task ends
+    /// when the log stream ends or fails, or when every subscriber is gone.
+    pub fn spawn(self, mut log_stream: LogStream<'static>) -> JoinHandle<()> {
+        let mut routes = self.routes;
+        tokio::spawn(async move {
+            while !routes.is_empty() {
+                let all_closed = future::join_all(routes.iter().map(|r| r.closed()));
+                let line = tokio::select! {
+                    line = log_stream.next() => line,
+                    _ = all_closed => break,
+                };
+                match line {
+                    Some(Ok(line)) => {
+                        let Some(tag) = line_tag(&line) else {
+                            continue;
+                        };
+                        let mut closed = vec![];
+                        for (i, route) in routes.iter().enumerate() {
+                            if route.tag() == tag && !route.dispatch(&line).await {
+                                closed.push(i);
+                            }
+                        }
+                        for i in closed.into_iter().rev() {
+                            routes.remove(i);
+                        }
+                    }
+                    // An unrecoverable I/O error occurred while reading the file.
+                    Some(Err(e)) => {
+                        for route in &routes {
+                            route.fail(e.to_string()).await;
+                        }
+                        break;
+                    }
+                    None => break,
+                }
+            }
+        })
+    }
 }
 
 #[cfg(test)]
@@ -147,10 +286,111 @@ mod tests {
             MockEvent::tag(),
             serde_json::to_string(&event).unwrap()
         );
-        let pattern = filter_pattern::<MockEvent>().unwrap();
+        let pattern = mux_pattern(&[MockEvent::tag()]).unwrap();
         assert!(pattern.is_match(&line));
 
         let wrong_line = "[WRONG]: abc".to_string();
         assert!(!pattern.is_match(&wrong_line));
+
+        let pattern = mux_pattern(&[MockEvent::tag(), OtherEvent::tag()]).unwrap();
+        assert!(pattern.is_match(&line));
+        assert!(pattern.is_match("[OTHER]{}"));
+        assert!(!pattern.is_match(&wrong_line));
+    }
+
+    #[test]
+    fn parses_line_tag() {
+        assert_eq!(line_tag("[MOCK]{}"), Some("MOCK"));
+        assert_eq!(line_tag("  [MOCK] {}"), Some("MOCK"));
+        assert_eq!(line_tag("MOCK {}"), None);
+        assert_eq!(line_tag("[MOCK"), None);
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct OtherEvent {
+        pub name: String,
+    }
+
+    impl Event for OtherEvent {
+        fn tag() -> &'static str {
+            "OTHER"
+        }
+    }
+
+    fn log_stream(lines: Vec<String>) -> LogStream<'static> {
+        futures::stream::iter(lines.into_iter().map(Ok)).boxed()
+    }
+
+    #[tokio::test]
+    async fn mux_routes_events_by_tag() -> anyhow::Result<()> {
+        let mut mux = EventMux::default();
+        let mut mocks = mux.subscribe::<MockEvent>();
+        let mut others = mux.subscribe::<OtherEvent>();
+
+        let other = OtherEvent {
+            name: "foo".to_string(),
+        };
+        let handle = mux.spawn(log_stream(vec![
+            make_line(&mock_event(1)),
+            format!("[OTHER]{}", serde_json::to_string(&other)?),
+            "[MOCK] not json".to_string(),
+            "[UNKNOWN] {}".to_string(),
+            make_line(&mock_event(2)),
+        ]));
+
+        assert_eq!(mocks.next().await.unwrap()?, mock_event(1));
+        assert_eq!(mocks.next().await.unwrap()?, mock_event(2));
+        assert!(mocks.next().await.is_none());
+        assert_eq!(others.next().await.unwrap()?, other);
+        assert!(others.next().await.is_none());
+        handle.await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mux_applies_backpressure() -> anyhow::Result<()> {
+        let mut mux = EventMux::new(1);
+        let mut mocks = mux.subscribe::<MockEvent>();
+        let handle = mux.spawn(log_stream(
+            (0..10).map(|id| make_line(&mock_event(id))).collect(),
+        ));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!handle.is_finished());
+        for id in 0..10 {
+            assert_eq!(mocks.next().await.unwrap()?, mock_event(id));
+        }
+        handle.await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mux_stops_without_subscribers() -> anyhow::Result<()> {
+        let mut mux = EventMux::default();
+        let mocks = mux.subscribe::<MockEvent>();
+        let others = mux.subscribe::<OtherEvent>();
+        let handle = mux.spawn(futures::stream::pending::<Result<String>>().boxed());
+
+        drop(mocks);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!handle.is_finished());
+
+        drop(others);
+        timeout(Duration::from_secs(1), handle).await??;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mux_forwards_stream_errors() -> anyhow::Result<()> {
+        let mut mux = EventMux::default();
+        let mut mocks = mux.subscribe::<MockEvent>();
+        let mut others = mux.subscribe::<OtherEvent>();
+        let stream = futures::stream::iter(vec![Err::<String, _>(anyhow!("io error"))]);
+        mux.spawn(stream.boxed()).await?;
+
+        assert!(mocks.next().await.unwrap().is_err());
+        assert!(others.next().await.unwrap().is_err());
+        assert!(mocks.next().await.is_none());
+        Ok(())
     }
 }
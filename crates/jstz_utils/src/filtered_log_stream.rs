@@ -25,7 +25,7 @@ pub(crate) struct FilteredLogStream {
 
 impl FilteredLogStream {
     pub async fn new(pattern: Regex, path: PathBuf) -> Result<Self> {
-        let file = TailedFile::init(&path).await?;
+        let mut file = TailedFile::init(&path).await?;
 
         let (tx, rx) = mpsc::channel(1024);
 
@@ -33,11 +33,10 @@ impl FilteredLogStream {
         let token = cancel.clone();
 
         tokio::spawn(async move {
-            let mut lines = file.lines();
             loop {
                 tokio::select! {
                     _ = token.cancelled() => break,
-                    line = lines.next_line() => match line {
+                    line = file.next_line() => match line {
                         Ok(Some(line)) => { // A new line matching `pattern` was appended.
                             if pattern.is_match(&line.to_string())
                                 && tx.send(Ok(line)).await.is_err()
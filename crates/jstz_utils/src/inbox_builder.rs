@@ -12,25 +12,29 @@ use jstz_proto::{
 #[cfg(feature = "v2_runtime")]
 use jstz_proto::{operation::OracleResponse, runtime::v2::fetch::http::Response};
 use std::error::Error;
-use tezos_crypto_rs::hash::ContractKt1Hash;
+use tezos_crypto_rs::hash::{BlockHash, ContractKt1Hash};
 use tezos_data_encoding::enc::BinWriter;
 use tezos_smart_rollup::{
-    inbox::{ExternalMessageFrame, InboxMessage, InternalInboxMessage, Transfer},
+    inbox::{
+        ExternalMessageFrame, InboxMessage, InfoPerLevel, InternalInboxMessage, Transfer,
+    },
     michelson::{
         ticket::{FA2_1Ticket, Ticket},
-        Michelson, MichelsonContract, MichelsonNat, MichelsonOption, MichelsonOr,
-        MichelsonPair, MichelsonUnit,
+        Michelson, MichelsonBytes, MichelsonContract, MichelsonNat, MichelsonOption,
+        MichelsonOr, MichelsonPair, MichelsonUnit,
     },
-    types::{Contract, PublicKeyHash, SmartRollupAddress},
+    types::{Contract, PublicKeyHash, SmartRollupAddress, Timestamp},
     utils::inbox::file::{InboxFile, Message},
 };
 pub type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
 // tag + 20 byte address
 const EXTERNAL_FRAME_SIZE: usize = 21;
-const DEFAULT_GAS_LIMIT: u32 = 100_000;
+pub const DEFAULT_GAS_LIMIT: u32 = 100_000;
 const MNEMONIC: &str =
     "donate kidney style loyal nose core inflict cup symptom speed giant polar";
+// any user address is okay as the source of transfers since L1 is not really involved
+const L1_SOURCE: &str = "tz1W8rEphWEjMcD1HsxEhsBFocfMeGsW7Qxg";
 // FIXME: JSTZ-854
 type DepositInboxMsgPayloadType = MichelsonOr<
     MichelsonPair<MichelsonContract, FA2_1Ticket>,
@@ -47,6 +51,53 @@ pub struct Account {
     pub address: Address,
 }
 
+impl Account {
+    /// The `index`-th account derived from the benchmark mnemonic
+    pub fn from_index(index: usize) -> Result<Self> {
+        let (pk, sk) = keypair_from_mnemonic(MNEMONIC, &index.to_string())?;
+        Ok(Self {
+            address: Address::from_base58(&pk.hash())?,
+            sk,
+            pk,
+            nonce: Default::default(),
+        })
+    }
+
+    /// Signs an operation with the current nonce of the account
+    pub fn sign_operation(&self, content: Content) -> Result<SignedOperation> {
+        let op = Operation {
+            public_key: self.pk.clone(),
+            nonce: self.nonce,
+            content,
+            max_fee: None,
+            priority_fee: None,
+        };
+        let hash = op.hash();
+        Ok(SignedOperation::new(self.sk.sign(hash)?, op))
+    }
+}
+
+/// An FA2.1 ticket minted by `ticketer`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaTicket {
+    pub ticketer: ContractKt1Hash,
+    pub token_id: u32,
+    pub content: Option<Vec<u8>>,
+}
+
+impl FaTicket {
+    fn ticket(&self, amount: u64) -> Result<FA2_1Ticket> {
+        Ok(Ticket::new(
+            Contract::Originated(self.ticketer.clone()),
+            MichelsonPair(
+                MichelsonNat::from(self.token_id),
+                MichelsonOption(self.content.clone().map(MichelsonBytes)),
+            ),
+            amount,
+        )?)
+    }
+}
+
 pub struct InboxBuilder {
     messages: Vec<Message>,
     rollup_address: SmartRollupAddress,
@@ -83,17 +134,9 @@ impl InboxBuilder {
     }
 
     pub fn create_accounts(&mut self, count: usize) -> Result<Vec<Account>> {
-        let mut accounts = vec![];
-        for i in self.next_account_id..count + self.next_account_id {
-            let (pk, sk) = keypair_from_mnemonic(MNEMONIC, &i.to_string())?;
-            let account = Account {
-                address: Address::from_base58(&pk.hash())?,
-                sk,
-                pk,
-                nonce: Default::default(),
-            };
-            accounts.push(account);
-        }
+        let accounts = (self.next_account_id..count + self.next_account_id)
+            .map(Account::from_index)
+            .collect::<Result<Vec<_>>>()?;
         self.next_account_id += count;
         Ok(accounts)
     }
@@ -107,14 +150,7 @@ impl InboxBuilder {
         signer: &Account,
         content: Content,
     ) -> Result<Message> {
-        let op = Operation {
-            public_key: signer.pk.clone(),
-            nonce: signer.nonce,
-            content,
-        };
-
-        let hash = op.hash();
-        let signed_op = SignedOperation::new(signer.sk.sign(hash)?, op);
+        let signed_op = signer.sign_operation(content)?;
 
         let bytes = signed_op.encode()?;
         let mut external = Vec::with_capacity(bytes.len() + EXTERNAL_FRAME_SIZE);
@@ -155,6 +191,7 @@ impl InboxBuilder {
         let content = Content::DeployFunction(DeployFunction {
             function_code: code,
             account_credit,
+            interface: None,
         });
 
         let message = self.generate_external_message(account, content)?;
@@ -187,45 +224,47 @@ impl InboxBuilder {
         Ok(())
     }
 
+    fn receiver_contract(receiver: &Address) -> Result<MichelsonContract> {
+        Ok(MichelsonContract(Contract::from_b58check(
+            &receiver.to_string(),
+        )?))
+    }
+
     fn deposit_payload(
         ticketer: &ContractKt1Hash,
-        account: &Account,
+        receiver: &Address,
         amount_mutez: u64,
-    ) -> DepositInboxMsgPayloadType {
-        MichelsonOr::Left(MichelsonPair(
-            MichelsonContract(Contract::Implicit(
-                PublicKeyHash::from_b58check(&account.address.to_string())
-                    .expect("serialised address should be parsable"),
-            )),
-            Ticket::new(
-                Contract::Originated(ticketer.clone()),
-                MichelsonPair(MichelsonNat::from(0), MichelsonOption(None)),
-                amount_mutez,
-            )
-            .expect("ticket creation from ticketer should work"),
-        ))
+    ) -> Result<DepositInboxMsgPayloadType> {
+        let ticket = FaTicket {
+            ticketer: ticketer.clone(),
+            token_id: 0,
+            content: None,
+        };
+        Ok(MichelsonOr::Left(MichelsonPair(
+            Self::receiver_contract(receiver)?,
+            ticket.ticket(amount_mutez)?,
+        )))
     }
 
     fn fa_deposit_payload(
-        ticketer: &ContractKt1Hash,
-        account: &Account,
-        amount_mutez: u64,
-    ) -> DepositInboxMsgPayloadType {
-        MichelsonOr::Right(MichelsonPair(
-            MichelsonContract(Contract::Implicit(
-                PublicKeyHash::from_b58check(&account.address.to_string())
-                    .expect("serialised address should be parsable"),
-            )),
-            MichelsonPair(
-                MichelsonOption(None),
-                Ticket::new(
-                    Contract::Originated(ticketer.clone()),
-                    MichelsonPair(MichelsonNat::from(0), MichelsonOption(None)),
-                    amount_mutez,
-                )
-                .expect("ticket creation from ticketer should work"),
-            ),
-        ))
+        ticket: &FaTicket,
+        receiver: &Address,
+        proxy: Option<&SmartFunctionHash>,
+        amount: u64,
+    ) -> Result<DepositInboxMsgPayloadType> {
+        let proxy = proxy
+            .map(|proxy| Self::receiver_contract(&Address::SmartFunction(proxy.clone())))
+            .transpose()?;
+        Ok(MichelsonOr::Right(MichelsonPair(
+            Self::receiver_contract(receiver)?,
+            MichelsonPair(MichelsonOption(proxy), ticket.ticket(amount)?),
+        )))
+    }
+
+    fn native_ticketer(&self) -> Result<ContractKt1Hash> {
+        self.ticketer_address
+            .clone()
+            .ok_or_else(|| "ticketer address is not provided".into())
     }
 
     pub fn fa_deposit_from_l1(
@@ -233,16 +272,12 @@ impl InboxBuilder {
         account: &Account,
         amount_mutez: u64,
     ) -> Result<()> {
-        match &self.ticketer_address {
-            Some(ticketer) => {
-                let ticketer = ticketer.clone();
-                self.transfer_from_l1(
-                    &ticketer,
-                    Self::fa_deposit_payload(&ticketer, account, amount_mutez),
-                )
-            }
-            None => Err("ticketer address is not provided".into()),
-        }
+        let ticket = FaTicket {
+            ticketer: self.native_ticketer()?,
+            token_id: 0,
+            content: None,
+        };
+        self.fa_deposit(&account.address, &ticket, None, amount_mutez)
     }
 
     pub fn deposit_from_l1(
@@ -250,31 +285,50 @@ impl InboxBuilder {
         account: &Account,
         amount_mutez: u64,
     ) -> Result<()> {
-        match &self.ticketer_address {
-            Some(ticketer) => {
-                let ticketer = ticketer.clone();
-                self.transfer_from_l1(
-                    &ticketer,
-                    Self::deposit_payload(&ticketer, account, amount_mutez),
-                )
-            }
-            None => Err("ticketer address is not provided".into()),
-        }
+        self.deposit(&account.address, amount_mutez)
+    }
+
+    /// Deposits native tez to `receiver`, which may be a user or a smart function
+    pub fn deposit(&mut self, receiver: &Address, amount_mutez: u64) -> Result<()> {
+        let ticketer = self.native_ticketer()?;
+        let payload = Self::deposit_payload(&ticketer, receiver, amount_mutez)?;
+        self.transfer_from_l1(&ticketer, payload)
+    }
+
+    /// Deposits `amount` FA tokens to `receiver`. If `proxy` is given, the tokens
+    /// are credited to the proxy smart function on behalf of `receiver`.
+    pub fn fa_deposit(
+        &mut self,
+        receiver: &Address,
+        ticket: &FaTicket,
+        proxy: Option<&SmartFunctionHash>,
+        amount: u64,
+    ) -> Result<()> {
+        let payload = Self::fa_deposit_payload(ticket, receiver, proxy, amount)?;
+        self.transfer_from_l1(&ticket.ticketer, payload)
     }
 
     fn transfer_from_l1(
         &mut self,
         ticketer: &ContractKt1Hash,
         payload: DepositInboxMsgPayloadType,
+    ) -> Result<()> {
+        let source = PublicKeyHash::from_b58check(L1_SOURCE)
+            .expect("the constant source address should be parsable");
+        self.transfer(ticketer, &source, payload)
+    }
+
+    /// Appends an internal transfer of `payload` from `sender` to the rollup
+    pub fn transfer<T: Michelson>(
+        &mut self,
+        sender: &ContractKt1Hash,
+        source: &PublicKeyHash,
+        payload: T,
     ) -> Result<()> {
         let message =
             self.generate_internal_messge(InternalInboxMessage::Transfer(Transfer {
-                sender: ticketer.clone(),
-                // any user address is okay here since L1 is not really involved
-                source: PublicKeyHash::from_b58check(
-                    "tz1W8rEphWEjMcD1HsxEhsBFocfMeGsW7Qxg",
-                )
-                .expect("the constant source address should be parsable"),
+                sender: sender.clone(),
+                source: source.clone(),
                 destination: self.rollup_address.clone(),
                 payload,
             }))?;
@@ -328,6 +382,23 @@ impl InboxBuilder {
         Ok(())
     }
 
+    /// Appends the information on the previous L1 block that follows the
+    /// start of every level
+    pub fn level_info(
+        &mut self,
+        predecessor: BlockHash,
+        predecessor_timestamp: Timestamp,
+    ) -> Result<()> {
+        self.messages
+            .push(self.generate_internal_messge(
+                InternalInboxMessage::<MichelsonUnit>::InfoPerLevel(InfoPerLevel {
+                    predecessor,
+                    predecessor_timestamp,
+                }),
+            )?);
+        Ok(())
+    }
+
     pub fn bump_level(&mut self) -> Result<()> {
         if self.next_level > 0 {
             self.messages.push(self.generate_internal_messge(
@@ -349,22 +420,25 @@ mod tests {
 
     use http::{HeaderMap, Method, Uri};
     use jstz_core::BinEncodable;
-    use jstz_crypto::{public_key::PublicKey, secret_key::SecretKey};
+    use jstz_crypto::{
+        public_key::PublicKey, secret_key::SecretKey,
+        smart_function_hash::SmartFunctionHash,
+    };
     use jstz_proto::{
         context::account::{Address, Nonce},
         executor::withdraw::Withdrawal,
         operation::{Content, DeployFunction, RunFunction, SignedOperation},
         HttpBody,
     };
-    use tezos_crypto_rs::hash::ContractKt1Hash;
+    use tezos_crypto_rs::hash::{BlockHash, ContractKt1Hash, HashTrait};
     use tezos_smart_rollup::{
-        inbox::{ExternalMessageFrame, InboxMessage, InternalInboxMessage},
-        michelson::{MichelsonOr, MichelsonUnit},
-        types::SmartRollupAddress,
+        inbox::{ExternalMessageFrame, InboxMessage, InternalInboxMessage, Transfer},
+        michelson::{MichelsonOption, MichelsonOr, MichelsonPair, MichelsonUnit},
+        types::{SmartRollupAddress, Timestamp},
         utils::inbox::file::Message,
     };
 
-    use super::{DepositInboxMsgPayloadType, InboxBuilder};
+    use super::{DepositInboxMsgPayloadType, FaTicket, InboxBuilder};
 
     fn default_account() -> super::Account {
         super::Account {
@@ -487,6 +561,7 @@ mod tests {
         let content = Content::DeployFunction(DeployFunction {
             function_code: "foo".to_string(),
             account_credit: 123,
+            interface: None,
         });
 
         let rollup_address =
@@ -812,4 +887,137 @@ mod tests {
             _ => panic!("should be raw message"),
         }
     }
+
+    #[test]
+    fn level_info() {
+        let rollup_address =
+            SmartRollupAddress::from_b58check("sr1Uuiucg1wk5aovEY2dj1ZBsqjwxndrSaao")
+                .unwrap();
+        let mut builder = InboxBuilder::new(
+            rollup_address,
+            None,
+            #[cfg(feature = "v2_runtime")]
+            None,
+        );
+        let predecessor = BlockHash::try_from_bytes(&[7; 32]).unwrap();
+        builder.bump_level().unwrap();
+        builder
+            .level_info(predecessor.clone(), Timestamp::from(42))
+            .unwrap();
+        match builder.messages.last().unwrap() {
+            Message::Raw(raw) => {
+                match InboxMessage::<MichelsonUnit>::parse(raw).unwrap().1 {
+                    InboxMessage::Internal(InternalInboxMessage::InfoPerLevel(info)) => {
+                        assert_eq!(info.predecessor, predecessor);
+                        assert_eq!(info.predecessor_timestamp, Timestamp::from(42));
+                    }
+                    _ => panic!("should be info per level message"),
+                }
+            }
+            _ => panic!("should be raw message"),
+        }
+    }
+
+    fn parse_deposit(message: Message) -> Transfer<DepositInboxMsgPayloadType> {
+        match message {
+            Message::Raw(raw) => {
+                match InboxMessage::<DepositInboxMsgPayloadType>::parse(&raw)
+                    .unwrap()
+                    .1
+                {
+                    InboxMessage::Internal(InternalInboxMessage::Transfer(transfer)) => {
+                        transfer
+                    }
+                    _ => panic!("should be internal transfer"),
+                }
+            }
+            _ => panic!("should be raw message"),
+        }
+    }
+
+    #[test]
+    fn deposit_to_smart_function() {
+        let rollup_address =
+            SmartRollupAddress::from_b58check("sr1Uuiucg1wk5aovEY2dj1ZBsqjwxndrSaao")
+                .unwrap();
+        let ticketer =
+            ContractKt1Hash::from_base58_check("KT1TxqZ8QtKvLu3V3JH7Gx58n7Co8pgtpQU5")
+                .unwrap();
+        let mut builder = InboxBuilder::new(
+            rollup_address,
+            Some(ticketer.clone()),
+            #[cfg(feature = "v2_runtime")]
+            None,
+        );
+        let receiver =
+            Address::from_base58("KT1RycYvM4EVs6BAXWEsGXaAaRqiMP53KT4w").unwrap();
+        builder.deposit(&receiver, 42).unwrap();
+
+        let transfer = parse_deposit(builder.messages.pop().unwrap());
+        assert_eq!(transfer.sender, ticketer);
+        match transfer.payload {
+            MichelsonOr::Left(MichelsonPair(contract, ticket)) => {
+                assert_eq!(contract.0.to_b58check(), receiver.to_string());
+                let expected = FaTicket {
+                    ticketer: ticketer.clone(),
+                    token_id: 0,
+                    content: None,
+                };
+                assert_eq!(ticket, expected.ticket(42).unwrap());
+            }
+            _ => panic!("should be native deposit"),
+        }
+    }
+
+    #[test]
+    fn fa_deposit() {
+        let rollup_address =
+            SmartRollupAddress::from_b58check("sr1Uuiucg1wk5aovEY2dj1ZBsqjwxndrSaao")
+                .unwrap();
+        let mut builder = InboxBuilder::new(
+            rollup_address,
+            None,
+            #[cfg(feature = "v2_runtime")]
+            None,
+        );
+        let account = builder.create_accounts(1).unwrap().pop().unwrap();
+        let ticket = FaTicket {
+            ticketer: ContractKt1Hash::from_base58_check(
+                "KT1TxqZ8QtKvLu3V3JH7Gx58n7Co8pgtpQU5",
+            )
+            .unwrap(),
+            token_id: 7,
+            content: Some(b"foo".to_vec()),
+        };
+        let proxy =
+            SmartFunctionHash::from_base58("KT1RycYvM4EVs6BAXWEsGXaAaRqiMP53KT4w")
+                .unwrap();
+        builder
+            .fa_deposit(&account.address, &ticket, Some(&proxy), 100)
+            .unwrap();
+        builder
+            .fa_deposit(&account.address, &ticket, None, 200)
+            .unwrap();
+        assert_eq!(builder.messages.len(), 2);
+
+        let transfer = parse_deposit(builder.messages.remove(0));
+        assert_eq!(transfer.sender, ticket.ticketer);
+        match transfer.payload {
+            MichelsonOr::Right(MichelsonPair(
+                receiver,
+                MichelsonPair(MichelsonOption(Some(p)), t),
+            )) => {
+                assert_eq!(receiver.0.to_b58check(), account.address.to_string());
+                assert_eq!(p.0.to_b58check(), proxy.to_string());
+                assert_eq!(t, ticket.ticket(100).unwrap());
+            }
+            _ => panic!("should be FA deposit with proxy"),
+        }
+
+        let transfer = parse_deposit(builder.messages.remove(0));
+        assert!(matches!(
+            transfer.payload,
+            MichelsonOr::Right(MichelsonPair(_, MichelsonPair(MichelsonOption(None), _)))
+        ));
+    }
 }
@@ -1,9 +1,22 @@
-use std::path::PathBuf;
+use std::{fmt, path::PathBuf, str::FromStr};
 
-use anyhow::Context;
+use anyhow::{anyhow, bail, Context};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
 use jstz_crypto::{public_key::PublicKey, secret_key::SecretKey};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
+/// Environment variable holding the password of encrypted key files
+pub const KEYSTORE_PASSWORD_ENV: &str = "JSTZ_KEYSTORE_PASSWORD";
+
+// scrypt parameters recommended for interactive logins
+const DEFAULT_SCRYPT_LOG_N: u8 = 15;
+const DEFAULT_SCRYPT_R: u32 = 8;
+const DEFAULT_SCRYPT_P: u32 = 1;
+
 #[derive(Debug, Clone, PartialEq, Serialize)]
 #[serde(into = "PublicKey")]
 pub struct KeyPair(pub PublicKey, pub SecretKey);
@@ -20,22 +33,202 @@ struct RawKeyPair {
     secret_key: String,
 }
 
+/// A key pair whose secret key is encrypted with a key derived from a password
+#[derive(Debug, Serialize, Deserialize)]
+struct Keystore {
+    public_key: String,
+    crypto: EncryptedSecretKey,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedSecretKey {
+    kdf: ScryptParams,
+    /// Hex-encoded ChaCha20-Poly1305 nonce
+    nonce: String,
+    /// Hex-encoded encrypted base58 secret key
+    ciphertext: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ScryptParams {
+    log_n: u8,
+    r: u32,
+    p: u32,
+    /// Hex-encoded salt
+    salt: String,
+}
+
+impl ScryptParams {
+    fn derive_key(&self, password: &str) -> anyhow::Result<[u8; 32]> {
+        let salt = hex::decode(&self.salt).context("Invalid keystore salt")?;
+        let params = scrypt::Params::new(self.log_n, self.r, self.p, 32)
+            .map_err(|e| anyhow!("Invalid keystore parameters: {e}"))?;
+        let mut key = [0u8; 32];
+        scrypt::scrypt(password.as_bytes(), &salt, &params, &mut key)
+            .map_err(|e| anyhow!("Failed to derive keystore key: {e}"))?;
+        Ok(key)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum KeyFile {
+    Plain(RawKeyPair),
+    Encrypted(Keystore),
+}
+
+/// Where to load a [`KeyPair`] from:
+/// * `env:<NAME>` reads the environment variable `NAME`
+/// * `file:<PATH>` or `<PATH>` reads the file at `PATH`
+///
+/// The content is either a plain key pair (see [`parse_key_file`]) or an encrypted
+/// keystore created with [`KeyPair::encrypt`], which is decrypted with the password
+/// in [`KEYSTORE_PASSWORD_ENV`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    File(PathBuf),
+    Env(String),
+}
+
+impl FromStr for KeySource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if let Some(name) = s.strip_prefix("env:") {
+            if name.is_empty() {
+                bail!("Environment variable name must not be empty");
+            }
+            return Ok(Self::Env(name.to_string()));
+        }
+        let path = s.strip_prefix("file:").unwrap_or(s);
+        if path.is_empty() {
+            bail!("Key file path must not be empty");
+        }
+        Ok(Self::File(PathBuf::from(path)))
+    }
+}
+
+impl fmt::Display for KeySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "file:{}", path.display()),
+            Self::Env(name) => write!(f, "env:{name}"),
+        }
+    }
+}
+
+impl From<PathBuf> for KeySource {
+    fn from(path: PathBuf) -> Self {
+        Self::File(path)
+    }
+}
+
+impl KeyPair {
+    /// Loads a key pair from `source`. Encrypted keystores are decrypted with the
+    /// password in [`KEYSTORE_PASSWORD_ENV`].
+    pub fn load(source: &KeySource) -> anyhow::Result<Self> {
+        let password = std::env::var(KEYSTORE_PASSWORD_ENV).ok();
+        Self::load_with_password(source, password.as_deref())
+    }
+
+    pub fn load_with_password(
+        source: &KeySource,
+        password: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        let content = match source {
+            KeySource::File(path) => {
+                std::fs::read_to_string(path).context("Failed to read key file")?
+            }
+            KeySource::Env(name) => std::env::var(name).with_context(|| {
+                format!("Failed to read environment variable '{name}'")
+            })?,
+        };
+        Self::parse(&content, password)
+    }
+
+    fn parse(content: &str, password: Option<&str>) -> anyhow::Result<Self> {
+        let (public_key, secret_key) = match serde_json::from_str(content).map_err(|_| {
+            anyhow::anyhow!("Failed to parse key file. Key file must be JSON with 'public_key' and 'secret_key' fields")
+        })? {
+            KeyFile::Plain(RawKeyPair {
+                public_key,
+                secret_key,
+            }) => (public_key, secret_key),
+            KeyFile::Encrypted(keystore) => {
+                let password = password.with_context(|| {
+                    format!("Key file is encrypted but {KEYSTORE_PASSWORD_ENV} is not set")
+                })?;
+                let secret_key = keystore.crypto.decrypt(password)?;
+                (keystore.public_key, secret_key)
+            }
+        };
+
+        let public_key =
+            PublicKey::from_base58(&public_key).context("Invalid public key")?;
+        let secret_key =
+            SecretKey::from_base58(&secret_key).context("Invalid secret key")?;
+
+        Ok(KeyPair(public_key, secret_key))
+    }
+
+    /// Encrypts the key pair into a keystore that can be loaded with `password`
+    pub fn encrypt(&self, password: &str) -> anyhow::Result<String> {
+        self.encrypt_with(password, DEFAULT_SCRYPT_LOG_N)
+    }
+
+    fn encrypt_with(&self, password: &str, log_n: u8) -> anyhow::Result<String> {
+        let mut rng = rand::thread_rng();
+        let mut salt = [0u8; 16];
+        rng.fill(&mut salt);
+        let mut nonce = [0u8; 12];
+        rng.fill(&mut nonce);
+
+        let kdf = ScryptParams {
+            log_n,
+            r: DEFAULT_SCRYPT_R,
+            p: DEFAULT_SCRYPT_P,
+            salt: hex::encode(salt),
+        };
+        let key = kdf.derive_key(password)?;
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&key))
+            .encrypt(Nonce::from_slice(&nonce), self.1.to_base58().as_bytes())
+            .map_err(|_| anyhow!("Failed to encrypt secret key"))?;
+
+        let keystore = Keystore {
+            public_key: self.0.to_base58(),
+            crypto: EncryptedSecretKey {
+                kdf,
+                nonce: hex::encode(nonce),
+                ciphertext: hex::encode(ciphertext),
+            },
+        };
+        Ok(serde_json::to_string_pretty(&keystore)?)
+    }
+}
+
+impl EncryptedSecretKey {
+    fn decrypt(&self, password: &str) -> anyhow::Result<String> {
+        let key = self.kdf.derive_key(password)?;
+        let nonce = hex::decode(&self.nonce).context("Invalid keystore nonce")?;
+        if nonce.len() != 12 {
+            bail!("Invalid keystore nonce");
+        }
+        let ciphertext =
+            hex::decode(&self.ciphertext).context("Invalid keystore ciphertext")?;
+        let secret_key = ChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| anyhow!("Failed to decrypt key file. Wrong password?"))?;
+        String::from_utf8(secret_key).context("Invalid secret key")
+    }
+}
+
 /// Parses a public-secret key pair from a JSON file. The JSON content must have two keys:
 /// * `public_key`: with a public key string starting with `edpk`
 /// * `secret_key`: with a secret key string starting with `edsk`
+///
+/// Encrypted keystores are also accepted, see [`KeyPair::load`].
 pub fn parse_key_file(path: PathBuf) -> anyhow::Result<KeyPair> {
-    let key_pair = std::fs::read_to_string(path).context("Failed to read key file")?;
-    let RawKeyPair {
-        public_key,
-        secret_key,
-    } = serde_json::from_str(&key_pair).map_err(|_| {
-        anyhow::anyhow!("Failed to parse key file. Key file must be JSON with 'public_key' and 'secret_key' fields")
-    })?;
-
-    let public_key = PublicKey::from_base58(&public_key).context("Invalid public key")?;
-    let secret_key = SecretKey::from_base58(&secret_key).context("Invalid secret key")?;
-
-    Ok(KeyPair(public_key, secret_key))
+    KeyPair::load(&KeySource::File(path))
 }
 
 #[cfg(test)]
@@ -46,10 +239,96 @@ mod tests {
         str::FromStr,
     };
 
-    use super::KeyPair;
+    use super::{KeyPair, KeySource};
     use jstz_crypto::{public_key::PublicKey, secret_key::SecretKey};
     use tempfile::NamedTempFile;
 
+    fn key_pair() -> KeyPair {
+        KeyPair(
+            PublicKey::from_base58(
+                "edpkuSLWfVU1Vq7Jg9FucPyKmma6otcMHac9zG4oU1KMHSTBpJuGQ2",
+            )
+            .unwrap(),
+            SecretKey::from_base58(
+                "edsk31vznjHSSpGExDMHYASz45VZqXN4DPxvsa4hAyY8dHM28cZzp6",
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn parse_key_source() {
+        assert_eq!(
+            "env:FOO".parse::<KeySource>().unwrap(),
+            KeySource::Env("FOO".to_string())
+        );
+        assert_eq!(
+            "file:/foo/bar".parse::<KeySource>().unwrap(),
+            KeySource::File(PathBuf::from("/foo/bar"))
+        );
+        assert_eq!(
+            "/foo/bar".parse::<KeySource>().unwrap(),
+            KeySource::File(PathBuf::from("/foo/bar"))
+        );
+        assert!("env:".parse::<KeySource>().is_err());
+        assert!("".parse::<KeySource>().is_err());
+
+        for source in ["env:FOO", "file:/foo/bar"] {
+            assert_eq!(source.parse::<KeySource>().unwrap().to_string(), source);
+        }
+    }
+
+    #[test]
+    fn load_from_env() {
+        let source = KeySource::Env("JSTZ_UTILS_TEST_KEY_PAIR".to_string());
+        assert_eq!(
+            KeyPair::load_with_password(&source, None)
+                .unwrap_err()
+                .to_string(),
+            "Failed to read environment variable 'JSTZ_UTILS_TEST_KEY_PAIR'"
+        );
+
+        std::env::set_var(
+            "JSTZ_UTILS_TEST_KEY_PAIR",
+            r#"{
+  "public_key": "edpkuSLWfVU1Vq7Jg9FucPyKmma6otcMHac9zG4oU1KMHSTBpJuGQ2",
+  "secret_key": "edsk31vznjHSSpGExDMHYASz45VZqXN4DPxvsa4hAyY8dHM28cZzp6"
+}"#,
+        );
+        assert_eq!(
+            KeyPair::load_with_password(&source, None).unwrap(),
+            key_pair()
+        );
+    }
+
+    #[test]
+    fn load_encrypted() {
+        let keystore = key_pair().encrypt_with("password", 4).unwrap();
+        assert!(!keystore.contains(&key_pair().1.to_base58()));
+
+        let mut tmp_file = NamedTempFile::new().unwrap();
+        tmp_file.write_all(keystore.as_bytes()).unwrap();
+        tmp_file.flush().unwrap();
+        let source = KeySource::File(tmp_file.path().to_path_buf());
+
+        assert_eq!(
+            KeyPair::load_with_password(&source, Some("password")).unwrap(),
+            key_pair()
+        );
+        assert_eq!(
+            KeyPair::load_with_password(&source, Some("wrong"))
+                .unwrap_err()
+                .to_string(),
+            "Failed to decrypt key file. Wrong password?"
+        );
+        assert_eq!(
+            KeyPair::load_with_password(&source, None)
+                .unwrap_err()
+                .to_string(),
+            "Key file is encrypted but JSTZ_KEYSTORE_PASSWORD is not set"
+        );
+    }
+
     #[test]
     fn parse_key_file() {
         assert_eq!(
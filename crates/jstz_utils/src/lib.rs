@@ -4,25 +4,110 @@ pub mod filtered_log_stream;
 pub mod inbox_builder;
 pub mod key_pair;
 pub mod retry;
+#[cfg(feature = "riscv_sandbox")]
+pub mod riscv_sandbox;
 pub mod tailed_file;
 pub use key_pair::KeyPair;
 
-pub async fn poll<'a, F, T>(
+/// Waits `interval_ms` and then calls `f` up to `max_attempts` times, `interval_ms`
+/// apart, until it returns a value
+pub async fn poll<F, T>(
     max_attempts: u16,
     interval_ms: u64,
     f: impl Fn() -> F,
 ) -> Option<T>
 where
-    F: std::future::Future<Output = Option<T>> + Send + 'a,
+    F: std::future::Future<Output = Option<T>>,
 {
-    let duration = tokio::time::Duration::from_millis(interval_ms);
-    for _ in 0..max_attempts {
-        tokio::time::sleep(duration).await;
+    if max_attempts == 0 {
+        return None;
+    }
+    let interval = std::time::Duration::from_millis(interval_ms);
+    tokio::time::sleep(interval).await;
+    let policy =
+        retry::RetryPolicy::fixed(interval).with_max_attempts(max_attempts.into());
+    retry::retry(policy, || async { f().await.ok_or(()) })
+        .await
+        .ok()
+}
+
+/// Progress of [`poll_until_with`] after a failed attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollAttempt {
+    /// Number of attempts made so far
+    pub attempt: usize,
+    /// Time since polling started
+    pub elapsed: std::time::Duration,
+    /// Delay before the next attempt
+    pub next_delay: std::time::Duration,
+}
+
+/// Error returned when [`poll_until`] gives up without getting a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollTimeout {
+    pub attempts: usize,
+    pub elapsed: std::time::Duration,
+}
+
+impl std::fmt::Display for PollTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "gave up after {} attempt(s) in {:.1}s",
+            self.attempts,
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+impl std::error::Error for PollTimeout {}
+
+/// Calls `f` until it returns a value, waiting between attempts as described by
+/// `backoff`. The last attempt is made at `deadline` at the latest.
+pub async fn poll_until<F, T>(
+    deadline: std::time::Instant,
+    backoff: retry::RetryPolicy,
+    f: impl FnMut() -> F,
+) -> Result<T, PollTimeout>
+where
+    F: std::future::Future<Output = Option<T>>,
+{
+    poll_until_with(deadline, backoff, f, |_| {}).await
+}
+
+/// Like [`poll_until`] but calls `on_attempt` after every failed attempt that is
+/// followed by another one, e.g. to report progress
+pub async fn poll_until_with<F, T>(
+    deadline: std::time::Instant,
+    backoff: retry::RetryPolicy,
+    mut f: impl FnMut() -> F,
+    mut on_attempt: impl FnMut(&PollAttempt),
+) -> Result<T, PollTimeout>
+where
+    F: std::future::Future<Output = Option<T>>,
+{
+    let start = std::time::Instant::now();
+    let mut delays = backoff.delays();
+    let mut attempts = 0;
+    loop {
         if let Some(v) = f().await {
-            return Some(v);
+            return Ok(v);
         }
+        attempts += 1;
+
+        let now = std::time::Instant::now();
+        let elapsed = now - start;
+        let next_delay = match delays.next() {
+            Some(delay) if now < deadline => delay.min(deadline - now),
+            _ => return Err(PollTimeout { attempts, elapsed }),
+        };
+        on_attempt(&PollAttempt {
+            attempt: attempts,
+            elapsed,
+            next_delay,
+        });
+        tokio::time::sleep(next_delay).await;
     }
-    None
 }
 
 // WARNING: Should only be used in tests!
@@ -162,4 +247,55 @@ mod tests {
                 .is_none()
         );
     }
+
+    #[tokio::test]
+    async fn poll_until() {
+        use super::{poll_until, poll_until_with, retry::RetryPolicy, PollTimeout};
+        use std::time::{Duration, Instant};
+
+        let backoff = RetryPolicy::fixed(Duration::from_millis(5));
+
+        // succeeds before the deadline
+        let mut n = 0;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let v = poll_until(deadline, backoff, || {
+            n += 1;
+            std::future::ready((n == 3).then_some(n))
+        })
+        .await
+        .unwrap();
+        assert_eq!(v, 3);
+
+        // gives up at the deadline and reports progress
+        let mut progress = vec![];
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(50);
+        let PollTimeout { attempts, elapsed } = poll_until_with(
+            deadline,
+            backoff,
+            || std::future::ready(None::<()>),
+            |attempt| progress.push(*attempt),
+        )
+        .await
+        .unwrap_err();
+        assert!(Instant::now() >= deadline);
+        assert!(elapsed >= Duration::from_millis(50));
+        assert_eq!(progress.len(), attempts - 1);
+        for (i, attempt) in progress.iter().enumerate() {
+            assert_eq!(attempt.attempt, i + 1);
+            assert!(attempt.next_delay <= Duration::from_millis(5));
+        }
+
+        // gives up once the backoff is exhausted
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let err = poll_until(deadline, backoff.with_max_attempts(3), || {
+            std::future::ready(None::<()>)
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.attempts, 3);
+        assert!(err
+            .to_string()
+            .starts_with("gave up after 3 attempt(s) in "));
+    }
 }
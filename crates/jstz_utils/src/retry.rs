@@ -1,6 +1,126 @@
 use std::{future::Future, time::Duration};
 
-use tokio_retry2::{strategy::ExponentialBackoff, Retry, RetryError};
+use tokio_retry2::{
+    strategy::{jitter_range, ExponentialBackoff, ExponentialFactorBackoff, MaxInterval},
+    Retry, RetryError,
+};
+
+/// Describes how often and for how long a failing operation is retried, in terms
+/// of the strategies of `tokio_retry2`.
+///
+/// The delay before the n-th retry is `initial_delay * factor^n`, capped by
+/// `max_delay` and optionally randomised by `jitter`. Retrying stops once
+/// `max_attempts` attempts were made or once `max_elapsed_time` has passed,
+/// whichever comes first. A policy without any limit retries forever.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    initial_delay: Duration,
+    factor: f64,
+    max_delay: Option<Duration>,
+    max_attempts: Option<usize>,
+    max_elapsed_time: Option<Duration>,
+    jitter: f64,
+}
+
+impl RetryPolicy {
+    /// Retries with a constant `interval` between attempts
+    pub fn fixed(interval: Duration) -> Self {
+        Self {
+            initial_delay: interval,
+            factor: 1.0,
+            max_delay: None,
+            max_attempts: None,
+            max_elapsed_time: None,
+            jitter: 0.0,
+        }
+    }
+
+    /// Retries with a delay starting at `initial_delay` and doubling after
+    /// every attempt
+    pub fn exponential(initial_delay: Duration) -> Self {
+        Self {
+            factor: 2.0,
+            ..Self::fixed(initial_delay)
+        }
+    }
+
+    pub fn with_factor(mut self, factor: f64) -> Self {
+        self.factor = factor.max(1.0);
+        self
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay.replace(max_delay);
+        self
+    }
+
+    /// Maximum number of attempts, including the first one
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts.replace(max_attempts);
+        self
+    }
+
+    pub fn with_max_elapsed_time(mut self, max_elapsed_time: Duration) -> Self {
+        self.max_elapsed_time.replace(max_elapsed_time);
+        self
+    }
+
+    /// Randomises every delay by up to `jitter` (a ratio between 0 and 1) of
+    /// its value in either direction, so that concurrent clients do not retry
+    /// in lockstep
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Delays before each retry. The elapsed time is measured from the call.
+    pub fn delays(&self) -> impl Iterator<Item = Duration> + Send {
+        let mut strategy = ExponentialFactorBackoff::from_millis(
+            self.initial_delay
+                .as_millis()
+                .try_into()
+                .unwrap_or(u64::MAX),
+            self.factor,
+        );
+        if let Some(max_delay) = self.max_delay {
+            strategy = strategy.max_delay(max_delay);
+        }
+        let jitter = (self.jitter > 0.0)
+            .then(|| jitter_range(1.0 - self.jitter, 1.0 + self.jitter));
+        strategy
+            .map(move |delay| jitter.as_ref().map_or(delay, |jitter| jitter(delay)))
+            .take(
+                self.max_attempts
+                    .map_or(usize::MAX, |n| n.saturating_sub(1)),
+            )
+            .max_duration(self.max_elapsed_time.unwrap_or(Duration::MAX))
+    }
+}
+
+/// Runs `op` until it succeeds or `policy` gives up, in which case the last
+/// error is returned
+pub async fn retry<F, Fut, T, E>(policy: RetryPolicy, op: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    retry_if(policy, op, |_| true).await
+}
+
+/// Like [`retry`] but gives up immediately on errors for which `should_retry`
+/// returns false
+pub async fn retry_if<F, Fut, T, E, C>(
+    policy: RetryPolicy,
+    op: F,
+    should_retry: C,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    C: Fn(&E) -> bool + Copy,
+{
+    retry_async(policy.delays(), op, should_retry).await
+}
 
 pub fn exponential_backoff(
     base: u64,
@@ -67,6 +187,84 @@ mod tests {
         assert_eq!(intervals, expected);
     }
 
+    fn failing_op(
+        attempts: Arc<AtomicUsize>,
+        failures: usize,
+    ) -> impl FnMut() -> std::future::Ready<Result<usize, &'static str>> {
+        move || {
+            let n = attempts.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(if n < failures { Err("fail") } else { Ok(n) })
+        }
+    }
+
+    #[test]
+    fn test_retry_policy_delays() {
+        let policy = RetryPolicy::exponential(Duration::from_millis(10))
+            .with_max_delay(Duration::from_millis(50));
+        let delays: Vec<_> = policy.delays().take(5).collect();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(10),
+                Duration::from_millis(20),
+                Duration::from_millis(40),
+                Duration::from_millis(50),
+                Duration::from_millis(50),
+            ]
+        );
+
+        let policy = RetryPolicy::fixed(Duration::from_millis(10)).with_max_attempts(3);
+        assert_eq!(
+            policy.delays().collect::<Vec<_>>(),
+            vec![Duration::from_millis(10); 2]
+        );
+    }
+
+    #[test]
+    fn test_retry_policy_jitter() {
+        let policy = RetryPolicy::fixed(Duration::from_millis(100)).with_jitter(0.5);
+        for delay in policy.delays().take(100) {
+            assert!(delay >= Duration::from_millis(50));
+            assert!(delay <= Duration::from_millis(150));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_max_attempts() {
+        let policy = RetryPolicy::fixed(Duration::from_millis(1)).with_max_attempts(3);
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let result = retry(policy, failing_op(attempts.clone(), 2)).await;
+        assert_eq!(result, Ok(2));
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let result = retry(policy, failing_op(attempts.clone(), 3)).await;
+        assert_eq!(result, Err("fail"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_max_elapsed_time() {
+        let policy = RetryPolicy::fixed(Duration::from_millis(20))
+            .with_max_elapsed_time(Duration::from_millis(50));
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let result = retry(policy, failing_op(attempts.clone(), usize::MAX)).await;
+        assert_eq!(result, Err("fail"));
+        // No retry is scheduled once 50ms have passed, which happens after the
+        // third or the fourth attempt
+        assert!((3..=4).contains(&attempts.load(Ordering::SeqCst)));
+    }
+
+    #[tokio::test]
+    async fn test_retry_if() {
+        let policy = RetryPolicy::fixed(Duration::from_millis(1)).with_max_attempts(5);
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let result =
+            retry_if(policy, failing_op(attempts.clone(), 2), |e| *e != "fail").await;
+        assert_eq!(result, Err("fail"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
     #[tokio::test]
     async fn test_retry_async_success() {
         let attempts = Arc::new(AtomicUsize::new(0));
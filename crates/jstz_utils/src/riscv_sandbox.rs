@@ -0,0 +1,278 @@
+//! Builds RISC-V kernels and runs them in `riscv-sandbox`.
+//!
+//! The sandbox executes a kernel over an [`InboxFile`] and writes the debug log of
+//! the kernel to stdout, which is captured in a [`SandboxOutput`]. Tests and benches
+//! use it to target the RISC-V kernel without shelling out to the sandbox manually.
+
+use std::{
+    env,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::de::DeserializeOwned;
+use tezos_smart_rollup::{types::SmartRollupAddress, utils::inbox::file::InboxFile};
+
+pub const RISCV_TARGET: &str = "riscv64gc-unknown-linux-musl";
+/// Rollup address the kernels are run under by default
+pub const DEFAULT_ROLLUP_ADDRESS: &str = "sr1FXevDx86EyU1BBwhn94gtKvVPTNwoVxUC";
+
+/// Release build of a RISC-V kernel executable
+#[derive(Debug, Clone)]
+pub struct KernelBuild {
+    package: String,
+    bin: String,
+    features: Vec<String>,
+    default_features: bool,
+}
+
+impl KernelBuild {
+    pub fn new(package: impl Into<String>, bin: impl Into<String>) -> Self {
+        Self {
+            package: package.into(),
+            bin: bin.into(),
+            features: vec![],
+            default_features: true,
+        }
+    }
+
+    /// The jstz kernel, as built by `make riscv-pvm-kernel`
+    pub fn jstz_kernel() -> Self {
+        Self::new("jstz_kernel", "kernel-executable")
+            .no_default_features()
+            .with_feature("riscv_kernel")
+    }
+
+    pub fn with_feature(mut self, feature: impl Into<String>) -> Self {
+        self.features.push(feature.into());
+        self
+    }
+
+    pub fn no_default_features(mut self) -> Self {
+        self.default_features = false;
+        self
+    }
+
+    /// Path of the executable in the workspace at `workspace_root`
+    pub fn executable_path(&self, workspace_root: &Path) -> PathBuf {
+        workspace_root
+            .join("target")
+            .join(RISCV_TARGET)
+            .join("release")
+            .join(&self.bin)
+    }
+
+    /// Builds the kernel in the workspace at `workspace_root` and returns the path
+    /// of the executable. The V8 archive of the RISC-V target is read from
+    /// `RISCV_V8_ARCHIVE_DIR`, like the kernel targets of the Makefile.
+    pub fn build(&self, workspace_root: &Path) -> Result<PathBuf> {
+        let mut command =
+            Command::new(env::var_os("CARGO").unwrap_or_else(|| "cargo".into()));
+        command.current_dir(workspace_root).args([
+            "build",
+            "-p",
+            &self.package,
+            "--release",
+            "--target",
+            RISCV_TARGET,
+        ]);
+        if !self.default_features {
+            command.arg("--no-default-features");
+        }
+        if !self.features.is_empty() {
+            command.args(["--features", &self.features.join(",")]);
+        }
+        command.env_remove("NIX_LDFLAGS");
+        if let Some(dir) = env::var_os("RISCV_V8_ARCHIVE_DIR") {
+            let dir = PathBuf::from(dir);
+            command
+                .env("RUSTY_V8_ARCHIVE", dir.join("librusty_v8.a"))
+                .env("RUSTY_V8_SRC_BINDING_PATH", dir.join("src_binding.rs"));
+        }
+
+        let status = command.status().context("failed to run cargo")?;
+        if !status.success() {
+            bail!("failed to build {}: {status}", self.package);
+        }
+        Ok(self.executable_path(workspace_root))
+    }
+}
+
+/// Runner of a RISC-V kernel in `riscv-sandbox`
+#[derive(Debug, Clone)]
+pub struct RiscvSandbox {
+    program: PathBuf,
+    kernel: PathBuf,
+    address: SmartRollupAddress,
+    timeout: Option<Duration>,
+}
+
+impl RiscvSandbox {
+    pub fn new(kernel: impl Into<PathBuf>) -> Self {
+        Self {
+            program: PathBuf::from("riscv-sandbox"),
+            kernel: kernel.into(),
+            address: SmartRollupAddress::from_b58check(DEFAULT_ROLLUP_ADDRESS).unwrap(),
+            timeout: None,
+        }
+    }
+
+    /// Path of the `riscv-sandbox` executable, looked up in `PATH` by default
+    pub fn with_program(mut self, program: impl Into<PathBuf>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    pub fn with_address(mut self, address: SmartRollupAddress) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Kills the sandbox if it runs for longer than `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Runs the kernel over the messages of `inbox`
+    pub fn run(&self, inbox: &InboxFile) -> Result<SandboxOutput> {
+        let inbox_file = tempfile::NamedTempFile::new()?;
+        inbox
+            .save(inbox_file.path())
+            .map_err(|e| anyhow!("failed to save inbox file: {e:?}"))?;
+
+        let mut child = Command::new(&self.program)
+            .arg("run")
+            .arg("--timings")
+            .arg("--address")
+            .arg(self.address.to_b58check())
+            .arg("--inbox-file")
+            .arg(inbox_file.path())
+            .arg("--input")
+            .arg(&self.kernel)
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to run {}", self.program.display()))?;
+
+        // Drain stdout on a separate thread so that the sandbox never blocks on a full pipe
+        let mut stdout = child.stdout.take().expect("stdout should be piped");
+        let reader = thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stdout.read_to_end(&mut buf);
+            buf
+        });
+
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let status = loop {
+            match child.try_wait()? {
+                Some(status) => break Some(status),
+                None if deadline.is_some_and(|d| Instant::now() >= d) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    break None;
+                }
+                None => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        let stdout = reader.join().unwrap_or_default();
+        Ok(SandboxOutput {
+            status,
+            stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        })
+    }
+}
+
+/// Captured output of a sandbox run
+#[derive(Debug, Clone)]
+pub struct SandboxOutput {
+    /// Exit status of the sandbox, `None` if it was killed after timing out
+    pub status: Option<ExitStatus>,
+    /// Debug log of the kernel
+    pub stdout: String,
+}
+
+impl SandboxOutput {
+    pub fn success(&self) -> bool {
+        self.status.is_some_and(|status| status.success())
+    }
+
+    pub fn timed_out(&self) -> bool {
+        self.status.is_none()
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.stdout.lines()
+    }
+
+    /// Deserializes the JSON following `prefix` in the log lines that start with it
+    pub fn records<'a, T: DeserializeOwned>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = serde_json::Result<T>> + 'a {
+        self.lines()
+            .filter_map(move |line| line.strip_prefix(prefix))
+            .map(|record| serde_json::from_str(record.trim()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt, time::Duration};
+
+    use serde::Deserialize;
+    use tezos_smart_rollup::utils::inbox::file::InboxFile;
+
+    use super::{RiscvSandbox, SandboxOutput};
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Record {
+        value: u32,
+    }
+
+    fn fake_sandbox(dir: &tempfile::TempDir, script: &str) -> RiscvSandbox {
+        let program = dir.path().join("riscv-sandbox");
+        fs::write(&program, format!("#!/bin/sh\n{script}\n")).unwrap();
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+        RiscvSandbox::new("kernel-executable").with_program(program)
+    }
+
+    #[test]
+    fn records_parses_prefixed_lines() {
+        let output = SandboxOutput {
+            status: None,
+            stdout: "[REC] {\"value\": 1}\nother line\n[REC] {\"value\": 2}\n"
+                .to_string(),
+        };
+        let records = output
+            .records::<Record>("[REC]")
+            .collect::<serde_json::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(records, vec![Record { value: 1 }, Record { value: 2 }]);
+    }
+
+    #[test]
+    fn run_captures_stdout() {
+        let dir = tempfile::tempdir().unwrap();
+        let sandbox = fake_sandbox(&dir, "echo \"$1 $7 $8\"");
+        let output = sandbox.run(&InboxFile(vec![vec![]])).unwrap();
+        assert!(output.success());
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            vec!["run --input kernel-executable"]
+        );
+    }
+
+    #[test]
+    fn run_kills_sandbox_after_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let sandbox =
+            fake_sandbox(&dir, "exec sleep 10").with_timeout(Duration::from_millis(100));
+        let output = sandbox.run(&InboxFile(vec![vec![]])).unwrap();
+        assert!(output.timed_out());
+        assert!(!output.success());
+    }
+}
@@ -1,24 +1,277 @@
-use std::{io::SeekFrom, path::Path};
+use std::{
+    fs::Metadata,
+    io::{Error, ErrorKind, SeekFrom},
+    path::{Path, PathBuf},
+};
 
 use tokio::{
     fs::File,
-    io::Lines,
-    io::{AsyncSeekExt, BufReader, Result},
+    io::{AsyncBufReadExt, AsyncSeekExt, BufReader, Result},
 };
 
-pub struct TailedFile(BufReader<File>);
+/// Follows a file, yielding the lines appended to it.
+///
+/// The file is expected to be rotated by an external tool such as logrotate, so
+/// following survives:
+/// * truncation (`copytruncate`), after which the file is read from the start again
+/// * rotation (`create`) and deletion followed by re-creation, after which the old
+///   file is drained before the new one is read from the start
+///
+/// Incomplete lines are buffered until their line terminator is written.
+///
+/// NOTE: Truncation is detected by the file shrinking below the current read
+/// position, so a file that is truncated and refilled past that position before
+/// the next read is not detected. Rotation is only detected on unix.
+pub struct TailedFile {
+    path: PathBuf,
+    reader: BufReader<File>,
+    id: Option<FileId>,
+    position: u64,
+    partial: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileId {
+    dev: u64,
+    ino: u64,
+}
+
+impl FileId {
+    #[cfg(unix)]
+    fn of(metadata: &Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+        Some(Self {
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn of(_metadata: &Metadata) -> Option<Self> {
+        None
+    }
+}
 
-pub use tokio::io::AsyncBufReadExt;
+enum Change {
+    Truncated,
+    Replaced,
+}
 
 impl TailedFile {
+    /// Opens the file at `path` and skips its existing content
     pub async fn init(path: &Path) -> Result<Self> {
         let file = File::open(path).await?;
+        let id = FileId::of(&file.metadata().await?);
         let mut reader = BufReader::new(file);
-        let _ = reader.seek(SeekFrom::End(0)).await?;
-        Ok(TailedFile(reader))
+        let position = reader.seek(SeekFrom::End(0)).await?;
+        Ok(TailedFile {
+            path: path.to_path_buf(),
+            reader,
+            id,
+            position,
+            partial: vec![],
+        })
     }
 
-    pub fn lines(self) -> Lines<BufReader<File>> {
-        self.0.lines()
+    /// Returns the next line without its line terminator, or `None` if no complete
+    /// line has been appended yet.
+    pub async fn next_line(&mut self) -> Result<Option<String>> {
+        let mut drained = false;
+        loop {
+            let n = self.reader.read_until(b'\n', &mut self.partial).await?;
+            self.position += n as u64;
+            if self.partial.ends_with(b"\n") {
+                return self.take_line().map(Some);
+            }
+
+            match self.detect_change().await? {
+                None => return Ok(None),
+                // The writer may still append to the old file until it reopens
+                // the path, so the old file is read once more before switching
+                Some(Change::Replaced) if !drained => drained = true,
+                Some(change) => {
+                    drained = false;
+                    if !self.reset(change).await? {
+                        return Ok(None);
+                    }
+                    // The old file will not be completed anymore
+                    if !self.partial.is_empty() {
+                        return self.take_line().map(Some);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn detect_change(&self) -> Result<Option<Change>> {
+        let metadata = match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) => metadata,
+            // Deleted and not re-created yet
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if FileId::of(&metadata) != self.id {
+            return Ok(Some(Change::Replaced));
+        }
+        if metadata.len() < self.position {
+            return Ok(Some(Change::Truncated));
+        }
+        Ok(None)
+    }
+
+    /// Rewinds to the start of the file at `path`. Returns false if the file
+    /// disappeared in the meantime.
+    async fn reset(&mut self, change: Change) -> Result<bool> {
+        match change {
+            Change::Truncated => {
+                self.reader.seek(SeekFrom::Start(0)).await?;
+            }
+            Change::Replaced => {
+                let file = match File::open(&self.path).await {
+                    Ok(file) => file,
+                    Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+                    Err(e) => return Err(e),
+                };
+                self.id = FileId::of(&file.metadata().await?);
+                self.reader = BufReader::new(file);
+            }
+        }
+        self.position = 0;
+        Ok(true)
+    }
+
+    fn take_line(&mut self) -> Result<String> {
+        let mut line = std::mem::take(&mut self.partial);
+        if line.ends_with(b"\n") {
+            line.pop();
+            if line.ends_with(b"\r") {
+                line.pop();
+            }
+        }
+        String::from_utf8(line).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, OpenOptions},
+        io::Write,
+        path::Path,
+        time::Duration,
+    };
+
+    use tempfile::TempDir;
+
+    use super::TailedFile;
+
+    fn append(path: &Path, content: &str) {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file.sync_all().unwrap();
+    }
+
+    async fn next_line(file: &mut TailedFile) -> String {
+        tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                if let Some(line) = file.next_line().await.unwrap() {
+                    return line;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("should read a line")
+    }
+
+    async fn assert_no_line(file: &mut TailedFile) {
+        assert_eq!(file.next_line().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn follows_appended_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("log");
+        append(&path, "early\n");
+
+        let mut file = TailedFile::init(&path).await.unwrap();
+        assert_no_line(&mut file).await;
+
+        append(&path, "foo\r\nba");
+        assert_eq!(next_line(&mut file).await, "foo");
+        assert_no_line(&mut file).await;
+        append(&path, "r\n");
+        assert_eq!(next_line(&mut file).await, "bar");
+    }
+
+    #[tokio::test]
+    async fn follows_truncation() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("log");
+        append(&path, "early\n");
+
+        let mut file = TailedFile::init(&path).await.unwrap();
+        append(&path, "foo\n");
+        assert_eq!(next_line(&mut file).await, "foo");
+
+        // logrotate copytruncate
+        fs::copy(&path, dir.path().join("log.1")).unwrap();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(0)
+            .unwrap();
+        append(&path, "bar\n");
+        assert_eq!(next_line(&mut file).await, "bar");
+        assert_no_line(&mut file).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn follows_rotation() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("log");
+        let rotated = dir.path().join("log.1");
+        append(&path, "early\n");
+
+        let mut file = TailedFile::init(&path).await.unwrap();
+        append(&path, "foo\n");
+        assert_eq!(next_line(&mut file).await, "foo");
+
+        // logrotate create: the writer keeps appending to the rotated file until
+        // it reopens the path
+        fs::rename(&path, &rotated).unwrap();
+        append(&rotated, "bar\nba");
+        append(&path, "qux\n");
+        append(&rotated, "z\n");
+
+        assert_eq!(next_line(&mut file).await, "bar");
+        assert_eq!(next_line(&mut file).await, "baz");
+        assert_eq!(next_line(&mut file).await, "qux");
+        assert_no_line(&mut file).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn follows_recreation() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("log");
+        append(&path, "early\n");
+
+        let mut file = TailedFile::init(&path).await.unwrap();
+        append(&path, "foo\n");
+        assert_eq!(next_line(&mut file).await, "foo");
+
+        fs::remove_file(&path).unwrap();
+        assert_no_line(&mut file).await;
+
+        append(&path, "bar\n");
+        assert_eq!(next_line(&mut file).await, "bar");
+        assert_no_line(&mut file).await;
     }
 }
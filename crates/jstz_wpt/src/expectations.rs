@@ -0,0 +1,442 @@
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use anyhow::{Context, Result};
+use jstz_runtime::wpt::WptSubtestStatus;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::report::WptReport;
+
+/// Expected outcome of a subtest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum WptExpectation {
+    Pass,
+    Fail,
+    Timeout,
+}
+
+impl From<&WptSubtestStatus> for WptExpectation {
+    fn from(status: &WptSubtestStatus) -> Self {
+        match status {
+            WptSubtestStatus::Pass => Self::Pass,
+            WptSubtestStatus::Timeout => Self::Timeout,
+            _ => Self::Fail,
+        }
+    }
+}
+
+impl fmt::Display for WptExpectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pass => write!(f, "PASS"),
+            Self::Fail => write!(f, "FAIL"),
+            Self::Timeout => write!(f, "TIMEOUT"),
+        }
+    }
+}
+
+fn load_json<T: DeserializeOwned>(path: &Path, kind: &str) -> Result<T> {
+    let file = fs::read_to_string(path)
+        .with_context(|| format!("failed to read {kind} file {}", path.display()))?;
+    serde_json::from_str(&file).with_context(|| format!("failed to parse {kind} file"))
+}
+
+fn save_json<T: Serialize>(value: &T, path: &Path, kind: &str) -> Result<()> {
+    let mut content = serde_json::to_string_pretty(value)?;
+    content.push('\n');
+    fs::write(path, content)
+        .with_context(|| format!("failed to write {kind} file {}", path.display()))
+}
+
+/// WptExpectations records the expected outcome of every subtest, keyed by
+/// test file path and subtest name. It is meant to be checked in alongside the
+/// runner so that new runs can be compared against it with [`WptExpectations::diff`].
+///
+/// A subtest that runs in several variations (e.g. `.any.html` and `.any.worker.html`)
+/// is only expected to pass if it passes in all of them.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct WptExpectations(BTreeMap<String, BTreeMap<String, WptExpectation>>);
+
+impl WptExpectations {
+    pub fn from_report(report: &WptReport) -> Self {
+        let mut expectations = BTreeMap::new();
+        for (path, variations) in report.tests() {
+            let mut subtests = BTreeMap::new();
+            for subtest in variations.iter().flat_map(|v| &v.subtests) {
+                let outcome = WptExpectation::from(&subtest.status);
+                subtests
+                    .entry(subtest.name.clone())
+                    .and_modify(|e: &mut WptExpectation| *e = (*e).max(outcome))
+                    .or_insert(outcome);
+            }
+
+            if !subtests.is_empty() {
+                expectations.insert(path, subtests);
+            }
+        }
+        Self(expectations)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        load_json(path, "expectations")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        save_json(self, path, "expectations")
+    }
+
+    pub fn get(&self, path: &str, subtest: &str) -> Option<WptExpectation> {
+        self.0.get(path)?.get(subtest).copied()
+    }
+
+    /// Compares the outcomes of `report` against the expectations
+    pub fn diff(&self, report: &WptReport) -> WptDiff {
+        let actual = Self::from_report(report);
+        let mut diff = WptDiff::default();
+
+        for (path, subtests) in &self.0 {
+            for (name, &expected) in subtests {
+                let entry = |actual| WptDiffEntry {
+                    path: path.clone(),
+                    subtest: name.clone(),
+                    expected: Some(expected),
+                    actual,
+                };
+                match actual.get(path, name) {
+                    None if expected == WptExpectation::Pass => {
+                        diff.regressions.push(entry(None))
+                    }
+                    None => diff.missing.push(entry(None)),
+                    Some(outcome) if outcome == expected => {}
+                    Some(WptExpectation::Pass) => {
+                        diff.newly_passing.push(entry(Some(WptExpectation::Pass)))
+                    }
+                    Some(outcome)
+                        if expected == WptExpectation::Pass
+                            && report.is_flaky(path, name) =>
+                    {
+                        diff.flaky.push(entry(Some(outcome)))
+                    }
+                    Some(outcome) if expected == WptExpectation::Pass => {
+                        diff.regressions.push(entry(Some(outcome)))
+                    }
+                    Some(outcome) => diff.changed.push(entry(Some(outcome))),
+                }
+            }
+        }
+
+        for (path, subtests) in &actual.0 {
+            for (name, &outcome) in subtests {
+                if self.get(path, name).is_none() {
+                    diff.added.push(WptDiffEntry {
+                        path: path.clone(),
+                        subtest: name.clone(),
+                        expected: None,
+                        actual: Some(outcome),
+                    });
+                }
+            }
+        }
+
+        diff
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WptDiffEntry {
+    pub path: String,
+    pub subtest: String,
+    /// `None` if the subtest has no expectation
+    pub expected: Option<WptExpectation>,
+    /// `None` if the subtest did not run
+    pub actual: Option<WptExpectation>,
+}
+
+impl fmt::Display for WptDiffEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = |v: Option<WptExpectation>| match v {
+            Some(v) => v.to_string(),
+            None => "MISSING".to_string(),
+        };
+        write!(
+            f,
+            "{} > {} ({} -> {})",
+            self.path,
+            self.subtest,
+            outcome(self.expected),
+            outcome(self.actual)
+        )
+    }
+}
+
+/// Result of comparing a report against [`WptExpectations`]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct WptDiff {
+    /// Subtests expected to pass that failed, timed out or did not run
+    pub regressions: Vec<WptDiffEntry>,
+    /// Subtests expected to pass that failed, but are flaky or quarantined.
+    /// These do not count as regressions.
+    pub flaky: Vec<WptDiffEntry>,
+    /// Subtests expected to fail or time out that now pass
+    pub newly_passing: Vec<WptDiffEntry>,
+    /// Subtests whose non-passing outcome changed (e.g. FAIL -> TIMEOUT)
+    pub changed: Vec<WptDiffEntry>,
+    /// Subtests expected to fail or time out that did not run
+    pub missing: Vec<WptDiffEntry>,
+    /// Subtests without an expectation
+    pub added: Vec<WptDiffEntry>,
+}
+
+impl WptDiff {
+    pub fn has_regressions(&self) -> bool {
+        !self.regressions.is_empty()
+    }
+
+    /// Moves the regressions of subtests in `quarantine` to [`WptDiff::flaky`]
+    pub fn quarantine(mut self, quarantine: &WptQuarantine) -> Self {
+        let (flaky, regressions) = self
+            .regressions
+            .into_iter()
+            .partition::<Vec<_>, _>(|e| quarantine.contains(&e.path, &e.subtest));
+        self.regressions = regressions;
+        self.flaky.extend(flaky);
+        self
+    }
+}
+
+/// WptQuarantine lists the subtests known to be flaky, keyed by test file path
+/// and subtest name, along with the number of runs in which they were found flaky.
+/// Failures of quarantined subtests are not treated as regressions.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct WptQuarantine(BTreeMap<String, BTreeMap<String, u64>>);
+
+impl WptQuarantine {
+    pub fn load(path: &Path) -> Result<Self> {
+        load_json(path, "quarantine")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        save_json(self, path, "quarantine")
+    }
+
+    pub fn contains(&self, path: &str, subtest: &str) -> bool {
+        self.flaky_runs(path, subtest) > 0
+    }
+
+    /// Number of recorded runs in which the subtest only passed on retry
+    pub fn flaky_runs(&self, path: &str, subtest: &str) -> u64 {
+        self.0
+            .get(path)
+            .and_then(|s| s.get(subtest))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Adds the flaky subtests of `report` to the quarantine
+    pub fn record(&mut self, report: &WptReport) {
+        for (path, subtests) in report.flaky() {
+            let entry = self.0.entry(path.clone()).or_default();
+            for subtest in subtests {
+                *entry.entry(subtest.clone()).or_default() += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use jstz_runtime::wpt::{WptSubtest, WptSubtestStatus, WptTestStatus};
+
+    use super::{WptDiffEntry, WptExpectation, WptExpectations, WptQuarantine};
+    use crate::{WptReport, WptReportTest};
+
+    fn subtest(name: &str, status: WptSubtestStatus) -> WptSubtest {
+        WptSubtest {
+            name: name.to_string(),
+            status,
+            message: None,
+        }
+    }
+
+    fn report(tests: &[(&str, Vec<WptSubtest>)]) -> WptReport {
+        let mut report = WptReport::default();
+        for (path, subtests) in tests {
+            report
+                .insert(
+                    path,
+                    WptReportTest::new(WptTestStatus::Ok, subtests.clone()),
+                )
+                .unwrap();
+        }
+        report
+    }
+
+    #[test]
+    fn from_report() {
+        let report = report(&[
+            (
+                "foo/a.any.js",
+                vec![
+                    subtest("a", WptSubtestStatus::Pass),
+                    subtest("b", WptSubtestStatus::Pass),
+                ],
+            ),
+            (
+                "foo/a.any.js",
+                vec![
+                    subtest("a", WptSubtestStatus::Pass),
+                    subtest("b", WptSubtestStatus::NotRun),
+                ],
+            ),
+            (
+                "foo/b.any.js",
+                vec![subtest("c", WptSubtestStatus::Timeout)],
+            ),
+            ("foo/c.any.js", vec![]),
+        ]);
+
+        let expectations = WptExpectations::from_report(&report);
+        assert_eq!(
+            expectations.get("foo/a.any.js", "a"),
+            Some(WptExpectation::Pass)
+        );
+        assert_eq!(
+            expectations.get("foo/a.any.js", "b"),
+            Some(WptExpectation::Fail)
+        );
+        assert_eq!(
+            expectations.get("foo/b.any.js", "c"),
+            Some(WptExpectation::Timeout)
+        );
+        assert!(!expectations.0.contains_key("foo/c.any.js"));
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let expectations = WptExpectations::from_report(&report(&[(
+            "foo/a.any.js",
+            vec![subtest("a", WptSubtestStatus::Pass)],
+        )]));
+        let json = serde_json::to_value(&expectations).unwrap();
+        assert_eq!(json, serde_json::json!({ "foo/a.any.js": { "a": "PASS" } }));
+        assert_eq!(
+            serde_json::from_value::<WptExpectations>(json).unwrap(),
+            expectations
+        );
+    }
+
+    #[test]
+    fn diff() {
+        let expectations = WptExpectations::from_report(&report(&[(
+            "a.any.js",
+            vec![
+                subtest("unchanged", WptSubtestStatus::Pass),
+                subtest("regressed", WptSubtestStatus::Pass),
+                subtest("missing_pass", WptSubtestStatus::Pass),
+                subtest("fixed", WptSubtestStatus::Fail),
+                subtest("changed", WptSubtestStatus::Fail),
+                subtest("missing_fail", WptSubtestStatus::Fail),
+            ],
+        )]));
+        let diff = expectations.diff(&report(&[(
+            "a.any.js",
+            vec![
+                subtest("unchanged", WptSubtestStatus::Pass),
+                subtest("regressed", WptSubtestStatus::Fail),
+                subtest("fixed", WptSubtestStatus::Pass),
+                subtest("changed", WptSubtestStatus::Timeout),
+                subtest("added", WptSubtestStatus::Pass),
+            ],
+        )]));
+
+        let names = |entries: &[WptDiffEntry]| {
+            entries
+                .iter()
+                .map(|e| e.subtest.clone())
+                .collect::<Vec<_>>()
+        };
+        assert!(diff.has_regressions());
+        assert_eq!(names(&diff.regressions), ["missing_pass", "regressed"]);
+        assert_eq!(names(&diff.newly_passing), ["fixed"]);
+        assert_eq!(names(&diff.changed), ["changed"]);
+        assert_eq!(names(&diff.missing), ["missing_fail"]);
+        assert_eq!(names(&diff.added), ["added"]);
+        assert_eq!(
+            diff.regressions[1].to_string(),
+            "a.any.js > regressed (PASS -> FAIL)"
+        );
+    }
+
+    #[test]
+    fn diff_flaky() {
+        let expectations = WptExpectations::from_report(&report(&[(
+            "a.any.js",
+            vec![
+                subtest("flaky", WptSubtestStatus::Pass),
+                subtest("quarantined", WptSubtestStatus::Pass),
+                subtest("regressed", WptSubtestStatus::Pass),
+            ],
+        )]));
+        let mut report = report(&[(
+            "a.any.js",
+            vec![
+                subtest("flaky", WptSubtestStatus::Fail),
+                subtest("quarantined", WptSubtestStatus::Timeout),
+                subtest("regressed", WptSubtestStatus::Fail),
+            ],
+        )]);
+        report.mark_flaky("a.any.js", BTreeSet::from_iter(["flaky".to_string()]));
+
+        let mut quarantine = WptQuarantine::default();
+        quarantine.record(&report);
+        quarantine.record(&report);
+        assert_eq!(quarantine.flaky_runs("a.any.js", "flaky"), 2);
+        assert!(!quarantine.contains("a.any.js", "quarantined"));
+        quarantine
+            .0
+            .entry("a.any.js".to_string())
+            .or_default()
+            .insert("quarantined".to_string(), 1);
+
+        let names = |entries: &[WptDiffEntry]| {
+            entries
+                .iter()
+                .map(|e| e.subtest.clone())
+                .collect::<Vec<_>>()
+        };
+        let diff = expectations.diff(&report);
+        assert_eq!(names(&diff.flaky), ["flaky"]);
+        assert_eq!(names(&diff.regressions), ["quarantined", "regressed"]);
+
+        let diff = diff.quarantine(&quarantine);
+        assert_eq!(names(&diff.flaky), ["flaky", "quarantined"]);
+        assert_eq!(names(&diff.regressions), ["regressed"]);
+    }
+
+    #[test]
+    fn quarantine_serde() {
+        let quarantine: WptQuarantine =
+            serde_json::from_value(serde_json::json!({ "a.any.js": { "foo": 3 } }))
+                .unwrap();
+        assert_eq!(quarantine.flaky_runs("a.any.js", "foo"), 3);
+        assert_eq!(quarantine.flaky_runs("a.any.js", "bar"), 0);
+    }
+
+    #[test]
+    fn diff_without_regressions() {
+        let report = report(&[(
+            "a.any.js",
+            vec![
+                subtest("a", WptSubtestStatus::Pass),
+                subtest("b", WptSubtestStatus::Fail),
+            ],
+        )]);
+        let diff = WptExpectations::from_report(&report).diff(&report);
+        assert!(!diff.has_regressions());
+        assert_eq!(diff, Default::default());
+    }
+}
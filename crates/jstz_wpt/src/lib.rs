@@ -1,10 +1,14 @@
+mod expectations;
 mod manifest;
 mod report;
 mod serve;
+mod wptfyi;
 
+pub use expectations::*;
 pub use manifest::*;
 pub use report::*;
 pub use serve::*;
+pub use wptfyi::*;
 
 use std::{ffi::OsStr, fs, path::PathBuf, process::Stdio, time::Duration};
 
@@ -1,12 +1,13 @@
 use std::{
     fs,
     io::{self, Write},
+    path::PathBuf,
     process,
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
-use jstz_wpt::Wpt;
+use jstz_wpt::{Wpt, WptExpectations, WptQuarantine, WptReport};
 
 #[derive(Parser)]
 #[command(author, version)]
@@ -33,6 +34,22 @@ enum Command {
     },
     // Update the `hosts` file to match the current wpt version
     UpdateHosts,
+    // Compare a report against an expectations file. Fails only on regressions.
+    Diff {
+        // Path to the report generated by the test runner
+        #[clap(long)]
+        report: PathBuf,
+        // Path to the expectations file
+        #[clap(long)]
+        expectations: PathBuf,
+        // Path to the quarantine file listing known flaky tests
+        #[clap(long)]
+        quarantine: Option<PathBuf>,
+        // Overwrite the expectations file with the outcomes of the report
+        // and add the flaky tests of the report to the quarantine file
+        #[clap(long, action)]
+        update: bool,
+    },
 }
 
 fn run_git(args: &[&str]) -> Result<()> {
@@ -46,6 +63,71 @@ fn run_git(args: &[&str]) -> Result<()> {
     Ok(())
 }
 
+fn diff(
+    report: PathBuf,
+    expectations: PathBuf,
+    quarantine: Option<PathBuf>,
+    update: bool,
+) -> Result<()> {
+    let report: WptReport = serde_json::from_str(
+        &fs::read_to_string(&report).context("failed to read report")?,
+    )
+    .context("failed to parse report")?;
+
+    let load_quarantine = |path: &PathBuf| match path.exists() {
+        true => WptQuarantine::load(path),
+        false => Ok(WptQuarantine::default()),
+    };
+
+    if update {
+        WptExpectations::from_report(&report).save(&expectations)?;
+        println!("Updated {}", expectations.display());
+        if let Some(path) = quarantine {
+            let mut q = load_quarantine(&path)?;
+            q.record(&report);
+            q.save(&path)?;
+            println!("Updated {}", path.display());
+        }
+        return Ok(());
+    }
+
+    let mut diff = WptExpectations::load(&expectations)?.diff(&report);
+    if let Some(path) = &quarantine {
+        diff = diff.quarantine(&load_quarantine(path)?);
+    }
+
+    for (title, entries) in [
+        ("Flaky", &diff.flaky),
+        ("Newly passing", &diff.newly_passing),
+        ("Changed", &diff.changed),
+        ("Missing", &diff.missing),
+        ("Without expectation", &diff.added),
+        ("Regressions", &diff.regressions),
+    ] {
+        if entries.is_empty() {
+            continue;
+        }
+        println!("{title} ({}):", entries.len());
+        for entry in entries {
+            println!("  {entry}");
+        }
+    }
+
+    if diff.has_regressions() {
+        return Err(anyhow::anyhow!(
+            "Found {} regression(s) against {}",
+            diff.regressions.len(),
+            expectations.display()
+        ));
+    }
+
+    if !diff.newly_passing.is_empty() {
+        println!("Run with `--update` to record the newly passing tests.");
+    }
+    println!("No regressions found");
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let command = Command::parse();
@@ -143,6 +225,12 @@ async fn main() -> Result<()> {
             Wpt::new().await?.update_manifest(rebuild).await?
         }
         Command::UpdateHosts => Wpt::new().await?.update_hosts().await?,
+        Command::Diff {
+            report,
+            expectations,
+            quarantine,
+            update,
+        } => diff(report, expectations, quarantine, update)?,
     }
 
     Ok(())
@@ -1,6 +1,7 @@
 use std::{
     collections::{BTreeMap, HashSet},
     result,
+    time::Duration,
 };
 
 use regex::RegexSet;
@@ -126,17 +127,67 @@ pub struct WptTestOptions {
     pub script_metadata: Vec<(String, String)>,
 }
 
+/// Environment variable restricting the run to a directory, e.g. `url` or `streams/piping`
+pub const WPT_DIR_ENV: &str = "WPT_DIR";
+/// Environment variable holding comma-separated globs of tests to include
+pub const WPT_INCLUDE_ENV: &str = "WPT_INCLUDE";
+/// Environment variable holding comma-separated globs of tests to exclude
+pub const WPT_EXCLUDE_ENV: &str = "WPT_EXCLUDE";
+
+/// Converts a glob into an anchored regex matching URL paths relative to the
+/// wpt root. `**` matches across directories, `*` and `?` do not.
+fn glob_to_regex(glob: &str) -> String {
+    let glob = glob.trim().trim_start_matches('/');
+    let mut regex = String::from("^/");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches no directory at all
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+fn glob_set<S: AsRef<str>>(globs: &[S]) -> anyhow::Result<RegexSet> {
+    Ok(RegexSet::new(
+        globs.iter().map(|glob| glob_to_regex(glob.as_ref())),
+    )?)
+}
+
 /// TestFilters provide a way to filter tests from [`WptManifest`]
 #[derive(Default, Debug, Clone)]
 pub struct TestFilter {
     pub folders: RegexSet,
+    include: RegexSet,
+    exclude: RegexSet,
     expected_tests: Option<HashSet<String>>,
 }
 
 impl TestFilter {
-    /// Returns true if the path matches a folder in
-    /// the filter
+    /// Returns true if the path matches a folder in the filter,
+    /// matches an include glob (if any) and matches no exclude glob
     pub fn is_match(&self, path: &str) -> bool {
+        if self.exclude.is_match(path) {
+            return false;
+        }
+
+        if !self.include.is_empty() && !self.include.is_match(path) {
+            return false;
+        }
+
         if self.folders.is_empty() {
             return true;
         }
@@ -144,6 +195,53 @@ impl TestFilter {
         self.folders.is_match(path)
     }
 
+    /// Only run tests whose URL path matches one of the `globs`,
+    /// e.g. `url/**` or `streams/**/*.any.html`
+    pub fn set_include<S: AsRef<str>>(&mut self, globs: &[S]) -> anyhow::Result<()> {
+        self.include = glob_set(globs)?;
+        Ok(())
+    }
+
+    /// Skip tests whose URL path matches one of the `globs`
+    pub fn set_exclude<S: AsRef<str>>(&mut self, globs: &[S]) -> anyhow::Result<()> {
+        self.exclude = glob_set(globs)?;
+        Ok(())
+    }
+
+    /// Only run tests located under `dir` (relative to the wpt root).
+    /// Equivalent to including `{dir}/**`.
+    pub fn set_dir(&mut self, dir: &str) -> anyhow::Result<()> {
+        let dir = dir.trim().trim_matches('/');
+        self.set_include(&[format!("{dir}/**")])
+    }
+
+    /// Applies the selection given by [`WPT_DIR_ENV`], [`WPT_INCLUDE_ENV`]
+    /// and [`WPT_EXCLUDE_ENV`], if set. This allows running a subset of the
+    /// suite through `cargo test`, e.g. `WPT_DIR=url cargo test --test wpt`.
+    pub fn apply_env(&mut self) -> anyhow::Result<()> {
+        let globs = |name| {
+            std::env::var(name).ok().map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        let mut include = globs(WPT_INCLUDE_ENV).unwrap_or_default();
+        if let Ok(dir) = std::env::var(WPT_DIR_ENV) {
+            include.push(format!("{}/**", dir.trim().trim_matches('/')));
+        }
+        if !include.is_empty() {
+            self.set_include(&include)?;
+        }
+        if let Some(exclude) = globs(WPT_EXCLUDE_ENV) {
+            self.set_exclude(&exclude)?;
+        }
+        Ok(())
+    }
+
     pub fn is_expected(&self, path: &str) -> bool {
         self.expected_tests
             .as_ref()
@@ -171,7 +269,7 @@ impl TryFrom<&[&str]> for TestFilter {
         let folders = RegexSet::new(value)?;
         Ok(Self {
             folders,
-            expected_tests: None,
+            ..Default::default()
         })
     }
 }
@@ -181,6 +279,9 @@ pub struct TestToRun {
     pub manifest_path: String,
     pub url_path: String,
     pub options: WptTestOptions,
+    /// Wall-clock limit for the test, after which runners should terminate
+    /// the test and report it as timed out
+    pub timeout: Option<Duration>,
 }
 
 pub trait WptTests {
@@ -233,6 +334,7 @@ impl WptTests for WptManifestTest {
                         url_path: url_path.clone(),
                         manifest_path: path.clone(),
                         options: options.clone(),
+                        timeout: None,
                     })
                 },
             )
@@ -306,6 +408,48 @@ mod tests {
         assert!(!filter.is_expected("http://host/bar"));
     }
 
+    #[test]
+    fn glob_to_regex() {
+        let re = |glob| regex::Regex::new(&super::glob_to_regex(glob)).unwrap();
+
+        assert!(re("url/**").is_match("/url/a.any.html"));
+        assert!(re("/url/**").is_match("/url/foo/a.any.html"));
+        assert!(!re("url/**").is_match("/urlpattern/a.any.html"));
+        assert!(re("url/*.any.html").is_match("/url/a.any.html"));
+        assert!(!re("url/*.any.html").is_match("/url/foo/a.any.html"));
+        assert!(re("streams/**/*.any.html").is_match("/streams/a.any.html"));
+        assert!(re("streams/**/*.any.html").is_match("/streams/piping/a.any.html"));
+        assert!(re("fetch/?.any.html").is_match("/fetch/a.any.html"));
+        assert!(!re("fetch/?.any.html").is_match("/fetch/ab.any.html"));
+    }
+
+    #[test]
+    fn test_filter_include_exclude() {
+        let mut filter = TestFilter::default();
+        assert!(filter.is_match("/url/a.any.html"));
+
+        filter
+            .set_include(&["url/**", "encoding/*.any.html"])
+            .unwrap();
+        assert!(filter.is_match("/url/a.any.html"));
+        assert!(filter.is_match("/encoding/a.any.html"));
+        assert!(!filter.is_match("/encoding/streams/a.any.html"));
+        assert!(!filter.is_match("/streams/a.any.html"));
+
+        filter.set_exclude(&["**/*.worker.html"]).unwrap();
+        assert!(filter.is_match("/url/a.any.html"));
+        assert!(!filter.is_match("/url/a.any.worker.html"));
+    }
+
+    #[test]
+    fn test_filter_set_dir() {
+        let mut filter = TestFilter::try_from(["/url/a"].as_slice()).unwrap();
+        filter.set_dir("/url/").unwrap();
+        assert!(filter.is_match("/url/a.any.html"));
+        assert!(!filter.is_match("/url/b.any.html"));
+        assert!(!filter.is_match("/streams/a.any.html"));
+    }
+
     #[test]
     fn manifest_test_tests() {
         // efgh should be filtered out because they don't meet the requirements for paths
@@ -1,12 +1,44 @@
-use std::collections::{btree_map::Entry, BTreeMap};
+use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    fmt::Write,
+    time::{Duration, SystemTime},
+};
 
 use anyhow::Result;
 use jstz_runtime::wpt::{WptSubtest, WptSubtestStatus, WptTestStatus};
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
+use crate::TestToRun;
+
+#[derive(Default, Debug, Deserialize, Serialize, Clone)]
 pub struct WptReport {
     test_harness: WptReportFolder,
+    /// Individual test runs in the order they were recorded. These carry timing
+    /// information and are therefore not part of the persisted report.
+    #[serde(skip)]
+    runs: Vec<WptTestRun>,
+    /// Subtests, keyed by test file path, that only passed on retry
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    flaky: BTreeMap<String, BTreeSet<String>>,
+}
+
+// Runs are excluded so that reports of the same tests compare equal regardless of timing
+impl PartialEq for WptReport {
+    fn eq(&self, other: &Self) -> bool {
+        self.test_harness == other.test_harness && self.flaky == other.flaky
+    }
+}
+
+impl Eq for WptReport {}
+
+/// A single execution of a test variation
+#[derive(Debug, Clone)]
+pub struct WptTestRun {
+    /// URL path of the test, e.g. `url/a.any.html?include=foo`
+    pub url_path: String,
+    pub started_at: SystemTime,
+    pub duration: Duration,
+    pub test: WptReportTest,
 }
 
 impl WptReport {
@@ -18,6 +50,56 @@ impl WptReport {
         insert_test_in_folder(&mut self.test_harness, path, test)
     }
 
+    /// Inserts the outcome of `test` and keeps track of when and how long it ran
+    pub fn record(
+        &mut self,
+        test: &TestToRun,
+        started_at: SystemTime,
+        duration: Duration,
+        report: WptReportTest,
+    ) -> Result<()> {
+        self.insert(&test.manifest_path, report.clone())?;
+        self.runs.push(WptTestRun {
+            url_path: test.url_path.clone(),
+            started_at,
+            duration,
+            test: report,
+        });
+        Ok(())
+    }
+
+    pub fn runs(&self) -> &[WptTestRun] {
+        &self.runs
+    }
+
+    /// Marks `subtests` of the test file at `path` as flaky
+    pub fn mark_flaky(&mut self, path: &str, subtests: BTreeSet<String>) {
+        if !subtests.is_empty() {
+            self.flaky
+                .entry(path.to_string())
+                .or_default()
+                .extend(subtests);
+        }
+    }
+
+    pub fn flaky(&self) -> &BTreeMap<String, BTreeSet<String>> {
+        &self.flaky
+    }
+
+    pub fn is_flaky(&self, path: &str, subtest: &str) -> bool {
+        self.flaky.get(path).is_some_and(|s| s.contains(subtest))
+    }
+
+    /// Flattens the report into a map from test file path (e.g.
+    /// `url/url-constructor.any.js`) to the variations run for that file
+    pub fn tests(&self) -> BTreeMap<String, &[WptReportTest]> {
+        let mut tests = BTreeMap::new();
+        for (name, file) in self.test_harness() {
+            file.collect_tests(name.to_owned(), &mut tests);
+        }
+        tests
+    }
+
     pub fn stats(&self) -> BTreeMap<String, WptMetrics> {
         let mut stats = BTreeMap::new();
         for (folder, report) in self.test_harness() {
@@ -106,6 +188,23 @@ impl WptReportFile {
         }
     }
 
+    fn collect_tests<'a>(
+        &'a self,
+        path: String,
+        tests: &mut BTreeMap<String, &'a [WptReportTest]>,
+    ) {
+        match self {
+            WptReportFile::Folder(folder) => {
+                for (name, file) in folder {
+                    file.collect_tests(format!("{path}/{name}"), tests);
+                }
+            }
+            WptReportFile::Test { variations } => {
+                tests.insert(path, variations.as_slice());
+            }
+        }
+    }
+
     pub fn stats(&self) -> BTreeMap<String, WptMetrics> {
         match self {
             WptReportFile::Folder(folder_map) => {
@@ -165,6 +264,215 @@ impl WptReportTest {
     }
 }
 
+/// Escapes text for use in XML/HTML content and attribute values
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters are not allowed in XML 1.0
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn pass_rate(metrics: &WptMetrics) -> f64 {
+    match metrics.total() {
+        0 => 0f64,
+        total => 100f64 * metrics.passed as f64 / total as f64,
+    }
+}
+
+impl WptReport {
+    /// Aggregates the metrics of all test files by the directory they live in
+    pub fn directory_stats(&self) -> BTreeMap<String, WptMetrics> {
+        let mut stats: BTreeMap<String, WptMetrics> = BTreeMap::new();
+        for (path, metrics) in self.stats() {
+            let dir = match path.rsplit_once('/') {
+                Some((dir, _)) => dir.to_string(),
+                None => String::new(),
+            };
+            let entry = stats.entry(dir).or_default();
+            entry.passed += metrics.passed;
+            entry.failed += metrics.failed;
+            entry.timed_out += metrics.timed_out;
+        }
+        stats
+    }
+
+    /// Renders the report as JUnit XML. Every test file is a test suite
+    /// and every subtest a test case.
+    pub fn to_junit_xml(&self) -> String {
+        let tests = self.tests();
+        let total =
+            self.stats()
+                .values()
+                .fold(WptMetrics::default(), |acc, m| WptMetrics {
+                    passed: acc.passed + m.passed,
+                    failed: acc.failed + m.failed,
+                    timed_out: acc.timed_out + m.timed_out,
+                });
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuites name=\"wpt\" tests=\"{}\" failures=\"{}\">",
+            total.total(),
+            total.failed + total.timed_out
+        );
+
+        for (path, variations) in tests {
+            let path = escape_xml(&path);
+            let subtests = variations.iter().map(|v| v.subtests.len()).sum::<usize>();
+            let failures = variations
+                .iter()
+                .map(|v| v.metrics.failed + v.metrics.timed_out)
+                .sum::<u64>();
+            // A test file that failed before reporting any subtest
+            // is recorded as a single failing test case
+            let errored = variations
+                .iter()
+                .filter(|v| v.subtests.is_empty() && v.status != WptTestStatus::Ok)
+                .count();
+
+            let _ = writeln!(
+                xml,
+                "  <testsuite name=\"{path}\" tests=\"{}\" failures=\"{failures}\" errors=\"{errored}\">",
+                subtests + errored
+            );
+
+            for (index, variation) in variations.iter().enumerate() {
+                let suffix = match variations.len() {
+                    1 => String::new(),
+                    _ => format!(" [variation {}]", index + 1),
+                };
+
+                if variation.subtests.is_empty() && variation.status != WptTestStatus::Ok
+                {
+                    let _ = writeln!(
+                        xml,
+                        "    <testcase classname=\"{path}\" name=\"{path}{suffix}\">\n      <error message=\"{:?}\"/>\n    </testcase>",
+                        variation.status
+                    );
+                }
+
+                for subtest in &variation.subtests {
+                    let name = escape_xml(&format!("{}{suffix}", subtest.name));
+                    let message =
+                        escape_xml(subtest.message.as_deref().unwrap_or_default());
+                    let _ =
+                        write!(xml, "    <testcase classname=\"{path}\" name=\"{name}\"");
+                    match subtest.status {
+                        WptSubtestStatus::Pass => {
+                            let _ = writeln!(xml, "/>");
+                        }
+                        WptSubtestStatus::Timeout => {
+                            let _ = writeln!(
+                                xml,
+                                ">\n      <failure type=\"timeout\" message=\"{message}\"/>\n    </testcase>"
+                            );
+                        }
+                        _ => {
+                            let _ = writeln!(
+                                xml,
+                                ">\n      <failure type=\"{:?}\" message=\"{message}\"/>\n    </testcase>",
+                                subtest.status
+                            );
+                        }
+                    }
+                }
+            }
+
+            let _ = writeln!(xml, "  </testsuite>");
+        }
+
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+
+    /// Renders a static HTML page summarising the pass rate per directory
+    /// and listing the details of every failing subtest
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>WPT report</title>\n<style>\nbody { font-family: sans-serif; }\ntable { border-collapse: collapse; }\nth, td { border: 1px solid #ccc; padding: 2px 8px; text-align: left; }\n.fail { color: #b00; }\n.timeout { color: #b60; }\n</style>\n</head>\n<body>\n",
+        );
+
+        let stats = self.directory_stats();
+        let total = stats
+            .values()
+            .fold(WptMetrics::default(), |acc, m| WptMetrics {
+                passed: acc.passed + m.passed,
+                failed: acc.failed + m.failed,
+                timed_out: acc.timed_out + m.timed_out,
+            });
+        let _ = writeln!(
+            html,
+            "<h1>WPT report</h1>\n<p>Total pass rate: {:.2}% ({}/{})</p>",
+            pass_rate(&total),
+            total.passed,
+            total.total()
+        );
+
+        html.push_str("<h2>Directories</h2>\n<table>\n<tr><th>Directory</th><th>Passed</th><th>Failed</th><th>Timed out</th><th>Pass rate</th></tr>\n");
+        for (dir, metrics) in &stats {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.2}%</td></tr>",
+                escape_xml(dir),
+                metrics.passed,
+                metrics.failed,
+                metrics.timed_out,
+                pass_rate(metrics)
+            );
+        }
+        html.push_str("</table>\n<h2>Failures</h2>\n");
+
+        for (path, variations) in self.tests() {
+            let failures = variations
+                .iter()
+                .flat_map(|v| &v.subtests)
+                .filter(|s| s.status != WptSubtestStatus::Pass)
+                .collect::<Vec<_>>();
+            if failures.is_empty() {
+                continue;
+            }
+
+            let _ = writeln!(
+                html,
+                "<details>\n<summary>{} ({})</summary>\n<ul>",
+                escape_xml(&path),
+                failures.len()
+            );
+            for subtest in failures {
+                let class = match subtest.status {
+                    WptSubtestStatus::Timeout => "timeout",
+                    _ => "fail",
+                };
+                let _ = writeln!(
+                    html,
+                    "<li class=\"{class}\">{:?}: {}{}</li>",
+                    subtest.status,
+                    escape_xml(&subtest.name),
+                    match &subtest.message {
+                        Some(message) => format!("<pre>{}</pre>", escape_xml(message)),
+                        None => String::new(),
+                    }
+                );
+            }
+            html.push_str("</ul>\n</details>\n");
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeMap;
@@ -289,9 +597,133 @@ mod tests {
         );
     }
 
+    #[test]
+    fn wpt_report_tests() {
+        let mut report = WptReport::default();
+        let test = WptReportTest::new(WptTestStatus::Ok, vec![]);
+        report.insert("foo/a.any.js", test.clone()).unwrap();
+        report.insert("foo/a.any.js", test.clone()).unwrap();
+        report.insert("foo/bar/b.any.js", test.clone()).unwrap();
+        report.insert("c.any.js", test.clone()).unwrap();
+
+        let tests = report.tests();
+        assert_eq!(
+            tests.keys().collect::<Vec<_>>(),
+            ["c.any.js", "foo/a.any.js", "foo/bar/b.any.js"]
+        );
+        assert_eq!(tests["foo/a.any.js"].len(), 2);
+    }
+
+    fn junit_report() -> WptReport {
+        let mut report = WptReport::default();
+        report
+            .insert(
+                "url/a.any.js",
+                WptReportTest::new(
+                    WptTestStatus::Ok,
+                    vec![
+                        WptSubtest {
+                            name: "passes".to_string(),
+                            status: WptSubtestStatus::Pass,
+                            message: None,
+                        },
+                        WptSubtest {
+                            name: "fails <&>".to_string(),
+                            status: WptSubtestStatus::Fail,
+                            message: Some("expected \"a\"".to_string()),
+                        },
+                    ],
+                ),
+            )
+            .unwrap();
+        report
+            .insert(
+                "url/b.any.js",
+                WptReportTest::new(
+                    WptTestStatus::Ok,
+                    vec![WptSubtest {
+                        name: "times out".to_string(),
+                        status: WptSubtestStatus::Timeout,
+                        message: None,
+                    }],
+                ),
+            )
+            .unwrap();
+        report
+            .insert(
+                "streams/c.any.js",
+                WptReportTest::new(WptTestStatus::Err, vec![]),
+            )
+            .unwrap();
+        report
+    }
+
+    #[test]
+    fn wpt_report_directory_stats() {
+        assert_eq!(
+            junit_report().directory_stats(),
+            BTreeMap::from_iter([
+                ("streams".to_string(), WptMetrics::default()),
+                (
+                    "url".to_string(),
+                    WptMetrics {
+                        passed: 1,
+                        failed: 1,
+                        timed_out: 1,
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn wpt_report_to_junit_xml() {
+        let xml = junit_report().to_junit_xml();
+        assert_eq!(
+            xml,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="wpt" tests="3" failures="2">
+  <testsuite name="streams/c.any.js" tests="1" failures="0" errors="1">
+    <testcase classname="streams/c.any.js" name="streams/c.any.js">
+      <error message="Err"/>
+    </testcase>
+  </testsuite>
+  <testsuite name="url/a.any.js" tests="2" failures="1" errors="0">
+    <testcase classname="url/a.any.js" name="passes"/>
+    <testcase classname="url/a.any.js" name="fails &lt;&amp;&gt;">
+      <failure type="Fail" message="expected &quot;a&quot;"/>
+    </testcase>
+  </testsuite>
+  <testsuite name="url/b.any.js" tests="1" failures="1" errors="0">
+    <testcase classname="url/b.any.js" name="times out">
+      <failure type="timeout" message=""/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+
+    #[test]
+    fn wpt_report_to_html() {
+        let html = junit_report().to_html();
+        assert!(html.contains("Total pass rate: 33.33% (1/3)"));
+        assert!(html.contains(
+            "<tr><td>url</td><td>1</td><td>1</td><td>1</td><td>33.33%</td></tr>"
+        ));
+        assert!(html.contains("<summary>url/a.any.js (1)</summary>"));
+        assert!(html.contains(
+            "<li class=\"fail\">Fail: fails &lt;&amp;&gt;<pre>expected &quot;a&quot;</pre></li>"
+        ));
+        assert!(html.contains("<li class=\"timeout\">Timeout: times out</li>"));
+        assert!(!html.contains("passes"));
+    }
+
     #[test]
     fn wpt_report_stats() {
         let report = WptReport {
+            runs: vec![],
+            flaky: BTreeMap::new(),
             test_harness: BTreeMap::from_iter([
                 (
                     "test1".to_string(),
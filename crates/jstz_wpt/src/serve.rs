@@ -1,6 +1,19 @@
-use std::{borrow::Cow, future::IntoFuture, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::BTreeSet,
+    env,
+    future::IntoFuture,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
 
 use anyhow::Result;
+use jstz_runtime::wpt::{WptSubtestStatus, WptTestStatus};
 use nix::{
     sys::signal::{self, Signal::SIGINT},
     unistd::Pid,
@@ -17,6 +30,78 @@ use crate::{
 
 const TEST_HARNESS_REPORT_PATH: &str = "/resources/testharnessreport.js";
 
+/// Environment variable overriding [`WptRunOptions::workers`]
+pub const WPT_WORKERS_ENV: &str = "WPT_WORKERS";
+/// Environment variable overriding [`WptRunOptions::retries`]
+pub const WPT_RETRIES_ENV: &str = "WPT_RETRIES";
+/// Environment variable overriding [`WptRunOptions::timeout`] (in seconds, 0 disables it)
+pub const WPT_TIMEOUT_ENV: &str = "WPT_TIMEOUT";
+/// Environment variable overriding [`WptRunOptions::max_heap_size`] (in MiB)
+pub const WPT_MAX_HEAP_SIZE_ENV: &str = "WPT_MAX_HEAP_SIZE";
+
+/// Same as the `long` timeout of wpt
+const DEFAULT_TEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Options for [`WptServe::run_test_harness_parallel`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WptRunOptions {
+    /// Number of tests to execute concurrently
+    pub workers: NonZeroUsize,
+    /// Number of times a test with failing subtests is retried. Subtests that
+    /// only pass on retry are marked as flaky in the report.
+    pub retries: usize,
+    /// Wall-clock limit of a single test attempt. Tests exceeding it are
+    /// recorded as timed out. Passed on to runners through [`TestToRun::timeout`]
+    /// so that they can terminate the test.
+    pub timeout: Option<Duration>,
+    /// Heap size limit (in MiB) runners should apply to their runtimes
+    pub max_heap_size: Option<usize>,
+}
+
+impl Default for WptRunOptions {
+    fn default() -> Self {
+        Self {
+            workers: thread::available_parallelism().unwrap_or(NonZeroUsize::MIN),
+            retries: 0,
+            timeout: Some(DEFAULT_TEST_TIMEOUT),
+            max_heap_size: None,
+        }
+    }
+}
+
+impl WptRunOptions {
+    /// Default options, overridden by [`WPT_WORKERS_ENV`], [`WPT_RETRIES_ENV`],
+    /// [`WPT_TIMEOUT_ENV`] and [`WPT_MAX_HEAP_SIZE_ENV`] if set
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            env::var(name).ok().and_then(|v| v.trim().parse().ok())
+        }
+
+        let default = Self::default();
+        Self {
+            workers: var(WPT_WORKERS_ENV).unwrap_or(default.workers),
+            retries: var(WPT_RETRIES_ENV).unwrap_or(default.retries),
+            timeout: match var::<u64>(WPT_TIMEOUT_ENV) {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => default.timeout,
+            },
+            max_heap_size: var(WPT_MAX_HEAP_SIZE_ENV).or(default.max_heap_size),
+        }
+    }
+}
+
+/// Outcome of a test alongside the subtests that only passed on retry
+type TestOutcome = (WptReportTest, BTreeSet<String>);
+
+/// Outcome of the test at the given index in the list of tests to run
+struct IndexedOutcome {
+    index: usize,
+    started_at: SystemTime,
+    duration: Duration,
+    outcome: TestOutcome,
+}
+
 pub type Script = String;
 
 /// A bundle of scripts obtained from a web platform test
@@ -183,17 +268,199 @@ impl WptServe {
         let mut report = WptReport::default();
 
         for test in tests {
+            let started_at = SystemTime::now();
+            let start = Instant::now();
             let test_report = f(self, test.clone()).await?;
-            report.insert(&test.manifest_path, test_report)?;
+            report.record(&test, started_at, start.elapsed(), test_report)?;
         }
 
         Ok(report)
     }
+
+    /// Same as [`WptServe::run_test_harness`], but distributes the tests across
+    /// a pool of [`WptRunOptions::workers`] threads. Each worker drives its own async
+    /// runtime, so every test gets a runtime instance of its own and tests execute
+    /// concurrently.
+    ///
+    /// Reports are merged in manifest order, hence the resulting report is
+    /// identical to the one produced by a sequential run.
+    ///
+    /// NOTE: This blocks the calling thread until all workers are done.
+    pub fn run_test_harness_parallel<'a, F>(
+        &'a self,
+        manifest: &WptManifest,
+        filter: &TestFilter,
+        options: &WptRunOptions,
+        f: WptTestRunner<'a, F>,
+    ) -> Result<WptReport>
+    where
+        F: IntoFuture<Output = Result<WptReportTest>> + 'a,
+    {
+        let tests = manifest.tests(filter);
+        let next = AtomicUsize::new(0);
+        let workers = options.workers.get().min(tests.len()).max(1);
+
+        let results = thread::scope(|scope| {
+            let handles = (0..workers)
+                .map(|_| {
+                    scope.spawn(|| -> Result<Vec<IndexedOutcome>> {
+                        let rt = tokio::runtime::Builder::new_current_thread()
+                            .enable_all()
+                            .build()?;
+
+                        let mut outcomes = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(test) = tests.get(index) else {
+                                break;
+                            };
+
+                            let started_at = SystemTime::now();
+                            let start = Instant::now();
+                            let test = TestToRun {
+                                timeout: options.timeout,
+                                ..test.clone()
+                            };
+                            match rt.block_on(self.run_test_with_retries(
+                                &test,
+                                options.retries,
+                                f,
+                            )) {
+                                Ok(outcome) => outcomes.push(IndexedOutcome {
+                                    index,
+                                    started_at,
+                                    duration: start.elapsed(),
+                                    outcome,
+                                }),
+                                Err(e) => {
+                                    // Stop the other workers from picking up new tests
+                                    next.store(tests.len(), Ordering::Relaxed);
+                                    return Err(e);
+                                }
+                            }
+                        }
+
+                        Ok(outcomes)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .map_err(|_| anyhow::anyhow!("WPT worker panicked"))?
+                })
+                .collect::<Result<Vec<_>>>()
+        })?;
+
+        let mut results = results.into_iter().flatten().collect::<Vec<_>>();
+        results.sort_by_key(|result| result.index);
+
+        let mut report = WptReport::default();
+        for IndexedOutcome {
+            index,
+            started_at,
+            duration,
+            outcome: (test_report, flaky),
+        } in results
+        {
+            let test = &tests[index];
+            report.record(test, started_at, duration, test_report)?;
+            report.mark_flaky(&test.manifest_path, flaky);
+        }
+
+        Ok(report)
+    }
+
+    /// Runs `test`, retrying it up to `retries` times while some of its subtests
+    /// fail. The report of the first attempt is kept; subtests that failed in the
+    /// first attempt but passed in a retry are returned as flaky.
+    async fn run_test_with_retries<'a, F>(
+        &'a self,
+        test: &TestToRun,
+        retries: usize,
+        f: WptTestRunner<'a, F>,
+    ) -> Result<TestOutcome>
+    where
+        F: IntoFuture<Output = Result<WptReportTest>> + 'a,
+    {
+        let report = self.run_test_attempt(test, f).await?;
+
+        let mut failing = report
+            .subtests
+            .iter()
+            .filter(|subtest| subtest.status != WptSubtestStatus::Pass)
+            .map(|subtest| subtest.name.clone())
+            .collect::<BTreeSet<_>>();
+        let mut flaky = BTreeSet::new();
+
+        for _ in 0..retries {
+            if failing.is_empty() {
+                break;
+            }
+
+            let retry = self.run_test_attempt(test, f).await?;
+            for subtest in retry.subtests {
+                if subtest.status == WptSubtestStatus::Pass
+                    && failing.remove(&subtest.name)
+                {
+                    flaky.insert(subtest.name);
+                }
+            }
+        }
+
+        Ok((report, flaky))
+    }
+
+    /// Runs `test` once, recording it as timed out if it exceeds [`TestToRun::timeout`]
+    async fn run_test_attempt<'a, F>(
+        &'a self,
+        test: &TestToRun,
+        f: WptTestRunner<'a, F>,
+    ) -> Result<WptReportTest>
+    where
+        F: IntoFuture<Output = Result<WptReportTest>> + 'a,
+    {
+        let attempt = f(self, test.clone()).into_future();
+        match test.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, attempt).await {
+                Ok(report) => report,
+                Err(_) => Ok(WptReportTest::new(WptTestStatus::Timeout, vec![])),
+            },
+            None => attempt.await,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{BundleItem, WptServe};
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        future::{Future, Ready},
+        num::NonZeroUsize,
+        sync::Mutex,
+        time::Duration,
+    };
+
+    use jstz_runtime::wpt::{WptSubtest, WptSubtestStatus, WptTestStatus};
+
+    use crate::{
+        BundleItem, TestFilter, TestToRun, WptManifest, WptReportTest, WptRunOptions,
+        WptServe,
+    };
+
+    fn run_test(_: &WptServe, test: TestToRun) -> Ready<anyhow::Result<WptReportTest>> {
+        std::future::ready(Ok(WptReportTest::new(
+            WptTestStatus::Ok,
+            vec![WptSubtest {
+                name: test.url_path,
+                status: WptSubtestStatus::Pass,
+                message: None,
+            }],
+        )))
+    }
 
     #[tokio::test]
     async fn is_running() {
@@ -249,4 +516,185 @@ mod tests {
             vec!["location.search = '?a=b';".to_string()]
         );
     }
+
+    // Fails `flaky` on the first attempt of every test, and `broken` on every attempt
+    fn run_flaky_test(
+        _: &WptServe,
+        test: TestToRun,
+    ) -> Ready<anyhow::Result<WptReportTest>> {
+        static ATTEMPTS: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
+        let attempt = {
+            let mut attempts = ATTEMPTS.lock().unwrap();
+            let attempt = attempts.entry(test.url_path).or_default();
+            *attempt += 1;
+            *attempt
+        };
+        let subtest = |name: &str, status| WptSubtest {
+            name: name.to_string(),
+            status,
+            message: None,
+        };
+        std::future::ready(Ok(WptReportTest::new(
+            WptTestStatus::Ok,
+            vec![
+                subtest(
+                    "flaky",
+                    match attempt {
+                        1 => WptSubtestStatus::Fail,
+                        _ => WptSubtestStatus::Pass,
+                    },
+                ),
+                subtest("broken", WptSubtestStatus::Fail),
+                subtest("ok", WptSubtestStatus::Pass),
+            ],
+        )))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn run_test_harness_parallel_retries() {
+        let manifest: WptManifest = serde_json::from_value(serde_json::json!({
+            "items": {
+                "testharness": {
+                    "retry": {
+                        "a.any.js": ["hash", ["retry/a.any.html", {}]]
+                    },
+                    "no_retry": {
+                        "a.any.js": ["hash", ["no_retry/a.any.html", {}]]
+                    }
+                }
+            }
+        }))
+        .unwrap();
+        let wpt = WptServe::new(
+            "http://dummy/",
+            tokio::process::Command::new("echo").spawn().unwrap(),
+        )
+        .unwrap();
+
+        let mut options = WptRunOptions {
+            workers: NonZeroUsize::MIN,
+            ..Default::default()
+        };
+        let filter = TestFilter::try_from(["^/no_retry/"].as_slice()).unwrap();
+        let report = tokio::task::block_in_place(|| {
+            wpt.run_test_harness_parallel(&manifest, &filter, &options, run_flaky_test)
+        })
+        .unwrap();
+        assert!(report.flaky().is_empty());
+
+        options.retries = 3;
+        let filter = TestFilter::try_from(["^/retry/"].as_slice()).unwrap();
+        let report = tokio::task::block_in_place(|| {
+            wpt.run_test_harness_parallel(&manifest, &filter, &options, run_flaky_test)
+        })
+        .unwrap();
+        assert_eq!(
+            report.flaky(),
+            &BTreeMap::from_iter([(
+                "retry/a.any.js".to_string(),
+                BTreeSet::from_iter(["flaky".to_string()])
+            )])
+        );
+        assert!(report.is_flaky("retry/a.any.js", "flaky"));
+        assert!(!report.is_flaky("retry/a.any.js", "broken"));
+        // The outcome of the first attempt is kept
+        assert_eq!(report.tests()["retry/a.any.js"][0].metrics.failed, 2);
+    }
+
+    fn run_hanging_test(
+        _: &WptServe,
+        _: TestToRun,
+    ) -> impl Future<Output = anyhow::Result<WptReportTest>> {
+        async {
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+            Ok(WptReportTest::new(WptTestStatus::Ok, vec![]))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn run_test_harness_parallel_timeout() {
+        let manifest: WptManifest = serde_json::from_value(serde_json::json!({
+            "items": {
+                "testharness": {
+                    "foo": {
+                        "a.any.js": ["hash", ["foo/a.any.html", {}]]
+                    }
+                }
+            }
+        }))
+        .unwrap();
+        let wpt = WptServe::new(
+            "http://dummy/",
+            tokio::process::Command::new("echo").spawn().unwrap(),
+        )
+        .unwrap();
+
+        let options = WptRunOptions {
+            timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let report = tokio::task::block_in_place(|| {
+            wpt.run_test_harness_parallel(
+                &manifest,
+                &TestFilter::default(),
+                &options,
+                run_hanging_test,
+            )
+        })
+        .unwrap();
+        assert_eq!(
+            report.tests()["foo/a.any.js"][0].status,
+            WptTestStatus::Timeout
+        );
+        assert_eq!(report.runs()[0].test.status, WptTestStatus::Timeout);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn run_test_harness_parallel() {
+        let manifest: WptManifest = serde_json::from_value(serde_json::json!({
+            "items": {
+                "testharness": {
+                    "foo": {
+                        "a.any.js": [
+                            "hash",
+                            ["foo/a.any.html", {}],
+                            ["foo/a.any.worker.html", {}]
+                        ],
+                        "b.any.js": ["hash", ["foo/b.any.html", {}]]
+                    },
+                    "bar": {
+                        "c.any.js": ["hash", ["bar/c.any.html?1-10", {}], ["bar/c.any.html?11-20", {}]]
+                    }
+                }
+            }
+        }))
+        .unwrap();
+        let filter = TestFilter::default();
+        let wpt = WptServe::new(
+            "http://dummy/",
+            tokio::process::Command::new("echo").spawn().unwrap(),
+        )
+        .unwrap();
+
+        let expected = wpt
+            .run_test_harness(&manifest, &filter, run_test)
+            .await
+            .unwrap();
+        for workers in [1, 2, 16] {
+            let report = tokio::task::block_in_place(|| {
+                wpt.run_test_harness_parallel(
+                    &manifest,
+                    &filter,
+                    &WptRunOptions {
+                        workers: NonZeroUsize::new(workers).unwrap(),
+                        ..Default::default()
+                    },
+                    run_test,
+                )
+            })
+            .unwrap();
+            assert_eq!(report, expected);
+        }
+        assert_eq!(expected.stats().values().map(|m| m.passed).sum::<u64>(), 5);
+    }
 }
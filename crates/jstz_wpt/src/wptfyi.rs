@@ -0,0 +1,272 @@
+//! Export of results in the `wptreport.json` format consumed by [wpt.fyi]
+//!
+//! [wpt.fyi]: https://github.com/web-platform-tests/wpt.fyi/blob/main/api/README.md#results-creation
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use jstz_runtime::wpt::{WptSubtestStatus, WptTestStatus};
+use serde::{Deserialize, Serialize};
+
+use crate::report::WptReport;
+
+/// Metadata describing the environment the tests ran in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WptFyiRunInfo {
+    pub product: String,
+    pub browser_version: String,
+    pub os: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+}
+
+impl Default for WptFyiRunInfo {
+    fn default() -> Self {
+        Self {
+            product: "jstz".to_string(),
+            browser_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            revision: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WptFyiReport {
+    pub run_info: WptFyiRunInfo,
+    /// Milliseconds since the unix epoch
+    pub time_start: u64,
+    /// Milliseconds since the unix epoch
+    pub time_end: u64,
+    pub results: Vec<WptFyiTestResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WptFyiTestResult {
+    /// URL path of the test, e.g. `/url/a.any.html`
+    pub test: String,
+    pub status: WptFyiTestStatus,
+    pub message: Option<String>,
+    /// Milliseconds
+    pub duration: u64,
+    pub subtests: Vec<WptFyiSubtestResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WptFyiSubtestResult {
+    pub name: String,
+    pub status: WptFyiSubtestStatus,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum WptFyiTestStatus {
+    Ok,
+    Error,
+    Timeout,
+    PreconditionFailed,
+}
+
+impl From<&WptTestStatus> for WptFyiTestStatus {
+    fn from(status: &WptTestStatus) -> Self {
+        match status {
+            WptTestStatus::Ok => Self::Ok,
+            WptTestStatus::Err => Self::Error,
+            WptTestStatus::Timeout => Self::Timeout,
+            WptTestStatus::PreconditionFailed => Self::PreconditionFailed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum WptFyiSubtestStatus {
+    Pass,
+    Fail,
+    Timeout,
+    Notrun,
+    PreconditionFailed,
+}
+
+impl From<&WptSubtestStatus> for WptFyiSubtestStatus {
+    fn from(status: &WptSubtestStatus) -> Self {
+        match status {
+            WptSubtestStatus::Pass => Self::Pass,
+            WptSubtestStatus::Fail => Self::Fail,
+            WptSubtestStatus::Timeout => Self::Timeout,
+            WptSubtestStatus::NotRun => Self::Notrun,
+            WptSubtestStatus::PreconditionFailed => Self::PreconditionFailed,
+        }
+    }
+}
+
+fn millis_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+impl WptReport {
+    /// Exports the recorded test runs in the `wptreport.json` format.
+    ///
+    /// NOTE: Only runs recorded through [`WptReport::record`] are exported, i.e.
+    /// a report read back from disk exports no results.
+    pub fn to_wptfyi(&self, run_info: WptFyiRunInfo) -> WptFyiReport {
+        let runs = self.runs();
+        let time_start = runs.iter().map(|run| run.started_at).min();
+        let time_end = runs.iter().map(|run| run.started_at + run.duration).max();
+
+        let results = runs
+            .iter()
+            .map(|run| WptFyiTestResult {
+                test: format!("/{}", run.url_path.trim_start_matches('/')),
+                status: (&run.test.status).into(),
+                message: None,
+                duration: run.duration.as_millis() as u64,
+                subtests: run
+                    .test
+                    .subtests
+                    .iter()
+                    .map(|subtest| WptFyiSubtestResult {
+                        name: subtest.name.clone(),
+                        status: (&subtest.status).into(),
+                        message: subtest.message.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let now = SystemTime::now();
+        WptFyiReport {
+            run_info,
+            time_start: millis_since_epoch(time_start.unwrap_or(now)),
+            time_end: millis_since_epoch(time_end.unwrap_or(now)),
+            results,
+        }
+    }
+}
+
+impl WptFyiReport {
+    /// Total time spent running tests, which may exceed the wall-clock
+    /// time of the run when tests ran concurrently
+    pub fn total_duration(&self) -> Duration {
+        Duration::from_millis(self.results.iter().map(|r| r.duration).sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use jstz_runtime::wpt::{WptSubtest, WptSubtestStatus, WptTestStatus};
+    use serde_json::json;
+
+    use super::WptFyiRunInfo;
+    use crate::{TestToRun, WptReport, WptReportTest, WptTestOptions};
+
+    fn test_to_run(manifest_path: &str, url_path: &str) -> TestToRun {
+        TestToRun {
+            manifest_path: manifest_path.to_string(),
+            url_path: url_path.to_string(),
+            options: WptTestOptions {
+                script_metadata: vec![],
+            },
+            timeout: None,
+        }
+    }
+
+    #[test]
+    fn to_wptfyi() {
+        let mut report = WptReport::default();
+        report
+            .record(
+                &test_to_run("url/a.any.js", "url/a.any.html"),
+                UNIX_EPOCH + Duration::from_millis(1000),
+                Duration::from_millis(20),
+                WptReportTest::new(
+                    WptTestStatus::Ok,
+                    vec![
+                        WptSubtest {
+                            name: "foo".to_string(),
+                            status: WptSubtestStatus::Pass,
+                            message: None,
+                        },
+                        WptSubtest {
+                            name: "bar".to_string(),
+                            status: WptSubtestStatus::NotRun,
+                            message: Some("skipped".to_string()),
+                        },
+                    ],
+                ),
+            )
+            .unwrap();
+        report
+            .record(
+                &test_to_run("url/a.any.js", "/url/a.any.worker.html"),
+                UNIX_EPOCH + Duration::from_millis(1010),
+                Duration::from_millis(50),
+                WptReportTest::new(WptTestStatus::Err, vec![]),
+            )
+            .unwrap();
+
+        let run_info = WptFyiRunInfo {
+            product: "jstz".to_string(),
+            browser_version: "0.1.0".to_string(),
+            os: "linux".to_string(),
+            revision: None,
+        };
+        let wptfyi = report.to_wptfyi(run_info);
+        assert_eq!(wptfyi.total_duration(), Duration::from_millis(70));
+        assert_eq!(
+            serde_json::to_value(&wptfyi).unwrap(),
+            json!({
+                "run_info": {
+                    "product": "jstz",
+                    "browser_version": "0.1.0",
+                    "os": "linux"
+                },
+                "time_start": 1000,
+                "time_end": 1060,
+                "results": [
+                    {
+                        "test": "/url/a.any.html",
+                        "status": "OK",
+                        "message": null,
+                        "duration": 20,
+                        "subtests": [
+                            { "name": "foo", "status": "PASS", "message": null },
+                            { "name": "bar", "status": "NOTRUN", "message": "skipped" }
+                        ]
+                    },
+                    {
+                        "test": "/url/a.any.worker.html",
+                        "status": "ERROR",
+                        "message": null,
+                        "duration": 50,
+                        "subtests": []
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn runs_are_not_persisted() {
+        let mut report = WptReport::default();
+        report
+            .record(
+                &test_to_run("url/a.any.js", "url/a.any.html"),
+                UNIX_EPOCH,
+                Duration::from_millis(20),
+                WptReportTest::new(WptTestStatus::Ok, vec![]),
+            )
+            .unwrap();
+        assert_eq!(report.runs().len(), 1);
+
+        let json = serde_json::to_string(&report).unwrap();
+        let parsed: WptReport = serde_json::from_str(&json).unwrap();
+        assert!(parsed.runs().is_empty());
+        assert_eq!(parsed, report);
+        assert!(parsed.to_wptfyi(Default::default()).results.is_empty());
+    }
+}
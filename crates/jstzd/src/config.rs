@@ -4,7 +4,7 @@ use std::path::{Path, PathBuf};
 use jstz_crypto::public_key::PublicKey;
 use jstz_crypto::secret_key::SecretKey;
 #[cfg(feature = "oracle")]
-use jstz_oracle_node::OracleNodeConfig;
+use jstz_oracle_node::{OracleNodeConfig, ProvidersConfig};
 use jstz_utils::KeyPair;
 use octez::r#async::node_config::{OctezNodeHistoryMode, OctezNodeRunOptionsBuilder};
 use rust_embed::Embed;
@@ -19,7 +19,10 @@ use crate::{
 };
 use anyhow::{Context, Result};
 use http::Uri;
-use jstz_node::config::{JstzNodeConfig, RunModeBuilder, RunModeType};
+use jstz_node::{
+    config::{JstzNodeConfig, RunModeBuilder, RunModeType, AUTH_TOKEN_ENV},
+    reload::ConfigFile,
+};
 use octez::r#async::endpoint::Endpoint;
 use octez::r#async::protocol::{
     BootstrapContract, BootstrapSmartRollup, ProtocolParameter, SmartRollupPvmKind,
@@ -61,6 +64,9 @@ struct BootstrapRollupFile;
 
 #[derive(Deserialize, Default)]
 pub struct Config {
+    /// File this configuration was read from
+    #[serde(skip)]
+    path: Option<PathBuf>,
     server_port: Option<u16>,
     #[serde(default)]
     octez_node: OctezNodeConfigBuilder,
@@ -118,7 +124,10 @@ pub(crate) async fn build_config_from_path(
     config_path: &Option<String>,
 ) -> Result<(u16, JstzdConfig)> {
     let config = match config_path {
-        Some(p) => parse_config(p).await?,
+        Some(p) => Config {
+            path: Some(PathBuf::from(p)),
+            ..parse_config(p).await?
+        },
         None => Config::default(),
     };
     build_config(config).await
@@ -174,12 +183,21 @@ pub async fn build_config(mut config: Config) -> Result<(u16, JstzdConfig)> {
         .unwrap();
 
     let skip_jstz_node = config.jstz_node.skipped;
-    let jstz_node_config = build_jstz_node_config(
+    let mut jstz_node_config = build_jstz_node_config(
         config.jstz_node,
         &octez_rollup_config.rpc_endpoint,
         &kernel_debug_file_path,
     )
     .context("failed to build jstz node config")?;
+    // The node watches its section of the config file for the settings it can
+    // change without restarting
+    jstz_node_config.config_file = config.path.map(|path| ConfigFile {
+        path,
+        section: Some("jstz_node".to_string()),
+    });
+    jstz_node_config
+        .rollup_log_file
+        .replace(octez_rollup_config.log_file.path());
 
     #[cfg(feature = "oracle")]
     let oracle_node_config = match config.oracle_node.skipped {
@@ -187,6 +205,7 @@ pub async fn build_config(mut config: Config) -> Result<(u16, JstzdConfig)> {
         false => Some(build_oracle_config(
             Some(jstz_node_config.injector.clone()),
             &jstz_node_config,
+            config.oracle_node.providers,
         )),
     };
 
@@ -240,7 +259,7 @@ fn build_jstz_node_config(
         run_mode_builder = run_mode_builder.with_riscv_kernel_path(path)?;
     }
 
-    Ok(JstzNodeConfig::new(
+    let mut jstz_node_config = JstzNodeConfig::new(
         &jstz_node_rpc_endpoint,
         rollup_rpc_endpoint,
         &jstz_rollup_path::preimages_path(),
@@ -248,7 +267,14 @@ fn build_jstz_node_config(
         injector.clone(),
         run_mode_builder.build()?,
         config.storage_sync,
-    ))
+    );
+    jstz_node_config.auth_token = config
+        .auth_token
+        .or_else(|| std::env::var(AUTH_TOKEN_ENV).ok());
+    jstz_node_config.rate_limit = config.rate_limit;
+    jstz_node_config.cors_origins = config.cors_origins;
+    jstz_node_config.log_level = config.log_level;
+    Ok(jstz_node_config)
 }
 
 fn patch_octez_node_config(builder: &mut OctezNodeConfigBuilder) -> Result<()> {
@@ -294,16 +320,21 @@ fn find_injector_account(
 fn build_oracle_config(
     key_pair: Option<KeyPair>,
     jstz_node_config: &JstzNodeConfig,
+    providers: ProvidersConfig,
 ) -> OracleNodeConfig {
     OracleNodeConfig {
         key_pair,
+        key_rotation: vec![],
         jstz_node_endpoint: jstz_node_config.endpoint.clone(),
         log_path: match &jstz_node_config.mode {
-            jstz_node::RunMode::Default => jstz_node_config.kernel_log_file.clone(),
+            jstz_node::RunMode::Default | jstz_node::RunMode::Replica { .. } => {
+                jstz_node_config.kernel_log_file.clone()
+            }
             jstz_node::RunMode::Sequencer { debug_log_path, .. } => {
                 debug_log_path.clone()
             }
         },
+        providers,
     }
 }
 
@@ -428,7 +459,7 @@ mod tests {
 
     use crate::config::UserJstzNodeConfig;
 
-    use super::{jstz_rollup_path, Config, JSTZ_ROLLUP_ADDRESS};
+    use super::{jstz_rollup_path, Config, ConfigFile, JSTZ_ROLLUP_ADDRESS};
     use http::Uri;
     use octez::r#async::{
         baker::{BakerBinaryPath, OctezBakerConfigBuilder},
@@ -861,6 +892,13 @@ mod tests {
             jstz_node_config.rollup_endpoint,
             config.octez_rollup_config().rpc_endpoint
         );
+        assert_eq!(
+            jstz_node_config.config_file,
+            Some(ConfigFile {
+                path: tmp_file.path().to_path_buf(),
+                section: Some("jstz_node".to_string()),
+            })
+        );
         // checking serialised values here to skip internal config values not exposed to users
         let run_mode = serde_json::to_value(&jstz_node_config.mode).unwrap();
         assert_eq!(run_mode["capacity"], 42);
@@ -909,10 +947,19 @@ mod tests {
             rollup_address: Some(rollup_address.clone()),
             storage_sync: false,
             skipped: false,
+            auth_token: Some("secret".to_string()),
+            rate_limit: None,
+            cors_origins: Some(vec!["https://app.jstz.dev".to_string()]),
+            log_level: None,
         };
         let jstz_node_config =
             super::build_jstz_node_config(config, &Endpoint::default(), &PathBuf::new())
                 .unwrap();
+        assert_eq!(jstz_node_config.auth_token.as_deref(), Some("secret"));
+        assert_eq!(
+            jstz_node_config.cors_origins,
+            Some(vec!["https://app.jstz.dev".to_string()])
+        );
         // checking serialised values here to skip internal config values not exposed to users
         let run_mode = serde_json::to_value(jstz_node_config.mode).unwrap();
         assert_eq!(run_mode["capacity"], 42);
@@ -1256,6 +1303,7 @@ mod tests {
                 jstz_node::RunMode::Default,
                 true,
             ),
+            Default::default(),
         );
         assert_eq!(config.log_path.to_str().unwrap(), "/kernel/debug");
 
@@ -1274,6 +1322,7 @@ mod tests {
                 },
                 true,
             ),
+            Default::default(),
         );
         assert_eq!(config.log_path.to_str().unwrap(), "/jstz_node/debug");
     }
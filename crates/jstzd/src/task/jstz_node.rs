@@ -1,6 +1,7 @@
 use super::Task;
 use anyhow::Result;
 use async_trait::async_trait;
+use jstz_client::JstzClient;
 use jstz_node::{config::JstzNodeConfig, run_with_config};
 use tokio::task::JoinHandle;
 
@@ -25,7 +26,7 @@ impl Task for JstzNode {
     }
 
     async fn health_check(&self) -> Result<bool> {
-        let res = reqwest::get(format!("{}/health", self.config.endpoint)).await;
-        Ok(res.is_ok_and(|res| res.status().is_success()))
+        let client = JstzClient::new(self.config.endpoint.clone());
+        Ok(client.health().await.unwrap_or(false))
     }
 }
@@ -6,13 +6,8 @@ use crate::config::{
 #[cfg(feature = "oracle")]
 use super::oracle_node::OracleNode;
 use super::{
-    child_wrapper::Shared,
-    jstz_node::JstzNode,
-    octez_baker::OctezBaker,
-    octez_node::OctezNode,
-    octez_rollup::OctezRollup,
-    utils::{get_block_level, retry},
-    Task,
+    child_wrapper::Shared, jstz_node::JstzNode, octez_baker::OctezBaker,
+    octez_node::OctezNode, octez_rollup::OctezRollup, Task,
 };
 use anyhow::{bail, Context, Result};
 use async_dropper_simple::{AsyncDrop, AsyncDropper};
@@ -28,20 +23,22 @@ use jstz_crypto::public_key::PublicKey;
 use jstz_node::config::JstzNodeConfig;
 #[cfg(feature = "oracle")]
 use jstz_oracle_node::OracleNodeConfig;
+use jstz_utils::{poll_until, retry::RetryPolicy};
 use octez::r#async::{
     baker::OctezBakerConfig,
     client::{Address, OctezClient, OctezClientConfig},
-    endpoint::Endpoint,
     node_config::OctezNodeConfig,
     protocol::{BootstrapAccount, ProtocolParameter},
     rollup::OctezRollupConfig,
 };
+use octez::{L1Rpc, OctezNodeRpcClient};
 use prettytable::{format::consts::FORMAT_DEFAULT, Cell, Row, Table};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::{
     collections::HashMap,
     io::{stdout, Write},
+    time::Instant,
 };
 use tokio::{
     net::TcpListener,
@@ -162,7 +159,9 @@ impl Task for Jstzd {
         .await?;
         Self::activate_protocol(&octez_client, &config.protocol_params).await?;
         let baker = OctezBaker::spawn(config.baker_config.clone()).await?;
-        Self::wait_for_block_level(&config.octez_node_config.rpc_endpoint, 3).await?;
+        let l1_client =
+            OctezNodeRpcClient::new(config.octez_node_config.rpc_endpoint.to_string());
+        Self::wait_for_block_level(&l1_client, 3).await?;
         let rollup = OctezRollup::spawn(config.octez_rollup_config.clone()).await?;
         Self::wait_for_rollup(&rollup).await?;
         let jstz_node = match config.jstz_node_config {
@@ -292,43 +291,44 @@ impl Jstzd {
     }
 
     async fn wait_for_node(octez_node: &OctezNode) -> Result<()> {
-        let ready = retry(10, 1000, || async {
-            Ok(octez_node.health_check().await.unwrap_or(false))
+        poll_until(Self::deadline(10), Self::backoff(), || async {
+            octez_node
+                .health_check()
+                .await
+                .unwrap_or(false)
+                .then_some(())
         })
-        .await;
-        if !ready {
-            return Err(anyhow::anyhow!(
-                "octez node is still not ready after retries"
-            ));
-        }
-        Ok(())
+        .await
+        .context("octez node is still not ready after retries")
     }
 
     /// Wait for the baker to bake at least `level` blocks.
-    async fn wait_for_block_level(node_endpoint: &Endpoint, level: i64) -> Result<()> {
-        let ready = retry(10, 1000, || async {
-            get_block_level(&node_endpoint.to_string())
+    async fn wait_for_block_level(l1_client: &dyn L1Rpc, level: i64) -> Result<()> {
+        poll_until(Self::deadline(10), Self::backoff(), || async {
+            l1_client
+                .get_block_level()
                 .await
-                .map(|l| l >= level)
+                .is_ok_and(|l| l >= level)
+                .then_some(())
         })
-        .await;
-        if !ready {
-            bail!("baker is not ready after retries");
-        }
-        Ok(())
+        .await
+        .context("baker is not ready after retries")
     }
 
     async fn wait_for_rollup(rollup: &OctezRollup) -> Result<()> {
-        let ready = retry(20, 1000, || async {
-            Ok(rollup.health_check().await.unwrap_or(false))
+        poll_until(Self::deadline(20), Self::backoff(), || async {
+            rollup.health_check().await.unwrap_or(false).then_some(())
         })
-        .await;
-        if !ready {
-            return Err(anyhow::anyhow!(
-                "rollup node is still not ready after retries"
-            ));
-        }
-        Ok(())
+        .await
+        .context("rollup node is still not ready after retries")
+    }
+
+    fn deadline(secs: u64) -> Instant {
+        Instant::now() + Duration::from_secs(secs)
+    }
+
+    fn backoff() -> RetryPolicy {
+        RetryPolicy::fixed(Duration::from_secs(1))
     }
 }
 
@@ -418,6 +418,7 @@ impl JstzdServer {
 
         let router = Router::new()
             .route("/health", get(health_check_handler))
+            .route("/health/rollup", get(rollup_health_handler))
             .route("/shutdown", put(shutdown_handler))
             .route("/config/:config_type", get(config_handler))
             .route("/config/", get(all_config_handler))
@@ -632,6 +633,18 @@ async fn health_check_handler(state: State<Shared<ServerState>>) -> http::Status
     }
 }
 
+async fn rollup_health_handler(state: State<Shared<ServerState>>) -> impl IntoResponse {
+    let lock = state.read().await;
+    let report = match &lock.jstzd {
+        Some(jstzd) => jstzd.rollup.read().await.health_details(),
+        None => None,
+    };
+    match report {
+        Some(v) => Json(v).into_response(),
+        None => http::StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
 async fn shutdown_handler(state: State<Shared<ServerState>>) -> http::StatusCode {
     let mut lock = state.write().await;
     if shutdown(&mut lock).await.is_err() {
@@ -756,6 +769,13 @@ mod tests {
         assert_eq!(super::collect_progress(vec![Ok(false), Ok(false)]), 0);
     }
 
+    #[tokio::test]
+    async fn wait_for_block_level() {
+        let rpc = octez::mock::MockL1Rpc::default();
+        rpc.set_block_level(3);
+        assert!(super::Jstzd::wait_for_block_level(&rpc, 3).await.is_ok());
+    }
+
     #[test]
     fn clear_progress_bar() {
         let bar = ProgressBar::new(3);
@@ -869,6 +889,7 @@ mod tests {
             .unwrap(),
             #[cfg(feature = "oracle")]
             Some(OracleNodeConfig {
+                key_rotation: vec![],
                 key_pair: Some(KeyPair(
                     PublicKey::from_base58(
                         "edpkukK9ecWxib28zi52nvbXTdsYt8rYcvmt5bdH8KjipWXm8sH3Qi",
@@ -881,6 +902,7 @@ mod tests {
                 )),
                 jstz_node_endpoint: Endpoint::default(),
                 log_path: PathBuf::from_str("/log/path").unwrap(),
+                providers: Default::default(),
             }),
             Some(JstzNodeConfig::new(
                 &Endpoint::default(),
@@ -6,7 +6,8 @@ use octez::r#async::directory::Directory;
 use octez::r#async::endpoint::Endpoint;
 use octez::r#async::file::FileWrapper;
 use octez::r#async::node;
-use octez::r#async::node_config::OctezNodeConfig;
+use octez::r#async::node_config::{OctezNodeConfig, OctezNodeSnapshot};
+use octez::{L1Rpc, OctezNodeRpcClient};
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -30,6 +31,35 @@ impl OctezNode {
     }
 }
 
+/// File of the node data directory recording the snapshot imported into it
+const IMPORTED_SNAPSHOT_FILE: &str = "imported_snapshot";
+
+/// Imports `snapshot` into the data directory of `node`, unless it was already
+/// imported by a previous run on the same data directory.
+async fn import_snapshot(
+    node: &node::OctezNode,
+    snapshot: &OctezNodeSnapshot,
+) -> Result<()> {
+    let marker = node.octez_node_dir.join(IMPORTED_SNAPSHOT_FILE);
+    if tokio::fs::read_to_string(&marker).await.ok() == Some(snapshot.to_string()) {
+        return Ok(());
+    }
+    // keeps the downloaded file alive until the import completes
+    let downloaded;
+    let path = match snapshot {
+        OctezNodeSnapshot::Path(p) => p.as_path(),
+        OctezNodeSnapshot::Url(url) => {
+            downloaded = node::download_snapshot(url).await?;
+            downloaded.path()
+        }
+    };
+    let status = node.snapshot_import(path).await?.wait().await?;
+    match status.code() {
+        Some(0) => Ok(tokio::fs::write(&marker, snapshot.to_string()).await?),
+        _ => Err(anyhow::anyhow!("failed to import snapshot '{snapshot}'")),
+    }
+}
+
 #[async_trait]
 impl Task for OctezNode {
     type Config = OctezNodeConfig;
@@ -71,6 +101,10 @@ impl Task for OctezNode {
             _ => return Err(anyhow::anyhow!("failed to initialize node config")),
         }
 
+        if let Some(snapshot) = &config.snapshot {
+            import_snapshot(&node, snapshot).await?;
+        }
+
         Ok(OctezNode {
             inner: ChildWrapper::new_shared(node.run(&config.run_options)?),
             config,
@@ -87,23 +121,46 @@ impl Task for OctezNode {
 
     /// Conducts a health check on the running task.
     async fn health_check(&self) -> Result<bool> {
-        // Returns whether or not the node is ready to answer to requests.
-        // https://gitlab.com/tezos/tezos/-/raw/2e84c439c25c4d9b363127a6685868e223877034/docs/api/rpc-openapi.json
-        let res = reqwest::get(format!(
-            "{}/health/ready",
-            &self.config.rpc_endpoint.to_string()
-        ))
-        .await;
-        if res.is_err() {
-            return Ok(false);
-        }
-        let body = res
-            .unwrap()
-            .json::<std::collections::HashMap<String, bool>>()
-            .await?;
-        if let Some(v) = body.get("ready") {
-            return Ok(*v);
+        let client = OctezNodeRpcClient::new(self.config.rpc_endpoint.to_string());
+        health_check(&client).await
+    }
+}
+
+async fn health_check(client: &dyn L1Rpc) -> Result<bool> {
+    match client.is_ready().await {
+        Ok(v) => Ok(v),
+        // the node is not listening yet
+        Err(e)
+            if e.downcast_ref::<reqwest::Error>()
+                .is_some_and(reqwest::Error::is_connect) =>
+        {
+            Ok(false)
         }
-        return Err(anyhow::anyhow!("unexpected error: `ready` cannot be retrieved from octez-node health check endpoint"));
+        Err(e) => Err(e.context("failed to check the health of octez-node")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use octez::{mock::MockL1Rpc, OctezNodeRpcClient};
+
+    #[tokio::test]
+    async fn health_check() {
+        let rpc = MockL1Rpc::default();
+        assert!(!super::health_check(&rpc).await.unwrap());
+
+        rpc.set_ready(true);
+        assert!(super::health_check(&rpc).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn health_check_propagates_errors() {
+        // nothing listens on the discard port
+        let rpc = OctezNodeRpcClient::new("http://127.0.0.1:9".to_string());
+        assert!(!super::health_check(&rpc).await.unwrap());
+
+        let rpc = MockL1Rpc::default();
+        rpc.set_error(Some("unexpected response"));
+        assert!(super::health_check(&rpc).await.is_err());
     }
 }
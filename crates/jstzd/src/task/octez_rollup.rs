@@ -12,7 +12,10 @@ use async_trait::async_trait;
 use octez::r#async::{
     directory::Directory,
     endpoint::Endpoint,
-    rollup::{OctezRollupConfig, RollupDataDir},
+    rollup::{
+        log::{RollupLogMonitor, RollupNodeHealthReport, RollupNodeStatus},
+        OctezRollupConfig, RollupDataDir,
+    },
 };
 use serde::Deserialize;
 
@@ -22,12 +25,18 @@ pub struct OctezRollup {
     config: OctezRollupConfig,
     // holds the TempDir instance so that the directory does not get deleted too soon
     _data_dir: Arc<Directory>,
+    log_monitor: Option<Arc<RollupLogMonitor>>,
 }
 
 impl OctezRollup {
     pub fn rpc_endpoint(&self) -> &Endpoint {
         &self.config.rpc_endpoint
     }
+
+    /// Health of the rollup node classified from its logs.
+    pub fn health_details(&self) -> Option<RollupNodeHealthReport> {
+        self.log_monitor.as_ref().map(|m| m.health().report())
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -73,10 +82,13 @@ impl Task for OctezRollup {
                     .as_deref(),
             )?,
         );
+        let log_monitor =
+            Some(Arc::new(RollupLogMonitor::spawn(&config.log_file.path())));
         Ok(Self {
             inner,
             config,
             _data_dir: Arc::new(data_dir),
+            log_monitor,
         })
     }
 
@@ -90,6 +102,7 @@ impl Task for OctezRollup {
             reqwest::get(format!("{}/health/", &self.config.rpc_endpoint.to_string()))
                 .await?;
         let body = res.json::<HealthCheckResponse>().await?;
-        return Ok(body.healthy);
+        let log_status = self.health_details().map(|v| v.status);
+        Ok(body.healthy && log_status != Some(RollupNodeStatus::Unhealthy))
     }
 }
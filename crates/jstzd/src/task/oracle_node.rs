@@ -1,7 +1,8 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use jstz_oracle_node::{node::OracleNode as InnerOracleNode, OracleNodeConfig};
-use jstz_utils::KeyPair;
+use jstz_oracle_node::{
+    keys::KeyRing, node::OracleNode as InnerOracleNode, OracleNodeConfig,
+};
 
 use crate::task::Task;
 
@@ -15,13 +16,12 @@ impl Task for OracleNode {
 
     async fn spawn(config: Self::Config) -> Result<Self> {
         let oracle = if let Some(key_pair) = &config.key_pair {
-            let KeyPair(public_key, secret_key) = key_pair;
             Some(
                 InnerOracleNode::spawn(
                     config.log_path.clone(),
-                    public_key.clone(),
-                    secret_key.clone(),
+                    KeyRing::new(key_pair.clone(), config.key_rotation.clone()),
                     config.jstz_node_endpoint.to_string(),
+                    &config.providers,
                 )
                 .await?,
             )
@@ -37,7 +37,6 @@ impl Task for OracleNode {
     }
 
     async fn health_check(&self) -> Result<bool> {
-        // TODO: implement this properly once oracle node exposes health information
-        Ok(true)
+        Ok(self.inner.as_ref().is_none_or(InnerOracleNode::is_running))
     }
 }
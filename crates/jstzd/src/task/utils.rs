@@ -1,38 +1,33 @@
-use anyhow::{anyhow, Result};
-use jstz_utils::poll;
-use serde_json::Value;
+use std::time::Duration;
 
-pub async fn retry<'a, F>(
-    retries: u16,
-    interval_ms: u64,
-    f: impl Fn() -> F + Sync,
-) -> bool
+use anyhow::Result;
+use jstz_utils::retry::RetryPolicy;
+use octez::{L1Rpc, OctezNodeRpcClient};
+
+/// Calls `f` up to `retries` times, `interval_ms` apart, until it returns `Ok(true)`
+pub async fn retry<F>(retries: u16, interval_ms: u64, f: impl Fn() -> F) -> bool
 where
-    F: std::future::Future<Output = anyhow::Result<bool>> + Send + 'a,
+    F: std::future::Future<Output = anyhow::Result<bool>>,
 {
-    poll(retries, interval_ms, || async {
-        if let Ok(v) = f().await {
-            if v {
-                return Some(true);
-            }
+    if retries == 0 {
+        return false;
+    }
+    let policy = RetryPolicy::fixed(Duration::from_millis(interval_ms))
+        .with_max_attempts(retries.into());
+    jstz_utils::retry::retry(policy, || async {
+        match f().await {
+            Ok(true) => Ok(()),
+            _ => Err(()),
         }
-        None
     })
     .await
-    .unwrap_or(false)
+    .is_ok()
 }
 
 pub async fn get_block_level(rpc_endpoint: &str) -> Result<i64> {
-    let blocks_head_endpoint = format!("{rpc_endpoint}/chains/main/blocks/head");
-    let response: Value = reqwest::get(&blocks_head_endpoint).await?.json().await?;
-
-    let level = response
-        .get("header")
-        .and_then(|header| header.get("level"))
-        .ok_or_else(|| anyhow!("Failed to extract level from head block"))?;
-    level
-        .as_i64()
-        .ok_or_else(|| anyhow!("Level is not a valid i64"))
+    OctezNodeRpcClient::new(rpc_endpoint.to_owned())
+        .get_block_level()
+        .await
 }
 
 #[cfg(test)]
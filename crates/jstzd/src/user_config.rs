@@ -1,6 +1,11 @@
 use std::path::PathBuf;
 
-use jstz_node::config::RunModeType;
+use jstz_node::{
+    config::{LogLevel, RunModeType},
+    rate_limit::RateLimitConfig,
+};
+#[cfg(feature = "oracle")]
+use jstz_oracle_node::ProvidersConfig;
 use serde::Deserialize;
 use tezos_crypto_rs::hash::SmartRollupHash;
 
@@ -17,6 +22,11 @@ pub(crate) struct UserJstzNodeConfig {
     pub rollup_address: Option<SmartRollupHash>,
     #[serde(default)]
     pub storage_sync: bool,
+    /// Bearer token required by the operation and admin endpoints of the node.
+    pub auth_token: Option<String>,
+    pub rate_limit: Option<RateLimitConfig>,
+    pub cors_origins: Option<Vec<String>>,
+    pub log_level: Option<LogLevel>,
 }
 
 #[cfg(feature = "oracle")]
@@ -26,17 +36,22 @@ pub(crate) struct UserOracleNodeConfig {
     #[serde(default)]
     /// Flag indicating if oracle node should not be launched.
     pub skipped: bool,
+    #[serde(default)]
+    /// Routes oracle requests to data providers.
+    pub providers: ProvidersConfig,
 }
 
 #[cfg(test)]
 mod tests {
     use std::{path::PathBuf, str::FromStr};
 
-    use jstz_node::config::RunModeType;
+    use jstz_node::config::{LogLevel, RunModeType};
     use tezos_crypto_rs::hash::SmartRollupHash;
 
     #[cfg(feature = "oracle")]
     use super::UserOracleNodeConfig;
+    #[cfg(feature = "oracle")]
+    use jstz_oracle_node::ProviderConfig;
 
     use super::UserJstzNodeConfig;
 
@@ -51,7 +66,11 @@ mod tests {
                 riscv_kernel_path: None,
                 rollup_address: None,
                 storage_sync: false,
-                skipped: false
+                skipped: false,
+                auth_token: None,
+                rate_limit: None,
+                cors_origins: None,
+                log_level: None,
             }
         )
     }
@@ -65,7 +84,10 @@ mod tests {
             "debug_log_file": "/tmp/log",
             "riscv_kernel_path": "/riscv/kernel",
             "rollup_address": "sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK",
-            "storage_sync": true
+            "storage_sync": true,
+            "auth_token": "secret",
+            "cors_origins": ["https://app.jstz.dev"],
+            "log_level": "debug"
         }"#;
         let config = serde_json::from_str::<UserJstzNodeConfig>(s).unwrap();
         let expected = UserJstzNodeConfig {
@@ -81,6 +103,10 @@ mod tests {
                 .unwrap(),
             ),
             storage_sync: true,
+            auth_token: Some("secret".to_string()),
+            rate_limit: None,
+            cors_origins: Some(vec!["https://app.jstz.dev".to_string()]),
+            log_level: Some(LogLevel::Debug),
         };
         assert_eq!(config, expected);
 
@@ -105,5 +131,15 @@ mod tests {
         let s = r#"{}"#;
         let config = serde_json::from_str::<UserOracleNodeConfig>(s).unwrap();
         assert!(!config.skipped);
+        assert!(config.providers.routes.is_empty());
+
+        let s = r#"{"providers": {"routes": [{"pattern": ".*", "provider": {"type": "file", "root": "/srv"}}]}}"#;
+        let config = serde_json::from_str::<UserOracleNodeConfig>(s).unwrap();
+        assert_eq!(
+            config.providers.routes[0].provider,
+            ProviderConfig::File {
+                root: PathBuf::from("/srv")
+            }
+        );
     }
 }
@@ -205,8 +205,10 @@ async fn create_jstzd_server(
         #[cfg(feature = "oracle")]
         Some(OracleNodeConfig {
             key_pair: oracle_key_pair,
+            key_rotation: vec![],
             log_path: kernel_debug_file_path.clone(),
             jstz_node_endpoint: jstz_node_rpc_endpoint.to_owned(),
+            providers: Default::default(),
         }),
         Some(jstz_node_config),
         protocol_params,
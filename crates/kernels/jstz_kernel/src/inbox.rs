@@ -1,14 +1,21 @@
 use std::error::Error;
 
-use jstz_core::{host::WriteDebug, BinEncodable};
-use jstz_crypto::hash::Hash;
+use bincode::{Decode, Encode};
+use jstz_core::{
+    host::WriteDebug,
+    kv::{Storage, Transaction},
+    BinEncodable,
+};
+use jstz_crypto::hash::{Blake2b, Hash};
 use jstz_crypto::public_key_hash::PublicKeyHash;
 use jstz_proto::context::account::Address;
 use jstz_proto::operation::{
     internal::{Deposit, InboxId},
     InternalOperation, SignedOperation,
 };
+use jstz_proto::receipt::Receipt;
 use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
 use tezos_crypto_rs::hash::{ContractKt1Hash, SmartRollupHash};
 use tezos_data_encoding::enc::BinWriter;
 use tezos_smart_rollup::inbox::InfoPerLevel;
@@ -16,6 +23,7 @@ use tezos_smart_rollup::michelson::ticket::FA2_1Ticket;
 use tezos_smart_rollup::michelson::{
     MichelsonBytes, MichelsonContract, MichelsonNat, MichelsonOption, MichelsonOr,
 };
+use tezos_smart_rollup::storage::path::OwnedPath;
 use tezos_smart_rollup::types::SmartRollupAddress;
 pub use tezos_smart_rollup::{
     inbox::{ExternalMessageFrame, InboxMessage, InternalInboxMessage, Transfer},
@@ -24,6 +32,9 @@ pub use tezos_smart_rollup::{
     types::{self, Contract},
 };
 
+use utoipa::ToSchema;
+
+use crate::log::{log_debug, log_error, log_trace};
 use crate::parsing::try_parse_fa_deposit;
 
 pub type ExternalMessage = SignedOperation;
@@ -49,21 +60,140 @@ const EXTERNAL_FRAME_SIZE: usize = 21;
 const NATIVE_TICKET_ID: u32 = 0_u32;
 const NATIVE_TICKET_CONTENT: MichelsonOption<MichelsonBytes> = MichelsonOption(None);
 
+/// Number of dead letters kept in durable storage. Once reached, the oldest
+/// dead letters are overwritten.
+pub const DEAD_LETTER_CAPACITY: u64 = 100;
+/// Total number of dead letters recorded
+pub const DEAD_LETTER_COUNT_KEY: &str = "/jstz_dead_letter/count";
+
+/// An external message targetting the rollup that was quarantined instead of
+/// being executed
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Encode, Decode,
+)]
+pub struct DeadLetter {
+    /// Hash of the operation, or of the message contents if they cannot be
+    /// decoded as an operation
+    #[bincode(with_serde)]
+    pub hash: Blake2b,
+    pub reason: DeadLetterReason,
+    /// L1 level of the inbox the message was read from
+    pub level: u32,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, Encode, Decode,
+)]
+pub enum DeadLetterReason {
+    /// The message contents are not a signed operation
+    MalformedOperation,
+    /// The signature does not match the operation and its public key
+    InvalidSignature,
+}
+
+/// Key of the `index`th dead letter
+pub fn dead_letter_key(index: u64) -> String {
+    format!("/jstz_dead_letter/{}", index % DEAD_LETTER_CAPACITY)
+}
+
+/// Records a dead letter in durable storage, overwriting the oldest one if
+/// [`DEAD_LETTER_CAPACITY`] is reached
+fn quarantine(rt: &mut impl Runtime, dead_letter: &DeadLetter) -> jstz_core::Result<()> {
+    let count_path = OwnedPath::try_from(DEAD_LETTER_COUNT_KEY.to_string())?;
+    let count: u64 = Storage::get(rt, &count_path)?.unwrap_or(0);
+    let path = OwnedPath::try_from(dead_letter_key(count))?;
+    Storage::remove(rt, &path)?;
+    Storage::insert(rt, &path, dead_letter)?;
+    Storage::insert(rt, &count_path, &(count + 1))
+}
+
 // We reach None in 3 cases
 // 1. No more inputs
 // 2. Input targetting the wrong rollup
 // 3. Parsing failures
+//
+// External messages targetting the rollup that cannot be decoded or that have an
+// invalid signature are quarantined as dead letters and skipped. Operations with
+// an invalid signature also get a failed receipt. The signature of the returned
+// operations is verified, so it is not verified again when executing them.
 pub fn read_message(
     rt: &mut impl Runtime,
     ticketer: &ContractKt1Hash,
 ) -> Option<ParsedInboxMessageWrapper> {
-    let input = rt.read_input().ok()??;
-    let jstz_rollup_address = rt.reveal_metadata().address();
-    let inbox_id = InboxId {
-        l1_level: input.level,
-        l1_message_id: input.id,
-    };
-    parse_inbox_message(rt, inbox_id, input.as_ref(), ticketer, &jstz_rollup_address)
+    loop {
+        let input = rt.read_input().ok()??;
+        let jstz_rollup_address = rt.reveal_metadata().address();
+        let inbox_id = InboxId {
+            l1_level: input.level,
+            l1_message_id: input.id,
+        };
+        let dead_letter = match try_parse_inbox_message(
+            rt,
+            inbox_id,
+            input.as_ref(),
+            ticketer,
+            &jstz_rollup_address,
+        ) {
+            Ok(Some(message)) => match &message.content {
+                ParsedInboxMessage::JstzMessage(Message::External(op)) => {
+                    match op.verify() {
+                        Ok(()) => return Some(message),
+                        Err(e) => {
+                            log_debug!(
+                                rt,
+                                "Invalid signature of operation {}: {e}\n",
+                                op.hash()
+                            );
+                            write_rejection_receipt(rt, op.hash(), e);
+                            DeadLetter {
+                                hash: op.hash(),
+                                reason: DeadLetterReason::InvalidSignature,
+                                level: inbox_id.l1_level,
+                            }
+                        }
+                    }
+                }
+                _ => return Some(message),
+            },
+            Ok(None) => return None,
+            Err(dead_letter) => dead_letter,
+        };
+        record_dead_letter(rt, &dead_letter);
+    }
+}
+
+/// Records the failure of an operation that is not executed, unless it already
+/// has a receipt
+fn write_rejection_receipt(
+    rt: &mut impl Runtime,
+    hash: Blake2b,
+    error: jstz_proto::Error,
+) {
+    let mut tx = Transaction::default();
+    tx.begin();
+    if let Err(e) = Receipt::new(hash.clone(), Err(error)).write_if_absent(rt, &mut tx) {
+        log_error!(
+            rt,
+            "Failed to write the receipt of operation {hash}: {e:?}\n"
+        );
+        return;
+    }
+    if let Err(e) = tx.commit(rt) {
+        log_error!(
+            rt,
+            "Failed to write the receipt of operation {hash}: {e:?}\n"
+        );
+    }
+}
+
+fn record_dead_letter(rt: &mut impl Runtime, dead_letter: &DeadLetter) {
+    if let Err(e) = quarantine(rt, dead_letter) {
+        log_error!(
+            rt,
+            "Failed to quarantine message {}: {e:?}\n",
+            dead_letter.hash
+        );
+    }
 }
 
 /// Parse a hex-encoded L1 inbox input message into a jstz operation.
@@ -101,37 +231,56 @@ pub fn parse_inbox_message(
     ticketer: &ContractKt1Hash,
     jstz_rollup_address: &SmartRollupHash,
 ) -> Option<ParsedInboxMessageWrapper> {
-    let (_, message) = InboxMessage::<RollupType>::parse(inbox_msg).ok()?;
+    try_parse_inbox_message(logger, inbox_id, inbox_msg, ticketer, jstz_rollup_address)
+        .ok()
+        .flatten()
+}
+
+/// Like [`parse_inbox_message`], but returns the dead letter of external messages
+/// targetting the rollup whose contents cannot be decoded
+fn try_parse_inbox_message(
+    logger: &impl WriteDebug,
+    inbox_id: InboxId,
+    inbox_msg: &[u8],
+    ticketer: &ContractKt1Hash,
+    jstz_rollup_address: &SmartRollupHash,
+) -> Result<Option<ParsedInboxMessageWrapper>, DeadLetter> {
+    let Ok((_, message)) = InboxMessage::<RollupType>::parse(inbox_msg) else {
+        return Ok(None);
+    };
 
     let content = match message {
         InboxMessage::Internal(InternalInboxMessage::StartOfLevel) => {
             // Start of level message pushed by the Layer 1 at the
             // beginning of eavh level.
-            logger.write_debug("Internal message: start of level\n");
+            log_debug!(logger, "Internal message: start of level\n");
             Some(LevelInfo::Start.into())
         }
         InboxMessage::Internal(InternalInboxMessage::InfoPerLevel(info)) => {
             // The "Info per level" messages follows the "Start of level"
             // message and contains information on the previous Layer 1 block.
-            logger.write_debug(&format!(
+            log_debug!(
+                logger,
                 "Internal message: level info \
                         (block predecessor: {}, predecessor_timestamp: {}\n",
-                info.predecessor, info.predecessor_timestamp
-            ));
+                info.predecessor,
+                info.predecessor_timestamp
+            );
             Some(LevelInfo::Info(info).into())
         }
         InboxMessage::Internal(InternalInboxMessage::EndOfLevel) => {
             // The "End of level" message is pushed by the Layer 1
             // at the end of each level.
-            logger.write_debug("Internal message: end of level\n");
+            log_debug!(logger, "Internal message: end of level\n");
             Some(LevelInfo::End.into())
         }
         InboxMessage::Internal(InternalInboxMessage::Transfer(transfer)) => {
             if jstz_rollup_address != transfer.destination.hash() {
-                logger.write_debug(
-                    "Internal message ignored because of different smart rollup address",
+                log_debug!(
+                    logger,
+                    "Internal message ignored because of different smart rollup address"
                 );
-                return None;
+                return Ok(None);
             };
             read_transfer(logger, transfer, ticketer, inbox_id).map(|m| m.into())
         }
@@ -139,21 +288,26 @@ pub fn parse_inbox_message(
             Ok(frame) => match frame {
                 ExternalMessageFrame::Targetted { address, contents } => {
                     let message = if jstz_rollup_address != address.hash() {
-                        logger.write_debug(
-                            &format!(
-                                "External message ignored because of different smart rollup address: {:?} != {:?}",
-                                jstz_rollup_address, address.hash()
-                            ),
+                        log_debug!(
+                            logger,
+                            "External message ignored because of different smart rollup address: {:?} != {:?}",
+                            jstz_rollup_address,
+                            address.hash()
                         );
                         None
                     } else {
                         match read_external_message(logger, contents) {
                             Some(msg) => Some(Message::External(msg)),
                             None => {
-                                logger.write_debug(&format!(
+                                log_debug!(
+                                    logger,
                                     "Failed to parse the external message: {contents:?}\n"
-                                ));
-                                None
+                                );
+                                return Err(DeadLetter {
+                                    hash: Blake2b::from(contents),
+                                    reason: DeadLetterReason::MalformedOperation,
+                                    level: inbox_id.l1_level,
+                                });
                             }
                         }
                     };
@@ -161,13 +315,13 @@ pub fn parse_inbox_message(
                 }
             },
             Err(_) => {
-                logger.write_debug("Failed to parse the external message frame\n");
+                log_debug!(logger, "Failed to parse the external message frame\n");
                 None
             }
         },
-    }?;
+    };
 
-    Some(ParsedInboxMessageWrapper { inbox_id, content })
+    Ok(content.map(|content| ParsedInboxMessageWrapper { inbox_id, content }))
 }
 
 fn is_valid_native_deposit(
@@ -180,19 +334,22 @@ fn is_valid_native_deposit(
     match &creator.0 {
         Contract::Originated(kt1) if kt1 == native_ticketer => (),
         _ => {
-            logger.write_debug("Deposit ignored because of different ticketer");
+            log_debug!(logger, "Deposit ignored because of different ticketer");
             return false;
         }
     };
 
     let native_ticket_id = MichelsonNat::from(NATIVE_TICKET_ID);
     if contents.0 != native_ticket_id {
-        logger.write_debug("Deposit ignored because of different ticket id");
+        log_debug!(logger, "Deposit ignored because of different ticket id");
         return false;
     }
 
     if contents.1 != NATIVE_TICKET_CONTENT {
-        logger.write_debug("Deposit ignored because of different ticket content");
+        log_debug!(
+            logger,
+            "Deposit ignored because of different ticket content"
+        );
         return false;
     }
 
@@ -205,11 +362,11 @@ fn read_transfer(
     ticketer: &ContractKt1Hash,
     inbox_id: InboxId,
 ) -> Option<Message> {
-    logger.write_debug("Internal message: transfer\n");
+    log_debug!(logger, "Internal message: transfer\n");
     let source = match PublicKeyHash::from_base58(&transfer.source.to_b58check()) {
         Ok(addr) => addr,
         Err(e) => {
-            logger.write_debug(&format!("Failed to parse transfer source: {e:?}\n"));
+            log_debug!(logger, "Failed to parse transfer source: {e:?}\n");
             return None;
         }
     };
@@ -227,7 +384,7 @@ fn read_transfer(
                     receiver,
                     source,
                 };
-                logger.write_debug(format!("Deposit: {content:?}\n").as_str());
+                log_debug!(logger, "Deposit: {content:?}\n");
                 Some(Message::Internal(InternalMessage::Deposit(content)))
             } else {
                 None
@@ -249,7 +406,7 @@ fn read_external_message(
     bytes: &[u8],
 ) -> Option<ExternalMessage> {
     let msg = ExternalMessage::decode(bytes).ok()?;
-    logger.write_debug(&format!("External message: {msg:?}\n"));
+    log_trace!(logger, "External message: {msg:?}\n");
     Some(msg)
 }
 
@@ -296,14 +453,19 @@ pub fn encode_signed_operation(
 
 #[cfg(test)]
 mod test {
-    use jstz_core::host::WriteDebug;
+    use jstz_core::{host::WriteDebug, kv::Storage, BinEncodable};
     use jstz_crypto::{
-        hash::Hash, public_key::PublicKey, secret_key::SecretKey,
+        hash::{Blake2b, Hash},
+        public_key::PublicKey,
+        secret_key::SecretKey,
         smart_function_hash::SmartFunctionHash,
     };
     use jstz_mock::{
         host::JstzMockHost,
-        message::{fa_deposit::MockFaDeposit, native_deposit::MockNativeDeposit},
+        message::{
+            fa_deposit::MockFaDeposit, malformed_deposit::MockMalformedDeposit,
+            native_deposit::MockNativeDeposit,
+        },
     };
     use jstz_proto::{
         context::account::{Address, Addressable, Nonce},
@@ -311,19 +473,110 @@ mod test {
             internal::{self, InboxId},
             Content, DeployFunction, Operation, SignedOperation,
         },
+        receipt::{Receipt, ReceiptResult},
     };
+    use jstz_utils::{test_util::alice_keys, KeyPair};
     use tezos_crypto_rs::hash::{ContractKt1Hash, HashTrait};
-    use tezos_smart_rollup::types::SmartRollupAddress;
+    use tezos_smart_rollup::{storage::path::OwnedPath, types::SmartRollupAddress};
+    use tezos_smart_rollup_mock::MockHost;
 
     use crate::inbox::ParsedInboxMessage;
 
-    use super::{read_message, InternalMessage, Message};
+    use super::{
+        dead_letter_key, read_message, DeadLetter, DeadLetterReason, InternalMessage,
+        Message, DEAD_LETTER_COUNT_KEY,
+    };
 
     struct DummyLogger;
     impl WriteDebug for DummyLogger {
         fn write_debug(&self, _msg: &str) {}
     }
 
+    fn dead_letter_count(host: &MockHost) -> Option<u64> {
+        let path = OwnedPath::try_from(DEAD_LETTER_COUNT_KEY.to_string()).unwrap();
+        Storage::get(host, &path).unwrap()
+    }
+
+    fn dead_letter(host: &MockHost, index: u64) -> Option<DeadLetter> {
+        let path = OwnedPath::try_from(dead_letter_key(index)).unwrap();
+        Storage::get(host, &path).unwrap()
+    }
+
+    fn deploy_operation(public_key: PublicKey) -> Operation {
+        Operation {
+            public_key,
+            nonce: Nonce(0),
+            content: Content::DeployFunction(DeployFunction {
+                function_code: "code".to_string(),
+                account_credit: 0,
+                interface: None,
+            }),
+            max_fee: None,
+            priority_fee: None,
+        }
+    }
+
+    #[test]
+    fn read_message_quarantines_malformed_operation() {
+        let mut host = JstzMockHost::new(true);
+        let ticketer = host.get_ticketer();
+        let contents = vec![1u8, 2, 3];
+        host.add_external_message(contents.clone());
+
+        assert_eq!(read_message(host.rt(), &ticketer), None);
+
+        let dead_letter = dead_letter(host.rt(), 0).expect("Expected dead letter");
+        assert_eq!(dead_letter.reason, DeadLetterReason::MalformedOperation);
+        assert_eq!(dead_letter.hash, Blake2b::from(&contents.encode().unwrap()));
+        assert_eq!(dead_letter_count(host.rt()), Some(1));
+    }
+
+    #[test]
+    fn read_message_quarantines_invalid_signature() {
+        let mut host = JstzMockHost::new(true);
+        let ticketer = host.get_ticketer();
+        let KeyPair(pk, sk) = alice_keys();
+        let op = deploy_operation(pk);
+        let hash = op.hash();
+        let signature = sk
+            .sign(Blake2b::from(b"another operation".as_ref()))
+            .unwrap();
+        host.add_external_message(SignedOperation::new(signature, op));
+
+        assert_eq!(read_message(host.rt(), &ticketer), None);
+
+        let dead_letter = dead_letter(host.rt(), 0).expect("Expected dead letter");
+        assert_eq!(dead_letter.reason, DeadLetterReason::InvalidSignature);
+        assert_eq!(dead_letter.hash, hash);
+        assert_eq!(dead_letter_count(host.rt()), Some(1));
+
+        let path = OwnedPath::try_from(format!("/jstz_receipt/{hash}")).unwrap();
+        let receipt: Receipt = Storage::get(host.rt(), &path)
+            .unwrap()
+            .expect("Expected receipt");
+        assert!(matches!(receipt.result, ReceiptResult::Failed(_)));
+    }
+
+    #[test]
+    fn read_message_skips_quarantined_message() {
+        let mut host = JstzMockHost::new(true);
+        let ticketer = host.get_ticketer();
+        let KeyPair(pk, sk) = alice_keys();
+        let op = deploy_operation(pk);
+        let signature = sk.sign(op.hash()).unwrap();
+        let signed_op = SignedOperation::new(signature, op);
+        host.add_external_message(vec![1u8, 2, 3]);
+        host.add_external_message(signed_op.clone());
+
+        let message = read_message(host.rt(), &ticketer).expect("Expected message");
+
+        assert_eq!(
+            message.content,
+            ParsedInboxMessage::JstzMessage(Message::External(signed_op))
+        );
+        assert_eq!(dead_letter_count(host.rt()), Some(1));
+    }
+
     #[test]
     fn read_message_ignored_on_different_smart_rollup_address() {
         let mut host = JstzMockHost::new(true);
@@ -449,6 +702,42 @@ mod test {
         }
     }
 
+    #[test]
+    fn read_message_fa_deposit_without_proxy_succeeds() {
+        let mut host = JstzMockHost::new(true);
+        let fa_deposit = MockFaDeposit::new(42, None, None);
+        let ticketer = host.get_ticketer();
+        host.add_internal_message(&fa_deposit);
+
+        if let ParsedInboxMessage::JstzMessage(Message::Internal(
+            InternalMessage::FaDeposit(internal::FaDeposit {
+                amount,
+                proxy_smart_function,
+                ticket_hash,
+                ..
+            }),
+        )) = read_message(host.rt(), &ticketer)
+            .expect("Expected FA message")
+            .content
+        {
+            assert_eq!(42, amount);
+            assert_eq!(None, proxy_smart_function);
+            assert_eq!(fa_deposit.ticket_hash(), ticket_hash);
+        } else {
+            panic!("Expected deposit message")
+        }
+    }
+
+    #[test]
+    fn read_message_malformed_deposits_ignored() {
+        for deposit in MockMalformedDeposit::ALL {
+            let mut host = JstzMockHost::new(true);
+            let ticketer = host.get_ticketer();
+            host.add_malformed_deposit(deposit);
+            assert_eq!(read_message(host.rt(), &ticketer), None, "{deposit:?}");
+        }
+    }
+
     #[test]
     fn encode_signed_operation_round_trip() {
         let ticketer_addr =
@@ -467,7 +756,10 @@ mod test {
             content: Content::DeployFunction(DeployFunction {
                 function_code: "code".to_string(),
                 account_credit: 0,
+                interface: None,
             }),
+            max_fee: None,
+            priority_fee: None,
         };
         let hash = op.hash();
         let signed_op = SignedOperation::new(
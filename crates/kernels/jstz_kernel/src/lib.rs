@@ -3,15 +3,14 @@ use jstz_core::kv::{Storage, Transaction};
 use jstz_crypto::{public_key::PublicKey, smart_function_hash::SmartFunctionHash};
 use jstz_proto::executor;
 use jstz_proto::Result;
+use log::log_trace;
 use tezos_crypto_rs::hash::ContractKt1Hash;
-use tezos_smart_rollup::{
-    entrypoint,
-    prelude::{debug_msg, Runtime},
-    storage::path::RefPath,
-};
+use tezos_smart_rollup::{entrypoint, prelude::Runtime, storage::path::RefPath};
 
 pub mod inbox;
+pub mod log;
 pub mod parsing;
+pub mod upgrade;
 
 #[cfg(feature = "riscv_kernel")]
 pub mod riscv_kernel;
@@ -36,6 +35,8 @@ pub(crate) fn read_injector(rt: &impl Runtime) -> PublicKey {
         .expect("Revealer not found")
 }
 
+/// Executes a message read by [`inbox::read_message`], which verifies the
+/// signature of external operations
 pub async fn handle_message(
     hrt: &mut impl Runtime,
     message: Message,
@@ -50,8 +51,8 @@ pub async fn handle_message(
             receipt.write(hrt, tx)?
         }
         Message::External(signed_operation) => {
-            debug_msg!(hrt, "External operation: {signed_operation:?}\n");
-            let receipt = executor::execute_operation(
+            log_trace!(hrt, "External operation: {signed_operation:?}\n");
+            let receipt = executor::execute_verified_operation(
                 hrt,
                 tx,
                 signed_operation,
@@ -59,7 +60,7 @@ pub async fn handle_message(
                 injector,
             )
             .await;
-            debug_msg!(hrt, "Receipt: {receipt:?}\n");
+            log_trace!(hrt, "Receipt: {receipt:?}\n");
             receipt.write(hrt, tx)?
         }
     }
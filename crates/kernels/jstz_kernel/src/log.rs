@@ -0,0 +1,60 @@
+//! Leveled debug log of the kernel.
+//!
+//! Messages are only formatted and written if their level is enabled, so that
+//! production rollups can skip the cost of dumping every operation. The level
+//! is set by the injector with a `SetLogLevel` operation and loaded from
+//! durable storage with [`load`].
+
+use jstz_core::host::{HostRuntime, WriteDebug};
+use jstz_proto::executor::log_level;
+pub use jstz_proto::{
+    executor::log_level::{enabled, set_level},
+    operation::KernelLogLevel,
+};
+
+/// Writes a message to the debug log of `$logger` if `$level` is enabled
+macro_rules! log {
+    ($level:expr, $logger:expr, $($arg:tt)*) => {
+        if $crate::log::enabled($level) {
+            $crate::log::write($logger, &format!($($arg)*));
+        }
+    };
+}
+
+macro_rules! log_error {
+    ($logger:expr, $($arg:tt)*) => {
+        $crate::log::log!($crate::log::KernelLogLevel::Error, $logger, $($arg)*)
+    };
+}
+
+macro_rules! log_info {
+    ($logger:expr, $($arg:tt)*) => {
+        $crate::log::log!($crate::log::KernelLogLevel::Info, $logger, $($arg)*)
+    };
+}
+
+macro_rules! log_debug {
+    ($logger:expr, $($arg:tt)*) => {
+        $crate::log::log!($crate::log::KernelLogLevel::Debug, $logger, $($arg)*)
+    };
+}
+
+macro_rules! log_trace {
+    ($logger:expr, $($arg:tt)*) => {
+        $crate::log::log!($crate::log::KernelLogLevel::Trace, $logger, $($arg)*)
+    };
+}
+
+/// Loads the level from durable storage
+pub fn load(rt: &impl HostRuntime) {
+    match log_level::current(rt) {
+        Ok(level) => set_level(level),
+        Err(e) => log_error!(rt, "Failed to read the log level: {e:?}\n"),
+    }
+}
+
+pub(crate) fn write(logger: &impl WriteDebug, msg: &str) {
+    logger.write_debug(msg);
+}
+
+pub(crate) use {log, log_debug, log_error, log_info, log_trace};
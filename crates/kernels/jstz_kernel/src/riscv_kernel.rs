@@ -2,21 +2,25 @@ use std::sync::Arc;
 
 use jstz_core::{
     host::JsHostRuntime,
-    kv::{Storage, Transaction},
+    kv::{storage_update::BatchStorageUpdate, Storage, Transaction},
 };
 use jstz_crypto::{
     hash::Hash, public_key::PublicKey, smart_function_hash::SmartFunctionHash,
 };
-use jstz_proto::runtime::{
-    ProtoFetchHandler, ProtocolContext, PROTOCOL_CONTEXT, SNAPSHOT,
+use jstz_proto::{
+    executor::receipt_retention,
+    runtime::{ProtoFetchHandler, ProtocolContext, PROTOCOL_CONTEXT, SNAPSHOT},
 };
 use jstz_runtime::JstzRuntime;
-use tezos_smart_rollup::prelude::{debug_msg, Runtime};
+use tezos_smart_rollup::prelude::Runtime;
 
 use crate::{
     handle_message,
     inbox::{read_message, LevelInfo, ParsedInboxMessage},
-    read_injector, read_ticketer, INJECTOR, TICKETER,
+    log::{self, log_error, log_info},
+    read_injector, read_ticketer,
+    upgrade::install_scheduled_upgrade,
+    INJECTOR, TICKETER,
 };
 
 const TICKETER_PK: &str = std::env!("TICKETER");
@@ -40,7 +44,7 @@ pub fn run(rt: &mut impl Runtime) {
     let tokio_runtime = match tokio::runtime::Builder::new_current_thread().build() {
         Ok(runtime) => runtime,
         Err(e) => {
-            debug_msg!(rt, "Failed to build Tokio runtime: {:?}", e);
+            log_error!(rt, "Failed to build Tokio runtime: {:?}", e);
             return;
         }
     };
@@ -57,6 +61,7 @@ pub fn run(rt: &mut impl Runtime) {
 async fn run_event_loop(rt: &mut impl Runtime) {
     let ticketer = Arc::new(read_ticketer(rt));
     let injector = Arc::new(read_injector(rt));
+    log::load(rt);
     initialize_snapshot(rt);
     ProtocolContext::init_global(rt, 0).unwrap();
 
@@ -76,9 +81,9 @@ async fn run_event_loop(rt: &mut impl Runtime) {
                                 &mut host, message, &ticketer, &mut tx, &injector,
                             )
                             .await
-                            .unwrap_or_else(|err| debug_msg!(&host, "[🔴] {err:?}\n"));
+                            .unwrap_or_else(|err| log_error!(&host, "[🔴] {err:?}\n"));
                             if let Err(commit_error) = tx.commit(&mut host) {
-                                debug_msg!(
+                                log_error!(
                                     &host,
                                     "Failed to commit transaction: {commit_error:?}\n"
                                 );
@@ -86,11 +91,45 @@ async fn run_event_loop(rt: &mut impl Runtime) {
                         });
                     }
                     ParsedInboxMessage::LevelInfo(LevelInfo::Start) => {
+                        log::load(rt);
+                        if let Err(err) =
+                            BatchStorageUpdate::start_of_level(m.inbox_id.l1_level)
+                                .publish_event(rt)
+                        {
+                            log_error!(rt, "Failed to publish start of level: {err:?}\n");
+                        }
+                        if let Err(err) =
+                            install_scheduled_upgrade(rt, m.inbox_id.l1_level)
+                        {
+                            log_error!(rt, "Failed to install kernel upgrade: {err:?}\n");
+                        }
+                        let mut tx = Transaction::default();
+                        tx.begin();
+                        match receipt_retention::start_level(
+                            rt,
+                            &mut tx,
+                            m.inbox_id.l1_level,
+                        ) {
+                            Ok(0) => {}
+                            Ok(n) => log_info!(rt, "Removed {n} expired receipts\n"),
+                            Err(err) => log_error!(
+                                rt,
+                                "Failed to remove expired receipts: {err:?}\n"
+                            ),
+                        }
+                        if let Err(err) = tx.commit(rt) {
+                            log_error!(rt, "Failed to commit transaction: {err:?}\n");
+                        }
                         PROTOCOL_CONTEXT.get().unwrap().increment_level();
                         let oracle_ctx = PROTOCOL_CONTEXT.get().unwrap().oracle();
                         let mut oracle = oracle_ctx.lock();
                         oracle.gc_timeout_requests(rt);
                     }
+                    ParsedInboxMessage::LevelInfo(LevelInfo::End) => {
+                        if let Err(err) = Transaction::default().flush_outbox(rt) {
+                            log_error!(rt, "Failed to flush outbox: {err:?}\n");
+                        }
+                    }
                     ParsedInboxMessage::LevelInfo(_) => {}
                 }
             }
@@ -113,14 +152,18 @@ fn initialize_snapshot(rt: &mut impl Runtime) {
     if let Ok(snapshot) = snapshot {
         SNAPSHOT.get_or_init(|| Box::leak(snapshot.output));
     } else {
-        debug_msg!(rt, "Failed to generate snapshot: {:?}", snapshot.err());
+        log_error!(rt, "Failed to generate snapshot: {:?}", snapshot.err());
     }
 }
 
 #[cfg(test)]
 mod test {
 
-    use jstz_core::kv::Transaction;
+    use jstz_core::{
+        host::HostRuntime,
+        kv::{Storage, Transaction},
+        reveal_data::PreimageHash,
+    };
     use jstz_crypto::{hash::Hash, public_key_hash::PublicKeyHash};
     use jstz_mock::{
         host::{JstzMockHost, MOCK_SOURCE},
@@ -131,19 +174,23 @@ mod test {
             account::{Account, Address},
             ticket_table::TicketTable,
         },
-        executor::smart_function,
-        operation::{DeployFunction, Operation, RunFunction, SignedOperation},
+        executor::{kernel_upgrade, smart_function},
+        operation::{
+            DeployFunction, Operation, RunFunction, SignedOperation, UpgradeKernel,
+        },
+        storage::KERNEL_UPGRADE_PATH,
         HttpBody,
     };
     use jstz_utils::{
         test_util::{alice_keys, bob_keys},
         KeyPair,
     };
-    use tezos_smart_rollup::types::{
-        Contract as L1Address, PublicKeyHash as L1PublicKeyHash,
+    use tezos_smart_rollup::{
+        dac,
+        types::{Contract as L1Address, PublicKeyHash as L1PublicKeyHash},
     };
 
-    use crate::{parsing::try_parse_contract, read_ticketer};
+    use crate::{parsing::try_parse_contract, read_ticketer, upgrade::KERNEL_BOOT_PATH};
 
     use super::run;
 
@@ -193,6 +240,8 @@ mod test {
                 public_key: bob_pk.clone(),
                 nonce: 0.into(),
                 content: run_fn.into(),
+                max_fee: None,
+                priority_fee: None,
             };
             let sig = bob_sk.sign(op.hash())?;
             SignedOperation::new(sig, op)
@@ -216,11 +265,14 @@ mod test {
             let deploy_fn = DeployFunction {
                 function_code: code,
                 account_credit: 0,
+                interface: None,
             };
             let op = Operation {
                 public_key: alice_pk.clone(),
                 nonce: 0.into(),
                 content: deploy_fn.into(),
+                max_fee: None,
+                priority_fee: None,
             };
             let sig = alice_sk.sign(op.hash())?;
             SignedOperation::new(sig, op)
@@ -240,6 +292,8 @@ mod test {
                 public_key: alice_pk.clone(),
                 nonce: 1.into(),
                 content: run_fn.into(),
+                max_fee: None,
+                priority_fee: None,
             };
             let sig = alice_sk.sign(op.hash())?;
             SignedOperation::new(sig, op)
@@ -382,4 +436,35 @@ mod test {
             _ => panic!("Unexpected receiver"),
         }
     }
+
+    #[test]
+    fn installs_scheduled_upgrade_at_start_of_level() {
+        const NEW_KERNEL: &[u8] = b"\0asm new kernel";
+        let mut host = JstzMockHost::default();
+        let root_hash = dac::prepare_preimages(NEW_KERNEL, |_, page| {
+            host.rt().set_preimage(page);
+        })
+        .unwrap();
+        let KeyPair(injector, _) = alice_keys();
+        let mut tx = Transaction::default();
+        tx.begin();
+        kernel_upgrade::execute(
+            &mut tx,
+            &injector,
+            &injector,
+            UpgradeKernel {
+                root_hash: PreimageHash::from(root_hash),
+                activation_level: host.rt().level() + 1,
+            },
+        )
+        .unwrap();
+        tx.commit(host.rt()).unwrap();
+
+        host.rt().run_level(run);
+        assert_eq!(
+            host.rt().store_read_all(&KERNEL_BOOT_PATH).unwrap(),
+            NEW_KERNEL
+        );
+        assert!(!Storage::contains_key(host.rt(), &KERNEL_UPGRADE_PATH).unwrap());
+    }
 }
@@ -0,0 +1,102 @@
+//! Installation of kernel upgrades scheduled by the injector.
+//!
+//! The new kernel is installed the way the reveal installer does it: its
+//! preimages are revealed to a staging path, which is then moved to the boot
+//! path. The new kernel runs after the next reboot.
+
+use jstz_core::{host::HostRuntime, kv::Storage, reveal_data::RevealData};
+use jstz_proto::{executor::kernel_upgrade, storage::KERNEL_UPGRADE_PATH, Result};
+use tezos_smart_rollup::storage::path::RefPath;
+
+use crate::log::log_info;
+
+pub const KERNEL_BOOT_PATH: RefPath = RefPath::assert_from(b"/kernel/boot.wasm");
+const KERNEL_STAGING_PATH: RefPath = RefPath::assert_from(b"/kernel_upgrade/kernel");
+
+/// Installs the scheduled kernel upgrade if it activates at or before `level`.
+/// Returns whether a new kernel was installed.
+///
+/// The upgrade is unscheduled even if the installation fails, so that a kernel
+/// that cannot be revealed is not attempted at every level.
+pub fn install_scheduled_upgrade(rt: &mut impl HostRuntime, level: u32) -> Result<bool> {
+    let Some(upgrade) = kernel_upgrade::scheduled(rt)? else {
+        return Ok(false);
+    };
+    if upgrade.activation_level > level {
+        return Ok(false);
+    }
+    Storage::remove(rt, &KERNEL_UPGRADE_PATH)?;
+
+    Storage::remove(rt, &KERNEL_STAGING_PATH)?;
+    let size = RevealData::reveal_to_store(rt, &upgrade.root_hash, &KERNEL_STAGING_PATH)?;
+    rt.store_move(&KERNEL_STAGING_PATH, &KERNEL_BOOT_PATH)
+        .map_err(jstz_core::Error::from)?;
+    log_info!(
+        rt,
+        "Installed kernel {} ({size} bytes) at level {level}\n",
+        upgrade.root_hash
+    );
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use jstz_core::{
+        host::HostRuntime,
+        kv::{Storage, Transaction},
+        reveal_data::PreimageHash,
+    };
+    use jstz_proto::{
+        executor::kernel_upgrade, operation::UpgradeKernel, storage::KERNEL_UPGRADE_PATH,
+    };
+    use jstz_utils::{test_util::alice_keys, KeyPair};
+    use tezos_smart_rollup::dac;
+    use tezos_smart_rollup_mock::MockHost;
+
+    use super::{install_scheduled_upgrade, KERNEL_BOOT_PATH};
+
+    const NEW_KERNEL: &[u8] = b"\0asm new kernel";
+
+    fn schedule_upgrade(host: &mut MockHost, activation_level: u32) {
+        let root_hash = dac::prepare_preimages(NEW_KERNEL, |_, page| {
+            host.set_preimage(page);
+        })
+        .unwrap();
+        let KeyPair(injector, _) = alice_keys();
+        let mut tx = Transaction::default();
+        tx.begin();
+        kernel_upgrade::execute(
+            &mut tx,
+            &injector,
+            &injector,
+            UpgradeKernel {
+                root_hash: PreimageHash::from(root_hash),
+                activation_level,
+            },
+        )
+        .unwrap();
+        tx.commit(host).unwrap();
+    }
+
+    #[test]
+    fn installs_upgrade_at_activation_level() {
+        let mut host = MockHost::default();
+        schedule_upgrade(&mut host, 10);
+
+        assert!(!install_scheduled_upgrade(&mut host, 9).unwrap());
+        assert!(Storage::contains_key(&host, &KERNEL_UPGRADE_PATH).unwrap());
+
+        assert!(install_scheduled_upgrade(&mut host, 10).unwrap());
+        assert_eq!(host.store_read_all(&KERNEL_BOOT_PATH).unwrap(), NEW_KERNEL);
+        assert!(!Storage::contains_key(&host, &KERNEL_UPGRADE_PATH).unwrap());
+    }
+
+    #[test]
+    fn installs_upgrade_after_activation_level() {
+        let mut host = MockHost::default();
+        schedule_upgrade(&mut host, 10);
+
+        assert!(install_scheduled_upgrade(&mut host, 12).unwrap());
+        assert_eq!(host.store_read_all(&KERNEL_BOOT_PATH).unwrap(), NEW_KERNEL);
+    }
+}
@@ -1,9 +1,13 @@
 use crate::handle_message;
-use crate::inbox::{read_message, ParsedInboxMessage};
-use jstz_core::kv::Transaction;
-use tezos_smart_rollup::prelude::{debug_msg, Runtime};
+use crate::inbox::{read_message, LevelInfo, ParsedInboxMessage};
+use crate::log::{log_error, log_info};
+use crate::upgrade::install_scheduled_upgrade;
+use jstz_core::kv::{storage_update::BatchStorageUpdate, Transaction};
+use jstz_proto::executor::receipt_retention;
+use tezos_smart_rollup::prelude::Runtime;
 
 pub fn run(rt: &mut impl Runtime) {
+    crate::log::load(rt);
     jstz_core::future::block_on(async {
         // TODO(https://linear.app/tezos/issue/JSTZ-459/organize-protocol-consts-into-a-struct)
         // we should organize protocol consts into a struct
@@ -17,13 +21,36 @@ pub fn run(rt: &mut impl Runtime) {
                 ParsedInboxMessage::JstzMessage(message) => {
                     handle_message(rt, message, &ticketer, &mut tx, &injector)
                         .await
-                        .unwrap_or_else(|err| debug_msg!(rt, "[🔴] {err:?}\n"));
+                        .unwrap_or_else(|err| log_error!(rt, "[🔴] {err:?}\n"));
+                }
+                ParsedInboxMessage::LevelInfo(LevelInfo::Start) => {
+                    let level = message.inbox_id.l1_level;
+                    if let Err(err) =
+                        BatchStorageUpdate::start_of_level(level).publish_event(rt)
+                    {
+                        log_error!(rt, "Failed to publish start of level: {err:?}\n");
+                    }
+                    if let Err(err) = install_scheduled_upgrade(rt, level) {
+                        log_error!(rt, "Failed to install kernel upgrade: {err:?}\n");
+                    }
+                    match receipt_retention::start_level(rt, &mut tx, level) {
+                        Ok(0) => {}
+                        Ok(n) => log_info!(rt, "Removed {n} expired receipts\n"),
+                        Err(err) => {
+                            log_error!(rt, "Failed to remove expired receipts: {err:?}\n")
+                        }
+                    }
+                }
+                ParsedInboxMessage::LevelInfo(LevelInfo::End) => {
+                    if let Err(err) = tx.flush_outbox(rt) {
+                        log_error!(rt, "Failed to flush outbox: {err:?}\n");
+                    }
                 }
                 ParsedInboxMessage::LevelInfo(_) => (),
             }
         }
         if let Err(commit_error) = tx.commit(rt) {
-            debug_msg!(rt, "Failed to commit transaction: {commit_error:?}\n");
+            log_error!(rt, "Failed to commit transaction: {commit_error:?}\n");
         }
     })
 }
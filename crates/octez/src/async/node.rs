@@ -1,10 +1,18 @@
-use std::{path::PathBuf, process::Stdio, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+};
 
-use tokio::process::{Child, Command};
+use tempfile::NamedTempFile;
+use tokio::{
+    io::AsyncWriteExt,
+    process::{Child, Command},
+};
 
 use crate::path_or_default;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
 use super::{endpoint::Endpoint, file::FileWrapper, node_config::OctezNodeRunOptions};
 
@@ -69,6 +77,22 @@ impl OctezNode {
             .spawn()?)
     }
 
+    /// Imports the snapshot at `snapshot_path` into the node data directory.
+    /// The node config must have been initialised beforehand so that the
+    /// snapshot is checked against the right network.
+    pub async fn snapshot_import(&self, snapshot_path: &Path) -> Result<Child> {
+        Ok(self
+            .command()?
+            .args([
+                "snapshot",
+                "import",
+                snapshot_path.to_str().expect("Invalid path"),
+                "--data-dir",
+                self.octez_node_dir.to_str().expect("Invalid path"),
+            ])
+            .spawn()?)
+    }
+
     pub fn run(&self, options: &OctezNodeRunOptions) -> Result<Child> {
         let mut command = self.command()?;
 
@@ -84,3 +108,19 @@ impl OctezNode {
         Ok(command.spawn()?)
     }
 }
+
+/// Downloads the snapshot served at `url` into a temporary file. The file is
+/// removed once the returned handle is dropped.
+pub async fn download_snapshot(url: &str) -> Result<NamedTempFile> {
+    let tmp_file = NamedTempFile::new()?;
+    let mut response = reqwest::get(url)
+        .await
+        .and_then(|r| r.error_for_status())
+        .with_context(|| format!("failed to download snapshot from '{url}'"))?;
+    let mut file = tokio::fs::File::from_std(tmp_file.as_file().try_clone()?);
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok(tmp_file)
+}
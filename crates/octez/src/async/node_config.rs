@@ -1,7 +1,7 @@
 use crate::unused_port;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use serde_with::DeserializeFromStr;
+use serde_with::{DeserializeFromStr, SerializeDisplay};
 use std::{
     fmt::{self, Display, Formatter},
     path::{Path, PathBuf},
@@ -73,6 +73,39 @@ impl Serialize for OctezNodeHistoryMode {
     }
 }
 
+/// Source of a chain snapshot imported into the node data directory before
+/// the node starts.
+#[derive(Clone, PartialEq, Debug, DeserializeFromStr, SerializeDisplay)]
+pub enum OctezNodeSnapshot {
+    /// Snapshot file on the local file system.
+    Path(PathBuf),
+    /// Snapshot served over HTTP(S). It is downloaded before being imported.
+    Url(String),
+}
+
+impl Display for OctezNodeSnapshot {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Path(v) => write!(f, "{}", v.to_string_lossy()),
+            Self::Url(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+impl FromStr for OctezNodeSnapshot {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(anyhow::anyhow!("snapshot source cannot be empty"));
+        }
+        if s.starts_with("http://") || s.starts_with("https://") {
+            return Ok(Self::Url(s.to_owned()));
+        }
+        Ok(Self::Path(PathBuf::from(s)))
+    }
+}
+
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct OctezNodeRunOptions {
     synchronisation_threshold: u8,
@@ -201,6 +234,9 @@ pub struct OctezNodeConfig {
     pub log_file: Option<PathBuf>,
     /// Run options for octez node.
     pub run_options: OctezNodeRunOptions,
+    /// Snapshot to import before the node starts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<OctezNodeSnapshot>,
 }
 
 #[derive(Default, Deserialize, Debug, PartialEq)]
@@ -212,6 +248,7 @@ pub struct OctezNodeConfigBuilder {
     p2p_address: Option<Endpoint>,
     log_file: Option<PathBuf>,
     run_options: Option<OctezNodeRunOptions>,
+    snapshot: Option<OctezNodeSnapshot>,
 }
 
 impl OctezNodeConfigBuilder {
@@ -264,6 +301,16 @@ impl OctezNodeConfigBuilder {
         self.run_options.as_ref()
     }
 
+    /// Sets the snapshot to import before the node starts.
+    pub fn set_snapshot(&mut self, snapshot: OctezNodeSnapshot) -> &mut Self {
+        self.snapshot.replace(snapshot);
+        self
+    }
+
+    pub fn snapshot(&self) -> Option<&OctezNodeSnapshot> {
+        self.snapshot.as_ref()
+    }
+
     /// Builds a config set based on values collected.
     pub fn build(&mut self) -> Result<OctezNodeConfig> {
         Ok(OctezNodeConfig {
@@ -286,6 +333,7 @@ impl OctezNodeConfigBuilder {
             ),
             log_file: self.log_file.take(),
             run_options: self.run_options.take().unwrap_or_default(),
+            snapshot: self.snapshot.take(),
         })
     }
 }
@@ -324,6 +372,72 @@ mod tests {
         assert_eq!(config.binary_path, PathBuf::from(DEFAULT_BINARY_PATH));
         assert_eq!(config.network, DEFAULT_NETWORK.to_owned());
         assert_eq!(config.run_options, OctezNodeRunOptions::default());
+        assert!(config.snapshot.is_none());
+    }
+
+    #[test]
+    fn config_builder_snapshot() {
+        let mut builder = OctezNodeConfigBuilder::new();
+        builder.set_snapshot(OctezNodeSnapshot::Url(
+            "https://snapshots.example.com/rolling".to_owned(),
+        ));
+        assert_eq!(
+            builder.snapshot(),
+            Some(&OctezNodeSnapshot::Url(
+                "https://snapshots.example.com/rolling".to_owned()
+            ))
+        );
+        let config = builder.build().unwrap();
+        assert_eq!(
+            config.snapshot,
+            Some(OctezNodeSnapshot::Url(
+                "https://snapshots.example.com/rolling".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn snapshot_from_str() {
+        assert_eq!(
+            OctezNodeSnapshot::from_str("/tmp/snapshot.rolling").unwrap(),
+            OctezNodeSnapshot::Path(PathBuf::from("/tmp/snapshot.rolling"))
+        );
+        assert_eq!(
+            OctezNodeSnapshot::from_str("https://foo.bar/rolling").unwrap(),
+            OctezNodeSnapshot::Url("https://foo.bar/rolling".to_owned())
+        );
+        assert_eq!(
+            OctezNodeSnapshot::from_str("http://foo.bar/rolling").unwrap(),
+            OctezNodeSnapshot::Url("http://foo.bar/rolling".to_owned())
+        );
+        assert_eq!(
+            OctezNodeSnapshot::from_str("").unwrap_err().to_string(),
+            "snapshot source cannot be empty"
+        );
+    }
+
+    #[test]
+    fn snapshot_serde() {
+        let snapshot =
+            serde_json::from_str::<OctezNodeSnapshot>("\"https://foo.bar/rolling\"")
+                .unwrap();
+        assert_eq!(
+            snapshot,
+            OctezNodeSnapshot::Url("https://foo.bar/rolling".to_owned())
+        );
+        assert_eq!(
+            serde_json::to_value(OctezNodeSnapshot::Path(PathBuf::from("/tmp/s")))
+                .unwrap(),
+            serde_json::json!("/tmp/s")
+        );
+        let builder = serde_json::from_value::<OctezNodeConfigBuilder>(
+            serde_json::json!({"snapshot": "/tmp/s"}),
+        )
+        .unwrap();
+        assert_eq!(
+            builder.snapshot(),
+            Some(&OctezNodeSnapshot::Path(PathBuf::from("/tmp/s")))
+        );
     }
 
     #[test]
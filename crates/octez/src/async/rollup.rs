@@ -1,3 +1,5 @@
+pub mod log;
+
 use crate::unused_port;
 
 use super::file::FileWrapper;
@@ -0,0 +1,362 @@
+//! Classification of rollup node logs.
+//!
+//! The rollup node does not expose most of its internal events through RPC,
+//! so [`RollupLogMonitor`] tails the node's log file and turns the lines that
+//! matter for health reporting into [`RollupNodeEvent`]s. Events are only
+//! recognised at the start of a log message, after the optional timestamp, so
+//! that lines merely mentioning a commitment or the store are ignored.
+
+use std::{path::Path, sync::LazyLock, time::Duration};
+
+use regex::Regex;
+use serde::Serialize;
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, BufReader},
+    sync::watch,
+    task::JoinHandle,
+};
+
+/// Start of a log message: optional indentation and `Mon DD HH:MM:SS.mmm:` timestamp
+const MESSAGE_START: &str = r"^\s*(?:[A-Z][a-z]{2}\s+\d{1,2}\s+[\d:.]+:\s*)?";
+
+static COMMITMENT_PUBLISHED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?i){MESSAGE_START}(?:publishing commitment\b|commitment \S+ (?:was )?published\b)"
+    ))
+    .unwrap()
+});
+static REFUTATION_STARTED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?i){MESSAGE_START}(?:(?:starting|started|opening) refutation game\b|refutation game started\b|conflict detected with\b)"
+    ))
+    .unwrap()
+});
+static STORE_ERROR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?i){MESSAGE_START}(?:error|fatal error)\b.*\b(?:store|irmin)\b"
+    ))
+    .unwrap()
+});
+static LEVEL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\binbox level\s+(\d+)").unwrap());
+static IMPLICIT_ADDRESS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\btz[1-4][1-9A-HJ-NP-Za-km-z]{33}\b").unwrap());
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Meaningful events emitted by the rollup node.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RollupNodeEvent {
+    /// The operator published a commitment, optionally for a known inbox level.
+    CommitmentPublished { level: Option<u32> },
+    /// A refutation game started against the operator.
+    RefutationStarted { opponent: Option<String> },
+    /// The node failed to read from or write to its store.
+    StoreError { message: String },
+}
+
+impl RollupNodeEvent {
+    /// Classifies a log line. Returns `None` for lines that are not relevant.
+    pub fn parse(line: &str) -> Option<Self> {
+        if STORE_ERROR.is_match(line) {
+            return Some(Self::StoreError {
+                message: line.trim().to_string(),
+            });
+        }
+        if REFUTATION_STARTED.is_match(line) {
+            return Some(Self::RefutationStarted {
+                opponent: IMPLICIT_ADDRESS.find(line).map(|m| m.as_str().to_string()),
+            });
+        }
+        if COMMITMENT_PUBLISHED.is_match(line) {
+            return Some(Self::CommitmentPublished {
+                level: LEVEL
+                    .captures(line)
+                    .and_then(|c| c.get(1))
+                    .and_then(|m| m.as_str().parse().ok()),
+            });
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RollupNodeStatus {
+    Healthy,
+    /// The node runs but requires attention, e.g. it is in a refutation game.
+    Degraded,
+    /// The node reported errors it is unlikely to recover from.
+    Unhealthy,
+}
+
+/// Health of the rollup node accumulated from its events.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RollupNodeHealth {
+    pub commitments_published: u64,
+    pub last_commitment_level: Option<u32>,
+    pub refutations_started: u64,
+    pub store_errors: u64,
+    /// Last store error reported since the last published commitment
+    pub last_store_error: Option<String>,
+}
+
+impl RollupNodeHealth {
+    pub fn record(&mut self, event: &RollupNodeEvent) {
+        match event {
+            RollupNodeEvent::CommitmentPublished { level } => {
+                self.commitments_published += 1;
+                if level.is_some() {
+                    self.last_commitment_level = *level;
+                }
+                // the node made progress after the error, so it recovered
+                self.last_store_error = None;
+            }
+            RollupNodeEvent::RefutationStarted { .. } => self.refutations_started += 1,
+            RollupNodeEvent::StoreError { message } => {
+                self.store_errors += 1;
+                self.last_store_error.replace(message.clone());
+            }
+        }
+    }
+
+    pub fn status(&self) -> RollupNodeStatus {
+        if self.last_store_error.is_some() {
+            RollupNodeStatus::Unhealthy
+        } else if self.refutations_started > 0 {
+            RollupNodeStatus::Degraded
+        } else {
+            RollupNodeStatus::Healthy
+        }
+    }
+
+    pub fn report(&self) -> RollupNodeHealthReport {
+        RollupNodeHealthReport {
+            status: self.status(),
+            details: self.clone(),
+        }
+    }
+}
+
+/// Serialisable view of [`RollupNodeHealth`] together with its status.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RollupNodeHealthReport {
+    pub status: RollupNodeStatus,
+    #[serde(flatten)]
+    pub details: RollupNodeHealth,
+}
+
+/// Tails a rollup node log file in the background and keeps track of the
+/// node health. The background task stops when the monitor is dropped.
+pub struct RollupLogMonitor {
+    health: watch::Receiver<RollupNodeHealth>,
+    handle: JoinHandle<()>,
+}
+
+impl RollupLogMonitor {
+    /// Monitors the log file at `path`. The file is read from the start once it
+    /// exists, so the monitor can be spawned before the node creates it.
+    pub fn spawn(path: &Path) -> Self {
+        let path = path.to_path_buf();
+        let (tx, health) = watch::channel(RollupNodeHealth::default());
+        let handle = tokio::spawn(async move {
+            let file = loop {
+                match File::open(&path).await {
+                    Ok(file) => break file,
+                    Err(_) => tokio::time::sleep(POLL_INTERVAL).await,
+                }
+            };
+            let mut lines = BufReader::new(file).lines();
+            loop {
+                match lines.next_line().await {
+                    Ok(Some(line)) => {
+                        if let Some(event) = RollupNodeEvent::parse(&line) {
+                            tx.send_modify(|health| health.record(&event));
+                        }
+                    }
+                    // EOF – wait for the node to write more
+                    Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+                    Err(_) => break,
+                }
+            }
+        });
+        Self { health, handle }
+    }
+
+    pub fn health(&self) -> RollupNodeHealth {
+        self.health.borrow().clone()
+    }
+
+    /// Waits until the health of the node satisfies `f` and returns it.
+    pub async fn wait_for(
+        &self,
+        f: impl FnMut(&RollupNodeHealth) -> bool,
+    ) -> RollupNodeHealth {
+        let mut health = self.health.clone();
+        // the sender lives as long as the background task, which only stops
+        // on read errors
+        match health.wait_for(f).await {
+            Ok(v) => v.clone(),
+            Err(_) => self.health(),
+        }
+    }
+}
+
+impl Drop for RollupLogMonitor {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn parse_commitment_published() {
+        assert_eq!(
+            RollupNodeEvent::parse(
+                "Oct 16 10:00:00.000: Publishing commitment src13... for inbox level 42"
+            ),
+            Some(RollupNodeEvent::CommitmentPublished { level: Some(42) })
+        );
+        assert_eq!(
+            RollupNodeEvent::parse("Commitment src13... was published"),
+            Some(RollupNodeEvent::CommitmentPublished { level: None })
+        );
+        // the level must be the inbox level of the commitment
+        assert_eq!(
+            RollupNodeEvent::parse("Publishing commitment src13..., last level 7"),
+            Some(RollupNodeEvent::CommitmentPublished { level: None })
+        );
+    }
+
+    #[test]
+    fn parse_refutation_started() {
+        assert_eq!(
+            RollupNodeEvent::parse(
+                "Refutation game started with tz1KqTpEZ7Yob7QbPE4Hy4Wo8fHG8LhKxZSx"
+            ),
+            Some(RollupNodeEvent::RefutationStarted {
+                opponent: Some("tz1KqTpEZ7Yob7QbPE4Hy4Wo8fHG8LhKxZSx".to_string())
+            })
+        );
+        assert_eq!(
+            RollupNodeEvent::parse("Opening refutation game"),
+            Some(RollupNodeEvent::RefutationStarted { opponent: None })
+        );
+        assert_eq!(
+            RollupNodeEvent::parse(
+                "Oct 16 10:00:00.000: Conflict detected with tz1KqTpEZ7Yob7QbPE4Hy4Wo8fHG8LhKxZSx"
+            ),
+            Some(RollupNodeEvent::RefutationStarted {
+                opponent: Some("tz1KqTpEZ7Yob7QbPE4Hy4Wo8fHG8LhKxZSx".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn parse_store_error() {
+        assert_eq!(
+            RollupNodeEvent::parse("  Error: cannot write to store  "),
+            Some(RollupNodeEvent::StoreError {
+                message: "Error: cannot write to store".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_irrelevant() {
+        assert!(RollupNodeEvent::parse("Processing head 0x1234 at level 5").is_none());
+        assert!(RollupNodeEvent::parse("").is_none());
+        // events mentioned in other messages
+        assert!(RollupNodeEvent::parse("Store opened, 0 errors recovered").is_none());
+        assert!(
+            RollupNodeEvent::parse("Injecting operation: publish commitment").is_none()
+        );
+        assert!(
+            RollupNodeEvent::parse("Not starting a refutation game: no conflict")
+                .is_none()
+        );
+        assert!(RollupNodeEvent::parse("Retrying store write after error").is_none());
+    }
+
+    #[test]
+    fn health_status() {
+        let mut health = RollupNodeHealth::default();
+        assert_eq!(health.status(), RollupNodeStatus::Healthy);
+
+        health.record(&RollupNodeEvent::CommitmentPublished { level: Some(3) });
+        health.record(&RollupNodeEvent::CommitmentPublished { level: None });
+        assert_eq!(health.commitments_published, 2);
+        assert_eq!(health.last_commitment_level, Some(3));
+        assert_eq!(health.status(), RollupNodeStatus::Healthy);
+
+        health.record(&RollupNodeEvent::RefutationStarted { opponent: None });
+        assert_eq!(health.status(), RollupNodeStatus::Degraded);
+
+        health.record(&RollupNodeEvent::StoreError {
+            message: "boom".to_string(),
+        });
+        assert_eq!(health.status(), RollupNodeStatus::Unhealthy);
+        assert_eq!(health.last_store_error, Some("boom".to_string()));
+
+        // publishing a commitment after the error clears it
+        health.record(&RollupNodeEvent::CommitmentPublished { level: Some(4) });
+        assert_eq!(health.status(), RollupNodeStatus::Degraded);
+        assert_eq!(health.store_errors, 1);
+        assert_eq!(health.last_store_error, None);
+    }
+
+    #[test]
+    fn serialize_report() {
+        let mut health = RollupNodeHealth::default();
+        health.record(&RollupNodeEvent::CommitmentPublished { level: Some(3) });
+        assert_eq!(
+            serde_json::to_value(health.report()).unwrap(),
+            serde_json::json!({
+                "status": "healthy",
+                "commitments_published": 1,
+                "last_commitment_level": 3,
+                "refutations_started": 0,
+                "store_errors": 0,
+                "last_store_error": null
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn monitor_tails_log_file() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "Publishing commitment for inbox level 1").unwrap();
+        let monitor = RollupLogMonitor::spawn(file.path());
+
+        writeln!(file, "Processing head").unwrap();
+        writeln!(file, "Error: cannot write to store: disk full").unwrap();
+        file.flush().unwrap();
+
+        let health = monitor.wait_for(|health| health.store_errors == 1).await;
+        assert_eq!(health.commitments_published, 1);
+        assert_eq!(health.status(), RollupNodeStatus::Unhealthy);
+    }
+
+    #[tokio::test]
+    async fn monitor_waits_for_log_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rollup.log");
+        let monitor = RollupLogMonitor::spawn(&path);
+        assert_eq!(monitor.health(), RollupNodeHealth::default());
+
+        std::fs::write(&path, "Publishing commitment for inbox level 2\n").unwrap();
+        let health = monitor
+            .wait_for(|health| health.commitments_published == 1)
+            .await;
+        assert_eq!(health.last_commitment_level, Some(2));
+    }
+}
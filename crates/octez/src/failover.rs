@@ -0,0 +1,501 @@
+//! Failover between the RPC endpoints of several rollup nodes.
+//!
+//! [`FailoverRollupClient`] sends requests to the active endpoint, which is the first
+//! healthy endpoint in the order they were given. Reads are safe to repeat and are
+//! retried on the next endpoints when the active one fails, which is then marked
+//! unhealthy. Only failures of the endpoint, i.e. transport errors and 5xx responses,
+//! count against it: other errors are caused by the request and are returned as is.
+//! Reads fail over only to endpoints that are at most [`MAX_ENDPOINT_LAG`] levels behind the
+//! highest level seen, so that a lagging node does not serve stale values.
+//! Injections are only sent to the active endpoint, as retrying them on another node
+//! could inject the messages twice. The endpoints are health-checked periodically, so
+//! that the client switches back to a preferred endpoint once it recovers.
+
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use serde::Serialize;
+use tezos_smart_rollup_encoding::smart_rollup::SmartRollupAddress;
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{info, warn};
+
+use crate::{
+    rollup::{DurableValueProof, UnexpectedStatus},
+    OctezRollupClient, RollupRpc,
+};
+
+/// Interval at which the endpoints of a [`FailoverRollupClient`] are health-checked.
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Number of levels an endpoint can be behind the highest level seen before it is
+/// considered unhealthy. Nodes of the same rollup can be one block apart while a new
+/// block propagates.
+pub const MAX_ENDPOINT_LAG: u32 = 1;
+
+struct Endpoint {
+    url: String,
+    rpc: Arc<dyn RollupRpc>,
+    healthy: AtomicBool,
+}
+
+/// Status of an endpoint of a [`FailoverRollupClient`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EndpointStatus {
+    pub endpoint: String,
+    /// Whether the endpoint answered the last request or health check, without
+    /// lagging behind the other endpoints.
+    pub healthy: bool,
+    /// Whether requests are currently sent to the endpoint.
+    pub active: bool,
+}
+
+/// Rollup node client failing over between several endpoints.
+pub struct FailoverRollupClient {
+    endpoints: Vec<Endpoint>,
+    active: watch::Sender<usize>,
+    /// Highest head level reported by the endpoints.
+    level: AtomicU32,
+}
+
+/// Health checks of the endpoints of a [`FailoverRollupClient`], stopped when dropped.
+pub struct HealthChecks(JoinHandle<()>);
+
+impl Drop for HealthChecks {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+impl FailoverRollupClient {
+    /// Creates a client of the rollup nodes at `endpoints`, in order of preference.
+    pub fn new(endpoints: &[String]) -> Result<Self> {
+        Self::from_rpcs(
+            endpoints
+                .iter()
+                .map(|url| {
+                    let rpc: Arc<dyn RollupRpc> =
+                        Arc::new(OctezRollupClient::new(url.clone()));
+                    (url.clone(), rpc)
+                })
+                .collect(),
+        )
+    }
+
+    /// Creates a client of the rollup nodes served by `rpcs`, given with their
+    /// endpoint, in order of preference.
+    pub fn from_rpcs(rpcs: Vec<(String, Arc<dyn RollupRpc>)>) -> Result<Self> {
+        if rpcs.is_empty() {
+            bail!("at least one rollup endpoint is required");
+        }
+        Ok(Self {
+            endpoints: rpcs
+                .into_iter()
+                .map(|(url, rpc)| Endpoint {
+                    url,
+                    rpc,
+                    healthy: AtomicBool::new(true),
+                })
+                .collect(),
+            active: watch::Sender::new(0),
+            level: AtomicU32::new(0),
+        })
+    }
+
+    pub fn status(&self) -> Vec<EndpointStatus> {
+        let active = self.active();
+        self.endpoints
+            .iter()
+            .enumerate()
+            .map(|(index, endpoint)| EndpointStatus {
+                endpoint: endpoint.url.clone(),
+                healthy: endpoint.healthy.load(Ordering::Relaxed),
+                active: index == active,
+            })
+            .collect()
+    }
+
+    /// Whether at least one endpoint is healthy.
+    pub fn is_healthy(&self) -> bool {
+        self.endpoints
+            .iter()
+            .any(|endpoint| endpoint.healthy.load(Ordering::Relaxed))
+    }
+
+    /// Index of the endpoint requests are currently sent to, in the order the
+    /// endpoints were given.
+    pub fn active(&self) -> usize {
+        *self.active.borrow()
+    }
+
+    /// Endpoint requests are currently sent to.
+    pub fn active_endpoint(&self) -> String {
+        self.endpoints[self.active()].url.clone()
+    }
+
+    /// Subscribes to the index of the active endpoint, see [`Self::active`].
+    pub fn subscribe(&self) -> watch::Receiver<usize> {
+        self.active.subscribe()
+    }
+
+    /// Checks the health of every endpoint and activates the first healthy one. The
+    /// active endpoint is kept if none is healthy.
+    pub async fn check_health(&self) {
+        for endpoint in &self.endpoints {
+            let healthy = self.check_level(endpoint).await.is_ok();
+            set_healthy(endpoint, healthy);
+        }
+        if let Some(index) = self
+            .endpoints
+            .iter()
+            .position(|endpoint| endpoint.healthy.load(Ordering::Relaxed))
+        {
+            self.activate(index);
+        }
+    }
+
+    /// Spawns a task checking the health of the endpoints every `interval`.
+    pub fn spawn_health_checks(self: &Arc<Self>, interval: Duration) -> HealthChecks {
+        let client = self.clone();
+        HealthChecks(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                client.check_health().await;
+            }
+        }))
+    }
+
+    /// Fails if `endpoint` is more than [`MAX_ENDPOINT_LAG`] levels behind the highest level
+    /// seen.
+    async fn check_level(&self, endpoint: &Endpoint) -> Result<()> {
+        let level = endpoint.rpc.get_level().await?;
+        let highest = self.level.fetch_max(level, Ordering::Relaxed).max(level);
+        if level + MAX_ENDPOINT_LAG < highest {
+            bail!(
+                "rollup endpoint {} is at level {level}, behind level {highest}",
+                endpoint.url
+            );
+        }
+        Ok(())
+    }
+
+    fn activate(&self, index: usize) {
+        let previous = self.active.send_replace(index);
+        if previous != index {
+            info!(
+                "switched rollup endpoint from {} to {}",
+                self.endpoints[previous].url, self.endpoints[index].url
+            );
+        }
+    }
+
+    fn record(&self, index: usize, healthy: bool) {
+        set_healthy(&self.endpoints[index], healthy);
+        if healthy {
+            self.activate(index);
+        } else if self.active() == index {
+            self.activate((index + 1) % self.endpoints.len());
+        }
+    }
+
+    /// Sends a request that is safe to repeat, starting with the active endpoint and
+    /// retrying on the next ones until one succeeds. Errors that are not failures of
+    /// the endpoint are returned without retrying.
+    async fn with_failover<'a, T, F, Fut>(&'a self, request: F) -> Result<T>
+    where
+        F: Fn(&'a dyn RollupRpc) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let start = self.active();
+        let mut error = None;
+        for offset in 0..self.endpoints.len() {
+            let index = (start + offset) % self.endpoints.len();
+            let endpoint = &self.endpoints[index];
+            if offset > 0 {
+                if let Err(e) = self.check_level(endpoint).await {
+                    self.record(index, false);
+                    error = Some(e);
+                    continue;
+                }
+            }
+            match request(endpoint.rpc.as_ref()).await {
+                Err(e) if is_endpoint_failure(&e) => {
+                    self.record(index, false);
+                    error = Some(e);
+                }
+                result => {
+                    self.record(index, true);
+                    return result;
+                }
+            }
+        }
+        // safety: there is at least one endpoint, so at least one request failed
+        Err(error.unwrap())
+    }
+}
+
+/// Whether `e` is a failure of the endpoint, i.e. a transport error or a 5xx
+/// response, rather than an error caused by the request.
+fn is_endpoint_failure(e: &anyhow::Error) -> bool {
+    e.chain()
+        .any(|cause| match cause.downcast_ref::<UnexpectedStatus>() {
+            Some(UnexpectedStatus(status)) => status.is_server_error(),
+            None => cause.is::<reqwest::Error>(),
+        })
+}
+
+fn set_healthy(endpoint: &Endpoint, healthy: bool) {
+    let was_healthy = endpoint.healthy.swap(healthy, Ordering::Relaxed);
+    if was_healthy && !healthy {
+        warn!("rollup endpoint {} is unhealthy", endpoint.url);
+    } else if !was_healthy && healthy {
+        info!("rollup endpoint {} recovered", endpoint.url);
+    }
+}
+
+#[async_trait]
+impl RollupRpc for FailoverRollupClient {
+    async fn batcher_injection(&self, external_messages: Vec<Vec<u8>>) -> Result<()> {
+        let index = self.active();
+        let result = self.endpoints[index]
+            .rpc
+            .batcher_injection(external_messages)
+            .await;
+        self.record(index, !result.as_ref().is_err_and(is_endpoint_failure));
+        result
+    }
+
+    async fn get_value(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.with_failover(|rpc| rpc.get_value(key)).await
+    }
+
+    async fn get_subkeys(&self, key: &str) -> Result<Option<Vec<String>>> {
+        self.with_failover(|rpc| rpc.get_subkeys(key)).await
+    }
+
+    async fn get_value_proof(&self, key: &str) -> Result<Option<DurableValueProof>> {
+        self.with_failover(|rpc| rpc.get_value_proof(key)).await
+    }
+
+    async fn get_level(&self) -> Result<u32> {
+        self.with_failover(|rpc| rpc.get_level()).await
+    }
+
+    async fn get_rollup_address(&self) -> Result<SmartRollupAddress> {
+        self.with_failover(|rpc| rpc.get_rollup_address()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use reqwest::StatusCode;
+    use tezos_smart_rollup_encoding::smart_rollup::SmartRollupAddress;
+
+    use super::{EndpointStatus, FailoverRollupClient};
+    use crate::{
+        mock::MockRollupRpc,
+        rollup::{DurableValueProof, UnexpectedStatus},
+        RollupRpc,
+    };
+
+    /// Mock rollup node that responds to every request with an error status while
+    /// one is set.
+    struct FlakyRpc {
+        inner: MockRollupRpc,
+        status: Mutex<Option<StatusCode>>,
+    }
+
+    impl FlakyRpc {
+        fn new(value: &[u8]) -> Arc<Self> {
+            let inner = MockRollupRpc::new(
+                SmartRollupAddress::from_b58check("sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK")
+                    .unwrap(),
+            );
+            inner.insert("/key", value.to_vec());
+            Arc::new(Self {
+                inner,
+                status: Mutex::default(),
+            })
+        }
+
+        fn set_status(&self, status: Option<StatusCode>) {
+            *self.status.lock().unwrap() = status;
+        }
+
+        fn set_down(&self, down: bool) {
+            self.set_status(down.then_some(StatusCode::SERVICE_UNAVAILABLE));
+        }
+
+        fn check(&self) -> Result<()> {
+            match *self.status.lock().unwrap() {
+                Some(status) => Err(UnexpectedStatus(status).into()),
+                None => Ok(()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl RollupRpc for FlakyRpc {
+        async fn batcher_injection(&self, messages: Vec<Vec<u8>>) -> Result<()> {
+            self.check()?;
+            self.inner.batcher_injection(messages).await
+        }
+
+        async fn get_value(&self, key: &str) -> Result<Option<Vec<u8>>> {
+            self.check()?;
+            self.inner.get_value(key).await
+        }
+
+        async fn get_subkeys(&self, key: &str) -> Result<Option<Vec<String>>> {
+            self.check()?;
+            self.inner.get_subkeys(key).await
+        }
+
+        async fn get_value_proof(&self, key: &str) -> Result<Option<DurableValueProof>> {
+            self.check()?;
+            self.inner.get_value_proof(key).await
+        }
+
+        async fn get_level(&self) -> Result<u32> {
+            self.check()?;
+            self.inner.get_level().await
+        }
+
+        async fn get_rollup_address(&self) -> Result<SmartRollupAddress> {
+            self.check()?;
+            self.inner.get_rollup_address().await
+        }
+    }
+
+    fn client(rpcs: &[&Arc<FlakyRpc>]) -> FailoverRollupClient {
+        FailoverRollupClient::from_rpcs(
+            rpcs.iter()
+                .enumerate()
+                .map(|(i, &rpc)| {
+                    let rpc: Arc<dyn RollupRpc> = rpc.clone();
+                    (i.to_string(), rpc)
+                })
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    fn status(client: &FailoverRollupClient) -> Vec<(bool, bool)> {
+        client
+            .status()
+            .into_iter()
+            .map(
+                |EndpointStatus {
+                     healthy, active, ..
+                 }| (healthy, active),
+            )
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn fails_over_reads() {
+        let (first, second) = (FlakyRpc::new(b"first"), FlakyRpc::new(b"second"));
+        let client = client(&[&first, &second]);
+        let mut active = client.subscribe();
+        assert_eq!(status(&client), [(true, true), (true, false)]);
+        assert_eq!(
+            client.get_value("/key").await.unwrap(),
+            Some(b"first".to_vec())
+        );
+
+        // reads are retried on the next endpoint, which becomes active
+        first.set_down(true);
+        assert_eq!(
+            client.get_value("/key").await.unwrap(),
+            Some(b"second".to_vec())
+        );
+        assert_eq!(status(&client), [(false, false), (true, true)]);
+        assert!(active.has_changed().unwrap());
+        assert_eq!(*active.borrow_and_update(), 1);
+        assert_eq!(client.active_endpoint(), "1");
+        assert!(client.is_healthy());
+
+        // the request fails if every endpoint is down
+        second.set_down(true);
+        assert!(client.get_value("/key").await.is_err());
+        assert_eq!(status(&client), [(false, false), (false, true)]);
+        assert!(!client.is_healthy());
+
+        // health checks switch back to the preferred endpoint once it recovers
+        first.set_down(false);
+        client.check_health().await;
+        assert_eq!(status(&client), [(true, true), (false, false)]);
+        assert_eq!(*active.borrow_and_update(), 0);
+    }
+
+    #[tokio::test]
+    async fn does_not_fail_over_request_errors() {
+        let (first, second) = (FlakyRpc::new(b"first"), FlakyRpc::new(b"second"));
+        let client = client(&[&first, &second]);
+
+        // e.g. a key rejected by the rollup node
+        first.set_status(Some(StatusCode::BAD_REQUEST));
+        let e = client.get_value("/key").await.unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<UnexpectedStatus>(),
+            Some(&UnexpectedStatus(StatusCode::BAD_REQUEST))
+        );
+        assert!(client.batcher_injection(vec![vec![1]]).await.is_err());
+        assert_eq!(status(&client), [(true, true), (true, false)]);
+        assert!(second.inner.injected_messages().is_empty());
+    }
+
+    #[tokio::test]
+    async fn does_not_fail_over_to_lagging_endpoints() {
+        let (first, second) = (FlakyRpc::new(b"first"), FlakyRpc::new(b"second"));
+        first.inner.set_level(10);
+        second.inner.set_level(5);
+        let client = client(&[&first, &second]);
+        client.check_health().await;
+        assert_eq!(status(&client), [(true, true), (false, false)]);
+
+        first.set_down(true);
+        assert!(client.get_value("/key").await.is_err());
+        assert_eq!(status(&client), [(false, true), (false, false)]);
+
+        // the endpoint is read from once it catches up
+        second.inner.set_level(9);
+        assert_eq!(
+            client.get_value("/key").await.unwrap(),
+            Some(b"second".to_vec())
+        );
+        assert_eq!(status(&client), [(false, false), (true, true)]);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_injections() {
+        let (first, second) = (FlakyRpc::new(b""), FlakyRpc::new(b""));
+        let client = client(&[&first, &second]);
+
+        first.set_down(true);
+        assert!(client.batcher_injection(vec![vec![1]]).await.is_err());
+        assert!(second.inner.injected_messages().is_empty());
+
+        // the next injections are sent to the next endpoint
+        client.batcher_injection(vec![vec![2]]).await.unwrap();
+        assert_eq!(second.inner.injected_messages(), vec![vec![2]]);
+        assert_eq!(status(&client), [(false, false), (true, true)]);
+    }
+
+    #[test]
+    fn requires_an_endpoint() {
+        assert!(FailoverRollupClient::new(&[]).is_err());
+    }
+}
@@ -4,13 +4,17 @@ use anyhow::{anyhow, Result};
 
 pub mod r#async;
 mod client;
+mod failover;
 mod node;
 mod rollup;
+mod rpc;
 mod thread;
 
 pub use client::*;
+pub use failover::*;
 pub use node::*;
 pub use rollup::*;
+pub use rpc::*;
 pub use thread::*;
 
 pub(crate) fn path_or_default<'a>(
@@ -1,11 +1,13 @@
 use std::{
+    fmt,
     fs::File,
     path::PathBuf,
     process::{Child, Command, Stdio},
 };
 
 use anyhow::{anyhow, Result};
-use serde::{Deserialize, Serialize};
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tezos_smart_rollup_encoding::smart_rollup::SmartRollupAddress;
 
 use crate::path_or_default;
@@ -74,6 +76,18 @@ impl OctezRollupNode {
     }
 }
 
+/// Error of a request to which the rollup node responded with an unexpected status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnexpectedStatus(pub StatusCode);
+
+impl fmt::Display for UnexpectedStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unhandled response status: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnexpectedStatus {}
+
 #[derive(Debug, Clone)]
 pub struct OctezRollupClient {
     endpoint: String,
@@ -98,6 +112,30 @@ enum ValueResponse {
 #[derive(Debug, Deserialize)]
 struct SubkeysResponse(Vec<String>);
 
+#[derive(Debug, Deserialize)]
+struct StoredCommitment {
+    commitment: Commitment,
+}
+
+#[derive(Debug, Deserialize)]
+struct Commitment {
+    compressed_state: String,
+    inbox_level: u32,
+}
+
+/// A value of the durable storage with its Merkle proof, read from the block of the
+/// last commitment of the rollup node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DurableValueProof {
+    pub block_hash: String,
+    pub level: u32,
+    /// Hash of the PVM state of the block, as committed on L1
+    pub state_hash: String,
+    pub value: Vec<u8>,
+    /// Merkle proof of the value against `state_hash`
+    pub proof: Vec<u8>,
+}
+
 impl OctezRollupClient {
     pub fn new(endpoint: String) -> Self {
         Self {
@@ -127,16 +165,70 @@ impl OctezRollupClient {
             // TODO: Should we ignore the response?
             Ok(())
         } else {
-            Err(anyhow!("Unhandled response status: {}", res.status()))
+            Err(UnexpectedStatus(res.status()).into())
         }
     }
 
     pub async fn get_value(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.get_block_value("head", key).await
+    }
+
+    /// Reads the value of `key` with its Merkle proof at the block of the last
+    /// commitment stored by the rollup node, whose state hash is the one published on
+    /// L1. The head block is not committed yet, so its values cannot be verified.
+    pub async fn get_value_proof(&self, key: &str) -> Result<Option<DurableValueProof>> {
+        let commitment: Option<StoredCommitment> =
+            self.get_json("/global/last_stored_commitment").await?;
+        let Some(StoredCommitment { commitment }) = commitment else {
+            return Err(anyhow!("the rollup node has not stored any commitment yet"));
+        };
+        let level = commitment.inbox_level;
+        let block_hash: String = self
+            .get_json(&format!("/global/block/{level}/hash"))
+            .await?;
+        let Some(value) = self.get_block_value(&block_hash, key).await? else {
+            return Ok(None);
+        };
+        let proof: Option<String> = self
+            .get_json(&format!(
+                "/global/block/{block_hash}/durable/wasm_2_0_0/proof?key={}",
+                encode_key(key)
+            ))
+            .await?;
+        let Some(proof) = proof else {
+            return Ok(None);
+        };
+        Ok(Some(DurableValueProof {
+            block_hash,
+            level,
+            state_hash: commitment.compressed_state,
+            value,
+            proof: hex::decode(proof)?,
+        }))
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let res = self
+            .client
+            .get(format!("{}{}", self.endpoint, path))
+            .send()
+            .await?;
+
+        if res.status() == 200 {
+            Ok(res.json().await?)
+        } else {
+            Err(UnexpectedStatus(res.status()).into())
+        }
+    }
+
+    async fn get_block_value(&self, block: &str, key: &str) -> Result<Option<Vec<u8>>> {
         let res = self
             .client
             .get(format!(
-                "{}/global/block/head/durable/wasm_2_0_0/value?key={}",
-                self.endpoint, key
+                "{}/global/block/{}/durable/wasm_2_0_0/value?key={}",
+                self.endpoint,
+                block,
+                encode_key(key)
             ))
             .send()
             .await?;
@@ -156,7 +248,7 @@ impl OctezRollupClient {
                 None => Ok(None),
             }
         } else {
-            Err(anyhow!("Unhandled response status: {}", res.status()))
+            Err(UnexpectedStatus(res.status()).into())
         }
     }
 
@@ -165,7 +257,8 @@ impl OctezRollupClient {
             .client
             .get(format!(
                 "{}/global/block/head/durable/wasm_2_0_0/subkeys?key={}",
-                self.endpoint, key
+                self.endpoint,
+                encode_key(key)
             ))
             .send()
             .await?;
@@ -183,10 +276,15 @@ impl OctezRollupClient {
                 )),
             }
         } else {
-            Err(anyhow!("Unhandled response status: {}", res.status()))
+            Err(UnexpectedStatus(res.status()).into())
         }
     }
 
+    /// Level of the head block of the rollup node
+    pub async fn get_level(&self) -> Result<u32> {
+        self.get_json("/global/block/head/level").await
+    }
+
     pub async fn get_rollup_address(&self) -> Result<SmartRollupAddress> {
         let res = self
             .client
@@ -206,7 +304,27 @@ impl OctezRollupClient {
                 })?;
             Ok(address)
         } else {
-            Err(anyhow!("Unhandled response status: {}", res.status()))
+            Err(UnexpectedStatus(res.status()).into())
+        }
+    }
+}
+
+/// Percent-encodes `key` for the `key` query parameter of the durable storage RPCs.
+/// Separators are kept as is, since durable storage keys are paths.
+fn encode_key(key: &str) -> String {
+    let mut encoded = String::with_capacity(key.len());
+    for byte in key.bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'/'
+            | b'-'
+            | b'_'
+            | b'.'
+            | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
         }
     }
+    encoded
 }
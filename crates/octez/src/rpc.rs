@@ -0,0 +1,536 @@
+//! Abstractions over the RPC interfaces of octez services.
+//!
+//! Consumers should depend on [`RollupRpc`] and [`L1Rpc`] rather than on the
+//! concrete HTTP clients so that the in-memory implementations in [`mock`]
+//! can be swapped in for unit tests.
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde_json::Value;
+use tezos_smart_rollup_encoding::smart_rollup::SmartRollupAddress;
+use tracing::instrument;
+
+use crate::{rollup::DurableValueProof, OctezRollupClient};
+
+/// RPC interface of a smart rollup node.
+#[async_trait]
+pub trait RollupRpc: Send + Sync {
+    /// Injects external messages into the batcher of the rollup node.
+    async fn batcher_injection(&self, external_messages: Vec<Vec<u8>>) -> Result<()>;
+
+    /// Reads the value stored at `key` in the durable storage of the head block.
+    async fn get_value(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Lists the subkeys of `key` in the durable storage of the head block.
+    async fn get_subkeys(&self, key: &str) -> Result<Option<Vec<String>>>;
+
+    /// Reads the value stored at `key` in the durable storage of the block of the last
+    /// commitment with its Merkle proof. Not supported by default.
+    async fn get_value_proof(&self, _key: &str) -> Result<Option<DurableValueProof>> {
+        Err(anyhow!(
+            "value proofs are not supported by this rollup node"
+        ))
+    }
+
+    /// Level of the head block of the node.
+    async fn get_level(&self) -> Result<u32>;
+
+    /// Address of the rollup tracked by the node.
+    async fn get_rollup_address(&self) -> Result<SmartRollupAddress>;
+}
+
+#[async_trait]
+impl RollupRpc for OctezRollupClient {
+    #[instrument(skip_all, fields(count = external_messages.len()))]
+    async fn batcher_injection(&self, external_messages: Vec<Vec<u8>>) -> Result<()> {
+        OctezRollupClient::batcher_injection(self, external_messages).await
+    }
+
+    #[instrument(skip(self))]
+    async fn get_value(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        OctezRollupClient::get_value(self, key).await
+    }
+
+    #[instrument(skip(self))]
+    async fn get_subkeys(&self, key: &str) -> Result<Option<Vec<String>>> {
+        OctezRollupClient::get_subkeys(self, key).await
+    }
+
+    #[instrument(skip(self))]
+    async fn get_value_proof(&self, key: &str) -> Result<Option<DurableValueProof>> {
+        OctezRollupClient::get_value_proof(self, key).await
+    }
+
+    #[instrument(skip(self))]
+    async fn get_level(&self) -> Result<u32> {
+        OctezRollupClient::get_level(self).await
+    }
+
+    #[instrument(skip(self))]
+    async fn get_rollup_address(&self) -> Result<SmartRollupAddress> {
+        OctezRollupClient::get_rollup_address(self).await
+    }
+}
+
+/// RPC interface of a layer 1 octez node.
+#[async_trait]
+pub trait L1Rpc: Send + Sync {
+    /// Level of the current head block.
+    async fn get_block_level(&self) -> Result<i64>;
+
+    /// Whether or not the node is ready to answer to requests.
+    async fn is_ready(&self) -> Result<bool>;
+}
+
+/// HTTP client of the octez node RPC interface.
+#[derive(Debug, Clone)]
+pub struct OctezNodeRpcClient {
+    endpoint: String,
+    client: reqwest::Client,
+}
+
+impl OctezNodeRpcClient {
+    pub fn new(endpoint: String) -> Self {
+        Self {
+            endpoint,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl L1Rpc for OctezNodeRpcClient {
+    async fn get_block_level(&self) -> Result<i64> {
+        let response: Value = self
+            .client
+            .get(format!("{}/chains/main/blocks/head", self.endpoint))
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        let level = response
+            .get("header")
+            .and_then(|header| header.get("level"))
+            .ok_or_else(|| anyhow!("Failed to extract level from head block"))?;
+        level
+            .as_i64()
+            .ok_or_else(|| anyhow!("Level is not a valid i64"))
+    }
+
+    async fn is_ready(&self) -> Result<bool> {
+        // https://gitlab.com/tezos/tezos/-/raw/2e84c439c25c4d9b363127a6685868e223877034/docs/api/rpc-openapi.json
+        let body = self
+            .client
+            .get(format!("{}/health/ready", self.endpoint))
+            .send()
+            .await?
+            .json::<std::collections::HashMap<String, bool>>()
+            .await?;
+        body.get("ready").copied().ok_or(anyhow!(
+            "`ready` cannot be retrieved from octez-node health check endpoint"
+        ))
+    }
+}
+
+// WARNING: Should only be used in tests!
+#[cfg(any(test, feature = "test_utils"))]
+pub mod mock {
+    use std::{
+        collections::BTreeMap,
+        sync::{
+            atomic::{AtomicBool, AtomicI64, AtomicU32, Ordering},
+            Mutex,
+        },
+    };
+
+    use jstz_crypto::hash::Blake2b;
+
+    use super::*;
+
+    /// In-memory rollup node. Durable storage is a flat map from keys to values
+    /// and injected messages are recorded so that tests can inspect them. The head
+    /// level is set by tests.
+    #[derive(Debug)]
+    pub struct MockRollupRpc {
+        address: SmartRollupAddress,
+        storage: Mutex<BTreeMap<String, Vec<u8>>>,
+        injected: Mutex<Vec<Vec<u8>>>,
+        level: AtomicU32,
+    }
+
+    impl MockRollupRpc {
+        pub fn new(address: SmartRollupAddress) -> Self {
+            Self {
+                address,
+                storage: Mutex::default(),
+                injected: Mutex::default(),
+                level: AtomicU32::default(),
+            }
+        }
+
+        pub fn set_level(&self, level: u32) {
+            self.level.store(level, Ordering::SeqCst);
+        }
+
+        pub fn insert(&self, key: &str, value: Vec<u8>) {
+            self.storage.lock().unwrap().insert(key.to_owned(), value);
+        }
+
+        /// Messages injected so far, in injection order.
+        pub fn injected_messages(&self) -> Vec<Vec<u8>> {
+            self.injected.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl RollupRpc for MockRollupRpc {
+        async fn batcher_injection(&self, external_messages: Vec<Vec<u8>>) -> Result<()> {
+            self.injected.lock().unwrap().extend(external_messages);
+            Ok(())
+        }
+
+        async fn get_value(&self, key: &str) -> Result<Option<Vec<u8>>> {
+            Ok(self.storage.lock().unwrap().get(key).cloned())
+        }
+
+        async fn get_subkeys(&self, key: &str) -> Result<Option<Vec<String>>> {
+            let prefix = format!("{}/", key.trim_end_matches('/'));
+            let storage = self.storage.lock().unwrap();
+            let mut subkeys = storage
+                .keys()
+                .filter_map(|k| k.strip_prefix(&prefix))
+                .filter_map(|k| k.split('/').next())
+                .map(str::to_owned)
+                .collect::<Vec<_>>();
+            subkeys.dedup();
+            Ok(match subkeys.is_empty() && !storage.contains_key(key) {
+                true => None,
+                false => Some(subkeys),
+            })
+        }
+
+        /// Proofs of the mock are paths of a binary Merkle tree over the entries of
+        /// its storage, in key order, see [`verify_proof`]
+        async fn get_value_proof(&self, key: &str) -> Result<Option<DurableValueProof>> {
+            let storage = self.storage.lock().unwrap();
+            let Some(index) = storage.keys().position(|k| k == key) else {
+                return Ok(None);
+            };
+            let leaves = storage.iter().map(|(k, v)| leaf_hash(k, v)).collect();
+            let (root, proof) = merkle_proof(leaves, index);
+            Ok(Some(DurableValueProof {
+                block_hash: String::new(),
+                level: 0,
+                state_hash: hex::encode(root),
+                value: storage[key].clone(),
+                proof,
+            }))
+        }
+
+        async fn get_level(&self) -> Result<u32> {
+            Ok(self.level.load(Ordering::SeqCst))
+        }
+
+        async fn get_rollup_address(&self) -> Result<SmartRollupAddress> {
+            Ok(self.address.clone())
+        }
+    }
+
+    fn hash(preimage: &[u8]) -> [u8; 32] {
+        *Blake2b::from(preimage).as_array()
+    }
+
+    fn leaf_hash(key: &str, value: &[u8]) -> [u8; 32] {
+        let mut preimage = vec![0];
+        preimage.extend((key.len() as u32).to_be_bytes());
+        preimage.extend(key.as_bytes());
+        preimage.extend(value);
+        hash(&preimage)
+    }
+
+    fn node_hash(left: &[u8], right: &[u8]) -> [u8; 32] {
+        hash(&[&[1][..], left, right].concat())
+    }
+
+    /// Returns the root of the Merkle tree of `hashes` and the proof of the leaf at
+    /// `index`, made of a side byte, 1 if the sibling is on the left, and the hash of
+    /// the sibling for each level. A last node without sibling moves up unchanged.
+    fn merkle_proof(mut hashes: Vec<[u8; 32]>, mut index: usize) -> ([u8; 32], Vec<u8>) {
+        let mut proof = Vec::new();
+        while hashes.len() > 1 {
+            if let Some(sibling) = hashes.get(index ^ 1) {
+                proof.push((index & 1) as u8);
+                proof.extend(sibling);
+            }
+            hashes = hashes
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [node] => *node,
+                    _ => unreachable!(),
+                })
+                .collect();
+            index /= 2;
+        }
+        (hashes[0], proof)
+    }
+
+    /// Verifies a proof of [`MockRollupRpc`] that `value` is stored under `key` in
+    /// the state with hex-encoded hash `state_hash`
+    pub fn verify_proof(state_hash: &str, key: &str, value: &[u8], proof: &[u8]) -> bool {
+        let mut node = leaf_hash(key, value);
+        for step in proof.chunks(33) {
+            let (&side, sibling) = match step.split_first() {
+                Some(step) if step.1.len() == 32 => step,
+                _ => return false,
+            };
+            node = match side {
+                0 => node_hash(&node, sibling),
+                1 => node_hash(sibling, &node),
+                _ => return false,
+            };
+        }
+        hex::encode(node) == state_hash
+    }
+
+    /// In-memory layer 1 node whose head level and readiness are set by tests.
+    /// Tests can also make its calls fail with [`MockL1Rpc::set_error`].
+    #[derive(Debug, Default)]
+    pub struct MockL1Rpc {
+        level: AtomicI64,
+        ready: AtomicBool,
+        error: Mutex<Option<String>>,
+    }
+
+    impl MockL1Rpc {
+        pub fn set_block_level(&self, level: i64) {
+            self.level.store(level, Ordering::SeqCst);
+        }
+
+        pub fn set_ready(&self, ready: bool) {
+            self.ready.store(ready, Ordering::SeqCst);
+        }
+
+        /// Makes the calls fail with `error`, or succeed again if `None`
+        pub fn set_error(&self, error: Option<&str>) {
+            *self.error.lock().unwrap() = error.map(str::to_owned);
+        }
+
+        fn check_error(&self) -> Result<()> {
+            match self.error.lock().unwrap().as_ref() {
+                Some(error) => Err(anyhow!("{error}")),
+                None => Ok(()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl L1Rpc for MockL1Rpc {
+        async fn get_block_level(&self) -> Result<i64> {
+            self.check_error()?;
+            Ok(self.level.load(Ordering::SeqCst))
+        }
+
+        async fn is_ready(&self) -> Result<bool> {
+            self.check_error()?;
+            Ok(self.ready.load(Ordering::SeqCst))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mock::*, *};
+    use crate::UnexpectedStatus;
+
+    fn rollup_address() -> SmartRollupAddress {
+        SmartRollupAddress::from_b58check("sr1PuFMgaRUN12rKQ3J2ae5psNtwCxPNmGNK").unwrap()
+    }
+
+    #[tokio::test]
+    async fn mock_rollup_rpc() {
+        let rpc = MockRollupRpc::new(rollup_address());
+        rpc.insert("/jstz_kv/foo/a", b"1".to_vec());
+        rpc.insert("/jstz_kv/foo/b/c", b"2".to_vec());
+        rpc.insert("/jstz_kv/foo/b/d", b"3".to_vec());
+
+        assert_eq!(
+            rpc.get_value("/jstz_kv/foo/a").await.unwrap(),
+            Some(b"1".to_vec())
+        );
+        assert!(rpc.get_value("/jstz_kv/bar").await.unwrap().is_none());
+        assert_eq!(
+            rpc.get_subkeys("/jstz_kv/foo").await.unwrap(),
+            Some(vec!["a".to_owned(), "b".to_owned()])
+        );
+        assert!(rpc.get_subkeys("/jstz_kv/bar").await.unwrap().is_none());
+        assert_eq!(rpc.get_rollup_address().await.unwrap(), rollup_address());
+        assert_eq!(rpc.get_level().await.unwrap(), 0);
+        rpc.set_level(7);
+        assert_eq!(rpc.get_level().await.unwrap(), 7);
+
+        rpc.batcher_injection(vec![vec![1, 2], vec![3]])
+            .await
+            .unwrap();
+        assert_eq!(rpc.injected_messages(), vec![vec![1, 2], vec![3]]);
+    }
+
+    #[tokio::test]
+    async fn mock_rollup_rpc_value_proof() {
+        let rpc = MockRollupRpc::new(rollup_address());
+        let keys = ["/jstz_kv/foo/a", "/jstz_kv/foo/b", "/jstz_receipt/c"];
+        for (i, key) in keys.iter().enumerate() {
+            rpc.insert(key, vec![i as u8]);
+        }
+
+        let mut state_hashes = Vec::new();
+        for key in keys {
+            let proof = rpc.get_value_proof(key).await.unwrap().unwrap();
+            assert!(verify_proof(
+                &proof.state_hash,
+                key,
+                &proof.value,
+                &proof.proof
+            ));
+            // the proof does not hold for another value or key
+            assert!(!verify_proof(&proof.state_hash, key, &[9], &proof.proof));
+            assert!(!verify_proof(
+                &proof.state_hash,
+                "/jstz_kv/foo/z",
+                &proof.value,
+                &proof.proof
+            ));
+            state_hashes.push(proof.state_hash);
+        }
+        state_hashes.dedup();
+        assert_eq!(state_hashes.len(), 1);
+        assert!(rpc.get_value_proof("/jstz_kv/bar").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn mock_l1_rpc() {
+        let rpc = MockL1Rpc::default();
+        assert_eq!(rpc.get_block_level().await.unwrap(), 0);
+        assert!(!rpc.is_ready().await.unwrap());
+
+        rpc.set_block_level(42);
+        rpc.set_ready(true);
+        assert_eq!(rpc.get_block_level().await.unwrap(), 42);
+        assert!(rpc.is_ready().await.unwrap());
+
+        rpc.set_error(Some("boom"));
+        assert!(rpc.get_block_level().await.is_err());
+        assert!(rpc.is_ready().await.is_err());
+        rpc.set_error(None);
+        assert!(rpc.is_ready().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn octez_node_rpc_client() {
+        let mut server = mockito::Server::new_async().await;
+        let head = server
+            .mock("GET", "/chains/main/blocks/head")
+            .with_body(r#"{"header":{"level":12}}"#)
+            .create();
+        let ready = server
+            .mock("GET", "/health/ready")
+            .with_body(r#"{"ready":true}"#)
+            .create();
+
+        let client = OctezNodeRpcClient::new(server.url());
+        assert_eq!(client.get_block_level().await.unwrap(), 12);
+        assert!(client.is_ready().await.unwrap());
+
+        head.assert();
+        ready.assert();
+    }
+
+    #[tokio::test]
+    async fn rollup_client_level() {
+        let mut server = mockito::Server::new_async().await;
+        let level = server
+            .mock("GET", "/global/block/head/level")
+            .with_body("42")
+            .create();
+        let client = OctezRollupClient::new(server.url());
+        assert_eq!(client.get_level().await.unwrap(), 42);
+        level.assert();
+
+        // unexpected statuses are returned as is, so that callers can tell them apart
+        level.remove();
+        server
+            .mock("GET", "/global/block/head/level")
+            .with_status(503)
+            .create();
+        let e = client.get_level().await.unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<UnexpectedStatus>(),
+            Some(&UnexpectedStatus(reqwest::StatusCode::SERVICE_UNAVAILABLE))
+        );
+    }
+
+    #[tokio::test]
+    async fn rollup_client_value_proof() {
+        let mut server = mockito::Server::new_async().await;
+        let block = "/global/block/BMTest";
+        let key =
+            || mockito::Matcher::UrlEncoded("key".into(), "/jstz_kv/foo/a b&c".into());
+        let mocks = [
+            server
+                .mock("GET", "/global/last_stored_commitment")
+                .with_body(
+                    r#"{"commitment":{"compressed_state":"srs1Test","inbox_level":42,"predecessor":"src1Test","number_of_ticks":"100"},"hash":"src1Test"}"#,
+                )
+                .create(),
+            server
+                .mock("GET", "/global/block/42/hash")
+                .with_body(r#""BMTest""#)
+                .create(),
+            server
+                .mock("GET", format!("{block}/durable/wasm_2_0_0/value").as_str())
+                .match_query(key())
+                .with_body(r#""0102""#)
+                .create(),
+            server
+                .mock("GET", format!("{block}/durable/wasm_2_0_0/proof").as_str())
+                .match_query(key())
+                .with_body(r#""abcd""#)
+                .create(),
+        ];
+
+        let client = OctezRollupClient::new(server.url());
+        assert_eq!(
+            client.get_value_proof("/jstz_kv/foo/a b&c").await.unwrap(),
+            Some(DurableValueProof {
+                block_hash: "BMTest".to_owned(),
+                level: 42,
+                state_hash: "srs1Test".to_owned(),
+                value: vec![1, 2],
+                proof: vec![0xab, 0xcd],
+            })
+        );
+        for mock in mocks {
+            mock.assert();
+        }
+    }
+
+    #[tokio::test]
+    async fn rollup_client_value_proof_without_commitment() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/global/last_stored_commitment")
+            .with_body("null")
+            .create();
+
+        let client = OctezRollupClient::new(server.url());
+        assert_eq!(
+            client
+                .get_value_proof("/jstz_kv/foo")
+                .await
+                .unwrap_err()
+                .to_string(),
+            "the rollup node has not stored any commitment yet"
+        );
+    }
+}
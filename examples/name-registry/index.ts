@@ -0,0 +1,93 @@
+// Names are stored under `names/<name>` with the address they resolve to, and
+// addresses under `addresses/<address>` with their name, so that both lookups are
+// a single read. jstz nodes configured with the address of the registry serve these
+// entries under `/names/{name}` and `/addresses/{address}/name`.
+const NAME_PATTERN = /^[a-z0-9][a-z0-9_-]{0,62}$/;
+
+type NameRecord = { name: string; address: Address };
+
+const nameKey = (name: string) => `names/${name}`;
+const addressKey = (address: Address) => `addresses/${address}`;
+
+const json = (body: unknown, status = 200): Response =>
+  new Response(JSON.stringify(body), {
+    status,
+    headers: { "Content-Type": "application/json" },
+  });
+
+const error = (message: string, status: number): Response =>
+  json({ error: message }, status);
+
+// Registers `name` for `owner`, releasing the name `owner` previously registered
+const register = (name: string, owner: Address): Response => {
+  const address = Kv.get<Address>(nameKey(name));
+  if (address === owner) {
+    return json({ name, address });
+  }
+  if (address !== null) {
+    return error(`Name '${name}' is already registered`, 409);
+  }
+  const previous = Kv.get<string>(addressKey(owner));
+  if (previous !== null) {
+    Kv.delete(nameKey(previous));
+  }
+  Kv.set(nameKey(name), owner);
+  Kv.set(addressKey(owner), name);
+  return json({ name, address: owner } satisfies NameRecord, 201);
+};
+
+// Releases `name`, which only its owner can do
+const release = (name: string, caller: Address): Response => {
+  const address = Kv.get<Address>(nameKey(name));
+  if (address === null) {
+    return error(`Name '${name}' is not registered`, 404);
+  }
+  if (address !== caller) {
+    return error(`Name '${name}' is registered by another account`, 403);
+  }
+  Kv.delete(nameKey(name));
+  Kv.delete(addressKey(address));
+  return new Response(null, { status: 204 });
+};
+
+const handler = async (request: Request): Promise<Response> => {
+  const caller = request.headers.get("Referer") as Address;
+  const url = new URL(request.url);
+  const segments = url.pathname.split("/").filter((segment) => segment !== "");
+
+  if (segments.length === 2 && segments[0] === "names") {
+    const name = decodeURIComponent(segments[1]);
+    if (!NAME_PATTERN.test(name)) {
+      return error(`Invalid name '${name}'`, 400);
+    }
+    switch (request.method) {
+      case "GET": {
+        const address = Kv.get<Address>(nameKey(name));
+        return address === null
+          ? error(`Name '${name}' is not registered`, 404)
+          : json({ name, address } satisfies NameRecord);
+      }
+      case "POST":
+        return register(name, caller);
+      case "DELETE":
+        return release(name, caller);
+    }
+  }
+
+  if (
+    segments.length === 3 &&
+    segments[0] === "addresses" &&
+    segments[2] === "name" &&
+    request.method === "GET"
+  ) {
+    const address = segments[1] as Address;
+    const name = Kv.get<string>(addressKey(address));
+    return name === null
+      ? error(`Address '${address}' has no registered name`, 404)
+      : json({ name, address } satisfies NameRecord);
+  }
+
+  return error("Not found", 404);
+};
+
+export default handler;